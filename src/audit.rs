@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use similar::TextDiff;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::history;
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Sets the audit log written by [`AuditEntry::record`]: `path`, else `audit.jsonl` in
+/// the data directory. Until configured, nothing is recorded.
+pub fn configure(path: Option<PathBuf>) {
+    let path = match path {
+        Some(path) => path,
+        None => match history::data_dir() {
+            Ok(dir) => dir.join(AUDIT_FILE),
+            Err(_) => return,
+        },
+    };
+    let _ = PATH.set(path);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// The service and tag of a deploy were chosen.
+    DeployStarted,
+    /// A protected environment or service was confirmed, or approvals were given.
+    Confirmed,
+    /// A deploy was run during a freeze window.
+    FreezeOverridden,
+    /// The reviewed change was accepted for apply.
+    ReviewApproved,
+    /// The deploy was called off before anything was applied.
+    Cancelled,
+    /// The change was applied to the cluster; the entry holds the diff.
+    Applied,
+    /// The deploy ended after the apply, successfully or not.
+    Succeeded,
+    Failed,
+    /// The workload was rolled back to its previous revision.
+    RolledBack,
+    /// The pods of the workload were replaced without changing its tag.
+    Restarted,
+    /// The replica count of the workload was changed, live or in its manifest.
+    Scaled,
+    GitPushed,
+    GitPushFailed,
+    Locked,
+    Unlocked,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub user: String,
+    pub host: String,
+    pub action: Action,
+    pub env: String,
+    pub service: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Unified diff of the manifests applied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(action: Action, env: &str, service: &str) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            user: history::current_user(),
+            host: current_host(),
+            action,
+            env: env.to_string(),
+            service: service.to_string(),
+            tag: None,
+            detail: None,
+            diff: None,
+        }
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn diff(mut self, diff: String) -> Self {
+        self.diff = Some(diff);
+        self
+    }
+
+    /// Appends the entry to the audit log; failing to write it only warns.
+    pub fn record(self) {
+        let Some(path) = PATH.get() else {
+            return;
+        };
+        if let Err(e) = append(path, &self) {
+            eprintln!("⚠️  Could not write the audit log: {:#}", e);
+        }
+    }
+}
+
+fn append(path: &Path, entry: &AuditEntry) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    // One write per line, so that concurrent davit runs do not interleave entries.
+    let line = format!("{}\n", serde_json::to_string(entry)?);
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn current_host() -> String {
+    Command::new("hostname")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|h| !h.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Unified diff from `old` to `new`, as recorded in [`Action::Applied`] entries.
+pub fn unified_diff(old: &str, new: &str, label: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(label, label)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_are_appended_as_json_lines() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("logs").join(AUDIT_FILE);
+        let diff = unified_diff(
+            "image: gcr.io/p/app:v1\n",
+            "image: gcr.io/p/app:v2\n",
+            "app.yaml",
+        );
+        append(
+            &path,
+            &AuditEntry::new(Action::Applied, "production", "auth-api")
+                .tag("v2")
+                .diff(diff),
+        )?;
+        append(
+            &path,
+            &AuditEntry::new(Action::GitPushed, "production", "auth-api"),
+        )?;
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["action"], "applied");
+        assert_eq!(lines[0]["tag"], "v2");
+        assert_eq!(
+            lines[0]["diff"],
+            "--- app.yaml\n+++ app.yaml\n@@ -1 +1 @@\n-image: gcr.io/p/app:v1\n+image: gcr.io/p/app:v2\n"
+        );
+        assert_eq!(lines[1]["action"], "git_pushed");
+        assert!(lines[1].get("diff").is_none());
+        Ok(())
+    }
+}
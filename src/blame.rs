@@ -0,0 +1,307 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use console::style;
+use serde_yaml::Value;
+
+use crate::blueprint::{find_pod_spec, parse_document, split_image_ref};
+use crate::config::{Environment, ServiceSource};
+use crate::git::{Git, GitLogEntry};
+use crate::history::{DeployRecord, History, Outcome};
+
+/// The commit that set a container's current tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagOrigin {
+    pub commit: GitLogEntry,
+    pub previous_tag: Option<String>,
+}
+
+/// Reports who set the tags currently in the service's YAML, when and in which commit.
+pub fn show_blame(env: &Environment, service: &ServiceSource) -> Result<()> {
+    if service.live {
+        return Err(anyhow::anyhow!(
+            "{} was discovered from the cluster, which has no YAML history to blame",
+            service.name
+        ));
+    }
+    if let Some(release) = &service.helm_release {
+        return Err(anyhow::anyhow!(
+            "{} comes from Helm release {}/{}, which has no YAML history to blame",
+            service.name,
+            release.namespace,
+            release.name
+        ));
+    }
+    if !Git::is_repo(&service.source_root) {
+        return Err(anyhow::anyhow!(
+            "{} is not inside a git repository",
+            service.source_root.display()
+        ));
+    }
+    let commits = Git::file_log(&service.source_root, &service.yaml_path)?;
+    let deploys = History::open_default()
+        .and_then(|h| h.load())
+        .unwrap_or_default();
+
+    println!(
+        "\n{} in {} ({})",
+        style(&service.name).bold(),
+        env.name,
+        service.yaml_path.display()
+    );
+    for container in &service.containers {
+        let (image, tag) = split_image_ref(&container.image_path);
+        let Some(tag) = tag else {
+            println!("\n  {}: {} has no tag", container.name, image);
+            continue;
+        };
+        println!(
+            "\n  {} {}",
+            style(&container.name).cyan(),
+            style(format!("{}:{}", image, tag)).bold()
+        );
+
+        let target = service.with_container(container);
+        let tags = commits
+            .iter()
+            .map(|commit| {
+                let content =
+                    Git::file_at_commit(&service.source_root, &commit.hash, &service.yaml_path)?;
+                Ok(content.and_then(|c| deployed_tag(&c, &target)))
+            })
+            .collect::<Result<Vec<_>>>()
+            .context("Failed to read the YAML history")?;
+
+        match tag_origin(&commits, &tags, tag) {
+            Some(origin) => {
+                println!("    Set by:   {}", origin.commit.author);
+                println!("    When:     {}", origin.commit.date);
+                println!(
+                    "    Commit:   {} {}",
+                    &origin.commit.hash[..origin.commit.hash.len().min(8)],
+                    origin.commit.message
+                );
+                println!(
+                    "    Previous: {}",
+                    origin.previous_tag.as_deref().unwrap_or("-")
+                );
+            }
+            None => println!("    No commit sets this tag (uncommitted change?)"),
+        }
+
+        if let Some(record) = last_deploy(&deploys, &env.name, &service.name, tag) {
+            println!(
+                "    Deployed: by {} on {} with davit",
+                record.user,
+                record
+                    .timestamp
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The tag that the committed tag of the service's container replaced, according to
+/// the Git history of its YAML.
+pub fn committed_previous_tag(service: &ServiceSource) -> Option<String> {
+    if !service.is_file_backed() {
+        return None;
+    }
+    let commits = Git::file_log(&service.source_root, &service.yaml_path).ok()?;
+    let mut tags = commits.iter().map(|commit| {
+        Git::file_at_commit(&service.source_root, &commit.hash, &service.yaml_path)
+            .ok()
+            .flatten()
+            .and_then(|content| deployed_tag(&content, service))
+    });
+    let current = tags.next()??;
+    tags.find(|tag| tag.as_deref() != Some(current.as_str()))?
+}
+
+/// When `tag` reached the environment: the last successful davit deploy of it, else the
+/// commit that set it in the service's YAML.
+pub fn deployed_since(
+    env: &Environment,
+    service: &ServiceSource,
+    tag: &str,
+) -> Option<DateTime<Utc>> {
+    let deploys = History::open_default()
+        .and_then(|h| h.load())
+        .unwrap_or_default();
+    if let Some(record) = last_deploy(&deploys, &env.name, &service.name, tag) {
+        return Some(record.timestamp);
+    }
+    if !service.is_file_backed() || !Git::is_repo(&service.source_root) {
+        return None;
+    }
+    let commits = Git::file_log(&service.source_root, &service.yaml_path).ok()?;
+    let tags: Vec<Option<String>> = commits
+        .iter()
+        .map(|commit| {
+            Git::file_at_commit(&service.source_root, &commit.hash, &service.yaml_path)
+                .ok()
+                .flatten()
+                .and_then(|content| deployed_tag(&content, service))
+        })
+        .collect();
+    let origin = tag_origin(&commits, &tags, tag)?;
+    DateTime::parse_from_str(&origin.commit.date, "%Y-%m-%d %H:%M:%S %z")
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Tag of the service's container in a version of its YAML: the container image in a
+/// plain manifest, the matching `images` entry of a kustomization, or the tag value of
+/// a Helm values file.
+pub fn deployed_tag(content: &str, service: &ServiceSource) -> Option<String> {
+    let (base_image, _) = split_image_ref(&service.image_path);
+    if let Some(chart) = &service.helm_chart {
+        return chart.tag_in(content);
+    }
+    if service.kustomize_overlay.is_some() {
+        let kustomization: Value = serde_yaml::from_str(content).ok()?;
+        let entry = kustomization
+            .get("images")?
+            .as_sequence()?
+            .iter()
+            .find(|entry| {
+                ["name", "newName"]
+                    .iter()
+                    .any(|field| entry.get(field).and_then(Value::as_str) == Some(base_image))
+            })?;
+        return entry
+            .get("newTag")
+            .or_else(|| entry.get("digest"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+    }
+
+    let document = parse_document(content, service.document_index)?;
+    let pod_spec = find_pod_spec(&document)?;
+    ["containers", "initContainers"]
+        .iter()
+        .filter_map(|key| pod_spec.get(key)?.as_sequence())
+        .flatten()
+        .find(|c| c.get("name").and_then(Value::as_str) == Some(service.container_name.as_str()))?
+        .get("image")
+        .and_then(Value::as_str)
+        .and_then(|image| split_image_ref(image).1)
+        .map(str::to_string)
+}
+
+/// Finds the oldest commit of the latest unbroken run of commits carrying `tag`.
+/// `tags` holds the tag at each of `commits`, both newest first.
+pub fn tag_origin(
+    commits: &[GitLogEntry],
+    tags: &[Option<String>],
+    tag: &str,
+) -> Option<TagOrigin> {
+    let run = tags
+        .iter()
+        .take_while(|t| t.as_deref() == Some(tag))
+        .count();
+    let commit = commits.get(run.checked_sub(1)?)?.clone();
+    Some(TagOrigin {
+        commit,
+        previous_tag: tags.get(run).cloned().flatten(),
+    })
+}
+
+fn last_deploy<'a>(
+    deploys: &'a [DeployRecord],
+    env: &str,
+    service: &str,
+    tag: &str,
+) -> Option<&'a DeployRecord> {
+    deploys.iter().rev().find(|r| {
+        r.env == env && r.service == service && r.new_tag == tag && r.outcome == Outcome::Succeeded
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn service(kustomize: bool) -> ServiceSource {
+        ServiceSource {
+            name: "auth-api".to_string(),
+            kind: "Deployment".to_string(),
+            image_path: "europe-docker.pkg.dev/acme/apps/auth-api:v1.2.3".to_string(),
+            container_name: "auth-api".to_string(),
+            source_name: "default".to_string(),
+            source_root: PathBuf::from("/repo"),
+            yaml_path: PathBuf::from("/repo/auth-api.yaml"),
+            document_index: 1,
+            kustomize_overlay: kustomize.then(|| PathBuf::from("/repo/overlay")),
+            namespace: None,
+            selector: None,
+            containers: Vec::new(),
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
+        }
+    }
+
+    fn commit(hash: &str) -> GitLogEntry {
+        GitLogEntry {
+            hash: hash.to_string(),
+            author: "Jane Doe <jane@acme.io>".to_string(),
+            date: "2026-10-01 09:30:00 +0200".to_string(),
+            message: "deploy(staging): update auth-api".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_deployed_tag_reads_container_of_document() {
+        let manifest = r#"apiVersion: v1
+kind: Service
+metadata:
+  name: auth-api
+---
+apiVersion: apps/v1
+kind: Deployment
+spec:
+  template:
+    spec:
+      containers:
+        - name: proxy
+          image: envoyproxy/envoy:v1.30.0
+        - name: auth-api
+          image: europe-docker.pkg.dev/acme/apps/auth-api:v1.2.1
+"#;
+        assert_eq!(
+            deployed_tag(manifest, &service(false)),
+            Some("v1.2.1".to_string())
+        );
+
+        let kustomization = r#"resources:
+  - ../base
+images:
+  - name: europe-docker.pkg.dev/acme/apps/auth-api
+    newTag: v1.2.0
+"#;
+        assert_eq!(
+            deployed_tag(kustomization, &service(true)),
+            Some("v1.2.0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tag_origin_finds_first_commit_of_current_run() {
+        let commits = vec![commit("c4"), commit("c3"), commit("c2"), commit("c1")];
+        let tags = vec![
+            Some("v3".to_string()),
+            Some("v3".to_string()),
+            Some("v2".to_string()),
+            Some("v3".to_string()),
+        ];
+
+        let origin = tag_origin(&commits, &tags, "v3").unwrap();
+        assert_eq!(origin.commit.hash, "c3");
+        assert_eq!(origin.previous_tag.as_deref(), Some("v2"));
+        assert_eq!(tag_origin(&commits, &tags, "v4"), None);
+    }
+}
@@ -1,15 +1,174 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use console::style;
 use regex::Regex;
+use serde::Deserialize;
 use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+use crate::theme;
 
 pub struct Blueprint;
 
+/// Copies `root` without `.git` to a temporary directory, with `content` as the
+/// kustomization file, and returns the copy and the overlay within it.
+fn copy_with_kustomization(
+    root: &Path,
+    overlay: &Path,
+    kustomization: &Path,
+    content: &str,
+) -> Result<(TempDir, PathBuf)> {
+    let root =
+        fs::canonicalize(root).with_context(|| format!("Failed to resolve {}", root.display()))?;
+    let relative = |path: &Path| -> Result<PathBuf> {
+        let path = fs::canonicalize(path)
+            .with_context(|| format!("Failed to resolve {}", path.display()))?;
+        Ok(path
+            .strip_prefix(&root)
+            .with_context(|| format!("{} is outside {}", path.display(), root.display()))?
+            .to_path_buf())
+    };
+    let (overlay, kustomization) = (relative(overlay)?, relative(kustomization)?);
+
+    let copy = tempfile::tempdir().context("Failed to create a temporary directory")?;
+    for entry in WalkDir::new(&root)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+    {
+        let entry = entry?;
+        let target = copy.path().join(entry.path().strip_prefix(&root)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else if entry.path().is_file() {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+        }
+    }
+    fs::write(copy.path().join(&kustomization), content)?;
+    let overlay = copy.path().join(overlay);
+    Ok((copy, overlay))
+}
+
+/// Splits an image reference into its repository and its tag or digest.
+pub fn split_image_ref(image: &str) -> (&str, Option<&str>) {
+    let trimmed = image.trim();
+
+    if let Some((base, digest)) = trimmed.split_once('@') {
+        return (base, Some(digest));
+    }
+
+    if let Some((base, tag)) = trimmed.rsplit_once(':')
+        && !tag.contains('/')
+    {
+        return (base, Some(tag));
+    }
+
+    (trimmed, None)
+}
+
+/// Whether an image reference suffix is a digest rather than a tag, which cannot hold `:`.
+pub fn is_digest(reference: &str) -> bool {
+    reference.contains(':')
+}
+
+/// Returns the kustomization file of a directory, if it is a kustomize overlay.
+pub fn find_kustomization(dir: &Path) -> Option<PathBuf> {
+    ["kustomization.yaml", "kustomization.yml", "Kustomization"]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Finds the pod spec of a workload, i.e. the first mapping holding `containers`.
+pub fn find_pod_spec(value: &serde_yaml::Value) -> Option<&serde_yaml::Value> {
+    let map = value.as_mapping()?;
+    if map.contains_key("containers") {
+        return Some(value);
+    }
+    map.values().find_map(find_pod_spec)
+}
+
+/// Parses the YAML document at `index` of a multi-document file.
+pub fn parse_document(content: &str, index: usize) -> Option<serde_yaml::Value> {
+    serde_yaml::Deserializer::from_str(content)
+        .nth(index)
+        .and_then(|d| serde_yaml::Value::deserialize(d).ok())
+}
+
+fn join_lines(lines: &[String], original: &str) -> String {
+    let mut joined = lines.join("\n");
+    if original.ends_with('\n') {
+        joined.push('\n');
+    }
+    joined
+}
+
+/// Byte ranges of the documents of a YAML stream, indexed like `serde_yaml::Deserializer`.
+fn document_ranges(content: &str) -> Vec<Range<usize>> {
+    let separator = Regex::new(r"(?m)^---[ \t]*(?:#.*)?$").unwrap();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    for m in separator.find_iter(content) {
+        ranges.push(start..m.start());
+        start = m.end();
+    }
+    ranges.push(start..content.len());
+
+    // Text before the first separator only counts as a document when it holds YAML content.
+    let leading_has_content = content[ranges[0].clone()].lines().any(|line| {
+        let line = line.trim();
+        !line.is_empty() && !line.starts_with('#')
+    });
+    if ranges.len() > 1 && !leading_has_content {
+        ranges.remove(0);
+    }
+
+    ranges
+}
+
 impl Blueprint {
+    /// The documents of `new` that differ from the document at the same position in
+    /// `old`, as text.
+    pub fn changed_documents(old: &str, new: &str) -> Vec<String> {
+        let old_documents: Vec<&str> = document_ranges(old)
+            .into_iter()
+            .map(|r| old[r].trim())
+            .collect();
+        document_ranges(new)
+            .into_iter()
+            .enumerate()
+            .map(|(index, range)| (index, new[range].trim()))
+            .filter(|(index, document)| {
+                !document.is_empty() && old_documents.get(*index) != Some(document)
+            })
+            .map(|(_, document)| document.to_string())
+            .collect()
+    }
+
+    /// Returns true when any document in the YAML content is a ConfigMap.
+    pub fn contains_configmap(content: &str) -> bool {
+        serde_yaml::Deserializer::from_str(content).any(|document| {
+            serde_yaml::Value::deserialize(document)
+                .ok()
+                .and_then(|v| {
+                    v.get("kind")
+                        .and_then(|k| k.as_str())
+                        .map(|k| k == "ConfigMap")
+                })
+                .unwrap_or(false)
+        })
+    }
+
     /// Modifies the image tag in the YAML content while preserving formatting/comments.
     /// It searches for 'image: ...:<old_tag>' and replaces it.
     /// Modifies the image tag in the YAML content while preserving formatting/comments.
-    /// It searches for 'image: <base_image>:<old_tag>' and replaces it.
+    /// It searches for 'image: <base_image>:<old_tag>' and replaces it. A digest
+    /// (`sha256:...`) as `new_tag` pins the image as `<base_image>@<digest>`.
     pub fn update_image_tag(content: &str, base_image: &str, new_tag: &str) -> Result<String> {
         // Escape the base_image for regex safety
         let escaped_base = regex::escape(base_image);
@@ -26,16 +185,507 @@ impl Blueprint {
             ));
         }
 
+        let separator = if is_digest(new_tag) { '@' } else { ':' };
         let new_content = re
-            .replace_all(content, format!("$1:{}", new_tag))
+            .replace_all(content, format!("${{1}}{}{}", separator, new_tag))
             .to_string();
         Ok(new_content)
     }
 
+    /// Same as `update_image_tag`, but only rewrites the document at `document_index`
+    /// of a multi-document YAML file, leaving neighbouring workloads untouched.
+    pub fn update_image_tag_in_document(
+        content: &str,
+        document_index: usize,
+        base_image: &str,
+        new_tag: &str,
+    ) -> Result<String> {
+        let ranges = document_ranges(content);
+        let range = ranges.get(document_index).cloned().ok_or_else(|| {
+            anyhow::anyhow!(
+                "YAML content has {} documents, cannot update document #{}",
+                ranges.len(),
+                document_index
+            )
+        })?;
+
+        let updated = Self::update_image_tag(&content[range.clone()], base_image, new_tag)?;
+        Ok(format!(
+            "{}{}{}",
+            &content[..range.start],
+            updated,
+            &content[range.end..]
+        ))
+    }
+
+    /// Same as `update_image_tag_in_document`, but only rewrites the image of the container
+    /// named `container`, for workloads whose containers share a base image (e.g. an app
+    /// and its migration init container).
+    pub fn update_container_image_tag_in_document(
+        content: &str,
+        document_index: usize,
+        container: &str,
+        base_image: &str,
+        new_tag: &str,
+    ) -> Result<String> {
+        let ranges = document_ranges(content);
+        let range = ranges.get(document_index).cloned().ok_or_else(|| {
+            anyhow::anyhow!(
+                "YAML content has {} documents, cannot update document #{}",
+                ranges.len(),
+                document_index
+            )
+        })?;
+        let document = &content[range.clone()];
+        let mut lines: Vec<String> = document.lines().map(|l| l.to_string()).collect();
+        let indent_of = |line: &str| line.len() - line.trim_start().len();
+        let is_content = |line: &str| {
+            let trimmed = line.trim_start();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        };
+        let image = Regex::new(&format!(
+            r"^(\s*(?:-\s+)?image:\s*{})[:@][^\s#]+",
+            regex::escape(base_image)
+        ))
+        .unwrap();
+        let name = Regex::new(r"^\s*(?:-\s+)?name:\s*([^#]+)").unwrap();
+
+        // The list item holding line `at`, with the indent of its dash.
+        let item_of = |lines: &[String], at: usize| -> Option<(usize, usize)> {
+            if lines[at].trim_start().starts_with("- ") {
+                return Some((at, indent_of(&lines[at])));
+            }
+            let key_indent = indent_of(&lines[at]);
+            let start = (0..at)
+                .rev()
+                .find(|&j| is_content(&lines[j]) && indent_of(&lines[j]) < key_indent)?;
+            lines[start]
+                .trim_start()
+                .starts_with("- ")
+                .then(|| (start, indent_of(&lines[start])))
+        };
+
+        let separator = if is_digest(new_tag) { '@' } else { ':' };
+        let mut updated = 0;
+        for i in 0..lines.len() {
+            if !image.is_match(&lines[i]) {
+                continue;
+            }
+            let Some((start, dash_indent)) = item_of(&lines, i) else {
+                continue;
+            };
+            let end = (start + 1..lines.len())
+                .find(|&j| is_content(&lines[j]) && indent_of(&lines[j]) <= dash_indent)
+                .unwrap_or(lines.len());
+            let named = (start..end).any(|j| {
+                (j == start || indent_of(&lines[j]) == dash_indent + 2)
+                    && name.captures(&lines[j]).is_some_and(|c| {
+                        c[1].trim().trim_matches(|c| c == '"' || c == '\'') == container
+                    })
+            });
+            if named {
+                lines[i] = image
+                    .replace(&lines[i], format!("${{1}}{}{}", separator, new_tag))
+                    .to_string();
+                updated += 1;
+            }
+        }
+        if updated == 0 {
+            return Err(anyhow::anyhow!(
+                "Could not find 'image: {}' of container {} in the YAML content",
+                base_image,
+                container
+            ));
+        }
+        Ok(format!(
+            "{}{}{}",
+            &content[..range.start],
+            join_lines(&lines, document),
+            &content[range.end..]
+        ))
+    }
+
+    /// Sets `spec.replicas` of the document at `document_index`, keeping comments and the
+    /// rest of the file as they are; the field is added under `spec` when missing.
+    pub fn update_replicas_in_document(
+        content: &str,
+        document_index: usize,
+        replicas: i32,
+    ) -> Result<String> {
+        let ranges = document_ranges(content);
+        let range = ranges.get(document_index).cloned().ok_or_else(|| {
+            anyhow::anyhow!(
+                "YAML content has {} documents, cannot update document #{}",
+                ranges.len(),
+                document_index
+            )
+        })?;
+        let document = &content[range.clone()];
+        let mut lines: Vec<String> = document.lines().map(|l| l.to_string()).collect();
+        let indent_of = |line: &str| line.len() - line.trim_start().len();
+        let is_content = |line: &str| {
+            let trimmed = line.trim_start();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        };
+
+        let spec = lines
+            .iter()
+            .position(|l| l.trim_end() == "spec:")
+            .context("Could not find the top-level 'spec:' of the workload")?;
+        let end = (spec + 1..lines.len())
+            .find(|&i| is_content(&lines[i]) && indent_of(&lines[i]) == 0)
+            .unwrap_or(lines.len());
+        let child_indent = (spec + 1..end)
+            .find(|&i| is_content(&lines[i]))
+            .map(|i| indent_of(&lines[i]))
+            .unwrap_or(2);
+        let field = (spec + 1..end).find(|&i| {
+            indent_of(&lines[i]) == child_indent && lines[i].trim_start().starts_with("replicas:")
+        });
+        match field {
+            Some(i) => {
+                let comment = lines[i].find(" #").map(|c| lines[i][c..].to_string());
+                lines[i] = format!(
+                    "{}replicas: {}{}",
+                    " ".repeat(child_indent),
+                    replicas,
+                    comment.unwrap_or_default()
+                );
+            }
+            None => lines.insert(
+                spec + 1,
+                format!("{}replicas: {}", " ".repeat(child_indent), replicas),
+            ),
+        }
+        Ok(format!(
+            "{}{}{}",
+            &content[..range.start],
+            join_lines(&lines, document),
+            &content[range.end..]
+        ))
+    }
+
+    /// Sets annotations in the top-level `metadata` of a document, replacing the values of
+    /// keys already there and adding `annotations:` when missing. Comments and the other
+    /// lines are kept.
+    pub fn update_annotations_in_document(
+        content: &str,
+        document_index: usize,
+        annotations: &[(String, String)],
+    ) -> Result<String> {
+        let ranges = document_ranges(content);
+        let range = ranges.get(document_index).cloned().ok_or_else(|| {
+            anyhow::anyhow!(
+                "YAML content has {} documents, cannot update document #{}",
+                ranges.len(),
+                document_index
+            )
+        })?;
+        let document = &content[range.clone()];
+        let mut lines: Vec<String> = document.lines().map(|l| l.to_string()).collect();
+        let indent_of = |line: &str| line.len() - line.trim_start().len();
+        let is_content = |line: &str| {
+            let trimmed = line.trim_start();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        };
+        // The end of the block of children of line `parent`, which are indented deeper.
+        let block_end = |lines: &[String], parent: usize| {
+            let indent = indent_of(&lines[parent]);
+            let last = (parent + 1..lines.len())
+                .take_while(|&i| !is_content(&lines[i]) || indent_of(&lines[i]) > indent)
+                .filter(|&i| is_content(&lines[i]))
+                .last();
+            last.map_or(parent + 1, |i| i + 1)
+        };
+
+        let metadata = lines
+            .iter()
+            .position(|l| l.trim_end() == "metadata:")
+            .context("Could not find the top-level 'metadata:' of the workload")?;
+        let end = block_end(&lines, metadata);
+        let child_indent = (metadata + 1..end)
+            .find(|&i| is_content(&lines[i]))
+            .map(|i| indent_of(&lines[i]))
+            .unwrap_or(2);
+        let existing = (metadata + 1..end).find(|&i| {
+            indent_of(&lines[i]) == child_indent
+                && lines[i].trim_start().starts_with("annotations:")
+        });
+        let annotations_line = match existing {
+            Some(i) => {
+                // An empty flow mapping (`annotations: {}`) becomes a block.
+                lines[i] = format!("{}annotations:", " ".repeat(child_indent));
+                i
+            }
+            None => {
+                lines.insert(end, format!("{}annotations:", " ".repeat(child_indent)));
+                end
+            }
+        };
+        let entry_indent = (annotations_line + 1..block_end(&lines, annotations_line))
+            .find(|&i| is_content(&lines[i]))
+            .map(|i| indent_of(&lines[i]))
+            .unwrap_or(child_indent + 2);
+
+        for (key, value) in annotations {
+            let entry = format!(
+                "{}{}: {}",
+                " ".repeat(entry_indent),
+                key,
+                serde_json::to_string(value)?
+            );
+            let block = annotations_line + 1..block_end(&lines, annotations_line);
+            let found = block.clone().find(|&i| {
+                indent_of(&lines[i]) == entry_indent && {
+                    let name = lines[i].trim_start();
+                    let name = name.split_once(':').map_or(name, |(k, _)| k);
+                    name.trim_matches(|c| c == '"' || c == '\'') == key
+                }
+            });
+            match found {
+                Some(i) => {
+                    // Multi-line values continue on deeper-indented lines.
+                    let value_end = block_end(&lines, i);
+                    lines.splice(i..value_end, [entry]);
+                }
+                None => lines.insert(block.end, entry),
+            }
+        }
+        Ok(format!(
+            "{}{}{}",
+            &content[..range.start],
+            join_lines(&lines, document),
+            &content[range.end..]
+        ))
+    }
+
+    /// Sets `newTag` for an image in a kustomization's `images` section, adding the
+    /// entry when the image is not listed yet. Matches either `name` or `newName`.
+    /// A digest (`sha256:...`) is set as `digest` instead, replacing the tag.
+    pub fn update_kustomize_image_tag(
+        content: &str,
+        base_image: &str,
+        new_tag: &str,
+    ) -> Result<String> {
+        let (field, replaced) = if is_digest(new_tag) {
+            ("digest", "newTag")
+        } else {
+            ("newTag", "digest")
+        };
+        let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+        let Some(images_idx) = lines.iter().position(|l| l.trim_end() == "images:") else {
+            let mut updated = content.trim_end().to_string();
+            updated.push_str(&format!(
+                "\nimages:\n- name: {}\n  {}: {}\n",
+                base_image, field, new_tag
+            ));
+            return Ok(updated);
+        };
+
+        // Collect the `images` block items as (first line, end line exclusive).
+        let mut items: Vec<(usize, usize)> = Vec::new();
+        let mut idx = images_idx + 1;
+        while idx < lines.len() {
+            let line = &lines[idx];
+            let trimmed = line.trim_start();
+            let is_top_level_key = !line.starts_with(' ') && !trimmed.starts_with('-');
+            if !trimmed.is_empty() && is_top_level_key {
+                break;
+            }
+            if trimmed.starts_with("- ") {
+                items.push((idx, idx + 1));
+            } else if let Some(last) = items.last_mut() {
+                last.1 = idx + 1;
+            }
+            idx += 1;
+        }
+
+        let field_value = |line: &str, field: &str| -> Option<String> {
+            let trimmed = line.trim_start().trim_start_matches("- ").trim_start();
+            trimmed
+                .strip_prefix(field)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .map(|v| v.trim().trim_matches('"').trim_matches('\'').to_string())
+        };
+
+        for (start, end) in &items {
+            let matches = lines[*start..*end].iter().any(|l| {
+                field_value(l, "name").as_deref() == Some(base_image)
+                    || field_value(l, "newName").as_deref() == Some(base_image)
+            });
+            if !matches {
+                continue;
+            }
+
+            let item_indent = lines[*start].len() - lines[*start].trim_start().len() + 2;
+            let mut item: Vec<String> = lines[*start..*end]
+                .iter()
+                .enumerate()
+                .filter(|(i, l)| *i == 0 || field_value(l, replaced).is_none())
+                .map(|(_, l)| l.clone())
+                .collect();
+            match item.iter_mut().find(|l| field_value(l, field).is_some()) {
+                Some(line) => {
+                    let prefix_len = line.find(&format!("{}:", field)).unwrap_or(0);
+                    *line = format!("{}{}: {}", &line[..prefix_len], field, new_tag);
+                }
+                None => item.push(format!("{}{}: {}", " ".repeat(item_indent), field, new_tag)),
+            }
+            lines.splice(*start..*end, item);
+            return Ok(join_lines(&lines, content));
+        }
+
+        lines.insert(images_idx + 1, format!("  {}: {}", field, new_tag));
+        lines.insert(images_idx + 1, format!("- name: {}", base_image));
+        Ok(join_lines(&lines, content))
+    }
+
+    /// Sets the value at a dotted key (e.g. `image.tag`) of a Helm values file, keeping
+    /// comments, quoting and the rest of the file as they are. Unquoted tags that YAML
+    /// would read as another type (`1.10`, `true`) are quoted.
+    pub fn update_helm_values_tag(content: &str, key: &str, new_tag: &str) -> Result<String> {
+        let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+        let indent_of = |line: &str| line.len() - line.trim_start().len();
+        let is_content = |line: &str| {
+            let trimmed = line.trim_start();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        };
+        let not_found = || anyhow::anyhow!("Could not find '{}' in the Helm values", key);
+
+        // Narrow the search to the block of each key segment in turn.
+        let (mut start, mut end) = (0, lines.len());
+        let mut found = None;
+        for segment in key.split('.') {
+            let block_indent = lines[start..end]
+                .iter()
+                .find(|l| is_content(l))
+                .map(|l| indent_of(l))
+                .ok_or_else(not_found)?;
+            let idx = (start..end)
+                .find(|&i| {
+                    let line = &lines[i];
+                    is_content(line)
+                        && indent_of(line) == block_indent
+                        && line
+                            .trim_start()
+                            .split_once(':')
+                            .is_some_and(|(k, _)| k.trim().trim_matches(['"', '\'']) == segment)
+                })
+                .ok_or_else(not_found)?;
+            let indent = indent_of(&lines[idx]);
+            start = idx + 1;
+            end = (start..end)
+                .find(|&i| is_content(&lines[i]) && indent_of(&lines[i]) <= indent)
+                .unwrap_or(end);
+            found = Some(idx);
+        }
+
+        let idx = found.ok_or_else(not_found)?;
+        let line = &lines[idx];
+        let colon = line.find(':').ok_or_else(not_found)?;
+        let rest = &line[colon + 1..];
+        let value_start = colon + 1 + (rest.len() - rest.trim_start().len());
+        let value = &line[value_start..];
+        let (quote, value_len) = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => (
+                Some(q),
+                value[1..].find(q).map(|end| end + 2).unwrap_or(value.len()),
+            ),
+            _ => (
+                None,
+                value
+                    .find(" #")
+                    .unwrap_or(value.len())
+                    .min(value.trim_end().len()),
+            ),
+        };
+        let quote = quote.or_else(|| {
+            let plain = matches!(
+                serde_yaml::from_str::<serde_yaml::Value>(new_tag),
+                Ok(serde_yaml::Value::String(_))
+            );
+            (!plain).then_some('"')
+        });
+        let new_value = match quote {
+            Some(q) => format!("{}{}{}", q, new_tag, q),
+            None => new_tag.to_string(),
+        };
+        let separator = if value_start == colon + 1 { " " } else { "" };
+        lines[idx] = format!(
+            "{}{}{}{}",
+            &line[..value_start],
+            separator,
+            new_value,
+            &line[value_start + value_len..]
+        );
+        Ok(join_lines(&lines, content))
+    }
+
+    /// Renders a kustomization directory with `kustomize build`.
+    pub fn render_kustomize(dir: &Path) -> Result<String> {
+        let output = Command::new("kustomize")
+            .arg("build")
+            .arg(dir)
+            .output()
+            .context("Failed to execute kustomize build. Is kustomize installed?")?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "kustomize build {} failed: {}",
+                dir.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Renders the overlay as if its kustomization file held `content`, building a copy
+    /// of `root` (without `.git`) in a temporary directory so that the working tree is
+    /// never modified. Bases and patches referenced within `root` resolve as in place.
+    pub fn render_kustomize_edited(
+        root: &Path,
+        overlay: &Path,
+        kustomization: &Path,
+        content: &str,
+    ) -> Result<String> {
+        // The copy is removed when dropped, after the build.
+        let (_copy, overlay) = copy_with_kustomization(root, overlay, kustomization, content)?;
+        Self::render_kustomize(&overlay)
+    }
+
     /// Displays a colored diff between old and new content.
+    /// Prints a unified diff produced by another tool, e.g. `kubectl diff`.
+    pub fn show_patch(patch: &str, label: &str) {
+        let theme = theme::current();
+        println!("\n{}", style(label).bold());
+        for line in patch.lines() {
+            if line.starts_with("diff ") || line.starts_with("---") || line.starts_with("+++") {
+                println!("{}", theme.diff_context.apply_to(line));
+            } else if line.starts_with("@@") {
+                println!("{}", theme.diff_hunk.apply_to(line));
+            } else if line.starts_with('-') {
+                println!("{}", theme.diff_removed.apply_to(line));
+            } else if line.starts_with('+') {
+                println!("{}", theme.diff_added.apply_to(line));
+            } else {
+                println!("{}", theme.diff_context.apply_to(line));
+            }
+        }
+    }
+
     pub fn show_diff(old: &str, new: &str, filename: &str, unified: bool) {
-        println!("\n{} {}", style("---").dim(), style(filename).bold());
-        println!("{} {}", style("+++").dim(), style(filename).bold());
+        let theme = theme::current();
+        println!(
+            "\n{} {}",
+            theme.diff_context.apply_to("---"),
+            style(filename).bold()
+        );
+        println!(
+            "{} {}",
+            theme.diff_context.apply_to("+++"),
+            style(filename).bold()
+        );
 
         let diff = TextDiff::from_lines(old, new);
 
@@ -45,21 +695,21 @@ impl Blueprint {
                     match op {
                         similar::DiffOp::Equal { old_index, len, .. } => {
                             for line in &diff.old_slices()[old_index..old_index + len] {
-                                print!(" {}", style(line).dim());
+                                print!(" {}", theme.diff_context.apply_to(line));
                             }
                         }
                         similar::DiffOp::Delete {
                             old_index, old_len, ..
                         } => {
                             for line in &diff.old_slices()[old_index..old_index + old_len] {
-                                print!("-{}", style(line).red());
+                                print!("-{}", theme.diff_removed.apply_to(line));
                             }
                         }
                         similar::DiffOp::Insert {
                             new_index, new_len, ..
                         } => {
                             for line in &diff.new_slices()[new_index..new_index + new_len] {
-                                print!("+{}", style(line).green());
+                                print!("+{}", theme.diff_added.apply_to(line));
                             }
                         }
                         similar::DiffOp::Replace {
@@ -69,15 +719,15 @@ impl Blueprint {
                             new_len,
                         } => {
                             for line in &diff.old_slices()[old_index..old_index + old_len] {
-                                print!("-{}", style(line).red());
+                                print!("-{}", theme.diff_removed.apply_to(line));
                             }
                             for line in &diff.new_slices()[new_index..new_index + new_len] {
-                                print!("+{}", style(line).green());
+                                print!("+{}", theme.diff_added.apply_to(line));
                             }
                         }
                     }
                 }
-                println!("{}", style("@@ ... @@").cyan());
+                println!("{}", theme.diff_hunk.apply_to("@@ ... @@"));
             }
         } else {
             for change in diff.iter_all_changes() {
@@ -89,11 +739,11 @@ impl Blueprint {
 
                 let line = change.to_string();
                 let styled_line = if color == "red" {
-                    style(format!("{}{}", sign, line)).red()
+                    theme.diff_removed.apply_to(format!("{}{}", sign, line))
                 } else if color == "green" {
-                    style(format!("{}{}", sign, line)).green()
+                    theme.diff_added.apply_to(format!("{}{}", sign, line))
                 } else {
-                    style(format!("{}{}", sign, line)).dim()
+                    theme.diff_context.apply_to(format!("{}{}", sign, line))
                 };
 
                 print!("{}", styled_line);
@@ -130,4 +780,283 @@ spec:
         assert!(updated.contains("image: gcr.io/my-project/my-app:v2"));
         assert!(updated.contains("image: haproxy:2.4"));
     }
+
+    #[test]
+    fn test_update_image_tag_in_document_leaves_neighbors_untouched() {
+        let content = r#"# shared workloads
+---
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: api
+spec:
+  template:
+    spec:
+      containers:
+      - name: main
+        image: gcr.io/my-project/shared:v1
+---
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: worker
+spec:
+  template:
+    spec:
+      containers:
+      - name: main
+        image: gcr.io/my-project/shared:v1
+"#;
+        let updated =
+            Blueprint::update_image_tag_in_document(content, 1, "gcr.io/my-project/shared", "v2")
+                .unwrap();
+
+        let documents: Vec<&str> = updated.split("\n---\n").collect();
+        assert!(documents[1].contains("image: gcr.io/my-project/shared:v1"));
+        assert!(documents[2].contains("image: gcr.io/my-project/shared:v2"));
+        assert!(updated.starts_with("# shared workloads\n"));
+
+        let parsed: Vec<String> = serde_yaml::Deserializer::from_str(content)
+            .map(|d| {
+                let v = serde_yaml::Value::deserialize(d).unwrap();
+                v["metadata"]["name"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(parsed, vec!["api", "worker"]);
+        assert_eq!(document_ranges(content).len(), 2);
+    }
+
+    #[test]
+    fn test_update_replicas_in_document() {
+        let content = "apiVersion: v1\nkind: Service\nspec:\n  replicas: 9\n---\napiVersion: apps/v1\nkind: Deployment\nspec:\n  replicas: 2 # baseline\n  template:\n    spec:\n      replicas: 1\n";
+        let updated = Blueprint::update_replicas_in_document(content, 1, 6).unwrap();
+        assert!(updated.starts_with("apiVersion: v1\nkind: Service\nspec:\n  replicas: 9\n---"));
+        assert!(updated.contains("  replicas: 6 # baseline\n"));
+        assert!(updated.contains("      replicas: 1\n"));
+
+        let missing = "kind: Deployment\nspec:\n    selector: {}\n";
+        assert_eq!(
+            Blueprint::update_replicas_in_document(missing, 0, 3).unwrap(),
+            "kind: Deployment\nspec:\n    replicas: 3\n    selector: {}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_container_image_tag_of_shared_base_image() {
+        let content = r#"kind: Deployment
+spec:
+  template:
+    spec:
+      initContainers:
+      - image: gcr.io/p/app:v1 # migrations
+        name: migrate
+        args: ["migrate"]
+      containers:
+      -   name: "app"
+          image: gcr.io/p/app:v1
+"#;
+        let app = Blueprint::update_container_image_tag_in_document(
+            content,
+            0,
+            "app",
+            "gcr.io/p/app",
+            "v2",
+        )
+        .unwrap();
+        assert!(app.contains("      - image: gcr.io/p/app:v1 # migrations\n"));
+        assert!(app.contains("          image: gcr.io/p/app:v2\n"));
+        let both = Blueprint::update_container_image_tag_in_document(
+            &app,
+            0,
+            "migrate",
+            "gcr.io/p/app",
+            "v2",
+        )
+        .unwrap();
+        assert!(both.contains("      - image: gcr.io/p/app:v2 # migrations\n"));
+        assert!(
+            Blueprint::update_container_image_tag_in_document(
+                content,
+                0,
+                "worker",
+                "gcr.io/p/app",
+                "v2"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_update_annotations_in_document() {
+        let content = "kind: Service\nmetadata:\n  name: api\n---\nkind: Deployment\nmetadata:\n  name: api\n  labels:\n    app: api # team\nspec:\n  template:\n    metadata:\n      labels: {}\n";
+        let annotations = [
+            (
+                "kubernetes.io/change-cause".to_string(),
+                "deploy api v1 → v2".to_string(),
+            ),
+            ("davit.dev/previous-tag".to_string(), "v1".to_string()),
+        ];
+        let added = Blueprint::update_annotations_in_document(content, 1, &annotations).unwrap();
+        assert_eq!(
+            added,
+            "kind: Service\nmetadata:\n  name: api\n---\nkind: Deployment\nmetadata:\n  name: api\n  labels:\n    app: api # team\n  annotations:\n    kubernetes.io/change-cause: \"deploy api v1 → v2\"\n    davit.dev/previous-tag: \"v1\"\nspec:\n  template:\n    metadata:\n      labels: {}\n"
+        );
+
+        let existing = "metadata:\n    annotations:\n        owner: team\n        davit.dev/previous-tag: |\n          v0\n    name: api\n";
+        assert_eq!(
+            Blueprint::update_annotations_in_document(existing, 0, &annotations[1..]).unwrap(),
+            "metadata:\n    annotations:\n        owner: team\n        davit.dev/previous-tag: \"v1\"\n    name: api\n"
+        );
+    }
+
+    #[test]
+    fn test_kustomization_edits_are_built_from_a_copy() {
+        let root = tempfile::tempdir().unwrap();
+        let overlay = root.path().join("overlays/prod");
+        fs::create_dir_all(&overlay).unwrap();
+        fs::create_dir_all(root.path().join("base")).unwrap();
+        fs::create_dir_all(root.path().join(".git")).unwrap();
+        fs::write(
+            root.path().join("base/deployment.yaml"),
+            "kind: Deployment\n",
+        )
+        .unwrap();
+        let kustomization = overlay.join("kustomization.yaml");
+        fs::write(&kustomization, "resources: [../../base]\n").unwrap();
+
+        let edited = "resources: [../../base]\nimages: [{name: app, newTag: v2}]\n";
+        let (copy, copied_overlay) =
+            copy_with_kustomization(root.path(), &overlay, &kustomization, edited).unwrap();
+        assert_eq!(
+            fs::read_to_string(copied_overlay.join("kustomization.yaml")).unwrap(),
+            edited
+        );
+        assert!(copied_overlay.join("../../base/deployment.yaml").is_file());
+        assert!(!copy.path().join(".git").exists());
+        assert_eq!(
+            fs::read_to_string(&kustomization).unwrap(),
+            "resources: [../../base]\n"
+        );
+    }
+
+    #[test]
+    fn test_update_kustomize_image_tag_existing_entry() {
+        let content = "resources:\n- ../base\nimages:\n- name: gcr.io/p/app\n  newTag: v1\n- name: gcr.io/p/worker\n  newTag: v1\nnamespace: prod\n";
+        let updated = Blueprint::update_kustomize_image_tag(content, "gcr.io/p/app", "v2").unwrap();
+        assert_eq!(
+            updated,
+            "resources:\n- ../base\nimages:\n- name: gcr.io/p/app\n  newTag: v2\n- name: gcr.io/p/worker\n  newTag: v1\nnamespace: prod\n"
+        );
+    }
+
+    #[test]
+    fn test_update_kustomize_image_tag_adds_missing_entry() {
+        let content = "resources:\n- ../base\n";
+        let updated = Blueprint::update_kustomize_image_tag(content, "gcr.io/p/app", "v2").unwrap();
+        assert_eq!(
+            updated,
+            "resources:\n- ../base\nimages:\n- name: gcr.io/p/app\n  newTag: v2\n"
+        );
+
+        let content = "images:\n- name: app\n  newName: gcr.io/p/app\n";
+        let updated = Blueprint::update_kustomize_image_tag(content, "gcr.io/p/app", "v3").unwrap();
+        assert_eq!(
+            updated,
+            "images:\n- name: app\n  newName: gcr.io/p/app\n  newTag: v3\n"
+        );
+    }
+
+    #[test]
+    fn test_update_helm_values_tag_sets_nested_key_only() {
+        let values = r#"replicaCount: 2
+image:
+  repository: europe-docker.pkg.dev/acme/apps/auth-api
+  # bumped by davit
+  tag: "v1.2.3"  # current release
+sidecar:
+  image:
+    tag: v0.9.0
+"#;
+        let updated = Blueprint::update_helm_values_tag(values, "image.tag", "v1.3.0").unwrap();
+        assert!(updated.contains("  tag: \"v1.3.0\"  # current release\n"));
+        assert!(updated.contains("    tag: v0.9.0\n"));
+
+        let updated =
+            Blueprint::update_helm_values_tag(values, "sidecar.image.tag", "1.10").unwrap();
+        assert!(updated.contains("    tag: \"1.10\"\n"));
+        assert!(updated.contains("  tag: \"v1.2.3\"  # current release\n"));
+
+        assert!(Blueprint::update_helm_values_tag(values, "image.digest", "v2").is_err());
+        assert!(Blueprint::update_helm_values_tag(values, "tag", "v2").is_err());
+    }
+
+    #[test]
+    fn test_update_image_tag_pins_digests() {
+        let content = "containers:\n  - name: app\n    image: gcr.io/p/app:v1 # app\n";
+        let pinned = Blueprint::update_image_tag(content, "gcr.io/p/app", "sha256:abc123").unwrap();
+        assert_eq!(
+            pinned,
+            "containers:\n  - name: app\n    image: gcr.io/p/app@sha256:abc123 # app\n"
+        );
+
+        let content =
+            "images:\n- name: gcr.io/p/app\n  newTag: v1\n- name: gcr.io/p/worker\n  newTag: v1\n";
+        let pinned =
+            Blueprint::update_kustomize_image_tag(content, "gcr.io/p/app", "sha256:abc123")
+                .unwrap();
+        assert_eq!(
+            pinned,
+            "images:\n- name: gcr.io/p/app\n  digest: sha256:abc123\n- name: gcr.io/p/worker\n  newTag: v1\n"
+        );
+    }
+
+    #[test]
+    fn test_update_image_tag_replaces_pinned_digests() {
+        let content = "containers:\n  - name: app\n    image: gcr.io/p/app@sha256:abc123\n";
+        assert_eq!(
+            Blueprint::update_image_tag(content, "gcr.io/p/app", "v2").unwrap(),
+            "containers:\n  - name: app\n    image: gcr.io/p/app:v2\n"
+        );
+        assert_eq!(
+            Blueprint::update_image_tag(content, "gcr.io/p/app", "sha256:def456").unwrap(),
+            "containers:\n  - name: app\n    image: gcr.io/p/app@sha256:def456\n"
+        );
+
+        let content = "images:\n- name: gcr.io/p/app\n  digest: sha256:abc123\n";
+        assert_eq!(
+            Blueprint::update_kustomize_image_tag(content, "gcr.io/p/app", "v2").unwrap(),
+            "images:\n- name: gcr.io/p/app\n  newTag: v2\n"
+        );
+    }
+
+    #[test]
+    fn test_contains_configmap_in_multi_document_yaml() {
+        let content = r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: my-app
+---
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: my-app-config
+data:
+  LOG_LEVEL: debug
+"#;
+        assert!(Blueprint::contains_configmap(content));
+        assert!(!Blueprint::contains_configmap("kind: Deployment\n"));
+    }
+
+    #[test]
+    fn test_changed_documents_keeps_edited_documents_only() {
+        let old =
+            "# app\nkind: Service\n---\nkind: Deployment\nimage: app:v1\n---\nkind: ConfigMap\n";
+        let new = "# app\nkind: Service\n---\nkind: Deployment\nimage: app:v2\n---\nkind: ConfigMap\n---\nkind: Secret\n";
+        assert_eq!(
+            Blueprint::changed_documents(old, new),
+            ["kind: Deployment\nimage: app:v2", "kind: Secret"]
+        );
+        assert!(Blueprint::changed_documents(old, old).is_empty());
+    }
 }
@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use k8s_openapi::api::apps::v1::ReplicaSet;
+use k8s_openapi::api::core::v1::{Event, Pod};
+use kube::{
+    Api, Client,
+    api::{ListParams, LogParams},
+};
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::config::{Environment, ServiceSource};
+use crate::{cluster, history, mock};
+
+const LOG_TAIL_LINES: i64 = 200;
+const LOG_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a failed deploy tried to change.
+pub struct FailedDeploy<'a> {
+    pub env: &'a Environment,
+    pub service: &'a ServiceSource,
+    pub tag: &'a str,
+    pub reason: &'a str,
+    pub old_manifest: &'a str,
+    pub new_manifest: &'a str,
+}
+
+/// Collects the state of a failed deploy into a timestamped directory: summary, applied
+/// diff, `kubectl describe` of pods and replica sets, the objects themselves, recent
+/// events and the last log lines of crashed or unready containers.
+pub async fn capture(deploy: &FailedDeploy<'_>) -> Result<PathBuf> {
+    let dir = history::data_dir()?.join("failures").join(format!(
+        "{}-{}-{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        deploy.env.name,
+        deploy.service.name
+    ));
+    fs::create_dir_all(dir.join("logs"))
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    fs::write(dir.join("summary.txt"), summary(deploy))?;
+    fs::write(
+        dir.join("applied.diff"),
+        TextDiff::from_lines(deploy.old_manifest, deploy.new_manifest)
+            .unified_diff()
+            .header("deployed", "applied")
+            .to_string(),
+    )?;
+
+    let namespace = deploy.service.namespace.as_deref().unwrap_or("default");
+    let selector = deploy
+        .service
+        .selector
+        .clone()
+        .unwrap_or_else(|| format!("app={}", deploy.service.name));
+
+    if !mock::enabled() {
+        fs::write(
+            dir.join("describe.txt"),
+            describe(&deploy.env.kubectl_context, namespace, &selector),
+        )?;
+    }
+
+    // The cluster may be unreachable; whatever was collected so far is still useful.
+    let captured = match cluster::client(&deploy.env.kubectl_context).await {
+        Ok(client) => capture_cluster_state(client, namespace, &selector, &dir).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = captured {
+        fs::write(dir.join("errors.txt"), format!("{:#}\n", e))?;
+    }
+    Ok(dir)
+}
+
+fn summary(deploy: &FailedDeploy<'_>) -> String {
+    format!(
+        "environment: {}\ncontext: {}\nservice: {}\nprevious image: {}\nnew tag: {}\nfailure: {}\ncaptured at: {}\n",
+        deploy.env.name,
+        deploy.env.kubectl_context,
+        deploy.service.name,
+        deploy.service.image_path,
+        deploy.tag,
+        deploy.reason,
+        Local::now().to_rfc3339()
+    )
+}
+
+fn describe(context: &str, namespace: &str, selector: &str) -> String {
+    let output = Command::new("kubectl")
+        .args(["--context", context, "-n", namespace])
+        .args(["describe", "pods,replicasets", "-l", selector])
+        .output();
+    match output {
+        Ok(o) => format!(
+            "{}{}",
+            String::from_utf8_lossy(&o.stdout),
+            String::from_utf8_lossy(&o.stderr)
+        ),
+        Err(e) => format!("Failed to execute kubectl describe: {}\n", e),
+    }
+}
+
+async fn capture_cluster_state(
+    client: Client,
+    namespace: &str,
+    selector: &str,
+    dir: &Path,
+) -> Result<()> {
+    let pods_api: Api<Pod> = Api::namespaced(client.clone(), namespace);
+    let rs_api: Api<ReplicaSet> = Api::namespaced(client.clone(), namespace);
+    let events_api: Api<Event> = Api::namespaced(client, namespace);
+    let lp = ListParams::default().labels(selector);
+
+    let pods = pods_api.list(&lp).await?.items;
+    fs::write(dir.join("pods.yaml"), serde_yaml::to_string(&pods)?)?;
+    let replica_sets = rs_api.list(&lp).await.map(|l| l.items).unwrap_or_default();
+    fs::write(
+        dir.join("replicasets.yaml"),
+        serde_yaml::to_string(&replica_sets)?,
+    )?;
+
+    let names: Vec<String> = pods
+        .iter()
+        .filter_map(|p| p.metadata.name.clone())
+        .chain(replica_sets.iter().filter_map(|r| r.metadata.name.clone()))
+        .collect();
+    let mut events = events_api
+        .list(&ListParams::default())
+        .await
+        .map(|l| l.items)
+        .unwrap_or_default();
+    events.retain(|e| {
+        e.involved_object
+            .name
+            .as_ref()
+            .is_some_and(|n| names.contains(n))
+    });
+    fs::write(dir.join("events.txt"), format_events(&mut events))?;
+
+    for pod in &pods {
+        let pod_name = pod.metadata.name.clone().unwrap_or_default();
+        for status in pod
+            .status
+            .iter()
+            .flat_map(|s| s.container_statuses.iter().flatten())
+        {
+            if status.ready && status.restart_count == 0 {
+                continue;
+            }
+            // Crashed containers have their useful output in the previous run.
+            let params = LogParams {
+                container: Some(status.name.clone()),
+                tail_lines: Some(LOG_TAIL_LINES),
+                previous: status.restart_count > 0,
+                ..Default::default()
+            };
+            let logs =
+                match tokio::time::timeout(LOG_TIMEOUT, pods_api.logs(&pod_name, &params)).await {
+                    Ok(Ok(logs)) => logs,
+                    Ok(Err(e)) => format!("Failed to fetch logs: {}\n", e),
+                    Err(_) => "Timed out fetching logs\n".to_string(),
+                };
+            fs::write(
+                dir.join("logs")
+                    .join(format!("{}_{}.log", pod_name, status.name)),
+                logs,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Events oldest first, one per line.
+fn format_events(events: &mut [Event]) -> String {
+    let last_seen = |e: &Event| {
+        e.last_timestamp
+            .as_ref()
+            .map(|t| t.0.to_string())
+            .or_else(|| e.event_time.as_ref().map(|t| t.0.to_string()))
+            .unwrap_or_default()
+    };
+    events.sort_by_key(last_seen);
+    events
+        .iter()
+        .map(|e| {
+            format!(
+                "{} {} {}/{} {}: {}\n",
+                last_seen(e),
+                e.type_.as_deref().unwrap_or("Normal"),
+                e.involved_object.kind.as_deref().unwrap_or_default(),
+                e.involved_object.name.as_deref().unwrap_or_default(),
+                e.reason.as_deref().unwrap_or_default(),
+                e.message.as_deref().unwrap_or_default()
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_events_sorts_oldest_first() {
+        let event = |name: &str, at: &str, reason: &str| -> Event {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {"name": name},
+                "involvedObject": {"kind": "Pod", "name": "auth-api-1"},
+                "type": "Warning",
+                "reason": reason,
+                "message": "details",
+                "lastTimestamp": at
+            }))
+            .unwrap()
+        };
+        let mut events = vec![
+            event("b", "2026-10-01T09:31:00Z", "BackOff"),
+            event("a", "2026-10-01T09:30:00Z", "Failed"),
+        ];
+
+        let text = format_events(&mut events);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "2026-10-01T09:30:00Z Warning Pod/auth-api-1 Failed: details"
+        );
+        assert!(lines[1].ends_with("BackOff: details"));
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Clipboard helpers tried in order; the first one installed wins.
+const CLIPBOARD_COMMANDS: [(&str, &[&str]); 5] = [
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("clip.exe", &[]),
+];
+
+/// Copies text to the system clipboard. Over SSH, or when no clipboard helper is
+/// available, falls back to an OSC 52 escape sequence handled by the terminal.
+/// Returns the mechanism used.
+pub fn copy(text: &str) -> Result<&'static str> {
+    let remote = env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some();
+    if !remote {
+        for (program, args) in CLIPBOARD_COMMANDS {
+            if pipe_to(program, args, text) {
+                return Ok(program);
+            }
+        }
+    }
+
+    let sequence = osc52_sequence(text, env::var_os("TMUX").is_some());
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|_| stdout.flush())
+        .context("Failed to write OSC 52 sequence")?;
+    Ok("OSC 52")
+}
+
+fn pipe_to(program: &str, args: &[&str], text: &str) -> bool {
+    let Ok(mut child) = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return false;
+    };
+    let written = child
+        .stdin
+        .take()
+        .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+    child.wait().is_ok_and(|s| s.success()) && written
+}
+
+/// Builds the OSC 52 "set clipboard" sequence, wrapped for tmux passthrough if needed.
+fn osc52_sequence(text: &str, tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence_encodes_text_and_wraps_for_tmux() {
+        assert_eq!(osc52_sequence("panic!", false), "\x1b]52;c;cGFuaWMh\x07");
+        assert_eq!(
+            osc52_sequence("panic!", true),
+            "\x1bPtmux;\x1b\x1b]52;c;cGFuaWMh\x07\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::{Context, Result};
+use kube::Client;
+use kube::config::KubeConfigOptions;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Builds a Kubernetes client for the given kubeconfig context.
+pub async fn client(context: &str) -> Result<Client> {
+    if crate::mock::enabled() {
+        return Ok(crate::mock::client(context));
+    }
+
+    let options = KubeConfigOptions {
+        context: Some(context.to_string()),
+        ..Default::default()
+    };
+    let config = kube::Config::from_kubeconfig(&options)
+        .await
+        .context("Failed to load kubeconfig")?;
+    Client::try_from(config).context("Failed to create Kubernetes client")
+}
+
+/// Workloads of the kinds davit deploys, as listed by kubectl in the given namespaces
+/// (all namespaces when empty).
+pub fn list_workloads(context: &str, namespaces: &[String]) -> Result<Vec<serde_yaml::Value>> {
+    let scopes: Vec<Vec<&str>> = if namespaces.is_empty() {
+        vec![vec!["--all-namespaces"]]
+    } else {
+        namespaces
+            .iter()
+            .map(|ns| vec!["-n", ns.as_str()])
+            .collect()
+    };
+    let mut workloads = Vec::new();
+    for scope in scopes {
+        let list = kubectl(
+            context,
+            ["get", "deployments,statefulsets", "-o", "yaml"]
+                .into_iter()
+                .chain(scope),
+            None,
+        )?;
+        let list: serde_yaml::Value =
+            serde_yaml::from_str(&list).context("Failed to parse workload list")?;
+        workloads.extend(
+            list.get("items")
+                .and_then(|items| items.as_sequence())
+                .cloned()
+                .unwrap_or_default(),
+        );
+    }
+    Ok(workloads)
+}
+
+/// Waits with `kubectl rollout status` for the workload to be rolled out, failing after
+/// `timeout`.
+pub fn rollout_status(
+    context: &str,
+    kind: &str,
+    namespace: &str,
+    name: &str,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    kubectl(
+        context,
+        [
+            "rollout",
+            "status",
+            &format!("{}/{}", kind.to_lowercase(), name),
+            "-n",
+            namespace,
+            &format!("--timeout={}s", timeout.as_secs()),
+        ],
+        None,
+    )?;
+    Ok(())
+}
+
+/// Rolls the workload back to its previous revision with `kubectl rollout undo`.
+pub fn rollout_undo(context: &str, kind: &str, namespace: &str, name: &str) -> Result<()> {
+    kubectl(
+        context,
+        [
+            "rollout",
+            "undo",
+            &format!("{}/{}", kind.to_lowercase(), name),
+            "-n",
+            namespace,
+        ],
+        None,
+    )?;
+    Ok(())
+}
+
+/// Replaces the pods of the workload with `kubectl rollout restart`, which bumps the
+/// `kubectl.kubernetes.io/restartedAt` annotation of its pod template.
+pub fn rollout_restart(context: &str, kind: &str, namespace: &str, name: &str) -> Result<()> {
+    let args = rollout_restart_args(kind, namespace, name);
+    kubectl(context, args.iter().map(String::as_str), None)?;
+    Ok(())
+}
+
+/// Arguments of the `kubectl rollout restart` of a workload, also shown by dry runs.
+pub fn rollout_restart_args(kind: &str, namespace: &str, name: &str) -> Vec<String> {
+    vec![
+        "rollout".to_string(),
+        "restart".to_string(),
+        format!("{}/{}", kind.to_lowercase(), name),
+        "-n".to_string(),
+        namespace.to_string(),
+    ]
+}
+
+/// Sets the replica count of the live workload with `kubectl scale`.
+pub fn scale(context: &str, kind: &str, namespace: &str, name: &str, replicas: i32) -> Result<()> {
+    kubectl(
+        context,
+        [
+            "scale",
+            &format!("{}/{}", kind.to_lowercase(), name),
+            "-n",
+            namespace,
+            &format!("--replicas={}", replicas),
+        ],
+        None,
+    )?;
+    Ok(())
+}
+
+/// The live object as YAML, without server-populated fields. `metadata.resourceVersion`
+/// is kept so that replacing it fails if the object changed since it was read.
+pub fn live_manifest(context: &str, kind: &str, namespace: &str, name: &str) -> Result<String> {
+    let object = kubectl(
+        context,
+        [
+            "get",
+            &format!("{}/{}", kind.to_lowercase(), name),
+            "-n",
+            namespace,
+            "-o",
+            "yaml",
+        ],
+        None,
+    )?;
+    let object: serde_yaml::Value =
+        serde_yaml::from_str(&object).context("Failed to parse the live object")?;
+    Ok(serde_yaml::to_string(&strip_server_fields(object))?)
+}
+
+/// Replaces the live object with `manifest`, as a server-side dry-run when `dry_run`.
+pub fn replace(context: &str, manifest: &str, dry_run: bool) -> Result<String> {
+    let mut args = vec!["replace", "-f", "-"];
+    if dry_run {
+        args.push("--dry-run=server");
+    }
+    kubectl(context, args, Some(manifest))
+}
+
+/// Changes the API server would make to the live objects when applying `manifest`, as
+/// reported by `kubectl diff` from a server-side dry-run: defaulted fields and admission
+/// webhook mutations included. `None` when nothing would change.
+pub fn server_diff(context: &str, manifest: &str) -> Result<Option<String>> {
+    let mut child = Command::new("kubectl")
+        .args(["--context", context, "diff", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute kubectl")?;
+    if let Some(mut pipe) = child.stdin.take() {
+        pipe.write_all(manifest.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    diff_outcome(output.status.code(), &output.stdout, &output.stderr)
+}
+
+/// kubectl diff exits with 1 when there are differences and above on errors.
+fn diff_outcome(code: Option<i32>, stdout: &[u8], stderr: &[u8]) -> Result<Option<String>> {
+    match code {
+        Some(0) => Ok(None),
+        Some(1) => Ok(Some(String::from_utf8_lossy(stdout).into_owned())),
+        _ => Err(anyhow::anyhow!(
+            "kubectl diff failed: {}",
+            String::from_utf8_lossy(stderr).trim()
+        )),
+    }
+}
+
+pub fn strip_server_fields(mut object: serde_yaml::Value) -> serde_yaml::Value {
+    if let Some(map) = object.as_mapping_mut() {
+        map.remove("status");
+    }
+    if let Some(metadata) = object.get_mut("metadata").and_then(|m| m.as_mapping_mut()) {
+        for field in [
+            "managedFields",
+            "uid",
+            "creationTimestamp",
+            "generation",
+            "selfLink",
+        ] {
+            metadata.remove(field);
+        }
+        if let Some(annotations) = metadata
+            .get_mut("annotations")
+            .and_then(|a| a.as_mapping_mut())
+        {
+            annotations.remove("deployment.kubernetes.io/revision");
+            annotations.remove("kubectl.kubernetes.io/last-applied-configuration");
+            if annotations.is_empty() {
+                metadata.remove("annotations");
+            }
+        }
+    }
+    object
+}
+
+fn kubectl<'a>(
+    context: &str,
+    args: impl IntoIterator<Item = &'a str>,
+    stdin: Option<&str>,
+) -> Result<String> {
+    let args: Vec<&str> = args.into_iter().collect();
+    let mut child = Command::new("kubectl")
+        .args(["--context", context])
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute kubectl")?;
+    if let Some(mut pipe) = child.stdin.take() {
+        pipe.write_all(stdin.unwrap_or_default().as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "kubectl {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollout_restart_args_name_the_workload_in_its_namespace() {
+        assert_eq!(
+            rollout_restart_args("StatefulSet", "identity", "auth-api"),
+            vec![
+                "rollout",
+                "restart",
+                "statefulset/auth-api",
+                "-n",
+                "identity"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_outcome_maps_kubectl_diff_exit_codes() {
+        assert_eq!(diff_outcome(Some(0), b"", b"").unwrap(), None);
+        assert_eq!(
+            diff_outcome(Some(1), b"-  replicas: 2\n+  replicas: 3\n", b"").unwrap(),
+            Some("-  replicas: 2\n+  replicas: 3\n".to_string())
+        );
+        let error = diff_outcome(Some(2), b"", b"error: forbidden\n").unwrap_err();
+        assert_eq!(error.to_string(), "kubectl diff failed: error: forbidden");
+        assert!(diff_outcome(None, b"", b"").is_err());
+    }
+
+    #[test]
+    fn test_strip_server_fields_keeps_spec_and_resource_version() {
+        let live: serde_yaml::Value = serde_yaml::from_str(
+            r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: auth-api
+  namespace: identity
+  uid: 0f1e
+  resourceVersion: "48213"
+  generation: 12
+  creationTimestamp: "2026-01-10T08:00:00Z"
+  annotations:
+    deployment.kubernetes.io/revision: "12"
+  managedFields: []
+spec:
+  replicas: 2
+status:
+  readyReplicas: 2
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_yaml::to_string(&strip_server_fields(live)).unwrap(),
+            r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: auth-api
+  namespace: identity
+  resourceVersion: '48213'
+spec:
+  replicas: 2
+"#
+        );
+    }
+}
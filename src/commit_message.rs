@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use inquire::Text;
+use inquire::validator::Validation;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::interactive;
+
+/// Placeholders of a commit message template.
+pub const PLACEHOLDERS: [&str; 7] = [
+    "env", "service", "old_tag", "new_tag", "digest", "user", "ticket",
+];
+
+/// How deploy commits to an environment are written.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct CommitSettings {
+    /// Subject of deploy commits, e.g. `deploy({env}): {service} {old_tag} → {new_tag}`;
+    /// `deploy({env}): update {service} to {new_tag}` by default.
+    pub message: Option<String>,
+    /// Trailer key of a ticket ID asked for at each deploy (or given with `--ticket`),
+    /// e.g. `Jira` for a `Jira: PROJ-123` trailer; the ID also fills `{ticket}`.
+    pub ticket: Option<String>,
+    /// Ask for extra trailer lines (`Key: value`) appended to the commit body.
+    #[serde(default)]
+    pub prompt_trailers: bool,
+}
+
+fn placeholder_pattern() -> Regex {
+    Regex::new(r"\{([a-z_]+)\}").unwrap()
+}
+
+/// Fails on placeholders of the template that are not in [`PLACEHOLDERS`].
+pub fn validate(template: &str) -> Result<()> {
+    match placeholder_pattern()
+        .captures_iter(template)
+        .map(|c| c[1].to_string())
+        .find(|name| !PLACEHOLDERS.contains(&name.as_str()))
+    {
+        Some(unknown) => Err(anyhow::anyhow!(
+            "Unknown commit message placeholder {{{}}}; use {}",
+            unknown,
+            PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Fills the placeholders of the template; those without a value become empty.
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    placeholder_pattern()
+        .replace_all(template, |c: &regex::Captures| {
+            values
+                .iter()
+                .find(|(name, _)| *name == &c[1])
+                .map(|(_, value)| value.to_string())
+                .unwrap_or_default()
+        })
+        .trim()
+        .to_string()
+}
+
+fn reject_trailer(line: &str) -> Option<&'static str> {
+    let valid = line.split_once(':').is_some_and(|(key, value)| {
+        !key.is_empty()
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !value.trim().is_empty()
+    });
+    (!valid).then_some("Expected a trailer such as 'Refs: PROJ-123'")
+}
+
+/// The ticket ID and extra trailer lines of a deploy commit: the ticket given with
+/// `--ticket`, else asked for, and the trailers typed one per prompt. Non-interactive
+/// runs only use `--ticket`.
+pub fn collect(
+    settings: Option<&CommitSettings>,
+    ticket: Option<&str>,
+) -> Result<(Option<String>, Vec<String>)> {
+    let Some(settings) = settings else {
+        return Ok((ticket.map(str::to_string), Vec::new()));
+    };
+    let mut trailers = Vec::new();
+    let ticket = match (ticket, &settings.ticket) {
+        (Some(ticket), _) => Some(ticket.trim().to_string()),
+        (None, Some(key)) if interactive::enabled() => Some(
+            Text::new(&format!("{} ticket (empty for none):", key))
+                .prompt()
+                .context("Ticket entry was cancelled")?
+                .trim()
+                .to_string(),
+        ),
+        (None, _) => None,
+    }
+    .filter(|t| !t.is_empty());
+    if let (Some(key), Some(ticket)) = (&settings.ticket, &ticket) {
+        trailers.push(format!("{}: {}", key, ticket));
+    }
+    while settings.prompt_trailers && interactive::enabled() {
+        let line = Text::new("Extra commit trailer (e.g. Refs: PROJ-123), empty to finish:")
+            .with_validator(|input: &str| {
+                Ok(match reject_trailer(input.trim()) {
+                    Some(reason) if !input.trim().is_empty() => Validation::Invalid(reason.into()),
+                    _ => Validation::Valid,
+                })
+            })
+            .prompt()
+            .context("Trailer entry was cancelled")?;
+        if line.trim().is_empty() {
+            break;
+        }
+        trailers.push(line.trim().to_string());
+    }
+    Ok((ticket, trailers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_fill_known_placeholders() {
+        let template = "deploy({env}): {service} {old_tag} → {new_tag} {ticket}";
+        assert!(validate(template).is_ok());
+        assert!(validate("deploy({environment})").is_err());
+        assert_eq!(
+            render(
+                template,
+                &[
+                    ("env", "production"),
+                    ("service", "auth-api"),
+                    ("old_tag", "v1"),
+                    ("new_tag", "v2"),
+                ]
+            ),
+            "deploy(production): auth-api v1 → v2"
+        );
+        assert!(reject_trailer("Refs: PROJ-123").is_none());
+        assert!(reject_trailer("Refs PROJ-123").is_some());
+        assert!(reject_trailer("Two words: x").is_some());
+    }
+}
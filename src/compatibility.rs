@@ -0,0 +1,204 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::blueprint::split_image_ref;
+use crate::config::Environment;
+use crate::{cluster, info, mock};
+
+/// Services whose versions must stay aligned, e.g. an API and the worker consuming
+/// its queue, checked whenever one of them is deployed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CompatibilityRule {
+    pub services: Vec<String>,
+    /// Version components that must be equal.
+    #[serde(rename = "match", default)]
+    pub level: VersionMatch,
+    /// Abort deploys violating the rule instead of warning.
+    #[serde(default)]
+    pub block: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionMatch {
+    Major,
+    #[default]
+    Minor,
+    Patch,
+    /// The whole tag, for versions that are not semantic.
+    Exact,
+}
+
+impl VersionMatch {
+    /// Whether both tags agree on the compared components; `None` when one of them is not
+    /// a semantic version.
+    pub fn compatible(self, lhs: &str, rhs: &str) -> Option<bool> {
+        let components = match self {
+            VersionMatch::Exact => return Some(lhs == rhs),
+            VersionMatch::Major => 1,
+            VersionMatch::Minor => 2,
+            VersionMatch::Patch => 3,
+        };
+        let (lhs, rhs) = (semver(lhs)?, semver(rhs)?);
+        Some(lhs[..components] == rhs[..components])
+    }
+}
+
+impl fmt::Display for VersionMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionMatch::Major => write!(f, "major version"),
+            VersionMatch::Minor => write!(f, "minor version"),
+            VersionMatch::Patch => write!(f, "patch version"),
+            VersionMatch::Exact => write!(f, "tag"),
+        }
+    }
+}
+
+/// Major, minor and patch of tags like `v1.4.2` or `1.4.2-rc.1`.
+fn semver(tag: &str) -> Option<[u64; 3]> {
+    let version = tag.strip_prefix('v').unwrap_or(tag);
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some([major, minor, patch])
+}
+
+/// A rule the deploy would break.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub service: String,
+    pub tag: String,
+    pub other: String,
+    pub other_tag: String,
+    pub level: VersionMatch,
+    pub block: bool,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} and {} {} (deployed) must share the {}",
+            self.service, self.tag, self.other, self.other_tag, self.level
+        )
+    }
+}
+
+/// Rules involving `service` that deploying `tag` would break, given the tags deployed
+/// for the other services. Tags that cannot be compared are reported as violations of
+/// the whole-tag rule so that they are not silently accepted.
+pub fn violations(
+    rules: &[CompatibilityRule],
+    service: &str,
+    tag: &str,
+    deployed: &BTreeMap<String, String>,
+) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for rule in rules
+        .iter()
+        .filter(|r| r.services.iter().any(|s| s == service))
+    {
+        for other in rule.services.iter().filter(|s| *s != service) {
+            let Some(other_tag) = deployed.get(other) else {
+                continue;
+            };
+            let level = match rule.level.compatible(tag, other_tag) {
+                Some(true) => continue,
+                Some(false) => rule.level,
+                None if tag == other_tag => continue,
+                None => VersionMatch::Exact,
+            };
+            violations.push(Violation {
+                service: service.to_string(),
+                tag: tag.to_string(),
+                other: other.clone(),
+                other_tag: other_tag.clone(),
+                level,
+                block: rule.block,
+            });
+        }
+    }
+    violations
+}
+
+/// Tags currently deployed for the other services of the rules involving `service`:
+/// what runs in the cluster, or the manifest tag when it cannot be read.
+pub async fn deployed_tags(
+    rules: &[CompatibilityRule],
+    env: &Environment,
+    service: &str,
+) -> Result<BTreeMap<String, String>> {
+    let others: Vec<&String> = rules
+        .iter()
+        .filter(|r| r.services.iter().any(|s| s == service))
+        .flat_map(|r| r.services.iter())
+        .filter(|s| *s != service)
+        .collect();
+    if others.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+
+    let services = env.list_services()?;
+    let client = if mock::enabled() {
+        None
+    } else {
+        cluster::client(&env.kubectl_context).await.ok()
+    };
+    let mut tags = BTreeMap::new();
+    for source in services.iter().filter(|s| others.contains(&&s.name)) {
+        let running = match &client {
+            Some(client) => info::running_tag(client, source).await,
+            None => None,
+        };
+        if let Some(tag) =
+            running.or_else(|| split_image_ref(&source.image_path).1.map(str::to_string))
+        {
+            tags.insert(source.name.clone(), tag);
+        }
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_violations_compare_configured_components() {
+        let rules = vec![
+            CompatibilityRule {
+                services: vec!["payments-api".to_string(), "payments-worker".to_string()],
+                level: VersionMatch::Minor,
+                block: true,
+            },
+            CompatibilityRule {
+                services: vec!["payments-api".to_string(), "ledger".to_string()],
+                level: VersionMatch::Major,
+                block: false,
+            },
+        ];
+        let deployed = BTreeMap::from([
+            ("payments-worker".to_string(), "v2.3.7".to_string()),
+            ("ledger".to_string(), "sha-4f2a91c".to_string()),
+        ]);
+
+        assert_eq!(
+            violations(&rules, "payments-api", "v2.3.0-rc.1", &deployed).len(),
+            1
+        );
+        let found = violations(&rules, "payments-api", "v2.4.0", &deployed);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[0].to_string(),
+            "payments-api v2.4.0 and payments-worker v2.3.7 (deployed) must share the minor version"
+        );
+        assert!(found[0].block);
+        assert_eq!(found[1].level, VersionMatch::Exact);
+        assert!(violations(&rules, "billing", "v9.0.0", &deployed).is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Powershell, Zsh};
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::io;
+
+use crate::config::{Config, Environment};
+use crate::mock;
+
+/// Environment variable through which the shell asks davit for completions.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+    Powershell,
+}
+
+impl Shell {
+    fn completer(self) -> &'static dyn EnvCompleter {
+        match self {
+            Shell::Bash => &Bash,
+            Shell::Zsh => &Zsh,
+            Shell::Fish => &Fish,
+            Shell::Elvish => &Elvish,
+            Shell::Powershell => &Powershell,
+        }
+    }
+}
+
+/// Prints the script registering davit's completions in `shell`. The script calls back
+/// into the davit binary on every completion, so environment and service names come
+/// from the current config.
+pub fn write_registration(shell: Shell) -> Result<()> {
+    let binary = std::env::current_exe().context("Failed to locate the davit binary")?;
+    shell.completer().write_registration(
+        COMPLETE_VAR,
+        "davit",
+        "davit",
+        &binary.to_string_lossy(),
+        &mut io::stdout(),
+    )?;
+    Ok(())
+}
+
+/// Config for completions, which must stay silent when it does not load.
+fn config() -> Option<Config> {
+    if mock::enabled() {
+        mock::config().ok()
+    } else {
+        Config::load().ok()
+    }
+}
+
+/// Names of the configured environments.
+pub fn environments() -> Vec<CompletionCandidate> {
+    config()
+        .map(|config| {
+            config
+                .environments
+                .into_iter()
+                .map(|env| CompletionCandidate::new(env.name))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Services of the environment given with `--env` on the command line being completed,
+/// else of every environment discovering services from local YAML. Environments listing
+/// Helm releases or cluster workloads are skipped: completion must not wait on a cluster.
+pub fn services() -> Vec<CompletionCandidate> {
+    let Some(config) = config() else {
+        return Vec::new();
+    };
+    let wanted = env_argument(std::env::args_os());
+    let names: BTreeSet<String> = config
+        .environments
+        .iter()
+        .filter(|env| wanted.as_ref().is_none_or(|name| &env.name == name))
+        .filter(|env| lists_local_services(env))
+        .filter_map(|env| env.list_services().ok())
+        .flatten()
+        .map(|service| service.name)
+        .collect();
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+fn lists_local_services(env: &Environment) -> bool {
+    env.helm.is_none() && env.cluster_discovery.is_none()
+}
+
+/// Value of `--env`/`-e` among the words of the command line being completed.
+fn env_argument(args: impl IntoIterator<Item = OsString>) -> Option<String> {
+    let words: Vec<String> = args
+        .into_iter()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+    words.iter().enumerate().rev().find_map(|(i, word)| {
+        let value = if word == "--env" || word == "-e" {
+            words.get(i + 1).cloned()
+        } else if let Some(value) = word.strip_prefix("--env=") {
+            Some(value.to_string())
+        } else {
+            word.strip_prefix("-e")
+                .filter(|v| !v.is_empty() && !word.starts_with("--"))
+                .map(str::to_string)
+        };
+        value.filter(|v| !v.is_empty())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<OsString> {
+        line.split(' ').map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_env_argument_reads_every_spelling() {
+        assert_eq!(
+            env_argument(words("davit -- davit deploy --env staging -s ")),
+            Some("staging".to_string())
+        );
+        assert_eq!(
+            env_argument(words("davit -- davit tags --env=prod --service ")),
+            Some("prod".to_string())
+        );
+        assert_eq!(
+            env_argument(words("davit -- davit logs -eprod -s au")),
+            Some("prod".to_string())
+        );
+        assert_eq!(env_argument(words("davit -- davit deploy -s ")), None);
+        assert_eq!(env_argument(words("davit -- davit deploy -e ")), None);
+    }
+}
@@ -1,25 +1,628 @@
+use crate::blueprint::{
+    Blueprint, find_kustomization, find_pod_spec, parse_document, split_image_ref,
+};
+use crate::cluster;
+use crate::commit_message::{self, CommitSettings};
+use crate::compatibility::CompatibilityRule;
+use crate::contexts::{self, ContextAlias};
+use crate::discovery_cache::{self, DiscoveryCache};
+use crate::freeze::FreezeWindow;
+use crate::helm::{self, HelmChart, HelmRelease};
+use crate::history;
+use crate::keymap::DashboardKeys;
+use crate::lint::Severity;
+use crate::protection::ProtectedServices;
+use crate::resources::{parse_cpu, parse_memory};
+use crate::signing::CommitSigning;
+use crate::theme::ThemeName;
 use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
 use directories::ProjectDirs;
-use serde::Deserialize;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use walkdir::WalkDir;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub environments: Vec<Environment>,
+    #[serde(default)]
+    pub services: BTreeMap<String, ServiceConfig>,
+    pub pricing: Option<Pricing>,
+    #[serde(default)]
+    pub dashboard: DashboardLayout,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Version alignment rules between services, checked at deploy time.
+    #[serde(default)]
+    pub compatibility: Vec<CompatibilityRule>,
+    /// Services needing a typed confirmation, and possibly approvals, to be deployed.
+    #[serde(default)]
+    pub protected_services: Vec<ProtectedServices>,
+    /// Cluster aliases usable as `kubectl_context`, keyed by alias.
+    #[serde(default)]
+    pub contexts: BTreeMap<String, ContextAlias>,
+    #[serde(default)]
+    pub defaults: Defaults,
+    /// JSON-lines audit log of deploys; `audit.jsonl` in the data directory by default.
+    pub audit_log: Option<PathBuf>,
+    /// Signing of the commits davit makes.
+    #[serde(default)]
+    pub signing: CommitSigning,
+    /// Annotations written on the workload by each deploy.
+    #[serde(default)]
+    pub annotations: DeployAnnotations,
+    /// Fields of structured log lines, for services without their own.
+    #[serde(default)]
+    pub logs: LogFields,
+}
+
+/// Annotations set in the workload manifest with each new tag, so that
+/// `kubectl rollout history` shows who deployed what. Unset keys are not written.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeployAnnotations {
+    /// Key of a summary of the deploy, usually `kubernetes.io/change-cause`.
+    pub change_cause: Option<String>,
+    /// Key of the user who deployed, e.g. `davit.dev/deployed-by`.
+    pub deployed_by: Option<String>,
+    /// Key of the tag replaced, e.g. `davit.dev/previous-tag`.
+    pub previous_tag: Option<String>,
+}
+
+impl DeployAnnotations {
+    /// The annotations of a deploy of `service` from `old_tag` to `new_tag` by `user`.
+    pub fn values(
+        &self,
+        service: &str,
+        old_tag: Option<&str>,
+        new_tag: &str,
+        user: &str,
+    ) -> Vec<(String, String)> {
+        let change_cause = match old_tag {
+            Some(old_tag) => format!(
+                "davit deploy {} {} → {} by {}",
+                service, old_tag, new_tag, user
+            ),
+            None => format!("davit deploy {} {} by {}", service, new_tag, user),
+        };
+        [
+            (&self.change_cause, Some(change_cause)),
+            (&self.deployed_by, Some(user.to_string())),
+            (&self.previous_tag, old_tag.map(str::to_string)),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.clone()?, value?)))
+        .collect()
+    }
+}
+
+/// Defaults of command-line behavior.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Defaults {
+    /// `false` makes every run behave as with `--non-interactive`.
+    pub interactive: bool,
+    /// Colors of the dashboard and diffs, unless `--theme` is given.
+    pub theme: ThemeName,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self {
+            interactive: true,
+            theme: ThemeName::default(),
+        }
+    }
+}
+
+/// Retries of transient failures (timeouts, 5xx, rejected pushes) of registry, cluster
+/// and Git calls, with exponential backoff.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one.
+    pub attempts: u32,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_delay_ms: 500,
+            max_delay_ms: 8000,
+        }
+    }
+}
+
+/// Rollout dashboard pane sizes, adjusted with hotkeys and saved back on exit.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DashboardLayout {
+    /// Height of the pod list in rows; sized to the pod count when unset.
+    pub pod_panel_height: Option<u16>,
+    /// Width share of the old pod logs pane, in percent.
+    #[serde(default = "default_old_log_percent")]
+    pub old_log_percent: u16,
+    /// Log lines kept per pane for scrolling back.
+    #[serde(default = "default_log_scrollback")]
+    pub log_scrollback: usize,
+    /// Show the per-pod CPU and memory usage pane (toggled with `r`).
+    #[serde(default)]
+    pub show_resources: bool,
+    /// Keys of the dashboard actions, for vim- or less-style navigation.
+    #[serde(default)]
+    pub keys: DashboardKeys,
+}
+
+impl Default for DashboardLayout {
+    fn default() -> Self {
+        Self {
+            pod_panel_height: None,
+            old_log_percent: default_old_log_percent(),
+            log_scrollback: default_log_scrollback(),
+            show_resources: false,
+            keys: DashboardKeys::default(),
+        }
+    }
+}
+
+fn default_old_log_percent() -> u16 {
+    50
+}
+
+fn default_log_scrollback() -> usize {
+    5000
+}
+
+/// Resource rules a workload must satisfy before being applied to an environment.
+/// Violations block protected environments and only warn elsewhere.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ResourcePolicy {
+    pub max_replicas: Option<i64>,
+    /// Every container must set both CPU and memory limits.
+    #[serde(default)]
+    pub require_limits: bool,
+    /// Highest CPU limit allowed per container, as a Kubernetes quantity (e.g. `2`, `1500m`).
+    pub max_cpu_limit: Option<String>,
+    /// Highest memory limit allowed per container, as a Kubernetes quantity (e.g. `4Gi`).
+    pub max_memory_limit: Option<String>,
+}
+
+/// Approximate monthly prices used to estimate the cost of resource request changes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Pricing {
+    pub cpu_core_month: f64,
+    pub memory_gib_month: f64,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+/// Environment-independent settings keyed by service name.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ServiceConfig {
+    /// Local checkout of the service source code, used to build release notes.
+    pub source_repo: Option<PathBuf>,
+    /// HTTP endpoint probed by the rollout dashboard.
+    pub health: Option<HealthCheck>,
+    /// PromQL queries charted by the rollout dashboard.
+    #[serde(default)]
+    pub metrics: Vec<MetricQuery>,
+    /// Pull the new image onto the workload's nodes before every apply, as with `--prepull`.
+    #[serde(default)]
+    pub prepull: bool,
+    /// Canary deploys shifting traffic in steps, with `davit deploy --progressive`.
+    pub progressive: Option<ProgressiveDelivery>,
+    /// Other images released together with the service (migrations, cron jobs): deploys
+    /// retag them along with it, in a single reviewed change.
+    #[serde(default)]
+    pub version_group: Vec<GroupedImage>,
+    /// Environments the service is promoted through with `davit promote`, in order.
+    #[serde(default)]
+    pub promotion: Vec<PromotionStage>,
+    /// Commands run around the service's deploys, after those of the environment.
+    #[serde(default)]
+    pub hooks: DeployHooks,
+    /// Fields of the service's structured log lines, over the global `[logs]` ones.
+    #[serde(default)]
+    pub logs: LogFields,
+}
+
+/// Fields holding the level, message and timestamp of JSON or logfmt log lines, the
+/// first one present winning; nested JSON fields are written `fields.message`. Unset
+/// lists fall back to the global ones, then to the usual names.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogFields {
+    pub level_fields: Option<Vec<String>>,
+    pub message_fields: Option<Vec<String>>,
+    pub timestamp_fields: Option<Vec<String>>,
+    /// Regex of lines continuing the previous entry, on top of the stack trace lines
+    /// recognized already.
+    pub continuation_pattern: Option<String>,
+}
+
+impl LogFields {
+    /// These fields, completed with those of `fallback`.
+    pub fn or(&self, fallback: &LogFields) -> LogFields {
+        LogFields {
+            level_fields: self
+                .level_fields
+                .clone()
+                .or_else(|| fallback.level_fields.clone()),
+            message_fields: self
+                .message_fields
+                .clone()
+                .or_else(|| fallback.message_fields.clone()),
+            timestamp_fields: self
+                .timestamp_fields
+                .clone()
+                .or_else(|| fallback.timestamp_fields.clone()),
+            continuation_pattern: self
+                .continuation_pattern
+                .clone()
+                .or_else(|| fallback.continuation_pattern.clone()),
+        }
+    }
+
+    fn validate(&self, scope: &str) -> Result<()> {
+        if let Some(pattern) = &self.continuation_pattern {
+            regex::Regex::new(pattern).with_context(|| {
+                format!(
+                    "Invalid continuation_pattern in {} logs: {}",
+                    scope, pattern
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Shell commands run at each stage of a deploy, with `DAVIT_ENV`, `DAVIT_SERVICE`,
+/// `DAVIT_TAG`, `DAVIT_PREVIOUS_TAG`, `DAVIT_NAMESPACE` and `DAVIT_CONTEXT` set.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct DeployHooks {
+    /// Before the YAML is modified; a failure aborts the deploy.
+    #[serde(default)]
+    pub pre_deploy: Vec<String>,
+    /// After `kubectl apply` (or `helm upgrade`).
+    #[serde(default)]
+    pub post_apply: Vec<String>,
+    /// After a successful rollout, e.g. smoke tests.
+    #[serde(default)]
+    pub on_success: Vec<String>,
+    /// After a failed apply or rollout.
+    #[serde(default)]
+    pub on_failure: Vec<String>,
+}
+
+/// Stage of a promotion pipeline and the gates guarding its entry. The gates look at
+/// the previous stage, whose tag gets promoted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PromotionStage {
+    pub env: String,
+    /// Minimum time the tag must have been deployed in the previous stage, e.g. `24h`.
+    pub soak: Option<String>,
+    /// Require the service's health check to pass in the previous stage.
+    #[serde(default)]
+    pub smoke: bool,
+    /// Users (Git email) allowed to promote into the stage; anyone when empty.
+    #[serde(default)]
+    pub approvers: Vec<String>,
+}
+
+impl PromotionStage {
+    pub fn has_gates(&self) -> bool {
+        self.soak.is_some() || self.smoke || !self.approvers.is_empty()
+    }
+}
+
+/// Image of a version group, retagged wherever the service's manifests reference it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GroupedImage {
+    /// Image path without tag.
+    pub image: String,
+    /// Tag of the image, `{tag}` standing for the one selected for the service.
+    #[serde(default = "default_grouped_tag")]
+    pub tag: String,
+}
+
+impl GroupedImage {
+    pub fn tag_for(&self, tag: &str) -> String {
+        self.tag.replace("{tag}", tag)
+    }
+}
+
+fn default_grouped_tag() -> String {
+    "{tag}".to_string()
+}
+
+/// Progressive delivery: the new version runs as a canary next to the stable workload
+/// and receives an increasing share of the traffic, one step at a time from the dashboard.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProgressiveDelivery {
+    /// Percentages of the traffic sent to the canary, ending with 100.
+    #[serde(default = "default_progressive_steps")]
+    pub steps: Vec<u8>,
+    /// Pod label telling the versions apart, as in the Istio conventions.
+    #[serde(default = "default_version_label")]
+    pub version_label: String,
+    pub istio: Option<IstioRouting>,
+    pub gateway: Option<GatewayRouting>,
+}
+
+/// Istio resources routing the service: the DestinationRule gets a `stable` and a
+/// `canary` subset, the VirtualService weights between them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IstioRouting {
+    pub virtual_service: String,
+    /// Defaults to the VirtualService name.
+    pub destination_rule: Option<String>,
+}
+
+impl IstioRouting {
+    pub fn destination_rule(&self) -> &str {
+        self.destination_rule
+            .as_deref()
+            .unwrap_or(&self.virtual_service)
+    }
+}
+
+/// Gateway API route weighting a `<service>-stable` and a `<service>-canary` Service,
+/// created next to the backend Service and selecting the pods by version.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GatewayRouting {
+    pub http_route: String,
+    /// Backend Service the route points at; defaults to the workload name.
+    pub service: Option<String>,
+}
+
+fn default_progressive_steps() -> Vec<u8> {
+    vec![10, 50, 100]
+}
+
+fn default_version_label() -> String {
+    "version".to_string()
+}
+
+/// PromQL query evaluated against the environment's Prometheus during a rollout.
+/// A value outside `min`/`max` marks the deploy as failing.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MetricQuery {
+    pub name: String,
+    pub query: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl MetricQuery {
+    pub fn is_breached(&self, value: f64) -> bool {
+        self.min.is_some_and(|min| value < min) || self.max.is_some_and(|max| value > max)
+    }
+}
+
+/// Health endpoint hit periodically during a rollout, either through an external `url`
+/// or through a transient port-forward to a new pod on `port`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthCheck {
+    pub url: Option<String>,
+    #[serde(default = "default_health_path")]
+    pub path: String,
+    pub port: Option<u16>,
+    #[serde(default = "default_health_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_health_path() -> String {
+    "/".to_string()
+}
+
+fn default_health_interval_secs() -> u64 {
+    5
+}
+
+/// Discovery of services from the Helm releases installed in the cluster, for teams
+/// that never commit rendered manifests. Deploys run `helm upgrade` with the new tag.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct HelmDiscovery {
+    /// Values key receiving the tag, e.g. `image.tag`.
+    #[serde(default = "default_helm_tag_value")]
+    pub tag_value: String,
+    /// Chart repository the releases were installed from (`repo` alias or `oci://` URL).
+    /// Releases are upgraded with the chart version they already run.
+    pub chart_repo: Option<String>,
+    /// Chart references per release name, overriding `chart_repo`.
+    #[serde(default)]
+    pub charts: BTreeMap<String, String>,
+    /// Namespaces to list releases from; all namespaces when empty.
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+}
+
+impl Default for HelmDiscovery {
+    fn default() -> Self {
+        Self {
+            tag_value: default_helm_tag_value(),
+            chart_repo: None,
+            charts: BTreeMap::new(),
+            namespaces: Vec::new(),
+        }
+    }
+}
+
+fn default_helm_tag_value() -> String {
+    "image.tag".to_string()
+}
+
+/// A service deployed with `helm upgrade --install` from a chart, with its tag in a
+/// values file of the manifests repository.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct HelmChartService {
+    /// Chart directory relative to `env_yaml_dir`, or a reference (`oci://...`, `repo/chart`).
+    pub chart: String,
+    /// Values file holding the tag, relative to `env_yaml_dir`.
+    pub values: PathBuf,
+    /// Release name; the service name by default.
+    pub release: Option<String>,
+    pub namespace: Option<String>,
+    /// Values key receiving the tag, e.g. `image.tag`.
+    #[serde(default = "default_helm_tag_value")]
+    pub tag_value: String,
+}
+
+/// Why a service must not be deployed to an environment, e.g. during an incident freeze.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ServiceLock {
+    pub reason: String,
+    pub by: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Discovery of services from the Deployments and StatefulSets running in the cluster,
+/// for environments whose manifests repository lags or does not exist. Deploys replace
+/// the live object after reviewing the diff against it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct ClusterDiscovery {
+    /// Namespaces to list workloads from; all namespaces when empty.
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+}
+
+/// GKE cluster of an environment, used to create its kubeconfig context with
+/// `gcloud container clusters get-credentials` when it is missing. The project is the
+/// environment's `gcp_project`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GkeCluster {
+    pub name: String,
+    /// Region or zone of the cluster, e.g. `europe-west1`.
+    pub location: String,
+}
+
+/// Deploys from a network without registry access: candidate tags come from a local file
+/// and apply can go through a wrapper, e.g. kubectl on a bastion.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OfflineMode {
+    /// `davit registry export` JSON, or a mirror manifest listing one image reference
+    /// (`repo:tag`, `repo:tag@sha256:...`) per line.
+    pub images: PathBuf,
+    /// Command run instead of `kubectl` to apply, receiving the manifests on stdin.
+    #[serde(default)]
+    pub kubectl_wrapper: Vec<String>,
+}
+
+const DEFAULT_IMAGE_REGISTRIES: [&str; 3] = ["gcr.io", "pkg.dev", "amazonaws.com"];
+
+/// Whether an image comes from a registry pattern such as `gcr.io`,
+/// `europe-west1-docker.pkg.dev/my-proj` or `registry.internal.corp`.
+fn image_from_registry(image: &str, registry: &str) -> bool {
+    let registry = registry.trim_end_matches('/');
+    let (registry_host, registry_path) = registry.split_once('/').unwrap_or((registry, ""));
+    let Some((host, path)) = image.split_once('/') else {
+        return false;
+    };
+
+    let host_matches = host == registry_host || host.ends_with(&format!(".{}", registry_host));
+    let path_matches = registry_path.is_empty()
+        || path
+            .strip_prefix(registry_path)
+            .is_some_and(|rest| rest.starts_with('/'));
+    host_matches && path_matches
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Environment {
     pub name: String,
+    /// Manifests repository; not needed when services come from Helm releases.
+    #[serde(default)]
     pub env_yaml_dir: PathBuf,
     #[serde(default)]
     pub env_yaml_dir_extra: BTreeMap<String, PathBuf>,
+    /// kubeconfig context, or an alias from `[contexts]` resolved to the local context
+    /// pointing at the alias' cluster.
     pub kubectl_context: String,
+    /// The `[contexts]` alias `kubectl_context` was resolved from.
+    #[serde(skip)]
+    pub context_alias: Option<String>,
     pub gcp_project: Option<String>,
+    /// Cluster whose credentials are fetched when `kubectl_context` is missing locally.
+    pub gke_cluster: Option<GkeCluster>,
     pub protected: Option<bool>,
+    /// Sandbox environment: apply is always a server-side dry-run and deploy commits
+    /// go to a local scratch branch.
+    pub dry_only: Option<bool>,
+    /// Shell command rendering templated YAML (Helm/Go templates) to stdout.
+    /// `{file}` is replaced with the template path.
+    pub template_render_command: Option<String>,
+    /// GCP service account of the cluster nodes, used to verify cross-project image pulls.
+    pub node_service_account: Option<String>,
+    /// Registries whose images make a workload a deployable service, as a host
+    /// (subdomains included) optionally followed by a path prefix. Defaults to
+    /// Container Registry, Artifact Registry and ECR.
+    #[serde(default)]
+    pub image_registries: Vec<String>,
+    /// Lists services from the cluster's Helm releases instead of `env_yaml_dir`.
+    pub helm: Option<HelmDiscovery>,
+    /// Lists services from the cluster's workloads instead of `env_yaml_dir`.
+    pub cluster_discovery: Option<ClusterDiscovery>,
+    /// Services deployed from a chart and a values file of `env_yaml_dir`, keyed by name.
+    #[serde(default)]
+    pub helm_charts: BTreeMap<String, HelmChartService>,
+    /// Services whose deploys are blocked, keyed by service name.
+    #[serde(default)]
+    pub locks: BTreeMap<String, ServiceLock>,
+    /// Periods in local time during which deploys are refused without
+    /// `--override-freeze`, e.g. `Fri 16:00 - Mon 08:00` or `0 16 * * 5 for 64h`.
+    #[serde(default)]
+    pub freeze_windows: Vec<String>,
+    /// Resource rules checked against the manifest before apply.
+    pub policy: Option<ResourcePolicy>,
+    /// Lint severity (`info`, `warning`, `error`) from which findings block the deploy.
+    pub lint_fail_on: Option<Severity>,
+    /// Directory of Rego policies evaluated with conftest against the manifest before apply.
+    pub rego_policy_dir: Option<PathBuf>,
+    /// Policy bundle URL downloaded by conftest (`--update`) before evaluation.
+    pub rego_policy_bundle: Option<String>,
+    /// Prometheus base URL queried for the services' `metrics` during rollouts.
+    pub prometheus_url: Option<String>,
+    /// OAuth access token for registry calls, either literal or a `vault:<path>#<key>` reference.
+    pub registry_access_token: Option<String>,
+    /// Webhook (Slack-compatible `{"text": ...}`) notified of scheduled deploys and
+    /// countdowns, either literal or a `vault:<path>#<key>` reference.
+    pub notify_webhook: Option<String>,
+    /// Seconds between the final confirmation and the apply, announced to the team and
+    /// cancellable with a keypress.
+    pub countdown_secs: Option<u64>,
+    /// Deploys write the digest the selected tag points at instead of the tag.
+    pub pin_digest: Option<bool>,
+    /// Subject template and trailers of deploy commits.
+    pub commit: Option<CommitSettings>,
+    /// Air-gapped deploys: no registry calls, and an optional kubectl wrapper for apply.
+    pub offline: Option<OfflineMode>,
+    /// Commands run around every deploy to the environment.
+    #[serde(default)]
+    pub hooks: DeployHooks,
+    /// What a deploy applies: the edited files (`file`), those plus the sibling files
+    /// holding the workload's Service, autoscaler or ConfigMaps (`related`), or only the
+    /// documents it changed (`changed`).
+    #[serde(default)]
+    pub apply_scope: ApplyScope,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApplyScope {
+    #[default]
+    File,
+    Related,
+    Changed,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -28,7 +631,9 @@ pub struct YamlSource {
     pub root: PathBuf,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A deployable service. Only services of plain YAML files are serialized, into the
+/// discovery cache: the Helm and live fields are left out.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ServiceSource {
     pub name: String,
     pub kind: String,
@@ -37,76 +642,330 @@ pub struct ServiceSource {
     pub source_name: String,
     pub source_root: std::path::PathBuf,
     pub yaml_path: std::path::PathBuf,
+    /// Position of the workload among the documents of `yaml_path`.
+    pub document_index: usize,
+    /// Overlay directory when the service comes from `kustomize build`; `yaml_path` is then
+    /// the overlay's kustomization file.
+    pub kustomize_overlay: Option<PathBuf>,
     pub namespace: Option<String>,
     pub selector: Option<String>,
+    /// Every first-party container of the workload, in manifest order.
+    pub containers: Vec<ContainerImage>,
+    /// Release the service was discovered from, in Helm discovery mode; `yaml_path` is
+    /// then empty.
+    #[serde(skip)]
+    pub helm_release: Option<HelmRelease>,
+    /// Chart the service is deployed from; `yaml_path` is then its values file.
+    #[serde(skip)]
+    pub helm_chart: Option<HelmChart>,
+    /// Discovered from the live object in the cluster; `yaml_path` is then empty.
+    #[serde(skip)]
+    pub live: bool,
+    /// Discovered from the output of `template_render_command`; `yaml_path` is then the
+    /// template, which davit cannot edit nor apply.
+    #[serde(default)]
+    pub templated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ContainerImage {
+    pub name: String,
+    pub image_path: String,
+}
+
+/// A document deployed with a service's workload, from the same file or a sibling YAML
+/// file of its directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedDocument {
+    pub path: PathBuf,
+    pub document_index: usize,
+    pub kind: String,
+    pub name: String,
+}
+
+impl ServiceSource {
+    /// Returns a copy of the service targeting one of its other containers.
+    pub fn with_container(&self, container: &ContainerImage) -> ServiceSource {
+        ServiceSource {
+            image_path: container.image_path.clone(),
+            container_name: container.name.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// Fails for services whose manifest cannot be edited and applied: those rendered
+    /// from a templated YAML, where the tag lives in the template's inputs.
+    pub fn ensure_editable(&self) -> Result<()> {
+        if self.templated {
+            return Err(anyhow::anyhow!(
+                "{} is rendered from the templated YAML {}, which davit cannot edit nor apply: change its tag in the template's inputs and deploy it with your templating tool",
+                self.name,
+                self.yaml_path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether deploys edit and commit a manifest file, as opposed to upgrading a Helm
+    /// release or replacing the live object.
+    pub fn is_file_backed(&self) -> bool {
+        self.helm_release.is_none() && !self.live
+    }
+
+    /// Parses the workload document this service was discovered from, rendering the
+    /// kustomize overlay or Helm chart when there is one.
+    pub fn workload_document(&self) -> Option<serde_yaml::Value> {
+        let content = match (&self.kustomize_overlay, &self.helm_chart) {
+            (Some(overlay), _) => Blueprint::render_kustomize(overlay).ok()?,
+            (None, Some(chart)) => chart
+                .template(&fs::read_to_string(&self.yaml_path).ok()?)
+                .ok()?,
+            (None, None) => fs::read_to_string(&self.yaml_path).ok()?,
+        };
+
+        parse_document(&content, self.document_index)
+    }
+
+    /// The documents of the workload's directory that belong to it: autoscalers targeting
+    /// it, Services and disruption budgets selecting its pods, and the ConfigMaps, Secrets
+    /// and service account its pods use. Only plain manifest files have any.
+    pub fn related_documents(&self) -> Vec<RelatedDocument> {
+        if !self.is_file_backed() || self.kustomize_overlay.is_some() || self.helm_chart.is_some() {
+            return Vec::new();
+        }
+        let (Some(workload), Some(dir)) = (self.workload_document(), self.yaml_path.parent())
+        else {
+            return Vec::new();
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| e == "yaml" || e == "yml")
+            })
+            .collect();
+        paths.sort();
+
+        let mut related = Vec::new();
+        for path in paths {
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            for (document_index, document) in
+                serde_yaml::Deserializer::from_str(&content).enumerate()
+            {
+                if path == self.yaml_path && document_index == self.document_index {
+                    continue;
+                }
+                let Ok(document) = serde_yaml::Value::deserialize(document) else {
+                    continue;
+                };
+                if let Some((kind, name)) = relation(&workload, &document) {
+                    related.push(RelatedDocument {
+                        path: path.clone(),
+                        document_index,
+                        kind,
+                        name,
+                    });
+                }
+            }
+        }
+        related
+    }
+}
+
+/// The kind and name of `document` when it belongs to `workload`.
+fn relation(
+    workload: &serde_yaml::Value,
+    document: &serde_yaml::Value,
+) -> Option<(String, String)> {
+    let kind = document.get("kind")?.as_str()?;
+    let name = document.get("metadata")?.get("name")?.as_str()?;
+    let namespace = |v: &serde_yaml::Value| {
+        v.get("metadata")
+            .and_then(|m| m.get("namespace"))
+            .and_then(|n| n.as_str())
+            .unwrap_or("default")
+            .to_string()
+    };
+    if namespace(workload) != namespace(document) {
+        return None;
+    }
+    let spec = document.get("spec");
+    let belongs = match kind {
+        "HorizontalPodAutoscaler" | "VerticalPodAutoscaler" => {
+            let target =
+                spec.and_then(|s| s.get("scaleTargetRef").or_else(|| s.get("targetRef")))?;
+            target.get("kind") == workload.get("kind")
+                && target.get("name") == workload.get("metadata")?.get("name")
+        }
+        "Service" | "PodDisruptionBudget" => {
+            let selector = match kind {
+                "Service" => spec?.get("selector")?,
+                _ => spec?.get("selector")?.get("matchLabels")?,
+            }
+            .as_mapping()?;
+            let labels = workload
+                .get("spec")?
+                .get("template")?
+                .get("metadata")?
+                .get("labels")?
+                .as_mapping()?;
+            !selector.is_empty() && selector.iter().all(|(k, v)| labels.get(k) == Some(v))
+        }
+        "ConfigMap" | "Secret" | "ServiceAccount" => {
+            let mut references = Vec::new();
+            collect_references(find_pod_spec(workload)?, &mut references);
+            references.contains(&(kind, name))
+        }
+        _ => false,
+    };
+    belongs.then(|| (kind.to_string(), name.to_string()))
+}
+
+/// The ConfigMaps, Secrets and service account a pod spec refers to, by kind and name.
+fn collect_references<'a>(value: &'a serde_yaml::Value, references: &mut Vec<(&'a str, &'a str)>) {
+    if let Some(items) = value.as_sequence() {
+        items.iter().for_each(|v| collect_references(v, references));
+    }
+    let Some(map) = value.as_mapping() else {
+        return;
+    };
+    for (key, value) in map {
+        let name = |field: &str| value.get(field).and_then(|n| n.as_str());
+        let reference = match key.as_str() {
+            Some("configMap" | "configMapRef" | "configMapKeyRef") => {
+                name("name").map(|n| ("ConfigMap", n))
+            }
+            Some("secret") => name("secretName")
+                .or_else(|| name("name"))
+                .map(|n| ("Secret", n)),
+            Some("secretRef" | "secretKeyRef") => name("name").map(|n| ("Secret", n)),
+            Some("serviceAccountName") => value.as_str().map(|n| ("ServiceAccount", n)),
+            _ => None,
+        };
+        match (key.as_str(), value.as_sequence()) {
+            (Some("imagePullSecrets"), Some(secrets)) => references.extend(
+                secrets
+                    .iter()
+                    .filter_map(|s| s.get("name")?.as_str())
+                    .map(|n| ("Secret", n)),
+            ),
+            _ => references.extend(reference),
+        }
+        collect_references(value, references);
+    }
 }
 
 impl Environment {
     pub fn yaml_sources(&self) -> Vec<YamlSource> {
-        let mut sources = vec![YamlSource {
-            name: "main".to_string(),
-            root: self.env_yaml_dir.clone(),
-        }];
+        let mut sources = Vec::new();
+        if !self.env_yaml_dir.as_os_str().is_empty() {
+            sources.push(YamlSource {
+                name: "main".to_string(),
+                root: self.env_yaml_dir.clone(),
+            });
+        }
 
-        sources.extend(self.env_yaml_dir_extra.iter().map(|(name, root)| YamlSource {
-            name: name.clone(),
-            root: root.clone(),
-        }));
+        sources.extend(
+            self.env_yaml_dir_extra
+                .iter()
+                .map(|(name, root)| YamlSource {
+                    name: name.clone(),
+                    root: root.clone(),
+                }),
+        );
 
         sources
     }
 
+    /// Fails when the service is locked in this environment, showing the reason.
+    pub fn ensure_unlocked(&self, service: &str) -> Result<()> {
+        let Some(lock) = self.locks.get(service) else {
+            return Ok(());
+        };
+        let mut message = format!("🔒 {} is locked in {}: {}", service, self.name, lock.reason);
+        if let Some(by) = &lock.by {
+            message.push_str(&format!(" (locked by {}", by));
+            if let Some(since) = lock.since {
+                message.push_str(&format!(
+                    " on {}",
+                    since.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                ));
+            }
+            message.push(')');
+        }
+        Err(anyhow::anyhow!(
+            "{}. Run `davit unlock {} --env {}` when the freeze is over.",
+            message,
+            service,
+            self.name
+        ))
+    }
+
     pub fn list_services(&self) -> Result<Vec<ServiceSource>> {
+        if let Some(helm) = &self.helm {
+            return self.list_helm_services(helm);
+        }
+        if let Some(discovery) = &self.cluster_discovery {
+            return self.list_cluster_services(discovery);
+        }
         let mut services = HashSet::new();
+        let charts = self.helm_chart_services();
+        let chart_dirs: Vec<PathBuf> = charts.iter().map(|c| PathBuf::from(&c.chart)).collect();
 
         for source in self.yaml_sources() {
             if !source.root.exists() {
                 continue;
             }
 
-            for entry in WalkDir::new(&source.root)
+            if let Some(kustomization) = find_kustomization(&source.root) {
+                self.discover_kustomize_services(&source, &kustomization, &mut services);
+                continue;
+            }
+
+            let files: Vec<PathBuf> = WalkDir::new(&source.root)
                 .into_iter()
                 .filter_entry(|e| {
                     if e.depth() == 0 {
                         return true;
                     }
+                    // Chart templates are rendered through their values file.
+                    if chart_dirs.iter().any(|dir| dir == e.path()) {
+                        return false;
+                    }
                     !e.file_name()
                         .to_str()
                         .map(|s| s.starts_with('.'))
                         .unwrap_or(false)
                 })
                 .filter_map(|e| e.ok())
-            {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                        if ext == "yaml" || ext == "yml" {
-                            if let Ok(content) = fs::read_to_string(path) {
-                                let deserializer = serde_yaml::Deserializer::from_str(&content);
-                                for document in deserializer {
-                                    match serde_yaml::Value::deserialize(document) {
-                                        Ok(resource) => {
-                                            if let Some(source) =
-                                                self.extract_gcr_service(&source, &resource, path)
-                                            {
-                                                services.insert(source);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            let err_msg = e.to_string();
-                                            if !err_msg.contains("more than one document") {
-                                                eprintln!(
-                                                    "Failed to parse YAML doc in {:?}: {}",
-                                                    path, e
-                                                );
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+                .map(|e| e.into_path())
+                .filter(|path| {
+                    path.is_file()
+                        && path
+                            .extension()
+                            .and_then(|s| s.to_str())
+                            .is_some_and(|ext| ext == "yaml" || ext == "yml")
+                })
+                .collect();
+            services.extend(self.scan_yaml_files(&source, &files));
+        }
+
+        for chart in charts {
+            match self.helm_chart_service(&chart) {
+                Ok(Some(service)) => {
+                    services.insert(service);
                 }
+                Ok(None) => eprintln!(
+                    "Skipping Helm chart {}: no workload runs a deployable image",
+                    chart.release
+                ),
+                Err(e) => eprintln!("Skipping Helm chart {}: {:#}", chart.release, e),
             }
         }
 
@@ -115,93 +974,389 @@ impl Environment {
         Ok(sorted_services)
     }
 
-    fn extract_gcr_service(
-        &self,
-        source: &YamlSource,
-        resource: &serde_yaml::Value,
-        yaml_path: &std::path::Path,
-    ) -> Option<ServiceSource> {
-        let kind = resource.get("kind")?.as_str()?;
-        let metadata = resource.get("metadata")?;
-        let name = metadata.get("name")?.as_str()?;
-
-        let microservice_kinds = ["Deployment", "StatefulSet", "DaemonSet", "Job", "CronJob"];
-        if !microservice_kinds.contains(&kind) {
-            return None;
-        }
+    /// Services of the YAML files of a source, parsed in parallel. Files unchanged since
+    /// the last discovery are taken from the discovery cache; templated files are
+    /// rendered every time, as their output may depend on other files.
+    fn scan_yaml_files(&self, source: &YamlSource, files: &[PathBuf]) -> Vec<ServiceSource> {
+        let fingerprint = format!(
+            "{}|{}|{:?}",
+            source.name,
+            source.root.display(),
+            self.image_registries
+        );
+        let cache_path = discovery_cache::cache_path(&self.name, &source.root);
+        let cache = cache_path
+            .as_deref()
+            .map(|path| DiscoveryCache::load(path, &fingerprint))
+            .unwrap_or_default();
 
-        // Search for images in the spec
-        if let Some(spec) = resource.get("spec") {
-            if let Some((image_path, container_name)) = self.find_gcr_image(spec) {
-                let namespace = metadata
-                    .get("namespace")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string());
-                let mut selector = None;
-
-                // Extract app label selector
-                if let Some(sel) = spec.get("selector") {
-                    if let Some(match_labels) = sel.get("matchLabels") {
-                        if let Some(app) = match_labels.get("app") {
-                            if let Some(app_str) = app.as_str() {
-                                selector = Some(format!("app={}", app_str));
-                            }
-                        }
-                    }
+        let scanned: Vec<(&PathBuf, Option<fs::Metadata>, Vec<ServiceSource>, bool)> = files
+            .par_iter()
+            .map(|path| {
+                let metadata = fs::metadata(path).ok();
+                if let Some(services) = metadata.as_ref().and_then(|m| cache.lookup(path, m)) {
+                    return (path, metadata, services.to_vec(), true);
                 }
+                let (services, cacheable) = self.scan_yaml_file(source, path);
+                (path, metadata, services, cacheable)
+            })
+            .collect();
 
-                return Some(ServiceSource {
-                    name: name.to_string(),
-                    kind: kind.to_string(),
-                    image_path,
-                    container_name,
-                    source_name: source.name.clone(),
-                    source_root: source.root.clone(),
-                    yaml_path: yaml_path.to_path_buf(),
-                    namespace,
-                    selector,
-                });
+        let mut updated = DiscoveryCache::new(&fingerprint);
+        let mut services = Vec::new();
+        for (path, metadata, found, cacheable) in scanned {
+            if let Some(metadata) = metadata.filter(|_| cacheable) {
+                updated.insert(path, &metadata, found.clone());
             }
+            services.extend(found);
         }
-
-        None
+        // The cache only saves time: failing to write it is not worth a warning.
+        if let Some(path) = cache_path
+            && !updated.same_files(&cache)
+        {
+            let _ = updated.save(&path);
+        }
+        services
     }
 
-    fn find_gcr_image(&self, value: &serde_yaml::Value) -> Option<(String, String)> {
-        if let Some(map) = value.as_mapping() {
-            // Check if this mapping is a container definition
-            if let Some(image_val) = map.get(&serde_yaml::Value::String("image".to_string())) {
-                if let Some(img_str) = image_val.as_str() {
-                    if img_str.contains("gcr.io") || img_str.contains("pkg.dev") {
-                        let container_name = map
-                            .get(&serde_yaml::Value::String("name".to_string()))
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("default")
-                            .to_string();
-                        return Some((img_str.to_string(), container_name));
-                    }
-                }
-            }
+    /// Services of one YAML file, and whether they may be cached.
+    fn scan_yaml_file(&self, source: &YamlSource, path: &Path) -> (Vec<ServiceSource>, bool) {
+        let Ok(content) = fs::read_to_string(path) else {
+            return (Vec::new(), false);
+        };
+        let templated = is_templated_yaml(&content);
+        let cacheable = !templated;
+        let Some(content) = self.discovery_content(path, content) else {
+            return (Vec::new(), cacheable);
+        };
 
-            for (_k, v) in map {
-                if let Some(found) = self.find_gcr_image(v) {
-                    return Some(found);
+        let mut services = Vec::new();
+        let deserializer = serde_yaml::Deserializer::from_str(&content);
+        for (document_index, document) in deserializer.enumerate() {
+            match serde_yaml::Value::deserialize(document) {
+                Ok(resource) => {
+                    services.extend(
+                        self.extract_gcr_service(source, &resource, path, document_index)
+                            .map(|service| ServiceSource {
+                                templated,
+                                ..service
+                            }),
+                    );
+                }
+                Err(e) => {
+                    let err_msg = e.to_string();
+                    if !err_msg.contains("more than one document") {
+                        eprintln!("Failed to parse YAML doc in {:?}: {}", path, e);
+                    }
                 }
             }
         }
+        (services, cacheable)
+    }
 
-        if let Some(seq) = value.as_sequence() {
-            for v in seq {
-                if let Some(found) = self.find_gcr_image(v) {
-                    return Some(found);
+    /// The `helm_charts` of the environment, with paths resolved against `env_yaml_dir`.
+    fn helm_chart_services(&self) -> Vec<HelmChart> {
+        self.helm_charts
+            .iter()
+            .map(|(name, service)| {
+                let local_chart = self.env_yaml_dir.join(&service.chart);
+                HelmChart {
+                    release: service.release.clone().unwrap_or(name.clone()),
+                    namespace: service.namespace.clone(),
+                    chart: if local_chart.is_dir() {
+                        local_chart.display().to_string()
+                    } else {
+                        service.chart.clone()
+                    },
+                    values: self.env_yaml_dir.join(&service.values),
+                    tag_value: service.tag_value.clone(),
                 }
+            })
+            .collect()
+    }
+
+    /// The first workload of the rendered chart running a deployable image, named after
+    /// the release and pointing at the values file.
+    fn helm_chart_service(&self, chart: &HelmChart) -> Result<Option<ServiceSource>> {
+        let values = fs::read_to_string(&chart.values)
+            .with_context(|| format!("Failed to read {}", chart.values.display()))?;
+        let rendered = chart.template(&values)?;
+        let source = YamlSource {
+            name: "main".to_string(),
+            root: self.env_yaml_dir.clone(),
+        };
+        Ok(serde_yaml::Deserializer::from_str(&rendered)
+            .enumerate()
+            .find_map(|(document_index, document)| {
+                let resource = serde_yaml::Value::deserialize(document).ok()?;
+                let mut service =
+                    self.extract_gcr_service(&source, &resource, &chart.values, document_index)?;
+                if service.selector.is_none() {
+                    service.selector = match_labels_selector(&resource);
+                }
+                service.name = chart.release.clone();
+                service.namespace = service.namespace.or(chart.namespace.clone());
+                service.helm_chart = Some(chart.clone());
+                Some(service)
+            }))
+    }
+
+    /// Discovers one service per Helm release, from the manifest of its deployed revision.
+    fn list_helm_services(&self, helm: &HelmDiscovery) -> Result<Vec<ServiceSource>> {
+        let mut services = Vec::new();
+        for release in helm::list_releases(&self.kubectl_context, helm)? {
+            match helm::release_manifest(&self.kubectl_context, &release) {
+                Ok(manifest) => services.extend(self.helm_release_service(&release, &manifest)),
+                Err(e) => eprintln!("Skipping Helm release {}: {}", release.name, e),
+            }
+        }
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(services)
+    }
+
+    /// Discovers services from the live workloads running a deployable image.
+    fn list_cluster_services(&self, discovery: &ClusterDiscovery) -> Result<Vec<ServiceSource>> {
+        let workloads = cluster::list_workloads(&self.kubectl_context, &discovery.namespaces)
+            .context("Failed to list cluster workloads")?;
+        let mut services: Vec<ServiceSource> = workloads
+            .iter()
+            .filter_map(|workload| self.cluster_service(workload))
+            .collect();
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(services)
+    }
+
+    fn cluster_service(&self, workload: &serde_yaml::Value) -> Option<ServiceSource> {
+        let source = YamlSource {
+            name: "cluster".to_string(),
+            root: PathBuf::new(),
+        };
+        let mut service = self.extract_gcr_service(&source, workload, Path::new(""), 0)?;
+        if service.selector.is_none() {
+            service.selector = match_labels_selector(workload);
+        }
+        service.live = true;
+        Some(service)
+    }
+
+    /// The first workload of a release manifest running a deployable image, named after
+    /// the release.
+    fn helm_release_service(&self, release: &HelmRelease, manifest: &str) -> Option<ServiceSource> {
+        let source = YamlSource {
+            name: "helm".to_string(),
+            root: PathBuf::new(),
+        };
+        serde_yaml::Deserializer::from_str(manifest)
+            .enumerate()
+            .find_map(|(document_index, document)| {
+                let resource = serde_yaml::Value::deserialize(document).ok()?;
+                let mut service =
+                    self.extract_gcr_service(&source, &resource, Path::new(""), document_index)?;
+                // Charts label pods with app.kubernetes.io/* rather than `app`.
+                if service.selector.is_none() {
+                    service.selector = match_labels_selector(&resource);
+                }
+                service.name = release.name.clone();
+                service.namespace = Some(release.namespace.clone());
+                service.helm_release = Some(release.clone());
+                Some(service)
+            })
+    }
+
+    /// Discovers services from the effective manifest of a kustomize overlay.
+    /// Services point at the overlay's kustomization file, whose `images` section gets the tag.
+    fn discover_kustomize_services(
+        &self,
+        source: &YamlSource,
+        kustomization: &Path,
+        services: &mut HashSet<ServiceSource>,
+    ) {
+        let rendered = match Blueprint::render_kustomize(&source.root) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                eprintln!(
+                    "Skipping kustomize overlay {}: {}",
+                    source.root.display(),
+                    e
+                );
+                return;
+            }
+        };
+
+        for (document_index, document) in serde_yaml::Deserializer::from_str(&rendered).enumerate()
+        {
+            if let Ok(resource) = serde_yaml::Value::deserialize(document)
+                && let Some(mut service) =
+                    self.extract_gcr_service(source, &resource, kustomization, document_index)
+            {
+                service.kustomize_overlay = Some(source.root.clone());
+                services.insert(service);
+            }
+        }
+    }
+
+    /// Returns the YAML to scan for a file, rendering or skipping templated sources.
+    fn discovery_content(&self, path: &Path, content: String) -> Option<String> {
+        if !is_templated_yaml(&content) {
+            return Some(content);
+        }
+
+        let Some(command) = &self.template_render_command else {
+            eprintln!(
+                "Skipping templated YAML {} (contains '{{{{ ... }}}}'; set template_render_command to render it)",
+                path.display()
+            );
+            return None;
+        };
+
+        let command = command.replace("{file}", &path.display().to_string());
+        match Command::new("sh").arg("-c").arg(&command).output() {
+            Ok(output) if output.status.success() => {
+                Some(String::from_utf8_lossy(&output.stdout).into_owned())
+            }
+            Ok(output) => {
+                eprintln!(
+                    "Skipping templated YAML {}: render command failed: {}",
+                    path.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                None
+            }
+            Err(e) => {
+                eprintln!(
+                    "Skipping templated YAML {}: could not run render command: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn extract_gcr_service(
+        &self,
+        source: &YamlSource,
+        resource: &serde_yaml::Value,
+        yaml_path: &std::path::Path,
+        document_index: usize,
+    ) -> Option<ServiceSource> {
+        let kind = resource.get("kind")?.as_str()?;
+        let metadata = resource.get("metadata")?;
+        let name = metadata.get("name")?.as_str()?;
+
+        let microservice_kinds = ["Deployment", "StatefulSet", "DaemonSet", "Job", "CronJob"];
+        if !microservice_kinds.contains(&kind) {
+            return None;
+        }
+
+        // Search for images in the spec
+        let spec = resource.get("spec")?;
+        let containers = self.find_gcr_images(spec);
+        let primary = containers.first()?.clone();
+
+        let namespace = metadata
+            .get("namespace")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        // Extract app label selector
+        let selector = spec
+            .get("selector")
+            .and_then(|sel| sel.get("matchLabels"))
+            .and_then(|match_labels| match_labels.get("app"))
+            .and_then(|app| app.as_str())
+            .map(|app_str| format!("app={}", app_str));
+
+        Some(ServiceSource {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            image_path: primary.image_path,
+            container_name: primary.name,
+            source_name: source.name.clone(),
+            source_root: source.root.clone(),
+            yaml_path: yaml_path.to_path_buf(),
+            document_index,
+            kustomize_overlay: None,
+            namespace,
+            selector,
+            containers,
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
+        })
+    }
+
+    pub fn is_deployable_image(&self, image: &str) -> bool {
+        if self.image_registries.is_empty() {
+            return DEFAULT_IMAGE_REGISTRIES
+                .iter()
+                .any(|registry| image_from_registry(image, registry));
+        }
+        self.image_registries
+            .iter()
+            .any(|registry| image_from_registry(image, registry))
+    }
+
+    /// First-party containers of the spec, regular containers before init containers so
+    /// that the primary one is the application even when `initContainers` comes first.
+    fn find_gcr_images(&self, value: &serde_yaml::Value) -> Vec<ContainerImage> {
+        let mut found = Vec::new();
+        self.collect_gcr_images(value, &mut found);
+        let mut init = Vec::new();
+        if let Some(init_containers) = find_pod_spec(value).and_then(|s| s.get("initContainers")) {
+            self.collect_gcr_images(init_containers, &mut init);
+        }
+        found.sort_by_key(|c| init.contains(c));
+        found
+    }
+
+    fn collect_gcr_images(&self, value: &serde_yaml::Value, found: &mut Vec<ContainerImage>) {
+        if let Some(map) = value.as_mapping() {
+            // Check if this mapping is a container definition
+            if let Some(image_val) = map.get(serde_yaml::Value::String("image".to_string()))
+                && let Some(img_str) = image_val.as_str()
+                && self.is_deployable_image(img_str)
+            {
+                let container_name = map
+                    .get(serde_yaml::Value::String("name".to_string()))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("default")
+                    .to_string();
+                found.push(ContainerImage {
+                    name: container_name,
+                    image_path: img_str.to_string(),
+                });
+                return;
+            }
+
+            for (_k, v) in map {
+                self.collect_gcr_images(v, found);
             }
         }
 
-        None
+        if let Some(seq) = value.as_sequence() {
+            for v in seq {
+                self.collect_gcr_images(v, found);
+            }
+        }
     }
 }
 
+/// Label selector built from all `matchLabels` of a workload.
+fn match_labels_selector(resource: &serde_yaml::Value) -> Option<String> {
+    let labels = resource
+        .get("spec")?
+        .get("selector")?
+        .get("matchLabels")?
+        .as_mapping()?;
+    let pairs: Vec<String> = labels
+        .iter()
+        .filter_map(|(k, v)| Some(format!("{}={}", k.as_str()?, v.as_str()?)))
+        .collect();
+    (!pairs.is_empty()).then(|| pairs.join(","))
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
         let config_path = Self::get_config_path()?;
@@ -216,14 +1371,31 @@ impl Config {
         let content = fs::read_to_string(&config_path)
             .with_context(|| format!("Failed to read config file at {}", config_path.display()))?;
 
-        let config: Config = toml::from_str(&content)
+        let mut config = Self::parse(&content)
             .with_context(|| format!("Failed to parse TOML config at {}", config_path.display()))?;
-
-        config.validate()?;
-
+        config.resolve_context_aliases();
         Ok(config)
     }
 
+    /// Replaces `kubectl_context` values naming a `[contexts]` alias with the matching
+    /// local kubeconfig context. Unresolved aliases are kept for `davit contexts` to report.
+    pub fn resolve_context_aliases(&mut self) {
+        if self.contexts.is_empty() {
+            return;
+        }
+        let local = contexts::local_contexts().unwrap_or_default();
+        for env in &mut self.environments {
+            let Some(alias) = self.contexts.get(&env.kubectl_context) else {
+                continue;
+            };
+            let resolved = contexts::resolve(&env.kubectl_context, alias, &local);
+            env.context_alias = Some(env.kubectl_context.clone());
+            if let Some(context) = resolved {
+                env.kubectl_context = context.name.clone();
+            }
+        }
+    }
+
     pub fn get_config_path() -> Result<PathBuf> {
         if let Ok(path) = std::env::var("DAVIT_CONFIG") {
             return Ok(PathBuf::from(path));
@@ -238,17 +1410,188 @@ impl Config {
         Ok(config_path)
     }
 
-    fn validate(&self) -> Result<()> {
+    /// Parses and validates configuration content.
+    pub fn parse(content: &str) -> Result<Self> {
+        let config: Config = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn service(&self, name: &str) -> ServiceConfig {
+        self.services.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Fields of the service's structured log lines.
+    pub fn log_fields(&self, service: &str) -> LogFields {
+        self.service(service).logs.or(&self.logs)
+    }
+
+    /// Checks the rules a loaded config must satisfy.
+    pub fn validate(&self) -> Result<()> {
         for env in &self.environments {
             env.validate()?;
         }
+        if let Some(rule) = self.compatibility.iter().find(|r| r.services.len() < 2) {
+            return Err(anyhow::anyhow!(
+                "Compatibility rule on {:?} needs at least two services",
+                rule.services
+            ));
+        }
+        if let Some(rule) = self
+            .protected_services
+            .iter()
+            .find(|r| r.services.is_empty())
+        {
+            return Err(anyhow::anyhow!(
+                "Protected services rule for environments {:?} lists no services",
+                rule.environments
+            ));
+        }
+        self.dashboard.keys.validate()?;
+        self.logs.validate("global")?;
+        for (name, service) in &self.services {
+            service.logs.validate(&format!("service '{}'", name))?;
+            if let Some(health) = &service.health
+                && health.url.is_none()
+                && health.port.is_none()
+            {
+                return Err(anyhow::anyhow!(
+                    "Service '{}' health check needs either `url` or `port`",
+                    name
+                ));
+            }
+            if let Some(progressive) = &service.progressive {
+                progressive.validate(name)?;
+            }
+            self.validate_promotion(name, service)?;
+            if let Some(grouped) = service
+                .version_group
+                .iter()
+                .find(|g| !g.tag.contains("{tag}") || split_image_ref(&g.image).1.is_some())
+            {
+                return Err(anyhow::anyhow!(
+                    "Service '{}' version group image '{}' needs an untagged `image` and a `tag` containing {{tag}}",
+                    name,
+                    grouped.image
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_promotion(&self, name: &str, service: &ServiceConfig) -> Result<()> {
+        let stages = &service.promotion;
+        if stages.is_empty() {
+            return Ok(());
+        }
+        if stages.len() < 2 {
+            return Err(anyhow::anyhow!(
+                "Service '{}' promotion pipeline needs at least two stages",
+                name
+            ));
+        }
+        let mut seen = HashSet::new();
+        for stage in stages {
+            if !self.environments.iter().any(|e| e.name == stage.env) {
+                return Err(anyhow::anyhow!(
+                    "Service '{}' promotion stage '{}' is not a configured environment",
+                    name,
+                    stage.env
+                ));
+            }
+            if !seen.insert(&stage.env) {
+                return Err(anyhow::anyhow!(
+                    "Service '{}' promotion pipeline lists '{}' twice",
+                    name,
+                    stage.env
+                ));
+            }
+            if let Some(soak) = &stage.soak {
+                history::parse_since(soak).with_context(|| {
+                    format!(
+                        "Service '{}' promotion stage '{}' has an invalid soak time",
+                        name, stage.env
+                    )
+                })?;
+            }
+            if stage.smoke && service.health.is_none() {
+                return Err(anyhow::anyhow!(
+                    "Service '{}' promotion stage '{}' needs a health check for `smoke`",
+                    name,
+                    stage.env
+                ));
+            }
+        }
+        if stages[0].has_gates() {
+            return Err(anyhow::anyhow!(
+                "Service '{}' promotion gates apply to the stages promoted into, not to the first one",
+                name
+            ));
+        }
+        Ok(())
+    }
+}
 
+impl ProgressiveDelivery {
+    fn validate(&self, service: &str) -> Result<()> {
+        if self.istio.is_some() == self.gateway.is_some() {
+            return Err(anyhow::anyhow!(
+                "Service '{}' progressive delivery needs either `istio` or `gateway` routing",
+                service
+            ));
+        }
+        let increasing = self.steps.windows(2).all(|w| w[0] < w[1]);
+        if !increasing || self.steps.first() == Some(&0) || self.steps.last() != Some(&100) {
+            return Err(anyhow::anyhow!(
+                "Service '{}' progressive steps must increase from above 0 up to 100",
+                service
+            ));
+        }
         Ok(())
     }
 }
 
 impl Environment {
     fn validate(&self) -> Result<()> {
+        if self.helm.is_some() && self.cluster_discovery.is_some() {
+            return Err(anyhow::anyhow!(
+                "Environment '{}' sets both `helm` and `cluster_discovery`",
+                self.name
+            ));
+        }
+        if self.helm.is_none()
+            && self.cluster_discovery.is_none()
+            && self.env_yaml_dir.as_os_str().is_empty()
+        {
+            return Err(anyhow::anyhow!(
+                "Environment '{}' needs `env_yaml_dir` unless services come from Helm releases or the cluster",
+                self.name
+            ));
+        }
+        if !self.helm_charts.is_empty() && (self.helm.is_some() || self.cluster_discovery.is_some())
+        {
+            return Err(anyhow::anyhow!(
+                "Environment '{}' sets `helm_charts`, which need `env_yaml_dir` discovery",
+                self.name
+            ));
+        }
+        for window in &self.freeze_windows {
+            FreezeWindow::parse(window).with_context(|| {
+                format!("Environment '{}' has an invalid freeze window", self.name)
+            })?;
+        }
+        if let Some(template) = self.commit.as_ref().and_then(|c| c.message.as_deref()) {
+            commit_message::validate(template).with_context(|| {
+                format!("Environment '{}' has an invalid commit message", self.name)
+            })?;
+        }
+        if self.gke_cluster.is_some() && self.gcp_project.is_none() {
+            return Err(anyhow::anyhow!(
+                "Environment '{}' needs `gcp_project` to fetch the credentials of `gke_cluster`",
+                self.name
+            ));
+        }
         if self.env_yaml_dir_extra.contains_key("main") {
             return Err(anyhow::anyhow!(
                 "Environment '{}' uses reserved extra source name 'main'",
@@ -269,10 +1612,215 @@ impl Environment {
             }
         }
 
+        if let Some(policy) = &self.policy {
+            let invalid = |field: &str, value: &str| {
+                anyhow::anyhow!(
+                    "Environment '{}' has an invalid policy {} '{}'",
+                    self.name,
+                    field,
+                    value
+                )
+            };
+            if let Some(cpu) = &policy.max_cpu_limit
+                && parse_cpu(cpu).is_none()
+            {
+                return Err(invalid("max_cpu_limit", cpu));
+            }
+            if let Some(memory) = &policy.max_memory_limit
+                && parse_memory(memory).is_none()
+            {
+                return Err(invalid("max_memory_limit", memory));
+            }
+        }
+
         Ok(())
     }
 }
 
+/// Detects Helm/Go-template expressions, which are not valid YAML until rendered.
+pub fn is_templated_yaml(content: &str) -> bool {
+    content
+        .find("{{")
+        .is_some_and(|start| content[start..].contains("}}"))
+}
+
+/// Values replaced when cloning an environment block.
+pub struct EnvironmentClone {
+    pub name: String,
+    pub kubectl_context: String,
+    pub env_yaml_dir: PathBuf,
+    pub env_yaml_dir_extra: BTreeMap<String, PathBuf>,
+}
+
+/// Appends a copy of environment `from` to the config content, keeping the rest of
+/// the file (comments and formatting included) untouched. The result is validated.
+pub fn clone_environment(content: &str, from: &str, clone: &EnvironmentClone) -> Result<String> {
+    let mut document: toml_edit::DocumentMut =
+        content.parse().context("Failed to parse TOML config")?;
+    let environments = document
+        .get_mut("environments")
+        .and_then(|item| item.as_array_of_tables_mut())
+        .context("Config has no [[environments]] blocks")?;
+
+    if environments
+        .iter()
+        .any(|env| env.get("name").and_then(|n| n.as_str()) == Some(clone.name.as_str()))
+    {
+        return Err(anyhow::anyhow!(
+            "Environment '{}' already exists",
+            clone.name
+        ));
+    }
+
+    let mut table = environments
+        .iter()
+        .find(|env| env.get("name").and_then(|n| n.as_str()) == Some(from))
+        .cloned()
+        .with_context(|| format!("Environment '{}' not found in config", from))?;
+
+    table["name"] = toml_edit::value(clone.name.clone());
+    table["kubectl_context"] = toml_edit::value(clone.kubectl_context.clone());
+    // The credentials of the source cluster would not create the new context.
+    table.remove("gke_cluster");
+    table["env_yaml_dir"] = toml_edit::value(clone.env_yaml_dir.display().to_string());
+    for (source, path) in &clone.env_yaml_dir_extra {
+        table["env_yaml_dir_extra"][source.as_str()] = toml_edit::value(path.display().to_string());
+    }
+    table.decor_mut().set_prefix("\n");
+    environments.push(table);
+
+    let updated = document.to_string();
+    Config::parse(&updated).context("Cloned environment does not produce a valid config")?;
+    Ok(updated)
+}
+
+/// Environment collected by `davit config init`.
+pub struct EnvironmentInit {
+    pub name: String,
+    pub kubectl_context: String,
+    pub env_yaml_dir: PathBuf,
+    pub gcp_project: Option<String>,
+    pub gke_cluster: Option<GkeCluster>,
+    pub protected: bool,
+}
+
+/// Starter config file with one `[[environments]]` block per environment. The result
+/// is validated.
+pub fn starter_config(environments: &[EnvironmentInit]) -> Result<String> {
+    let mut document = toml_edit::DocumentMut::new();
+    document
+        .decor_mut()
+        .set_prefix("# Generated by `davit config init`; see the README for every option.\n\n");
+    let mut blocks = toml_edit::ArrayOfTables::new();
+    for env in environments {
+        let mut table = toml_edit::Table::new();
+        table["name"] = toml_edit::value(env.name.clone());
+        table["env_yaml_dir"] = toml_edit::value(env.env_yaml_dir.display().to_string());
+        table["kubectl_context"] = toml_edit::value(env.kubectl_context.clone());
+        if env.protected {
+            table["protected"] = toml_edit::value(true);
+        }
+        if let Some(project) = &env.gcp_project {
+            table["gcp_project"] = toml_edit::value(project.clone());
+        }
+        if let Some(cluster) = &env.gke_cluster {
+            let mut inline = toml_edit::InlineTable::new();
+            inline.insert("name", cluster.name.clone().into());
+            inline.insert("location", cluster.location.clone().into());
+            table["gke_cluster"] = toml_edit::value(inline);
+        }
+        if !blocks.is_empty() {
+            table.decor_mut().set_prefix("\n");
+        }
+        blocks.push(table);
+    }
+    document.insert("environments", toml_edit::Item::ArrayOfTables(blocks));
+
+    let content = document.to_string();
+    Config::parse(&content).context("Generated config is not valid")?;
+    Ok(content)
+}
+
+/// Writes the dashboard layout into the `[dashboard]` table, keeping the rest of the
+/// config file untouched.
+pub fn set_dashboard_layout(content: &str, layout: &DashboardLayout) -> Result<String> {
+    let mut document: toml_edit::DocumentMut =
+        content.parse().context("Failed to parse TOML config")?;
+    let table = document
+        .entry("dashboard")
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .context("`dashboard` in config is not a table")?;
+
+    match layout.pod_panel_height {
+        Some(height) => table["pod_panel_height"] = toml_edit::value(i64::from(height)),
+        None => {
+            table.remove("pod_panel_height");
+        }
+    }
+    table["old_log_percent"] = toml_edit::value(i64::from(layout.old_log_percent));
+    if layout.show_resources {
+        table["show_resources"] = toml_edit::value(true);
+    } else {
+        table.remove("show_resources");
+    }
+
+    let updated = document.to_string();
+    Config::parse(&updated).context("Dashboard layout does not produce a valid config")?;
+    Ok(updated)
+}
+
+/// Adds, replaces or (with `None`) removes the lock of a service in an environment,
+/// keeping the rest of the config file untouched.
+pub fn set_service_lock(
+    content: &str,
+    env: &str,
+    service: &str,
+    lock: Option<&ServiceLock>,
+) -> Result<String> {
+    let mut document: toml_edit::DocumentMut =
+        content.parse().context("Failed to parse TOML config")?;
+    let table = document
+        .get_mut("environments")
+        .and_then(|item| item.as_array_of_tables_mut())
+        .and_then(|envs| {
+            envs.iter_mut()
+                .find(|t| t.get("name").and_then(|n| n.as_str()) == Some(env))
+        })
+        .with_context(|| format!("Environment '{}' not found in config", env))?;
+
+    match lock {
+        Some(lock) => {
+            let mut entry = toml_edit::InlineTable::new();
+            entry.insert("reason", lock.reason.clone().into());
+            if let Some(by) = &lock.by {
+                entry.insert("by", by.clone().into());
+            }
+            if let Some(since) = lock.since {
+                entry.insert("since", since.to_rfc3339().into());
+            }
+            table["locks"][service] = toml_edit::value(entry);
+        }
+        None => {
+            let locks = table.get_mut("locks").and_then(|l| l.as_table_like_mut());
+            if locks.and_then(|l| l.remove(service)).is_none() {
+                return Err(anyhow::anyhow!("{} is not locked in {}", service, env));
+            }
+            if table
+                .get("locks")
+                .and_then(|l| l.as_table_like())
+                .is_some_and(|l| l.is_empty())
+            {
+                table.remove("locks");
+            }
+        }
+    }
+
+    let updated = document.to_string();
+    Config::parse(&updated).context("Service lock does not produce a valid config")?;
+    Ok(updated)
+}
+
 fn normalize_source_path(path: &Path) -> PathBuf {
     path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
 }
@@ -371,8 +1919,7 @@ spec:
             env_yaml_dir,
             env_yaml_dir_extra: BTreeMap::new(),
             kubectl_context: "test".to_string(),
-            gcp_project: None,
-            protected: None,
+            ..Default::default()
         };
 
         let services = env.list_services()?;
@@ -394,6 +1941,7 @@ spec:
             Some("app=gcr-service-app".to_string())
         );
         assert_eq!(gcr_service.namespace, Some("test-ns".to_string()));
+        assert_eq!(gcr_service.containers.len(), 1);
 
         let pkg_service = services.iter().find(|s| s.name == "pkg-service").unwrap();
         assert_eq!(pkg_service.kind, "StatefulSet");
@@ -415,6 +1963,120 @@ spec:
         Ok(())
     }
 
+    #[test]
+    fn test_related_documents_span_the_file_and_its_siblings() -> Result<()> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("deploy.yaml"),
+            r#"apiVersion: v1
+kind: Service
+metadata:
+  name: api
+spec:
+  selector:
+    app: api
+---
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: api
+spec:
+  template:
+    metadata:
+      labels:
+        app: api
+        tier: backend
+    spec:
+      serviceAccountName: api
+      containers:
+      - name: api
+        image: gcr.io/p/api:v1
+        envFrom:
+        - configMapRef:
+            name: api-config
+        env:
+        - name: TOKEN
+          valueFrom:
+            secretKeyRef:
+              name: api-token
+              key: token
+"#,
+        )?;
+        fs::write(
+            dir.path().join("hpa.yaml"),
+            r#"apiVersion: autoscaling/v2
+kind: HorizontalPodAutoscaler
+metadata:
+  name: api
+spec:
+  scaleTargetRef:
+    apiVersion: apps/v1
+    kind: Deployment
+    name: api
+  maxReplicas: 5
+"#,
+        )?;
+        fs::write(
+            dir.path().join("config.yml"),
+            r#"kind: ConfigMap
+metadata:
+  name: api-config
+---
+kind: ConfigMap
+metadata:
+  name: worker-config
+---
+kind: Secret
+metadata:
+  name: api-token
+---
+kind: Service
+metadata:
+  name: worker
+spec:
+  selector:
+    app: worker
+"#,
+        )?;
+
+        let env = Environment {
+            name: "test".to_string(),
+            env_yaml_dir: dir.path().to_path_buf(),
+            kubectl_context: "test".to_string(),
+            ..Default::default()
+        };
+        let services = env.list_services()?;
+        let related: Vec<(String, String, usize)> = services[0]
+            .related_documents()
+            .into_iter()
+            .map(|d| {
+                (
+                    d.path.file_name().unwrap().to_string_lossy().into_owned(),
+                    format!("{}/{}", d.kind, d.name),
+                    d.document_index,
+                )
+            })
+            .collect();
+        assert_eq!(
+            related,
+            [
+                (
+                    "config.yml".to_string(),
+                    "ConfigMap/api-config".to_string(),
+                    0
+                ),
+                ("config.yml".to_string(), "Secret/api-token".to_string(), 2),
+                ("deploy.yaml".to_string(), "Service/api".to_string(), 0),
+                (
+                    "hpa.yaml".to_string(),
+                    "HorizontalPodAutoscaler/api".to_string(),
+                    0
+                ),
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_list_services_includes_named_extra_sources() -> Result<()> {
         let dir = tempdir()?;
@@ -462,8 +2124,7 @@ spec:
             env_yaml_dir: env_yaml_dir.clone(),
             env_yaml_dir_extra: BTreeMap::from([("demo".to_string(), extra_yaml_dir.clone())]),
             kubectl_context: "test".to_string(),
-            gcp_project: None,
-            protected: None,
+            ..Default::default()
         };
 
         let services = env.list_services()?;
@@ -483,4 +2144,608 @@ spec:
 
         Ok(())
     }
+
+    #[test]
+    fn test_list_services_captures_all_first_party_containers() -> Result<()> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("deploy.yaml"),
+            r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: multi
+spec:
+  template:
+    spec:
+      initContainers:
+      - name: migrate
+        image: gcr.io/my-project/app:v1
+      containers:
+      - name: app
+        image: gcr.io/my-project/app:v1
+      - name: proxy
+        image: haproxy:2.4
+      - name: worker
+        image: gcr.io/my-project/worker:v3
+"#,
+        )?;
+
+        let env = Environment {
+            name: "test".to_string(),
+            env_yaml_dir: dir.path().to_path_buf(),
+            env_yaml_dir_extra: BTreeMap::new(),
+            kubectl_context: "test".to_string(),
+            ..Default::default()
+        };
+
+        let services = env.list_services()?;
+        assert_eq!(services.len(), 1);
+        let names: Vec<&str> = services[0]
+            .containers
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["app", "worker", "migrate"]);
+        assert_eq!(services[0].container_name, "app");
+
+        assert_eq!(services[0].document_index, 0);
+
+        let worker = services[0].with_container(&services[0].containers[1]);
+        assert_eq!(worker.image_path, "gcr.io/my-project/worker:v3");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_from_registry_matches_host_and_path_prefix() {
+        let image = "europe-west1-docker.pkg.dev/my-proj/apps/auth:v1";
+        assert!(image_from_registry(image, "pkg.dev"));
+        assert!(image_from_registry(
+            image,
+            "europe-west1-docker.pkg.dev/my-proj"
+        ));
+        assert!(!image_from_registry(
+            image,
+            "europe-west1-docker.pkg.dev/my-project"
+        ));
+        assert!(image_from_registry("eu.gcr.io/p/app:v1", "gcr.io"));
+        assert!(image_from_registry(
+            "registry.internal.corp/team/app:v2",
+            "registry.internal.corp"
+        ));
+        assert!(!image_from_registry("nginx:latest", "gcr.io"));
+        assert!(!image_from_registry("notgcr.io/p/app:v1", "gcr.io"));
+    }
+
+    #[test]
+    fn test_list_services_uses_configured_registries() -> Result<()> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("deploy.yaml"),
+            r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: internal
+spec:
+  template:
+    spec:
+      containers:
+      - name: app
+        image: registry.internal.corp/team/app:v1
+      - name: sidecar
+        image: gcr.io/my-project/sidecar:v1
+"#,
+        )?;
+
+        let env = Environment {
+            name: "test".to_string(),
+            env_yaml_dir: dir.path().to_path_buf(),
+            kubectl_context: "test".to_string(),
+            ..Default::default()
+        };
+        let services = env.list_services()?;
+        assert_eq!(services[0].container_name, "sidecar");
+
+        let env = Environment {
+            image_registries: vec!["registry.internal.corp".to_string()],
+            ..env
+        };
+        let services = env.list_services()?;
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].image_path, "registry.internal.corp/team/app:v1");
+        assert_eq!(services[0].containers.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_helm_release_service_uses_release_name_and_match_labels() -> Result<()> {
+        let manifest = r#"---
+# Source: auth-api/templates/service.yaml
+apiVersion: v1
+kind: Service
+metadata:
+  name: auth-api
+---
+# Source: auth-api/templates/deployment.yaml
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: auth-api-web
+spec:
+  selector:
+    matchLabels:
+      app.kubernetes.io/name: auth-api
+      app.kubernetes.io/instance: auth
+  template:
+    spec:
+      containers:
+      - name: auth-api
+        image: europe-docker.pkg.dev/acme/apps/auth-api:v2.3.0
+"#;
+        let env = Environment {
+            name: "test".to_string(),
+            kubectl_context: "test".to_string(),
+            helm: Some(HelmDiscovery::default()),
+            ..Default::default()
+        };
+        let release = HelmRelease {
+            name: "auth".to_string(),
+            namespace: "identity".to_string(),
+            chart: "auth-api-1.4.2".to_string(),
+        };
+
+        let service = env.helm_release_service(&release, manifest).unwrap();
+        assert_eq!(service.name, "auth");
+        assert_eq!(service.kind, "Deployment");
+        assert_eq!(service.document_index, 1);
+        assert_eq!(service.namespace.as_deref(), Some("identity"));
+        assert_eq!(
+            service.selector.as_deref(),
+            Some("app.kubernetes.io/name=auth-api,app.kubernetes.io/instance=auth")
+        );
+        assert_eq!(service.helm_release, Some(release));
+        assert!(env.yaml_sources().is_empty());
+        env.validate()?;
+
+        let env = Environment { helm: None, ..env };
+        assert!(env.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_service_from_live_workload() -> Result<()> {
+        let workload: serde_yaml::Value = serde_yaml::from_str(
+            r#"apiVersion: apps/v1
+kind: StatefulSet
+metadata:
+  name: ledger
+  namespace: billing
+spec:
+  selector:
+    matchLabels:
+      app: ledger
+  template:
+    spec:
+      containers:
+      - name: ledger
+        image: gcr.io/acme-prod/ledger:v4.1.0
+"#,
+        )?;
+        let env = Environment {
+            name: "test".to_string(),
+            kubectl_context: "test".to_string(),
+            cluster_discovery: Some(ClusterDiscovery::default()),
+            ..Default::default()
+        };
+        env.validate()?;
+
+        let service = env.cluster_service(&workload).unwrap();
+        assert_eq!(service.name, "ledger");
+        assert_eq!(service.kind, "StatefulSet");
+        assert_eq!(service.selector.as_deref(), Some("app=ledger"));
+        assert_eq!(service.source_name, "cluster");
+        assert!(service.live && !service.is_file_backed());
+
+        let env = Environment {
+            helm: Some(HelmDiscovery::default()),
+            ..env
+        };
+        assert!(env.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_services_skips_or_renders_templated_yaml() -> Result<()> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("chart.yaml"),
+            r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: {{ .Values.name }}
+spec:
+  template:
+    spec:
+      containers:
+      - name: main
+        image: gcr.io/my-project/templated:{{ .Values.tag }}
+"#,
+        )?;
+
+        let mut env = Environment {
+            name: "test".to_string(),
+            env_yaml_dir: dir.path().to_path_buf(),
+            kubectl_context: "test".to_string(),
+            ..Default::default()
+        };
+        assert!(env.list_services()?.is_empty());
+
+        env.template_render_command = Some(
+            "sed -e 's/{{ .Values.name }}/rendered/' -e 's/{{ .Values.tag }}/v1/' {file}"
+                .to_string(),
+        );
+        let services = env.list_services()?;
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].name, "rendered");
+        assert_eq!(services[0].image_path, "gcr.io/my-project/templated:v1");
+        // Deploys would rewrite and apply the template itself.
+        assert!(services[0].templated);
+        assert!(services[0].ensure_editable().is_err());
+
+        assert!(!is_templated_yaml("image: gcr.io/p/app:v1\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone_environment_appends_block_and_keeps_comments() -> Result<()> {
+        let content = r#"# team config
+[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+env_yaml_dir_extra.demo = "/demo/staging"
+kubectl_context = "ctx-staging"
+gcp_project = "proj" # shared project
+"#;
+        let clone = EnvironmentClone {
+            name: "preview".to_string(),
+            kubectl_context: "ctx-preview".to_string(),
+            env_yaml_dir: PathBuf::from("/repo/preview"),
+            env_yaml_dir_extra: BTreeMap::from([(
+                "demo".to_string(),
+                PathBuf::from("/demo/preview"),
+            )]),
+        };
+
+        let updated = clone_environment(content, "staging", &clone)?;
+        assert!(updated.starts_with(content));
+
+        let config = Config::parse(&updated)?;
+        assert_eq!(config.environments.len(), 2);
+        let preview = &config.environments[1];
+        assert_eq!(preview.name, "preview");
+        assert_eq!(preview.kubectl_context, "ctx-preview");
+        assert_eq!(preview.env_yaml_dir, PathBuf::from("/repo/preview"));
+        assert_eq!(
+            preview.env_yaml_dir_extra.get("demo"),
+            Some(&PathBuf::from("/demo/preview"))
+        );
+        assert_eq!(preview.gcp_project.as_deref(), Some("proj"));
+
+        assert!(clone_environment(&updated, "staging", &clone).is_err());
+        assert!(clone_environment(content, "missing", &clone).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_starter_config_writes_one_block_per_environment() -> Result<()> {
+        let content = starter_config(&[
+            EnvironmentInit {
+                name: "staging".to_string(),
+                kubectl_context: "minikube".to_string(),
+                env_yaml_dir: PathBuf::from("/repo/staging"),
+                gcp_project: None,
+                gke_cluster: None,
+                protected: false,
+            },
+            EnvironmentInit {
+                name: "production".to_string(),
+                kubectl_context: "gke_acme-prod_europe-west1_prod".to_string(),
+                env_yaml_dir: PathBuf::from("/repo/prod"),
+                gcp_project: Some("acme-prod".to_string()),
+                gke_cluster: Some(GkeCluster {
+                    name: "prod".to_string(),
+                    location: "europe-west1".to_string(),
+                }),
+                protected: true,
+            },
+        ])?;
+        assert!(content.starts_with("# Generated by `davit config init`"));
+        assert!(content.contains("gke_cluster = { name = \"prod\", location = \"europe-west1\" }"));
+
+        let config = Config::parse(&content)?;
+        assert_eq!(config.environments.len(), 2);
+        assert_eq!(config.environments[0].protected, None);
+        let production = &config.environments[1];
+        assert_eq!(production.protected, Some(true));
+        assert_eq!(production.gcp_project.as_deref(), Some("acme-prod"));
+        assert_eq!(
+            production.gke_cluster.as_ref().map(|c| c.location.as_str()),
+            Some("europe-west1")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_health_check_requires_url_or_port() -> Result<()> {
+        let base = r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+kubectl_context = "ctx-staging"
+"#;
+        let config = Config::parse(&format!(
+            "{}\n[services.auth-api.health]\npath = \"/health\"\nport = 8080\n",
+            base
+        ))?;
+        let health = config.service("auth-api").health.unwrap();
+        assert_eq!(health.path, "/health");
+        assert_eq!(health.interval_secs, 5);
+
+        assert!(
+            Config::parse(&format!(
+                "{}\n[services.auth-api.health]\npath = \"/health\"\n",
+                base
+            ))
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_progressive_delivery_steps() -> Result<()> {
+        let base = r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+kubectl_context = "ctx-staging"
+
+[services.auth-api.progressive.istio]
+virtual_service = "auth-api"
+"#;
+        let progressive = Config::parse(base)?
+            .service("auth-api")
+            .progressive
+            .unwrap();
+        assert_eq!(progressive.steps, vec![10, 50, 100]);
+        assert_eq!(progressive.version_label, "version");
+        assert_eq!(progressive.istio.unwrap().destination_rule(), "auth-api");
+        assert!(
+            Config::parse(&format!(
+                "{}\n[services.auth-api.progressive.gateway]\nhttp_route = \"auth-api\"\n",
+                base
+            ))
+            .is_err()
+        );
+
+        for steps in ["[10, 50]", "[50, 10, 100]", "[0, 100]"] {
+            assert!(
+                Config::parse(&format!(
+                    "{}
+[services.auth-api.progressive]
+steps = {}
+",
+                    base, steps
+                ))
+                .is_err(),
+                "{}",
+                steps
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_group_tags() -> Result<()> {
+        let base = r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+kubectl_context = "ctx-staging"
+"#;
+        let grouped = Config::parse(&format!(
+            "{}\n[[services.payments.version_group]]\nimage = \"gcr.io/p/payments-cron\"\ntag = \"{{tag}}-cron\"\n",
+            base
+        ))?;
+        assert_eq!(
+            grouped.service("payments").version_group[0].tag_for("v2"),
+            "v2-cron"
+        );
+        assert!(
+            Config::parse(&format!(
+                "{}\n[[services.payments.version_group]]\nimage = \"gcr.io/p/payments-cron:v1\"\n",
+                base
+            ))
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pin_digest_is_set_per_environment() -> Result<()> {
+        let config = Config::parse(
+            r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+kubectl_context = "ctx-staging"
+
+[[environments]]
+name = "production"
+env_yaml_dir = "/repo/production"
+kubectl_context = "ctx-production"
+pin_digest = true
+"#,
+        )?;
+        assert_eq!(config.environments[0].pin_digest, None);
+        assert_eq!(config.environments[1].pin_digest, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_countdown_secs_is_read_per_environment() -> Result<()> {
+        let config = Config::parse(
+            r#"[[environments]]
+name = "production"
+env_yaml_dir = "/repo/production"
+kubectl_context = "ctx-production"
+countdown_secs = 30
+"#,
+        )?;
+        assert_eq!(config.environments[0].countdown_secs, Some(30));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deploy_annotations_use_configured_keys() -> Result<()> {
+        let config = Config::parse(
+            r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+kubectl_context = "ctx-staging"
+
+[annotations]
+change_cause = "kubernetes.io/change-cause"
+previous_tag = "davit.dev/previous-tag"
+"#,
+        )?;
+        assert_eq!(
+            config
+                .annotations
+                .values("auth-api", Some("v1"), "v2", "jane"),
+            [
+                (
+                    "kubernetes.io/change-cause".to_string(),
+                    "davit deploy auth-api v1 → v2 by jane".to_string()
+                ),
+                ("davit.dev/previous-tag".to_string(), "v1".to_string()),
+            ]
+        );
+        assert_eq!(
+            config
+                .annotations
+                .values("auth-api", None, "v2", "jane")
+                .len(),
+            1
+        );
+        assert!(
+            DeployAnnotations::default()
+                .values("auth-api", Some("v1"), "v2", "jane")
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_promotion_pipeline_validation() -> Result<()> {
+        let base = r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+kubectl_context = "ctx-staging"
+
+[[environments]]
+name = "production"
+env_yaml_dir = "/repo/production"
+kubectl_context = "ctx-production"
+"#;
+        let pipeline = r#"
+[[services.payments.promotion]]
+env = "staging"
+[[services.payments.promotion]]
+env = "production"
+soak = "24h"
+approvers = ["jane@acme.io"]
+"#;
+        let config = Config::parse(&format!("{}{}", base, pipeline))?;
+        let stages = &config.service("payments").promotion;
+        assert_eq!(stages[1].soak.as_deref(), Some("24h"));
+        assert!(stages[1].has_gates() && !stages[0].has_gates());
+
+        for invalid in [
+            "[[services.payments.promotion]]\nenv = \"staging\"\n[[services.payments.promotion]]\nenv = \"qa\"\n",
+            "[[services.payments.promotion]]\nenv = \"staging\"\n[[services.payments.promotion]]\nenv = \"production\"\nsmoke = true\n",
+            "[[services.payments.promotion]]\nenv = \"staging\"\nsoak = \"1h\"\n[[services.payments.promotion]]\nenv = \"production\"\n",
+        ] {
+            assert!(Config::parse(&format!("{}\n{}", base, invalid)).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_service_lock_adds_and_removes_lock() -> Result<()> {
+        let content = r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/tmp/staging"
+kubectl_context = "staging"
+
+[[environments]]
+name = "production" # frozen often
+env_yaml_dir = "/tmp/prod"
+kubectl_context = "prod"
+"#;
+        let lock = ServiceLock {
+            reason: "INC-42: ledger corruption".to_string(),
+            by: Some("jane@acme.io".to_string()),
+            since: "2026-10-15T08:00:00Z".parse().ok(),
+        };
+
+        let locked = set_service_lock(content, "production", "payments-api", Some(&lock))?;
+        assert!(locked.contains("name = \"production\" # frozen often"));
+        let config = Config::parse(&locked)?;
+        assert_eq!(config.environments[1].locks["payments-api"], lock);
+        let err = config.environments[1]
+            .ensure_unlocked("payments-api")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("payments-api is locked in production: INC-42: ledger corruption"));
+        assert!(
+            config.environments[0]
+                .ensure_unlocked("payments-api")
+                .is_ok()
+        );
+
+        let unlocked = set_service_lock(&locked, "production", "payments-api", None)?;
+        assert_eq!(unlocked, content);
+        assert!(set_service_lock(&unlocked, "production", "payments-api", None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_dashboard_layout_updates_table() -> Result<()> {
+        let content = r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+kubectl_context = "ctx-staging"
+"#;
+        assert_eq!(
+            Config::parse(content)?.dashboard,
+            DashboardLayout::default()
+        );
+
+        let layout = DashboardLayout {
+            pod_panel_height: Some(14),
+            old_log_percent: 30,
+            show_resources: true,
+            ..DashboardLayout::default()
+        };
+        let updated = set_dashboard_layout(content, &layout)?;
+        assert!(updated.starts_with(content));
+        assert_eq!(Config::parse(&updated)?.dashboard, layout);
+
+        let reset = set_dashboard_layout(&updated, &DashboardLayout::default())?;
+        assert_eq!(Config::parse(&reset)?.dashboard, DashboardLayout::default());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,403 @@
+use console::style;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+use crate::cluster;
+use crate::config::Config;
+use crate::contexts::{self, LocalContext};
+use crate::lint::Severity;
+
+const CONTEXT_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Problem found in the config file, with the line it comes from when known.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Config content with the spans of its keys and values, to point issues at lines.
+struct SourceMap<'a> {
+    content: &'a str,
+    root: Option<Spanned<DeTable<'a>>>,
+}
+
+impl<'a> SourceMap<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            root: DeTable::parse(content).ok(),
+        }
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.content[..offset.min(self.content.len())]
+            .matches('\n')
+            .count()
+            + 1
+    }
+
+    /// Line of the key at `path`, e.g. `["environments", "1", "kubectl_context"]`. An
+    /// index or key that does not resolve stops at the deepest one that does.
+    fn line(&self, path: &[String]) -> Option<usize> {
+        let root = self.root.as_ref()?;
+        let mut span = root.span();
+        let mut table = Some(root.get_ref());
+        let mut value: Option<&DeValue> = None;
+        for segment in path {
+            let next = match (table, value) {
+                (Some(table), _) => table
+                    .iter()
+                    .find(|(k, _)| k.get_ref().as_ref() == segment.as_str())
+                    .map(|(k, v)| (k.span(), v)),
+                (None, Some(DeValue::Array(items))) => segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| items.get(i))
+                    .map(|v| (v.span(), v)),
+                _ => None,
+            };
+            let Some((next_span, next_value)) = next else {
+                break;
+            };
+            span = next_span;
+            match next_value.get_ref() {
+                DeValue::Table(t) => {
+                    table = Some(t);
+                    value = None;
+                }
+                other => {
+                    table = None;
+                    value = Some(other);
+                }
+            }
+        }
+        Some(self.line_of(span.start))
+    }
+}
+
+fn path_segments(path: &serde_ignored::Path, segments: &mut Vec<String>) {
+    match path {
+        serde_ignored::Path::Root => {}
+        serde_ignored::Path::Seq { parent, index } => {
+            path_segments(parent, segments);
+            segments.push(index.to_string());
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            path_segments(parent, segments);
+            segments.push(key.clone());
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => path_segments(parent, segments),
+    }
+}
+
+fn segments(path: &[&str]) -> Vec<String> {
+    path.iter().map(|s| s.to_string()).collect()
+}
+
+/// Parses the config content and checks it without contacting any cluster: TOML syntax
+/// and types, unknown keys, environments defined twice, the rules enforced when the
+/// config is loaded and YAML directories missing on this machine. Returns the parsed
+/// config unless it could not be read at all.
+pub fn check(content: &str) -> (Option<Config>, Vec<ConfigIssue>) {
+    let source = SourceMap::new(content);
+    let mut issues = Vec::new();
+    let mut issue = |severity, line, message: String| {
+        issues.push(ConfigIssue {
+            severity,
+            line,
+            message,
+        })
+    };
+
+    let mut unknown = Vec::new();
+    let parsed = toml::Deserializer::parse(content).and_then(|deserializer| {
+        serde_ignored::deserialize(deserializer, |path| {
+            let mut keys = Vec::new();
+            path_segments(&path, &mut keys);
+            unknown.push(keys);
+        })
+    });
+    let config: Config = match parsed {
+        Ok(config) => config,
+        Err(e) => {
+            let line = e.span().map(|span| source.line_of(span.start));
+            issue(Severity::Error, line, e.message().trim().to_string());
+            return (None, issues);
+        }
+    };
+
+    for keys in unknown {
+        issue(
+            Severity::Error,
+            source.line(&keys),
+            format!("unknown key `{}`", keys.join(".")),
+        );
+    }
+
+    let mut names: BTreeMap<String, usize> = BTreeMap::new();
+    for (i, env) in config.environments.iter().enumerate() {
+        let line = source.line(&segments(&["environments", &i.to_string(), "name"]));
+        if let Some(first) = names.insert(env.name.to_lowercase(), i) {
+            issue(
+                Severity::Error,
+                line,
+                format!(
+                    "environment '{}' is already defined by '{}'; names must be unique (case aside)",
+                    env.name, config.environments[first].name
+                ),
+            );
+        }
+    }
+
+    if let Err(e) = config.validate() {
+        issue(Severity::Error, None, format!("{:#}", e));
+    }
+
+    for (i, env) in config.environments.iter().enumerate() {
+        let index = i.to_string();
+        let mut dirs: Vec<(Vec<String>, &Path)> = Vec::new();
+        if !env.env_yaml_dir.as_os_str().is_empty() {
+            dirs.push((
+                segments(&["environments", &index, "env_yaml_dir"]),
+                &env.env_yaml_dir,
+            ));
+        }
+        for (name, dir) in &env.env_yaml_dir_extra {
+            dirs.push((
+                segments(&["environments", &index, "env_yaml_dir_extra", name]),
+                dir,
+            ));
+        }
+        for (keys, dir) in dirs {
+            if !dir.is_dir() {
+                issue(
+                    Severity::Error,
+                    source.line(&keys),
+                    format!(
+                        "`{}` of environment '{}' points at {}, which is not a directory; clone the manifests repository there or fix the path",
+                        keys[2..].join("."),
+                        env.name,
+                        dir.display()
+                    ),
+                );
+            }
+        }
+    }
+    for (name, service) in &config.services {
+        if let Some(repo) = &service.source_repo
+            && !repo.is_dir()
+        {
+            issue(
+                Severity::Warning,
+                source.line(&segments(&["services", name, "source_repo"])),
+                format!(
+                    "`source_repo` of service '{}' points at {}, which is not a directory; release notes will be skipped",
+                    name,
+                    repo.display()
+                ),
+            );
+        }
+    }
+
+    issues.sort_by_key(|i| i.line);
+    (Some(config), issues)
+}
+
+/// Checks that the kubectl context of every environment is in the local kubeconfig and
+/// that its API server answers.
+pub async fn check_contexts(content: &str, config: &mut Config) -> Vec<ConfigIssue> {
+    let source = SourceMap::new(content);
+    let mut issues = Vec::new();
+    let local = match contexts::local_contexts() {
+        Ok(local) => local,
+        Err(e) => {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                line: None,
+                message: format!("{:#}", e),
+            });
+            return issues;
+        }
+    };
+    config.resolve_context_aliases();
+
+    for (i, env) in config.environments.iter().enumerate() {
+        let line = source.line(&segments(&[
+            "environments",
+            &i.to_string(),
+            "kubectl_context",
+        ]));
+        let mut issue = |severity, message: String| {
+            issues.push(ConfigIssue {
+                severity,
+                line,
+                message,
+            })
+        };
+        if !local
+            .iter()
+            .any(|c: &LocalContext| c.name == env.kubectl_context)
+        {
+            let wanted = env.context_alias.as_ref().unwrap_or(&env.kubectl_context);
+            match (&env.context_alias, &env.gke_cluster) {
+                (_, Some(cluster)) => issue(
+                    Severity::Warning,
+                    format!(
+                        "kubectl context '{}' of environment '{}' is missing; davit offers to fetch the credentials of GKE cluster {} on first use",
+                        wanted, env.name, cluster.name
+                    ),
+                ),
+                (Some(alias), None) => issue(
+                    Severity::Error,
+                    format!(
+                        "no kubeconfig context points at the server of alias '{}' used by environment '{}'; add the cluster to your kubeconfig",
+                        alias, env.name
+                    ),
+                ),
+                (None, None) => issue(
+                    Severity::Error,
+                    format!(
+                        "kubectl context '{}' of environment '{}' is not in your kubeconfig; see `kubectl config get-contexts`",
+                        env.kubectl_context, env.name
+                    ),
+                ),
+            }
+            continue;
+        }
+
+        if let Err(reason) = probe_context(&env.kubectl_context).await {
+            issue(
+                Severity::Error,
+                format!(
+                    "kubectl context '{}' of environment '{}' is unreachable: {}",
+                    env.kubectl_context, env.name, reason
+                ),
+            );
+        }
+    }
+    issues
+}
+
+/// Checks that the API server of the kubectl context answers, with a short timeout.
+pub async fn probe_context(context: &str) -> Result<(), String> {
+    match cluster::client(context).await {
+        Ok(client) => {
+            match tokio::time::timeout(CONTEXT_PROBE_TIMEOUT, client.apiserver_version()).await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err(format!(
+                    "no answer within {}s",
+                    CONTEXT_PROBE_TIMEOUT.as_secs()
+                )),
+            }
+        }
+        Err(e) => Err(format!("{:#}", e)),
+    }
+}
+
+/// Prints the issues as `<file>:<line>: <severity>: <message>`, errors first.
+pub fn print_issues(path: &Path, issues: &[ConfigIssue]) {
+    let mut sorted: Vec<&ConfigIssue> = issues.iter().collect();
+    sorted.sort_by_key(|i| (std::cmp::Reverse(i.severity), i.line));
+    for issue in sorted {
+        let location = match issue.line {
+            Some(line) => format!("{}:{}", path.display(), line),
+            None => path.display().to_string(),
+        };
+        let severity = match issue.severity {
+            Severity::Error => style(issue.severity.to_string()).red().bold(),
+            Severity::Warning => style(issue.severity.to_string()).yellow(),
+            Severity::Info => style(issue.severity.to_string()).dim(),
+        };
+        println!("{}: {}: {}", location, severity, issue.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_reports_unknown_keys_and_duplicates_with_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = format!(
+            r#"[[environments]]
+name = "staging"
+env_yaml_dir = "{dir}"
+kubectl_context = "ctx-staging"
+protectd = true
+
+[[environments]]
+name = "Staging"
+env_yaml_dir = "{dir}/missing"
+kubectl_context = "ctx-staging"
+
+[dashboard]
+old_log_percent = 40
+colour = "dark"
+"#,
+            dir = dir.path().display()
+        );
+
+        let (config, issues) = check(&content);
+        assert!(config.is_some());
+        let found: Vec<(Option<usize>, &str)> = issues
+            .iter()
+            .map(|i| (i.line, i.message.split(';').next().unwrap()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Some(5), "unknown key `environments.0.protectd`"),
+                (
+                    Some(8),
+                    "environment 'Staging' is already defined by 'staging'"
+                ),
+                (
+                    Some(9),
+                    &*format!(
+                        "`env_yaml_dir` of environment 'Staging' points at {}/missing, which is not a directory",
+                        dir.path().display()
+                    )
+                ),
+                (Some(14), "unknown key `dashboard.colour`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_reports_missing_fields_and_rule_violations() {
+        let (config, issues) = check(
+            r#"[[environments]]
+name = "staging"
+env_yaml_dir = "/repo/staging"
+"#,
+        );
+        assert!(config.is_none());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(1));
+        assert!(
+            issues[0]
+                .message
+                .contains("missing field `kubectl_context`")
+        );
+
+        let (_, issues) = check(
+            r#"[[environments]]
+name = "staging"
+kubectl_context = "ctx-staging"
+"#,
+        );
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, None);
+        assert!(issues[0].message.contains("needs `env_yaml_dir`"));
+    }
+}
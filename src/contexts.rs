@@ -0,0 +1,335 @@
+use anyhow::{Context, Result};
+use inquire::Confirm;
+use kube::config::Kubeconfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::config::{Environment, GkeCluster};
+use crate::interactive;
+use crate::mock;
+
+/// Davit-level name of a cluster, resolved on each machine to the kubeconfig context
+/// pointing at the expected API server, whatever that context is called locally.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ContextAlias {
+    /// API server URL identifying the cluster, e.g. `https://35.187.10.20`.
+    pub server: String,
+}
+
+/// A context of the local kubeconfig with the API server of its cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalContext {
+    pub name: String,
+    pub server: Option<String>,
+}
+
+/// Contexts of the local kubeconfig (`KUBECONFIG` or `~/.kube/config`).
+pub fn local_contexts() -> Result<Vec<LocalContext>> {
+    let kubeconfig = Kubeconfig::read().context("Failed to read kubeconfig")?;
+    Ok(kubeconfig
+        .contexts
+        .iter()
+        .map(|named| {
+            let server = named.context.as_ref().and_then(|context| {
+                kubeconfig
+                    .clusters
+                    .iter()
+                    .find(|c| c.name == context.cluster)?
+                    .cluster
+                    .as_ref()?
+                    .server
+                    .clone()
+            });
+            LocalContext {
+                name: named.name.clone(),
+                server,
+            }
+        })
+        .collect())
+}
+
+/// The local context of an alias: among those pointing at its server, the one named
+/// like the alias, else the first by name.
+pub fn resolve<'a>(
+    alias_name: &str,
+    alias: &ContextAlias,
+    local: &'a [LocalContext],
+) -> Option<&'a LocalContext> {
+    let mut candidates: Vec<&LocalContext> = local
+        .iter()
+        .filter(|c| {
+            c.server
+                .as_deref()
+                .is_some_and(|s| same_server(s, &alias.server))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.name.cmp(&b.name));
+    candidates
+        .iter()
+        .find(|c| c.name == alias_name)
+        .or(candidates.first())
+        .copied()
+}
+
+pub fn same_server(lhs: &str, rhs: &str) -> bool {
+    lhs.trim_end_matches('/')
+        .eq_ignore_ascii_case(rhs.trim_end_matches('/'))
+}
+
+/// Offers to create the missing kubeconfig context of an environment with a `gke_cluster`
+/// through `gcloud container clusters get-credentials`, then points the environment at
+/// it. Declining leaves the environment untouched, so the first cluster call fails as usual.
+pub fn ensure_context(
+    aliases: &BTreeMap<String, ContextAlias>,
+    env: &mut Environment,
+) -> Result<()> {
+    if mock::enabled() {
+        return Ok(());
+    }
+    let Some((project, cluster)) = missing_gke_context(env, &local_contexts().unwrap_or_default())
+    else {
+        return Ok(());
+    };
+
+    let wanted = env
+        .context_alias
+        .clone()
+        .unwrap_or(env.kubectl_context.clone());
+    let fetch = interactive::confirm(
+        Confirm::new(&format!(
+            "kubectl context '{}' of {} is missing. Fetch the credentials of GKE cluster {} ({}, project {})?",
+            wanted, env.name, cluster.name, cluster.location, project
+        ))
+        .with_default(true),
+    )
+    .unwrap_or(false);
+    if !fetch {
+        return Ok(());
+    }
+
+    let output = Command::new("gcloud")
+        .args(get_credentials_args(&project, &cluster))
+        .output()
+        .context("Failed to execute gcloud. Is it installed?")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "gcloud get-credentials failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let created = gcloud_context_name(&project, &cluster);
+    match env.context_alias.as_ref().and_then(|a| aliases.get(a)) {
+        Some(alias) => {
+            let local = local_contexts()?;
+            let context = resolve(&wanted, alias, &local).with_context(|| {
+                format!(
+                    "Context {} does not point at {}, the server of alias {}",
+                    created, alias.server, wanted
+                )
+            })?;
+            env.kubectl_context = context.name.clone();
+        }
+        None if created != env.kubectl_context => {
+            let output = Command::new("kubectl")
+                .args(["config", "rename-context", &created, &env.kubectl_context])
+                .output()
+                .context("Failed to execute kubectl")?;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!(
+                    "Failed to rename context {} to {}: {}",
+                    created,
+                    env.kubectl_context,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+        }
+        None => {}
+    }
+    println!("✅ Created kubectl context {}", env.kubectl_context);
+    Ok(())
+}
+
+/// Project and GKE cluster to fetch the credentials of, when the environment has a
+/// `gke_cluster` but its kubeconfig context is not among the local ones.
+fn missing_gke_context(env: &Environment, local: &[LocalContext]) -> Option<(String, GkeCluster)> {
+    let (Some(cluster), Some(project)) = (&env.gke_cluster, &env.gcp_project) else {
+        return None;
+    };
+    if local.iter().any(|c| c.name == env.kubectl_context) {
+        return None;
+    }
+    Some((project.clone(), cluster.clone()))
+}
+
+/// `gcloud` arguments creating the kubeconfig context of a cluster; `--location` takes
+/// either a region or a zone.
+fn get_credentials_args<'a>(project: &'a str, cluster: &'a GkeCluster) -> [&'a str; 8] {
+    [
+        "container",
+        "clusters",
+        "get-credentials",
+        &cluster.name,
+        "--location",
+        &cluster.location,
+        "--project",
+        project,
+    ]
+}
+
+/// Name gcloud gives the contexts it creates.
+fn gcloud_context_name(project: &str, cluster: &GkeCluster) -> String {
+    format!("gke_{}_{}_{}", project, cluster.location, cluster.name)
+}
+
+/// Project and cluster of a context created by gcloud, which GCP names cannot make
+/// ambiguous since none of them may contain underscores.
+pub fn gke_context_cluster(context: &str) -> Option<(String, GkeCluster)> {
+    let mut parts = context.strip_prefix("gke_")?.splitn(3, '_');
+    let (project, location, name) = (parts.next()?, parts.next()?, parts.next()?);
+    if [project, location, name].iter().any(|p| p.is_empty()) {
+        return None;
+    }
+    Some((
+        project.to_string(),
+        GkeCluster {
+            name: name.to_string(),
+            location: location.to_string(),
+        },
+    ))
+}
+
+/// GCP projects visible to the gcloud account; empty when gcloud is missing or not
+/// logged in.
+pub fn gcloud_projects() -> Vec<String> {
+    Command::new("gcloud")
+        .args(["projects", "list", "--format=value(projectId)"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(name: &str, server: &str) -> LocalContext {
+        LocalContext {
+            name: name.to_string(),
+            server: Some(server.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_resolve_matches_server_not_name() {
+        let alias = ContextAlias {
+            server: "https://35.187.10.20/".to_string(),
+        };
+        let laptop = vec![
+            local("prod", "https://10.0.0.1"),
+            local("gke_acme_europe-west1_prod", "https://35.187.10.20"),
+            local("prod-admin", "https://35.187.10.20"),
+        ];
+        assert_eq!(
+            resolve("prod", &alias, &laptop).map(|c| c.name.as_str()),
+            Some("gke_acme_europe-west1_prod")
+        );
+
+        let named_like_alias = vec![
+            local("gke_acme_europe-west1_prod", "https://35.187.10.20"),
+            local("prod", "https://35.187.10.20"),
+        ];
+        assert_eq!(
+            resolve("prod", &alias, &named_like_alias).map(|c| c.name.as_str()),
+            Some("prod")
+        );
+        assert_eq!(resolve("prod", &alias, &laptop[..1]), None);
+    }
+
+    #[test]
+    fn test_gke_context_cluster_inverts_gcloud_naming() {
+        let cluster = GkeCluster {
+            name: "prod".to_string(),
+            location: "europe-west1".to_string(),
+        };
+        let context = gcloud_context_name("acme-prod", &cluster);
+        assert_eq!(
+            gke_context_cluster(&context),
+            Some(("acme-prod".to_string(), cluster))
+        );
+        assert_eq!(gke_context_cluster("minikube"), None);
+        assert_eq!(gke_context_cluster("gke_acme_europe-west1"), None);
+    }
+
+    #[test]
+    fn test_get_credentials_args_name_the_project_and_location() {
+        let regional = GkeCluster {
+            name: "prod".to_string(),
+            location: "europe-west1".to_string(),
+        };
+        assert_eq!(
+            get_credentials_args("acme-prod", &regional),
+            [
+                "container",
+                "clusters",
+                "get-credentials",
+                "prod",
+                "--location",
+                "europe-west1",
+                "--project",
+                "acme-prod"
+            ]
+        );
+
+        let zonal = GkeCluster {
+            name: "staging".to_string(),
+            location: "us-central1-a".to_string(),
+        };
+        assert_eq!(
+            get_credentials_args("acme-staging", &zonal)[3..],
+            [
+                "staging",
+                "--location",
+                "us-central1-a",
+                "--project",
+                "acme-staging"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_gke_context_skips_existing_contexts() {
+        let cluster = GkeCluster {
+            name: "prod".to_string(),
+            location: "europe-west1".to_string(),
+        };
+        let mut env = Environment {
+            name: "production".to_string(),
+            kubectl_context: "prod".to_string(),
+            gke_cluster: Some(cluster.clone()),
+            gcp_project: Some("acme-prod".to_string()),
+            ..Default::default()
+        };
+        let laptop = vec![local("staging", "https://10.0.0.1")];
+        assert_eq!(
+            missing_gke_context(&env, &laptop),
+            Some(("acme-prod".to_string(), cluster))
+        );
+
+        let configured = vec![local("prod", "https://35.187.10.20")];
+        assert_eq!(missing_gke_context(&env, &configured), None);
+
+        env.gcp_project = None;
+        assert_eq!(missing_gke_context(&env, &laptop), None);
+    }
+}
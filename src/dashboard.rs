@@ -1,44 +1,103 @@
 use anyhow::{Context, Result};
+use chrono::{Local, SecondsFormat, Utc};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use futures::StreamExt;
-use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, DeploymentCondition, StatefulSet};
 use k8s_openapi::api::core::v1::Pod;
 use kube::{
     Api, Client,
     api::{ListParams, LogParams},
-    config::KubeConfigOptions,
 };
 use ratatui::{
     Frame, Terminal,
     backend::{Backend, CrosstermBackend},
     layout::{Alignment, Constraint, Direction, Flex, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Clear, List, ListDirection, ListItem, Paragraph, Wrap},
+    text::{Line, Span, Text},
+    widgets::{
+        Block, Borders, Clear, Gauge, List, ListDirection, ListItem, ListState, Paragraph,
+        Sparkline, Wrap,
+    },
 };
+use regex::Regex;
 use std::{
-    collections::{HashSet, VecDeque},
-    io,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, LineWriter, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
     time::{Duration, Instant},
 };
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::config::{DashboardLayout, HealthCheck, LogFields, MetricQuery};
+use crate::health::{self, ProbeSample};
+use crate::keymap::{Action, Keymap};
+use crate::log_format;
+use crate::progressive::Canary;
+use crate::resources::{self, PodUsage};
+use crate::scale::{self, AutoscalerStatus};
+use crate::theme;
+use crate::traffic::{self, ServiceTraffic};
+use crate::{clipboard, cluster, output, prometheus};
 
-const MAX_LOG_LINES: usize = 100;
-const VISIBLE_LOG_LINES: usize = 50;
 const LOG_BATCH_SIZE: usize = 400;
 const UI_POLL_INTERVAL: Duration = Duration::from_millis(16);
 const HEADER_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
 const HEADER_HEIGHT: u16 = 3;
+const ROLLOUT_PANEL_HEIGHT: u16 = 3;
 const POD_PANEL_MIN_HEIGHT: u16 = 4;
 const POD_PANEL_MAX_HEIGHT: u16 = 10;
 const LOG_PANEL_MIN_HEIGHT: u16 = 6;
+const STATUS_BAR_HEIGHT: u16 = 1;
+const OLD_LOG_PERCENT_STEP: u16 = 5;
+const OLD_LOG_PERCENT_RANGE: (u16, u16) = (10, 90);
+const TRAFFIC_REFRESH_INTERVAL: Duration = Duration::from_secs(3);
+const AUTOSCALER_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_HEALTH_SAMPLES: usize = 120;
+const HEALTH_PANEL_HEIGHT: u16 = 4;
+const LEVEL_BUCKET: Duration = Duration::from_secs(5);
+const LEVEL_BUCKETS: usize = 24;
+const LEVEL_PANEL_HEIGHT: u16 = 4;
+const METRICS_REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+const MAX_METRIC_SAMPLES: usize = 60;
+const STATUS_MESSAGE_TTL: Duration = Duration::from_secs(3);
+/// Lines of a restarted container's previous run shown in the crash logs.
+const CRASH_LOG_LINES: i64 = 40;
+const MAX_CRASH_LOGS: usize = 5;
+const CRASH_PANEL_HEIGHT: u16 = 12;
+const RESOURCE_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_USAGE_SAMPLES: usize = 30;
+const RESOURCE_PANEL_MAX_PODS: usize = 6;
+/// New pods using this much more memory on average than the old ones are flagged.
+const MEMORY_REGRESSION_RATIO: f64 = 1.2;
+/// Log lines of the session kept for exporting, beyond the panes' scrollback.
+const MAX_SESSION_LOG_LINES: usize = 100_000;
+/// Container waiting reasons of a new pod that call for a rollback.
+const BROKEN_POD_REASONS: [&str; 3] = ["CrashLoopBackOff", "ImagePullBackOff", "ErrImagePull"];
+
+static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Appends the log lines of every dashboard session to `path`, as with `--log-file`.
+pub fn write_logs_to(path: PathBuf) {
+    let _ = LOG_FILE.set(path);
+}
 
 pub enum DashboardExit {
     UserQuit,
     RolloutCompleted,
+    /// The workload reported that the rollout cannot progress, with the reason.
+    RolloutFailed(String),
+    /// The user asked to roll back after new pods started crash-looping or failing to
+    /// pull their image, with what was seen.
+    RollbackRequested(String),
 }
 
 pub struct Dashboard {
@@ -46,17 +105,57 @@ pub struct Dashboard {
     workload_kind: String,
     env_name: String,
     tag: String,
+    /// What new pods' images contain: the tag, or the digest of a pinned manifest.
+    image_ref: String,
     kubectl_context: String,
     namespace: Option<String>,
     selector: Option<String>,
     container_name: String,
+    /// Containers of the workload's pods, regular ones first, as seen so far.
+    containers: Vec<PodContainer>,
+    /// Containers whose logs are streamed; lines are prefixed with their container
+    /// when there are several.
+    streamed: Vec<String>,
+    /// Container picker opened with `C`.
+    container_picker: Option<ContainerPicker>,
     pods: Vec<PodInfo>,
     old_logs: VecDeque<String>,
     new_logs: VecDeque<String>,
-    tailed_pods: HashSet<String>,
+    /// Log streams by pod and container.
+    log_streams: HashMap<(String, String), JoinHandle<()>>,
+    /// Restart counts by pod and container, to notice new restarts.
+    restart_counts: HashMap<(String, String), i32>,
+    /// Previous-run logs of the latest restarted containers of new pods, oldest first.
+    crash_logs: VecDeque<CrashLog>,
+    crash_logs_expanded: bool,
+    crash_rx: mpsc::UnboundedReceiver<CrashLog>,
+    crash_tx: mpsc::UnboundedSender<CrashLog>,
+    /// CPU and memory usage by pod, from the metrics API.
+    usage: BTreeMap<String, UsageSeries>,
+    /// Why the metrics API could not be read, e.g. no metrics-server.
+    usage_error: Option<String>,
+    usage_rx: mpsc::UnboundedReceiver<std::result::Result<Vec<PodUsage>, String>>,
+    usage_tx: mpsc::UnboundedSender<std::result::Result<Vec<PodUsage>, String>>,
     pod_rx: mpsc::UnboundedReceiver<Vec<Pod>>,
     pod_tx: mpsc::UnboundedSender<Vec<Pod>>,
     rollout_status: RolloutStatus,
+    traffic: Option<Vec<ServiceTraffic>>,
+    /// HorizontalPodAutoscaler of the workload, when it has one.
+    autoscaler: Option<AutoscalerStatus>,
+    autoscaler_rx: mpsc::UnboundedReceiver<Option<AutoscalerStatus>>,
+    autoscaler_tx: mpsc::UnboundedSender<Option<AutoscalerStatus>>,
+    traffic_rx: mpsc::UnboundedReceiver<Vec<ServiceTraffic>>,
+    traffic_tx: mpsc::UnboundedSender<Vec<ServiceTraffic>>,
+    old_levels: LevelRates,
+    new_levels: LevelRates,
+    prometheus_url: Option<String>,
+    metrics: Vec<MetricSeries>,
+    metrics_rx: mpsc::UnboundedReceiver<Vec<MetricResult>>,
+    metrics_tx: mpsc::UnboundedSender<Vec<MetricResult>>,
+    health_check: Option<HealthCheck>,
+    health_samples: VecDeque<ProbeSample>,
+    health_rx: mpsc::UnboundedReceiver<ProbeSample>,
+    health_tx: mpsc::UnboundedSender<ProbeSample>,
     rollout_rx: mpsc::UnboundedReceiver<RolloutStatus>,
     rollout_tx: mpsc::UnboundedSender<RolloutStatus>,
     log_rx: mpsc::UnboundedReceiver<LogLine>,
@@ -64,14 +163,268 @@ pub struct Dashboard {
     completion_modal_visible: bool,
     completion_acknowledged: bool,
     auto_close_on_rollout_complete: bool,
+    /// Log viewer without a rollout: pods are not told apart by version and their logs
+    /// are merged in a single pane.
+    logs_only: bool,
+    selection: Option<LogSelection>,
+    wrap_logs: bool,
+    /// Whether the log panes stick to the newest line; paused panes keep their view
+    /// while lines arrive.
+    follow_logs: bool,
+    /// Pattern highlighted in the log panes, updated live while typed in the prompt.
+    search: Option<Regex>,
+    /// Query being typed after `/`.
+    search_prompt: Option<String>,
+    /// Whether the log panes only show the lines matching the search.
+    filter_logs: bool,
+    /// Lines between the newest one and the bottom of each pane's view.
+    old_scroll: usize,
+    new_scroll: usize,
+    /// Lines ever pushed into each pane, numbering them.
+    old_pushed: u64,
+    new_pushed: u64,
+    /// Pane and number of the latest entry of each pod container, which continuation
+    /// lines join.
+    last_entries: HashMap<(String, String), (LogPane, u64)>,
+    /// Whether grouped stack traces are shown in full rather than folded.
+    expand_traces: bool,
+    /// Every log line of the session, with its pod and old/new marker, for exporting.
+    session_log: VecDeque<String>,
+    /// File of `--log-file`, written as lines arrive.
+    log_file: Option<LineWriter<File>>,
+    layout: DashboardLayout,
+    keymap: Keymap,
+    /// Fields of the service's structured log lines.
+    log_fields: LogFields,
+    last_pod_panel_height: u16,
+    last_log_rows: u16,
+    status_message: Option<(String, Instant)>,
+    /// Key bindings overlay opened with `?`.
+    help_visible: bool,
+    /// When the dashboard opened, right after the manifests were applied.
+    started_at: Instant,
+    first_pod_ready_at: Option<Instant>,
+    rolled_out_at: Option<Instant>,
+    canary: Option<CanaryControl>,
+    canary_rx: mpsc::UnboundedReceiver<(u8, Result<(), String>)>,
+    canary_tx: mpsc::UnboundedSender<(u8, Result<(), String>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PodContainer {
+    name: String,
+    init: bool,
+}
+
+/// Containers being checked in the picker, aligned with `Dashboard::containers`.
+struct ContainerPicker {
+    cursor: usize,
+    checked: Vec<bool>,
+}
+
+/// Traffic shift of a canary driven from the dashboard.
+struct CanaryControl {
+    canary: Canary,
+    weight: u8,
+    /// Weight being applied.
+    pending: Option<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LogPane {
+    Old,
+    New,
+}
+
+/// Visual selection in a log pane. Lines are frozen while selecting so new logs
+/// don't shift the range; offsets count from the newest line.
+struct LogSelection {
+    pane: LogPane,
+    lines: Vec<String>,
+    anchor: usize,
+    cursor: usize,
+}
+
+impl LogSelection {
+    fn range(&self) -> (usize, usize) {
+        (self.anchor.min(self.cursor), self.anchor.max(self.cursor))
+    }
+
+    /// Selected lines, oldest first.
+    fn text(&self) -> String {
+        let (newest, oldest) = self.range();
+        let end = self.lines.len() - newest;
+        let start = self.lines.len() - oldest - 1;
+        self.lines[start..end].join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct LevelBucket {
+    errors: u64,
+    warnings: u64,
+}
+
+/// ERROR/WARN log line counts over a sliding window of fixed-length buckets.
+struct LevelRates {
+    buckets: VecDeque<LevelBucket>,
+    bucket_started: Instant,
+}
+
+impl LevelRates {
+    fn new(now: Instant) -> Self {
+        Self {
+            buckets: VecDeque::from([LevelBucket::default()]),
+            bucket_started: now,
+        }
+    }
+
+    /// Starts new buckets for the time elapsed since the current one began.
+    fn roll(&mut self, now: Instant) {
+        while now.duration_since(self.bucket_started) >= LEVEL_BUCKET {
+            self.bucket_started += LEVEL_BUCKET;
+            self.buckets.push_back(LevelBucket::default());
+            if self.buckets.len() > LEVEL_BUCKETS {
+                self.buckets.pop_front();
+            }
+        }
+    }
+
+    fn record(&mut self, line: &LogLine, now: Instant) {
+        self.roll(now);
+        let level = line.level.as_deref().unwrap_or(line.content.as_str());
+        if let Some(bucket) = self.buckets.back_mut() {
+            if level.contains("ERROR") || level.contains("FATAL") || level.contains("CRITICAL") {
+                bucket.errors += 1;
+            } else if level.contains("WARN") {
+                bucket.warnings += 1;
+            }
+        }
+    }
+
+    fn totals(&self) -> LevelBucket {
+        self.buckets
+            .iter()
+            .fold(LevelBucket::default(), |acc, b| LevelBucket {
+                errors: acc.errors + b.errors,
+                warnings: acc.warnings + b.warnings,
+            })
+    }
+
+    fn errors(&self) -> Vec<u64> {
+        self.buckets.iter().map(|b| b.errors).collect()
+    }
+
+    fn window(&self) -> Duration {
+        LEVEL_BUCKET * self.buckets.len() as u32
+    }
+}
+
+/// CPU (cores) and memory (bytes) samples of a pod over the session.
+#[derive(Default)]
+struct UsageSeries {
+    cpu: VecDeque<f64>,
+    memory: VecDeque<f64>,
+}
+
+impl UsageSeries {
+    fn record(&mut self, usage: &PodUsage) {
+        for (series, value) in [(&mut self.cpu, usage.cpu), (&mut self.memory, usage.memory)] {
+            series.push_back(value);
+            if series.len() > MAX_USAGE_SAMPLES {
+                series.pop_front();
+            }
+        }
+    }
+}
+
+/// Average of the latest samples of `series`, `None` without samples.
+fn latest_average<'a>(series: impl Iterator<Item = &'a VecDeque<f64>>) -> Option<f64> {
+    let latest: Vec<f64> = series.filter_map(|s| s.back().copied()).collect();
+    (!latest.is_empty()).then(|| latest.iter().sum::<f64>() / latest.len() as f64)
+}
+
+fn format_cpu(cores: f64) -> String {
+    format!("{:.0}m", cores * 1000.0)
+}
+
+fn format_memory(bytes: f64) -> String {
+    format!("{:.0}Mi", bytes / (1024.0 * 1024.0))
+}
+
+/// Samples scaled to 0..=100 of the series' maximum, for sparklines.
+fn usage_trend(series: &VecDeque<f64>) -> Vec<u64> {
+    let max = series.iter().copied().fold(f64::EPSILON, f64::max);
+    series
+        .iter()
+        .map(|v| ((v / max) * 100.0).round() as u64)
+        .collect()
+}
+
+type MetricResult = std::result::Result<Option<f64>, String>;
+
+/// Values of one PromQL query over the session. A threshold breach is latched.
+struct MetricSeries {
+    query: MetricQuery,
+    values: VecDeque<f64>,
+    last: Option<MetricResult>,
+    breached: bool,
+}
+
+impl MetricSeries {
+    fn new(query: MetricQuery) -> Self {
+        Self {
+            query,
+            values: VecDeque::with_capacity(MAX_METRIC_SAMPLES),
+            last: None,
+            breached: false,
+        }
+    }
+
+    fn record(&mut self, result: MetricResult) {
+        if let Ok(Some(value)) = result {
+            self.breached |= self.query.is_breached(value);
+            self.values.push_back(value);
+            if self.values.len() > MAX_METRIC_SAMPLES {
+                self.values.pop_front();
+            }
+        }
+        self.last = Some(result);
+    }
+
+    /// Values scaled to 0..=100 between the session's minimum and maximum.
+    fn trend(&self) -> Vec<u64> {
+        let min = self.values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self
+            .values
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let span = (max - min).max(f64::EPSILON);
+        self.values
+            .iter()
+            .map(|v| (((v - min) / span) * 100.0).round() as u64)
+            .collect()
+    }
+}
+
+/// The end of the logs of a container's run before it restarted, where crash reasons are.
+struct CrashLog {
+    pod_name: String,
+    container: String,
+    restarts: i32,
+    lines: Vec<String>,
 }
 
 struct LogLine {
     pod_name: String,
+    container: String,
     content: String,
     level: Option<String>,
     timestamp: Option<String>,
     is_new: bool,
+    /// Whether the line continues the previous entry of its stream, e.g. a stack frame.
+    continuation: bool,
 }
 
 struct PodInfo {
@@ -83,15 +436,26 @@ struct PodInfo {
     restarts: i32,
     age: String,
     is_new: bool,
+    /// Why a container of the pod is not running, e.g. `CrashLoopBackOff`.
+    waiting_reason: Option<String>,
+    /// How the previous run of the most restarted container ended, e.g.
+    /// `OOMKilled, exit 137`.
+    last_termination: Option<String>,
 }
 
 #[derive(Clone, Default)]
 struct RolloutStatus {
     template_matches_tag: bool,
     workload_complete: bool,
+    desired_replicas: i32,
+    updated_replicas: i32,
+    ready_replicas: i32,
+    /// Why the controller gave up on the rollout, e.g. its progress deadline was exceeded.
+    failure: Option<String>,
 }
 
 impl Dashboard {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         service: String,
         workload_kind: String,
@@ -102,26 +466,72 @@ impl Dashboard {
         selector: Option<String>,
         container_name: String,
         auto_close_on_rollout_complete: bool,
+        layout: DashboardLayout,
+        health_check: Option<HealthCheck>,
+        prometheus_url: Option<String>,
+        metrics: Vec<MetricQuery>,
     ) -> Self {
         let (pod_tx, pod_rx) = mpsc::unbounded_channel();
         let (rollout_tx, rollout_rx) = mpsc::unbounded_channel();
         let (log_tx, log_rx) = mpsc::unbounded_channel();
+        let (traffic_tx, traffic_rx) = mpsc::unbounded_channel();
+        let (health_tx, health_rx) = mpsc::unbounded_channel();
+        let (metrics_tx, metrics_rx) = mpsc::unbounded_channel();
+        let (canary_tx, canary_rx) = mpsc::unbounded_channel();
+        let (crash_tx, crash_rx) = mpsc::unbounded_channel();
+        let (usage_tx, usage_rx) = mpsc::unbounded_channel();
+        let (autoscaler_tx, autoscaler_rx) = mpsc::unbounded_channel();
+        // Queries are only charted when the environment has a Prometheus to ask.
+        let metrics = if prometheus_url.is_some() {
+            metrics.into_iter().map(MetricSeries::new).collect()
+        } else {
+            Vec::new()
+        };
         Self {
             service,
             workload_kind,
             env_name,
+            image_ref: tag.clone(),
             tag,
             kubectl_context,
             namespace,
             selector,
+            streamed: vec![container_name.clone()],
             container_name,
+            containers: Vec::new(),
+            container_picker: None,
             pods: Vec::new(),
-            old_logs: VecDeque::with_capacity(MAX_LOG_LINES),
-            new_logs: VecDeque::with_capacity(MAX_LOG_LINES),
-            tailed_pods: HashSet::new(),
+            old_logs: VecDeque::new(),
+            new_logs: VecDeque::new(),
+            log_streams: HashMap::new(),
+            restart_counts: HashMap::new(),
+            crash_logs: VecDeque::new(),
+            crash_logs_expanded: false,
+            crash_rx,
+            crash_tx,
+            usage: BTreeMap::new(),
+            usage_error: None,
+            usage_rx,
+            usage_tx,
             pod_rx,
             pod_tx,
             rollout_status: RolloutStatus::default(),
+            traffic: None,
+            autoscaler: None,
+            autoscaler_rx,
+            autoscaler_tx,
+            old_levels: LevelRates::new(Instant::now()),
+            new_levels: LevelRates::new(Instant::now()),
+            traffic_rx,
+            traffic_tx,
+            prometheus_url,
+            metrics,
+            metrics_rx,
+            metrics_tx,
+            health_check,
+            health_samples: VecDeque::with_capacity(MAX_HEALTH_SAMPLES),
+            health_rx,
+            health_tx,
             rollout_rx,
             rollout_tx,
             log_rx,
@@ -129,24 +539,94 @@ impl Dashboard {
             completion_modal_visible: false,
             completion_acknowledged: false,
             auto_close_on_rollout_complete,
+            logs_only: false,
+            selection: None,
+            wrap_logs: false,
+            follow_logs: true,
+            search: None,
+            search_prompt: None,
+            filter_logs: false,
+            old_scroll: 0,
+            new_scroll: 0,
+            old_pushed: 0,
+            new_pushed: 0,
+            last_entries: HashMap::new(),
+            expand_traces: false,
+            session_log: VecDeque::new(),
+            log_file: None,
+            keymap: Keymap::new(&layout.keys),
+            log_fields: LogFields::default(),
+            layout,
+            last_pod_panel_height: POD_PANEL_MIN_HEIGHT,
+            last_log_rows: 0,
+            status_message: None,
+            help_visible: false,
+            started_at: Instant::now(),
+            first_pod_ready_at: None,
+            rolled_out_at: None,
+            canary: None,
+            canary_rx,
+            canary_tx,
         }
     }
 
+    /// Lets the user shift traffic to `canary` one step at a time. The dashboard reports
+    /// a completed rollout once the canary receives all the traffic; quitting before
+    /// means aborting.
+    pub fn control_canary(&mut self, canary: Canary) {
+        self.canary = Some(CanaryControl {
+            canary,
+            weight: 0,
+            pending: None,
+        });
+    }
+
+    /// Streams the logs of the workload's pods without following a rollout.
+    pub fn logs_only(&mut self) {
+        self.logs_only = true;
+    }
+
+    /// Reads the level, timestamp and message of structured log lines from `fields`.
+    pub fn parse_logs_with(&mut self, fields: LogFields) {
+        self.log_fields = fields;
+    }
+
+    /// Recognizes the new pods by `digest` rather than by tag, for manifests pinned to
+    /// digests.
+    pub fn match_digest(&mut self, digest: &str) {
+        self.image_ref = digest.to_string();
+    }
+
+    /// Names of the metrics whose thresholds were breached during the session.
+    pub fn breached_metrics(&self) -> Vec<String> {
+        self.metrics
+            .iter()
+            .filter(|m| m.breached)
+            .map(|m| m.query.name.clone())
+            .collect()
+    }
+
+    /// When the first new pod became ready and when the rollout completed, if seen.
+    pub fn rollout_timings(&self) -> (Option<Instant>, Option<Instant>) {
+        (self.first_pod_ready_at, self.rolled_out_at)
+    }
+
+    /// Pane sizes as left by the user's adjustments.
+    pub fn layout(&self) -> DashboardLayout {
+        self.layout.clone()
+    }
+
     pub async fn run(&mut self) -> Result<DashboardExit> {
+        if let Some(path) = LOG_FILE.get() {
+            self.log_file = Some(self.open_log_file(path)?);
+        }
         enable_raw_mode()?;
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
 
-        let options = KubeConfigOptions {
-            context: Some(self.kubectl_context.clone()),
-            ..Default::default()
-        };
-        let config = kube::Config::from_kubeconfig(&options)
-            .await
-            .context("Failed to load kubeconfig")?;
-        let client = Client::try_from(config).context("Failed to create K8s client")?;
+        let client = cluster::client(&self.kubectl_context).await?;
 
         let res = self.run_loop(&mut terminal, client).await;
 
@@ -158,9 +638,93 @@ impl Dashboard {
         )?;
         terminal.show_cursor()?;
 
+        if let (Some(path), Some(_)) = (LOG_FILE.get(), self.log_file.take()) {
+            output::progress(format!(
+                "📝 Logs of the session appended to {}",
+                path.display()
+            ));
+        }
         res
     }
 
+    /// Opens the `--log-file` for appending, marking the start of the session.
+    fn open_log_file(&self, path: &Path) -> Result<LineWriter<File>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        let mut file = LineWriter::new(file);
+        writeln!(
+            file,
+            "# davit session: {} in {} at {}, started {}",
+            self.service,
+            self.env_name,
+            self.tag,
+            Local::now().to_rfc3339_opts(SecondsFormat::Secs, false)
+        )?;
+        Ok(file)
+    }
+
+    /// A log line as exported: timestamp (the time received when the line has none),
+    /// old/new marker, pod and container, level and message.
+    fn session_line(&self, log: &LogLine) -> String {
+        let timestamp = log
+            .timestamp
+            .clone()
+            .unwrap_or_else(|| Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+        let marker = match (self.logs_only, log.is_new) {
+            (true, _) => "POD",
+            (false, true) => "NEW",
+            (false, false) => "OLD",
+        };
+        let level = log
+            .level
+            .as_deref()
+            .map(|l| format!("{} ", l))
+            .unwrap_or_default();
+        format!(
+            "{} {} {}/{} {}{}",
+            timestamp, marker, log.pod_name, log.container, level, log.content
+        )
+    }
+
+    /// Keeps a log line for exporting and writes it to the `--log-file`; a failed write
+    /// stops writing the file.
+    fn capture_log(&mut self, log: &LogLine) {
+        let line = self.session_line(log);
+        if let Some(file) = self.log_file.as_mut()
+            && let Err(e) = writeln!(file, "{}", line)
+        {
+            self.log_file = None;
+            self.set_status(&format!("Writing the log file failed: {}", e));
+        }
+        self.session_log.push_back(line);
+        if self.session_log.len() > MAX_SESSION_LOG_LINES {
+            self.session_log.pop_front();
+        }
+    }
+
+    /// Writes the log lines of the session to a new file in `dir`.
+    fn export_logs(&self, dir: &Path) -> Result<PathBuf> {
+        let path = dir.join(format!(
+            "davit-logs-{}-{}-{}.log",
+            self.service,
+            self.env_name,
+            Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        let mut content = self
+            .session_log
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+        content.push('\n');
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
     async fn run_loop<B: Backend>(
         &mut self,
         terminal: &mut Terminal<B>,
@@ -193,31 +757,131 @@ impl Dashboard {
             }
         });
 
-        let rollout_tx = self.rollout_tx.clone();
-        let rollout_kind = self.workload_kind.clone();
-        let rollout_name = self.service.clone();
-        let rollout_tag = self.tag.clone();
-        let rollout_container_name = self.container_name.clone();
-        let rollout_client = client.clone();
-        let rollout_namespace = namespace.clone();
+        let traffic_tx = self.traffic_tx.clone();
+        let traffic_pods_api = pods_api.clone();
+        let traffic_lp = lp.clone();
+        let traffic_client = client.clone();
+        let traffic_namespace = namespace.clone();
         tokio::spawn(async move {
             loop {
-                if let Ok(status) = fetch_rollout_status(
-                    rollout_client.clone(),
-                    &rollout_namespace,
-                    &rollout_kind,
-                    &rollout_name,
-                    &rollout_tag,
-                    &rollout_container_name,
-                )
-                .await
-                {
-                    let _ = rollout_tx.send(status);
+                // Services select pods by label, so match them against the labels of each
+                // version (a canary's version Services select only part of the pods).
+                if let Ok(pod_list) = traffic_pods_api.list(&traffic_lp).await {
+                    let label_sets: Vec<_> = pod_list
+                        .items
+                        .iter()
+                        .filter_map(|p| p.metadata.labels.clone())
+                        .map(|mut labels| {
+                            labels.remove("pod-template-hash");
+                            labels
+                        })
+                        .collect::<HashSet<_>>()
+                        .into_iter()
+                        .collect();
+                    let mut services = BTreeMap::new();
+                    let mut fetched = !label_sets.is_empty();
+                    for labels in &label_sets {
+                        match traffic::fetch(traffic_client.clone(), &traffic_namespace, labels)
+                            .await
+                        {
+                            Ok(found) => {
+                                services.extend(found.into_iter().map(|s| (s.name.clone(), s)))
+                            }
+                            Err(_) => fetched = false,
+                        }
+                    }
+                    if fetched {
+                        let _ = traffic_tx.send(services.into_values().collect());
+                    }
                 }
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                tokio::time::sleep(TRAFFIC_REFRESH_INTERVAL).await;
+            }
+        });
+
+        let usage_tx = self.usage_tx.clone();
+        let usage_client = client.clone();
+        let usage_namespace = namespace.clone();
+        let usage_selector = selector.clone();
+        tokio::spawn(async move {
+            loop {
+                let usage =
+                    resources::pod_usage(usage_client.clone(), &usage_namespace, &usage_selector)
+                        .await
+                        .map_err(|e| e.to_string());
+                let _ = usage_tx.send(usage);
+                tokio::time::sleep(RESOURCE_REFRESH_INTERVAL).await;
             }
         });
 
+        if let Some(base_url) = self.prometheus_url.clone()
+            && !self.metrics.is_empty()
+        {
+            let metrics_tx = self.metrics_tx.clone();
+            let queries: Vec<String> = self.metrics.iter().map(|m| m.query.query.clone()).collect();
+            let http = reqwest::Client::new();
+            tokio::spawn(async move {
+                loop {
+                    let mut results = Vec::with_capacity(queries.len());
+                    for query in &queries {
+                        results.push(
+                            prometheus::query(&http, &base_url, query)
+                                .await
+                                .map_err(|e| e.to_string()),
+                        );
+                    }
+                    let _ = metrics_tx.send(results);
+                    tokio::time::sleep(METRICS_REFRESH_INTERVAL).await;
+                }
+            });
+        }
+
+        if let Some(check) = self.health_check.clone() {
+            let health_tx = self.health_tx.clone();
+            let health_pods_api = pods_api.clone();
+            let health_lp = lp.clone();
+            let health_tag = self.image_ref.clone();
+            let http = reqwest::Client::new();
+            tokio::spawn(async move {
+                loop {
+                    let sample = match (&check.url, check.port) {
+                        (Some(url), _) => Some(health::probe_url(&http, url).await),
+                        (None, Some(port)) => {
+                            // Probe a running pod of the new version.
+                            let pod = health_pods_api
+                                .list(&health_lp)
+                                .await
+                                .ok()
+                                .and_then(|list| {
+                                    list.items.into_iter().find(|p| {
+                                        pod_runs_tag(p, &health_tag)
+                                            && p.status.as_ref().and_then(|s| s.phase.as_deref())
+                                                == Some("Running")
+                                    })
+                                })
+                                .and_then(|p| p.metadata.name);
+                            match pod {
+                                Some(pod) => Some(
+                                    health::probe_pod(&health_pods_api, &pod, port, &check.path)
+                                        .await,
+                                ),
+                                None => None,
+                            }
+                        }
+                        (None, None) => None,
+                    };
+                    if let Some(sample) = sample {
+                        let _ = health_tx.send(sample);
+                    }
+                    tokio::time::sleep(Duration::from_secs(check.interval_secs.max(1))).await;
+                }
+            });
+        }
+
+        if !self.logs_only {
+            self.spawn_rollout_status(client.clone(), namespace.clone());
+            self.spawn_autoscaler_status(client.clone(), namespace.clone());
+        }
+
         let mut last_header_refresh = Instant::now();
         let mut needs_redraw = true;
 
@@ -243,87 +907,48 @@ impl Dashboard {
                                 .or_else(|| s.containers.first())
                         })
                         .and_then(|c| c.image.as_ref())
-                        .map(|image| image.contains(&self.tag))
-                        .unwrap_or(false);
-
-                    if !self.tailed_pods.contains(&name) && status == "Running" {
-                        self.tailed_pods.insert(name.clone());
-                        let tx = self.log_tx.clone();
-                        let api = pods_api.clone();
-                        let p_name = name.clone();
-                        let container = self.container_name.clone();
-                        tokio::spawn(async move {
-                            let lp = LogParams {
-                                follow: true,
-                                tail_lines: Some(10),
-                                container: Some(container),
-                                ..Default::default()
+                        .map(|image| image.contains(&self.image_ref))
+                        .unwrap_or(false)
+                        || self.logs_only;
+
+                    for container in pod_containers(&p) {
+                        if self.containers.iter().all(|c| c.name != container.name) {
+                            let at = if container.init {
+                                self.containers.len()
+                            } else {
+                                self.containers.iter().take_while(|c| !c.init).count()
                             };
+                            self.containers.insert(at, container.clone());
+                        }
+                        let key = (name.clone(), container.name.clone());
+                        if self.streamed.contains(&container.name)
+                            && !self.log_streams.contains_key(&key)
+                            && (status == "Running" || container_started(&p, &container.name))
+                        {
+                            let task = tokio::spawn(stream_logs(
+                                pods_api.clone(),
+                                name.clone(),
+                                container.name,
+                                is_new,
+                                self.log_fields.clone(),
+                                self.log_tx.clone(),
+                            ));
+                            self.log_streams.insert(key, task);
+                        }
+                    }
 
-                            match api.log_stream(&p_name, &lp).await {
-                                Ok(stream) => {
-                                    use futures::io::AsyncBufReadExt;
-                                    let mut lines = stream.lines();
-                                    while let Some(res) = lines.next().await {
-                                        if let Ok(line) = res {
-                                            let raw_content = line.trim();
-                                            let mut log_line = LogLine {
-                                                pod_name: p_name.clone(),
-                                                content: raw_content.to_string(),
-                                                level: None,
-                                                timestamp: None,
-                                                is_new,
-                                            };
-
-                                            // Attempt JSON parsing only when the line looks like JSON.
-                                            if raw_content.starts_with('{')
-                                                && let Ok(v) = serde_json::from_str::<serde_json::Value>(raw_content)
-                                            {
-                                                // Extract level - GKE uses 'severity', others 'level'
-                                                log_line.level = v
-                                                    .get("severity")
-                                                    .or_else(|| v.get("level"))
-                                                    .and_then(|l| l.as_str())
-                                                    .map(|s| s.to_uppercase());
-
-                                                // Extract timestamp - GKE 'timestamp', others 'time' or 'timestamp'
-                                                log_line.timestamp = v
-                                                    .get("timestamp")
-                                                    .or_else(|| v.get("time"))
-                                                    .and_then(|t| t.as_str())
-                                                    .map(|s| s.to_string());
-
-                                                // Extract message - GKE 'message', others 'message' or 'msg' or 'fields.message'
-                                                let msg = v
-                                                    .get("message")
-                                                    .or_else(|| v.get("msg"))
-                                                    .or_else(|| v.get("textPayload"))
-                                                    .or_else(|| {
-                                                        v.get("fields")
-                                                            .and_then(|f| f.get("message"))
-                                                    })
-                                                    .and_then(|m| m.as_str());
-
-                                                if let Some(m) = msg {
-                                                    log_line.content = m.to_string();
-                                                }
-                                            }
-
-                                            let _ = tx.send(log_line);
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    let _ = tx.send(LogLine {
-                                        pod_name: p_name,
-                                        content: format!("Error streaming logs: {}", e),
-                                        level: Some("ERROR".to_string()),
-                                        timestamp: None,
-                                        is_new,
-                                    });
-                                }
-                            }
-                        });
+                    if is_new {
+                        for (container, restarts) in
+                            restarted_containers(&p, &mut self.restart_counts)
+                        {
+                            tokio::spawn(fetch_crash_log(
+                                pods_api.clone(),
+                                name.clone(),
+                                container,
+                                restarts,
+                                self.crash_tx.clone(),
+                            ));
+                        }
                     }
 
                     let container_statuses = p
@@ -343,6 +968,8 @@ impl Dashboard {
                         .as_ref()
                         .map(|t| format_age(t.0))
                         .unwrap_or_else(|| "-".to_string());
+                    let waiting_reason = pod_waiting_reason(&p);
+                    let last_termination = pod_last_termination(&p);
 
                     current_pods.push(PodInfo {
                         name,
@@ -353,15 +980,19 @@ impl Dashboard {
                         restarts,
                         age,
                         is_new,
+                        waiting_reason,
+                        last_termination,
                     });
                 }
                 self.pods = current_pods;
                 self.update_rollout_modal_state();
-                if self.auto_close_on_rollout_complete && self.is_rollout_complete() {
+                if self.auto_close_on_rollout_complete
+                    && let Some(exit) = self.rollout_outcome()
+                {
                     terminal
                         .draw(|f| self.ui(f))
                         .map_err(|e| anyhow::anyhow!("Draw error: {}", e))?;
-                    return Ok(DashboardExit::RolloutCompleted);
+                    return Ok(exit);
                 }
                 needs_redraw = true;
             }
@@ -369,11 +1000,90 @@ impl Dashboard {
             while let Ok(rollout_status) = self.rollout_rx.try_recv() {
                 self.rollout_status = rollout_status;
                 self.update_rollout_modal_state();
-                if self.auto_close_on_rollout_complete && self.is_rollout_complete() {
+                if self.auto_close_on_rollout_complete
+                    && let Some(exit) = self.rollout_outcome()
+                {
                     terminal
                         .draw(|f| self.ui(f))
                         .map_err(|e| anyhow::anyhow!("Draw error: {}", e))?;
-                    return Ok(DashboardExit::RolloutCompleted);
+                    return Ok(exit);
+                }
+                needs_redraw = true;
+            }
+
+            while let Ok(results) = self.metrics_rx.try_recv() {
+                for (series, result) in self.metrics.iter_mut().zip(results) {
+                    series.record(result);
+                }
+                let breached = self.breached_metrics();
+                if self.auto_close_on_rollout_complete && !breached.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Metric thresholds breached: {}",
+                        breached.join(", ")
+                    ));
+                }
+                needs_redraw = true;
+            }
+
+            while let Ok(sample) = self.health_rx.try_recv() {
+                self.health_samples.push_back(sample);
+                if self.health_samples.len() > MAX_HEALTH_SAMPLES {
+                    self.health_samples.pop_front();
+                }
+                needs_redraw = true;
+            }
+
+            while let Ok(crash_log) = self.crash_rx.try_recv() {
+                self.crash_logs.push_back(crash_log);
+                if self.crash_logs.len() > MAX_CRASH_LOGS {
+                    self.crash_logs.pop_front();
+                }
+                self.crash_logs_expanded = true;
+                needs_redraw = true;
+            }
+
+            while let Ok(usage) = self.usage_rx.try_recv() {
+                match usage {
+                    Ok(pods) => {
+                        self.usage
+                            .retain(|name, _| pods.iter().any(|p| &p.pod == name));
+                        for pod in &pods {
+                            self.usage.entry(pod.pod.clone()).or_default().record(pod);
+                        }
+                        self.usage_error = None;
+                    }
+                    Err(e) => self.usage_error = Some(e),
+                }
+                needs_redraw = true;
+            }
+
+            while let Ok(autoscaler) = self.autoscaler_rx.try_recv() {
+                self.autoscaler = autoscaler;
+                needs_redraw = true;
+            }
+
+            while let Ok(traffic) = self.traffic_rx.try_recv() {
+                self.traffic = Some(traffic);
+                needs_redraw = true;
+            }
+
+            while let Ok((weight, result)) = self.canary_rx.try_recv() {
+                let Some(control) = self.canary.as_mut() else {
+                    continue;
+                };
+                control.pending = None;
+                match result {
+                    Ok(()) => {
+                        control.weight = weight;
+                        if weight >= 100 {
+                            terminal
+                                .draw(|f| self.ui(f))
+                                .map_err(|e| anyhow::anyhow!("Draw error: {}", e))?;
+                            return Ok(DashboardExit::RolloutCompleted);
+                        }
+                        self.set_status(&format!("Canary receives {}% of the traffic", weight));
+                    }
+                    Err(e) => self.set_status(&format!("Traffic shift failed: {}", e)),
                 }
                 needs_redraw = true;
             }
@@ -383,23 +1093,35 @@ impl Dashboard {
                 let Ok(log) = self.log_rx.try_recv() else {
                     break;
                 };
+                self.capture_log(&log);
+                let stream = (log.pod_name.clone(), log.container.clone());
+                if log.continuation
+                    && let Some(&(pane, seq)) = self.last_entries.get(&stream)
+                    && self.append_to_entry(pane, seq, &log.content)
+                {
+                    needs_redraw = true;
+                    continue;
+                }
                 let display_line = self.format_log_line(&log);
                 if log.is_new {
-                    self.new_logs.push_back(display_line);
-                    if self.new_logs.len() > MAX_LOG_LINES {
-                        self.new_logs.pop_front();
-                    }
+                    self.new_levels.record(&log, Instant::now());
                 } else {
-                    self.old_logs.push_back(display_line);
-                    if self.old_logs.len() > MAX_LOG_LINES {
-                        self.old_logs.pop_front();
-                    }
+                    self.old_levels.record(&log, Instant::now());
                 }
+                let pane = if log.is_new {
+                    LogPane::New
+                } else {
+                    LogPane::Old
+                };
+                let seq = self.push_log(pane, display_line);
+                self.last_entries.insert(stream, (pane, seq));
                 needs_redraw = true;
             }
 
             if last_header_refresh.elapsed() >= HEADER_REFRESH_INTERVAL {
                 last_header_refresh = Instant::now();
+                self.old_levels.roll(last_header_refresh);
+                self.new_levels.roll(last_header_refresh);
                 needs_redraw = true;
             }
 
@@ -417,10 +1139,15 @@ impl Dashboard {
                     if let Event::Key(key) = event::read()?
                         && matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat)
                     {
-                        if self.completion_modal_visible {
+                        if self.help_visible {
+                            self.help_visible = false;
+                            needs_redraw = true;
+                        } else if self.completion_modal_visible {
                             match key.code {
                                 KeyCode::Enter | KeyCode::Char('c') => {
-                                    return Ok(DashboardExit::RolloutCompleted);
+                                    if let Some(exit) = self.rollout_outcome() {
+                                        return Ok(exit);
+                                    }
                                 }
                                 KeyCode::Esc | KeyCode::Char('k') => {
                                     self.completion_modal_visible = false;
@@ -429,8 +1156,144 @@ impl Dashboard {
                                 }
                                 _ => {}
                             }
-                        } else if let KeyCode::Char('q') = key.code {
-                            return Ok(DashboardExit::UserQuit);
+                        } else if self.container_picker.is_some() {
+                            self.handle_picker_key(key.code);
+                            needs_redraw = true;
+                        } else if self.search_prompt.is_some() {
+                            self.handle_search_key(key.code);
+                            needs_redraw = true;
+                        } else if self.selection.is_some() {
+                            self.handle_selection_key(key);
+                            needs_redraw = true;
+                        } else {
+                            match self.keymap.action(&key) {
+                                Some(Action::Quit) => return Ok(DashboardExit::UserQuit),
+                                Some(Action::Rollback) if !self.logs_only => {
+                                    if let Some((pod, reason)) = self.broken_new_pod() {
+                                        return Ok(DashboardExit::RollbackRequested(format!(
+                                            "{} is in {}",
+                                            pod, reason
+                                        )));
+                                    }
+                                }
+                                Some(Action::CanaryNext) => {
+                                    self.shift_canary(&client);
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Wrap) => {
+                                    self.wrap_logs = !self.wrap_logs;
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Help) => {
+                                    self.help_visible = true;
+                                    needs_redraw = true;
+                                }
+                                Some(Action::NextContainer) => {
+                                    self.cycle_container();
+                                    needs_redraw = true;
+                                }
+                                Some(Action::PickContainers) => {
+                                    self.open_container_picker();
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Resources) => {
+                                    self.layout.show_resources = !self.layout.show_resources;
+                                    needs_redraw = true;
+                                }
+                                Some(Action::ExportLogs) => {
+                                    match self.export_logs(Path::new(".")) {
+                                        Ok(path) => self.set_status(&format!(
+                                            "Saved {} log line(s) to {}",
+                                            self.session_log.len(),
+                                            path.display()
+                                        )),
+                                        Err(e) => self.set_status(&format!("{:#}", e)),
+                                    }
+                                    needs_redraw = true;
+                                }
+                                Some(Action::ExpandTraces) => {
+                                    self.expand_traces = !self.expand_traces;
+                                    needs_redraw = true;
+                                }
+                                Some(Action::CrashLogs) if !self.crash_logs.is_empty() => {
+                                    self.crash_logs_expanded = !self.crash_logs_expanded;
+                                    needs_redraw = true;
+                                }
+                                Some(Action::ScrollUp) => {
+                                    self.scroll_logs(1);
+                                    needs_redraw = true;
+                                }
+                                Some(Action::ScrollDown) => {
+                                    self.scroll_logs(-1);
+                                    needs_redraw = true;
+                                }
+                                Some(Action::PageUp) => {
+                                    self.scroll_logs(self.log_page() as isize);
+                                    needs_redraw = true;
+                                }
+                                Some(Action::PageDown) => {
+                                    self.scroll_logs(-(self.log_page() as isize));
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Top) => {
+                                    self.scroll_logs(isize::MAX);
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Bottom) => {
+                                    self.follow_latest_logs();
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Search) => {
+                                    let query = self.search.as_ref().map(|r| r.to_string());
+                                    self.search_prompt = Some(query.unwrap_or_default());
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Filter) => {
+                                    if self.search.is_some() {
+                                        self.filter_logs = !self.filter_logs;
+                                    } else {
+                                        self.set_status(&format!(
+                                            "Press {} to search before filtering",
+                                            self.keymap.label(Action::Search)
+                                        ));
+                                    }
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Pause) => {
+                                    if self.follow_logs {
+                                        self.follow_logs = false;
+                                    } else {
+                                        self.follow_latest_logs();
+                                    }
+                                    needs_redraw = true;
+                                }
+                                Some(Action::GrowPods) => {
+                                    self.resize_pod_panel(1);
+                                    needs_redraw = true;
+                                }
+                                Some(Action::ShrinkPods) => {
+                                    self.resize_pod_panel(-1);
+                                    needs_redraw = true;
+                                }
+                                Some(Action::ShrinkOldLogs) => {
+                                    self.resize_log_split(-(OLD_LOG_PERCENT_STEP as i16));
+                                    needs_redraw = true;
+                                }
+                                Some(Action::GrowOldLogs) => {
+                                    self.resize_log_split(OLD_LOG_PERCENT_STEP as i16);
+                                    needs_redraw = true;
+                                }
+                                Some(Action::Select) => {
+                                    let pane = if self.new_logs.is_empty() {
+                                        LogPane::Old
+                                    } else {
+                                        LogPane::New
+                                    };
+                                    self.start_selection(pane);
+                                    needs_redraw = true;
+                                }
+                                _ => {}
+                            }
                         }
                     }
                     if !event::poll(Duration::from_millis(0))? {
@@ -441,6 +1304,458 @@ impl Dashboard {
         }
     }
 
+    /// Polls the workload status in the background for the rollout gauge and outcome.
+    fn spawn_rollout_status(&self, client: Client, namespace: String) {
+        let rollout_tx = self.rollout_tx.clone();
+        let rollout_kind = self.workload_kind.clone();
+        let rollout_name = self.service.clone();
+        let rollout_tag = self.image_ref.clone();
+        let rollout_container_name = self.container_name.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Ok(status) = fetch_rollout_status(
+                    client.clone(),
+                    &namespace,
+                    &rollout_kind,
+                    &rollout_name,
+                    &rollout_tag,
+                    &rollout_container_name,
+                )
+                .await
+                {
+                    let _ = rollout_tx.send(status);
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    /// Polls the autoscaler of the workload in the background, if it has one.
+    fn spawn_autoscaler_status(&self, client: Client, namespace: String) {
+        let autoscaler_tx = self.autoscaler_tx.clone();
+        let kind = self.workload_kind.clone();
+        let name = self.service.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Ok(status) =
+                    scale::autoscaler_status(client.clone(), &namespace, &kind, &name).await
+                {
+                    let _ = autoscaler_tx.send(status);
+                }
+                tokio::time::sleep(AUTOSCALER_REFRESH_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Moves the canary to its next traffic step in the background.
+    fn shift_canary(&mut self, client: &Client) {
+        let Some(control) = self.canary.as_mut() else {
+            return;
+        };
+        if control.pending.is_some() {
+            return;
+        }
+        let Some(weight) = control.canary.next_step(control.weight) else {
+            return;
+        };
+        control.pending = Some(weight);
+        let canary = control.canary.clone();
+        let client = client.clone();
+        let canary_tx = self.canary_tx.clone();
+        tokio::spawn(async move {
+            let result = canary
+                .set_weight(client, weight)
+                .await
+                .map_err(|e| format!("{:#}", e));
+            let _ = canary_tx.send((weight, result));
+        });
+    }
+
+    fn pane_logs(&self, pane: LogPane) -> &VecDeque<String> {
+        match pane {
+            LogPane::Old => &self.old_logs,
+            LogPane::New => &self.new_logs,
+        }
+    }
+
+    fn pane_scroll(&self, pane: LogPane) -> usize {
+        match pane {
+            LogPane::Old => self.old_scroll,
+            LogPane::New => self.new_scroll,
+        }
+    }
+
+    /// Appends a line to a pane, dropping the oldest ones beyond the scrollback, and
+    /// returns its sequence number in the pane. A paused pane moves its view back by one
+    /// line so that it keeps showing the same lines.
+    fn push_log(&mut self, pane: LogPane, line: String) -> u64 {
+        let capacity = self.layout.log_scrollback.max(1);
+        let (logs, scroll, pushed) = match pane {
+            LogPane::Old => (
+                &mut self.old_logs,
+                &mut self.old_scroll,
+                &mut self.old_pushed,
+            ),
+            LogPane::New => (
+                &mut self.new_logs,
+                &mut self.new_scroll,
+                &mut self.new_pushed,
+            ),
+        };
+        let seq = *pushed;
+        *pushed += 1;
+        logs.push_back(line);
+        if logs.len() > capacity {
+            logs.pop_front();
+        }
+        if !self.follow_logs {
+            *scroll = (*scroll + 1).min(logs.len().saturating_sub(1));
+        }
+        seq
+    }
+
+    /// Adds a continuation line to the entry pushed as `seq` into `pane`, other streams'
+    /// lines pushed since notwithstanding; `false` once the entry left the scrollback.
+    fn append_to_entry(&mut self, pane: LogPane, seq: u64, line: &str) -> bool {
+        let (logs, pushed) = match pane {
+            LogPane::Old => (&mut self.old_logs, self.old_pushed),
+            LogPane::New => (&mut self.new_logs, self.new_pushed),
+        };
+        let age = (pushed - seq) as usize;
+        let Some(entry) = logs.len().checked_sub(age).and_then(|i| logs.get_mut(i)) else {
+            return false;
+        };
+        entry.push('\n');
+        entry.push_str(line);
+        true
+    }
+
+    /// Moves the view of both panes `lines` back in time (forward when negative),
+    /// pausing the auto-scroll.
+    fn scroll_logs(&mut self, lines: isize) {
+        self.follow_logs = false;
+        let limit = |logs: &VecDeque<String>| logs.len().saturating_sub(1);
+        self.old_scroll = self
+            .old_scroll
+            .saturating_add_signed(lines)
+            .min(limit(&self.old_logs));
+        self.new_scroll = self
+            .new_scroll
+            .saturating_add_signed(lines)
+            .min(limit(&self.new_logs));
+    }
+
+    fn follow_latest_logs(&mut self) {
+        self.follow_logs = true;
+        self.old_scroll = 0;
+        self.new_scroll = 0;
+    }
+
+    /// Lines moved by PageUp/PageDown: the rows of a log pane, less one for context.
+    fn log_page(&self) -> usize {
+        (self.last_log_rows as usize).saturating_sub(1).max(1)
+    }
+
+    fn handle_search_key(&mut self, code: KeyCode) {
+        let Some(query) = self.search_prompt.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Char(c) => query.push(c),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Enter => {
+                self.search_prompt = None;
+                return;
+            }
+            KeyCode::Esc => {
+                self.search_prompt = None;
+                self.search = None;
+                self.filter_logs = false;
+                return;
+            }
+            _ => return,
+        }
+        // An incomplete pattern keeps the last valid one until it compiles again.
+        if query.is_empty() {
+            self.search = None;
+            self.filter_logs = false;
+        } else if let Ok(regex) = Regex::new(query) {
+            self.search = Some(regex);
+        }
+    }
+
+    /// Whether `line` is shown in the log panes under the current filter.
+    fn log_visible(&self, line: &str) -> bool {
+        !self.filter_logs || self.search.as_ref().is_none_or(|r| r.is_match(line))
+    }
+
+    fn start_selection(&mut self, pane: LogPane) {
+        let lines: Vec<String> = self
+            .pane_logs(pane)
+            .iter()
+            .filter(|l| self.log_visible(l))
+            .cloned()
+            .collect();
+        if lines.is_empty() {
+            self.set_status("No log lines to select");
+            self.selection = None;
+            return;
+        }
+        self.selection = Some(LogSelection {
+            pane,
+            lines,
+            anchor: 0,
+            cursor: 0,
+        });
+    }
+
+    fn handle_selection_key(&mut self, key: KeyEvent) {
+        let Some(selection) = self.selection.as_mut() else {
+            return;
+        };
+        if self.keymap.is(&key, Action::SwitchPane) {
+            let other = match selection.pane {
+                LogPane::Old => LogPane::New,
+                LogPane::New => LogPane::Old,
+            };
+            self.start_selection(other);
+            return;
+        }
+        match self.keymap.action(&key) {
+            Some(Action::Select) => {
+                selection.anchor = selection.cursor;
+                return;
+            }
+            Some(Action::Wrap) => {
+                self.wrap_logs = !self.wrap_logs;
+                return;
+            }
+            Some(Action::Quit) => {
+                self.selection = None;
+                return;
+            }
+            _ => {}
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                selection.cursor = (selection.cursor + 1).min(selection.lines.len() - 1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                selection.cursor = selection.cursor.saturating_sub(1);
+            }
+            KeyCode::Char('y') | KeyCode::Enter => {
+                let text = selection.text();
+                let count = text.lines().count();
+                self.selection = None;
+                match clipboard::copy(&text) {
+                    Ok(method) => self.set_status(&format!(
+                        "Copied {} log line(s) to the clipboard ({})",
+                        count, method
+                    )),
+                    Err(e) => self.set_status(&format!("Copy failed: {}", e)),
+                }
+            }
+            KeyCode::Esc => self.selection = None,
+            _ => {}
+        }
+    }
+
+    /// Streams the logs of the next container alone, init containers included.
+    fn cycle_container(&mut self) {
+        if self.containers.len() < 2 {
+            self.set_status("The pods have no other container");
+            return;
+        }
+        let next = self
+            .containers
+            .iter()
+            .position(|c| self.streamed.first() == Some(&c.name))
+            .map_or(0, |i| (i + 1) % self.containers.len());
+        self.stream_containers(vec![self.containers[next].name.clone()]);
+    }
+
+    /// Streams the logs of `containers`, stopping the streams of the others. Streams of
+    /// newly selected containers start with the next pod refresh.
+    fn stream_containers(&mut self, containers: Vec<String>) {
+        self.log_streams.retain(|(_, container), task| {
+            let keep = containers.contains(container);
+            if !keep {
+                task.abort();
+            }
+            keep
+        });
+        self.set_status(&format!("Streaming the logs of {}", containers.join(", ")));
+        self.streamed = containers;
+    }
+
+    fn open_container_picker(&mut self) {
+        if self.containers.is_empty() {
+            self.set_status("No pod seen yet");
+            return;
+        }
+        self.container_picker = Some(ContainerPicker {
+            cursor: 0,
+            checked: self
+                .containers
+                .iter()
+                .map(|c| self.streamed.contains(&c.name))
+                .collect(),
+        });
+    }
+
+    fn handle_picker_key(&mut self, code: KeyCode) {
+        let Some(picker) = self.container_picker.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => picker.cursor = picker.cursor.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                picker.cursor = (picker.cursor + 1).min(picker.checked.len() - 1);
+            }
+            KeyCode::Char(' ') => picker.checked[picker.cursor] = !picker.checked[picker.cursor],
+            KeyCode::Enter => {
+                let selected: Vec<String> = self
+                    .containers
+                    .iter()
+                    .zip(&picker.checked)
+                    .filter(|(_, checked)| **checked)
+                    .map(|(c, _)| c.name.clone())
+                    .collect();
+                if selected.is_empty() {
+                    self.set_status("Select at least one container");
+                    return;
+                }
+                self.container_picker = None;
+                self.stream_containers(selected);
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.container_picker = None,
+            _ => {}
+        }
+    }
+
+    fn resize_pod_panel(&mut self, delta: i16) {
+        let height = self.last_pod_panel_height.saturating_add_signed(delta);
+        self.layout.pod_panel_height = Some(height.max(POD_PANEL_MIN_HEIGHT));
+    }
+
+    fn resize_log_split(&mut self, delta: i16) {
+        let (min, max) = OLD_LOG_PERCENT_RANGE;
+        self.layout.old_log_percent = self
+            .layout
+            .old_log_percent
+            .saturating_add_signed(delta)
+            .clamp(min, max);
+    }
+
+    fn set_status(&mut self, message: &str) {
+        self.status_message = Some((message.to_string(), Instant::now()));
+    }
+
+    fn header_text(&self) -> String {
+        if let Some((message, at)) = &self.status_message
+            && at.elapsed() < STATUS_MESSAGE_TTL
+        {
+            return format!(" {}", message);
+        }
+        if let Some(query) = &self.search_prompt {
+            let invalid = self.search.as_ref().is_none_or(|r| r.as_str() != query);
+            return format!(
+                " SEARCH: /{}{} | Enter: keep | Esc: clear",
+                query,
+                if invalid && !query.is_empty() {
+                    " (invalid regex)"
+                } else {
+                    ""
+                }
+            );
+        }
+        let key = |action| self.keymap.label(action);
+        if self.selection.is_some() {
+            return format!(
+                " SELECT: ↑/↓ extend | {}: set anchor | {}: switch pane | y: copy | Esc: cancel",
+                key(Action::Select),
+                key(Action::SwitchPane)
+            );
+        }
+        if self.container_picker.is_some() {
+            return " CONTAINERS: ↑/↓ move | Space: toggle | Enter: stream | Esc: cancel"
+                .to_string();
+        }
+        if let Some((pod, reason)) = self.broken_new_pod()
+            && self.logs_only
+        {
+            return format!(
+                " ⚠️  Pod {} is in {} | {}: exit",
+                pod,
+                reason,
+                key(Action::Quit)
+            );
+        }
+        if let Some((pod, reason)) = self.broken_new_pod() {
+            return format!(
+                " ⚠️  New pod {} is in {} | {}: roll back (undo the rollout and revert the YAML) | {}: exit",
+                pod,
+                reason,
+                key(Action::Rollback),
+                key(Action::Quit)
+            );
+        }
+        let logs_help = format!(
+            "{}: help | {}: search | {}: filter | {}: select logs | {}/{}: containers | {}: resources | {}: wrap | {}: stack traces | {}: save logs | {}/{}: scroll | {}: follow",
+            key(Action::Help),
+            key(Action::Search),
+            key(Action::Filter),
+            key(Action::Select),
+            key(Action::NextContainer),
+            key(Action::PickContainers),
+            key(Action::Resources),
+            key(Action::Wrap),
+            key(Action::ExpandTraces),
+            key(Action::ExportLogs),
+            key(Action::PageUp),
+            key(Action::PageDown),
+            key(Action::Pause)
+        );
+        let resize_pods = format!("{}/{}", key(Action::GrowPods), key(Action::ShrinkPods));
+        if self.logs_only {
+            return format!(
+                " Davit Logs: {} | Env: {} | Tag: {} ({} | {}: resize | {}: exit)",
+                self.service,
+                self.env_name,
+                self.tag,
+                logs_help,
+                resize_pods,
+                key(Action::Quit)
+            );
+        }
+        if self.canary.is_some() {
+            return format!(
+                " Davit Canary: {} | Env: {} | Tag: {} ({}: help | {}: next traffic step | {}: select logs | {}: wrap | {}: abort canary)",
+                self.service,
+                self.env_name,
+                self.tag,
+                key(Action::Help),
+                key(Action::CanaryNext),
+                key(Action::Select),
+                key(Action::Wrap),
+                key(Action::Quit)
+            );
+        }
+        format!(
+            " Davit Rollout: {} | Env: {} | Tag: {} ({} | {}, {}/{}: resize | {}: exit)",
+            self.service,
+            self.env_name,
+            self.tag,
+            logs_help,
+            resize_pods,
+            key(Action::ShrinkOldLogs),
+            key(Action::GrowOldLogs),
+            key(Action::Quit)
+        )
+    }
+
     fn format_log_line(&self, log: &LogLine) -> String {
         let pod_id = log.pod_name.split('-').next_back().unwrap_or("");
         let ts = log
@@ -452,118 +1767,950 @@ impl Dashboard {
             .unwrap_or_default();
 
         let level = log.level.as_deref().unwrap_or("INFO");
+        if self.streamed.len() > 1 {
+            return format!(
+                "[{}|{}] {}{} {}",
+                pod_id, log.container, ts, level, log.content
+            );
+        }
         format!("[{}] {}{} {}", pod_id, ts, level, log.content)
     }
 
-    fn ui(&self, f: &mut Frame) {
-        let pod_panel_height = self.pod_panel_height(f.area().height);
+    /// Length and color of the `[pod|container]` prefix of a line streamed along with
+    /// other containers.
+    fn container_prefix(&self, line: &str) -> Option<(usize, Color)> {
+        let end = line.find(']')?;
+        let (_, container) = line.strip_prefix('[')?[..end - 1].split_once('|')?;
+        let index = self.containers.iter().position(|c| c.name == container)?;
+        let colors = theme::current().containers;
+        Some((end + 1, colors[index % colors.len()]))
+    }
+
+    fn ui(&mut self, f: &mut Frame) {
+        let traffic_lines = self.traffic_lines();
+        let traffic_height = (traffic_lines.len() as u16).saturating_add(2);
+        let health_height = if self.health_check.is_some() {
+            HEALTH_PANEL_HEIGHT
+        } else {
+            0
+        };
+        let metrics_height = if self.metrics.is_empty() {
+            0
+        } else {
+            self.metrics.len() as u16 + 2
+        };
+        let rollout_height = if self.logs_only {
+            0
+        } else {
+            ROLLOUT_PANEL_HEIGHT
+        };
+        let crash_height = match (self.crash_logs.is_empty(), self.crash_logs_expanded) {
+            (true, _) => 0,
+            (false, false) => 3,
+            (false, true) => CRASH_PANEL_HEIGHT,
+        };
+        let autoscaler_height = match &self.autoscaler {
+            None => 0,
+            Some(_) if self.autoscaler_interference().is_some() => 4,
+            Some(_) => 3,
+        };
+        let resources_height = if self.layout.show_resources {
+            self.resource_rows().len().max(1) as u16 + 3
+        } else {
+            0
+        };
+        let pod_panel_height = self.pod_panel_height(f.area().height.saturating_sub(
+            rollout_height
+                + autoscaler_height
+                + traffic_height
+                + health_height
+                + metrics_height
+                + resources_height
+                + LEVEL_PANEL_HEIGHT
+                + crash_height
+                + STATUS_BAR_HEIGHT,
+        ));
+        self.last_pod_panel_height = pod_panel_height;
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(HEADER_HEIGHT),
+                Constraint::Length(rollout_height),
+                Constraint::Length(autoscaler_height),
                 Constraint::Length(pod_panel_height),
+                Constraint::Length(traffic_height),
+                Constraint::Length(health_height),
+                Constraint::Length(metrics_height),
+                Constraint::Length(resources_height),
+                Constraint::Length(LEVEL_PANEL_HEIGHT),
+                Constraint::Length(crash_height),
                 Constraint::Min(LOG_PANEL_MIN_HEIGHT),
+                Constraint::Length(STATUS_BAR_HEIGHT),
             ])
             .split(f.area());
 
-        let header = Paragraph::new(format!(
-            " Davit Rollout: {} | Env: {} | Tag: {} (Press 'q' to exit)",
-            self.service, self.env_name, self.tag
-        ))
-        .block(Block::default().borders(Borders::ALL));
+        let theme = theme::current();
+        let header_style = if self.broken_new_pod().is_some() {
+            theme.error.add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let header = Paragraph::new(self.header_text())
+            .style(header_style)
+            .block(Block::default().borders(Borders::ALL));
         f.render_widget(header, chunks[0]);
+        if !self.logs_only {
+            self.render_rollout_gauge(f, chunks[1]);
+        }
+        if self.autoscaler.is_some() {
+            self.render_autoscaler(f, chunks[2]);
+        }
 
         let pods: Vec<ListItem> = self
             .pods
             .iter()
             .map(|p| {
-                let style = if p.is_new {
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(Modifier::BOLD)
+                let broken = p
+                    .waiting_reason
+                    .as_deref()
+                    .is_some_and(|reason| BROKEN_POD_REASONS.contains(&reason));
+                let style = if p.is_new && broken {
+                    theme.error.add_modifier(Modifier::BOLD)
+                } else if p.is_new {
+                    theme.success.add_modifier(Modifier::BOLD)
                 } else {
-                    Style::default().fg(Color::DarkGray)
+                    theme.muted
                 };
-                let prefix = if p.is_new { "NEW" } else { "OLD" };
-                let restarts_str = if p.restarts > 0 {
-                    format!("{}r", p.restarts)
+                let prefix = match (self.logs_only, p.is_new) {
+                    (true, _) => "POD",
+                    (false, true) => "NEW",
+                    (false, false) => "OLD",
+                };
+                let mut restarts = format!("restarts:{}", p.restarts);
+                if let Some(termination) = &p.last_termination {
+                    restarts.push_str(&format!(" ({})", termination));
+                }
+                let ready_style = if p.ready_count < p.total_containers {
+                    style.patch(theme.warning)
                 } else {
-                    "0r".to_string()
+                    style
                 };
-                ListItem::new(format!(
-                    " [{prefix}] {name:<48} {status:<12} {ready:<6} {restarts:<5} {age}",
-                    prefix = prefix,
-                    name = p.name,
-                    status = p.status,
-                    ready = p.ready,
-                    restarts = restarts_str,
-                    age = p.age,
-                ))
-                .style(style)
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!(
+                            " [{}] {:<48} {:<16} ",
+                            prefix,
+                            p.name,
+                            p.waiting_reason.as_deref().unwrap_or(&p.status)
+                        ),
+                        style,
+                    ),
+                    Span::styled(format!("ready {:<6}", p.ready), ready_style),
+                    Span::styled(format!("{:<5} ", p.age), style),
+                    Span::styled(restarts, restart_style(p.restarts).unwrap_or(style)),
+                ]))
             })
             .collect();
 
         let pods_list =
             List::new(pods).block(Block::default().title(" Pod Status ").borders(Borders::ALL));
-        f.render_widget(pods_list, chunks[1]);
+        f.render_widget(pods_list, chunks[3]);
+
+        let traffic = List::new(traffic_lines)
+            .block(Block::default().title(" Traffic ").borders(Borders::ALL));
+        f.render_widget(traffic, chunks[4]);
 
         let log_chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(chunks[2]);
+            .constraints([
+                Constraint::Percentage(self.layout.old_log_percent),
+                Constraint::Percentage(100 - self.layout.old_log_percent),
+            ])
+            .split(chunks[10]);
+
+        if let Some(check) = &self.health_check {
+            self.render_health(f, chunks[5], check);
+        }
+        if !self.metrics.is_empty() {
+            self.render_metrics(f, chunks[6]);
+        }
+        if self.layout.show_resources {
+            self.render_resources(f, chunks[7]);
+        }
+        self.render_level_rates(f, chunks[8]);
+        if !self.crash_logs.is_empty() {
+            self.render_crash_logs(f, chunks[9]);
+        }
+        self.last_log_rows = chunks[10].height.saturating_sub(2);
+        if self.logs_only {
+            self.render_log_pane(f, chunks[10], LogPane::New);
+        } else {
+            self.render_log_pane(f, log_chunks[0], LogPane::Old);
+            self.render_log_pane(f, log_chunks[1], LogPane::New);
+        }
+        let status_bar = Paragraph::new(self.status_bar_text(Instant::now()))
+            .style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_widget(status_bar, chunks[11]);
+
+        if self.completion_modal_visible {
+            self.render_completion_modal(f);
+        }
+        if self.container_picker.is_some() {
+            self.render_container_picker(f);
+        }
+        if self.help_visible {
+            self.render_help(f);
+        }
+    }
+
+    /// Time since apply, rollout state, ready pods and the filters applied to the logs.
+    fn status_bar_text(&self, now: Instant) -> String {
+        let elapsed = format_elapsed(now.saturating_duration_since(self.started_at));
+        let ready = |new: bool| {
+            let pods = self.pods.iter().filter(|pod| pod.is_new == new);
+            let ready = pods
+                .clone()
+                .filter(|pod| pod.total_containers > 0 && pod.ready_count == pod.total_containers)
+                .count();
+            format!("{}/{}", ready, pods.count())
+        };
+        let mut parts = if self.logs_only {
+            vec![
+                format!("⏱ {} since start", elapsed),
+                format!("Pods ready {}", ready(true)),
+            ]
+        } else {
+            let status = &self.rollout_status;
+            let rollout = if status.failure.is_some() {
+                "failed".to_string()
+            } else if self.rolled_out_at.is_some() {
+                "complete".to_string()
+            } else if let Some(control) = &self.canary {
+                format!("canary at {}% traffic", control.weight)
+            } else {
+                format!(
+                    "updating {}/{}",
+                    status.updated_replicas, status.desired_replicas
+                )
+            };
+            vec![
+                format!("⏱ {} since apply", elapsed),
+                format!("Rollout: {}", rollout),
+                format!("New pods ready {}", ready(true)),
+                format!("Old pods ready {}", ready(false)),
+            ]
+        };
+
+        let mut filters = Vec::new();
+        if let Some(search) = &self.search {
+            filters.push(format!(
+                "/{}{}",
+                search,
+                if self.filter_logs { " (filtered)" } else { "" }
+            ));
+        }
+        if self.streamed != [self.container_name.as_str()] {
+            filters.push(format!("containers {}", self.streamed.join(",")));
+        }
+        if !self.follow_logs {
+            filters.push("paused".to_string());
+        }
+        if filters.is_empty() {
+            filters.push("none".to_string());
+        }
+        parts.push(format!("Filters: {}", filters.join(" ")));
+        format!(" {}", parts.join(" | "))
+    }
+
+    /// Lines of the help overlay: every action with its keys.
+    fn help_lines(&self) -> Vec<String> {
+        self.keymap
+            .help()
+            .into_iter()
+            .map(|(keys, action)| format!("{:>14}  {}", keys, action.description()))
+            .collect()
+    }
+
+    fn render_help(&self, f: &mut Frame) {
+        let mut lines = self.help_lines();
+        lines.push(String::new());
+        lines.push(format!("{:>14}  Close this help", "any key"));
+        let area = centered_rect(80, lines.len() as u16 + 2, f.area());
+        let help = Paragraph::new(lines.join("\n")).block(
+            Block::default()
+                .title(" Keys ")
+                .borders(Borders::ALL)
+                .border_style(theme::current().info),
+        );
+        f.render_widget(Clear, area);
+        f.render_widget(help, area);
+    }
+
+    /// Replicas of the workload on the new template and ready, out of the desired ones.
+    fn render_rollout_gauge(&self, f: &mut Frame, area: Rect) {
+        let status = &self.rollout_status;
+        let theme = theme::current();
+        let (label, style) = if let Some(failure) = &status.failure {
+            (format!("Rollout failed: {}", failure), theme.error)
+        } else if self.rolled_out_at.is_some() {
+            ("Rollout complete".to_string(), theme.success)
+        } else {
+            (
+                format!(
+                    "updated {}/{} | ready {}/{}",
+                    status.updated_replicas,
+                    status.desired_replicas,
+                    status.ready_replicas,
+                    status.desired_replicas
+                ),
+                theme.warning,
+            )
+        };
+        let gauge = Gauge::default()
+            .block(Block::default().title(" Rollout ").borders(Borders::ALL))
+            .gauge_style(style)
+            .ratio(status.progress())
+            .label(label);
+        f.render_widget(gauge, area);
+    }
+
+    /// How autoscaling skews the rollout's pod counts, while the rollout is in progress.
+    fn autoscaler_interference(&self) -> Option<String> {
+        if self.rolled_out_at.is_some() {
+            return None;
+        }
+        self.autoscaler
+            .as_ref()?
+            .interference(self.rollout_status.desired_replicas)
+    }
+
+    fn render_autoscaler(&self, f: &mut Frame, area: Rect) {
+        let Some(autoscaler) = &self.autoscaler else {
+            return;
+        };
+        let mut summary = format!(
+            " hpa/{}: {} → {} replicas (min {}, max {})",
+            autoscaler.name,
+            autoscaler.current_replicas,
+            autoscaler.desired_replicas,
+            autoscaler.min_replicas,
+            autoscaler.max_replicas
+        );
+        if !autoscaler.metrics.is_empty() {
+            summary.push_str(&format!(" | {}", autoscaler.metrics.join(" | ")));
+        }
+        let mut lines = vec![Line::from(summary)];
+        let interference = self.autoscaler_interference();
+        if let Some(warning) = &interference {
+            lines.push(Line::styled(
+                format!(" ⚠️  {}", warning),
+                theme::current().warning.add_modifier(Modifier::BOLD),
+            ));
+        }
+        let block = Block::default()
+            .title(" Autoscaler ")
+            .borders(Borders::ALL)
+            .border_style(if interference.is_some() {
+                theme::current().warning
+            } else {
+                Style::default()
+            });
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    /// One line per Service routing to the workload: which pods receive traffic and
+    /// the external URLs.
+    fn traffic_lines(&self) -> Vec<ListItem<'static>> {
+        let mut lines: Vec<ListItem<'static>> = self.canary_line().into_iter().collect();
+        lines.extend(self.service_traffic_lines());
+        lines
+    }
+
+    /// Current split between the stable version and the canary.
+    fn canary_line(&self) -> Option<ListItem<'static>> {
+        let control = self.canary.as_ref()?;
+        let (stable, canary) = control.canary.backend_names();
+        let mut line = format!(
+            " {}: {} {}% | {} {}%",
+            control.canary.route_name(),
+            stable,
+            100 - control.weight,
+            canary,
+            control.weight
+        );
+        let theme = theme::current();
+        let style = match (control.pending, control.canary.next_step(control.weight)) {
+            (Some(pending), _) => {
+                line.push_str(&format!(" | shifting to {}%...", pending));
+                theme.warning
+            }
+            (None, Some(next)) => {
+                line.push_str(&format!(" | n: shift to {}%", next));
+                theme.info
+            }
+            (None, None) => theme.success,
+        };
+        Some(ListItem::new(line).style(style.add_modifier(Modifier::BOLD)))
+    }
+
+    fn service_traffic_lines(&self) -> Vec<ListItem<'static>> {
+        let Some(services) = &self.traffic else {
+            return vec![ListItem::new(" Resolving services...").style(theme::current().muted)];
+        };
+        if services.is_empty() {
+            return vec![
+                ListItem::new(" No Service selects these pods").style(theme::current().muted),
+            ];
+        }
 
-        let old_logs: Vec<ListItem> = self
-            .old_logs
+        services
             .iter()
-            .rev()
-            .take(VISIBLE_LOG_LINES)
-            .map(|l| {
-                let style = self.get_log_style(l, Color::DarkGray);
-                ListItem::new(l.as_str()).style(style)
+            .map(|service| {
+                let serving = |is_new: bool| {
+                    let pods: Vec<&PodInfo> =
+                        self.pods.iter().filter(|p| p.is_new == is_new).collect();
+                    let count = pods
+                        .iter()
+                        .filter(|p| service.serving_pods.contains(&p.name))
+                        .count();
+                    (count, pods.len())
+                };
+                let (new_serving, new_total) = serving(true);
+                let (old_serving, _) = serving(false);
+
+                let mut line = format!(
+                    " svc/{}: new pods serving {}/{}, old {}",
+                    service.name, new_serving, new_total, old_serving
+                );
+                if service.not_ready_endpoints > 0 {
+                    line.push_str(&format!(
+                        ", {} endpoint(s) not ready",
+                        service.not_ready_endpoints
+                    ));
+                }
+                if !service.urls.is_empty() {
+                    line.push_str(&format!(" | {}", service.urls.join(" ")));
+                }
+
+                let theme = theme::current();
+                let style = if new_total > 0 && new_serving == new_total {
+                    theme.success
+                } else if new_serving > 0 {
+                    theme.warning
+                } else {
+                    theme.muted
+                };
+                ListItem::new(line).style(style)
             })
+            .collect()
+    }
+
+    /// Current value and trend of each PromQL query, red once its threshold is breached.
+    fn render_metrics(&self, f: &mut Frame, area: Rect) {
+        let theme = theme::current();
+        let failing = self.metrics.iter().any(|m| m.breached);
+        let block = Block::default()
+            .title(if failing {
+                " Metrics - THRESHOLD BREACHED "
+            } else {
+                " Metrics "
+            })
+            .borders(Borders::ALL)
+            .border_style(if failing {
+                theme.error
+            } else {
+                Style::default()
+            });
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let rows = Layout::vertical(vec![Constraint::Length(1); self.metrics.len()]).split(inner);
+
+        for (series, row) in self.metrics.iter().zip(rows.iter()) {
+            let value = match &series.last {
+                None => "...".to_string(),
+                Some(Ok(None)) => "no data".to_string(),
+                Some(Ok(Some(value))) => format!("{:.4}", value),
+                Some(Err(e)) => format!("error: {}", e),
+            };
+            let threshold = match (series.query.min, series.query.max) {
+                (Some(min), Some(max)) => format!("[{}..{}]", min, max),
+                (Some(min), None) => format!("[>= {}]", min),
+                (None, Some(max)) => format!("[<= {}]", max),
+                (None, None) => String::new(),
+            };
+            let style = match &series.last {
+                _ if series.breached => theme.error,
+                Some(Err(_)) => theme.warning,
+                _ => theme.success,
+            };
+            let [text_area, sparkline_area] =
+                Layout::horizontal([Constraint::Length(56), Constraint::Min(0)]).areas(*row);
+            f.render_widget(
+                Paragraph::new(format!(
+                    " {:<20} {:<18} {}",
+                    series.query.name, value, threshold
+                ))
+                .style(style),
+                text_area,
+            );
+            f.render_widget(
+                Sparkline::default().data(series.trend()).style(style),
+                sparkline_area,
+            );
+        }
+    }
+
+    /// ERROR/WARN counts of old vs new pods with an error sparkline each, normalized
+    /// per pod so a new version logging many more errors stands out.
+    /// Pods shown in the resources pane with their usage, new pods first.
+    fn resource_rows(&self) -> Vec<(&PodInfo, &UsageSeries)> {
+        let mut rows: Vec<_> = self
+            .pods
+            .iter()
+            .filter_map(|p| Some((p, self.usage.get(&p.name)?)))
             .collect();
-        let old_list = List::new(old_logs).block(
-            Block::default()
-                .title(" Old Pod Logs ")
-                .borders(Borders::ALL),
-        )
-        .direction(ListDirection::BottomToTop);
-        f.render_widget(old_list, log_chunks[0]);
+        rows.sort_by_key(|(p, _)| !p.is_new);
+        rows.truncate(RESOURCE_PANEL_MAX_PODS);
+        rows
+    }
 
-        let new_logs: Vec<ListItem> = self
-            .new_logs
+    /// Average memory of the new pods against the old ones, with the ratio.
+    fn memory_comparison(&self) -> Option<(f64, f64, f64)> {
+        let average = |is_new: bool| {
+            latest_average(
+                self.pods
+                    .iter()
+                    .filter(|p| p.is_new == is_new)
+                    .filter_map(|p| self.usage.get(&p.name))
+                    .map(|u| &u.memory),
+            )
+        };
+        let (old, new) = (average(false)?, average(true)?);
+        Some((old, new, new / old.max(f64::EPSILON)))
+    }
+
+    /// CPU and memory of each pod with their trend, and the memory of the new pods
+    /// compared to the old ones.
+    fn render_resources(&self, f: &mut Frame, area: Rect) {
+        let theme = theme::current();
+        let regression = self
+            .memory_comparison()
+            .is_some_and(|(_, _, ratio)| ratio >= MEMORY_REGRESSION_RATIO);
+        let block = Block::default()
+            .title(format!(
+                " Resources{} ({}: hide) ",
+                if regression {
+                    " - MEMORY REGRESSION"
+                } else {
+                    ""
+                },
+                self.keymap.label(Action::Resources)
+            ))
+            .borders(Borders::ALL)
+            .border_style(if regression {
+                theme.error
+            } else {
+                Style::default()
+            });
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let rows = self.resource_rows();
+        let lines =
+            Layout::vertical(vec![Constraint::Length(1); rows.len().max(1) + 1]).split(inner);
+
+        let summary = match (&self.usage_error, self.memory_comparison()) {
+            (Some(e), _) => format!(" Metrics API unavailable: {}", e),
+            (None, Some((old, new, ratio))) if !self.logs_only => format!(
+                " Memory: new pods {} vs old {} ({:+.0}%)",
+                format_memory(new),
+                format_memory(old),
+                (ratio - 1.0) * 100.0
+            ),
+            (None, _) if rows.is_empty() => " Waiting for the metrics API...".to_string(),
+            (None, _) => String::new(),
+        };
+        let summary_style = match (&self.usage_error, regression) {
+            (Some(_), _) => theme.warning,
+            (None, true) => theme.error,
+            (None, false) => theme.muted,
+        };
+        f.render_widget(Paragraph::new(summary).style(summary_style), lines[0]);
+
+        for ((pod, usage), row) in rows.iter().zip(lines.iter().skip(1)) {
+            let style = if pod.is_new {
+                theme.success
+            } else {
+                theme.muted
+            };
+            let [name, cpu, cpu_trend, memory, memory_trend] = Layout::horizontal([
+                Constraint::Length(36),
+                Constraint::Length(12),
+                Constraint::Fill(1),
+                Constraint::Length(14),
+                Constraint::Fill(1),
+            ])
+            .areas(*row);
+            let prefix = match (self.logs_only, pod.is_new) {
+                (true, _) => "POD",
+                (false, true) => "NEW",
+                (false, false) => "OLD",
+            };
+            let latest = |series: &VecDeque<f64>| series.back().copied().unwrap_or_default();
+            f.render_widget(
+                Paragraph::new(format!(" [{}] {}", prefix, pod.name)).style(style),
+                name,
+            );
+            f.render_widget(
+                Paragraph::new(format!(" cpu {}", format_cpu(latest(&usage.cpu)))).style(style),
+                cpu,
+            );
+            f.render_widget(
+                Sparkline::default()
+                    .data(usage_trend(&usage.cpu))
+                    .style(style),
+                cpu_trend,
+            );
+            f.render_widget(
+                Paragraph::new(format!(" mem {}", format_memory(latest(&usage.memory))))
+                    .style(style),
+                memory,
+            );
+            f.render_widget(
+                Sparkline::default()
+                    .data(usage_trend(&usage.memory))
+                    .style(style),
+                memory_trend,
+            );
+        }
+    }
+
+    /// The previous-run logs of restarted containers, newest at the bottom; collapsed to
+    /// the last line of the latest restart.
+    fn render_crash_logs(&self, f: &mut Frame, area: Rect) {
+        let red = theme::current().error;
+        let title = format!(
+            " Crash Logs ({} restart(s)) {}: {} ",
+            self.crash_logs.len(),
+            self.keymap.label(Action::CrashLogs),
+            if self.crash_logs_expanded {
+                "collapse"
+            } else {
+                "expand"
+            }
+        );
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(red);
+        let heading = |crash: &CrashLog| {
+            ListItem::new(format!(
+                "── {}/{} before restart #{} ──",
+                crash.pod_name, crash.container, crash.restarts
+            ))
+            .style(red.add_modifier(Modifier::BOLD))
+        };
+        let items: Vec<ListItem> = if self.crash_logs_expanded {
+            self.crash_logs
+                .iter()
+                .flat_map(|crash| {
+                    std::iter::once(heading(crash)).chain(
+                        crash
+                            .lines
+                            .iter()
+                            .map(|line| ListItem::new(line.clone()).style(red)),
+                    )
+                })
+                .rev()
+                .take(area.height.saturating_sub(2) as usize)
+                .collect()
+        } else {
+            self.crash_logs
+                .back()
+                .map(|crash| {
+                    ListItem::new(format!(
+                        "{}/{} restarted ({}): {}",
+                        crash.pod_name,
+                        crash.container,
+                        crash.restarts,
+                        crash
+                            .lines
+                            .last()
+                            .map(String::as_str)
+                            .unwrap_or("no output")
+                    ))
+                    .style(red)
+                })
+                .into_iter()
+                .collect()
+        };
+        let list = List::new(items)
+            .block(block)
+            .direction(ListDirection::BottomToTop);
+        f.render_widget(list, area);
+    }
+
+    fn render_level_rates(&self, f: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .title(format!(
+                " Errors/Warnings (last {}s) ",
+                self.new_levels.window().as_secs()
+            ))
+            .borders(Borders::ALL);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let rows = Layout::vertical([Constraint::Length(1), Constraint::Length(1)]).split(inner);
+
+        for (row, label, rates, is_new) in [
+            (rows[0], "OLD", &self.old_levels, false),
+            (rows[1], "NEW", &self.new_levels, true),
+        ] {
+            let pods = self
+                .pods
+                .iter()
+                .filter(|p| p.is_new == is_new)
+                .count()
+                .max(1);
+            let totals = rates.totals();
+            let [text_area, sparkline_area] =
+                Layout::horizontal([Constraint::Length(44), Constraint::Min(0)]).areas(row);
+            let theme = theme::current();
+            let style = if totals.errors > 0 {
+                theme.error
+            } else if totals.warnings > 0 {
+                theme.warning
+            } else if is_new {
+                theme.success
+            } else {
+                theme.muted
+            };
+
+            f.render_widget(
+                Paragraph::new(format!(
+                    " [{}] ERROR {:<5} WARN {:<5} {:.1} err/pod",
+                    label,
+                    totals.errors,
+                    totals.warnings,
+                    totals.errors as f64 / pods as f64
+                ))
+                .style(style),
+                text_area,
+            );
+            f.render_widget(
+                Sparkline::default().data(rates.errors()).style(style),
+                sparkline_area,
+            );
+        }
+    }
+
+    /// Latest probe result, success ratio and a latency sparkline.
+    fn render_health(&self, f: &mut Frame, area: Rect, check: &HealthCheck) {
+        let block = Block::default()
+            .title(format!(" Health: GET {} ", health::target(check)))
+            .borders(Borders::ALL);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let [summary_area, sparkline_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Length(1)]).areas(inner);
+
+        let Some(last) = self.health_samples.back() else {
+            f.render_widget(
+                Paragraph::new(" Waiting for the first probe...").style(theme::current().muted),
+                summary_area,
+            );
+            return;
+        };
+
+        let healthy = self
+            .health_samples
+            .iter()
+            .filter(|s| s.is_healthy())
+            .count();
+        let result = match (last.status, &last.error) {
+            (Some(status), _) => format!("{} in {}ms", status, last.latency.as_millis()),
+            (None, Some(error)) => format!("error: {}", error),
+            (None, None) => "no response".to_string(),
+        };
+        let style = if last.is_healthy() {
+            theme::current().success
+        } else {
+            theme::current().error
+        };
+        f.render_widget(
+            Paragraph::new(format!(
+                " Last: {} | healthy {}/{}",
+                result,
+                healthy,
+                self.health_samples.len()
+            ))
+            .style(style),
+            summary_area,
+        );
+
+        let latencies: Vec<u64> = self
+            .health_samples
+            .iter()
+            .rev()
+            .take(sparkline_area.width as usize)
+            .rev()
+            .map(|s| s.latency.as_millis() as u64)
+            .collect();
+        f.render_widget(
+            Sparkline::default().data(&latencies).style(style),
+            sparkline_area,
+        );
+    }
+
+    fn render_log_pane(&self, f: &mut Frame, area: Rect, pane: LogPane) {
+        let theme = theme::current();
+        let (title, pane_style) = match pane {
+            _ if self.logs_only => (" Pod Logs ", theme.success),
+            LogPane::Old => (" Old Pod Logs ", theme.muted),
+            LogPane::New => (" New Pod Logs ", theme.success),
+        };
+
+        let Some(selection) = self.selection.as_ref().filter(|s| s.pane == pane) else {
+            let logs = self.pane_logs(pane);
+            let scroll = self.pane_scroll(pane);
+            let items: Vec<ListItem> = logs
+                .iter()
+                .rev()
+                .skip(scroll)
+                .filter(|l| self.log_visible(l))
+                .take(area.height.saturating_sub(2) as usize)
+                .map(|l| {
+                    self.log_item(l, area.width)
+                        .style(self.get_log_style(l, pane_style))
+                })
+                .collect();
+            let mut title = title.to_string();
+            if let Some(regex) = &self.search {
+                let matches = logs.iter().filter(|l| regex.is_match(l)).count();
+                title.push_str(&format!(
+                    "[/{}: {} match(es){}] ",
+                    regex,
+                    matches,
+                    if self.filter_logs { ", filtered" } else { "" }
+                ));
+            }
+            if !self.follow_logs {
+                title.push_str(&format!(
+                    "[paused, {} of {} lines back] ",
+                    scroll,
+                    logs.len()
+                ));
+            }
+            let list = List::new(items)
+                .block(Block::default().title(title).borders(Borders::ALL))
+                .direction(ListDirection::BottomToTop);
+            f.render_widget(list, area);
+            return;
+        };
+
+        let (newest, oldest) = selection.range();
+        let items: Vec<ListItem> = selection
+            .lines
             .iter()
             .rev()
-            .take(VISIBLE_LOG_LINES)
-            .map(|l| {
-                let style = self.get_log_style(l, Color::Green);
-                ListItem::new(l.as_str()).style(style)
+            .enumerate()
+            .map(|(offset, l)| {
+                let mut style = self.get_log_style(l, pane_style);
+                if (newest..=oldest).contains(&offset) {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                self.log_item(l, area.width).style(style)
             })
             .collect();
-        let new_list = List::new(new_logs).block(
-            Block::default()
-                .title(" New Pod Logs ")
-                .borders(Borders::ALL),
-        )
-        .direction(ListDirection::BottomToTop);
-        f.render_widget(new_list, log_chunks[1]);
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(format!("{}[{} selected] ", title, oldest - newest + 1))
+                    .borders(Borders::ALL)
+                    .border_style(theme.warning),
+            )
+            .direction(ListDirection::BottomToTop);
+        let mut state = ListState::default().with_selected(Some(selection.cursor));
+        f.render_stateful_widget(list, area, &mut state);
+    }
 
-        if self.completion_modal_visible {
-            self.render_completion_modal(f);
+    /// A log entry as a list item, soft-wrapped to the pane width when wrapping is on
+    /// so the list scrolls by the real item heights. The continuation lines of an entry
+    /// are folded into a `[+N lines]` marker unless stack traces are expanded.
+    fn log_item(&self, entry: &str, pane_width: u16) -> ListItem<'static> {
+        let mut lines: Vec<String> = entry.split('\n').map(str::to_string).collect();
+        if !self.expand_traces && lines.len() > 1 {
+            let folded = lines.len() - 1;
+            lines.truncate(1);
+            lines[0].push_str(&format!(" [+{} lines]", folded));
         }
+        let width = pane_width.saturating_sub(2).max(1) as usize;
+        let mut text = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if self.wrap_logs {
+                for (j, part) in wrap_line(line, width).iter().enumerate() {
+                    text.push(self.log_line(part, i == 0 && j == 0));
+                }
+            } else {
+                text.push(self.log_line(line, i == 0));
+            }
+        }
+        ListItem::new(Text::from(text))
+    }
+
+    /// A log line with its search matches highlighted and, at its start, the container
+    /// prefix in the container's color.
+    fn log_line(&self, text: &str, first: bool) -> Line<'static> {
+        let (prefix, rest) = match self.container_prefix(text).filter(|_| first) {
+            Some((len, color)) => (
+                Some(Span::styled(
+                    text[..len].to_string(),
+                    Style::default().fg(color),
+                )),
+                &text[len..],
+            ),
+            None => (None, text),
+        };
+        let mut line = match &self.search {
+            Some(regex) => highlight_matches(rest, regex),
+            None => Line::from(rest.to_string()),
+        };
+        if let Some(prefix) = prefix {
+            line.spans.insert(0, prefix);
+        }
+        line
     }
 
     fn pod_panel_height(&self, total_height: u16) -> u16 {
-        let desired_height = (self.pods.len() as u16).saturating_add(2);
-        let clamped_height = desired_height.clamp(POD_PANEL_MIN_HEIGHT, POD_PANEL_MAX_HEIGHT);
+        let clamped_height = self.layout.pod_panel_height.unwrap_or_else(|| {
+            let desired_height = (self.pods.len() as u16).saturating_add(2);
+            desired_height.clamp(POD_PANEL_MIN_HEIGHT, POD_PANEL_MAX_HEIGHT)
+        });
         let available_height = total_height.saturating_sub(HEADER_HEIGHT);
         let max_pod_height = available_height.saturating_sub(LOG_PANEL_MIN_HEIGHT);
 
-        clamped_height.clamp(POD_PANEL_MIN_HEIGHT, max_pod_height.max(POD_PANEL_MIN_HEIGHT))
+        clamped_height.clamp(
+            POD_PANEL_MIN_HEIGHT,
+            max_pod_height.max(POD_PANEL_MIN_HEIGHT),
+        )
     }
 
     fn update_rollout_modal_state(&mut self) {
-        if self.is_rollout_complete() {
+        let now = Instant::now();
+        if self.first_pod_ready_at.is_none()
+            && self.pods.iter().any(|pod| {
+                pod.is_new
+                    && pod.status == "Running"
+                    && pod.total_containers > 0
+                    && pod.ready_count == pod.total_containers
+            })
+        {
+            self.first_pod_ready_at = Some(now);
+        }
+        if self.rolled_out_at.is_none() && self.is_rollout_complete() {
+            self.rolled_out_at = Some(now);
+        }
+
+        // Old pods stay around while a canary runs: it completes through its traffic steps.
+        if self.canary.is_none() && self.rollout_outcome().is_some() {
             if !self.completion_acknowledged {
                 self.completion_modal_visible = true;
             }
@@ -589,15 +2736,45 @@ impl Dashboard {
             && self.rollout_status.workload_complete
     }
 
+    /// A pod of the new tag stuck crash-looping or pulling its image, with the reason.
+    fn broken_new_pod(&self) -> Option<(&str, &str)> {
+        self.pods.iter().filter(|pod| pod.is_new).find_map(|pod| {
+            pod.waiting_reason
+                .as_deref()
+                .filter(|reason| BROKEN_POD_REASONS.contains(reason))
+                .map(|reason| (pod.name.as_str(), reason))
+        })
+    }
+
+    /// How the rollout ended, once it did: completed, or failed as reported by the
+    /// workload controller.
+    fn rollout_outcome(&self) -> Option<DashboardExit> {
+        if self.is_rollout_complete() {
+            return Some(DashboardExit::RolloutCompleted);
+        }
+        self.rollout_status
+            .failure
+            .clone()
+            .map(DashboardExit::RolloutFailed)
+    }
+
     fn render_completion_modal(&self, f: &mut Frame) {
         let area = centered_rect(72, 9, f.area());
-        let text = "Release rollout completed.\nAll impacted pods are on the requested tag and reported ready.\n\nEnter/c: close dashboard and continue\nEsc/k: keep dashboard open";
+        let (title, text) = match self.rollout_outcome() {
+            Some(DashboardExit::RolloutFailed(reason)) => (
+                " Rollout Failed ",
+                format!(
+                    "Release rollout failed: {}\n\nEnter/c: close dashboard and fail the deploy\nEsc/k: keep dashboard open",
+                    reason
+                ),
+            ),
+            _ => (
+                " Rollout Completed ",
+                "Release rollout completed.\nAll impacted pods are on the requested tag and reported ready.\n\nEnter/c: close dashboard and continue\nEsc/k: keep dashboard open".to_string(),
+            ),
+        };
         let modal = Paragraph::new(text)
-            .block(
-                Block::default()
-                    .title(" Rollout Completed ")
-                    .borders(Borders::ALL),
-            )
+            .block(Block::default().title(title).borders(Borders::ALL))
             .alignment(Alignment::Center)
             .wrap(Wrap { trim: true });
 
@@ -605,13 +2782,48 @@ impl Dashboard {
         f.render_widget(modal, area);
     }
 
-    fn get_log_style(&self, line: &str, default_color: Color) -> Style {
+    fn render_container_picker(&self, f: &mut Frame) {
+        let Some(picker) = &self.container_picker else {
+            return;
+        };
+        let theme = theme::current();
+        let area = centered_rect(48, self.containers.len() as u16 + 2, f.area());
+        let items: Vec<ListItem> = self
+            .containers
+            .iter()
+            .zip(&picker.checked)
+            .enumerate()
+            .map(|(index, (container, checked))| {
+                ListItem::new(format!(
+                    "[{}] {}{}",
+                    if *checked { "x" } else { " " },
+                    container.name,
+                    if container.init { " (init)" } else { "" }
+                ))
+                .style(Style::default().fg(theme.containers[index % theme.containers.len()]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(" Stream logs of ")
+                    .borders(Borders::ALL)
+                    .border_style(theme.warning),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(picker.cursor));
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn get_log_style(&self, line: &str, default_style: Style) -> Style {
+        let theme = theme::current();
         if line.contains("ERROR") || line.contains("FATAL") {
-            Style::default().fg(Color::Red)
+            theme.error
         } else if line.contains("WARN") {
-            Style::default().fg(Color::Yellow)
+            theme.warning
         } else {
-            Style::default().fg(default_color)
+            default_style
         }
     }
 }
@@ -665,6 +2877,12 @@ impl RolloutStatus {
         let available_replicas = status.and_then(|s| s.available_replicas);
         let updated_replicas = status.and_then(|s| s.updated_replicas);
         let desired = desired_replicas.unwrap_or(1);
+        // Conditions describe the previous rollout until the controller sees the new spec.
+        let observed = status.and_then(|s| s.observed_generation) >= deployment.metadata.generation;
+        let failure = status
+            .and_then(|s| s.conditions.as_ref())
+            .filter(|_| observed)
+            .and_then(|conditions| deployment_failure(conditions));
 
         Self {
             template_matches_tag: workload_template_matches_tag(spec, container_name, tag),
@@ -672,6 +2890,10 @@ impl RolloutStatus {
                 && ready_replicas.unwrap_or(0) >= desired
                 && available_replicas.unwrap_or(0) >= desired
                 && updated_replicas.unwrap_or(0) >= desired,
+            desired_replicas: desired,
+            updated_replicas: updated_replicas.unwrap_or(0),
+            ready_replicas: ready_replicas.unwrap_or(0),
+            failure,
         }
     }
 
@@ -688,6 +2910,10 @@ impl RolloutStatus {
             workload_complete: desired > 0
                 && ready_replicas.unwrap_or(0) >= desired
                 && updated_replicas.unwrap_or(0) >= desired,
+            desired_replicas: desired,
+            updated_replicas: updated_replicas.unwrap_or(0),
+            ready_replicas: ready_replicas.unwrap_or(0),
+            failure: None,
         }
     }
 
@@ -706,10 +2932,93 @@ impl RolloutStatus {
                 && ready_replicas.unwrap_or(0) >= desired
                 && available_replicas.unwrap_or(0) >= desired
                 && updated_replicas.unwrap_or(0) >= desired,
+            desired_replicas: desired,
+            updated_replicas: updated_replicas.unwrap_or(0),
+            ready_replicas: ready_replicas.unwrap_or(0),
+            failure: None,
+        }
+    }
+
+    /// Share of the desired replicas both updated and ready.
+    fn progress(&self) -> f64 {
+        if self.desired_replicas <= 0 {
+            return 0.0;
         }
+        let done = self.updated_replicas.min(self.ready_replicas);
+        (f64::from(done) / f64::from(self.desired_replicas)).clamp(0.0, 1.0)
     }
 }
 
+/// Why a Deployment stopped progressing: its progress deadline was exceeded or it
+/// cannot create replicas (quota, admission...).
+fn deployment_failure(conditions: &[DeploymentCondition]) -> Option<String> {
+    conditions.iter().find_map(|c| {
+        let failed = match c.type_.as_str() {
+            "Progressing" => c.status == "False",
+            "ReplicaFailure" => c.status == "True",
+            _ => false,
+        };
+        failed.then(|| {
+            let reason = c.reason.clone().unwrap_or_else(|| c.type_.clone());
+            match &c.message {
+                Some(message) => format!("{} ({})", reason, message),
+                None => reason,
+            }
+        })
+    })
+}
+
+/// Waiting reason of the first container of the pod that is not running.
+fn pod_waiting_reason(pod: &Pod) -> Option<String> {
+    pod.status
+        .as_ref()?
+        .container_statuses
+        .iter()
+        .flatten()
+        .find_map(|c| c.state.as_ref()?.waiting.as_ref()?.reason.clone())
+}
+
+/// How the previous run of the pod's most restarted container ended: its reason
+/// (e.g. `OOMKilled`) and exit code.
+fn pod_last_termination(pod: &Pod) -> Option<String> {
+    let container = pod
+        .status
+        .as_ref()?
+        .container_statuses
+        .iter()
+        .flatten()
+        .filter(|c| {
+            c.last_state
+                .as_ref()
+                .is_some_and(|s| s.terminated.is_some())
+        })
+        .max_by_key(|c| c.restart_count)?;
+    let terminated = container.last_state.as_ref()?.terminated.as_ref()?;
+    Some(format!(
+        "{}, exit {}",
+        terminated.reason.as_deref().unwrap_or("Error"),
+        terminated.exit_code
+    ))
+}
+
+/// Restart counts escalate from yellow to bold red; none keeps the pod's style.
+fn restart_style(restarts: i32) -> Option<Style> {
+    match restarts {
+        ..=0 => None,
+        1..=2 => Some(theme::current().warning),
+        3..=5 => Some(theme::current().error),
+        _ => Some(theme::current().error.add_modifier(Modifier::BOLD)),
+    }
+}
+
+pub fn pod_runs_tag(pod: &Pod, tag: &str) -> bool {
+    pod.spec.as_ref().is_some_and(|s| {
+        s.containers
+            .iter()
+            .any(|c| c.image.as_ref().is_some_and(|i| i.contains(tag)))
+    })
+}
+
 fn workload_template_matches_tag<T>(spec: Option<&T>, container_name: &str, tag: &str) -> bool
 where
     T: WorkloadTemplateSpec,
@@ -762,6 +3071,178 @@ impl WorkloadTemplateSpec for k8s_openapi::api::apps::v1::DaemonSetSpec {
     }
 }
 
+/// Follows the logs of a container of a pod, parsing JSON and logfmt lines for their
+/// level, timestamp and message, and flagging the lines continuing the previous one.
+async fn stream_logs(
+    api: Api<Pod>,
+    pod_name: String,
+    container: String,
+    is_new: bool,
+    fields: LogFields,
+    tx: mpsc::UnboundedSender<LogLine>,
+) {
+    let lp = LogParams {
+        follow: true,
+        tail_lines: Some(10),
+        container: Some(container.clone()),
+        ..Default::default()
+    };
+
+    let continuation = log_format::continuation_regex(&fields);
+    match api.log_stream(&pod_name, &lp).await {
+        Ok(stream) => {
+            use futures::io::AsyncBufReadExt;
+            let mut lines = stream.lines();
+            while let Some(res) = lines.next().await {
+                if let Ok(line) = res {
+                    let raw_content = line.trim();
+                    let parsed = log_format::parse(raw_content, &fields);
+                    let continuation = parsed.is_none() && continuation.is_match(line.trim_end());
+                    let parsed = parsed.unwrap_or_default();
+                    // Continuation lines keep their indentation under their entry.
+                    let content = if continuation {
+                        line.trim_end()
+                    } else {
+                        raw_content
+                    };
+                    let log_line = LogLine {
+                        pod_name: pod_name.clone(),
+                        container: container.clone(),
+                        content: parsed.message.unwrap_or_else(|| content.to_string()),
+                        level: parsed.level,
+                        timestamp: parsed.timestamp,
+                        is_new,
+                        continuation,
+                    };
+
+                    let _ = tx.send(log_line);
+                }
+            }
+        }
+        Err(e) => {
+            let _ = tx.send(LogLine {
+                pod_name,
+                container,
+                content: format!("Error streaming logs: {}", e),
+                level: Some("ERROR".to_string()),
+                timestamp: None,
+                is_new,
+                continuation: false,
+            });
+        }
+    }
+}
+
+/// Fetches the end of the logs of the run of a container before its latest restart.
+async fn fetch_crash_log(
+    api: Api<Pod>,
+    pod_name: String,
+    container: String,
+    restarts: i32,
+    tx: mpsc::UnboundedSender<CrashLog>,
+) {
+    let lp = LogParams {
+        previous: true,
+        tail_lines: Some(CRASH_LOG_LINES),
+        container: Some(container.clone()),
+        ..Default::default()
+    };
+    let lines = match api.logs(&pod_name, &lp).await {
+        Ok(output) => output.lines().map(str::to_string).collect(),
+        Err(e) => vec![format!("Failed to fetch the previous logs: {}", e)],
+    };
+    let _ = tx.send(CrashLog {
+        pod_name,
+        container,
+        restarts,
+        lines,
+    });
+}
+
+/// The containers of the pod whose restart count went up since the last call, with
+/// their count; containers seen for the first time count from zero.
+fn restarted_containers(
+    pod: &Pod,
+    restart_counts: &mut HashMap<(String, String), i32>,
+) -> Vec<(String, i32)> {
+    let pod_name = pod.metadata.name.clone().unwrap_or_default();
+    pod.status
+        .iter()
+        .flat_map(|s| {
+            s.init_container_statuses
+                .iter()
+                .flatten()
+                .chain(s.container_statuses.iter().flatten())
+        })
+        .filter(|c| {
+            let seen = restart_counts
+                .insert((pod_name.clone(), c.name.clone()), c.restart_count)
+                .unwrap_or(0);
+            c.restart_count > seen
+        })
+        .map(|c| (c.name.clone(), c.restart_count))
+        .collect()
+}
+
+/// The containers of a pod, regular ones first.
+fn pod_containers(pod: &Pod) -> Vec<PodContainer> {
+    let Some(spec) = pod.spec.as_ref() else {
+        return Vec::new();
+    };
+    let regular = spec.containers.iter().map(|c| PodContainer {
+        name: c.name.clone(),
+        init: false,
+    });
+    let init = spec.init_containers.iter().flatten().map(|c| PodContainer {
+        name: c.name.clone(),
+        init: true,
+    });
+    regular.chain(init).collect()
+}
+
+/// Whether a container of the pod has started, so that it has logs to stream; init
+/// containers run before the pod does.
+fn container_started(pod: &Pod, container: &str) -> bool {
+    pod.status
+        .as_ref()
+        .into_iter()
+        .flat_map(|s| {
+            s.init_container_statuses
+                .iter()
+                .flatten()
+                .chain(s.container_statuses.iter().flatten())
+        })
+        .filter(|c| c.name == container)
+        .filter_map(|c| c.state.as_ref())
+        .any(|state| state.running.is_some() || state.terminated.is_some())
+}
+
+/// Splits a line into chunks of at most `width` characters.
+/// `line` with the matches of `regex` highlighted; the rest keeps the item's style.
+fn highlight_matches(line: &str, regex: &Regex) -> Line<'static> {
+    let highlight = theme::current().highlight;
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for m in regex.find_iter(line).filter(|m| !m.is_empty()) {
+        spans.push(Span::raw(line[last..m.start()].to_string()));
+        spans.push(Span::styled(m.as_str().to_string(), highlight));
+        last = m.end();
+    }
+    spans.push(Span::raw(line[last..].to_string()));
+    Line::from(spans)
+}
+
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(width)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
 fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
     let [vertical] = Layout::vertical([Constraint::Length(height)])
         .flex(Flex::Center)
@@ -772,6 +3253,16 @@ fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
     horizontal
 }
 
+/// `mm:ss`, or `h:mm:ss` past an hour.
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs < 3600 {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+    }
+}
+
 fn format_age(created: k8s_openapi::jiff::Timestamp) -> String {
     let now = k8s_openapi::jiff::Timestamp::now();
     let secs = (now - created).get_seconds();
@@ -787,3 +3278,544 @@ fn format_age(created: k8s_openapi::jiff::Timestamp) -> String {
         format!("{}d{}h", days, hours)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dashboard(layout: DashboardLayout) -> Dashboard {
+        Dashboard::new(
+            "auth-api".to_string(),
+            "Deployment".to_string(),
+            "staging".to_string(),
+            "v2".to_string(),
+            "ctx-staging".to_string(),
+            None,
+            None,
+            "app".to_string(),
+            false,
+            layout,
+            None,
+            None,
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_log_selection_text_is_oldest_first() {
+        let selection = LogSelection {
+            pane: LogPane::New,
+            lines: ["a", "b", "c", "d"].map(String::from).to_vec(),
+            anchor: 2,
+            cursor: 1,
+        };
+        assert_eq!(selection.text(), "b\nc");
+    }
+
+    #[test]
+    fn test_level_rates_count_per_bucket_and_slide() {
+        let start = Instant::now();
+        let line = |level: &str, content: &str| LogLine {
+            pod_name: "auth-api-1".to_string(),
+            container: "app".to_string(),
+            content: content.to_string(),
+            level: (!level.is_empty()).then(|| level.to_string()),
+            timestamp: None,
+            is_new: true,
+            continuation: false,
+        };
+        let mut rates = LevelRates::new(start);
+        rates.record(&line("ERROR", "boom"), start);
+        rates.record(&line("", "WARN disk almost full"), start);
+        rates.record(&line("INFO", "ERROR in message only"), start);
+        rates.record(&line("ERROR", "boom"), start + LEVEL_BUCKET);
+
+        assert_eq!(rates.errors(), vec![1, 1]);
+        assert_eq!(
+            rates.totals(),
+            LevelBucket {
+                errors: 2,
+                warnings: 1
+            }
+        );
+
+        rates.roll(start + LEVEL_BUCKET * (LEVEL_BUCKETS as u32 + 1));
+        assert_eq!(rates.buckets.len(), LEVEL_BUCKETS);
+        assert_eq!(rates.totals(), LevelBucket::default());
+    }
+
+    #[test]
+    fn test_metric_series_latches_breach() {
+        let mut series = MetricSeries::new(MetricQuery {
+            name: "error rate".to_string(),
+            query: "sum(rate(errors[1m]))".to_string(),
+            min: None,
+            max: Some(0.05),
+        });
+        series.record(Ok(Some(0.01)));
+        assert!(!series.breached);
+        series.record(Ok(Some(0.2)));
+        series.record(Ok(Some(0.02)));
+        assert!(series.breached);
+        assert_eq!(series.trend(), vec![0, 100, 5]);
+    }
+
+    #[test]
+    fn test_wrap_line_splits_at_width() {
+        assert_eq!(wrap_line("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(wrap_line("", 3), vec![""]);
+    }
+
+    #[test]
+    fn test_deployment_rollout_failure_needs_observed_generation() {
+        let deployment = |observed: i64| -> Deployment {
+            serde_json::from_value(serde_json::json!({
+                "metadata": { "name": "auth-api", "generation": 4 },
+                "spec": {
+                    "replicas": 3,
+                    "selector": {},
+                    "template": { "spec": { "containers": [
+                        { "name": "app", "image": "gcr.io/p/auth-api:v2" }
+                    ] } }
+                },
+                "status": {
+                    "observedGeneration": observed,
+                    "updatedReplicas": 2,
+                    "readyReplicas": 1,
+                    "conditions": [{
+                        "type": "Progressing",
+                        "status": "False",
+                        "reason": "ProgressDeadlineExceeded",
+                        "message": "ReplicaSet \"auth-api-7d\" has timed out progressing."
+                    }]
+                }
+            }))
+            .unwrap()
+        };
+
+        let stale = RolloutStatus::from_deployment(&deployment(3), "v2", "app");
+        assert_eq!(stale.failure, None);
+        assert!((stale.progress() - 1.0 / 3.0).abs() < f64::EPSILON);
+
+        let failed = RolloutStatus::from_deployment(&deployment(4), "v2", "app");
+        assert_eq!(
+            failed.failure.as_deref(),
+            Some(
+                "ProgressDeadlineExceeded (ReplicaSet \"auth-api-7d\" has timed out progressing.)"
+            )
+        );
+        assert!(failed.template_matches_tag && !failed.workload_complete);
+    }
+
+    #[test]
+    fn test_pod_waiting_reason_and_last_termination_report_crash_loop() {
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "auth-api-7d-x2k" },
+            "status": {
+                "phase": "Running",
+                "containerStatuses": [
+                    {
+                        "name": "sidecar", "image": "envoy", "imageID": "", "ready": true,
+                        "restartCount": 0, "state": { "running": {} }
+                    },
+                    {
+                        "name": "app", "image": "gcr.io/p/auth-api:v2", "imageID": "",
+                        "ready": false, "restartCount": 4,
+                        "state": { "waiting": { "reason": "CrashLoopBackOff" } },
+                        "lastState": { "terminated": { "reason": "OOMKilled", "exitCode": 137 } }
+                    }
+                ]
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            pod_waiting_reason(&pod).as_deref(),
+            Some("CrashLoopBackOff")
+        );
+        assert_eq!(pod_waiting_reason(&Pod::default()), None);
+        assert_eq!(
+            pod_last_termination(&pod).as_deref(),
+            Some("OOMKilled, exit 137")
+        );
+        assert_eq!(pod_last_termination(&Pod::default()), None);
+        assert_eq!(restart_style(0), None);
+        assert_eq!(restart_style(1), Some(theme::current().warning));
+        assert_eq!(restart_style(4), Some(theme::current().error));
+    }
+
+    #[test]
+    fn test_pod_last_termination_reports_the_most_restarted_container() {
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "auth-api-7d-x2k" },
+            "status": {
+                "phase": "Running",
+                "containerStatuses": [
+                    {
+                        "name": "migrate", "image": "gcr.io/p/auth-api:v2", "imageID": "",
+                        "ready": true, "restartCount": 1, "state": { "running": {} },
+                        "lastState": { "terminated": { "reason": "OOMKilled", "exitCode": 137 } }
+                    },
+                    {
+                        "name": "app", "image": "gcr.io/p/auth-api:v2", "imageID": "",
+                        "ready": true, "restartCount": 7, "state": { "running": {} },
+                        "lastState": { "terminated": { "exitCode": 1 } }
+                    }
+                ]
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(pod_last_termination(&pod).as_deref(), Some("Error, exit 1"));
+        assert_eq!(
+            restart_style(7),
+            Some(theme::current().error.add_modifier(Modifier::BOLD))
+        );
+    }
+
+    #[test]
+    fn test_paused_logs_keep_their_view_within_the_scrollback() {
+        let layout = DashboardLayout {
+            log_scrollback: 5,
+            ..DashboardLayout::default()
+        };
+        let mut dashboard = test_dashboard(layout);
+        for i in 0..4 {
+            dashboard.push_log(LogPane::New, format!("line {}", i));
+        }
+        assert_eq!(dashboard.new_scroll, 0);
+
+        dashboard.scroll_logs(2);
+        assert!(!dashboard.follow_logs);
+        assert_eq!(dashboard.new_scroll, 2);
+
+        dashboard.push_log(LogPane::New, "line 4".to_string());
+        dashboard.push_log(LogPane::New, "line 5".to_string());
+        assert_eq!(dashboard.new_logs.len(), 5);
+        assert_eq!(dashboard.new_scroll, 4);
+
+        dashboard.scroll_logs(isize::MAX);
+        assert_eq!(dashboard.new_scroll, 4);
+        dashboard.scroll_logs(-10);
+        assert_eq!(dashboard.new_scroll, 0);
+        assert!(!dashboard.follow_logs);
+
+        dashboard.follow_latest_logs();
+        dashboard.push_log(LogPane::New, "line 6".to_string());
+        assert_eq!(dashboard.new_scroll, 0);
+        assert_eq!(
+            dashboard.new_logs.front().map(String::as_str),
+            Some("line 2")
+        );
+    }
+
+    #[test]
+    fn test_continuation_lines_join_their_entry_across_interleaved_lines() {
+        let layout = DashboardLayout {
+            log_scrollback: 3,
+            ..DashboardLayout::default()
+        };
+        let mut dashboard = test_dashboard(layout);
+        let seq = dashboard.push_log(LogPane::New, "[x2k] ERROR request failed".to_string());
+        dashboard.push_log(LogPane::New, "[p9z] INFO healthy".to_string());
+        assert!(dashboard.append_to_entry(LogPane::New, seq, "java.lang.IllegalStateException"));
+        assert!(dashboard.append_to_entry(LogPane::New, seq, "\tat com.acme.Pool.take"));
+        assert_eq!(
+            dashboard.new_logs[0],
+            "[x2k] ERROR request failed\njava.lang.IllegalStateException\n\tat com.acme.Pool.take"
+        );
+        let folded = dashboard.log_item(&dashboard.new_logs[0], 80);
+        assert_eq!(folded.height(), 1);
+        dashboard.expand_traces = true;
+        assert_eq!(dashboard.log_item(&dashboard.new_logs[0], 80).height(), 3);
+
+        for i in 0..3 {
+            dashboard.push_log(LogPane::New, format!("[p9z] INFO tick {}", i));
+        }
+        assert!(!dashboard.append_to_entry(LogPane::New, seq, "\tat com.acme.Main.run"));
+    }
+
+    #[test]
+    fn test_session_logs_keep_pod_and_marker_and_export_to_a_file() -> Result<()> {
+        let mut dashboard = test_dashboard(DashboardLayout::default());
+        dashboard.capture_log(&LogLine {
+            pod_name: "auth-api-7d-x2k".to_string(),
+            container: "app".to_string(),
+            content: "pool exhausted".to_string(),
+            level: Some("ERROR".to_string()),
+            timestamp: Some("2024-05-01T10:00:00Z".to_string()),
+            is_new: true,
+            continuation: false,
+        });
+        dashboard.capture_log(&LogLine {
+            pod_name: "auth-api-5c-p9z".to_string(),
+            container: "app".to_string(),
+            content: "\tat com.acme.Pool.take".to_string(),
+            level: None,
+            timestamp: Some("2024-05-01T10:00:01Z".to_string()),
+            is_new: false,
+            continuation: true,
+        });
+
+        let dir = tempfile::tempdir()?;
+        let path = dashboard.export_logs(dir.path())?;
+        assert_eq!(
+            std::fs::read_to_string(path)?,
+            "2024-05-01T10:00:00Z NEW auth-api-7d-x2k/app ERROR pool exhausted\n\
+             2024-05-01T10:00:01Z OLD auth-api-5c-p9z/app \tat com.acme.Pool.take\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_updates_live_and_filters_logs() {
+        let mut dashboard = test_dashboard(DashboardLayout::default());
+        dashboard.search_prompt = Some(String::new());
+        for c in "req-4[".chars() {
+            dashboard.handle_search_key(KeyCode::Char(c));
+        }
+        // The unbalanced bracket does not compile: the last valid pattern stays.
+        assert_eq!(dashboard.search.as_ref().map(Regex::as_str), Some("req-4"));
+        dashboard.handle_search_key(KeyCode::Char('2'));
+        dashboard.handle_search_key(KeyCode::Char(']'));
+        dashboard.handle_search_key(KeyCode::Enter);
+        assert_eq!(dashboard.search_prompt, None);
+
+        dashboard.filter_logs = true;
+        assert!(dashboard.log_visible("GET /users req-42 200"));
+        assert!(!dashboard.log_visible("GET /users req-7 200"));
+
+        let line = highlight_matches("a req-42 b", dashboard.search.as_ref().unwrap());
+        let spans: Vec<&str> = line.spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(spans, ["a ", "req-42", " b"]);
+
+        dashboard.search_prompt = Some("req-42".to_string());
+        dashboard.handle_search_key(KeyCode::Esc);
+        assert!(dashboard.search.is_none());
+        assert!(dashboard.log_visible("GET /users req-7 200"));
+    }
+
+    #[test]
+    fn test_container_picker_streams_several_containers_with_prefixes() {
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "auth-api-7d-x2k" },
+            "spec": {
+                "initContainers": [{ "name": "migrate", "image": "gcr.io/p/auth-api:v2" }],
+                "containers": [
+                    { "name": "app", "image": "gcr.io/p/auth-api:v2" },
+                    { "name": "envoy", "image": "envoyproxy/envoy:v1.30" }
+                ]
+            },
+            "status": {
+                "phase": "Pending",
+                "initContainerStatuses": [{
+                    "name": "migrate", "image": "gcr.io/p/auth-api:v2", "imageID": "",
+                    "ready": false, "restartCount": 0, "state": { "running": {} }
+                }]
+            }
+        }))
+        .unwrap();
+        assert!(container_started(&pod, "migrate"));
+        assert!(!container_started(&pod, "app"));
+
+        let mut dashboard = test_dashboard(DashboardLayout::default());
+        dashboard.containers = pod_containers(&pod);
+        assert_eq!(
+            dashboard
+                .containers
+                .iter()
+                .map(|c| (c.name.as_str(), c.init))
+                .collect::<Vec<_>>(),
+            [("app", false), ("envoy", false), ("migrate", true)]
+        );
+        dashboard.cycle_container();
+        dashboard.cycle_container();
+        assert_eq!(dashboard.streamed, ["migrate"]);
+
+        dashboard.open_container_picker();
+        dashboard.handle_picker_key(KeyCode::Char(' '));
+        dashboard.handle_picker_key(KeyCode::Down);
+        dashboard.handle_picker_key(KeyCode::Char(' '));
+        dashboard.handle_picker_key(KeyCode::Enter);
+        assert!(dashboard.container_picker.is_none());
+        assert_eq!(dashboard.streamed, ["app", "envoy", "migrate"]);
+
+        let line = dashboard.format_log_line(&LogLine {
+            pod_name: "auth-api-7d-x2k".to_string(),
+            container: "envoy".to_string(),
+            content: "upstream reset".to_string(),
+            level: Some("WARN".to_string()),
+            timestamp: None,
+            is_new: true,
+            continuation: false,
+        });
+        assert_eq!(line, "[x2k|envoy] WARN upstream reset");
+        assert_eq!(
+            dashboard.container_prefix(&line),
+            Some((11, theme::current().containers[1]))
+        );
+        assert_eq!(dashboard.container_prefix("[x2k] INFO ready"), None);
+    }
+
+    #[test]
+    fn test_restarted_containers_reports_new_restarts_once() {
+        let pod = |restarts: i32| -> Pod {
+            serde_json::from_value(serde_json::json!({
+                "metadata": { "name": "auth-api-7d-x2k" },
+                "status": {
+                    "phase": "Running",
+                    "containerStatuses": [
+                        {
+                            "name": "app", "image": "gcr.io/p/auth-api:v2", "imageID": "",
+                            "ready": false, "restartCount": restarts,
+                            "state": { "waiting": { "reason": "CrashLoopBackOff" } }
+                        },
+                        {
+                            "name": "envoy", "image": "envoy", "imageID": "", "ready": true,
+                            "restartCount": 0, "state": { "running": {} }
+                        }
+                    ]
+                }
+            }))
+            .unwrap()
+        };
+        let mut counts = HashMap::new();
+        assert!(restarted_containers(&pod(0), &mut counts).is_empty());
+        assert_eq!(
+            restarted_containers(&pod(1), &mut counts),
+            [("app".to_string(), 1)]
+        );
+        assert!(restarted_containers(&pod(1), &mut counts).is_empty());
+        assert_eq!(
+            restarted_containers(&pod(3), &mut counts),
+            [("app".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_memory_comparison_flags_new_pods_using_more() {
+        let mut dashboard = test_dashboard(DashboardLayout::default());
+        let pod = |name: &str, is_new: bool| PodInfo {
+            name: name.to_string(),
+            status: "Running".to_string(),
+            ready: "1/1".to_string(),
+            ready_count: 1,
+            total_containers: 1,
+            restarts: 0,
+            age: "1m".to_string(),
+            is_new,
+            waiting_reason: None,
+            last_termination: None,
+        };
+        dashboard.pods = vec![pod("api-old", false), pod("api-new", true)];
+        assert_eq!(dashboard.memory_comparison(), None);
+
+        let mib = 1024.0 * 1024.0;
+        for (name, memory) in [("api-old", 200.0), ("api-new", 180.0), ("api-new", 300.0)] {
+            dashboard
+                .usage
+                .entry(name.to_string())
+                .or_default()
+                .record(&PodUsage {
+                    pod: name.to_string(),
+                    cpu: 0.12,
+                    memory: memory * mib,
+                });
+        }
+        let (old, new, ratio) = dashboard.memory_comparison().unwrap();
+        assert_eq!(
+            (format_memory(old), format_memory(new)),
+            ("200Mi".to_string(), "300Mi".to_string())
+        );
+        assert!(ratio >= MEMORY_REGRESSION_RATIO);
+        assert_eq!(
+            usage_trend(&dashboard.usage["api-new"].memory),
+            vec![60, 100]
+        );
+        assert_eq!(format_cpu(0.12), "120m");
+        assert_eq!(dashboard.resource_rows()[0].0.name, "api-new");
+    }
+
+    #[test]
+    fn test_logs_only_dashboard_drops_the_rollout_from_header_and_status_bar() {
+        let mut dashboard = test_dashboard(DashboardLayout::default());
+        dashboard.logs_only();
+        dashboard.pods = vec![PodInfo {
+            name: "api-1".to_string(),
+            status: "Running".to_string(),
+            ready: "1/1".to_string(),
+            ready_count: 1,
+            total_containers: 1,
+            restarts: 0,
+            age: "1m".to_string(),
+            is_new: true,
+            waiting_reason: None,
+            last_termination: None,
+        }];
+
+        let header = dashboard.header_text();
+        assert!(
+            header.starts_with(" Davit Logs: auth-api | Env: staging | Tag: v2 ("),
+            "{}",
+            header
+        );
+        let at = dashboard.started_at + Duration::from_secs(65);
+        assert_eq!(
+            dashboard.status_bar_text(at),
+            " ⏱ 01:05 since start | Pods ready 1/1 | Filters: none"
+        );
+    }
+
+    #[test]
+    fn test_status_bar_reports_rollout_pods_and_filters() {
+        let mut dashboard = test_dashboard(DashboardLayout::default());
+        let pod = |name: &str, is_new: bool, ready_count: usize| PodInfo {
+            name: name.to_string(),
+            status: "Running".to_string(),
+            ready: format!("{}/1", ready_count),
+            ready_count,
+            total_containers: 1,
+            restarts: 0,
+            age: "1m".to_string(),
+            is_new,
+            waiting_reason: None,
+            last_termination: None,
+        };
+        dashboard.pods = vec![
+            pod("api-old-1", false, 1),
+            pod("api-old-2", false, 1),
+            pod("api-new-1", true, 1),
+            pod("api-new-2", true, 0),
+        ];
+        dashboard.rollout_status.updated_replicas = 2;
+        dashboard.rollout_status.desired_replicas = 3;
+        let at = dashboard.started_at + Duration::from_secs(125);
+        assert_eq!(
+            dashboard.status_bar_text(at),
+            " ⏱ 02:05 since apply | Rollout: updating 2/3 | New pods ready 1/2 | Old pods ready 2/2 | Filters: none"
+        );
+
+        dashboard.search = Some(Regex::new("timeout").unwrap());
+        dashboard.filter_logs = true;
+        dashboard.follow_logs = false;
+        dashboard.rollout_status.failure = Some("ProgressDeadlineExceeded".to_string());
+        let text = dashboard.status_bar_text(at + Duration::from_secs(3600));
+        assert!(
+            text.starts_with(" ⏱ 1:02:05 since apply | Rollout: failed |"),
+            "{}",
+            text
+        );
+        assert!(
+            text.ends_with("Filters: /timeout (filtered) paused"),
+            "{}",
+            text
+        );
+
+        assert!(
+            dashboard
+                .help_lines()
+                .iter()
+                .any(|line| line.trim() == "?  Show / hide this help")
+        );
+    }
+}
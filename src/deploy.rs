@@ -0,0 +1,1720 @@
+//! The deploy command: a single service through review, apply, rollout and commit, or
+//! every service of a release manifest together.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use inquire::{Confirm, Select};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+use crate::blueprint::{Blueprint, parse_document, split_image_ref};
+use crate::bundle::FailedDeploy;
+use crate::config::{ApplyScope, Config, ContainerImage, Environment, ServiceSource};
+use crate::dashboard::{Dashboard, DashboardExit};
+use crate::deploy_lock::{self, DeployLock};
+use crate::git::Git;
+use crate::history::{self, Outcome, PhaseDurations};
+use crate::protection::{self, ProtectedTarget};
+use crate::resources::{self, CapacityImpact};
+use crate::{
+    TAG_WAIT_CANCELLED_MESSAGE, annotate_manifest, apply_service_file, audit, build_release_notes,
+    capture_failure_bundle, check_commit_signing, cluster, collect_configmap_changes,
+    commit_message, count_down, deploy_summary, enforce_compatibility, enforce_lint,
+    enforce_pull_secrets, enforce_rego_policies, enforce_resource_policy, fetch_service_images,
+    freeze, helm, hooks, interactive, kubectl_command, lint, mock, notify, offline,
+    pin_container_digests, prepull_images, progressive_delivery, pull_yaml_sources, rbac,
+    record_deploy, release, render_kustomize_diff, report_image_pull_warnings,
+    report_sealed_secret_warnings, report_stuck_rollout, resolve_container_tags,
+    resolve_environment, resolve_service, resolve_tag, retag_version_group, retire_canary,
+    revalidate_scheduled_plan, revert_local_changes, roll_back_deploy, run_canary, sandbox_branch,
+    save_dashboard_layout, schedule, sealed_secrets, secrets, select_target_containers,
+    show_configmap_changes, show_version_group_changes, tag_digest, update_manifest,
+    verify_applied_images, wait_for_rollout, wait_for_schedule, workload_resources_before_after,
+};
+
+/// What `davit deploy` was asked to do.
+pub struct DeployArgs {
+    pub env: Option<String>,
+    pub service: Option<String>,
+    pub tag: Option<String>,
+    pub manifest: Option<PathBuf>,
+    pub wait_for_tag: Option<String>,
+    pub dry_run: bool,
+    pub auto_apply: bool,
+    pub auto_continue: bool,
+    pub prepull: bool,
+    pub progressive: bool,
+    pub at: Option<String>,
+    pub dashboard: bool,
+    pub pin_digest: bool,
+    pub approved_by: Vec<String>,
+    pub override_freeze: Option<String>,
+    pub ticket: Option<String>,
+    pub confirm_protected: Option<String>,
+}
+
+/// Deploys a service, or the services of a release manifest, to an environment.
+pub async fn run(config: &Config, args: DeployArgs) -> Result<()> {
+    let DeployArgs {
+        env,
+        service,
+        tag,
+        manifest,
+        wait_for_tag,
+        dry_run,
+        auto_apply,
+        auto_continue,
+        prepull,
+        progressive,
+        at,
+        dashboard,
+        pin_digest,
+        approved_by,
+        override_freeze,
+        ticket,
+        confirm_protected,
+    } = args;
+    if let Some(manifest) = manifest {
+        return run_release(
+            config,
+            env,
+            &manifest,
+            dry_run,
+            auto_apply,
+            &approved_by,
+            override_freeze.as_deref(),
+            confirm_protected.as_deref(),
+        )
+        .await;
+    }
+    if progressive && !interactive::enabled() {
+        return Err(anyhow::anyhow!(
+            "--progressive shifts traffic from the dashboard and needs an interactive session"
+        ));
+    }
+    // Unattended runs apply what they were given and follow the rollout without the TUI.
+    let auto_apply = auto_apply || !interactive::enabled();
+    let auto_continue = auto_continue || auto_apply;
+    let headless = !interactive::enabled() && !dashboard;
+    let scheduled_at = at
+        .as_deref()
+        .map(|at| schedule::parse_at(at, Local::now()))
+        .transpose()?;
+    let target = prepare_target(config, env, dry_run, "deployment").await?;
+    let (selected_env, sandbox, git_dry_run) = (&target.env, target.sandbox, target.git_dry_run);
+
+    let resolved_service = resolve_service(selected_env, service)?;
+    resolved_service.ensure_editable()?;
+    selected_env.ensure_unlocked(&resolved_service.name)?;
+    rbac::preflight(selected_env, &resolved_service).await?;
+    // Scheduled deploys are checked against the windows at the time they will run.
+    let freeze_override = freeze_override(
+        selected_env,
+        scheduled_at.unwrap_or(Local::now()),
+        override_freeze.as_deref(),
+    )?;
+    let _deploy_locks = acquire_locks(&target, &[&resolved_service], dry_run).await?;
+    let target_containers = select_target_containers(&resolved_service)?;
+    let selected_service = resolved_service.with_container(&target_containers[0]);
+    let progressive_delivery = if progressive {
+        Some(progressive_delivery(config, &selected_service)?)
+    } else {
+        None
+    };
+
+    let mut phases = PhaseDurations::default();
+    let registry_started = Instant::now();
+    let (selected_tag, images) =
+        match resolve_tag(selected_env, &selected_service, tag, wait_for_tag).await {
+            Ok(resolved) => resolved,
+            Err(err) if err.to_string() == TAG_WAIT_CANCELLED_MESSAGE => {
+                println!("Tag wait cancelled. Deployment aborted.");
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+    let selected_digest = tag_digest(&images, &selected_tag);
+    let container_tags = resolve_container_tags(
+        selected_env,
+        &resolved_service,
+        &target_containers,
+        &selected_tag,
+    )
+    .await?;
+    let helm_managed =
+        selected_service.helm_release.is_some() || selected_service.helm_chart.is_some();
+    let pin_digest = pin_digest || selected_env.pin_digest.unwrap_or(false);
+    let manifest_tags = match pin_digest {
+        true if helm_managed => {
+            println!(
+                "⚠️  {} is deployed with Helm: its tag value cannot hold a digest, deploying the tag.",
+                selected_service.name
+            );
+            container_tags.clone()
+        }
+        true => {
+            pin_container_digests(selected_env, &resolved_service, &container_tags, &images).await?
+        }
+        false => container_tags.clone(),
+    };
+    // New pods run the digest rather than the tag when the manifest is pinned.
+    let pinned_digest = (manifest_tags != container_tags).then(|| manifest_tags[0].1.clone());
+    let rollout_ref = pinned_digest.as_deref().unwrap_or(&selected_tag);
+    phases.registry_secs = Some(registry_started.elapsed().as_secs_f64());
+    enforce_compatibility(config, selected_env, &selected_service, &selected_tag).await?;
+    let review_started = Instant::now();
+    let trail = AuditTrail::new(
+        &selected_env.name,
+        vec![(&selected_service.name, &selected_tag)],
+        dry_run,
+    );
+    let audit = |entry| trail.record(entry);
+    let audit_entry = |action| trail.entry(action, 0);
+    trail.start(freeze_override.as_deref(), sandbox.then_some("sandbox"));
+
+    // 6.3 Production Protection
+    let approvals = confirm_protection(
+        config,
+        selected_env,
+        &trail,
+        confirm_protected.as_deref(),
+        &approved_by,
+        "Deployment",
+    )?;
+
+    // Asked before the review, so that nothing waits for input after the apply.
+    let (ticket, extra_trailers) = match selected_service.is_file_backed() {
+        true => commit_message::collect(selected_env.commit.as_ref(), ticket.as_deref())?,
+        false => (None, Vec::new()),
+    };
+
+    // Phase 4 - YAML modification & Visual Diff
+    let yaml_path = selected_service.yaml_path.clone();
+    let helm_release = selected_service
+        .helm_release
+        .as_ref()
+        .zip(selected_env.helm.as_ref());
+
+    let (original_content, updated_content) = match helm_release {
+        // Helm releases have no YAML to edit: the review compares rendered manifests.
+        Some((release, helm)) => (
+            helm::release_manifest(&selected_env.kubectl_context, release)?,
+            helm::upgrade(
+                &selected_env.kubectl_context,
+                helm,
+                release,
+                &selected_tag,
+                true,
+            )
+            .context("Failed to render the Helm upgrade")?,
+        ),
+        // Without a manifests repository the live object is what gets edited.
+        None if selected_service.live => {
+            let original_content = cluster::live_manifest(
+                &selected_env.kubectl_context,
+                &selected_service.kind,
+                selected_service.namespace.as_deref().unwrap_or("default"),
+                &selected_service.name,
+            )?;
+            let updated_content =
+                update_manifest(&selected_service, &original_content, &manifest_tags)?;
+            let updated_content = annotate_manifest(
+                &config.annotations,
+                &selected_service,
+                &updated_content,
+                &selected_tag,
+            )?;
+            (original_content, updated_content)
+        }
+        None => {
+            let original_content = fs::read_to_string(&yaml_path)
+                .with_context(|| format!("Failed to read YAML file at {}", yaml_path.display()))?;
+            let updated_content =
+                update_manifest(&selected_service, &original_content, &manifest_tags)?;
+            let updated_content = annotate_manifest(
+                &config.annotations,
+                &selected_service,
+                &updated_content,
+                &selected_tag,
+            )?;
+            (original_content, updated_content)
+        }
+    };
+
+    let version_group = config.service(&selected_service.name).version_group;
+    let (updated_content, group_changes) = match helm_managed {
+        true if !version_group.is_empty() => {
+            println!(
+                "⚠️  The version group of {} is not applied to Helm releases: set its tags in the chart values.",
+                selected_service.name
+            );
+            (updated_content, Vec::new())
+        }
+        true => (updated_content, Vec::new()),
+        false => {
+            retag_version_group(
+                selected_env,
+                &selected_service,
+                &version_group,
+                &selected_tag,
+                &updated_content,
+                auto_continue,
+            )
+            .await?
+        }
+    };
+    let configmap_changes = collect_configmap_changes(&selected_service);
+
+    let sealed_secrets = sealed_secrets::service_sealed_secrets(&selected_service);
+    report_sealed_secret_warnings(selected_env, &selected_service, &sealed_secrets, false).await;
+    report_image_pull_warnings(selected_env, &selected_service).await;
+
+    let mut show_unified = true;
+    let filename = match helm_release {
+        Some((release, _)) => format!("helm release {}", release.name),
+        None if selected_service.live => format!(
+            "live {}/{}",
+            selected_service.kind.to_lowercase(),
+            selected_service.name
+        ),
+        None => yaml_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("deployment.yaml")
+            .to_string(),
+    };
+    let filename = match &pinned_digest {
+        Some(_) => format!("{} ({} pinned to its digest)", filename, selected_tag),
+        None => filename,
+    };
+
+    let rendered_diff = match (
+        &selected_service.kustomize_overlay,
+        &selected_service.helm_chart,
+    ) {
+        (Some(overlay), _) => Some(render_kustomize_diff(
+            overlay,
+            &yaml_path,
+            &updated_content,
+        )?),
+        (None, Some(chart)) => Some((
+            chart.template(&original_content)?,
+            chart
+                .template(&updated_content)
+                .context("Failed to render the chart with the new tag")?,
+        )),
+        (None, None) => None,
+    };
+    let rendered_label = match selected_service.helm_chart {
+        Some(_) => "helm template (effective manifest)",
+        None => "kustomize build (effective manifest)",
+    };
+
+    let applied_manifest = rendered_diff
+        .as_ref()
+        .map(|(_, new_rendered)| new_rendered.as_str())
+        .unwrap_or(&updated_content);
+
+    let workload_resources = workload_resources_before_after(
+        selected_env,
+        &selected_service,
+        &original_content,
+        applied_manifest,
+    )
+    .await;
+    let resource_changes = workload_resources
+        .as_ref()
+        .map(|(before, after)| resources::summarize_changes(before.as_ref(), after));
+    let capacity_impact = workload_resources
+        .as_ref()
+        .and_then(|(before, after)| CapacityImpact::between(before.as_ref(), after));
+    enforce_resource_policy(
+        selected_env,
+        workload_resources.as_ref().map(|(_, after)| after),
+    )?;
+    enforce_rego_policies(selected_env, applied_manifest)?;
+    let lint_findings = parse_document(applied_manifest, selected_service.document_index)
+        .map(|w| lint::lint_workload(&w))
+        .unwrap_or_default();
+    enforce_lint(selected_env, &lint_findings)?;
+    enforce_pull_secrets(selected_env, &selected_service, applied_manifest).await?;
+
+    // kubectl diff needs direct cluster access and a manifest kubectl can apply.
+    let server_diff_available = !mock::enabled()
+        && helm_release.is_none()
+        && selected_env
+            .offline
+            .as_ref()
+            .is_none_or(|o| o.kubectl_wrapper.is_empty());
+    let mut server_diff: Option<Option<String>> = None;
+
+    loop {
+        if let Some(changes) = &resource_changes {
+            resources::print_changes(changes);
+        }
+        lint::print_findings(&lint_findings);
+        Blueprint::show_diff(&original_content, &updated_content, &filename, show_unified);
+        if let Some((old_rendered, new_rendered)) = &rendered_diff {
+            Blueprint::show_diff(old_rendered, new_rendered, rendered_label, show_unified);
+        }
+        show_configmap_changes(&configmap_changes, show_unified);
+        show_version_group_changes(&group_changes, show_unified);
+        if let Some(impact) = &capacity_impact {
+            impact.print(config.pricing.as_ref());
+        }
+        match &server_diff {
+            Some(Some(patch)) => Blueprint::show_patch(patch, "kubectl diff (server-side dry-run)"),
+            Some(None) => {
+                println!("\nkubectl diff (server-side dry-run): the cluster would not change.")
+            }
+            None => {}
+        }
+
+        if auto_apply {
+            print!("Auto-apply enabled. ");
+            audit(audit_entry(audit::Action::ReviewApproved).detail("auto-apply"));
+            break;
+        }
+
+        let mut choices = if show_unified {
+            vec!["Apply", "Show full diff", "Dismiss"]
+        } else {
+            vec!["Apply", "Show unified diff", "Dismiss"]
+        };
+        if server_diff_available {
+            choices.insert(2, "Preview server-side changes");
+        }
+
+        let selection = Select::new("Action:", choices).prompt()?;
+
+        match selection {
+            "Apply" => {
+                audit(audit_entry(audit::Action::ReviewApproved));
+                break;
+            }
+            "Show full diff" => show_unified = false,
+            "Show unified diff" => show_unified = true,
+            "Preview server-side changes" => {
+                match cluster::server_diff(&selected_env.kubectl_context, applied_manifest) {
+                    Ok(patch) => server_diff = Some(patch),
+                    Err(e) => println!("⚠️  {:#}", e),
+                }
+            }
+            _ => {
+                println!("Deployment cancelled. No changes made.");
+                audit(audit_entry(audit::Action::Cancelled));
+                return Ok(());
+            }
+        }
+    }
+
+    // Scheduled deploys run unattended once the reviewed plan is still current.
+    let auto_continue = auto_continue || scheduled_at.is_some();
+    if let Some(at) = scheduled_at {
+        if !wait_for_schedule(at)? {
+            println!("Scheduled deploy cancelled. No changes made.");
+            return Ok(());
+        }
+        let announce = |message: String| {
+            if !dry_run {
+                notify::send(selected_env.notify_webhook.as_deref(), &message);
+            }
+        };
+        if let Err(e) = revalidate_scheduled_plan(
+            selected_env,
+            &selected_service,
+            &original_content,
+            &group_changes,
+            git_dry_run,
+        ) {
+            announce(format!(
+                "Scheduled deploy of {} {} to {} aborted: {:#}",
+                selected_service.name, selected_tag, selected_env.name, e
+            ));
+            return Err(e);
+        }
+        announce(format!(
+            "Scheduled deploy of {} {} to {} starting",
+            selected_service.name, selected_tag, selected_env.name
+        ));
+    }
+
+    // Last chance to call the deploy off, with the team watching.
+    if let Some(secs) = selected_env.countdown_secs.filter(|s| *s > 0 && !dry_run) {
+        let announce = |what: String| {
+            notify::send(
+                selected_env.notify_webhook.as_deref(),
+                &format!(
+                    "{} deploy of {} {} {} ({})",
+                    selected_env.name,
+                    selected_service.name,
+                    selected_tag,
+                    what,
+                    history::current_user()
+                ),
+            )
+        };
+        announce(format!("starting in {}s", secs));
+        if !count_down(Local::now() + chrono::Duration::seconds(secs as i64))? {
+            announce("cancelled".to_string());
+            println!("Deployment cancelled. No changes made.");
+            return Ok(());
+        }
+    }
+
+    let service_hooks = config.service(&selected_service.name).hooks;
+    let hook_sets = [&selected_env.hooks, &service_hooks];
+    let hook_context = hooks::HookContext {
+        env: selected_env,
+        service: &selected_service,
+        tag: &selected_tag,
+    };
+    let hooks_dry_run = dry_run || sandbox;
+    hooks::run(
+        &hook_sets,
+        hooks::Stage::PreDeploy,
+        &hook_context,
+        hooks_dry_run,
+    )?;
+
+    if (prepull || config.service(&selected_service.name).prepull)
+        && !dry_run
+        && !sandbox
+        && !mock::enabled()
+    {
+        prepull_images(
+            selected_env,
+            &selected_service,
+            &manifest_tags,
+            applied_manifest,
+        )
+        .await?;
+    }
+
+    let canary = match &progressive_delivery {
+        Some(_) if dry_run || sandbox || mock::enabled() => {
+            println!("Skipping the canary: traffic steps need a real rollout.");
+            None
+        }
+        Some(delivery) => match run_canary(
+            config,
+            selected_env,
+            &selected_service,
+            &selected_tag,
+            pinned_digest.as_deref(),
+            delivery,
+            applied_manifest,
+        )
+        .await
+        {
+            Ok(canary) => Some(canary),
+            Err(e) => {
+                record_deploy(
+                    selected_env,
+                    &selected_service,
+                    &selected_tag,
+                    selected_digest.as_deref(),
+                    Utc::now(),
+                    Outcome::Failed,
+                    phases,
+                    &approvals,
+                    freeze_override.as_deref(),
+                );
+                return Err(e);
+            }
+        },
+        None => None,
+    };
+
+    if helm_release.is_some() {
+        println!("Executing helm upgrade...");
+    } else if selected_service.live {
+        println!("Replacing the live object...");
+    } else {
+        if dry_run {
+            println!(
+                "Dry-run: would write updated YAML to {}",
+                yaml_path.display()
+            );
+            for change in &group_changes {
+                println!(
+                    "Dry-run: would write updated YAML to {}",
+                    change.path.display()
+                );
+            }
+        } else {
+            fs::write(&yaml_path, &updated_content).with_context(|| {
+                format!("Failed to write updated YAML to {}", yaml_path.display())
+            })?;
+            for change in &group_changes {
+                fs::write(&change.path, &change.new_content).with_context(|| {
+                    format!("Failed to write updated YAML to {}", change.path.display())
+                })?;
+            }
+        }
+        if selected_service.helm_chart.is_some() {
+            println!("Local values updated. Executing helm upgrade...");
+        } else {
+            println!("Local YAML updated. Executing kubectl apply...");
+        }
+    }
+
+    let mut apply_files = vec![yaml_path.clone()];
+    apply_files.extend(configmap_changes.iter().map(|c| c.path.clone()));
+    for change in &group_changes {
+        if !apply_files.contains(&change.path) {
+            apply_files.push(change.path.clone());
+        }
+    }
+    let related_documents = match selected_env.apply_scope {
+        ApplyScope::Related => selected_service.related_documents(),
+        _ => Vec::new(),
+    };
+    let mut applied_files = apply_files.clone();
+    for document in &related_documents {
+        if !applied_files.contains(&document.path) {
+            applied_files.push(document.path.clone());
+        }
+    }
+    let changed_manifests = (selected_env.apply_scope == ApplyScope::Changed
+        && selected_service.kustomize_overlay.is_none())
+    .then(|| {
+        std::iter::once((original_content.as_str(), updated_content.as_str()))
+            .chain(
+                configmap_changes
+                    .iter()
+                    .chain(&group_changes)
+                    .map(|c| (c.old_content.as_str(), c.new_content.as_str())),
+            )
+            .flat_map(|(old, new)| Blueprint::changed_documents(old, new))
+            .collect::<Vec<_>>()
+            .join("\n---\n")
+    });
+    let mut apply_args: Vec<String> = applied_files
+        .iter()
+        .flat_map(|f| ["-f".to_string(), f.display().to_string()])
+        .collect();
+    if let Some(overlay) = &selected_service.kustomize_overlay {
+        apply_args = vec!["-k".to_string(), overlay.display().to_string()];
+    } else if changed_manifests.is_some() {
+        apply_args = vec!["-f".to_string(), "-".to_string()];
+    }
+
+    phases.review_secs = Some(review_started.elapsed().as_secs_f64());
+    let applied_at = Utc::now();
+    let apply_started = Instant::now();
+    let (old_manifest, new_manifest) = rendered_diff
+        .as_ref()
+        .map(|(old, new)| (old.as_str(), new.as_str()))
+        .unwrap_or((&original_content, &updated_content));
+    let failed = |reason| FailedDeploy {
+        env: selected_env,
+        service: &selected_service,
+        tag: &selected_tag,
+        reason,
+        old_manifest,
+        new_manifest,
+    };
+    let record = |outcome, phases: &PhaseDurations| {
+        let stage = match outcome {
+            Outcome::Succeeded => hooks::Stage::Succeeded,
+            Outcome::Failed => hooks::Stage::Failed,
+        };
+        hooks::run_after_apply(&hook_sets, stage, &hook_context, hooks_dry_run);
+        audit(audit_entry(match outcome {
+            Outcome::Succeeded => audit::Action::Succeeded,
+            Outcome::Failed => audit::Action::Failed,
+        }));
+        if !dry_run && !sandbox {
+            record_deploy(
+                selected_env,
+                &selected_service,
+                &selected_tag,
+                selected_digest.as_deref(),
+                applied_at,
+                outcome,
+                *phases,
+                &approvals,
+                freeze_override.as_deref(),
+            );
+        }
+        if scheduled_at.is_some() && !dry_run {
+            notify::send(
+                selected_env.notify_webhook.as_deref(),
+                &format!(
+                    "Scheduled deploy of {} {} to {} {}",
+                    selected_service.name, selected_tag, selected_env.name, outcome
+                ),
+            );
+        }
+    };
+
+    if let (true, Some((release, helm))) = (dry_run, helm_release) {
+        println!(
+            "Dry-run: helm --kube-context {} upgrade {} --reuse-values --set-string {}={}",
+            selected_env.kubectl_context, release.name, helm.tag_value, selected_tag
+        );
+    } else if let (true, Some(chart)) = (dry_run, &selected_service.helm_chart) {
+        println!(
+            "Dry-run: helm {}",
+            chart
+                .upgrade_args(&selected_env.kubectl_context, false)
+                .join(" ")
+        );
+    } else if dry_run && selected_service.live {
+        println!(
+            "Dry-run: kubectl --context {} replace {}/{}",
+            selected_env.kubectl_context,
+            selected_service.kind.to_lowercase(),
+            selected_service.name
+        );
+    } else if dry_run {
+        println!(
+            "Dry-run: kubectl --context {} apply {}",
+            selected_env.kubectl_context,
+            apply_args.join(" ")
+        );
+    } else if let Some((release, helm)) = helm_release {
+        if let Err(e) = helm::upgrade(
+            &selected_env.kubectl_context,
+            helm,
+            release,
+            &selected_tag,
+            sandbox,
+        ) {
+            println!("❌ {:#}", e);
+            retire_canary(selected_env, canary.as_ref(), false).await;
+            phases.apply_secs = Some(apply_started.elapsed().as_secs_f64());
+            record(Outcome::Failed, &phases);
+            if !sandbox {
+                capture_failure_bundle(&failed("helm upgrade failed")).await;
+            }
+            return Err(anyhow::anyhow!("helm upgrade failed"));
+        }
+    } else if selected_service.live {
+        if let Err(e) = cluster::replace(&selected_env.kubectl_context, &updated_content, sandbox) {
+            println!("❌ {:#}", e);
+            if e.to_string().contains("has been modified") {
+                println!(
+                    "The live object changed since it was reviewed. Run the deploy again to review the new state."
+                );
+            }
+            retire_canary(selected_env, canary.as_ref(), false).await;
+            phases.apply_secs = Some(apply_started.elapsed().as_secs_f64());
+            record(Outcome::Failed, &phases);
+            if !sandbox {
+                capture_failure_bundle(&failed("kubectl replace failed")).await;
+            }
+            return Err(anyhow::anyhow!("kubectl replace failed"));
+        }
+    } else if let Some(chart) = &selected_service.helm_chart {
+        if let Err(e) = chart.upgrade(&selected_env.kubectl_context, sandbox) {
+            println!("❌ {:#}", e);
+            retire_canary(selected_env, canary.as_ref(), false).await;
+            if !auto_continue
+                && Confirm::new("Revert local values changes?")
+                    .with_default(true)
+                    .prompt()?
+            {
+                revert_local_changes(&yaml_path, &original_content, &group_changes)?;
+                println!("Values reverted.");
+            }
+            phases.apply_secs = Some(apply_started.elapsed().as_secs_f64());
+            record(Outcome::Failed, &phases);
+            if !sandbox {
+                capture_failure_bundle(&failed("helm upgrade failed")).await;
+            }
+            return Err(anyhow::anyhow!("helm upgrade failed"));
+        }
+    } else if mock::enabled() && !sandbox {
+        let (image, _) = split_image_ref(&selected_service.image_path);
+        mock::apply(
+            &selected_env.kubectl_context,
+            &selected_service.name,
+            &selected_service.image_path,
+            &format!("{}:{}", image, selected_tag),
+        );
+    } else if mock::enabled() {
+        println!("Sandbox: server-side dry-run accepted by the simulated cluster.");
+    } else {
+        let wrapper = selected_env
+            .offline
+            .as_ref()
+            .map(|o| o.kubectl_wrapper.as_slice())
+            .filter(|w| !w.is_empty());
+        let output = match (wrapper, &changed_manifests) {
+            (Some(wrapper), _) => {
+                let manifests = match (&selected_service.kustomize_overlay, &changed_manifests) {
+                    (Some(overlay), _) => Blueprint::render_kustomize(overlay)?,
+                    (None, Some(changed)) => changed.clone(),
+                    (None, None) => applied_files
+                        .iter()
+                        .map(fs::read_to_string)
+                        .collect::<io::Result<Vec<_>>>()
+                        .context("Failed to read the manifests to apply")?
+                        .join("\n---\n"),
+                };
+                offline::apply_through(wrapper, &manifests, sandbox)?
+            }
+            (None, Some(changed)) => offline::apply_through(
+                &kubectl_command(&selected_env.kubectl_context),
+                changed,
+                sandbox,
+            )?,
+            (None, None) => Command::new("kubectl")
+                .args(["--context", &selected_env.kubectl_context, "apply"])
+                .args(sandbox.then_some("--dry-run=server"))
+                .args(&apply_args)
+                .output()
+                .context("Failed to execute kubectl apply")?,
+        };
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            println!("❌ kubectl apply failed: {}", stderr);
+            retire_canary(selected_env, canary.as_ref(), false).await;
+            if !auto_continue
+                && Confirm::new("Revert local YAML changes?")
+                    .with_default(true)
+                    .prompt()?
+            {
+                revert_local_changes(&yaml_path, &original_content, &group_changes)?;
+                println!("YAML reverted.");
+            }
+            phases.apply_secs = Some(apply_started.elapsed().as_secs_f64());
+            record(Outcome::Failed, &phases);
+            if !sandbox {
+                capture_failure_bundle(&failed("kubectl apply failed")).await;
+            }
+            return Err(anyhow::anyhow!("kubectl apply failed"));
+        }
+    }
+    phases.apply_secs = Some(apply_started.elapsed().as_secs_f64());
+    audit(
+        audit_entry(audit::Action::Applied).diff(match &rendered_diff {
+            Some((old_rendered, new_rendered)) => {
+                audit::unified_diff(old_rendered, new_rendered, rendered_label)
+            }
+            None => audit::unified_diff(&original_content, &updated_content, &filename),
+        }),
+    );
+    hooks::run_after_apply(
+        &hook_sets,
+        hooks::Stage::PostApply,
+        &hook_context,
+        hooks_dry_run,
+    );
+    let rollout_started = Instant::now();
+
+    if !dry_run && !sandbox && !sealed_secrets.is_empty() {
+        report_sealed_secret_warnings(selected_env, &selected_service, &sealed_secrets, true).await;
+    }
+
+    if sandbox {
+        println!("Sandbox dry-run succeeded. Nothing was rolled out, skipping the dashboard.");
+    } else {
+        if !dry_run
+            && !mock::enabled()
+            && helm_release.is_none()
+            && !verify_applied_images(
+                selected_env,
+                &selected_service,
+                &manifest_tags,
+                auto_continue,
+            )?
+        {
+            retire_canary(selected_env, canary.as_ref(), false).await;
+            record(Outcome::Failed, &phases);
+            return Err(anyhow::anyhow!(
+                "The applied images were rewritten in the cluster"
+            ));
+        }
+        if headless {
+            println!("Deployment applied. Waiting for the rollout...");
+            if let Err(e) = wait_for_rollout(selected_env, &selected_service) {
+                println!("❌ {:#}", e);
+                report_stuck_rollout(selected_env, &selected_service, rollout_ref).await;
+                record(Outcome::Failed, &phases);
+                capture_failure_bundle(&failed(&e.to_string())).await;
+                return Err(e);
+            }
+            phases.rolled_out_secs = Some(rollout_started.elapsed().as_secs_f64());
+            println!("Rollout completed. Continuing to the Git step...");
+        } else {
+            println!("Deployment applied. Starting dashboard...");
+
+            let mut dashboard = Dashboard::new(
+                selected_service.name.clone(),
+                selected_service.kind.clone(),
+                selected_env.name.clone(),
+                selected_tag.clone(),
+                selected_env.kubectl_context.clone(),
+                selected_service.namespace.clone(),
+                selected_service.selector.clone(),
+                selected_service.container_name.clone(),
+                auto_continue,
+                config.dashboard.clone(),
+                config.service(&selected_service.name).health,
+                selected_env.prometheus_url.clone(),
+                config.service(&selected_service.name).metrics,
+            );
+            dashboard.parse_logs_with(config.log_fields(&selected_service.name));
+            if let Some(digest) = &pinned_digest {
+                dashboard.match_digest(digest);
+            }
+            let res = dashboard.run().await;
+            let rolling_back = matches!(res, Ok(DashboardExit::RollbackRequested(_)));
+            retire_canary(selected_env, canary.as_ref(), !rolling_back).await;
+            let (first_pod_ready_at, rolled_out_at) = dashboard.rollout_timings();
+            let since_apply =
+                |at: Instant| at.saturating_duration_since(rollout_started).as_secs_f64();
+            phases.first_pod_ready_secs = first_pod_ready_at.map(since_apply);
+            phases.rolled_out_secs = rolled_out_at.map(since_apply);
+            if dashboard.layout() != config.dashboard
+                && let Err(e) = save_dashboard_layout(&dashboard.layout())
+            {
+                println!("⚠️  Could not save the dashboard layout: {}", e);
+            }
+
+            // A failed rollout fails the deploy, and the process with it.
+            let res = res.and_then(|exit| match exit {
+                DashboardExit::RolloutFailed(reason) => {
+                    Err(anyhow::anyhow!("Rollout failed: {}", reason))
+                }
+                exit => Ok(exit),
+            });
+            match res {
+                Err(e) => {
+                    println!("❌ Dashboard error or aborted: {}", e);
+                    report_stuck_rollout(selected_env, &selected_service, rollout_ref).await;
+                    record(Outcome::Failed, &phases);
+                    capture_failure_bundle(&failed(&e.to_string())).await;
+                    if !auto_continue
+                        && selected_service.is_file_backed()
+                        && Confirm::new("Revert local YAML changes?")
+                            .with_default(true)
+                            .prompt()?
+                    {
+                        revert_local_changes(&yaml_path, &original_content, &group_changes)?;
+                        println!("YAML reverted.");
+                    }
+                    return Err(e);
+                }
+                Ok(DashboardExit::UserQuit) => {
+                    if rolled_out_at.is_none() {
+                        report_stuck_rollout(selected_env, &selected_service, rollout_ref).await;
+                    }
+                    if auto_continue {
+                        record(Outcome::Failed, &phases);
+                        capture_failure_bundle(&failed(
+                            "Dashboard closed before rollout completion",
+                        ))
+                        .await;
+                        return Err(anyhow::anyhow!(
+                            "Dashboard closed before rollout completion in auto-continue mode"
+                        ));
+                    }
+                    println!("Dashboard closed before rollout completion check.");
+                }
+                Ok(DashboardExit::RolloutCompleted) => {
+                    println!("✅ Rollout complete. Continuing to the Git step...");
+                }
+                Ok(DashboardExit::RolloutFailed(_)) => {
+                    unreachable!("mapped to an error above")
+                }
+                Ok(DashboardExit::RollbackRequested(reason)) => {
+                    println!("❌ {}. Rolling back...", reason);
+                    record(Outcome::Failed, &phases);
+                    capture_failure_bundle(&failed(&reason)).await;
+                    roll_back_deploy(selected_env, &selected_service, &selected_tag)?;
+                    if selected_service.is_file_backed() {
+                        revert_local_changes(&yaml_path, &original_content, &group_changes)?;
+                        println!("YAML reverted.");
+                    }
+                    return Err(anyhow::anyhow!("Deployment rolled back: {}", reason));
+                }
+            }
+
+            let breached = dashboard.breached_metrics();
+            if !breached.is_empty() {
+                println!("❌ Metric thresholds breached: {}", breached.join(", "));
+                if auto_continue
+                    || !Confirm::new(
+                        "The deploy is marked as failing. Continue to the Git step anyway?",
+                    )
+                    .with_default(false)
+                    .prompt()?
+                {
+                    record(Outcome::Failed, &phases);
+                    capture_failure_bundle(&failed(&format!(
+                        "Metric thresholds breached: {}",
+                        breached.join(", ")
+                    )))
+                    .await;
+                    return Err(anyhow::anyhow!(
+                        "Deployment marked as failing by metric thresholds"
+                    ));
+                }
+            }
+        }
+    }
+
+    record(Outcome::Succeeded, &phases);
+
+    // 6.1 Git Automation
+    println!("\n🚀 Deployment successful. Preparing to commit changes...");
+    println!("\n--- Deploy Timings ---");
+    for line in phases.lines() {
+        println!("{}", line);
+    }
+    if !selected_service.is_file_backed() {
+        println!(
+            "\n{} updated to {} in the cluster. There is no YAML to commit.",
+            selected_service.name, selected_tag
+        );
+        return Ok(());
+    }
+    let template = selected_env
+        .commit
+        .as_ref()
+        .and_then(|c| c.message.as_deref());
+    let mut commit_msg = match template {
+        Some(template) => commit_message::render(
+            template,
+            &[
+                ("env", &selected_env.name),
+                ("service", &selected_service.name),
+                (
+                    "old_tag",
+                    split_image_ref(&selected_service.image_path)
+                        .1
+                        .unwrap_or_default(),
+                ),
+                ("new_tag", &selected_tag),
+                ("digest", selected_digest.as_deref().unwrap_or_default()),
+                ("user", &history::current_user()),
+                ("ticket", ticket.as_deref().unwrap_or_default()),
+            ],
+        ),
+        None => format!(
+            "deploy({}): update {}",
+            selected_env.name,
+            deploy_summary(&selected_service.name, &container_tags)
+        ),
+    };
+    if let Some(digest) = &pinned_digest {
+        commit_msg = format!("{}\n\nPinned to {}", commit_msg, digest);
+    }
+    let release_notes = build_release_notes(config, &selected_service, &selected_tag);
+
+    // Protected deploys carry the release notes in the commit body for reviewers.
+    if selected_env.protected.unwrap_or(false)
+        && let Some(notes) = &release_notes
+    {
+        commit_msg = format!("{}\n\n{}", commit_msg, notes.to_markdown());
+    }
+    let commit_msg = with_trailers(
+        commit_msg,
+        &approvals,
+        freeze_override.as_deref(),
+        extra_trailers,
+    );
+
+    println!("\n--- Commit Recap ---");
+    for file in &apply_files {
+        println!("File to commit:   {}", file.display());
+    }
+    for document in &related_documents {
+        println!(
+            "Applied with it:  {}/{} ({})",
+            document.kind,
+            document.name,
+            document.path.display()
+        );
+    }
+    println!("Commit message:   {}", commit_msg);
+    if let Some(notes) = &release_notes {
+        println!("\nRelease notes:");
+        notes.print();
+    }
+    Blueprint::show_diff(&original_content, &updated_content, &filename, true);
+    show_configmap_changes(&configmap_changes, true);
+    show_version_group_changes(&group_changes, true);
+    println!("--------------------\n");
+
+    let commit_deploy = || {
+        commit(
+            selected_env,
+            &selected_service.source_root,
+            (&selected_service.name, &selected_tag),
+            &commit_msg,
+            &apply_files,
+            git_dry_run,
+            &trail,
+            &[0],
+        )
+    };
+    if auto_continue {
+        commit_deploy()?;
+    } else {
+        if Confirm::new("Do you want to commit and push these changes?")
+            .with_default(true)
+            .prompt()?
+        {
+            if let Err(e) = commit_deploy() {
+                println!("⚠️  Failed to commit/push changes: {}", e);
+            }
+        } else {
+            println!("Committing skipped by user.");
+            if sandbox && !dry_run {
+                revert_local_changes(&yaml_path, &original_content, &group_changes)?;
+                println!("Sandbox YAML changes reverted.");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Deploys every service of a release manifest to one environment: their tags are
+/// checked in the registry, the changes reviewed together, applied in dependency order
+/// with each rollout awaited, and committed as a single release commit.
+#[allow(clippy::too_many_arguments)]
+async fn run_release(
+    config: &Config,
+    env: Option<String>,
+    manifest: &Path,
+    dry_run: bool,
+    auto_apply: bool,
+    approved_by: &[String],
+    override_freeze: Option<&str>,
+    confirm_protected: Option<&str>,
+) -> Result<()> {
+    let entries = release::read(manifest)?;
+    let release_name = manifest
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("release");
+    let auto_apply = auto_apply || !interactive::enabled();
+    let target = prepare_target(config, env, dry_run, "release").await?;
+    let (selected_env, sandbox, git_dry_run) = (&target.env, target.sandbox, target.git_dry_run);
+
+    let available = selected_env
+        .list_services()
+        .context("Failed to list services")?;
+    let mut services = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let service = available
+            .iter()
+            .find(|s| s.name == entry.name)
+            .cloned()
+            .with_context(|| {
+                format!("Service {} not found in {}", entry.name, selected_env.name)
+            })?;
+        if !service.is_file_backed() {
+            return Err(anyhow::anyhow!(
+                "{} is not deployed from a manifest file and cannot be part of a release: deploy it on its own",
+                service.name
+            ));
+        }
+        service.ensure_editable()?;
+        selected_env.ensure_unlocked(&service.name)?;
+        rbac::preflight(selected_env, &service).await?;
+        services.push(service);
+    }
+
+    // Every tag is checked before anything is changed, so that a typo fails the release
+    // up front rather than half-way through.
+    println!("Checking {} tag(s) in the registry...", entries.len());
+    let mut missing = Vec::new();
+    let mut digests = Vec::with_capacity(entries.len());
+    let mut container_tags = Vec::with_capacity(entries.len());
+    for (entry, service) in entries.iter().zip(&services) {
+        let containers = match service.helm_chart {
+            Some(_) => Vec::new(),
+            None => service.containers.clone(),
+        };
+        let containers = match containers.is_empty() {
+            true => vec![ContainerImage {
+                name: service.container_name.clone(),
+                image_path: service.image_path.clone(),
+            }],
+            false => containers,
+        };
+        let mut digest = None;
+        for container in &containers {
+            let images =
+                fetch_service_images(selected_env, &service.with_container(container), false)
+                    .await?;
+            match images
+                .iter()
+                .find(|image| image.tags.iter().any(|t| t == &entry.tag))
+            {
+                Some(image) => digest = digest.or_else(|| image.digest().map(str::to_string)),
+                None => missing.push(format!(
+                    "{}:{}",
+                    split_image_ref(&container.image_path).0,
+                    entry.tag
+                )),
+            }
+        }
+        digests.push(digest);
+        container_tags.push(
+            containers
+                .into_iter()
+                .map(|c| (c, entry.tag.clone()))
+                .collect::<Vec<_>>(),
+        );
+    }
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Tags not found in the registry: {}",
+            missing.join(", ")
+        ));
+    }
+
+    let trail = AuditTrail::new(
+        &selected_env.name,
+        entries
+            .iter()
+            .zip(&services)
+            .map(|(entry, service)| (service.name.as_str(), entry.tag.as_str()))
+            .collect(),
+        dry_run,
+    );
+    let audit = |entry| trail.record(entry);
+    let audit_entry = |action, i| trail.entry(action, i);
+
+    let freeze_override = freeze_override(selected_env, Local::now(), override_freeze)?;
+    trail.start(
+        freeze_override.as_deref(),
+        Some(&format!("release {}", release_name)),
+    );
+    let approvals = confirm_protection(
+        config,
+        selected_env,
+        &trail,
+        confirm_protected,
+        approved_by,
+        "Release",
+    )?;
+
+    // Services sharing a file are updated in the same copy of it.
+    let mut files: BTreeMap<PathBuf, release::ReleaseFile> = BTreeMap::new();
+    for ((entry, service), tags) in entries.iter().zip(&services).zip(&container_tags) {
+        if !files.contains_key(&service.yaml_path) {
+            let original = fs::read_to_string(&service.yaml_path).with_context(|| {
+                format!(
+                    "Failed to read YAML file at {}",
+                    service.yaml_path.display()
+                )
+            })?;
+            files.insert(
+                service.yaml_path.clone(),
+                release::ReleaseFile {
+                    path: service.yaml_path.clone(),
+                    source_root: service.source_root.clone(),
+                    updated: original.clone(),
+                    original,
+                },
+            );
+        }
+        let file = files
+            .get_mut(&service.yaml_path)
+            .expect("file was just read");
+        file.updated = update_manifest(service, &file.updated, tags)?;
+        file.updated = annotate_manifest(&config.annotations, service, &file.updated, &entry.tag)?;
+    }
+
+    println!(
+        "\n--- Release {} to {} ---",
+        release_name, selected_env.name
+    );
+    for (entry, service) in entries.iter().zip(&services) {
+        let current = split_image_ref(&service.image_path).1.unwrap_or("-");
+        match entry.after.is_empty() {
+            true => println!("📦 {}: {} → {}", service.name, current, entry.tag),
+            false => println!(
+                "📦 {}: {} → {} (after {})",
+                service.name,
+                current,
+                entry.tag,
+                entry.after.join(", ")
+            ),
+        }
+    }
+    for file in files.values().filter(|f| f.updated != f.original) {
+        let filename = file
+            .path
+            .strip_prefix(&file.source_root)
+            .unwrap_or(&file.path)
+            .display()
+            .to_string();
+        Blueprint::show_diff(&file.original, &file.updated, &filename, true);
+    }
+    if files.values().all(|f| f.updated == f.original) {
+        println!(
+            "Every service of the release already runs its tag in {}.",
+            selected_env.name
+        );
+        return Ok(());
+    }
+
+    let confirmed = auto_apply
+        || Confirm::new(&format!(
+            "Deploy {} service(s) to {}?",
+            services.len(),
+            selected_env.name
+        ))
+        .with_default(false)
+        .prompt()
+        .context("Confirmation was cancelled")?;
+    if !confirmed {
+        println!("Release cancelled. No changes made.");
+        for i in 0..services.len() {
+            audit(audit_entry(audit::Action::Cancelled, i));
+        }
+        return Ok(());
+    }
+    for i in 0..services.len() {
+        audit(match auto_apply {
+            true => audit_entry(audit::Action::ReviewApproved, i).detail("auto-apply"),
+            false => audit_entry(audit::Action::ReviewApproved, i),
+        });
+    }
+
+    let _deploy_locks =
+        acquire_locks(&target, &services.iter().collect::<Vec<_>>(), dry_run).await?;
+
+    let hooks_dry_run = dry_run || sandbox;
+    let mut written: HashSet<PathBuf> = HashSet::new();
+    let mut applied: Vec<&str> = Vec::new();
+    for (i, (entry, service)) in entries.iter().zip(&services).enumerate() {
+        let file = &files[&service.yaml_path];
+        if file.updated == file.original {
+            println!("⏭️  {} already runs {}.", service.name, entry.tag);
+            continue;
+        }
+        let service_hooks = config.service(&service.name).hooks;
+        let hook_sets = [&selected_env.hooks, &service_hooks];
+        let hook_context = hooks::HookContext {
+            env: selected_env,
+            service,
+            tag: &entry.tag,
+        };
+        hooks::run(
+            &hook_sets,
+            hooks::Stage::PreDeploy,
+            &hook_context,
+            hooks_dry_run,
+        )?;
+        if written.insert(file.path.clone()) {
+            if dry_run {
+                println!(
+                    "Dry-run: would write updated YAML to {}",
+                    file.path.display()
+                );
+            } else {
+                fs::write(&file.path, &file.updated).with_context(|| {
+                    format!("Failed to write updated YAML to {}", file.path.display())
+                })?;
+            }
+        }
+
+        println!("🚀 Applying {} {}...", service.name, entry.tag);
+        let applied_at = Utc::now();
+        let outcome = apply_service_file(selected_env, service, &entry.tag, dry_run, sandbox)
+            .and_then(|_| {
+                hooks::run_after_apply(
+                    &hook_sets,
+                    hooks::Stage::PostApply,
+                    &hook_context,
+                    hooks_dry_run,
+                );
+                match dry_run || sandbox {
+                    true => Ok(()),
+                    false => wait_for_rollout(selected_env, service),
+                }
+            });
+        let record = |outcome| {
+            hooks::run_after_apply(
+                &hook_sets,
+                match outcome {
+                    Outcome::Succeeded => hooks::Stage::Succeeded,
+                    Outcome::Failed => hooks::Stage::Failed,
+                },
+                &hook_context,
+                hooks_dry_run,
+            );
+            if !dry_run && !sandbox {
+                record_deploy(
+                    selected_env,
+                    service,
+                    &entry.tag,
+                    digests[i].as_deref(),
+                    applied_at,
+                    outcome,
+                    PhaseDurations::default(),
+                    &approvals,
+                    freeze_override.as_deref(),
+                );
+            }
+        };
+        if let Err(e) = outcome {
+            println!("❌ {}: {:#}", service.name, e);
+            audit(audit_entry(audit::Action::Failed, i).detail(format!("{:#}", e)));
+            record(Outcome::Failed);
+            // Files only holding services that were not applied go back to their state.
+            for path in &written {
+                let holds_applied = services
+                    .iter()
+                    .any(|s| &s.yaml_path == path && applied.contains(&s.name.as_str()));
+                if !dry_run && !holds_applied {
+                    fs::write(path, &files[path].original)
+                        .with_context(|| format!("Failed to revert YAML at {}", path.display()))?;
+                }
+            }
+            return Err(match applied.is_empty() {
+                true => anyhow::anyhow!("Release stopped at {}: nothing was applied", service.name),
+                false => anyhow::anyhow!(
+                    "Release stopped at {}: {} were applied and their YAML changes are left uncommitted",
+                    service.name,
+                    applied.join(", ")
+                ),
+            });
+        }
+        audit(
+            audit_entry(audit::Action::Applied, i).diff(audit::unified_diff(
+                &file.original,
+                &file.updated,
+                &file.path.display().to_string(),
+            )),
+        );
+        audit(audit_entry(audit::Action::Succeeded, i));
+        record(Outcome::Succeeded);
+        println!("✅ {} {} rolled out.", service.name, entry.tag);
+        applied.push(&service.name);
+    }
+
+    let lines: Vec<String> = services
+        .iter()
+        .zip(&container_tags)
+        .map(|(service, tags)| format!("- {}", deploy_summary(&service.name, tags)))
+        .collect();
+    let commit_msg = with_trailers(
+        format!(
+            "release({}): deploy {}\n\n{}",
+            selected_env.name,
+            release_name,
+            lines.join("\n")
+        ),
+        &approvals,
+        freeze_override.as_deref(),
+        Vec::new(),
+    );
+
+    let mut by_root: BTreeMap<&Path, Vec<PathBuf>> = BTreeMap::new();
+    for file in files.values().filter(|f| f.updated != f.original) {
+        by_root
+            .entry(file.source_root.as_path())
+            .or_default()
+            .push(file.path.clone());
+    }
+    println!("\n--- Commit Recap ---");
+    for file in by_root.values().flatten() {
+        println!("File to commit:   {}", file.display());
+    }
+    println!("Commit message:   {}", commit_msg);
+    println!("--------------------\n");
+    for (root, paths) in by_root {
+        let pushed: Vec<usize> = (0..services.len())
+            .filter(|&i| services[i].source_root == root)
+            .collect();
+        commit(
+            selected_env,
+            root,
+            ("release", release_name),
+            &commit_msg,
+            &paths,
+            git_dry_run,
+            &trail,
+            &pushed,
+        )?;
+    }
+    if !dry_run {
+        println!(
+            "✅ Release {} deployed to {}: {} service(s) committed together.",
+            release_name,
+            selected_env.name,
+            services.len()
+        );
+    }
+    Ok(())
+}
+
+/// The environment of a deploy, with its secrets resolved and its YAML sources pulled
+/// and checked for commit signing.
+struct Target {
+    env: Environment,
+    /// Applies run as server-side dry-runs and commits go to a scratch branch.
+    sandbox: bool,
+    /// Git steps only print what they would do. The demo manifests live outside any
+    /// repository.
+    git_dry_run: bool,
+}
+
+async fn prepare_target(
+    config: &Config,
+    env: Option<String>,
+    dry_run: bool,
+    action: &str,
+) -> Result<Target> {
+    let mut env = resolve_environment(config, env)?;
+    env.registry_access_token = secrets::resolve_optional(env.registry_access_token.as_deref())
+        .await
+        .context("Failed to resolve registry access token")?;
+    env.notify_webhook = secrets::resolve_optional(env.notify_webhook.as_deref())
+        .await
+        .context("Failed to resolve notification webhook")?;
+
+    let git_dry_run = dry_run || mock::enabled();
+    let sandbox = env.dry_only.unwrap_or(false);
+    if sandbox {
+        println!(
+            "🧪 {} is a sandbox environment: apply runs as a server-side dry-run and changes are committed to a scratch branch.",
+            env.name
+        );
+    }
+    pull_yaml_sources(&env, git_dry_run, action)?;
+    check_commit_signing(&env, git_dry_run)?;
+    Ok(Target {
+        env,
+        sandbox,
+        git_dry_run,
+    })
+}
+
+/// The justification of a deploy at `at` during a freeze window of the environment, with
+/// the window it overrides.
+fn freeze_override(
+    env: &Environment,
+    at: DateTime<Local>,
+    justification: Option<&str>,
+) -> Result<Option<String>> {
+    Ok(match freeze::active_window(env, at)? {
+        Some(window) => Some(format!(
+            "{} ({})",
+            freeze::override_justification(env, &window, justification)?,
+            window
+        )),
+        None => None,
+    })
+}
+
+/// Audit entries of the services a deploy changes, each with its tag. Dry runs change
+/// nothing and leave no trail.
+pub(crate) struct AuditTrail<'a> {
+    env: &'a str,
+    services: Vec<(&'a str, &'a str)>,
+    dry_run: bool,
+}
+
+impl<'a> AuditTrail<'a> {
+    pub(crate) fn new(env: &'a str, services: Vec<(&'a str, &'a str)>, dry_run: bool) -> Self {
+        Self {
+            env,
+            services,
+            dry_run,
+        }
+    }
+
+    fn entry(&self, action: audit::Action, i: usize) -> audit::AuditEntry {
+        let (service, tag) = self.services[i];
+        audit::AuditEntry::new(action, self.env, service).tag(tag)
+    }
+
+    fn record(&self, entry: audit::AuditEntry) {
+        if !self.dry_run {
+            entry.record();
+        }
+    }
+
+    fn record_all(&self, action: audit::Action, detail: Option<&str>) {
+        for i in 0..self.services.len() {
+            self.record(match detail {
+                Some(detail) => self.entry(action, i).detail(detail),
+                None => self.entry(action, i),
+            });
+        }
+    }
+
+    /// Records the start of the deploy, after the freeze window it overrides if any.
+    fn start(&self, freeze_override: Option<&str>, detail: Option<&str>) {
+        if let Some(justification) = freeze_override {
+            self.record_all(audit::Action::FreezeOverridden, Some(justification));
+        }
+        self.record_all(audit::Action::DeployStarted, detail);
+    }
+}
+
+/// Confirms the deploy to a protected environment, then of each protected service, and
+/// collects the approvals the services require, auditing how each was given.
+pub(crate) fn confirm_protection(
+    config: &Config,
+    env: &Environment,
+    trail: &AuditTrail,
+    confirm_protected: Option<&str>,
+    approved_by: &[String],
+    operation: &str,
+) -> Result<Vec<String>> {
+    if env.protected.unwrap_or(false) {
+        let how = protection::confirm_protected(
+            ProtectedTarget::Environment(&env.name),
+            confirm_protected,
+            operation,
+        )?;
+        trail.record_all(audit::Action::Confirmed, Some(&how));
+    }
+    let mut min_approvals = 0;
+    for (i, (service, _)) in trail.services.iter().enumerate() {
+        let protection = protection::service_protection(config, &env.name, service);
+        min_approvals = min_approvals.max(protection.min_approvals);
+        if !protection.confirm {
+            continue;
+        }
+        let how = protection::confirm_protected(
+            ProtectedTarget::Service {
+                name: service,
+                env: &env.name,
+            },
+            confirm_protected,
+            operation,
+        )?;
+        trail.record(trail.entry(audit::Action::Confirmed, i).detail(how));
+    }
+    let approvals =
+        protection::collect_approvals(min_approvals, approved_by, &history::current_user())?;
+    if !approvals.is_empty() {
+        trail.record_all(
+            audit::Action::Confirmed,
+            Some(&format!("approved by {}", approvals.join(", "))),
+        );
+    }
+    Ok(approvals)
+}
+
+/// Takes the deploy locks of the services, held until dropped so that nobody else
+/// deploys them meanwhile. Dry runs and sandboxes take none.
+async fn acquire_locks(
+    target: &Target,
+    services: &[&ServiceSource],
+    dry_run: bool,
+) -> Result<Vec<DeployLock>> {
+    let mut locks = Vec::new();
+    if dry_run || target.sandbox {
+        return Ok(locks);
+    }
+    for service in services {
+        locks.extend(deploy_lock::acquire(&target.env, service).await?);
+    }
+    Ok(locks)
+}
+
+/// Appends the approvals, the freeze override and any other trailers to a commit message.
+pub(crate) fn with_trailers(
+    message: String,
+    approvals: &[String],
+    freeze_override: Option<&str>,
+    extra: Vec<String>,
+) -> String {
+    let mut trailers: Vec<String> = approvals
+        .iter()
+        .map(|name| format!("Approved-by: {}", name))
+        .collect();
+    if let Some(justification) = freeze_override {
+        trailers.push(format!("Freeze-override: {}", justification));
+    }
+    trailers.extend(extra);
+    match trailers.is_empty() {
+        true => message,
+        false => format!("{}\n\n{}", message, trailers.join("\n")),
+    }
+}
+
+/// Commits the files of a repository: pushed to the current branch, with the push
+/// audited for the services at `pushed`, or committed to a local scratch branch named
+/// after `name` and `tag` for sandbox environments.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn commit(
+    env: &Environment,
+    root: &Path,
+    (name, tag): (&str, &str),
+    message: &str,
+    files: &[PathBuf],
+    dry_run: bool,
+    trail: &AuditTrail,
+    pushed: &[usize],
+) -> Result<()> {
+    if !env.dry_only.unwrap_or(false) {
+        let result = Git::commit_and_push(root, message, files, dry_run);
+        for &i in pushed.iter().filter(|_| !dry_run) {
+            trail.record(match &result {
+                Ok(_) => trail.entry(audit::Action::GitPushed, i),
+                Err(e) => trail
+                    .entry(audit::Action::GitPushFailed, i)
+                    .detail(format!("{:#}", e)),
+            });
+        }
+        result?;
+        if !dry_run {
+            println!("✅ Changes committed and pushed to Git.");
+        }
+        return Ok(());
+    }
+
+    let branch = sandbox_branch(&env.name, name, tag, Local::now());
+    Git::commit_to_branch(root, &branch, message, files, dry_run)?;
+    if !dry_run {
+        println!(
+            "✅ Sandbox changes committed to local branch {} (not pushed).",
+            branch
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_trailers_appends_approvals_then_freeze_override_then_extra() {
+        let message = with_trailers(
+            "deploy(production): update auth-api to v1.2.3".to_string(),
+            &["ada".to_string(), "bob".to_string()],
+            Some("hotfix (Fri 16:00 - Mon 08:00)"),
+            vec!["Ticket: OPS-42".to_string()],
+        );
+
+        assert_eq!(
+            message,
+            "deploy(production): update auth-api to v1.2.3\n\n\
+             Approved-by: ada\n\
+             Approved-by: bob\n\
+             Freeze-override: hotfix (Fri 16:00 - Mon 08:00)\n\
+             Ticket: OPS-42"
+        );
+        assert_eq!(
+            with_trailers(
+                "release(staging): deploy r1".to_string(),
+                &[],
+                None,
+                Vec::new()
+            ),
+            "release(staging): deploy r1"
+        );
+    }
+}
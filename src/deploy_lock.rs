@@ -0,0 +1,192 @@
+use anyhow::{Context, Result};
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+use k8s_openapi::jiff::{SignedDuration, Timestamp};
+use kube::api::{Api, DeleteParams, PostParams};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::cluster;
+use crate::config::{Environment, ServiceSource};
+use crate::history;
+use crate::mock;
+
+/// Seconds after its last renewal at which a lease left behind by a crashed davit can be
+/// taken over.
+const LEASE_DURATION_SECS: i32 = 120;
+const LEASE_RENEW_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Lease held in the service's namespace for the length of a deploy, so that two people
+/// deploying the same service do not race on `kubectl apply` and `git push`. It is
+/// renewed in the background and deleted when dropped; if davit is killed, it expires.
+pub struct DeployLock {
+    api: Api<Lease>,
+    name: String,
+    holder: String,
+    renewal: JoinHandle<()>,
+}
+
+/// Takes the deploy lease of the service, failing while someone else holds it. Demo mode
+/// has no cluster to coordinate through.
+pub async fn acquire(env: &Environment, service: &ServiceSource) -> Result<Option<DeployLock>> {
+    if mock::enabled() {
+        return Ok(None);
+    }
+    let client = cluster::client(&env.kubectl_context).await?;
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let api: Api<Lease> = Api::namespaced(client, namespace);
+    let name = format!("davit-deploy-{}", service.name);
+    let holder = format!("{} (pid {})", history::current_user(), std::process::id());
+    let now = Timestamp::now();
+
+    let existing = api
+        .get_opt(&name)
+        .await
+        .with_context(|| format!("Failed to read the deploy lease {}/{}", namespace, name))?;
+    let taken = match existing {
+        None => {
+            let lease = Lease {
+                metadata: ObjectMeta {
+                    name: Some(name.clone()),
+                    annotations: Some(BTreeMap::from([
+                        ("davit.io/environment".to_string(), env.name.clone()),
+                        ("davit.io/service".to_string(), service.name.clone()),
+                    ])),
+                    ..Default::default()
+                },
+                spec: Some(held_spec(&holder, now, 0)),
+            };
+            api.create(&PostParams::default(), &lease).await
+        }
+        Some(lease) => {
+            if let Some(other) = held_by_other(&lease, &holder, now) {
+                return Err(anyhow::anyhow!(
+                    "🔒 {} is being deployed to {} by {}; wait for that deploy to finish (its lease expires {}s after it stops being renewed)",
+                    service.name,
+                    env.name,
+                    other,
+                    LEASE_DURATION_SECS
+                ));
+            }
+            let transitions = lease
+                .spec
+                .as_ref()
+                .and_then(|s| s.lease_transitions)
+                .unwrap_or(0);
+            let mut lease = lease;
+            lease.spec = Some(held_spec(&holder, now, transitions + 1));
+            api.replace(&name, &PostParams::default(), &lease).await
+        }
+    };
+    // A conflict means another deploy took the lease between our read and write.
+    taken.with_context(|| {
+        format!(
+            "Failed to take the deploy lease {}/{}: another deploy of {} may have just started",
+            namespace, name, service.name
+        )
+    })?;
+
+    let renewal = tokio::spawn(renew(api.clone(), name.clone(), holder.clone()));
+    Ok(Some(DeployLock {
+        api,
+        name,
+        holder,
+        renewal,
+    }))
+}
+
+fn held_spec(holder: &str, now: Timestamp, transitions: i32) -> LeaseSpec {
+    LeaseSpec {
+        holder_identity: Some(holder.to_string()),
+        lease_duration_seconds: Some(LEASE_DURATION_SECS),
+        acquire_time: Some(MicroTime(now)),
+        renew_time: Some(MicroTime(now)),
+        lease_transitions: Some(transitions),
+        ..Default::default()
+    }
+}
+
+/// The holder of the lease, with since when, unless it is `holder`, released or expired.
+fn held_by_other(lease: &Lease, holder: &str, now: Timestamp) -> Option<String> {
+    let spec = lease.spec.as_ref()?;
+    let other = spec.holder_identity.as_deref().filter(|h| *h != holder)?;
+    let renewed = spec.renew_time.as_ref().or(spec.acquire_time.as_ref())?.0;
+    let duration = spec.lease_duration_seconds.unwrap_or(LEASE_DURATION_SECS);
+    if renewed + SignedDuration::from_secs(duration as i64) < now {
+        return None;
+    }
+    Some(match &spec.acquire_time {
+        Some(acquired) => format!(
+            "{} since {}",
+            other,
+            acquired.0.strftime("%Y-%m-%d %H:%M UTC")
+        ),
+        None => other.to_string(),
+    })
+}
+
+async fn renew(api: Api<Lease>, name: String, holder: String) {
+    loop {
+        tokio::time::sleep(LEASE_RENEW_INTERVAL).await;
+        let Ok(mut lease) = api.get(&name).await else {
+            continue;
+        };
+        let Some(spec) = lease.spec.as_mut() else {
+            continue;
+        };
+        if spec.holder_identity.as_deref() != Some(holder.as_str()) {
+            return;
+        }
+        spec.renew_time = Some(MicroTime(Timestamp::now()));
+        let _ = api.replace(&name, &PostParams::default(), &lease).await;
+    }
+}
+
+impl Drop for DeployLock {
+    fn drop(&mut self) {
+        self.renewal.abort();
+        let release = async {
+            let lease = self.api.get_opt(&self.name).await.ok().flatten()?;
+            let held = lease.spec.as_ref()?.holder_identity.as_deref() == Some(&self.holder);
+            if held {
+                self.api
+                    .delete(&self.name, &DeleteParams::default())
+                    .await
+                    .ok()?;
+            }
+            Some(())
+        };
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            tokio::task::block_in_place(|| runtime.block_on(release));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_blocks_until_released_or_expired() {
+        let acquired: Timestamp = "2026-10-15T09:00:00Z".parse().unwrap();
+        let lease = Lease {
+            spec: Some(held_spec("jane@acme.io (pid 7)", acquired, 0)),
+            ..Default::default()
+        };
+
+        let soon = acquired + SignedDuration::from_secs(60);
+        assert_eq!(
+            held_by_other(&lease, "joe@acme.io (pid 9)", soon).as_deref(),
+            Some("jane@acme.io (pid 7) since 2026-10-15 09:00 UTC")
+        );
+        assert_eq!(held_by_other(&lease, "jane@acme.io (pid 7)", soon), None);
+
+        let expired = acquired + SignedDuration::from_secs(LEASE_DURATION_SECS as i64 + 1);
+        assert_eq!(held_by_other(&lease, "joe@acme.io (pid 9)", expired), None);
+        assert_eq!(
+            held_by_other(&Lease::default(), "joe@acme.io (pid 9)", soon),
+            None
+        );
+    }
+}
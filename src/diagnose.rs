@@ -0,0 +1,368 @@
+use anyhow::Result;
+use console::style;
+use k8s_openapi::api::core::v1::{Event, Pod, Probe};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::{Api, Client, api::ListParams};
+
+use crate::dashboard::pod_runs_tag;
+
+/// Why a new pod is not becoming ready, with what to check next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub pod: String,
+    pub cause: String,
+    pub details: Vec<String>,
+    pub hints: Vec<String>,
+}
+
+/// Inspects the pods running the new tag and their events, and explains the ones
+/// holding the rollout back.
+pub async fn diagnose(
+    client: Client,
+    namespace: &str,
+    selector: &str,
+    tag: &str,
+) -> Result<Vec<Finding>> {
+    let pods_api: Api<Pod> = Api::namespaced(client.clone(), namespace);
+    let events_api: Api<Event> = Api::namespaced(client, namespace);
+
+    let pods = pods_api
+        .list(&ListParams::default().labels(selector))
+        .await?
+        .items;
+    let mut findings = Vec::new();
+    for pod in pods.iter().filter(|p| pod_runs_tag(p, tag)) {
+        let name = pod.metadata.name.clone().unwrap_or_default();
+        // Events are best effort: the pod state alone classifies most causes.
+        let events = events_api
+            .list(&ListParams::default().fields(&format!("involvedObject.name={}", name)))
+            .await
+            .map(|l| l.items)
+            .unwrap_or_default();
+        findings.extend(classify(pod, &events));
+    }
+    Ok(findings)
+}
+
+/// Classifies why a pod is not ready: image pull failures, scheduling, crashes,
+/// configuration errors or failing readiness probes.
+pub fn classify(pod: &Pod, events: &[Event]) -> Option<Finding> {
+    let name = pod.metadata.name.clone().unwrap_or_default();
+    let status = pod.status.as_ref()?;
+    let event_messages = |reason: &str| -> Vec<String> {
+        events
+            .iter()
+            .filter(|e| e.reason.as_deref() == Some(reason))
+            .filter_map(|e| e.message.clone())
+            .collect()
+    };
+    let finding = |cause: &str, details: Vec<String>, hints: Vec<String>| Finding {
+        pod: name.clone(),
+        cause: cause.to_string(),
+        details,
+        hints,
+    };
+
+    if let Some(condition) = status.conditions.iter().flatten().find(|c| {
+        c.type_ == "PodScheduled"
+            && c.status == "False"
+            && c.reason.as_deref() == Some("Unschedulable")
+    }) {
+        let message = condition.message.clone().unwrap_or_default();
+        return Some(finding(
+            "Unschedulable",
+            vec![message.clone()],
+            scheduling_hints(pod, &message),
+        ));
+    }
+
+    for container in status.container_statuses.iter().flatten() {
+        if container.ready {
+            continue;
+        }
+        let waiting = container.state.as_ref().and_then(|s| s.waiting.as_ref());
+        let reason = waiting
+            .and_then(|w| w.reason.as_deref())
+            .unwrap_or_default();
+        let message = waiting.and_then(|w| w.message.clone()).unwrap_or_default();
+
+        match reason {
+            "ImagePullBackOff" | "ErrImagePull" | "InvalidImageName" => {
+                let mut details = vec![format!("{}: {}", container.name, container.image)];
+                details.extend(event_messages("Failed"));
+                let text = details.join(" ").to_lowercase();
+                return Some(finding(reason, details, image_pull_hints(&text)));
+            }
+            "CrashLoopBackOff" => {
+                let terminated = container
+                    .last_state
+                    .as_ref()
+                    .and_then(|s| s.terminated.as_ref());
+                let exit = terminated
+                    .map(|t| {
+                        format!(
+                            "last exit code {} ({})",
+                            t.exit_code,
+                            t.reason.as_deref().unwrap_or("Error")
+                        )
+                    })
+                    .unwrap_or_else(|| "container keeps exiting".to_string());
+                let mut hints = vec![format!(
+                    "Check the logs of the previous run: kubectl logs {} -c {} --previous",
+                    name, container.name
+                )];
+                if terminated.and_then(|t| t.reason.as_deref()) == Some("OOMKilled") {
+                    hints.push(
+                        "The container ran out of memory: raise its memory limit or reduce usage"
+                            .to_string(),
+                    );
+                }
+                return Some(finding(
+                    "CrashLoopBackOff",
+                    vec![format!(
+                        "{}: {}, {} restarts",
+                        container.name, exit, container.restart_count
+                    )],
+                    hints,
+                ));
+            }
+            "CreateContainerConfigError" | "CreateContainerError" => {
+                return Some(finding(
+                    reason,
+                    vec![format!("{}: {}", container.name, message)],
+                    vec![
+                        "A referenced ConfigMap, Secret or key is probably missing: check the env and volume sources of the container"
+                            .to_string(),
+                    ],
+                ));
+            }
+            _ => {}
+        }
+
+        let running = container
+            .state
+            .as_ref()
+            .is_some_and(|s| s.running.is_some());
+        let probe_failures = event_messages("Unhealthy");
+        if running && !probe_failures.is_empty() {
+            let probe = pod
+                .spec
+                .as_ref()
+                .and_then(|s| s.containers.iter().find(|c| c.name == container.name))
+                .and_then(|c| c.readiness_probe.as_ref());
+            let mut details: Vec<String> = probe_failures.into_iter().take(3).collect();
+            if let Some(probe) = probe {
+                details.push(format!("readinessProbe: {}", describe_probe(probe)));
+            }
+            return Some(finding(
+                "Failing readiness probe",
+                details,
+                vec![
+                    "Check the endpoint answers inside the pod (port, path, startup time) and that initialDelaySeconds/failureThreshold leave enough time to start"
+                        .to_string(),
+                ],
+            ));
+        }
+    }
+    None
+}
+
+fn image_pull_hints(text: &str) -> Vec<String> {
+    if text.contains("not found") || text.contains("manifest unknown") {
+        vec!["The tag does not exist in the registry: check it was pushed and the image path is right".to_string()]
+    } else if ["403", "denied", "unauthorized", "permission"]
+        .iter()
+        .any(|k| text.contains(k))
+    {
+        vec![
+            "The nodes cannot read the registry: grant the node service account (`node_service_account`) reader access, or add an imagePullSecret".to_string(),
+        ]
+    } else if text.contains("timeout") || text.contains("i/o") {
+        vec![
+            "The registry could not be reached from the nodes: check egress/firewall rules"
+                .to_string(),
+        ]
+    } else {
+        vec![
+            "Check the image reference and the registry credentials available to the nodes"
+                .to_string(),
+        ]
+    }
+}
+
+fn scheduling_hints(pod: &Pod, message: &str) -> Vec<String> {
+    let mut hints = Vec::new();
+    if message.contains("Insufficient") {
+        let requests: Vec<String> = pod
+            .spec
+            .iter()
+            .flat_map(|s| &s.containers)
+            .filter_map(|c| {
+                let requests = c.resources.as_ref()?.requests.as_ref()?;
+                let described: Vec<String> = requests
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v.0))
+                    .collect();
+                Some(format!("{} requests {}", c.name, described.join(", ")))
+            })
+            .collect();
+        hints.push(format!(
+            "No node has enough free resources{}: lower the requests or scale up the node pool",
+            if requests.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", requests.join("; "))
+            }
+        ));
+    }
+    if message.contains("node selector") || message.contains("affinity") {
+        hints.push("No node matches the nodeSelector/affinity rules of the pod".to_string());
+    }
+    if message.contains("taint") {
+        hints.push("Nodes are tainted: add matching tolerations or target other nodes".to_string());
+    }
+    if message.contains("PersistentVolumeClaim") || message.contains("volume") {
+        hints.push("A volume claim is unbound or bound to another zone".to_string());
+    }
+    if hints.is_empty() {
+        hints.push("Check node capacity and the scheduling constraints of the pod".to_string());
+    }
+    hints
+}
+
+fn describe_probe(probe: &Probe) -> String {
+    let port = |p: &IntOrString| match p {
+        IntOrString::Int(i) => i.to_string(),
+        IntOrString::String(s) => s.clone(),
+    };
+    let action = if let Some(http) = &probe.http_get {
+        format!(
+            "GET :{}{}",
+            port(&http.port),
+            http.path.as_deref().unwrap_or("/")
+        )
+    } else if let Some(tcp) = &probe.tcp_socket {
+        format!("TCP :{}", port(&tcp.port))
+    } else if let Some(exec) = &probe.exec {
+        format!(
+            "exec {}",
+            exec.command
+                .iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+    } else {
+        "unknown action".to_string()
+    };
+    format!(
+        "{}, initialDelaySeconds={}, periodSeconds={}, timeoutSeconds={}, failureThreshold={}",
+        action,
+        probe.initial_delay_seconds.unwrap_or(0),
+        probe.period_seconds.unwrap_or(10),
+        probe.timeout_seconds.unwrap_or(1),
+        probe.failure_threshold.unwrap_or(3)
+    )
+}
+
+pub fn print_findings(findings: &[Finding]) {
+    if findings.is_empty() {
+        return;
+    }
+    println!("\n🔍 Why the rollout is stuck:");
+    for f in findings {
+        println!(
+            "\n  {} {}",
+            style(&f.cause).red().bold(),
+            style(&f.pod).dim()
+        );
+        for detail in &f.details {
+            println!("    {}", detail);
+        }
+        for hint in &f.hints {
+            println!("    💡 {}", hint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod(value: serde_json::Value) -> Pod {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn event(reason: &str, message: &str) -> Event {
+        serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "e"},
+            "involvedObject": {"name": "auth-api-1"},
+            "reason": reason,
+            "message": message
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_classify_image_pull_permission_error() {
+        let pod = pod(serde_json::json!({
+            "metadata": {"name": "auth-api-1"},
+            "status": {"containerStatuses": [{
+                "name": "auth-api", "image": "europe-docker.pkg.dev/acme/apps/auth-api:v2",
+                "imageID": "", "ready": false, "restartCount": 0,
+                "state": {"waiting": {"reason": "ImagePullBackOff"}}
+            }]}
+        }));
+        let finding = classify(
+            &pod,
+            &[event("Failed", "Failed to pull image: 403 Forbidden")],
+        )
+        .unwrap();
+        assert_eq!(finding.cause, "ImagePullBackOff");
+        assert!(finding.hints[0].contains("node_service_account"));
+    }
+
+    #[test]
+    fn test_classify_unschedulable_shows_requests() {
+        let pod = pod(serde_json::json!({
+            "metadata": {"name": "auth-api-1"},
+            "spec": {"containers": [{"name": "auth-api",
+                "resources": {"requests": {"cpu": "4"}}}]},
+            "status": {"conditions": [{
+                "type": "PodScheduled", "status": "False", "reason": "Unschedulable",
+                "message": "0/3 nodes are available: 3 Insufficient cpu."
+            }]}
+        }));
+        let finding = classify(&pod, &[]).unwrap();
+        assert_eq!(finding.cause, "Unschedulable");
+        assert!(finding.hints[0].contains("auth-api requests cpu=4"));
+    }
+
+    #[test]
+    fn test_classify_failing_readiness_probe_shows_probe() {
+        let pod = pod(serde_json::json!({
+            "metadata": {"name": "auth-api-1"},
+            "spec": {"containers": [{"name": "auth-api", "readinessProbe": {
+                "httpGet": {"path": "/ready", "port": 8080}, "periodSeconds": 5
+            }}]},
+            "status": {"containerStatuses": [{
+                "name": "auth-api", "image": "auth-api:v2", "imageID": "",
+                "ready": false, "restartCount": 0, "state": {"running": {}}
+            }]}
+        }));
+        let finding = classify(
+            &pod,
+            &[event(
+                "Unhealthy",
+                "Readiness probe failed: HTTP probe failed with statuscode: 503",
+            )],
+        )
+        .unwrap();
+        assert_eq!(finding.cause, "Failing readiness probe");
+        assert_eq!(
+            finding.details[1],
+            "readinessProbe: GET :8080/ready, initialDelaySeconds=0, periodSeconds=5, timeoutSeconds=1, failureThreshold=3"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::ServiceSource;
+use crate::mock;
+
+/// Bumped when the extraction of services from a manifest changes, so that caches
+/// written by older versions are discarded.
+const CACHE_VERSION: u32 = 1;
+
+/// Services discovered in each YAML file of a source, with the modification time and
+/// size the file had. Files whose metadata still match are not parsed again; files gone
+/// from the walk drop out of the cache when it is rebuilt.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DiscoveryCache {
+    /// Settings the services were extracted with; a change discards the whole cache.
+    fingerprint: String,
+    files: BTreeMap<PathBuf, CachedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFile {
+    modified: SystemTime,
+    size: u64,
+    services: Vec<ServiceSource>,
+}
+
+impl DiscoveryCache {
+    pub fn new(fingerprint: &str) -> Self {
+        Self {
+            fingerprint: versioned(fingerprint),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Cache at `path`, or an empty one when it is missing, unreadable or was written
+    /// with another fingerprint.
+    pub fn load(path: &Path, fingerprint: &str) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|cache| cache.fingerprint == versioned(fingerprint))
+            .unwrap_or_else(|| Self::new(fingerprint))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Services of `file` when it has not changed since they were cached.
+    pub fn lookup(&self, file: &Path, metadata: &fs::Metadata) -> Option<&[ServiceSource]> {
+        let cached = self.files.get(file)?;
+        let modified = metadata.modified().ok()?;
+        (cached.modified == modified && cached.size == metadata.len())
+            .then_some(cached.services.as_slice())
+    }
+
+    pub fn insert(&mut self, file: &Path, metadata: &fs::Metadata, services: Vec<ServiceSource>) {
+        let Ok(modified) = metadata.modified() else {
+            return;
+        };
+        self.files.insert(
+            file.to_path_buf(),
+            CachedFile {
+                modified,
+                size: metadata.len(),
+                services,
+            },
+        );
+    }
+
+    /// Whether both caches hold the same files with the same metadata.
+    pub fn same_files(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint
+            && self.files.len() == other.files.len()
+            && self
+                .files
+                .iter()
+                .zip(&other.files)
+                .all(|((a, a_file), (b, b_file))| {
+                    a == b && a_file.modified == b_file.modified && a_file.size == b_file.size
+                })
+    }
+}
+
+fn versioned(fingerprint: &str) -> String {
+    format!("v{}|{}", CACHE_VERSION, fingerprint)
+}
+
+/// Cache file of a YAML source of an environment; none in tests, which must not share
+/// state through the user's cache directory.
+pub fn cache_path(env_name: &str, source_root: &Path) -> Option<PathBuf> {
+    if cfg!(test) {
+        return None;
+    }
+    let dir = if mock::enabled() {
+        std::env::temp_dir().join("davit-mock").join("cache")
+    } else {
+        ProjectDirs::from("com", "davit", "davit")?
+            .cache_dir()
+            .to_path_buf()
+    };
+    let mut hasher = DefaultHasher::new();
+    source_root.hash(&mut hasher);
+    Some(
+        dir.join("discovery")
+            .join(format!("{}-{:016x}.json", env_name, hasher.finish())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_misses_changed_files_and_other_fingerprints() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let manifest = dir.path().join("deployment.yaml");
+        fs::write(&manifest, "kind: Deployment\n")?;
+        let metadata = fs::metadata(&manifest)?;
+
+        let mut cache = DiscoveryCache::new("gcr.io");
+        cache.insert(&manifest, &metadata, Vec::new());
+        assert!(cache.lookup(&manifest, &metadata).is_some());
+
+        let cache_file = dir.path().join("cache.json");
+        cache.save(&cache_file)?;
+        let reloaded = DiscoveryCache::load(&cache_file, "gcr.io");
+        assert!(reloaded.same_files(&cache));
+        assert!(reloaded.lookup(&manifest, &metadata).is_some());
+
+        fs::write(&manifest, "kind: Deployment\nmetadata: {}\n")?;
+        let changed = fs::metadata(&manifest)?;
+        assert!(reloaded.lookup(&manifest, &changed).is_none());
+
+        let other = DiscoveryCache::load(&cache_file, "pkg.dev");
+        assert!(other.lookup(&manifest, &metadata).is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,450 @@
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::io::ErrorKind;
+use std::process::Command;
+
+use crate::blueprint::split_image_ref;
+use crate::cluster;
+use crate::config::{Config, Environment, ServiceSource};
+use crate::config_check;
+use crate::contexts;
+use crate::git::Git;
+use crate::mock;
+use crate::offline;
+use crate::output;
+use crate::rbac;
+use crate::secrets;
+
+/// Command-line tools davit runs, with whether deploys need them and what for.
+const TOOLS: [(&str, &[&str], bool, &str); 3] = [
+    (
+        "kubectl",
+        &["version", "--client"],
+        true,
+        "applies manifests and follows rollouts",
+    ),
+    (
+        "gcloud",
+        &["--version"],
+        false,
+        "lists GCR images and fetches GKE credentials",
+    ),
+    (
+        "git",
+        &["--version"],
+        false,
+        "used by deploy hooks and to fix checkouts by hand; davit itself uses libgit2",
+    ),
+];
+
+/// How many uncommitted files are named in a report line.
+const LISTED_CHANGES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// One line of the `davit doctor` report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    /// `tools`, `config` or the environment name.
+    pub scope: String,
+    pub name: String,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(scope: &str, name: &str, outcome: Outcome, detail: impl Into<String>) -> Self {
+        Self {
+            scope: scope.to_string(),
+            name: name.to_string(),
+            outcome,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Runs the preflight checks of a machine: the tools on the `PATH`, then for each
+/// environment its kubectl context, the RBAC to patch its workloads, its YAML
+/// checkouts and its registry. Environments are only checked when the config loaded.
+pub async fn run(config: Result<Config>) -> Vec<Check> {
+    let mut checks: Vec<Check> = TOOLS
+        .iter()
+        .map(|(tool, args, required, purpose)| check_tool(tool, args, *required, purpose))
+        .collect();
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            checks.push(Check::new(
+                "config",
+                "configuration",
+                Outcome::Fail,
+                format!("{:#}; see `davit config init`", e),
+            ));
+            return checks;
+        }
+    };
+    for env in &config.environments {
+        output::progress(format!("🩺 Checking {}...", env.name));
+        checks.extend(check_environment(env).await);
+    }
+    checks
+}
+
+fn check_tool(tool: &str, args: &[&str], required: bool, purpose: &str) -> Check {
+    let missing = if required {
+        Outcome::Fail
+    } else {
+        Outcome::Warn
+    };
+    match Command::new(tool).args(args).output() {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            Check::new("tools", tool, Outcome::Pass, version)
+        }
+        Ok(output) => Check::new(
+            "tools",
+            tool,
+            missing,
+            format!(
+                "`{} {}` failed: {}",
+                tool,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ),
+        Err(e) if e.kind() == ErrorKind::NotFound => Check::new(
+            "tools",
+            tool,
+            missing,
+            format!("not found in PATH; it {}", purpose),
+        ),
+        Err(e) => Check::new("tools", tool, missing, e.to_string()),
+    }
+}
+
+async fn check_environment(env: &Environment) -> Vec<Check> {
+    let scope = env.name.as_str();
+    let mut checks = Vec::new();
+
+    let reachable = if mock::enabled() {
+        checks.push(Check::new(
+            scope,
+            "context",
+            Outcome::Pass,
+            "demo mode: simulated cluster",
+        ));
+        false
+    } else {
+        let check = check_context(env).await;
+        let reachable = check.outcome == Outcome::Pass;
+        checks.push(check);
+        reachable
+    };
+
+    let services = match env.list_services() {
+        Ok(services) => {
+            checks.push(Check::new(
+                scope,
+                "services",
+                Outcome::Pass,
+                format!("{} services discovered", services.len()),
+            ));
+            services
+        }
+        Err(e) => {
+            checks.push(Check::new(
+                scope,
+                "services",
+                Outcome::Fail,
+                format!("{:#}", e),
+            ));
+            Vec::new()
+        }
+    };
+    if reachable {
+        checks.extend(check_rbac(env, &services).await);
+    }
+    checks.extend(check_checkouts(env));
+    checks.extend(check_registries(env, &services).await);
+    checks
+}
+
+async fn check_context(env: &Environment) -> Check {
+    let scope = env.name.as_str();
+    let local = match contexts::local_contexts() {
+        Ok(local) => local,
+        Err(e) => return Check::new(scope, "context", Outcome::Fail, format!("{:#}", e)),
+    };
+    if !local.iter().any(|c| c.name == env.kubectl_context) {
+        let wanted = env.context_alias.as_ref().unwrap_or(&env.kubectl_context);
+        let detail = match &env.gke_cluster {
+            Some(cluster) => format!(
+                "'{}' is not in your kubeconfig; davit offers to fetch the credentials of GKE cluster {} on first use",
+                wanted, cluster.name
+            ),
+            None => format!(
+                "'{}' is not in your kubeconfig; see `kubectl config get-contexts`",
+                wanted
+            ),
+        };
+        return Check::new(scope, "context", Outcome::Fail, detail);
+    }
+    match config_check::probe_context(&env.kubectl_context).await {
+        Ok(()) => Check::new(
+            scope,
+            "context",
+            Outcome::Pass,
+            format!("{} reachable", env.kubectl_context),
+        ),
+        Err(reason) => Check::new(
+            scope,
+            "context",
+            Outcome::Fail,
+            format!("{} is unreachable: {}", env.kubectl_context, reason),
+        ),
+    }
+}
+
+/// Workload kinds and namespaces of the services, e.g. `Deployment` in `auth`.
+fn workload_targets<'a>(
+    workloads: impl Iterator<Item = (&'a str, Option<&'a str>)>,
+) -> BTreeSet<(String, String)> {
+    let mut targets: BTreeSet<(String, String)> = workloads
+        .map(|(kind, namespace)| (kind.to_string(), namespace.unwrap_or("default").to_string()))
+        .collect();
+    if targets.is_empty() {
+        targets.insert(("Deployment".to_string(), "default".to_string()));
+    }
+    targets
+}
+
+async fn check_rbac(env: &Environment, services: &[ServiceSource]) -> Vec<Check> {
+    let scope = env.name.as_str();
+    let client = match cluster::client(&env.kubectl_context).await {
+        Ok(client) => client,
+        Err(e) => return vec![Check::new(scope, "rbac", Outcome::Fail, format!("{:#}", e))],
+    };
+    let workloads = services
+        .iter()
+        .map(|s| (s.kind.as_str(), s.namespace.as_deref()));
+    let mut checks = Vec::new();
+    for (kind, namespace) in workload_targets(workloads) {
+        let permissions = rbac::deploy_permissions(&kind);
+        let check = match rbac::denied(client.clone(), &namespace, &permissions).await {
+            Ok(denied) if denied.is_empty() => Check::new(
+                scope,
+                "rbac",
+                Outcome::Pass,
+                format!("can deploy {} in {}", permissions[0].resource, namespace),
+            ),
+            Ok(denied) => Check::new(
+                scope,
+                "rbac",
+                Outcome::Fail,
+                format!(
+                    "missing in {}: {}",
+                    namespace,
+                    denied
+                        .iter()
+                        .map(|(permission, _)| permission.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ),
+            Err(e) => Check::new(
+                scope,
+                "rbac",
+                Outcome::Fail,
+                format!("access review in {} failed: {:#}", namespace, e),
+            ),
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+/// The YAML sources must be checkouts on a branch with an upstream, so that deploys
+/// can pull, commit and push; uncommitted changes only warn.
+fn check_checkouts(env: &Environment) -> Vec<Check> {
+    let scope = env.name.as_str();
+    let mut seen = BTreeSet::new();
+    let mut checks = Vec::new();
+    for source in env.yaml_sources() {
+        let key = source
+            .root
+            .canonicalize()
+            .unwrap_or_else(|_| source.root.clone());
+        if !seen.insert(key) {
+            continue;
+        }
+        let name = format!("repo {}", source.name);
+        let root = source.root.display();
+        let (outcome, detail) = if !source.root.exists() {
+            (Outcome::Fail, format!("{} does not exist", root))
+        } else if mock::enabled() {
+            (
+                Outcome::Pass,
+                format!("{} (demo mode: not committed)", root),
+            )
+        } else {
+            match Git::checkout(&source.root) {
+                Err(e) => (Outcome::Fail, format!("{}: {:#}", root, e)),
+                Ok(checkout) => match (&checkout.upstream, Git::check_signing(&source.root)) {
+                    (None, _) => (
+                        Outcome::Fail,
+                        format!(
+                            "{}: branch {} has no upstream to push deploys to",
+                            root, checkout.branch
+                        ),
+                    ),
+                    (_, Err(e)) => (Outcome::Fail, format!("{}: {:#}", root, e)),
+                    (Some(upstream), Ok(())) if checkout.changes.is_empty() => (
+                        Outcome::Pass,
+                        format!(
+                            "{} clean on {} tracking {}",
+                            root, checkout.branch, upstream
+                        ),
+                    ),
+                    (Some(_), Ok(())) => (
+                        Outcome::Warn,
+                        format!(
+                            "{} has {} uncommitted changes ({}{})",
+                            root,
+                            checkout.changes.len(),
+                            checkout.changes[..checkout.changes.len().min(LISTED_CHANGES)]
+                                .join(", "),
+                            if checkout.changes.len() > LISTED_CHANGES {
+                                ", ..."
+                            } else {
+                                ""
+                            }
+                        ),
+                    ),
+                },
+            }
+        };
+        checks.push(Check::new(scope, &name, outcome, detail));
+    }
+    checks
+}
+
+/// Lists one repository per registry host the services pull from.
+async fn check_registries(env: &Environment, services: &[ServiceSource]) -> Vec<Check> {
+    let scope = env.name.as_str();
+    let mut hosts = BTreeSet::new();
+    let repositories: Vec<&str> = services
+        .iter()
+        .map(|s| split_image_ref(&s.image_path).0)
+        .filter(|base| hosts.insert(base.split('/').next().unwrap_or(base).to_string()))
+        .collect();
+    if repositories.is_empty() {
+        return Vec::new();
+    }
+    if mock::enabled() {
+        return vec![Check::new(
+            scope,
+            "registry",
+            Outcome::Pass,
+            "demo mode: simulated registry",
+        )];
+    }
+    let mut env = env.clone();
+    match secrets::resolve_optional(env.registry_access_token.as_deref()).await {
+        Ok(token) => env.registry_access_token = token,
+        Err(e) => {
+            return vec![Check::new(
+                scope,
+                "registry",
+                Outcome::Fail,
+                format!("registry_access_token: {:#}", e),
+            )];
+        }
+    }
+    let mut checks = Vec::new();
+    for repository in repositories {
+        let check = match offline::list_images(&env, repository).await {
+            Ok(images) => Check::new(
+                scope,
+                "registry",
+                Outcome::Pass,
+                format!("{} lists {} images", repository, images.len()),
+            ),
+            Err(e) => Check::new(
+                scope,
+                "registry",
+                Outcome::Fail,
+                format!("{}: {:#}", repository, e),
+            ),
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+/// Prints the report grouped by scope, then the totals.
+pub fn print_report(checks: &[Check]) {
+    let mut scope = None;
+    for check in checks {
+        if scope != Some(&check.scope) {
+            println!("\n{}", style(&check.scope).bold());
+            scope = Some(&check.scope);
+        }
+        let mark = match check.outcome {
+            Outcome::Pass => style("✅ pass").green(),
+            Outcome::Warn => style("⚠️  warn").yellow(),
+            Outcome::Fail => style("❌ fail").red().bold(),
+        };
+        println!("  {}  {:<20} {}", mark, check.name, check.detail);
+    }
+    let count = |outcome| checks.iter().filter(|c| c.outcome == outcome).count();
+    println!(
+        "\n{} passed, {} warnings, {} failed",
+        count(Outcome::Pass),
+        count(Outcome::Warn),
+        count(Outcome::Fail)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_tools_fail_only_when_required() {
+        let required = check_tool("davit-no-such-tool", &["--version"], true, "deploys");
+        assert_eq!(required.outcome, Outcome::Fail);
+        assert_eq!(required.detail, "not found in PATH; it deploys");
+        let optional = check_tool("davit-no-such-tool", &["--version"], false, "deploys");
+        assert_eq!(optional.outcome, Outcome::Warn);
+
+        let targets = workload_targets(
+            [
+                ("Deployment", Some("auth")),
+                ("StatefulSet", None),
+                ("Deployment", Some("auth")),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(
+            targets.into_iter().collect::<Vec<_>>(),
+            [
+                ("Deployment".to_string(), "auth".to_string()),
+                ("StatefulSet".to_string(), "default".to_string()),
+            ]
+        );
+        assert_eq!(workload_targets(std::iter::empty()).len(), 1);
+    }
+}
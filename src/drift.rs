@@ -0,0 +1,366 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::fmt;
+
+use crate::cluster;
+use crate::config::{Environment, ServiceSource};
+use crate::mock;
+use crate::resources::{parse_cpu, parse_memory};
+
+/// Maps whose every entry is set by the user: keys found only in the live object were
+/// added by hand rather than defaulted by the API server.
+const USER_MAPS: [&str; 4] = ["labels", "annotations", "nodeSelector", "matchLabels"];
+
+/// Annotations written by kubectl itself, e.g. by `kubectl rollout restart`.
+const TOOL_ANNOTATIONS: [&str; 1] = ["kubectl.kubernetes.io/restartedAt"];
+
+/// A field of the workload whose live value differs from the repository manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Drift {
+    /// Location of the field, e.g. `spec.template.spec.containers[app].image`; list
+    /// items with a `name` are addressed by it.
+    pub path: String,
+    /// Value in the repository; `None` when the field was added in the cluster.
+    pub repo: Option<String>,
+    /// Value in the cluster; `None` when the field is missing from the live object.
+    pub live: Option<String>,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.repo, &self.live) {
+            (Some(repo), Some(live)) => {
+                write!(
+                    f,
+                    "{}: repository has {}, cluster has {}",
+                    self.path, repo, live
+                )
+            }
+            (Some(repo), None) => write!(
+                f,
+                "{}: repository has {}, missing from the cluster",
+                self.path, repo
+            ),
+            (None, live) => write!(
+                f,
+                "{}: only in the cluster ({})",
+                self.path,
+                live.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+}
+
+/// Drift of one service of an environment.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceDrift {
+    pub env: String,
+    pub service: String,
+    pub kind: String,
+    pub drifts: Vec<Drift>,
+    /// Why the service could not be compared, e.g. the live object is missing.
+    pub error: Option<String>,
+}
+
+/// Compares the workload of `service` in the repository with the live object. In demo
+/// mode the cluster is assumed to run the repository manifest.
+pub fn service_drift(env: &Environment, service: &ServiceSource) -> ServiceDrift {
+    let compared = (|| -> Result<Vec<Drift>> {
+        let repo = service
+            .workload_document()
+            .with_context(|| format!("Failed to read the manifest of {}", service.name))?;
+        let live = if mock::enabled() {
+            repo.clone()
+        } else {
+            let manifest = cluster::live_manifest(
+                &env.kubectl_context,
+                &service.kind,
+                service.namespace.as_deref().unwrap_or("default"),
+                &service.name,
+            )?;
+            serde_yaml::from_str(&manifest).context("Failed to parse the live object")?
+        };
+        Ok(compare(&repo, &live))
+    })();
+    let (drifts, error) = match compared {
+        Ok(drifts) => (drifts, None),
+        Err(e) => (Vec::new(), Some(format!("{:#}", e))),
+    };
+    ServiceDrift {
+        env: env.name.clone(),
+        service: service.name.clone(),
+        kind: service.kind.clone(),
+        drifts,
+        error,
+    }
+}
+
+/// Fields of `repo` that the live object does not match. Fields only present in the
+/// live object are defaults filled in by the API server and are ignored, except in
+/// label, annotation and selector maps and in named lists (containers, env, volumes),
+/// where they come from manual edits. Resource quantities are compared by value.
+pub fn compare(repo: &Value, live: &Value) -> Vec<Drift> {
+    let mut live = cluster::strip_server_fields(live.clone());
+    if let Some(metadata) = live.get_mut("metadata").and_then(Value::as_mapping_mut) {
+        metadata.remove("resourceVersion");
+    }
+    let repo = cluster::strip_server_fields(repo.clone());
+    let mut drifts = Vec::new();
+    walk("", &repo, Some(&live), &mut drifts);
+    drifts
+}
+
+fn walk(path: &str, repo: &Value, live: Option<&Value>, drifts: &mut Vec<Drift>) {
+    let Some(live) = live.filter(|live| !live.is_null()) else {
+        if !is_empty(repo) {
+            drifts.push(Drift {
+                path: path.to_string(),
+                repo: Some(summary(repo)),
+                live: None,
+            });
+        }
+        return;
+    };
+
+    match (repo, live) {
+        (Value::Mapping(repo_map), Value::Mapping(live_map)) => {
+            for (key, value) in repo_map {
+                let key = summary(key);
+                walk(&join(path, &key), value, live_map.get(key.as_str()), drifts);
+            }
+            let user_map = USER_MAPS.iter().any(|m| last_segment(path) == *m);
+            if user_map {
+                for (key, value) in live_map {
+                    let key = summary(key);
+                    if !repo_map.contains_key(key.as_str())
+                        && !TOOL_ANNOTATIONS.contains(&key.as_str())
+                    {
+                        drifts.push(Drift {
+                            path: join(path, &key),
+                            repo: None,
+                            live: Some(summary(value)),
+                        });
+                    }
+                }
+            }
+        }
+        (Value::Sequence(repo_items), Value::Sequence(live_items)) => {
+            match (named(repo_items), named(live_items)) {
+                (Some(repo_named), Some(live_named)) => {
+                    for (name, item) in &repo_named {
+                        let live_item = live_named.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+                        walk(&format!("{}[{}]", path, name), item, live_item, drifts);
+                    }
+                    for (name, item) in &live_named {
+                        if !repo_named.iter().any(|(n, _)| n == name) {
+                            drifts.push(Drift {
+                                path: format!("{}[{}]", path, name),
+                                repo: None,
+                                live: Some(summary(item)),
+                            });
+                        }
+                    }
+                }
+                _ => {
+                    for (i, item) in repo_items.iter().enumerate() {
+                        walk(&format!("{}[{}]", path, i), item, live_items.get(i), drifts);
+                    }
+                    for (i, item) in live_items.iter().enumerate().skip(repo_items.len()) {
+                        drifts.push(Drift {
+                            path: format!("{}[{}]", path, i),
+                            repo: None,
+                            live: Some(summary(item)),
+                        });
+                    }
+                }
+            }
+        }
+        (Value::Mapping(_) | Value::Sequence(_), _)
+        | (_, Value::Mapping(_) | Value::Sequence(_)) => {
+            drifts.push(Drift {
+                path: path.to_string(),
+                repo: Some(summary(repo)),
+                live: Some(summary(live)),
+            });
+        }
+        _ => {
+            let (repo_value, live_value) = (summary(repo), summary(live));
+            if !same_scalar(last_segment(path), &repo_value, &live_value) {
+                drifts.push(Drift {
+                    path: path.to_string(),
+                    repo: Some(repo_value),
+                    live: Some(live_value),
+                });
+            }
+        }
+    }
+}
+
+/// Items keyed by their `name`, when every item has one.
+fn named(items: &[Value]) -> Option<Vec<(String, &Value)>> {
+    if items.is_empty() {
+        return None;
+    }
+    items
+        .iter()
+        .map(|item| Some((item.get("name")?.as_str()?.to_string(), item)))
+        .collect()
+}
+
+/// Scalars are compared as text, so that `8080` matches `"8080"`; CPU and memory
+/// quantities are compared by value, so that `1000m` matches `1` and `1Gi` matches
+/// `1024Mi`.
+fn same_scalar(key: &str, repo: &str, live: &str) -> bool {
+    if repo == live {
+        return true;
+    }
+    let parse = match key {
+        "cpu" => parse_cpu,
+        "memory" | "ephemeral-storage" => parse_memory,
+        _ => return false,
+    };
+    matches!((parse(repo), parse(live)), (Some(a), Some(b)) if (a - b).abs() < 1e-9)
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Mapping(map) => map.is_empty(),
+        Value::Sequence(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+fn summary(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+        other => serde_json::to_string(other).unwrap_or_else(|_| "…".to_string()),
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit('.').next().unwrap_or(path)
+}
+
+pub fn print_report(reports: &[ServiceDrift]) {
+    if reports.is_empty() {
+        println!("No services found.");
+        return;
+    }
+    for report in reports {
+        let label = format!("{}/{} ({})", report.env, report.service, report.kind);
+        match (&report.error, report.drifts.as_slice()) {
+            (Some(error), _) => println!("⚠️  {}: {}", label, error),
+            (None, []) => println!("✅ {}: in sync", label),
+            (None, drifts) => {
+                println!("❌ {}: {} field(s) drifted", label, drifts.len());
+                for drift in drifts {
+                    println!("     {}", drift);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_ignores_server_defaults_and_flags_manual_edits() {
+        let repo: Value = serde_yaml::from_str(
+            r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: auth-api
+  labels:
+    app: auth-api
+spec:
+  replicas: 2
+  template:
+    metadata:
+      labels:
+        app: auth-api
+    spec:
+      containers:
+        - name: app
+          image: gcr.io/p/auth-api:v2
+          ports:
+            - containerPort: 8080
+          resources:
+            limits:
+              cpu: "1"
+              memory: 1Gi
+"#,
+        )
+        .unwrap();
+        let live: Value = serde_yaml::from_str(
+            r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: auth-api
+  namespace: default
+  resourceVersion: "4242"
+  uid: 0b5c
+  labels:
+    app: auth-api
+    hotfix: "true"
+  annotations:
+    deployment.kubernetes.io/revision: "7"
+spec:
+  replicas: 5
+  progressDeadlineSeconds: 600
+  template:
+    metadata:
+      labels:
+        app: auth-api
+      annotations:
+        kubectl.kubernetes.io/restartedAt: "2026-10-01T10:00:00Z"
+    spec:
+      dnsPolicy: ClusterFirst
+      containers:
+        - name: app
+          image: gcr.io/p/auth-api:v2
+          imagePullPolicy: IfNotPresent
+          ports:
+            - containerPort: 8080
+              protocol: TCP
+          resources:
+            limits:
+              cpu: 1000m
+              memory: 1024Mi
+        - name: debug
+          image: busybox
+status:
+  readyReplicas: 5
+"#,
+        )
+        .unwrap();
+
+        let drifts: Vec<String> = compare(&repo, &live)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            drifts,
+            [
+                "metadata.labels.hotfix: only in the cluster (true)",
+                "spec.replicas: repository has 2, cluster has 5",
+                r#"spec.template.spec.containers[debug]: only in the cluster ({"name":"debug","image":"busybox"})"#,
+            ]
+        );
+        assert!(compare(&repo, &repo).is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday,
+};
+use inquire::Text;
+
+use crate::config::Environment;
+use crate::history;
+use crate::interactive;
+use crate::schedule::Cron;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Period during which deploys to an environment are refused, in local time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FreezeWindow {
+    /// `Fri 16:00 - Mon 08:00`, every week.
+    Weekly { start: u32, end: u32 },
+    /// `18:00 - 08:00`, every day.
+    Daily { start: u32, end: u32 },
+    /// `2026-12-20 - 2027-01-02` (whole days) or `2026-12-20 18:00 - 2027-01-02 08:00`.
+    Dates {
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    },
+    /// `0 16 * * 5 for 64h`: from every run of the cron expression, for the duration.
+    Cron { cron: Cron, duration: Duration },
+}
+
+impl FreezeWindow {
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let invalid = || {
+            format!(
+                "Invalid freeze window '{}': expected e.g. 'Fri 16:00 - Mon 08:00', '18:00 - 08:00', '2026-12-20 - 2027-01-02' or '0 16 * * 5 for 64h'",
+                value
+            )
+        };
+        if let Some((expression, duration)) = value.split_once(" for ") {
+            return Ok(Self::Cron {
+                cron: Cron::parse(expression)?,
+                duration: history::parse_since(duration)?,
+            });
+        }
+        let (start, end) = value
+            .split_once('–')
+            .or_else(|| value.split_once('—'))
+            .or_else(|| value.split_once(" - "))
+            .with_context(invalid)?;
+        let (start, end) = (start.trim(), end.trim());
+
+        if let (Some(start), Some(end)) = (weekly_minute(start), weekly_minute(end)) {
+            return Ok(Self::Weekly { start, end });
+        }
+        if let (Ok(start), Ok(end)) = (
+            NaiveTime::parse_from_str(start, "%H:%M"),
+            NaiveTime::parse_from_str(end, "%H:%M"),
+        ) {
+            return Ok(Self::Daily {
+                start: minute_of_day(start),
+                end: minute_of_day(end),
+            });
+        }
+        if let (Ok(start), Ok(end)) = (
+            NaiveDate::parse_from_str(start, "%Y-%m-%d"),
+            NaiveDate::parse_from_str(end, "%Y-%m-%d"),
+        ) {
+            return Ok(Self::Dates {
+                start: start.and_time(NaiveTime::MIN),
+                end: (end + Duration::days(1)).and_time(NaiveTime::MIN),
+            });
+        }
+        let (start, end) = (
+            NaiveDateTime::parse_from_str(start, "%Y-%m-%d %H:%M").with_context(invalid)?,
+            NaiveDateTime::parse_from_str(end, "%Y-%m-%d %H:%M").with_context(invalid)?,
+        );
+        Ok(Self::Dates { start, end })
+    }
+
+    /// Whether the local time `at` falls in the window.
+    pub fn contains(&self, at: NaiveDateTime) -> bool {
+        match self {
+            Self::Weekly { start, end } => {
+                let minute = at.weekday().num_days_from_monday() * MINUTES_PER_DAY
+                    + minute_of_day(at.time());
+                in_cycle(*start, *end, minute)
+            }
+            Self::Daily { start, end } => in_cycle(*start, *end, minute_of_day(at.time())),
+            Self::Dates { start, end } => *start <= at && at < *end,
+            Self::Cron { cron, duration } => {
+                let Some(at) = at.with_second(0).and_then(|t| t.with_nanosecond(0)) else {
+                    return false;
+                };
+                (0..duration.num_minutes()).any(|back| cron.matches(at - Duration::minutes(back)))
+            }
+        }
+    }
+}
+
+/// `Fri 16:00` as minutes since Monday midnight.
+fn weekly_minute(value: &str) -> Option<u32> {
+    let (day, time) = value.split_once(' ')?;
+    let day: Weekday = day.parse().ok()?;
+    let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()?;
+    Some(day.num_days_from_monday() * MINUTES_PER_DAY + minute_of_day(time))
+}
+
+fn minute_of_day(time: NaiveTime) -> u32 {
+    time.hour() * 60 + time.minute()
+}
+
+/// Whether `minute` is in `[start, end)` on a cycle, wrapping past its end (e.g. from
+/// Friday to Monday) when `end` comes before `start`.
+fn in_cycle(start: u32, end: u32, minute: u32) -> bool {
+    if start <= end {
+        start <= minute && minute < end
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+/// The first freeze window of the environment containing `at`, as configured.
+pub fn active_window(env: &Environment, at: DateTime<Local>) -> Result<Option<String>> {
+    for value in &env.freeze_windows {
+        if FreezeWindow::parse(value)?.contains(at.naive_local()) {
+            return Ok(Some(value.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// Justification for deploying to `env` during its freeze `window`: the one given
+/// with `--override-freeze`, else typed at a prompt. Deploys without `--override-freeze`
+/// are refused.
+pub fn override_justification(
+    env: &Environment,
+    window: &str,
+    override_freeze: Option<&str>,
+) -> Result<String> {
+    let Some(given) = override_freeze else {
+        return Err(anyhow::anyhow!(
+            "❄️  {} is frozen ({}). Pass --override-freeze with a justification to deploy anyway.",
+            env.name,
+            window
+        ));
+    };
+    println!(
+        "❄️  WARNING: {} is frozen ({}); overriding the freeze.",
+        env.name, window
+    );
+    let given = given.trim();
+    if !given.is_empty() {
+        return Ok(given.to_string());
+    }
+    let justification = interactive::text(
+        Text::new("Why does this deploy have to happen during the freeze?")
+            .with_validator(inquire::required!("A justification is required")),
+        "The justification for overriding the freeze",
+    )?;
+    Ok(justification.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_freeze_windows_cover_their_periods() -> Result<()> {
+        // 2026-10-16 is a Friday.
+        let weekend = FreezeWindow::parse("Fri 16:00 – Mon 08:00")?;
+        assert!(!weekend.contains(at("2026-10-16 15:59")));
+        assert!(weekend.contains(at("2026-10-16 16:00")));
+        assert!(weekend.contains(at("2026-10-18 12:00")));
+        assert!(!weekend.contains(at("2026-10-19 08:00")));
+
+        let nights = FreezeWindow::parse("18:00 - 08:00")?;
+        assert!(nights.contains(at("2026-10-14 23:30")));
+        assert!(!nights.contains(at("2026-10-14 12:00")));
+
+        let holidays = FreezeWindow::parse("2026-12-20 - 2027-01-02")?;
+        assert!(holidays.contains(at("2027-01-02 23:59")));
+        assert!(!holidays.contains(at("2027-01-03 00:00")));
+
+        let cron = FreezeWindow::parse("0 16 * * 5 for 64h")?;
+        assert!(cron.contains(at("2026-10-19 07:59")));
+        assert!(!cron.contains(at("2026-10-19 08:00")));
+
+        assert!(FreezeWindow::parse("Friday afternoon").is_err());
+        Ok(())
+    }
+}
@@ -1,16 +1,110 @@
-use anyhow::{Context, Result};
-use std::path::Path;
-use std::process::Command;
+use anyhow::Result;
+use chrono::{FixedOffset, TimeZone};
+use git2::build::CheckoutBuilder;
+use git2::{
+    Commit, Cred, CredentialType, ErrorClass, ErrorCode, FetchOptions, Oid, PushOptions,
+    RemoteCallbacks, Repository, Sort, Status, StatusOptions, Tree,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::retry;
+use crate::signing;
+
+/// Credential requests answered per operation, so that a rejected key is not offered
+/// forever.
+const MAX_CREDENTIAL_ATTEMPTS: usize = 5;
+/// Token for HTTPS remotes, used before the git credential helpers.
+const TOKEN_ENV_VAR: &str = "DAVIT_GIT_TOKEN";
+/// Key files tried after the SSH agent, under `~/.ssh`.
+const SSH_KEY_FILES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
 
 pub struct Git;
 
+#[derive(Debug, Error)]
+pub enum GitError {
+    #[error("Not inside a git repository: {}", .0.display())]
+    NotARepository(PathBuf),
+    #[error("{} is outside the git repository", .0.display())]
+    OutsideRepository(PathBuf),
+    #[error("{} has no checked-out branch", .0.display())]
+    DetachedHead(PathBuf),
+    #[error("Branch {0} has no upstream branch to pull from or push to")]
+    NoUpstream(String),
+    #[error("Branch {branch} has diverged from {upstream} and cannot be fast-forwarded")]
+    Diverged { branch: String, upstream: String },
+    #[error("Nothing to commit: the files are unchanged")]
+    NothingToCommit,
+    #[error("Authentication to {url} failed: {message}")]
+    Authentication { url: String, message: String },
+    #[error("git push to {url} was rejected: {message}")]
+    PushRejected { url: String, message: String },
+    #[error(
+        "git push to {url} was rejected because the branch requires signed commits: {message}; enable [signing] in the davit configuration or git's commit.gpgsign"
+    )]
+    SignatureRequired { url: String, message: String },
+    #[error("Commits of {} cannot be signed: {message}", .path.display())]
+    Signing { path: PathBuf, message: String },
+    #[error("Rebasing on {0} failed with conflicts")]
+    RebaseConflict(String),
+    #[error("git {operation} {url} failed: {source}")]
+    Network {
+        operation: &'static str,
+        url: String,
+        #[source]
+        source: git2::Error,
+    },
+    #[error("git {operation} failed: {source}")]
+    Operation {
+        operation: &'static str,
+        #[source]
+        source: git2::Error,
+    },
+}
+
+impl GitError {
+    /// Whether retrying may succeed: the remote could not be reached or moved ahead.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::PushRejected { .. } | Self::Network { .. })
+    }
+}
+
+/// Wraps a libgit2 error as a failure of `operation`.
+fn op(operation: &'static str) -> impl FnOnce(git2::Error) -> GitError {
+    move |source| GitError::Operation { operation, source }
+}
+
+/// Wraps a libgit2 error of `operation` against the remote at `url`.
+fn remote_error(operation: &'static str, url: &str) -> impl FnOnce(git2::Error) -> GitError {
+    let url = url.to_string();
+    move |source| {
+        if source.code() == ErrorCode::Auth || source.class() == ErrorClass::Callback {
+            GitError::Authentication {
+                url,
+                message: source.message().to_string(),
+            }
+        } else if source.code() == ErrorCode::NotFastForward {
+            GitError::PushRejected {
+                url,
+                message: source.message().to_string(),
+            }
+        } else {
+            GitError::Network {
+                operation,
+                url,
+                source,
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GitPullReport {
-    pub stdout: String,
-    pub stderr: String,
-    pub success: bool,
+    pub summary: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GitLogEntry {
     pub hash: String,
     pub author: String,
@@ -18,78 +112,271 @@ pub struct GitLogEntry {
     pub message: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitCommitMessage {
+    pub hash: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// State of a work tree that deploys commit to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitCheckout {
+    pub branch: String,
+    /// Remote-tracking branch pulled from and pushed to, e.g. `refs/remotes/origin/main`.
+    pub upstream: Option<String>,
+    /// Modified, staged and untracked files, relative to the work tree.
+    pub changes: Vec<String>,
+}
+
+/// The remote branch the checked-out branch tracks.
+struct Upstream {
+    branch: String,
+    remote: String,
+    /// Branch on the remote, e.g. `refs/heads/main`.
+    merge: String,
+    /// Remote-tracking reference, e.g. `refs/remotes/origin/main`.
+    tracking: String,
+}
+
 impl Git {
-    /// Checks if the given directory is inside a git repository.
+    /// Checks if the given directory is inside a git work tree.
     pub fn is_repo(path: &Path) -> bool {
-        Command::new("git")
-            .arg("-C")
-            .arg(path)
-            .arg("rev-parse")
-            .arg("--is-inside-work-tree")
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+        open(path).is_ok()
     }
 
-    /// Performs a git pull.
+    /// The root of the work tree containing `path`.
+    pub fn work_tree(path: &Path) -> Result<PathBuf> {
+        let repo = open(path)?;
+        Ok(repo.workdir().unwrap_or(repo.path()).to_path_buf())
+    }
+
+    /// Fetches the upstream of the checked-out branch and fast-forwards to it.
     pub fn pull(path: &Path, dry_run: bool) -> Result<GitPullReport> {
         if dry_run {
             return Ok(GitPullReport {
-                stdout: format!("Dry-run: git -C {} pull\n", path.display()),
-                stderr: String::new(),
-                success: true,
+                summary: format!("Dry-run: git -C {} pull", path.display()),
+            });
+        }
+
+        let repo = open(path)?;
+        let upstream = upstream(&repo, path)?;
+        fetch(&repo, &upstream)?;
+
+        let fetched = repo
+            .find_reference(&upstream.tracking)
+            .and_then(|r| repo.reference_to_annotated_commit(&r))
+            .map_err(op("pull"))?;
+        let (analysis, _) = repo.merge_analysis(&[&fetched]).map_err(op("pull"))?;
+        if analysis.is_up_to_date() {
+            return Ok(GitPullReport {
+                summary: "Already up to date.".to_string(),
             });
         }
+        if !analysis.is_fast_forward() {
+            return Err(GitError::Diverged {
+                branch: upstream.branch,
+                upstream: upstream.tracking,
+            }
+            .into());
+        }
 
-        let output = Command::new("git")
-            .arg("-C")
-            .arg(path)
-            .arg("pull")
-            .output()
-            .context("Failed to execute git pull")?;
+        let refname = format!("refs/heads/{}", upstream.branch);
+        let old = repo.refname_to_id(&refname).unwrap_or_else(|_| Oid::zero());
+        let target = repo.find_object(fetched.id(), None).map_err(op("pull"))?;
+        // A safe checkout refuses to overwrite local modifications of the updated files.
+        repo.checkout_tree(&target, Some(CheckoutBuilder::new().safe()))
+            .map_err(op("checkout"))?;
+        repo.reference(&refname, fetched.id(), true, "pull: fast-forward")
+            .map_err(op("pull"))?;
 
         Ok(GitPullReport {
-            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
-            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
-            success: output.status.success(),
+            summary: format!(
+                "Updating {}..{}\nFast-forward",
+                short(old),
+                short(fetched.id())
+            ),
         })
     }
 
     /// Gets the last commit that modified a specific file.
     pub fn last_commit_for_file(repo_path: &Path, file_path: &Path) -> Result<Option<GitLogEntry>> {
-        let output = Command::new("git")
-            .arg("-C")
-            .arg(repo_path)
-            .arg("log")
-            .arg("-1")
-            .arg("--format=%H%n%an%n%ai%n%s")
-            .arg("--")
-            .arg(file_path)
-            .output()
-            .context("Failed to execute git log")?;
-
-        if !output.status.success() || output.stdout.is_empty() {
+        let Ok(repo) = open(repo_path) else {
             return Ok(None);
-        }
+        };
+        let relative = repo_relative(&repo, repo_path, file_path)?;
+        Ok(file_history(&repo, &relative)?
+            .first()
+            .map(|commit| log_entry(commit, false)))
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let lines: Vec<&str> = stdout.trim().lines().collect();
-        if lines.len() < 4 {
+    /// Lists the commits that modified a specific file, newest first.
+    pub fn file_log(repo_path: &Path, file_path: &Path) -> Result<Vec<GitLogEntry>> {
+        let repo = open(repo_path)?;
+        let relative = repo_relative(&repo, repo_path, file_path)?;
+        Ok(file_history(&repo, &relative)?
+            .iter()
+            .map(|commit| log_entry(commit, true))
+            .collect())
+    }
+
+    /// Reads a file as it was at the given commit. Returns `None` if it did not exist there.
+    pub fn file_at_commit(
+        repo_path: &Path,
+        commit: &str,
+        file_path: &Path,
+    ) -> Result<Option<String>> {
+        let repo = open(repo_path)?;
+        let relative = repo_relative(&repo, repo_path, file_path)?;
+        let Ok(tree) = repo.revparse_single(commit).and_then(|o| o.peel_to_tree()) else {
             return Ok(None);
+        };
+        let Ok(entry) = tree.get_path(&relative) else {
+            return Ok(None);
+        };
+        let blob = entry
+            .to_object(&repo)
+            .and_then(|o| o.peel_to_blob())
+            .map_err(op("show"))?;
+        Ok(Some(String::from_utf8_lossy(blob.content()).into_owned()))
+    }
+
+    /// Lists commits reachable from `to` but not from `from`, newest first.
+    pub fn log_range(repo_path: &Path, from: &str, to: &str) -> Result<Vec<GitCommitMessage>> {
+        let repo = open(repo_path)?;
+        let resolve = |rev: &str| {
+            repo.revparse_single(rev)
+                .and_then(|o| o.peel_to_commit())
+                .map(|c| c.id())
+                .map_err(op("log"))
+        };
+        let mut walk = repo.revwalk().map_err(op("log"))?;
+        walk.set_sorting(Sort::TIME).map_err(op("log"))?;
+        walk.push(resolve(to)?).map_err(op("log"))?;
+        walk.hide(resolve(from)?).map_err(op("log"))?;
+
+        let mut commits = Vec::new();
+        for id in walk {
+            let commit = id.and_then(|id| repo.find_commit(id)).map_err(op("log"))?;
+            if commit.parent_count() > 1 {
+                continue;
+            }
+            commits.push(GitCommitMessage {
+                hash: commit.id().to_string(),
+                subject: commit.summary().unwrap_or_default().to_string(),
+                body: commit.body().unwrap_or_default().trim().to_string(),
+            });
+        }
+        Ok(commits)
+    }
+
+    /// Commits the changed files on a new local branch, leaving the checked-out branch
+    /// and the files as they were before the changes. Nothing is pushed.
+    pub fn commit_to_branch(
+        path: &Path,
+        branch: &str,
+        message: &str,
+        files: &[PathBuf],
+        dry_run: bool,
+    ) -> Result<()> {
+        if dry_run {
+            println!("Dry-run: git -C {} checkout -b {}", path.display(), branch);
+            for file in files {
+                println!("Dry-run: git -C {} add {}", path.display(), file.display());
+            }
+            println!(
+                "Dry-run: git -C {} commit -m \"{}\"",
+                path.display(),
+                message
+            );
+            println!("Dry-run: git -C {} checkout -", path.display());
+            return Ok(());
+        }
+
+        let repo = open(path)?;
+        let relative = files
+            .iter()
+            .map(|file| repo_relative(&repo, path, file))
+            .collect::<Result<Vec<_>, _>>()?;
+        let head = repo
+            .head()
+            .and_then(|h| h.peel_to_commit())
+            .map_err(op("commit"))?;
+        repo.branch(branch, &head, false).map_err(op("branch"))?;
+
+        // The index is staged in memory only: the checked-out branch keeps it as it was.
+        let mut index = repo.index().map_err(op("add"))?;
+        stage(&repo, &mut index, &relative)?;
+        let tree_id = index.write_tree().map_err(op("commit"))?;
+        if tree_id == head.tree_id() {
+            return Err(GitError::NothingToCommit.into());
+        }
+        let tree = repo.find_tree(tree_id).map_err(op("commit"))?;
+        commit(
+            &repo,
+            &format!("refs/heads/{}", branch),
+            &clean_message(message)?,
+            &tree,
+            &[&head],
+        )?;
+
+        // The changes now live on the new branch.
+        let mut checkout = CheckoutBuilder::new();
+        checkout.force();
+        for file in &relative {
+            checkout.path(file);
         }
+        repo.checkout_head(Some(&mut checkout))
+            .map_err(op("checkout"))?;
+        Ok(())
+    }
 
-        Ok(Some(GitLogEntry {
-            hash: lines[0].to_string(),
-            author: lines[1].to_string(),
-            date: lines[2].to_string(),
-            message: lines[3].to_string(),
-        }))
+    /// Reads the checked-out branch, its upstream and the uncommitted changes.
+    pub fn checkout(path: &Path) -> Result<GitCheckout> {
+        let repo = open(path)?;
+        let branch = branch_name(&repo, path)?;
+        let upstream = upstream(&repo, path).ok().map(|u| u.tracking);
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).include_ignored(false);
+        let changes = repo
+            .statuses(Some(&mut options))
+            .map_err(op("status"))?
+            .iter()
+            .filter(|entry| entry.status() != Status::CURRENT)
+            .filter_map(|entry| entry.path().map(str::to_string))
+            .collect();
+        Ok(GitCheckout {
+            branch,
+            upstream,
+            changes,
+        })
     }
 
-    /// Adds, commits and pushes the change.
-    pub fn commit_and_push(path: &Path, message: &str, file: &Path, dry_run: bool) -> Result<()> {
+    /// Fails when commits of the repository must be signed but cannot be, e.g. no key is
+    /// set or the signing program rejects it, before anything is changed. Only the local
+    /// settings are known: a remote branch requiring signatures shows when it rejects the
+    /// push, as [`GitError::SignatureRequired`].
+    pub fn check_signing(path: &Path) -> Result<()> {
+        let repo = open(path)?;
+        if let Some(signer) = signer(&repo)? {
+            signer
+                .sign("davit signing check\n")
+                .map_err(signing_error(&repo))?;
+        }
+        Ok(())
+    }
+
+    /// Adds, commits and pushes the changed files.
+    pub fn commit_and_push(
+        path: &Path,
+        message: &str,
+        files: &[PathBuf],
+        dry_run: bool,
+    ) -> Result<()> {
         if dry_run {
-            println!("Dry-run: git -C {} add {}", path.display(), file.display());
+            for file in files {
+                println!("Dry-run: git -C {} add {}", path.display(), file.display());
+            }
             println!(
                 "Dry-run: git -C {} commit -m \"{}\"",
                 path.display(),
@@ -99,52 +386,595 @@ impl Git {
             return Ok(());
         }
 
-        if !Self::is_repo(path) {
-            return Err(anyhow::anyhow!(
-                "Not inside a git repository: {}",
-                path.display()
-            ));
+        let repo = open(path)?;
+        let upstream = upstream(&repo, path)?;
+        let relative = files
+            .iter()
+            .map(|file| repo_relative(&repo, path, file))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut index = repo.index().map_err(op("add"))?;
+        stage(&repo, &mut index, &relative)?;
+        index.write().map_err(op("add"))?;
+        let tree_id = index.write_tree().map_err(op("commit"))?;
+        let parent = repo.head().and_then(|h| h.peel_to_commit()).ok();
+        if parent.as_ref().map(|p| p.tree_id()) == Some(tree_id) {
+            return Err(GitError::NothingToCommit.into());
+        }
+        let tree = repo.find_tree(tree_id).map_err(op("commit"))?;
+        let parents: Vec<&Commit> = parent.iter().collect();
+        let refname = format!("refs/heads/{}", upstream.branch);
+        commit(&repo, &refname, &clean_message(message)?, &tree, &parents)?;
+
+        // Rebase on the remote when it moved ahead meanwhile, then push again.
+        retry::with_backoff_blocking("git push", || match push(&repo, &upstream) {
+            Err(rejected @ GitError::PushRejected { .. }) => {
+                fetch(&repo, &upstream)?;
+                rebase_on(&repo, &upstream)?;
+                Err(rejected.into())
+            }
+            result => Ok(result?),
+        })
+    }
+}
+
+/// Opens the repository whose work tree contains `path`.
+fn open(path: &Path) -> Result<Repository, GitError> {
+    match Repository::discover(path) {
+        Ok(repo) if !repo.is_bare() => Ok(repo),
+        _ => Err(GitError::NotARepository(path.to_path_buf())),
+    }
+}
+
+fn signing_error(repo: &Repository) -> impl FnOnce(anyhow::Error) -> GitError {
+    let path = repo.workdir().unwrap_or(repo.path()).to_path_buf();
+    move |e| GitError::Signing {
+        path,
+        message: format!("{:#}", e),
+    }
+}
+
+fn signer(repo: &Repository) -> Result<Option<signing::Signer>, GitError> {
+    signing::signer(repo).map_err(signing_error(repo))
+}
+
+/// Commits the tree on top of `parents` and moves `refname` to it, signing the commit
+/// when the repository or the `[signing]` settings ask for it.
+fn commit(
+    repo: &Repository,
+    refname: &str,
+    message: &str,
+    tree: &Tree,
+    parents: &[&Commit],
+) -> Result<Oid, GitError> {
+    let signature = repo.signature().map_err(op("commit"))?;
+    let Some(signer) = signer(repo)? else {
+        return repo
+            .commit(
+                Some(refname),
+                &signature,
+                &signature,
+                message,
+                tree,
+                parents,
+            )
+            .map_err(op("commit"));
+    };
+    let buffer = repo
+        .commit_create_buffer(&signature, &signature, message, tree, parents)
+        .map_err(op("commit"))?;
+    let id = sign_buffer(repo, &signer, &buffer)?;
+    let reflog = format!("commit: {}", message.lines().next().unwrap_or_default());
+    repo.reference(refname, id, true, &reflog)
+        .map_err(op("commit"))?;
+    Ok(id)
+}
+
+fn sign_buffer(
+    repo: &Repository,
+    signer: &signing::Signer,
+    buffer: &git2::Buf,
+) -> Result<Oid, GitError> {
+    let content = buffer.as_str().ok_or_else(|| GitError::Signing {
+        path: repo.path().to_path_buf(),
+        message: "the commit is not valid UTF-8".to_string(),
+    })?;
+    let gpgsig = signer.sign(content).map_err(signing_error(repo))?;
+    repo.commit_signed(content, &gpgsig, None)
+        .map_err(op("commit"))
+}
+
+/// Recreates the commits of the checked-out branch made since `base` with signatures,
+/// as the rebase left them unsigned.
+fn sign_since(repo: &Repository, base: Oid, signer: &signing::Signer) -> Result<(), GitError> {
+    let head = repo.head().map_err(op("rebase"))?;
+    let refname = head.name().unwrap_or("HEAD").to_string();
+    let mut walk = repo.revwalk().map_err(op("rebase"))?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)
+        .map_err(op("rebase"))?;
+    walk.push_head().map_err(op("rebase"))?;
+    walk.hide(base).map_err(op("rebase"))?;
+
+    let mut tip = base;
+    for id in walk {
+        let original = id
+            .and_then(|id| repo.find_commit(id))
+            .map_err(op("rebase"))?;
+        let parent = repo.find_commit(tip).map_err(op("rebase"))?;
+        let tree = original.tree().map_err(op("rebase"))?;
+        let buffer = repo
+            .commit_create_buffer(
+                &original.author(),
+                &original.committer(),
+                original.message().unwrap_or_default(),
+                &tree,
+                &[&parent],
+            )
+            .map_err(op("rebase"))?;
+        tip = sign_buffer(repo, signer, &buffer)?;
+    }
+    if tip != base {
+        repo.reference(&refname, tip, true, "rebase: sign commits")
+            .map_err(op("rebase"))?;
+    }
+    Ok(())
+}
+
+/// The checked-out branch, which may have no commit yet.
+fn branch_name(repo: &Repository, path: &Path) -> Result<String, GitError> {
+    let head = repo.find_reference("HEAD").map_err(op("rev-parse"))?;
+    head.symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/heads/"))
+        .map(str::to_string)
+        .ok_or_else(|| GitError::DetachedHead(path.to_path_buf()))
+}
+
+fn upstream(repo: &Repository, path: &Path) -> Result<Upstream, GitError> {
+    let branch = branch_name(repo, path)?;
+    let refname = format!("refs/heads/{}", branch);
+    let config = |buf: Result<git2::Buf, git2::Error>| {
+        buf.ok()
+            .and_then(|b| b.as_str().map(str::to_string))
+            .ok_or_else(|| GitError::NoUpstream(branch.clone()))
+    };
+    Ok(Upstream {
+        remote: config(repo.branch_upstream_remote(&refname))?,
+        merge: config(repo.branch_upstream_merge(&refname))?,
+        tracking: config(repo.branch_upstream_name(&refname))?,
+        branch,
+    })
+}
+
+/// Answers credential requests with the SSH agent then the default key files for SSH
+/// remotes, and `DAVIT_GIT_TOKEN` then the git credential helpers for HTTPS remotes.
+fn callbacks<'a>(repo: &Repository) -> RemoteCallbacks<'a> {
+    let config = repo.config().ok();
+    let home = directories::BaseDirs::new().map(|dirs| dirs.home_dir().join(".ssh"));
+    let mut attempts = 0;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        attempts += 1;
+        if attempts > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str("no credentials were accepted"));
+        }
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username.unwrap_or("git"));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            let username = username.unwrap_or("git");
+            if attempts == 1 {
+                return Cred::ssh_key_from_agent(username);
+            }
+            let key = home.as_ref().and_then(|dir| {
+                SSH_KEY_FILES
+                    .iter()
+                    .map(|name| dir.join(name))
+                    .filter(|key| key.exists())
+                    .nth(attempts - 2)
+            });
+            return match key {
+                Some(key) => Cred::ssh_key(username, None, &key, None),
+                None => Err(git2::Error::from_str(
+                    "the SSH agent and the default keys were rejected",
+                )),
+            };
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Ok(token) = std::env::var(TOKEN_ENV_VAR)
+                && attempts == 1
+            {
+                return Cred::userpass_plaintext(username.unwrap_or("x-access-token"), &token);
+            }
+            if let Some(config) = &config {
+                return Cred::credential_helper(config, url, username);
+            }
+        }
+        if allowed.contains(CredentialType::DEFAULT) {
+            return Cred::default();
+        }
+        Err(git2::Error::from_str("no supported credentials"))
+    });
+    callbacks
+}
+
+fn fetch(repo: &Repository, upstream: &Upstream) -> Result<(), GitError> {
+    let mut remote = repo.find_remote(&upstream.remote).map_err(op("fetch"))?;
+    let url = remote.url().unwrap_or(&upstream.remote).to_string();
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks(repo));
+    // The configured refspecs update the remote-tracking branches.
+    remote
+        .fetch(&[] as &[&str], Some(&mut options), None)
+        .map_err(remote_error("fetch", &url))
+}
+
+fn push(repo: &Repository, upstream: &Upstream) -> Result<(), GitError> {
+    let mut remote = repo.find_remote(&upstream.remote).map_err(op("push"))?;
+    let url = remote.url().unwrap_or(&upstream.remote).to_string();
+    let rejection = RefCell::new(None);
+    let remote_output = RefCell::new(String::new());
+    let mut callbacks = callbacks(repo);
+    callbacks.push_update_reference(|_, status| {
+        if let Some(status) = status {
+            *rejection.borrow_mut() = Some(status.to_string());
         }
+        Ok(())
+    });
+    // Hooks of the remote explain their rejections here, e.g. unsigned commits.
+    callbacks.sideband_progress(|data| {
+        remote_output
+            .borrow_mut()
+            .push_str(&String::from_utf8_lossy(data));
+        true
+    });
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+    remote
+        .push(
+            &[format!("refs/heads/{}:{}", upstream.branch, upstream.merge)],
+            Some(&mut options),
+        )
+        .map_err(remote_error("push", &url))?;
+    drop(options);
+    let Some(message) = rejection.into_inner() else {
+        return Ok(());
+    };
+    let remote_output = remote_output.into_inner();
+    if requires_signatures(&message) || requires_signatures(&remote_output) {
+        let detail = remote_output
+            .lines()
+            .map(|line| line.trim())
+            .find(|line| requires_signatures(line))
+            .unwrap_or(&message)
+            .to_string();
+        return Err(GitError::SignatureRequired {
+            url,
+            message: detail,
+        });
+    }
+    Err(GitError::PushRejected { url, message })
+}
 
-        // git add <file>
-        let status = Command::new("git")
-            .arg("-C")
-            .arg(path)
-            .arg("add")
-            .arg(file)
-            .status()
-            .context("Failed to execute git add")?;
+/// Whether a push rejection is about unsigned or unverified commits.
+fn requires_signatures(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("signature") || message.contains("signed")
+}
 
-        if !status.success() {
-            return Err(anyhow::anyhow!("git add failed"));
+/// Replays the local commits on top of the fetched upstream branch.
+fn rebase_on(repo: &Repository, upstream: &Upstream) -> Result<(), GitError> {
+    let onto = repo
+        .find_reference(&upstream.tracking)
+        .and_then(|r| repo.reference_to_annotated_commit(&r))
+        .map_err(op("rebase"))?;
+    let signature = repo.signature().map_err(op("rebase"))?;
+    let mut rebase = repo
+        .rebase(None, Some(&onto), None, None)
+        .map_err(op("rebase"))?;
+    while let Some(operation) = rebase.next() {
+        operation.map_err(op("rebase"))?;
+        let conflicts = repo
+            .index()
+            .map(|i| i.has_conflicts())
+            .map_err(op("rebase"))?;
+        if conflicts {
+            let _ = rebase.abort();
+            return Err(GitError::RebaseConflict(upstream.tracking.clone()));
         }
+        match rebase.commit(None, &signature, None) {
+            // The change is already upstream.
+            Err(e) if e.code() == ErrorCode::Applied => {}
+            result => {
+                result.map_err(op("rebase"))?;
+            }
+        }
+    }
+    rebase.finish(Some(&signature)).map_err(op("rebase"))?;
+    if let Some(signer) = signer(repo)? {
+        sign_since(repo, onto.id(), &signer)?;
+    }
+    Ok(())
+}
 
-        // git commit -m <message>
-        let status = Command::new("git")
-            .arg("-C")
-            .arg(path)
-            .arg("commit")
-            .arg("-m")
-            .arg(message)
-            .status()
-            .context("Failed to execute git commit")?;
+/// Stages the files, removing from the index those deleted from the work tree.
+fn stage(repo: &Repository, index: &mut git2::Index, files: &[PathBuf]) -> Result<(), GitError> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::NotARepository(repo.path().to_path_buf()))?;
+    for file in files {
+        if workdir.join(file).exists() {
+            index.add_path(file).map_err(op("add"))?;
+        } else {
+            index.remove_path(file).map_err(op("add"))?;
+        }
+    }
+    Ok(())
+}
+
+/// `file`, relative to `base` unless absolute, as a path in the work tree of `repo`.
+fn repo_relative(repo: &Repository, base: &Path, file: &Path) -> Result<PathBuf, GitError> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::NotARepository(base.to_path_buf()))?;
+    let workdir = resolve(workdir);
+    let file = resolve(&base.join(file));
+    file.strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .map_err(|_| GitError::OutsideRepository(file.clone()))
+}
+
+/// Resolves symlinks in the deepest existing ancestor of `path`, which may not exist
+/// anymore (e.g. a file deleted since the commit being read).
+fn resolve(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find_map(|ancestor| {
+            let rest = path.strip_prefix(ancestor).ok()?;
+            let resolved = ancestor.canonicalize().ok()?;
+            Some(if rest.as_os_str().is_empty() {
+                resolved
+            } else {
+                resolved.join(rest)
+            })
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Commits of the checked-out branch that changed `file`, newest first.
+fn file_history<'r>(repo: &'r Repository, file: &Path) -> Result<Vec<Commit<'r>>, GitError> {
+    let mut walk = repo.revwalk().map_err(op("log"))?;
+    walk.set_sorting(Sort::TIME).map_err(op("log"))?;
+    if walk.push_head().is_err() {
+        // No commit yet.
+        return Ok(Vec::new());
+    }
+
+    let entry_id = |commit: &Commit| {
+        commit
+            .tree()
+            .ok()
+            .and_then(|tree| tree.get_path(file).ok())
+            .map(|entry| entry.id())
+    };
+    let mut commits = Vec::new();
+    for id in walk {
+        let commit = id.and_then(|id| repo.find_commit(id)).map_err(op("log"))?;
+        let current = entry_id(&commit);
+        // As `git log -- <file>`, a commit counts when the file differs from every parent.
+        let changed = if commit.parent_count() == 0 {
+            current.is_some()
+        } else {
+            commit.parents().all(|parent| entry_id(&parent) != current)
+        };
+        if changed {
+            commits.push(commit);
+        }
+    }
+    Ok(commits)
+}
+
+fn log_entry(commit: &Commit, with_email: bool) -> GitLogEntry {
+    let author = commit.author();
+    let name = author.name().unwrap_or_default();
+    let time = commit.time();
+    let date = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| offset.timestamp_opt(time.seconds(), 0).single())
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S %z").to_string())
+        .unwrap_or_default();
+    GitLogEntry {
+        hash: commit.id().to_string(),
+        author: if with_email {
+            format!("{} <{}>", name, author.email().unwrap_or_default())
+        } else {
+            name.to_string()
+        },
+        date,
+        message: commit.summary().unwrap_or_default().to_string(),
+    }
+}
+
+/// The message as `git commit -m` stores it: trailing whitespace stripped, one final newline.
+fn clean_message(message: &str) -> Result<String, GitError> {
+    git2::message_prettify(message, None).map_err(op("commit"))
+}
+
+fn short(id: Oid) -> String {
+    id.to_string().chars().take(7).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
 
-        if !status.success() {
-            return Err(anyhow::anyhow!("git commit failed"));
+    fn init(path: &Path) -> Repository {
+        let repo = Repository::init(path).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Davit").unwrap();
+        config.set_str("user.email", "davit@example.com").unwrap();
+        repo
+    }
+
+    /// A bare remote and a clone tracking its `main` branch, with one commit.
+    fn clone_with_remote(dir: &Path, name: &str) -> PathBuf {
+        let remote = dir.join("remote.git");
+        if !remote.exists() {
+            Repository::init_bare(&remote).unwrap();
         }
+        let path = dir.join(name);
+        let repo = init(&path);
+        repo.remote("origin", remote.to_str().unwrap()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.main.remote", "origin").unwrap();
+        config
+            .set_str("branch.main.merge", "refs/heads/main")
+            .unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+        path
+    }
+
+    #[test]
+    fn test_commit_and_push_then_pull_fast_forwards() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = clone_with_remote(dir.path(), "first");
+        fs::create_dir_all(first.join("k8s")).unwrap();
+        let yaml = first.join("k8s/auth-api.yaml");
+        fs::write(&yaml, "image: auth-api:v1\n").unwrap();
+        Git::commit_and_push(
+            &first.join("k8s"),
+            "deploy: v1",
+            std::slice::from_ref(&yaml),
+            false,
+        )
+        .unwrap();
+
+        let second = clone_with_remote(dir.path(), "second");
+        let report = Git::pull(&second, false).unwrap();
+        assert!(report.summary.starts_with("Updating 0000000.."));
+        assert_eq!(
+            fs::read_to_string(second.join("k8s/auth-api.yaml")).unwrap(),
+            "image: auth-api:v1\n"
+        );
+
+        fs::write(&yaml, "image: auth-api:v2\n").unwrap();
+        Git::commit_and_push(
+            &first,
+            "deploy: v2",
+            &[PathBuf::from("k8s/auth-api.yaml")],
+            false,
+        )
+        .unwrap();
+        Git::pull(&second, false).unwrap();
+        assert_eq!(
+            Git::pull(&second, false).unwrap().summary,
+            "Already up to date."
+        );
+
+        let log = Git::file_log(&second, Path::new("k8s/auth-api.yaml")).unwrap();
+        assert_eq!(
+            log.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(),
+            ["deploy: v2", "deploy: v1"]
+        );
+        assert_eq!(log[0].author, "Davit <davit@example.com>");
+        assert_eq!(
+            Git::file_at_commit(&second, &log[1].hash, Path::new("k8s/auth-api.yaml")).unwrap(),
+            Some("image: auth-api:v1\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_errors_are_typed() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!Git::is_repo(dir.path()));
+        let error = Git::pull(dir.path(), false).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<GitError>(),
+            Some(GitError::NotARepository(_))
+        ));
+
+        let path = clone_with_remote(dir.path(), "clone");
+        fs::write(path.join("a.yaml"), "a: 1\n").unwrap();
+        Git::commit_and_push(&path, "add a", &[PathBuf::from("a.yaml")], false).unwrap();
+        let checkout = Git::checkout(&path).unwrap();
+        assert_eq!(
+            checkout.upstream.as_deref(),
+            Some("refs/remotes/origin/main")
+        );
+        assert!(checkout.changes.is_empty());
+        fs::write(path.join("b.yaml"), "b: 1\n").unwrap();
+        assert_eq!(Git::checkout(&path).unwrap().changes, ["b.yaml"]);
+        fs::remove_file(path.join("b.yaml")).unwrap();
+        let error =
+            Git::commit_and_push(&path, "again", &[PathBuf::from("a.yaml")], false).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<GitError>(),
+            Some(GitError::NothingToCommit)
+        ));
+    }
+
+    #[test]
+    fn test_rejected_push_rebases_on_the_remote() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = clone_with_remote(dir.path(), "first");
+        fs::write(first.join("a.yaml"), "a: 1\n").unwrap();
+        Git::commit_and_push(&first, "add a", &[PathBuf::from("a.yaml")], false).unwrap();
+        let second = clone_with_remote(dir.path(), "second");
+        Git::pull(&second, false).unwrap();
 
-        // git push
-        let status = Command::new("git")
-            .arg("-C")
-            .arg(path)
-            .arg("push")
-            .status()
-            .context("Failed to execute git push")?;
+        fs::write(first.join("a.yaml"), "a: 2\n").unwrap();
+        Git::commit_and_push(&first, "bump a", &[PathBuf::from("a.yaml")], false).unwrap();
+        fs::write(second.join("b.yaml"), "b: 1\n").unwrap();
+        Git::commit_and_push(&second, "add b", &[PathBuf::from("b.yaml")], false).unwrap();
 
-        if !status.success() {
-            return Err(anyhow::anyhow!("git push failed"));
+        Git::pull(&first, false).unwrap();
+        assert_eq!(fs::read_to_string(first.join("b.yaml")).unwrap(), "b: 1\n");
+        assert_eq!(fs::read_to_string(second.join("a.yaml")).unwrap(), "a: 2\n");
+    }
+
+    #[test]
+    fn test_commits_are_signed_when_the_repository_requires_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("signing_key");
+        let generated = std::process::Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key)
+            .status();
+        if !generated.is_ok_and(|status| status.success()) {
+            // No OpenSSH to sign with.
+            return;
         }
+        let first = clone_with_remote(dir.path(), "first");
+        let mut config = Repository::open(&first).unwrap().config().unwrap();
+        config.set_bool("commit.gpgsign", true).unwrap();
+        config.set_str("gpg.format", "ssh").unwrap();
+        assert!(matches!(
+            Git::check_signing(&first)
+                .unwrap_err()
+                .downcast_ref::<GitError>(),
+            Some(GitError::Signing { .. })
+        ));
+        config
+            .set_str("user.signingkey", key.to_str().unwrap())
+            .unwrap();
+        Git::check_signing(&first).unwrap();
 
-        Ok(())
+        fs::write(first.join("a.yaml"), "a: 1\n").unwrap();
+        Git::commit_and_push(&first, "add a", &[PathBuf::from("a.yaml")], false).unwrap();
+        let repo = Repository::open(&first).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let (signature, signed) = repo.extract_signature(&head.id(), None).unwrap();
+        assert!(
+            signature
+                .as_str()
+                .unwrap()
+                .starts_with("-----BEGIN SSH SIGNATURE-----")
+        );
+        assert!(signed.as_str().unwrap().ends_with("\nadd a\n"));
+
+        let second = clone_with_remote(dir.path(), "second");
+        Git::pull(&second, false).unwrap();
+        assert_eq!(
+            Git::file_log(&second, Path::new("a.yaml")).unwrap()[0].hash,
+            head.id().to_string()
+        );
     }
 }
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use k8s_openapi::api::core::v1::Pod;
+use kube::Api;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::config::HealthCheck;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of one health probe: the HTTP status, or the error when no response arrived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeSample {
+    pub status: Option<u16>,
+    pub latency: Duration,
+    pub error: Option<String>,
+}
+
+impl ProbeSample {
+    pub fn is_healthy(&self) -> bool {
+        self.status.is_some_and(|s| (200..400).contains(&s))
+    }
+
+    fn from_result(result: Result<u16>, started: Instant) -> Self {
+        let latency = started.elapsed();
+        match result {
+            Ok(status) => Self {
+                status: Some(status),
+                latency,
+                error: None,
+            },
+            Err(e) => Self {
+                status: None,
+                latency,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Short description of what a health check hits, for the dashboard title.
+pub fn target(check: &HealthCheck) -> String {
+    match (&check.url, check.port) {
+        (Some(url), _) => url.clone(),
+        (None, Some(port)) => format!("pod:{}{}", port, check.path),
+        (None, None) => check.path.clone(),
+    }
+}
+
+/// Probes the configured external URL.
+pub async fn probe_url(client: &reqwest::Client, url: &str) -> ProbeSample {
+    let started = Instant::now();
+    let result = client
+        .get(url)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .map(|r| r.status().as_u16())
+        .context("request failed");
+    ProbeSample::from_result(result, started)
+}
+
+/// Probes a pod through a transient port-forward.
+pub async fn probe_pod(pods: &Api<Pod>, pod: &str, port: u16, path: &str) -> ProbeSample {
+    let started = Instant::now();
+    let result = tokio::time::timeout(
+        PROBE_TIMEOUT,
+        get_through_port_forward(pods, pod, port, path),
+    )
+    .await
+    .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out")));
+    ProbeSample::from_result(result, started)
+}
+
+async fn get_through_port_forward(
+    pods: &Api<Pod>,
+    pod: &str,
+    port: u16,
+    path: &str,
+) -> Result<u16> {
+    let mut forwarder = pods
+        .portforward(pod, &[port])
+        .await
+        .context("port-forward failed")?;
+    let mut stream = forwarder
+        .take_stream(port)
+        .context("port-forward returned no stream")?;
+
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nUser-Agent: davit\r\nConnection: close\r\n\r\n",
+        path
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    // Only the status line is needed.
+    let mut response = Vec::new();
+    let mut buffer = [0u8; 512];
+    while !response.contains(&b'\n') {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        response.extend_from_slice(&buffer[..read]);
+    }
+    drop(stream);
+    forwarder.abort();
+
+    parse_status_line(&response).context("invalid HTTP response")
+}
+
+fn parse_status_line(response: &[u8]) -> Option<u16> {
+    let line = response.split(|b| *b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.split_whitespace();
+    parts.next().filter(|v| v.starts_with("HTTP/"))?;
+    parts.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status_line() {
+        assert_eq!(
+            parse_status_line(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n"),
+            Some(503)
+        );
+        assert_eq!(parse_status_line(b"SSH-2.0-OpenSSH\r\n"), None);
+    }
+}
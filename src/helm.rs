@@ -0,0 +1,280 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::config::HelmDiscovery;
+
+/// A Helm release installed in the cluster, as listed by `helm list`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct HelmRelease {
+    pub name: String,
+    pub namespace: String,
+    /// Chart name and version, e.g. `auth-api-1.4.2`.
+    pub chart: String,
+}
+
+impl HelmRelease {
+    /// Splits `chart` into the chart name and its version.
+    pub fn chart_name_and_version(&self) -> (&str, Option<&str>) {
+        self.chart
+            .match_indices('-')
+            .map(|(i, _)| (&self.chart[..i], &self.chart[i + 1..]))
+            .find(|(_, version)| version.starts_with(|c: char| c.is_ascii_digit()))
+            .map(|(name, version)| (name, Some(version)))
+            .unwrap_or((self.chart.as_str(), None))
+    }
+
+    /// Chart reference to upgrade the release with: the configured override, or the
+    /// chart name in `chart_repo`.
+    pub fn chart_reference(&self, helm: &HelmDiscovery) -> Result<String> {
+        if let Some(chart) = helm.charts.get(&self.name) {
+            return Ok(chart.clone());
+        }
+        let (name, _) = self.chart_name_and_version();
+        let repo = helm.chart_repo.as_deref().with_context(|| {
+            format!(
+                "No chart configured for Helm release '{}': set `chart_repo` or `charts.{}`",
+                self.name, self.name
+            )
+        })?;
+        Ok(format!("{}/{}", repo.trim_end_matches('/'), name))
+    }
+}
+
+/// A release deployed from a chart with its tag in a committed values file, which
+/// deploys edit before running `helm upgrade --install`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HelmChart {
+    pub release: String,
+    pub namespace: Option<String>,
+    /// Chart directory, or reference such as `oci://...` or `repo/chart`.
+    pub chart: String,
+    pub values: PathBuf,
+    /// Values key holding the tag, e.g. `image.tag`.
+    pub tag_value: String,
+}
+
+impl HelmChart {
+    /// Renders the chart with `values` as values file content, without touching the file.
+    pub fn template(&self, values: &str) -> Result<String> {
+        let mut args = vec!["template", self.release.as_str(), self.chart.as_str()];
+        if let Some(namespace) = &self.namespace {
+            args.extend(["--namespace", namespace]);
+        }
+        args.extend(["--values", "-"]);
+        let mut child = Command::new("helm")
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to execute helm")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(values.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "helm template {} failed: {}",
+                self.chart,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Installs or upgrades the release with the values file as written on disk.
+    pub fn upgrade(&self, context: &str, dry_run: bool) -> Result<String> {
+        run_helm(
+            self.upgrade_args(context, dry_run)
+                .iter()
+                .map(String::as_str),
+        )
+    }
+
+    pub fn upgrade_args(&self, context: &str, dry_run: bool) -> Vec<String> {
+        let mut args: Vec<String> = ["upgrade", "--install", &self.release, &self.chart]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        if let Some(namespace) = &self.namespace {
+            args.extend(["--namespace".to_string(), namespace.clone()]);
+        }
+        args.extend([
+            "--kube-context".to_string(),
+            context.to_string(),
+            "--values".to_string(),
+            self.values.display().to_string(),
+        ]);
+        if dry_run {
+            args.push("--dry-run=server".to_string());
+        }
+        args
+    }
+
+    /// Tag set in a version of the values file.
+    pub fn tag_in(&self, values: &str) -> Option<String> {
+        let values: serde_yaml::Value = serde_yaml::from_str(values).ok()?;
+        let tag = self
+            .tag_value
+            .split('.')
+            .try_fold(&values, |value, key| value.get(key))?;
+        match tag {
+            serde_yaml::Value::String(tag) => Some(tag.clone()),
+            serde_yaml::Value::Number(tag) => Some(tag.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Lists the releases of the configured namespaces, or of all namespaces.
+pub fn list_releases(context: &str, helm: &HelmDiscovery) -> Result<Vec<HelmRelease>> {
+    let mut args = vec!["list", "--kube-context", context, "-o", "json"];
+    if helm.namespaces.is_empty() {
+        args.push("--all-namespaces");
+    }
+    let mut releases = Vec::new();
+    let namespaces: Vec<Option<&str>> = if helm.namespaces.is_empty() {
+        vec![None]
+    } else {
+        helm.namespaces.iter().map(|ns| Some(ns.as_str())).collect()
+    };
+    for namespace in namespaces {
+        let output = run_helm(
+            args.iter()
+                .copied()
+                .chain(namespace.into_iter().flat_map(|ns| ["--namespace", ns])),
+        )?;
+        releases.extend(parse_releases(&output)?);
+    }
+    Ok(releases)
+}
+
+pub fn parse_releases(json: &str) -> Result<Vec<HelmRelease>> {
+    serde_json::from_str(json).context("Failed to parse `helm list` output")
+}
+
+/// Rendered manifest of the deployed revision of a release.
+pub fn release_manifest(context: &str, release: &HelmRelease) -> Result<String> {
+    run_helm([
+        "get",
+        "manifest",
+        &release.name,
+        "--namespace",
+        &release.namespace,
+        "--kube-context",
+        context,
+    ])
+}
+
+/// Upgrades the release to `tag`, keeping its values and chart version. With
+/// `dry_run`, nothing is changed and the manifest the upgrade would apply is returned.
+pub fn upgrade(
+    context: &str,
+    helm: &HelmDiscovery,
+    release: &HelmRelease,
+    tag: &str,
+    dry_run: bool,
+) -> Result<String> {
+    let chart = release.chart_reference(helm)?;
+    let tag_value = format!("{}={}", helm.tag_value, tag);
+    let mut args = vec![
+        "upgrade",
+        &release.name,
+        &chart,
+        "--namespace",
+        &release.namespace,
+        "--kube-context",
+        context,
+        "--reuse-values",
+        "--set-string",
+        &tag_value,
+    ];
+    if let (_, Some(version)) = release.chart_name_and_version() {
+        args.extend(["--version", version]);
+    }
+    if !dry_run {
+        return run_helm(args);
+    }
+
+    args.extend(["--dry-run=server", "-o", "json"]);
+    let output = run_helm(args)?;
+    let rendered: serde_json::Value =
+        serde_json::from_str(&output).context("Failed to parse `helm upgrade` output")?;
+    rendered
+        .get("manifest")
+        .and_then(|m| m.as_str())
+        .map(str::to_string)
+        .context("`helm upgrade --dry-run` returned no manifest")
+}
+
+fn run_helm<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<String> {
+    let args: Vec<&str> = args.into_iter().collect();
+    let output = Command::new("helm")
+        .args(&args)
+        .output()
+        .context("Failed to execute helm")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "helm {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_releases_and_chart_reference() -> Result<()> {
+        let releases = parse_releases(
+            r#"[{"name":"auth-api","namespace":"auth","revision":"7","updated":"2026-10-01 09:30:00.0 +0000 UTC","status":"deployed","chart":"auth-api-1.4.2-rc.1","app_version":"v2.3.0"},
+                {"name":"billing","namespace":"billing","revision":"2","status":"deployed","chart":"go-service-0.9.0","app_version":""}]"#,
+        )?;
+        assert_eq!(
+            releases[0].chart_name_and_version(),
+            ("auth-api", Some("1.4.2-rc.1"))
+        );
+
+        let mut helm = HelmDiscovery {
+            chart_repo: Some("oci://europe-docker.pkg.dev/acme/charts/".to_string()),
+            ..Default::default()
+        };
+        helm.charts
+            .insert("billing".to_string(), "./charts/billing".to_string());
+        assert_eq!(
+            releases[0].chart_reference(&helm)?,
+            "oci://europe-docker.pkg.dev/acme/charts/auth-api"
+        );
+        assert_eq!(releases[1].chart_reference(&helm)?, "./charts/billing");
+
+        helm.chart_repo = None;
+        assert!(releases[0].chart_reference(&helm).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_helm_chart_upgrade_args_and_tag() {
+        let chart = HelmChart {
+            release: "auth-api".to_string(),
+            namespace: Some("auth".to_string()),
+            chart: "/repo/charts/auth-api".to_string(),
+            values: PathBuf::from("/repo/values-production.yaml"),
+            tag_value: "image.tag".to_string(),
+        };
+        assert_eq!(
+            chart.upgrade_args("prod", true).join(" "),
+            "upgrade --install auth-api /repo/charts/auth-api --namespace auth --kube-context prod --values /repo/values-production.yaml --dry-run=server"
+        );
+        assert_eq!(
+            chart.tag_in("image:\n  repository: auth-api\n  tag: v1.2.3\n"),
+            Some("v1.2.3".to_string())
+        );
+        assert_eq!(chart.tag_in("image:\n  repository: auth-api\n"), None);
+    }
+}
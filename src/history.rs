@@ -0,0 +1,669 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local, SecondsFormat, Utc};
+use directories::ProjectDirs;
+use rusqlite::{Connection, Row, params};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{mock, output};
+
+const HISTORY_FILE: &str = "history.db";
+/// History file of davit versions before the SQLite one, imported once.
+const LEGACY_HISTORY_FILE: &str = "history.jsonl";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS deploys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    env TEXT NOT NULL,
+    service TEXT NOT NULL,
+    old_tag TEXT,
+    new_tag TEXT NOT NULL,
+    digest TEXT,
+    user TEXT NOT NULL,
+    duration_secs INTEGER NOT NULL,
+    outcome TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    rolls_back TEXT,
+    phases TEXT NOT NULL,
+    approvals TEXT NOT NULL,
+    freeze_override TEXT
+);
+CREATE INDEX IF NOT EXISTS deploys_by_service ON deploys (env, service, timestamp);
+";
+
+const COLUMNS: &str = "timestamp, env, service, old_tag, new_tag, digest, user, duration_secs, \
+     outcome, kind, rolls_back, phases, approvals, freeze_override";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Succeeded,
+    Failed,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Succeeded => write!(f, "succeeded"),
+            Outcome::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployKind {
+    #[default]
+    Deploy,
+    /// A deploy back to the tag that an earlier deploy replaced.
+    Rollback,
+}
+
+impl fmt::Display for DeployKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeployKind::Deploy => write!(f, "deploy"),
+            DeployKind::Rollback => write!(f, "rollback"),
+        }
+    }
+}
+
+/// Seconds spent in each phase of a deploy; rollout phases count from the end of the apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseDurations {
+    /// Fetching tags from the registry and picking one.
+    pub registry_secs: Option<f64>,
+    /// Reviewing the diff and checks until the apply was confirmed.
+    pub review_secs: Option<f64>,
+    pub apply_secs: Option<f64>,
+    pub first_pod_ready_secs: Option<f64>,
+    pub rolled_out_secs: Option<f64>,
+}
+
+impl PhaseDurations {
+    /// Printable breakdown, one line per measured phase.
+    pub fn lines(&self) -> Vec<String> {
+        [
+            ("Registry", self.registry_secs),
+            ("Review", self.review_secs),
+            ("Apply", self.apply_secs),
+            ("First pod ready", self.first_pod_ready_secs),
+            ("Fully rolled out", self.rolled_out_secs),
+        ]
+        .into_iter()
+        .filter_map(|(label, secs)| Some(format!("{:<17} {:>7.1}s", label, secs?)))
+        .collect()
+    }
+}
+
+/// One applied deploy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeployRecord {
+    pub timestamp: DateTime<Utc>,
+    pub env: String,
+    pub service: String,
+    pub old_tag: Option<String>,
+    pub new_tag: String,
+    /// Manifest digest the new tag pointed at when it was deployed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    pub user: String,
+    pub duration_secs: u64,
+    pub outcome: Outcome,
+    #[serde(default)]
+    pub kind: DeployKind,
+    /// Timestamp of the deploy a rollback reverted, when it is in the history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rolls_back: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub phases: PhaseDurations,
+    /// People who approved the deploy of a protected service.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvals: Vec<String>,
+    /// Justification given for deploying during a freeze window, with the window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub freeze_override: Option<String>,
+}
+
+/// Directory for davit's local data: the data directory, or the demo workspace in mock mode.
+pub fn data_dir() -> Result<PathBuf> {
+    if mock::enabled() {
+        return Ok(std::env::temp_dir().join("davit-mock"));
+    }
+    Ok(ProjectDirs::from("com", "davit", "davit")
+        .context("Could not determine project directories")?
+        .data_dir()
+        .to_path_buf())
+}
+
+/// Local deploy history, a SQLite database with one row per deploy.
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// History of this machine, kept in the data directory. Records of the former
+    /// `history.jsonl` file are imported on first use.
+    pub fn open_default() -> Result<Self> {
+        let dir = data_dir()?;
+        let history = Self::open(&dir.join(HISTORY_FILE))?;
+        history.import_legacy(&dir.join(LEGACY_HISTORY_FILE))?;
+        Ok(history)
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open history at {}", path.display()))?;
+        // Concurrent davit runs wait for each other's writes.
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to set up history at {}", path.display()))?;
+        Ok(Self { conn })
+    }
+
+    pub fn record(&self, record: &DeployRecord) -> Result<()> {
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO deploys ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                    COLUMNS
+                ),
+                params![
+                    timestamp_column(record.timestamp),
+                    record.env,
+                    record.service,
+                    record.old_tag,
+                    record.new_tag,
+                    record.digest,
+                    record.user,
+                    record.duration_secs as i64,
+                    record.outcome.to_string(),
+                    record.kind.to_string(),
+                    record.rolls_back.map(timestamp_column),
+                    serde_json::to_string(&record.phases)?,
+                    serde_json::to_string(&record.approvals)?,
+                    record.freeze_override,
+                ],
+            )
+            .context("Failed to record the deploy in the history")?;
+        Ok(())
+    }
+
+    /// All records, oldest first.
+    pub fn load(&self) -> Result<Vec<DeployRecord>> {
+        self.query(
+            &format!("SELECT {} FROM deploys ORDER BY timestamp, id", COLUMNS),
+            params![],
+        )
+    }
+
+    /// The `limit` most recent records matching the filters, newest first.
+    pub fn latest(
+        &self,
+        env: Option<&str>,
+        service: Option<&str>,
+        cutoff: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<DeployRecord>> {
+        self.query(
+            &format!(
+                "SELECT {} FROM deploys
+                 WHERE (?1 IS NULL OR env = ?1) AND (?2 IS NULL OR service = ?2)
+                   AND (?3 IS NULL OR timestamp >= ?3)
+                 ORDER BY timestamp DESC, id DESC LIMIT ?4",
+                COLUMNS
+            ),
+            params![
+                env,
+                service,
+                cutoff.map(timestamp_column),
+                i64::try_from(limit).unwrap_or(i64::MAX)
+            ],
+        )
+    }
+
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<DeployRecord>> {
+        let mut statement = self.conn.prepare(sql)?;
+        let records = statement
+            .query_map(params, read_record)?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read the history")?;
+        Ok(records)
+    }
+
+    /// Moves the records of the JSON-lines history into the database, then renames the
+    /// file so that they are imported once. Unreadable lines are skipped.
+    fn import_legacy(&self, path: &Path) -> Result<()> {
+        let Ok(file) = fs::File::open(path) else {
+            return Ok(());
+        };
+        let records: Vec<DeployRecord> = BufReader::new(file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect();
+        self.conn.execute_batch("BEGIN")?;
+        for record in &records {
+            if let Err(e) = self.record(record) {
+                self.conn.execute_batch("ROLLBACK")?;
+                return Err(e);
+            }
+        }
+        self.conn.execute_batch("COMMIT")?;
+        fs::rename(path, path.with_extension("jsonl.imported"))
+            .with_context(|| format!("Failed to rename {}", path.display()))?;
+        output::progress(format!(
+            "Imported {} deploy(s) from {} into the history database.",
+            records.len(),
+            path.display()
+        ));
+        Ok(())
+    }
+}
+
+/// Timestamps are stored in a fixed-width UTC form, so that they sort as text.
+fn timestamp_column(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn read_record(row: &Row) -> rusqlite::Result<DeployRecord> {
+    Ok(DeployRecord {
+        timestamp: parse_column(row, 0, |s| s.parse::<DateTime<Utc>>().ok())?,
+        env: row.get(1)?,
+        service: row.get(2)?,
+        old_tag: row.get(3)?,
+        new_tag: row.get(4)?,
+        digest: row.get(5)?,
+        user: row.get(6)?,
+        duration_secs: row.get::<_, i64>(7)?.max(0) as u64,
+        outcome: parse_column(row, 8, json_string)?,
+        kind: parse_column(row, 9, json_string)?,
+        rolls_back: row
+            .get::<_, Option<String>>(10)?
+            .and_then(|s| s.parse().ok()),
+        phases: parse_column(row, 11, |s| serde_json::from_str(s).ok())?,
+        approvals: parse_column(row, 12, |s| serde_json::from_str(s).ok())?,
+        freeze_override: row.get(13)?,
+    })
+}
+
+/// Parses a text column, failing the row when it holds an unexpected value.
+fn parse_column<T>(
+    row: &Row,
+    index: usize,
+    parse: impl Fn(&str) -> Option<T>,
+) -> rusqlite::Result<T> {
+    let text: String = row.get(index)?;
+    parse(&text).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            index,
+            rusqlite::types::Type::Text,
+            format!("unexpected value '{}'", text).into(),
+        )
+    })
+}
+
+/// An enum stored as its serialized name, e.g. `succeeded`.
+fn json_string<T: DeserializeOwned>(text: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(text.to_string())).ok()
+}
+
+/// Name stamped on history records: the Git identity, else the OS user.
+pub fn current_user() -> String {
+    Command::new("git")
+        .args(["config", "user.email"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|u| !u.is_empty())
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The last successful deploy of the service, when deploying `new_tag` reverts it,
+/// i.e. goes back to the tag it replaced. `previous` is ordered oldest first.
+pub fn rollback_of<'a>(
+    previous: &'a [DeployRecord],
+    env: &str,
+    service: &str,
+    new_tag: &str,
+) -> Option<&'a DeployRecord> {
+    previous
+        .iter()
+        .rev()
+        .find(|r| r.env == env && r.service == service && r.outcome == Outcome::Succeeded)
+        .filter(|last| last.old_tag.as_deref() == Some(new_tag) && last.new_tag != new_tag)
+}
+
+/// The tag a rollback of the service returns to: the one replaced by the last successful
+/// deploy of `current`. Rollbacks are skipped, so rolling back again goes further back.
+pub fn previous_tag<'a>(
+    records: &'a [DeployRecord],
+    env: &str,
+    service: &str,
+    current: &str,
+) -> Option<&'a str> {
+    records
+        .iter()
+        .rev()
+        .find(|r| {
+            r.env == env
+                && r.service == service
+                && r.outcome == Outcome::Succeeded
+                && r.kind == DeployKind::Deploy
+                && r.new_tag == current
+        })?
+        .old_tag
+        .as_deref()
+        .filter(|tag| *tag != current)
+}
+
+/// Whether `record` is a rollback: recorded as such, or (for records predating rollback
+/// tracking) going back to the tag the previous deploy replaced.
+pub fn is_rollback(previous: &[DeployRecord], record: &DeployRecord) -> bool {
+    record.kind == DeployKind::Rollback
+        || rollback_of(previous, &record.env, &record.service, &record.new_tag).is_some()
+}
+
+/// Whether the successful deploy at `index` was later rolled back: a rollback links to it,
+/// or the next successful deploy of the service is a rollback.
+pub fn was_rolled_back(records: &[DeployRecord], index: usize) -> bool {
+    let record = &records[index];
+    if record.outcome != Outcome::Succeeded {
+        return false;
+    }
+    let later = records
+        .iter()
+        .enumerate()
+        .skip(index + 1)
+        .filter(|(_, r)| r.env == record.env && r.service == record.service);
+    for (later_index, r) in later {
+        if r.rolls_back == Some(record.timestamp) {
+            return true;
+        }
+        if r.outcome == Outcome::Succeeded {
+            return r.rolls_back.is_none() && is_rollback(&records[..later_index], r);
+        }
+    }
+    false
+}
+
+pub fn print_table(records: &[DeployRecord]) {
+    if records.is_empty() {
+        println!("No deploys recorded.");
+        return;
+    }
+    println!(
+        "{:<16} {:<15} {:<25} {:<31} {:<19} {:<10} {:<8} USER",
+        "TIME", "ENV", "SERVICE", "CHANGE", "DIGEST", "OUTCOME", "KIND"
+    );
+    for r in records {
+        println!(
+            "{:<16} {:<15} {:<25} {:<31} {:<19} {:<10} {:<8} {}",
+            r.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            r.env,
+            r.service,
+            format!("{} → {}", r.old_tag.as_deref().unwrap_or("-"), r.new_tag),
+            r.digest
+                .as_deref()
+                .map(|d| d.get(..19).unwrap_or(d))
+                .unwrap_or("-"),
+            r.outcome,
+            r.kind,
+            r.user
+        );
+    }
+}
+
+/// Parses a relative period such as `90d`, `12h` or `4w`.
+pub fn parse_since(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let unit_start = value.char_indices().last().map(|(i, _)| i).unwrap_or(0);
+    let (amount, unit) = value.split_at(unit_start);
+    let amount: i64 = amount
+        .parse()
+        .with_context(|| format!("Invalid period '{}', expected e.g. 90d, 12h or 4w", value))?;
+    match unit {
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        _ => Err(anyhow::anyhow!(
+            "Invalid period '{}', expected e.g. 90d, 12h or 4w",
+            value
+        )),
+    }
+}
+
+pub fn to_json(records: &[DeployRecord]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(records)?)
+}
+
+pub fn to_csv(records: &[DeployRecord]) -> String {
+    let mut csv = String::from(
+        "timestamp,env,service,old_tag,new_tag,digest,user,duration_secs,outcome,kind,rolls_back,\
+         registry_secs,review_secs,apply_secs,first_pod_ready_secs,rolled_out_secs\n",
+    );
+    for r in records {
+        let fields = [
+            r.timestamp.to_rfc3339(),
+            r.env.clone(),
+            r.service.clone(),
+            r.old_tag.clone().unwrap_or_default(),
+            r.new_tag.clone(),
+            r.digest.clone().unwrap_or_default(),
+            r.user.clone(),
+            r.duration_secs.to_string(),
+            r.outcome.to_string(),
+            r.kind.to_string(),
+            r.rolls_back.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            phase_field(r.phases.registry_secs),
+            phase_field(r.phases.review_secs),
+            phase_field(r.phases.apply_secs),
+            phase_field(r.phases.first_pod_ready_secs),
+            phase_field(r.phases.rolled_out_secs),
+        ];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&line.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn phase_field(secs: Option<f64>) -> String {
+    secs.map(|s| format!("{:.1}", s)).unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn record(service: &str, outcome: Outcome) -> DeployRecord {
+        DeployRecord {
+            timestamp: "2026-10-01T09:30:00Z".parse().unwrap(),
+            env: "production".to_string(),
+            service: service.to_string(),
+            old_tag: Some("v1.2.2".to_string()),
+            new_tag: "v1.2.3".to_string(),
+            digest: None,
+            user: "dev@acme.io".to_string(),
+            duration_secs: 95,
+            outcome,
+            kind: DeployKind::Deploy,
+            rolls_back: None,
+            phases: PhaseDurations::default(),
+            approvals: Vec::new(),
+            freeze_override: None,
+        }
+    }
+
+    #[test]
+    fn test_history_records_and_loads_deploys() -> Result<()> {
+        let dir = tempdir()?;
+        let history = History::open(&dir.path().join("nested").join("history.db"))?;
+        assert!(history.load()?.is_empty());
+
+        let mut full = record("auth-api", Outcome::Succeeded);
+        full.digest = Some("sha256:abc".to_string());
+        full.kind = DeployKind::Rollback;
+        full.rolls_back = Some("2026-09-30T08:00:00Z".parse().unwrap());
+        full.phases.apply_secs = Some(1.5);
+        full.approvals = vec!["JD".to_string()];
+        full.freeze_override = Some("hotfix".to_string());
+        history.record(&full)?;
+        history.record(&record("billing", Outcome::Failed))?;
+        let records = history.load()?;
+        assert_eq!(records, [full, record("billing", Outcome::Failed)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_imports_the_legacy_jsonl_file_once() -> Result<()> {
+        let dir = tempdir()?;
+        let legacy = dir.path().join(LEGACY_HISTORY_FILE);
+        let lines = [
+            serde_json::to_string(&record("auth-api", Outcome::Succeeded))?,
+            "not json".to_string(),
+        ];
+        fs::write(&legacy, lines.join("\n"))?;
+
+        let history = History::open(&dir.path().join(HISTORY_FILE))?;
+        history.import_legacy(&legacy)?;
+        history.import_legacy(&legacy)?;
+        assert_eq!(history.load()?, [record("auth-api", Outcome::Succeeded)]);
+        assert!(!legacy.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_csv_quotes_fields() {
+        let mut r = record("auth-api", Outcome::Succeeded);
+        r.user = "Doe, Jane".to_string();
+        r.phases.apply_secs = Some(1.25);
+        assert_eq!(
+            to_csv(&[r]),
+            "timestamp,env,service,old_tag,new_tag,digest,user,duration_secs,outcome,kind,rolls_back,\
+             registry_secs,review_secs,apply_secs,first_pod_ready_secs,rolled_out_secs\n\
+             2026-10-01T09:30:00+00:00,production,auth-api,v1.2.2,v1.2.3,,\"Doe, Jane\",95,succeeded,deploy,,\
+             ,,1.2,,\n"
+        );
+    }
+
+    #[test]
+    fn test_rollbacks_link_to_the_reverted_deploy() {
+        let original = record("auth-api", Outcome::Succeeded);
+        let mut rollback = record("auth-api", Outcome::Succeeded);
+        rollback.timestamp = "2026-10-01T10:00:00Z".parse().unwrap();
+        rollback.old_tag = Some("v1.2.3".to_string());
+        rollback.new_tag = "v1.2.2".to_string();
+
+        let records = vec![original.clone()];
+        assert_eq!(
+            rollback_of(&records, "production", "auth-api", "v1.2.2"),
+            Some(&original)
+        );
+        assert_eq!(
+            rollback_of(&records, "production", "auth-api", "v1.2.4"),
+            None
+        );
+
+        // Recorded rollbacks are recognized through their link, legacy ones by their tags.
+        let legacy = vec![original.clone(), rollback.clone()];
+        assert!(was_rolled_back(&legacy, 0));
+        rollback.kind = DeployKind::Rollback;
+        rollback.rolls_back = Some(original.timestamp);
+        rollback.new_tag = "v1.2.1".to_string();
+        let linked = vec![original, rollback];
+        assert!(is_rollback(&linked[..1], &linked[1]));
+        assert!(was_rolled_back(&linked, 0));
+    }
+
+    #[test]
+    fn test_previous_tag_skips_rollbacks() {
+        let mut first = record("auth-api", Outcome::Succeeded);
+        first.old_tag = Some("v1.2.1".to_string());
+        first.new_tag = "v1.2.2".to_string();
+        let second = record("auth-api", Outcome::Succeeded);
+        let mut rollback = record("auth-api", Outcome::Succeeded);
+        rollback.old_tag = Some("v1.2.3".to_string());
+        rollback.new_tag = "v1.2.2".to_string();
+        rollback.kind = DeployKind::Rollback;
+        let mut records = vec![first, second];
+
+        assert_eq!(
+            previous_tag(&records, "production", "auth-api", "v1.2.3"),
+            Some("v1.2.2")
+        );
+        records.push(rollback);
+        assert_eq!(
+            previous_tag(&records, "production", "auth-api", "v1.2.2"),
+            Some("v1.2.1")
+        );
+        assert_eq!(
+            previous_tag(&records, "staging", "auth-api", "v1.2.2"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_latest_filters_newest_first() -> Result<()> {
+        let dir = tempdir()?;
+        let history = History::open(&dir.path().join(HISTORY_FILE))?;
+        let mut older = record("auth-api", Outcome::Succeeded);
+        older.timestamp = "2026-09-01T09:30:00Z".parse().unwrap();
+        older.new_tag = "v1.2.2".to_string();
+        history.record(&record("auth-api", Outcome::Succeeded))?;
+        history.record(&older)?;
+        history.record(&record("billing", Outcome::Failed))?;
+
+        let auth = history.latest(Some("production"), Some("auth-api"), None, 10)?;
+        assert_eq!(auth.len(), 2);
+        assert_eq!(auth[0].new_tag, "v1.2.3");
+        assert_eq!(auth[1], older);
+        let cutoff = "2026-09-15T00:00:00Z".parse().ok();
+        assert_eq!(history.latest(None, None, cutoff, 10)?.len(), 2);
+        assert_eq!(history.latest(None, None, None, 1)?.len(), 1);
+        assert!(history.latest(Some("staging"), None, None, 10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_phase_lines_skip_unmeasured_phases() {
+        let phases = PhaseDurations {
+            apply_secs: Some(2.04),
+            rolled_out_secs: Some(95.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            phases.lines(),
+            vec!["Apply                 2.0s", "Fully rolled out     95.0s"]
+        );
+    }
+
+    #[test]
+    fn test_parse_since() -> Result<()> {
+        assert_eq!(parse_since("90d")?, Duration::days(90));
+        assert_eq!(parse_since("12h")?, Duration::hours(12));
+        assert!(parse_since("soon").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::process::Command;
+
+use crate::blueprint::split_image_ref;
+use crate::config::{DeployHooks, Environment, ServiceSource};
+
+/// Point of the deploy at which hooks run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// After the review, before the YAML is modified; a failing hook aborts the deploy.
+    PreDeploy,
+    /// After the apply, before the rollout is followed.
+    PostApply,
+    Succeeded,
+    Failed,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::PreDeploy => write!(f, "pre_deploy"),
+            Stage::PostApply => write!(f, "post_apply"),
+            Stage::Succeeded => write!(f, "on_success"),
+            Stage::Failed => write!(f, "on_failure"),
+        }
+    }
+}
+
+impl DeployHooks {
+    fn commands(&self, stage: Stage) -> &[String] {
+        match stage {
+            Stage::PreDeploy => &self.pre_deploy,
+            Stage::PostApply => &self.post_apply,
+            Stage::Succeeded => &self.on_success,
+            Stage::Failed => &self.on_failure,
+        }
+    }
+}
+
+/// The deploy hooks are run for.
+pub struct HookContext<'a> {
+    pub env: &'a Environment,
+    pub service: &'a ServiceSource,
+    pub tag: &'a str,
+}
+
+impl HookContext<'_> {
+    fn variables(&self, stage: Stage) -> Vec<(&'static str, String)> {
+        let previous_tag = split_image_ref(&self.service.image_path).1;
+        vec![
+            ("DAVIT_HOOK", stage.to_string()),
+            ("DAVIT_ENV", self.env.name.clone()),
+            ("DAVIT_SERVICE", self.service.name.clone()),
+            ("DAVIT_TAG", self.tag.to_string()),
+            (
+                "DAVIT_PREVIOUS_TAG",
+                previous_tag.unwrap_or_default().to_string(),
+            ),
+            (
+                "DAVIT_NAMESPACE",
+                self.service.namespace.clone().unwrap_or_default(),
+            ),
+            ("DAVIT_CONTEXT", self.env.kubectl_context.clone()),
+        ]
+    }
+}
+
+/// Runs the hooks of a stage with `sh -c`, environment hooks before service hooks,
+/// stopping at the first failure. With `dry_run` they are only listed.
+pub fn run(
+    hooks: &[&DeployHooks],
+    stage: Stage,
+    context: &HookContext,
+    dry_run: bool,
+) -> Result<()> {
+    let commands = hooks.iter().flat_map(|h| h.commands(stage));
+    for command in commands {
+        if dry_run {
+            println!("Dry-run: would run {} hook: {}", stage, command);
+            continue;
+        }
+        println!("🪝 Running {} hook: {}", stage, command);
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .envs(context.variables(stage))
+            .status()
+            .with_context(|| format!("Failed to run {} hook '{}'", stage, command))?;
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "{} hook '{}' failed ({})",
+                stage,
+                command,
+                status
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Runs the hooks of a stage that comes after the apply, where a failing hook is reported
+/// without changing the deploy's outcome.
+pub fn run_after_apply(hooks: &[&DeployHooks], stage: Stage, context: &HookContext, dry_run: bool) {
+    if let Err(e) = run(hooks, stage, context, dry_run) {
+        println!("⚠️  {:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_see_deploy_variables_and_stop_on_failure() {
+        let env = Environment {
+            name: "staging".to_string(),
+            kubectl_context: "gke_staging".to_string(),
+            ..Default::default()
+        };
+        let service = ServiceSource {
+            name: "auth-api".to_string(),
+            kind: "Deployment".to_string(),
+            image_path: "gcr.io/p/auth-api:v1".to_string(),
+            container_name: "app".to_string(),
+            source_name: "main".to_string(),
+            source_root: Default::default(),
+            yaml_path: Default::default(),
+            document_index: 0,
+            kustomize_overlay: None,
+            namespace: None,
+            selector: None,
+            containers: Vec::new(),
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
+        };
+        let context = HookContext {
+            env: &env,
+            service: &service,
+            tag: "v2",
+        };
+        let passing = DeployHooks {
+            pre_deploy: vec![
+                r#"test "$DAVIT_HOOK $DAVIT_ENV $DAVIT_SERVICE $DAVIT_PREVIOUS_TAG $DAVIT_TAG" = "pre_deploy staging auth-api v1 v2""#.to_string(),
+            ],
+            ..Default::default()
+        };
+        let failing = DeployHooks {
+            pre_deploy: vec!["exit 3".to_string()],
+            ..Default::default()
+        };
+        assert!(run(&[&passing], Stage::PreDeploy, &context, false).is_ok());
+        assert!(run(&[&passing, &failing], Stage::PreDeploy, &context, false).is_err());
+        assert!(run(&[&failing], Stage::PreDeploy, &context, true).is_ok());
+        assert!(run(&[&failing], Stage::PostApply, &context, false).is_ok());
+    }
+}
@@ -0,0 +1,324 @@
+use anyhow::Result;
+use k8s_openapi::api::core::v1::{Secret, ServiceAccount};
+use kube::{Api, Client};
+use std::collections::BTreeSet;
+
+use crate::blueprint::find_pod_spec;
+use crate::config::{Environment, ServiceSource};
+use crate::registry::{Registry, RegistryLocation};
+
+const WORKLOAD_IDENTITY_ANNOTATION: &str = "iam.gke.io/gcp-service-account";
+
+/// Checks that the workload's pull credentials can reach the image's registry project and
+/// returns human-readable warnings.
+///
+/// A matching imagePullSecret or a same-project image is considered enough; otherwise the
+/// IAM policies of the registry are checked for the environment's `node_service_account`.
+pub async fn check(
+    client: &Client,
+    env: &Environment,
+    service: &ServiceSource,
+) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    let Some(location) = RegistryLocation::parse(&service.image_path) else {
+        return Ok(warnings);
+    };
+    let Some(workload) = service.workload_document() else {
+        return Ok(warnings);
+    };
+    let Some(pod_spec) = find_pod_spec(&workload) else {
+        return Ok(warnings);
+    };
+
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let sa_name = pod_spec
+        .get("serviceAccountName")
+        .or_else(|| pod_spec.get("serviceAccount"))
+        .and_then(|n| n.as_str())
+        .unwrap_or("default")
+        .to_string();
+
+    let mut secret_names = pull_secret_names(pod_spec.get("imagePullSecrets"));
+    let service_accounts: Api<ServiceAccount> = Api::namespaced(client.clone(), namespace);
+    let service_account = service_accounts.get_opt(&sa_name).await?;
+    match &service_account {
+        Some(sa) => secret_names.extend(
+            sa.image_pull_secrets
+                .iter()
+                .flatten()
+                .map(|r| r.name.clone()),
+        ),
+        None => warnings.push(format!(
+            "ServiceAccount '{}' used by {} does not exist in namespace '{}'; pods will not be created.",
+            sa_name, service.name, namespace
+        )),
+    }
+
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    for name in &secret_names {
+        match secrets.get_opt(name).await? {
+            Some(secret) if secret_covers_host(&secret, &location.host) => return Ok(warnings),
+            Some(_) => {}
+            None => warnings.push(format!(
+                "imagePullSecret '{}' is referenced but missing in namespace '{}'.",
+                name, namespace
+            )),
+        }
+    }
+
+    if env.gcp_project.as_deref() == Some(location.project.as_str()) {
+        return Ok(warnings);
+    }
+
+    let workload_identity_note = service_account
+        .as_ref()
+        .and_then(|sa| sa.metadata.annotations.as_ref())
+        .and_then(|a| a.get(WORKLOAD_IDENTITY_ANNOTATION))
+        .map(|gsa| {
+            format!(
+                " The Workload Identity binding to {} does not apply to image pulls, which use the node service account.",
+                gsa
+            )
+        })
+        .unwrap_or_default();
+
+    // Offline environments cannot reach the registry IAM policies.
+    if env.offline.is_some() {
+        return Ok(warnings);
+    }
+
+    let Some(node_sa) = &env.node_service_account else {
+        warnings.push(format!(
+            "{} pulls from project '{}' ({}) with no matching imagePullSecret; set node_service_account on environment '{}' to verify the nodes can pull it.{}",
+            service.image_path, location.project, location.host, env.name, workload_identity_note
+        ));
+        return Ok(warnings);
+    };
+
+    let token = Registry::access_token(env.registry_access_token.as_deref()).await?;
+    let member = format!("serviceAccount:{}", node_sa);
+    if !Registry::grants_pull(&location, &member, &token).await? {
+        warnings.push(format!(
+            "Node service account {} has no Artifact Registry read role on project '{}'{}; pods will fail with ImagePullBackOff.{}",
+            node_sa,
+            location.project,
+            location
+                .repository
+                .as_ref()
+                .map(|r| format!(" or repository '{}'", r))
+                .unwrap_or_default(),
+            workload_identity_note
+        ));
+    }
+
+    Ok(warnings)
+}
+
+/// Verifies the imagePullSecrets referenced by a workload before apply: each must exist,
+/// and together with those of its ServiceAccount they must hold credentials for the
+/// registry host of every first-party image. Returns the problems found.
+///
+/// Google registries are left to `check`, since the nodes may pull from them without a secret.
+pub async fn verify_pull_secrets(
+    client: &Client,
+    env: &Environment,
+    namespace: &str,
+    workload: &serde_yaml::Value,
+) -> Result<Vec<String>> {
+    let Some(pod_spec) = find_pod_spec(workload) else {
+        return Ok(Vec::new());
+    };
+    let referenced = pull_secret_names(pod_spec.get("imagePullSecrets"));
+    if referenced.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut problems = Vec::new();
+    let mut secret_names = referenced.clone();
+    let sa_name = pod_spec
+        .get("serviceAccountName")
+        .or_else(|| pod_spec.get("serviceAccount"))
+        .and_then(|n| n.as_str())
+        .unwrap_or("default");
+    let service_accounts: Api<ServiceAccount> = Api::namespaced(client.clone(), namespace);
+    if let Some(sa) = service_accounts.get_opt(sa_name).await? {
+        secret_names.extend(
+            sa.image_pull_secrets
+                .iter()
+                .flatten()
+                .map(|r| r.name.clone()),
+        );
+    }
+
+    let secrets_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let mut secrets = Vec::new();
+    for name in &secret_names {
+        match secrets_api.get_opt(name).await? {
+            Some(secret) => secrets.push(secret),
+            None if referenced.contains(name) => problems.push(format!(
+                "imagePullSecret '{}' does not exist in namespace '{}'",
+                name, namespace
+            )),
+            None => {}
+        }
+    }
+
+    let images = ["initContainers", "containers"]
+        .iter()
+        .filter_map(|key| pod_spec.get(key)?.as_sequence())
+        .flatten()
+        .filter_map(|c| c.get("image")?.as_str())
+        .filter(|image| env.is_deployable_image(image));
+    for (image, host) in uncovered_images(images, &secrets) {
+        problems.push(format!(
+            "No imagePullSecret of the workload ({}) holds credentials for {}, the registry of {}",
+            secret_names.iter().cloned().collect::<Vec<_>>().join(", "),
+            host,
+            image
+        ));
+    }
+    Ok(problems)
+}
+
+/// Images outside Google registries whose host no secret has credentials for.
+fn uncovered_images<'a>(
+    images: impl Iterator<Item = &'a str>,
+    secrets: &[Secret],
+) -> Vec<(&'a str, String)> {
+    let mut seen = BTreeSet::new();
+    images
+        .filter_map(|image| {
+            let host = registry_host(image);
+            let google =
+                host == "gcr.io" || host.ends_with(".gcr.io") || host.ends_with("-docker.pkg.dev");
+            let covered = secrets.iter().any(|s| secret_covers_host(s, &host));
+            (!google && !covered && seen.insert(host.clone())).then_some((image, host))
+        })
+        .collect()
+}
+
+/// Registry host of an image reference; Docker Hub when the first segment is not a host.
+fn registry_host(image: &str) -> String {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains(['.', ':']) || first == "localhost" => first.to_string(),
+        _ => "docker.io".to_string(),
+    }
+}
+
+fn pull_secret_names(value: Option<&serde_yaml::Value>) -> BTreeSet<String> {
+    value
+        .and_then(|v| v.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.get("name").and_then(|n| n.as_str()))
+        .map(|n| n.to_string())
+        .collect()
+}
+
+/// Whether a docker config Secret holds credentials for `host`.
+fn secret_covers_host(secret: &Secret, host: &str) -> bool {
+    let Some(data) = &secret.data else {
+        return false;
+    };
+
+    [".dockerconfigjson", ".dockercfg"]
+        .iter()
+        .filter_map(|key| data.get(*key))
+        .filter_map(|raw| serde_json::from_slice::<serde_json::Value>(&raw.0).ok())
+        .any(|config| {
+            let auths = config.get("auths").unwrap_or(&config);
+            auths.as_object().is_some_and(|auths| {
+                auths.keys().any(|registry| {
+                    let registry = registry
+                        .trim_start_matches("https://")
+                        .trim_start_matches("http://")
+                        .split('/')
+                        .next()
+                        .unwrap_or_default();
+                    registry == host
+                        || (host == "docker.io"
+                            && matches!(registry, "index.docker.io" | "registry-1.docker.io"))
+                })
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::ByteString;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_find_pod_spec_and_pull_secrets() {
+        let workload: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+kind: CronJob
+spec:
+  jobTemplate:
+    spec:
+      template:
+        spec:
+          serviceAccountName: batch
+          imagePullSecrets:
+          - name: shared-registry
+          containers:
+          - name: main
+            image: europe-west1-docker.pkg.dev/shared/apps/batch:v1
+"#,
+        )
+        .unwrap();
+
+        let pod_spec = find_pod_spec(&workload).unwrap();
+        assert_eq!(pod_spec.get("serviceAccountName").unwrap(), "batch");
+        assert_eq!(
+            pull_secret_names(pod_spec.get("imagePullSecrets")),
+            BTreeSet::from(["shared-registry".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_secret_covers_host_matches_docker_config_auths() {
+        let config = r#"{"auths":{"https://europe-west1-docker.pkg.dev":{"auth":"x"}}}"#;
+        let secret = Secret {
+            data: Some(BTreeMap::from([(
+                ".dockerconfigjson".to_string(),
+                ByteString(config.as_bytes().to_vec()),
+            )])),
+            ..Default::default()
+        };
+
+        assert!(secret_covers_host(&secret, "europe-west1-docker.pkg.dev"));
+        assert!(!secret_covers_host(&secret, "us-docker.pkg.dev"));
+    }
+
+    #[test]
+    fn test_uncovered_images_reports_private_hosts_without_credentials() {
+        let secret = |config: &str| Secret {
+            data: Some(BTreeMap::from([(
+                ".dockerconfigjson".to_string(),
+                ByteString(config.as_bytes().to_vec()),
+            )])),
+            ..Default::default()
+        };
+        let secrets = [
+            secret(r#"{"auths":{"harbor.acme.io":{"auth":"x"}}}"#),
+            secret(r#"{"auths":{"https://index.docker.io/v1/":{"auth":"x"}}}"#),
+        ];
+        let images = [
+            "harbor.acme.io/apps/auth-api:v2",
+            "acme/worker:v2",
+            "registry.internal.corp:5000/apps/cron:v2",
+            "registry.internal.corp:5000/apps/cron-migrate:v2",
+            "europe-west1-docker.pkg.dev/acme/apps/auth-api:v2",
+        ];
+
+        assert_eq!(
+            uncovered_images(images.into_iter(), &secrets),
+            vec![(
+                "registry.internal.corp:5000/apps/cron:v2",
+                "registry.internal.corp:5000".to_string()
+            )]
+        );
+    }
+}
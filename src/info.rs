@@ -6,12 +6,14 @@ use k8s_openapi::api::batch::v1::{CronJob, Job};
 use k8s_openapi::api::core::v1::{Event, Pod};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
 use kube::api::ListParams;
-use kube::config::KubeConfigOptions;
 use kube::{Api, Client};
 use std::collections::BTreeMap;
 
+use crate::blueprint::split_image_ref;
+use crate::cluster;
 use crate::config::{Environment, ServiceSource};
 use crate::git::Git;
+use crate::retry;
 
 /// Convert a k8s Time (jiff::Timestamp) to chrono::DateTime<Utc>.
 fn to_chrono(t: &Time) -> DateTime<Utc> {
@@ -90,18 +92,15 @@ pub async fn show_info(env: &Environment, service: &ServiceSource) -> Result<()>
     let ns = service.namespace.as_deref().unwrap_or("default");
 
     // Build kube client
-    let options = KubeConfigOptions {
-        context: Some(env.kubectl_context.clone()),
-        ..Default::default()
-    };
-    let config = kube::Config::from_kubeconfig(&options)
-        .await
-        .context("Failed to load kubeconfig")?;
-    let client = Client::try_from(config).context("Failed to create Kubernetes client")?;
+    let client = cluster::client(&env.kubectl_context).await?;
 
     // Fetch all data
-    let workload = fetch_workload_info(&client, ns, service).await?;
-    let pods = fetch_pod_details(&client, ns, service).await?;
+    let workload = retry::with_backoff("Workload lookup", || {
+        fetch_workload_info(&client, ns, service)
+    })
+    .await?;
+    let pods =
+        retry::with_backoff("Pod listing", || fetch_pod_details(&client, ns, service)).await?;
     let events = fetch_events(&client, ns, &service.name).await;
     let last_commit = fetch_git_info(service);
     let image_comparison = build_image_comparison(service, &workload.running_images);
@@ -123,22 +122,6 @@ fn normalize_image_ref(image: &str) -> String {
     image.trim().to_ascii_lowercase()
 }
 
-fn split_image_ref(image: &str) -> (&str, Option<&str>) {
-    let trimmed = image.trim();
-
-    if let Some((base, digest)) = trimmed.split_once('@') {
-        return (base, Some(digest));
-    }
-
-    if let Some((base, tag)) = trimmed.rsplit_once(':')
-        && !tag.contains('/')
-    {
-        return (base, Some(tag));
-    }
-
-    (trimmed, None)
-}
-
 fn same_image_ref(lhs: &str, rhs: &str) -> bool {
     let (lhs_base, lhs_ver) = split_image_ref(lhs);
     let (rhs_base, rhs_ver) = split_image_ref(rhs);
@@ -164,6 +147,30 @@ fn build_image_comparison(service: &ServiceSource, running_images: &[String]) ->
     }
 }
 
+/// Images of the workload as deployed in the cluster.
+pub async fn running_images(
+    client: &Client,
+    namespace: &str,
+    service: &ServiceSource,
+) -> Result<Vec<String>> {
+    Ok(fetch_workload_info(client, namespace, service)
+        .await?
+        .running_images)
+}
+
+/// Tag of the service's image running in the cluster, if it can be read.
+pub async fn running_tag(client: &Client, service: &ServiceSource) -> Option<String> {
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let (base, _) = split_image_ref(&service.image_path);
+    running_images(client, namespace, service)
+        .await
+        .ok()?
+        .iter()
+        .map(|image| split_image_ref(image))
+        .find(|(image, _)| *image == base)
+        .and_then(|(_, tag)| tag.map(str::to_string))
+}
+
 async fn fetch_workload_info(
     client: &Client,
     namespace: &str,
@@ -598,7 +605,7 @@ async fn fetch_events(client: &Client, namespace: &str, workload_name: &str) ->
         })
         .collect();
 
-    events.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+    events.sort_by_key(|e| std::cmp::Reverse(e.last_seen));
     events.truncate(10);
     events
 }
@@ -734,10 +741,15 @@ fn print_info(info: &ServiceInfo, service: &ServiceSource, env: &Environment) {
     if info.image_comparison.drift {
         println!(
             "  Status:   {}",
-            style("DIFF: cluster image does not match YAML").yellow().bold()
+            style("DIFF: cluster image does not match YAML")
+                .yellow()
+                .bold()
         );
     } else {
-        println!("  Status:   {}", style("OK: cluster and YAML are aligned").green());
+        println!(
+            "  Status:   {}",
+            style("OK: cluster and YAML are aligned").green()
+        );
     }
     println!();
 
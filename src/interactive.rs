@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use inquire::{Confirm, Text};
+use std::sync::OnceLock;
+
+use crate::output;
+
+static INTERACTIVE: OnceLock<bool> = OnceLock::new();
+
+/// Sets whether davit may prompt; it does until then. Non-interactive runs (CI) take the
+/// default answer of prompts that have one and fail on those that need input.
+pub fn configure(interactive: bool) {
+    let _ = INTERACTIVE.set(interactive);
+}
+
+pub fn enabled() -> bool {
+    INTERACTIVE.get().copied().unwrap_or(true)
+}
+
+/// Fails in non-interactive mode, where a missing `what` cannot be asked for.
+pub fn require(what: &str) -> Result<()> {
+    if enabled() {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "{} is required in non-interactive mode",
+        what
+    ))
+}
+
+/// Asks for confirmation, or takes the default answer in non-interactive mode.
+pub fn confirm(prompt: Confirm) -> Result<bool> {
+    let message = prompt.message;
+    if enabled() {
+        return prompt
+            .prompt()
+            .with_context(|| format!("'{}' was cancelled", message));
+    }
+    let answer = prompt
+        .default
+        .with_context(|| format!("'{}' needs an answer in non-interactive mode", message))?;
+    output::progress(format!("{} {}", message, if answer { "yes" } else { "no" }));
+    Ok(answer)
+}
+
+/// Asks for a value, or takes the default in non-interactive mode, where `what` is
+/// required when there is none.
+pub fn text(prompt: Text, what: &str) -> Result<String> {
+    let message = prompt.message;
+    if enabled() {
+        return prompt
+            .prompt()
+            .with_context(|| format!("'{}' was cancelled", message));
+    }
+    prompt
+        .default
+        .map(str::to_string)
+        .with_context(|| format!("{} is required in non-interactive mode", what))
+}
@@ -0,0 +1,374 @@
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+/// Keys of the dashboard actions, each action bound to a list of key names: a
+/// character (`q`, `/`, `G`), a named key (`Esc`, `Enter`, `Tab`, `Space`, `Up`,
+/// `PageDown`, `Home`, `F1`, ...), optionally prefixed with `Ctrl-` or `Alt-`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DashboardKeys {
+    pub quit: Vec<String>,
+    pub rollback: Vec<String>,
+    pub canary_next: Vec<String>,
+    pub search: Vec<String>,
+    pub filter: Vec<String>,
+    /// Pause / resume following the newest log lines.
+    pub pause: Vec<String>,
+    pub select: Vec<String>,
+    /// Switch between the old and new log panes while selecting.
+    pub switch_pane: Vec<String>,
+    pub wrap: Vec<String>,
+    pub scroll_up: Vec<String>,
+    pub scroll_down: Vec<String>,
+    pub page_up: Vec<String>,
+    pub page_down: Vec<String>,
+    pub top: Vec<String>,
+    pub bottom: Vec<String>,
+    pub grow_pods: Vec<String>,
+    pub shrink_pods: Vec<String>,
+    pub shrink_old_logs: Vec<String>,
+    pub grow_old_logs: Vec<String>,
+    pub next_container: Vec<String>,
+    pub pick_containers: Vec<String>,
+    pub resources: Vec<String>,
+    pub crash_logs: Vec<String>,
+    /// Unfold / fold the stack traces grouped with their log entries.
+    pub expand_traces: Vec<String>,
+    /// Save the log lines of the session to a file in the current directory.
+    pub export_logs: Vec<String>,
+    /// Show / hide the list of key bindings.
+    pub help: Vec<String>,
+}
+
+fn keys(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+impl Default for DashboardKeys {
+    fn default() -> Self {
+        Self {
+            quit: keys(&["q"]),
+            rollback: keys(&["u"]),
+            canary_next: keys(&["n"]),
+            search: keys(&["/"]),
+            filter: keys(&["f"]),
+            pause: keys(&["F"]),
+            select: keys(&["v"]),
+            switch_pane: keys(&["Tab", "Left", "Right", "h", "l"]),
+            wrap: keys(&["w"]),
+            scroll_up: keys(&["Up"]),
+            scroll_down: keys(&["Down"]),
+            page_up: keys(&["PageUp"]),
+            page_down: keys(&["PageDown"]),
+            top: keys(&["Home"]),
+            bottom: keys(&["End"]),
+            grow_pods: keys(&["+", "="]),
+            shrink_pods: keys(&["-"]),
+            shrink_old_logs: keys(&["<", "["]),
+            grow_old_logs: keys(&[">", "]"]),
+            next_container: keys(&["c"]),
+            pick_containers: keys(&["C"]),
+            resources: keys(&["r"]),
+            crash_logs: keys(&["x"]),
+            expand_traces: keys(&["e"]),
+            export_logs: keys(&["s"]),
+            help: keys(&["?"]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Rollback,
+    CanaryNext,
+    Search,
+    Filter,
+    Pause,
+    Select,
+    SwitchPane,
+    Wrap,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    GrowPods,
+    ShrinkPods,
+    ShrinkOldLogs,
+    GrowOldLogs,
+    NextContainer,
+    PickContainers,
+    Resources,
+    CrashLogs,
+    ExpandTraces,
+    ExportLogs,
+    Help,
+}
+
+impl Action {
+    /// What the action does, as listed in the dashboard's help.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Close the dashboard (abort a canary)",
+            Action::Rollback => "Roll back when a new pod is crashing or cannot pull its image",
+            Action::CanaryNext => "Shift the next step of traffic to the canary",
+            Action::Search => "Search the logs with a regex",
+            Action::Filter => "Show only the log lines matching the search",
+            Action::Pause => "Pause / resume following the newest log lines",
+            Action::Select => "Select log lines to copy (sets the anchor while selecting)",
+            Action::SwitchPane => "Switch log pane while selecting",
+            Action::Wrap => "Wrap long log lines",
+            Action::ScrollUp => "Scroll the logs back by a line",
+            Action::ScrollDown => "Scroll the logs forward by a line",
+            Action::PageUp => "Scroll the logs back by a page",
+            Action::PageDown => "Scroll the logs forward by a page",
+            Action::Top => "Jump to the oldest kept log line",
+            Action::Bottom => "Jump to the newest log line and follow",
+            Action::GrowPods => "Grow the pod list",
+            Action::ShrinkPods => "Shrink the pod list",
+            Action::ShrinkOldLogs => "Narrow the old pod logs",
+            Action::GrowOldLogs => "Widen the old pod logs",
+            Action::NextContainer => "Stream the logs of the next container",
+            Action::PickContainers => "Pick the containers to stream",
+            Action::Resources => "Show / hide CPU and memory usage",
+            Action::CrashLogs => "Expand / collapse the crash logs",
+            Action::ExpandTraces => "Unfold / fold stack traces",
+            Action::ExportLogs => "Save the session's log lines to a file",
+            Action::Help => "Show / hide this help",
+        }
+    }
+}
+
+impl DashboardKeys {
+    fn bindings(&self) -> [(Action, &[String]); 26] {
+        [
+            (Action::Quit, &self.quit),
+            (Action::Rollback, &self.rollback),
+            (Action::CanaryNext, &self.canary_next),
+            (Action::Search, &self.search),
+            (Action::Filter, &self.filter),
+            (Action::Pause, &self.pause),
+            (Action::Select, &self.select),
+            (Action::SwitchPane, &self.switch_pane),
+            (Action::Wrap, &self.wrap),
+            (Action::ScrollUp, &self.scroll_up),
+            (Action::ScrollDown, &self.scroll_down),
+            (Action::PageUp, &self.page_up),
+            (Action::PageDown, &self.page_down),
+            (Action::Top, &self.top),
+            (Action::Bottom, &self.bottom),
+            (Action::GrowPods, &self.grow_pods),
+            (Action::ShrinkPods, &self.shrink_pods),
+            (Action::ShrinkOldLogs, &self.shrink_old_logs),
+            (Action::GrowOldLogs, &self.grow_old_logs),
+            (Action::NextContainer, &self.next_container),
+            (Action::PickContainers, &self.pick_containers),
+            (Action::Resources, &self.resources),
+            (Action::CrashLogs, &self.crash_logs),
+            (Action::ExpandTraces, &self.expand_traces),
+            (Action::ExportLogs, &self.export_logs),
+            (Action::Help, &self.help),
+        ]
+    }
+
+    /// Fails on unknown key names and on keys bound to several actions. Switching panes
+    /// only happens while selecting, so its keys may also serve another action.
+    pub fn validate(&self) -> Result<()> {
+        let mut seen: Vec<(Key, Action)> = Vec::new();
+        for (action, names) in self.bindings() {
+            for name in names {
+                let key = Key::parse(name).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown key '{}' in dashboard.keys: use a character, a key name (Esc, Enter, Tab, Space, Up, PageDown, Home, F1, ...) or Ctrl-/Alt- with one",
+                        name
+                    )
+                })?;
+                if action == Action::SwitchPane {
+                    continue;
+                }
+                if let Some((_, other)) = seen.iter().find(|(k, a)| *k == key && *a != action) {
+                    return Err(anyhow::anyhow!(
+                        "Key '{}' is bound to both {:?} and {:?} in dashboard.keys",
+                        name,
+                        other,
+                        action
+                    ));
+                }
+                seen.push((key, action));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Key {
+    code: KeyCode,
+    ctrl: bool,
+    alt: bool,
+}
+
+impl Key {
+    fn parse(name: &str) -> Option<Key> {
+        let (ctrl, rest) = match name.strip_prefix("Ctrl-") {
+            Some(rest) => (true, rest),
+            None => (false, name),
+        };
+        let (alt, rest) = match rest.strip_prefix("Alt-") {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "esc" | "escape" => KeyCode::Esc,
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                other => KeyCode::F(other.strip_prefix('f')?.parse().ok()?),
+            },
+        };
+        Some(Key { code, ctrl, alt })
+    }
+
+    /// Whether the key event is this key; Shift is part of the character itself.
+    fn matches(&self, event: &KeyEvent) -> bool {
+        event.code == self.code
+            && event.modifiers.contains(KeyModifiers::CONTROL) == self.ctrl
+            && event.modifiers.contains(KeyModifiers::ALT) == self.alt
+    }
+}
+
+/// The dashboard's key bindings, resolved from [`DashboardKeys`].
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Key, Action)>,
+    keys: DashboardKeys,
+}
+
+impl Keymap {
+    /// Binds the valid keys of `keys`; see [`DashboardKeys::validate`] for the others.
+    pub fn new(keys: &DashboardKeys) -> Self {
+        let bindings = keys
+            .bindings()
+            .into_iter()
+            .flat_map(|(action, names)| {
+                names
+                    .iter()
+                    .filter_map(move |name| Some((Key::parse(name)?, action)))
+            })
+            .collect();
+        Self {
+            bindings,
+            keys: keys.clone(),
+        }
+    }
+
+    /// The action of a key event outside of selection, if any.
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(key, action)| *action != Action::SwitchPane && key.matches(event))
+            .map(|(_, action)| *action)
+    }
+
+    /// Whether the key event is bound to `action`.
+    pub fn is(&self, event: &KeyEvent, action: Action) -> bool {
+        self.bindings
+            .iter()
+            .any(|(key, bound)| *bound == action && key.matches(event))
+    }
+
+    /// Every action with its keys, in configuration order.
+    pub fn help(&self) -> Vec<(String, Action)> {
+        self.keys
+            .bindings()
+            .into_iter()
+            .map(|(action, names)| {
+                let keys = if names.is_empty() {
+                    "unbound".to_string()
+                } else {
+                    names.join(" ")
+                };
+                (keys, action)
+            })
+            .collect()
+    }
+
+    /// The first key of `action`, as shown in the dashboard's help.
+    pub fn label(&self, action: Action) -> &str {
+        self.keys
+            .bindings()
+            .into_iter()
+            .find(|(a, _)| *a == action)
+            .and_then(|(_, names)| names.first())
+            .map(String::as_str)
+            .unwrap_or("unbound")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keymap_resolves_configured_keys() {
+        let vim = DashboardKeys {
+            quit: keys(&["Ctrl-c", "q"]),
+            page_down: keys(&["Space", "PageDown"]),
+            bottom: keys(&["G"]),
+            ..DashboardKeys::default()
+        };
+        assert!(vim.validate().is_ok());
+        let keymap = Keymap::new(&vim);
+        let event = |code, modifiers| KeyEvent::new(code, modifiers);
+
+        assert_eq!(
+            keymap.action(&event(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            keymap.action(&event(KeyCode::Char('c'), KeyModifiers::NONE)),
+            Some(Action::NextContainer)
+        );
+        assert_eq!(
+            keymap.action(&event(KeyCode::Char('G'), KeyModifiers::SHIFT)),
+            Some(Action::Bottom)
+        );
+        assert_eq!(
+            keymap.action(&event(KeyCode::Char(' '), KeyModifiers::NONE)),
+            Some(Action::PageDown)
+        );
+        assert_eq!(
+            keymap.action(&event(KeyCode::End, KeyModifiers::NONE)),
+            None
+        );
+        assert!(keymap.is(&event(KeyCode::Tab, KeyModifiers::NONE), Action::SwitchPane));
+        assert_eq!(keymap.label(Action::Quit), "Ctrl-c");
+
+        let clash = DashboardKeys {
+            wrap: keys(&["q"]),
+            ..DashboardKeys::default()
+        };
+        assert!(clash.validate().is_err());
+        let unknown = DashboardKeys {
+            quit: keys(&["Hyper-q"]),
+            ..DashboardKeys::default()
+        };
+        assert!(unknown.validate().is_err());
+    }
+}
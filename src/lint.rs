@@ -0,0 +1,218 @@
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::blueprint::{find_pod_spec, split_image_ref};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    pub severity: Severity,
+    pub check: &'static str,
+    pub message: String,
+}
+
+/// Runs the built-in best-practice checks on a workload manifest.
+pub fn lint_workload(workload: &serde_yaml::Value) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    let Some(pod_spec) = find_pod_spec(workload) else {
+        return findings;
+    };
+    let is_long_running = workload
+        .get("kind")
+        .and_then(|k| k.as_str())
+        .is_some_and(|k| matches!(k, "Deployment" | "StatefulSet" | "DaemonSet"));
+
+    let mut push = |severity, check, message: String| {
+        findings.push(LintFinding {
+            severity,
+            check,
+            message,
+        })
+    };
+
+    if pod_spec.get("hostNetwork").and_then(|h| h.as_bool()) == Some(true) {
+        push(
+            Severity::Warning,
+            "host-network",
+            "pod uses the host network".to_string(),
+        );
+    }
+
+    for container in pod_spec
+        .get("containers")
+        .and_then(|c| c.as_sequence())
+        .into_iter()
+        .flatten()
+    {
+        let name = container
+            .get("name")
+            .and_then(|n| n.as_str())
+            .unwrap_or("default");
+
+        if let Some(image) = container.get("image").and_then(|i| i.as_str()) {
+            let (_, tag) = split_image_ref(image);
+            if !image.contains('@') && matches!(tag, None | Some("latest")) {
+                push(
+                    Severity::Error,
+                    "latest-tag",
+                    format!("container '{}' uses a mutable tag ({})", name, image),
+                );
+            }
+        }
+
+        let security_context = container.get("securityContext");
+        if security_context
+            .and_then(|s| s.get("privileged"))
+            .and_then(|p| p.as_bool())
+            == Some(true)
+        {
+            push(
+                Severity::Error,
+                "privileged-container",
+                format!("container '{}' runs privileged", name),
+            );
+        }
+        if security_context
+            .and_then(|s| s.get("allowPrivilegeEscalation"))
+            .and_then(|p| p.as_bool())
+            == Some(true)
+        {
+            push(
+                Severity::Warning,
+                "privilege-escalation",
+                format!("container '{}' allows privilege escalation", name),
+            );
+        }
+
+        let limits = container.get("resources").and_then(|r| r.get("limits"));
+        for resource in ["cpu", "memory"] {
+            if limits.and_then(|l| l.get(resource)).is_none() {
+                push(
+                    Severity::Warning,
+                    "no-resource-limits",
+                    format!("container '{}' has no {} limit", name, resource),
+                );
+            }
+        }
+
+        if is_long_running {
+            if container.get("readinessProbe").is_none() {
+                push(
+                    Severity::Warning,
+                    "no-readiness-probe",
+                    format!("container '{}' has no readiness probe", name),
+                );
+            }
+            if container.get("livenessProbe").is_none() {
+                push(
+                    Severity::Info,
+                    "no-liveness-probe",
+                    format!("container '{}' has no liveness probe", name),
+                );
+            }
+        }
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    findings
+}
+
+pub fn print_findings(findings: &[LintFinding]) {
+    if findings.is_empty() {
+        return;
+    }
+
+    println!("{}", style("Lint findings").bold());
+    for finding in findings {
+        let severity = match finding.severity {
+            Severity::Error => style(finding.severity.to_string()).red().bold(),
+            Severity::Warning => style(finding.severity.to_string()).yellow(),
+            Severity::Info => style(finding.severity.to_string()).dim(),
+        };
+        println!("  [{}] {}: {}", severity, finding.check, finding.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_workload_flags_common_issues() {
+        let workload: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+kind: Deployment
+spec:
+  template:
+    spec:
+      containers:
+      - name: main
+        image: europe-west1-docker.pkg.dev/acme/apps/auth-api:latest
+        securityContext:
+          privileged: true
+        resources:
+          limits:
+            cpu: 500m
+        readinessProbe:
+          httpGet:
+            path: /health
+            port: 8080
+"#,
+        )
+        .unwrap();
+
+        let checks: Vec<(Severity, &str)> = lint_workload(&workload)
+            .iter()
+            .map(|f| (f.severity, f.check))
+            .collect();
+        assert_eq!(
+            checks,
+            vec![
+                (Severity::Error, "latest-tag"),
+                (Severity::Error, "privileged-container"),
+                (Severity::Warning, "no-resource-limits"),
+                (Severity::Info, "no-liveness-probe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_workload_skips_probes_for_jobs() {
+        let workload: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+kind: Job
+spec:
+  template:
+    spec:
+      containers:
+      - name: main
+        image: europe-west1-docker.pkg.dev/acme/apps/migrate:v1.2.0
+        resources:
+          limits:
+            cpu: 500m
+            memory: 256Mi
+"#,
+        )
+        .unwrap();
+
+        assert!(lint_workload(&workload).is_empty());
+    }
+}
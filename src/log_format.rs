@@ -0,0 +1,172 @@
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::LogFields;
+
+/// Level fields tried when none are configured; GKE uses `severity`.
+const DEFAULT_LEVEL_FIELDS: [&str; 2] = ["severity", "level"];
+const DEFAULT_MESSAGE_FIELDS: [&str; 4] = ["message", "msg", "textPayload", "fields.message"];
+const DEFAULT_TIMESTAMP_FIELDS: [&str; 3] = ["timestamp", "time", "ts"];
+
+/// Lines of Java and Python stack traces following the line that logged them: indented
+/// frames, `Caused by:`/`Suppressed:`, `... 12 more`, `Traceback (most recent call
+/// last):` and exception headers such as `java.lang.IllegalStateException: boom`.
+const STACK_TRACE_LINE: &str = r"^(?:\s+\S|Caused by:|Suppressed:|\.\.\. \d+ more|Traceback \(most recent call last\):|[\w.$]+(?:Error|Exception|Throwable)(?::|$))";
+
+/// Level, timestamp and message of a structured log line.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StructuredLine {
+    pub level: Option<String>,
+    pub timestamp: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Parses a JSON or logfmt log line with the configured fields; `None` for plain text
+/// and for lines holding none of them.
+pub fn parse(line: &str, fields: &LogFields) -> Option<StructuredLine> {
+    let parsed = if line.starts_with('{') {
+        let value = serde_json::from_str::<Value>(line).ok()?;
+        let field = |names: &[String]| {
+            names.iter().find_map(|name| {
+                match name.split('.').try_fold(&value, |v, key| v.get(key))? {
+                    Value::String(s) => Some(s.clone()),
+                    Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                }
+            })
+        };
+        structured(fields, field)
+    } else {
+        let pairs = logfmt_pairs(line);
+        let field = |names: &[String]| {
+            names.iter().find_map(|name| {
+                pairs
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.clone())
+            })
+        };
+        structured(fields, field)
+    };
+    (parsed != StructuredLine::default()).then_some(parsed)
+}
+
+/// Regex of the raw (untrimmed) log lines continuing the previous entry of their
+/// stream: stack trace lines, and those matching the configured pattern.
+pub fn continuation_regex(fields: &LogFields) -> Regex {
+    let pattern = match &fields.continuation_pattern {
+        Some(custom) => format!("{}|(?:{})", STACK_TRACE_LINE, custom),
+        None => STACK_TRACE_LINE.to_string(),
+    };
+    Regex::new(&pattern).unwrap_or_else(|_| Regex::new(STACK_TRACE_LINE).unwrap())
+}
+
+/// The first of the configured fields, or of the default ones, found by `field`.
+fn structured(fields: &LogFields, field: impl Fn(&[String]) -> Option<String>) -> StructuredLine {
+    let lookup = |configured: &Option<Vec<String>>, defaults: &[&str]| match configured {
+        Some(names) => field(names),
+        None => field(&defaults.iter().map(|n| n.to_string()).collect::<Vec<_>>()),
+    };
+    StructuredLine {
+        level: lookup(&fields.level_fields, &DEFAULT_LEVEL_FIELDS).map(|l| l.to_uppercase()),
+        timestamp: lookup(&fields.timestamp_fields, &DEFAULT_TIMESTAMP_FIELDS),
+        message: lookup(&fields.message_fields, &DEFAULT_MESSAGE_FIELDS),
+    }
+}
+
+/// The `key=value` pairs of a logfmt line; values may be double-quoted with `\`
+/// escapes. Bare words are skipped, so that plain text yields no pairs.
+fn logfmt_pairs(line: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return pairs;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            continue;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => match chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some(escaped) => value.push(escaped),
+                        None => {}
+                    },
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+        pairs.push((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reads_logfmt_and_custom_json_fields() {
+        let logfmt = parse(
+            r#"ts=2024-05-01T10:00:00Z level=warn msg="slow query \"users\"" duration=1.2s"#,
+            &LogFields::default(),
+        );
+        assert_eq!(
+            logfmt,
+            Some(StructuredLine {
+                level: Some("WARN".to_string()),
+                timestamp: Some("2024-05-01T10:00:00Z".to_string()),
+                message: Some("slow query \"users\"".to_string()),
+            })
+        );
+
+        let custom = LogFields {
+            level_fields: Some(vec!["log.level".to_string()]),
+            message_fields: Some(vec!["event.text".to_string()]),
+            ..LogFields::default()
+        };
+        let json = r#"{"log":{"level":"error"},"event":{"text":"boom"},"level":"info"}"#;
+        let parsed = parse(json, &custom).unwrap();
+        assert_eq!(parsed.level.as_deref(), Some("ERROR"));
+        assert_eq!(parsed.message.as_deref(), Some("boom"));
+
+        assert_eq!(parse("Listening on :8080", &LogFields::default()), None);
+    }
+
+    #[test]
+    fn test_continuation_regex_recognizes_stack_trace_lines() {
+        let continuation = continuation_regex(&LogFields::default());
+        for line in [
+            "java.lang.IllegalStateException: pool exhausted",
+            "\tat com.acme.Pool.take(Pool.java:42)",
+            "Caused by: java.io.IOException: reset",
+            "\t... 12 more",
+            "Traceback (most recent call last):",
+            "  File \"app.py\", line 3, in <module>",
+            "ValueError: invalid literal",
+        ] {
+            assert!(continuation.is_match(line), "{}", line);
+        }
+        for line in ["Started in 2.1s", "Exception in thread \"main\" boom", ""] {
+            assert!(!continuation.is_match(line), "{}", line);
+        }
+        let custom = continuation_regex(&LogFields {
+            continuation_pattern: Some(r"^\| ".to_string()),
+            ..LogFields::default()
+        });
+        assert!(custom.is_match("| row 2"));
+    }
+}
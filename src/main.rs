@@ -1,15 +1,75 @@
+mod audit;
+mod blame;
 mod blueprint;
+mod bundle;
+mod clipboard;
+mod cluster;
+mod commit_message;
+mod compatibility;
+mod completions;
 mod config;
+mod config_check;
+mod contexts;
 mod dashboard;
+mod deploy;
+mod deploy_lock;
+mod diagnose;
+mod discovery_cache;
+mod doctor;
+mod drift;
+mod freeze;
 mod git;
+mod health;
+mod helm;
+mod history;
+mod hooks;
+mod image_pull;
 mod info;
+mod interactive;
+mod keymap;
+mod lint;
+mod log_format;
+mod mock;
+mod notify;
+mod oci;
+mod offline;
+mod output;
+mod pin;
+mod policy;
+mod prepull;
+mod progressive;
+mod prometheus;
+mod promotion;
+mod protection;
+mod rbac;
 mod registry;
+mod rego;
+mod release;
+mod release_notes;
+mod resources;
+mod retry;
+mod scaffold;
+mod scale;
+mod schedule;
+mod sealed_secrets;
+mod secrets;
+mod signing;
+mod stats;
+mod status;
+mod theme;
+mod traffic;
+mod verify;
 
 use anyhow::{Context, Result};
-use blueprint::Blueprint;
-use chrono::Utc;
-use clap::{Parser, Subcommand};
-use config::{Config, Environment, ServiceSource, YamlSource};
+use blueprint::{Blueprint, parse_document, split_image_ref};
+use bundle::FailedDeploy;
+use chrono::{DateTime, Local, Utc};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, CompleteEnv};
+use config::{
+    ApplyScope, Config, ContainerImage, DeployAnnotations, Environment, GroupedImage,
+    ProgressiveDelivery, ServiceSource, YamlSource,
+};
 use crossterm::{
     cursor::MoveToColumn,
     event::{self, Event, KeyCode, KeyEventKind},
@@ -17,11 +77,16 @@ use crossterm::{
 };
 use dashboard::{Dashboard, DashboardExit};
 use git::Git;
-use inquire::{Confirm, Select, Text};
+use history::{DeployKind, DeployRecord, History, Outcome, PhaseDurations};
+use inquire::{Confirm, MultiSelect, Select, Text};
+use progressive::Canary;
 use registry::{ImageMetadata, Registry};
-use std::collections::HashSet;
+use release_notes::ReleaseNotes;
+use resources::WorkloadResources;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
 use std::thread;
@@ -31,11 +96,37 @@ const MAX_PARALLEL_PULLS: usize = 5;
 const TAG_RETRY_INTERVAL: Duration = Duration::from_secs(60);
 const TAG_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
 const TAG_WAIT_CANCELLED_MESSAGE: &str = "__TAG_WAIT_CANCELLED__";
+const HEADLESS_ROLLOUT_TIMEOUT: Duration = Duration::from_secs(600);
 
 #[derive(Parser)]
 #[command(name = "davit")]
 #[command(about = "A safe Kubernetes deployment wrapper & TUI", long_about = None)]
 struct Cli {
+    /// Never prompt: take default answers, fail when a required argument is missing,
+    /// apply without review and follow rollouts without the dashboard (for CI)
+    #[arg(long, short = 'y', alias = "yes", global = true)]
+    non_interactive: bool,
+
+    /// Confirm changes to a protected environment, or to protected services in it, by
+    /// naming the environment instead of typing it; required in non-interactive mode
+    #[arg(long, global = true, value_name = "ENV")]
+    confirm_protected: Option<String>,
+
+    /// Format of command results; `json` prints machine-readable output on stdout and
+    /// progress messages on stderr
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    output: output::OutputFormat,
+
+    /// Colors of the dashboard and diffs; overrides `defaults.theme`, and `NO_COLOR`
+    /// which selects `monochrome`
+    #[arg(long, global = true, value_enum)]
+    theme: Option<theme::ThemeName>,
+
+    /// Append the log lines streamed by the dashboard to this file, with their timestamp,
+    /// pod and old/new marker, for post-mortem analysis
+    #[arg(long, global = true, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -45,17 +136,26 @@ enum Commands {
     /// Deploy a service to an environment
     Deploy {
         /// Target environment (e.g., staging, production)
-        #[arg(short, long)]
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
         env: Option<String>,
 
         /// Service name to deploy
-        #[arg(short, long)]
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
         service: Option<String>,
 
         /// Image tag to deploy
         #[arg(short, long)]
         tag: Option<String>,
 
+        /// Deploy every service of a release file mapping services to tags (with an optional
+        /// `after` list per service), reviewed together and committed as a single release
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["service", "tag", "wait_for_tag", "progressive", "at", "pin_digest"]
+        )]
+        manifest: Option<PathBuf>,
+
         /// Wait until the provided image tag appears in the registry instead of prompting for similar tags
         #[arg(long, conflicts_with = "tag", value_name = "TAG")]
         wait_for_tag: Option<String>,
@@ -71,11 +171,196 @@ enum Commands {
         /// After `kubectl apply`, continue automatically through rollout completion and Git push unless errors occur
         #[arg(long)]
         auto_continue: bool,
+
+        /// Pull the new image onto the workload's nodes before applying, to shorten the rollout
+        #[arg(long)]
+        prepull: bool,
+
+        /// Run the new version as a canary and shift traffic to it in steps from the dashboard
+        #[arg(long, conflicts_with_all = ["auto_apply", "auto_continue"])]
+        progressive: bool,
+
+        /// Review the deploy now and apply it at this time (e.g. 2024-06-01T02:00Z, 22:30 or a
+        /// cron expression such as "0 2 * * 1-5"), continuing automatically after the apply
+        #[arg(long, value_name = "TIME", conflicts_with = "progressive")]
+        at: Option<String>,
+
+        /// Open the rollout dashboard even in non-interactive mode
+        #[arg(long)]
+        dashboard: bool,
+
+        /// Write the digest the tag points at (`repo@sha256:...`) instead of the mutable tag
+        #[arg(long)]
+        pin_digest: bool,
+
+        /// Name or initials of a person approving the deploy of a protected service (repeatable)
+        #[arg(long, value_name = "NAME")]
+        approved_by: Vec<String>,
+
+        /// Deploy during a freeze window of the environment; the justification is prompted
+        /// for when not given, and recorded in the commit message and deploy history
+        #[arg(long, value_name = "JUSTIFICATION", num_args = 0..=1, default_missing_value = "")]
+        override_freeze: Option<String>,
+
+        /// Ticket ID of the deploy (e.g. a Jira key), filling `{ticket}` in the commit message
+        /// template and the environment's ticket trailer instead of prompting for it
+        #[arg(long, value_name = "ID", conflicts_with = "manifest")]
+        ticket: Option<String>,
+    },
+    /// Deploy the tag of a pipeline stage to the next one, once the configured gates pass
+    Promote {
+        /// Service to promote
+        #[arg(add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+
+        /// Stage to promote into; defaults to the first one behind the previous stage
+        #[arg(long, add = ArgValueCandidates::new(completions::environments))]
+        to: Option<String>,
+
+        /// Dry run: show commands without executing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Deploy during a freeze window of the environment; the justification is prompted
+        /// for when not given, and recorded in the commit message and deploy history
+        #[arg(long, value_name = "JUSTIFICATION", num_args = 0..=1, default_missing_value = "")]
+        override_freeze: Option<String>,
+    },
+    /// Deploy the tag the current one replaced, found in the deploy history or the Git
+    /// history of the service's YAML
+    Rollback {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Service to roll back
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+
+        /// Dry run: show commands without executing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// After `kubectl apply`, continue automatically through rollout completion and Git push unless errors occur
+        #[arg(long)]
+        auto_continue: bool,
+
+        /// Deploy during a freeze window of the environment; the justification is prompted
+        /// for when not given, and recorded in the commit message and deploy history
+        #[arg(long, value_name = "JUSTIFICATION", num_args = 0..=1, default_missing_value = "")]
+        override_freeze: Option<String>,
+    },
+    /// Show the tag of every service per environment, with its age in the registry
+    Status {
+        /// Only this environment; all of them by default
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Also read the running tags from the cluster and flag those differing from the YAML
+        #[arg(long)]
+        live: bool,
+    },
+    /// List the services of an environment with their current tag
+    Services {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+    },
+    /// List the image tags available in the registry for a service
+    Tags {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Service name
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
     },
     /// Show deployment information for a service
     Info {
         /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Kubernetes namespace filter
+        #[arg(short, long)]
+        namespace: Option<String>,
+
+        /// Service name to inspect
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+    },
+    /// Stream the logs of a service's pods in the dashboard, without deploying
+    Logs {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Kubernetes namespace filter
+        #[arg(short, long)]
+        namespace: Option<String>,
+
+        /// Service whose logs to stream
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+    },
+    /// Restart the pods of a service without changing its tag (`kubectl rollout restart`)
+    /// and watch them cycle in the dashboard
+    Restart {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Kubernetes namespace filter
+        #[arg(short, long)]
+        namespace: Option<String>,
+
+        /// Service to restart
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+
+        /// Name or initials of a person approving the restart of a protected service (repeatable)
+        #[arg(long, value_name = "NAME")]
+        approved_by: Vec<String>,
+
+        /// Dry run: show commands without executing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Change the replica count of a service, in its YAML (applied and committed) or live
+    Scale {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Kubernetes namespace filter
+        #[arg(short, long)]
+        namespace: Option<String>,
+
+        /// Service to scale
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+
+        /// Number of replicas
         #[arg(short, long)]
+        replicas: i32,
+
+        /// Only scale the live workload (`kubectl scale`), leaving the YAML unchanged
+        #[arg(long)]
+        live: bool,
+
+        /// Name or initials of a person approving the scaling of a protected service (repeatable)
+        #[arg(long, value_name = "NAME")]
+        approved_by: Vec<String>,
+
+        /// Dry run: show commands without executing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show who set the currently deployed tag of a service, when and in which commit
+    Blame {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
         env: Option<String>,
 
         /// Kubernetes namespace filter
@@ -83,14 +368,213 @@ enum Commands {
         namespace: Option<String>,
 
         /// Service name to inspect
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+    },
+    /// Compare the workloads in the cluster with the YAML in the repository and report drift
+    Diff {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Kubernetes namespace filter
+        #[arg(short, long)]
+        namespace: Option<String>,
+
+        /// Only this service; every service of the environment by default
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+    },
+    /// Summarize recorded deploys per service: outcomes, rollbacks and median duration
+    Stats {
+        /// Only deploys to this environment
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Only deploys within this period (e.g. 30d, 12h, 4w)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "table")]
+        format: StatsFormat,
+    },
+    /// Report DORA metrics per environment from recorded deploys
+    Dora {
+        /// Reporting period ending now (e.g. 90d, 12w)
+        #[arg(long, default_value = "90d")]
+        since: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: ReportFormat,
+    },
+    /// Rewrite every service of an environment to digest-pinned image references
+    Pin {
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Dry run: show commands without executing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Block deploys of a service to an environment, e.g. during an incident freeze
+    Lock {
+        /// Service to lock
+        #[arg(add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Why deploys are blocked, shown to anyone attempting one
         #[arg(short, long)]
+        reason: Option<String>,
+    },
+    /// Allow deploys of a locked service again
+    Unlock {
+        /// Service to unlock
+        #[arg(add = ArgValueCandidates::new(completions::services))]
         service: Option<String>,
+
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+    },
+    /// Generate davit-ready manifests
+    Scaffold {
+        #[command(subcommand)]
+        command: ScaffoldCommands,
+    },
+    /// List the kubectl context of each environment and check that it exists locally
+    Contexts,
+    /// Check this machine is ready to deploy: kubectl, gcloud and git on the PATH, and
+    /// for each environment its kubectl context, RBAC, YAML checkouts and registry
+    Doctor,
+    /// Registry listings for offline environments
+    Registry {
+        #[command(subcommand)]
+        command: RegistryCommands,
+    },
+    /// Print the shell script enabling completion of davit commands, environments and
+    /// services, e.g. `source <(davit completions bash)` in ~/.bashrc
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
     },
     /// Configuration management
     Config {
         #[command(subcommand)]
         command: ConfigCommands,
     },
+    /// Deployment history
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommands>,
+
+        /// Only deploys to this environment
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Only deploys of this service
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+
+        /// Only deploys within this period (e.g. 7d, 12h, 4w)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Number of deploys to show, newest first
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScaffoldCommands {
+    /// Write a Deployment (and a Service when --port is set) for a new service into the environment's YAML directory
+    Service {
+        /// Service name, used for the workload, its container and the `app` label
+        name: String,
+
+        /// Container image with its initial tag
+        #[arg(long)]
+        image: String,
+
+        /// Target environment (e.g., staging, production)
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Kubernetes namespace of the manifests
+        #[arg(short, long)]
+        namespace: Option<String>,
+
+        /// Container port, exposed through a Service
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Initial replica count
+        #[arg(long, default_value_t = 1)]
+        replicas: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommands {
+    /// Export recorded deploys as a flat CSV or JSON file
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+
+        /// Only deploys within this period (e.g. 90d, 12h, 4w)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RegistryCommands {
+    /// Save the registry listings of an environment's images to a file, read by offline
+    /// environments instead of the registry
+    Export {
+        /// Environment whose services are exported
+        #[arg(short, long, add = ArgValueCandidates::new(completions::environments))]
+        env: Option<String>,
+
+        /// Only export this service's images
+        #[arg(short, long, add = ArgValueCandidates::new(completions::services))]
+        service: Option<String>,
+
+        /// File to write, referenced by `offline.images`
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum StatsFormat {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ReportFormat {
+    Markdown,
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -99,298 +583,2454 @@ enum ConfigCommands {
     Show,
     /// Get path to configuration file
     Path,
+    /// Create a starter configuration file from the local kubeconfig contexts
+    Init {
+        /// Overwrite an existing configuration file without asking
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the configuration file: unknown keys, missing fields, missing YAML
+    /// directories, duplicate environments and unreachable kubectl contexts
+    Validate {
+        /// Skip the kubeconfig and cluster reachability checks
+        #[arg(long)]
+        skip_contexts: bool,
+    },
+    /// Copy an existing environment block into a new environment
+    CloneEnv {
+        /// Environment to copy
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Name of the new environment
+        #[arg(long)]
+        name: Option<String>,
+
+        /// kubectl context of the new environment
+        #[arg(long)]
+        context: Option<String>,
+
+        /// YAML directory of the new environment
+        #[arg(long)]
+        env_yaml_dir: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    CompleteEnv::with_factory(Cli::command)
+        .var(completions::COMPLETE_VAR)
+        .complete();
     let cli = Cli::parse();
-    let config = Config::load().context("Failed to load configuration")?;
+    if let Commands::Completions { shell } = cli.command {
+        return completions::write_registration(shell);
+    }
+    // These read or write the file themselves: the config may not load, or not exist yet.
+    if let Commands::Config { command } = &cli.command {
+        match command {
+            ConfigCommands::Validate { skip_contexts } => {
+                output::configure(cli.output);
+                theme::configure(theme::resolve(cli.theme, Default::default()));
+                return validate_config(*skip_contexts).await;
+            }
+            ConfigCommands::Init { force } => {
+                interactive::configure(!cli.non_interactive);
+                theme::configure(theme::resolve(cli.theme, Default::default()));
+                return init_config(*force);
+            }
+            _ => {}
+        }
+    }
+    let config = if mock::enabled() {
+        mock::config().context("Failed to prepare demo configuration")
+    } else {
+        Config::load().context("Failed to load configuration")
+    };
+    // The report covers a missing or broken config too.
+    if let Commands::Doctor = cli.command {
+        output::configure(cli.output);
+        let configured = config.as_ref().map(|c| c.defaults.theme);
+        theme::configure(theme::resolve(cli.theme, configured.unwrap_or_default()));
+        if let Ok(config) = &config {
+            retry::configure(config.retry);
+            signing::configure(config.signing.clone());
+        }
+        return run_doctor(config).await;
+    }
+    let config = config?;
+    retry::configure(config.retry);
+    audit::configure(config.audit_log.clone());
+    signing::configure(config.signing.clone());
+    interactive::configure(!cli.non_interactive && config.defaults.interactive);
+    output::configure(cli.output);
+    theme::configure(theme::resolve(cli.theme, config.defaults.theme));
+    if let Some(path) = cli.log_file {
+        dashboard::write_logs_to(path);
+    }
+
+    let confirm_protected = cli.confirm_protected;
+    let command = match cli.command {
+        Commands::Promote {
+            service,
+            to,
+            dry_run,
+            override_freeze,
+        } => promote(&config, service, to.as_deref(), dry_run, override_freeze).await?,
+        Commands::Rollback {
+            env,
+            service,
+            dry_run,
+            auto_continue,
+            override_freeze,
+        } => rollback(
+            &config,
+            env,
+            service,
+            dry_run,
+            auto_continue,
+            override_freeze,
+        )?,
+        command => command,
+    };
 
-    match cli.command {
+    match command {
         Commands::Deploy {
             env,
             service,
             tag,
+            manifest,
             wait_for_tag,
             dry_run,
             auto_apply,
             auto_continue,
+            prepull,
+            progressive,
+            at,
+            dashboard,
+            pin_digest,
+            approved_by,
+            override_freeze,
+            ticket,
+        } => {
+            deploy::run(
+                &config,
+                deploy::DeployArgs {
+                    env,
+                    service,
+                    tag,
+                    manifest,
+                    wait_for_tag,
+                    dry_run,
+                    auto_apply,
+                    auto_continue,
+                    prepull,
+                    progressive,
+                    at,
+                    dashboard,
+                    pin_digest,
+                    approved_by,
+                    override_freeze,
+                    ticket,
+                    confirm_protected,
+                },
+            )
+            .await?
+        }
+        Commands::Info {
+            env,
+            namespace,
+            service,
         } => {
-            let auto_continue = auto_continue || auto_apply;
             let selected_env = resolve_environment(&config, env)?;
 
-            pull_yaml_sources(&selected_env, dry_run, "deployment")?;
-
-            let selected_service = resolve_service(&selected_env, service)?;
-
-            let selected_tag =
-                match resolve_tag(&selected_env, &selected_service, tag, wait_for_tag) {
-                    Ok(tag) => tag,
-                    Err(err) if err.to_string() == TAG_WAIT_CANCELLED_MESSAGE => {
-                        println!("Tag wait cancelled. Deployment aborted.");
-                        return Ok(());
-                    }
-                    Err(err) => return Err(err),
-                };
-
-            // 6.3 Production Protection
-            if selected_env.protected.unwrap_or(false) {
-                println!(
-                    "⚠️  WARNING: Deployment to {} is PROTECTED!",
-                    selected_env.name
-                );
-                let confirmation = Text::new(&format!(
-                    "Type the environment name '{}' to confirm:",
-                    selected_env.name
-                ))
-                .prompt()
-                .context("Production confirmation was cancelled")?;
+            pull_yaml_sources(&selected_env, mock::enabled(), "info")?;
 
-                if confirmation != selected_env.name {
-                    return Err(anyhow::anyhow!("Confirmation failed. Deployment aborted."));
-                }
+            let selected_service =
+                resolve_service_with_ns_filter(&selected_env, service, namespace)?;
+            info::show_info(&selected_env, &selected_service).await?;
+        }
+        Commands::Logs {
+            env,
+            namespace,
+            service,
+        } => {
+            if !interactive::enabled() {
+                return Err(anyhow::anyhow!(
+                    "davit logs streams into the dashboard and needs an interactive session"
+                ));
             }
+            let selected_env = resolve_environment(&config, env)?;
+            pull_yaml_sources(&selected_env, mock::enabled(), "logs")?;
+            let selected_service =
+                resolve_service_with_ns_filter(&selected_env, service, namespace)?;
+            let (_, tag) = split_image_ref(&selected_service.image_path);
 
-            // Phase 4 - YAML modification & Visual Diff
-            let yaml_path = selected_service.yaml_path.clone();
-
-            let original_content = fs::read_to_string(&yaml_path)
-                .with_context(|| format!("Failed to read YAML file at {}", yaml_path.display()))?;
-
-            let base_image = selected_service
-                .image_path
-                .split([':', '@'])
-                .next()
-                .unwrap_or(&selected_service.image_path);
-
-            let updated_content =
-                Blueprint::update_image_tag(&original_content, base_image, &selected_tag)
-                    .context("Failed to update image tag in YAML")?;
-
-            let mut show_unified = true;
-            let filename = yaml_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("deployment.yaml");
-
-            loop {
-                Blueprint::show_diff(&original_content, &updated_content, filename, show_unified);
-
-                if auto_apply {
-                    if dry_run {
-                        println!(
-                            "Dry-run: would write updated YAML to {}",
-                            yaml_path.display()
-                        );
-                    } else {
-                        fs::write(&yaml_path, &updated_content).with_context(|| {
-                            format!("Failed to write updated YAML to {}", yaml_path.display())
-                        })?;
-                    }
-                    println!("Auto-apply enabled. Local YAML updated. Executing kubectl apply...");
-                    break;
-                }
-
-                let choices = if show_unified {
-                    vec!["Apply", "Show full diff", "Dismiss"]
-                } else {
-                    vec!["Apply", "Show unified diff", "Dismiss"]
-                };
-
-                let selection = Select::new("Action:", choices).prompt()?;
-
-                match selection {
-                    "Apply" => {
-                        if dry_run {
-                            println!(
-                                "Dry-run: would write updated YAML to {}",
-                                yaml_path.display()
-                            );
-                        } else {
-                            fs::write(&yaml_path, &updated_content).with_context(|| {
-                                format!("Failed to write updated YAML to {}", yaml_path.display())
-                            })?;
-                        }
-                        println!("Local YAML updated. Executing kubectl apply...");
-                        break;
-                    }
-                    "Show full diff" => show_unified = false,
-                    "Show unified diff" => show_unified = true,
-                    _ => {
-                        println!("Deployment cancelled. No changes made.");
-                        return Ok(());
-                    }
-                }
+            let mut dashboard = Dashboard::new(
+                selected_service.name.clone(),
+                selected_service.kind.clone(),
+                selected_env.name.clone(),
+                tag.unwrap_or("-").to_string(),
+                selected_env.kubectl_context.clone(),
+                selected_service.namespace.clone(),
+                selected_service.selector.clone(),
+                selected_service.container_name.clone(),
+                false,
+                config.dashboard.clone(),
+                None,
+                None,
+                Vec::new(),
+            );
+            dashboard.parse_logs_with(config.log_fields(&selected_service.name));
+            dashboard.logs_only();
+            let res = dashboard.run().await;
+            if dashboard.layout() != config.dashboard
+                && let Err(e) = save_dashboard_layout(&dashboard.layout())
+            {
+                println!("⚠️  Could not save the dashboard layout: {}", e);
             }
+            res?;
+        }
+        Commands::Restart {
+            env,
+            namespace,
+            service,
+            approved_by,
+            dry_run,
+        } => {
+            let selected_env = resolve_environment(&config, env)?;
+            pull_yaml_sources(&selected_env, dry_run || mock::enabled(), "restart")?;
+            let selected_service =
+                resolve_service_with_ns_filter(&selected_env, service, namespace)?;
+            selected_env.ensure_unlocked(&selected_service.name)?;
+            let namespace = selected_service.namespace.as_deref().unwrap_or("default");
+            let (_, tag) = split_image_ref(&selected_service.image_path);
+            let tag = tag.unwrap_or("-");
 
             if dry_run {
                 println!(
-                    "Dry-run: kubectl --context {} apply -f {}",
+                    "Dry-run: kubectl --context {} {}",
                     selected_env.kubectl_context,
-                    yaml_path.display()
+                    cluster::rollout_restart_args(
+                        &selected_service.kind,
+                        namespace,
+                        &selected_service.name
+                    )
+                    .join(" ")
+                );
+                return Ok(());
+            }
+            if !confirm_restart(
+                &config,
+                &selected_env,
+                (&selected_service.name, tag),
+                confirm_protected.as_deref(),
+                &approved_by,
+            )? {
+                println!("Restart cancelled.");
+                return Ok(());
+            }
+            if mock::enabled() {
+                // The simulated cluster replaces the pods like a rollout to the same image.
+                mock::apply(
+                    &selected_env.kubectl_context,
+                    &selected_service.name,
+                    &selected_service.image_path,
+                    &selected_service.image_path,
                 );
             } else {
-                let output = Command::new("kubectl")
-                    .args([
-                        "--context",
-                        &selected_env.kubectl_context,
-                        "apply",
-                        "-f",
-                        yaml_path.to_str().unwrap(),
-                    ])
-                    .output()
-                    .context("Failed to execute kubectl apply")?;
-
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    println!("❌ kubectl apply failed: {}", stderr);
-                    if !auto_continue {
-                        if Confirm::new("Revert local YAML changes?")
-                            .with_default(true)
-                            .prompt()?
-                        {
-                            fs::write(&yaml_path, &original_content)?;
-                            println!("YAML reverted.");
-                        }
-                    }
-                    return Err(anyhow::anyhow!("kubectl apply failed"));
-                }
+                cluster::rollout_restart(
+                    &selected_env.kubectl_context,
+                    &selected_service.kind,
+                    namespace,
+                    &selected_service.name,
+                )
+                .context("Failed to restart the workload")?;
             }
-
-            println!("Deployment applied. Starting dashboard...");
-
+            audit::AuditEntry::new(
+                audit::Action::Restarted,
+                &selected_env.name,
+                &selected_service.name,
+            )
+            .tag(tag)
+            .record();
+
+            if !interactive::enabled() {
+                println!("Restart requested. Waiting for the rollout...");
+                wait_for_rollout(&selected_env, &selected_service)?;
+                println!("✅ {} restarted.", selected_service.name);
+                return Ok(());
+            }
+            println!("Restart requested. Starting dashboard...");
             let mut dashboard = Dashboard::new(
                 selected_service.name.clone(),
                 selected_service.kind.clone(),
                 selected_env.name.clone(),
-                selected_tag.clone(),
+                tag.to_string(),
                 selected_env.kubectl_context.clone(),
                 selected_service.namespace.clone(),
                 selected_service.selector.clone(),
                 selected_service.container_name.clone(),
-                auto_continue,
+                false,
+                config.dashboard.clone(),
+                config.service(&selected_service.name).health,
+                selected_env.prometheus_url.clone(),
+                config.service(&selected_service.name).metrics,
             );
+            dashboard.parse_logs_with(config.log_fields(&selected_service.name));
             let res = dashboard.run().await;
-
-            match res {
-                Err(e) => {
-                    println!("❌ Dashboard error or aborted: {}", e);
-                    if !auto_continue {
-                        if Confirm::new("Revert local YAML changes?")
-                            .with_default(true)
-                            .prompt()?
-                        {
-                            fs::write(&yaml_path, &original_content)?;
-                            println!("YAML reverted.");
-                        }
-                    }
-                    return Err(e);
+            if dashboard.layout() != config.dashboard
+                && let Err(e) = save_dashboard_layout(&dashboard.layout())
+            {
+                println!("⚠️  Could not save the dashboard layout: {}", e);
+            }
+            match res? {
+                DashboardExit::RolloutFailed(reason) => {
+                    return Err(anyhow::anyhow!("Restart failed: {}", reason));
                 }
-                Ok(DashboardExit::UserQuit) => {
-                    if auto_continue {
-                        return Err(anyhow::anyhow!(
-                            "Dashboard closed before rollout completion in auto-continue mode"
-                        ));
-                    }
-                    println!("Dashboard closed before rollout completion check.");
+                DashboardExit::RolloutCompleted => {
+                    println!("✅ {} restarted.", selected_service.name)
                 }
-                Ok(DashboardExit::RolloutCompleted) => {
-                    println!("Rollout completed. Continuing to the Git step...");
+                _ => println!("Dashboard closed; the restart continues in the cluster."),
+            }
+        }
+        Commands::Scale {
+            env,
+            namespace,
+            service,
+            replicas,
+            live,
+            approved_by,
+            dry_run,
+        } => {
+            if replicas < 0 {
+                return Err(anyhow::anyhow!("--replicas cannot be negative"));
+            }
+            let selected_env = resolve_environment(&config, env)?;
+            let git_dry_run = dry_run || mock::enabled();
+            pull_yaml_sources(&selected_env, git_dry_run, "scaling")?;
+            let selected_service =
+                resolve_service_with_ns_filter(&selected_env, service, namespace)?;
+            selected_env.ensure_unlocked(&selected_service.name)?;
+            let namespace = selected_service.namespace.as_deref().unwrap_or("default");
+            let live = live || !selected_service.is_file_backed();
+            if !live {
+                selected_service.ensure_editable()?;
+                check_commit_signing(&selected_env, git_dry_run)?;
+            }
+            if !live
+                && (selected_service.kustomize_overlay.is_some()
+                    || selected_service.helm_chart.is_some())
+            {
+                return Err(anyhow::anyhow!(
+                    "The replicas of {} are set in its kustomize overlay or Helm values: edit them there, or pass --live",
+                    selected_service.name
+                ));
+            }
+            let tag = split_image_ref(&selected_service.image_path)
+                .1
+                .unwrap_or("-")
+                .to_string();
+
+            let original_content = match live {
+                true => None,
+                false => Some(
+                    fs::read_to_string(&selected_service.yaml_path).with_context(|| {
+                        format!(
+                            "Failed to read YAML file at {}",
+                            selected_service.yaml_path.display()
+                        )
+                    })?,
+                ),
+            };
+            let workload = match &original_content {
+                Some(content) => parse_document(content, selected_service.document_index),
+                None if mock::enabled() => selected_service.workload_document(),
+                None => cluster::live_manifest(
+                    &selected_env.kubectl_context,
+                    &selected_service.kind,
+                    namespace,
+                    &selected_service.name,
+                )
+                .ok()
+                .and_then(|m| serde_yaml::from_str(&m).ok()),
+            };
+            let current = workload
+                .as_ref()
+                .and_then(|w| w.get("spec")?.get("replicas")?.as_i64())
+                .map_or("?".to_string(), |r| r.to_string());
+            println!(
+                "{} in {}: {} → {} replicas{}",
+                selected_service.name,
+                selected_env.name,
+                current,
+                replicas,
+                if live { " (live only)" } else { "" }
+            );
+            match scale::guards(&selected_env, &selected_service).await {
+                Ok(guards) => {
+                    for warning in guards.warnings(replicas) {
+                        println!("⚠️  {}", warning);
+                    }
                 }
+                Err(e) => println!(
+                    "⚠️  Could not check the autoscaler and disruption budgets: {:#}",
+                    e
+                ),
             }
 
-            // 6.1 Git Automation
-            println!("\n🚀 Deployment successful. Preparing to commit changes...");
-            let commit_msg = format!(
-                "deploy({}): update {} to {}",
-                selected_env.name, selected_service.name, selected_tag
-            );
+            let updated_content = original_content
+                .as_deref()
+                .map(|content| {
+                    Blueprint::update_replicas_in_document(
+                        content,
+                        selected_service.document_index,
+                        replicas,
+                    )
+                })
+                .transpose()?;
+            let filename = get_service_source_display_path(&selected_service);
+            if let (Some(original), Some(updated)) = (&original_content, &updated_content) {
+                Blueprint::show_diff(original, updated, &filename, true);
+            }
 
-            println!("\n--- Commit Recap ---");
-            println!("File to commit:   {}", yaml_path.display());
-            println!("Commit message:   {}", commit_msg);
-            Blueprint::show_diff(&original_content, &updated_content, filename, true);
-            println!("--------------------\n");
+            let trail = deploy::AuditTrail::new(
+                &selected_env.name,
+                vec![(&selected_service.name, &tag)],
+                dry_run,
+            );
+            let mut approvals = Vec::new();
+            if selected_env.protected.unwrap_or(false)
+                || protection::service_protection(
+                    &config,
+                    &selected_env.name,
+                    &selected_service.name,
+                )
+                .confirm
+            {
+                approvals = deploy::confirm_protection(
+                    &config,
+                    &selected_env,
+                    &trail,
+                    confirm_protected.as_deref(),
+                    &approved_by,
+                    "Scaling",
+                )?;
+            } else if interactive::enabled()
+                && !Confirm::new(&format!(
+                    "Scale {} to {} replicas?",
+                    selected_service.name, replicas
+                ))
+                .with_default(true)
+                .prompt()
+                .context("Confirmation was cancelled")?
+            {
+                println!("Scaling cancelled. No changes made.");
+                return Ok(());
+            }
 
-            if auto_continue {
-                Git::commit_and_push(
+            let sandbox = selected_env.dry_only.unwrap_or(false);
+            match &updated_content {
+                None if dry_run => println!(
+                    "Dry-run: kubectl --context {} scale {}/{} -n {} --replicas={}",
+                    selected_env.kubectl_context,
+                    selected_service.kind.to_lowercase(),
+                    selected_service.name,
+                    namespace,
+                    replicas
+                ),
+                None if mock::enabled() || sandbox => {
+                    println!("Skipping kubectl scale: the cluster is simulated or a sandbox.")
+                }
+                None => cluster::scale(
+                    &selected_env.kubectl_context,
+                    &selected_service.kind,
+                    namespace,
+                    &selected_service.name,
+                    replicas,
+                )
+                .context("Failed to scale the workload")?,
+                Some(updated) => {
+                    let yaml_path = &selected_service.yaml_path;
+                    if dry_run {
+                        println!(
+                            "Dry-run: would write updated YAML to {}",
+                            yaml_path.display()
+                        );
+                    } else {
+                        fs::write(yaml_path, updated).with_context(|| {
+                            format!("Failed to write updated YAML to {}", yaml_path.display())
+                        })?;
+                    }
+                    if let Err(e) =
+                        apply_service_file(&selected_env, &selected_service, &tag, dry_run, sandbox)
+                    {
+                        if !dry_run && let Some(original) = &original_content {
+                            revert_local_changes(yaml_path, original, &[])?;
+                            println!("YAML reverted.");
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+            if !dry_run {
+                audit::AuditEntry::new(
+                    audit::Action::Scaled,
+                    &selected_env.name,
+                    &selected_service.name,
+                )
+                .tag(&tag)
+                .detail(format!(
+                    "{} → {} replicas{}",
+                    current,
+                    replicas,
+                    if live { " (live)" } else { "" }
+                ))
+                .record();
+            }
+            if updated_content.is_some() {
+                let message = deploy::with_trailers(
+                    format!(
+                        "scale({}): set {} replicas to {}",
+                        selected_env.name, selected_service.name, replicas
+                    ),
+                    &approvals,
+                    None,
+                    Vec::new(),
+                );
+                deploy::commit(
+                    &selected_env,
                     &selected_service.source_root,
-                    &commit_msg,
-                    &yaml_path,
-                    dry_run,
+                    (&selected_service.name, &tag),
+                    &message,
+                    std::slice::from_ref(&selected_service.yaml_path),
+                    git_dry_run,
+                    &trail,
+                    &[0],
                 )?;
-                if !dry_run {
-                    println!("✅ Changes committed and pushed to Git.");
+            } else if !dry_run {
+                println!(
+                    "✅ {} scaled to {} replicas. The YAML is unchanged, so the next deploy resets it.",
+                    selected_service.name, replicas
+                );
+            }
+        }
+        Commands::Status { env, live } => {
+            let environments = match env {
+                Some(name) => vec![resolve_environment(&config, Some(name))?],
+                None => config.environments.clone(),
+            };
+            let mut statuses = Vec::new();
+            for env in &environments {
+                pull_yaml_sources(env, mock::enabled(), "status")?;
+                match status::collect(env, live).await {
+                    Ok(found) => statuses.extend(found),
+                    Err(e) => output::progress(format!("⚠️  Skipping {}: {:#}", env.name, e)),
                 }
+            }
+            if output::json() {
+                output::print_json(&statuses)?;
             } else {
-                if Confirm::new("Do you want to commit and push these changes?")
-                    .with_default(true)
-                    .prompt()?
-                {
-                    if let Err(e) = Git::commit_and_push(
-                        &selected_service.source_root,
-                        &commit_msg,
-                        &yaml_path,
-                        dry_run,
-                    ) {
-                        println!("⚠️  Failed to commit/push changes: {}", e);
-                    } else if !dry_run {
-                        println!("✅ Changes committed and pushed to Git.");
+                println!();
+                status::print_table(&statuses, live);
+            }
+        }
+        Commands::Services { env } => {
+            let selected_env = resolve_environment(&config, env)?;
+            pull_yaml_sources(&selected_env, mock::enabled(), "listing")?;
+            let services = selected_env.list_services()?;
+            if output::json() {
+                let entries: Vec<output::ServiceEntry> =
+                    services.iter().map(output::ServiceEntry::from).collect();
+                output::print_json(&entries)?;
+            } else {
+                output::print_services(&services);
+            }
+        }
+        Commands::Tags { env, service } => {
+            let selected_env = resolve_environment(&config, env)?;
+            pull_yaml_sources(&selected_env, mock::enabled(), "listing")?;
+            let selected_service = resolve_service(&selected_env, service)?;
+            let images = fetch_service_images(&selected_env, &selected_service, false).await?;
+            if output::json() {
+                let entries: Vec<output::TagEntry> =
+                    images.iter().map(output::TagEntry::from).collect();
+                output::print_json(&entries)?;
+            } else {
+                output::print_tags(&images);
+            }
+        }
+        Commands::Promote { .. } => unreachable!("promotions run as deploys"),
+        Commands::Rollback { .. } => unreachable!("rollbacks run as deploys"),
+        Commands::Blame {
+            env,
+            namespace,
+            service,
+        } => {
+            let selected_env = resolve_environment(&config, env)?;
+
+            pull_yaml_sources(&selected_env, mock::enabled(), "blame")?;
+
+            let selected_service =
+                resolve_service_with_ns_filter(&selected_env, service, namespace)?;
+            blame::show_blame(&selected_env, &selected_service)?;
+        }
+        Commands::Diff {
+            env,
+            namespace,
+            service,
+        } => {
+            let selected_env = resolve_environment(&config, env)?;
+            pull_yaml_sources(&selected_env, mock::enabled(), "diff")?;
+            let services = match service {
+                Some(_) => vec![resolve_service_with_ns_filter(
+                    &selected_env,
+                    service,
+                    namespace,
+                )?],
+                None => selected_env
+                    .list_services()
+                    .context("Failed to list services")?
+                    .into_iter()
+                    .filter(|s| {
+                        namespace.is_none() || s.namespace.as_deref() == namespace.as_deref()
+                    })
+                    .collect(),
+            };
+            let mut reports = Vec::new();
+            for service in services.iter().filter(|s| s.is_file_backed()) {
+                output::progress(format!("🔍 Comparing {}...", service.name));
+                reports.push(drift::service_drift(&selected_env, service));
+            }
+            if output::json() {
+                output::print_json(&reports)?;
+            } else {
+                println!();
+                drift::print_report(&reports);
+            }
+            let drifted = reports.iter().filter(|r| !r.drifts.is_empty()).count();
+            if drifted > 0 {
+                return Err(anyhow::anyhow!(
+                    "{} service(s) of {} drifted from the repository",
+                    drifted,
+                    selected_env.name
+                ));
+            }
+        }
+        Commands::Stats { env, since, format } => {
+            let cutoff = history_cutoff(since.as_deref())?;
+            let records = History::open_default()?.load()?;
+            let stats = stats::service_stats(&records, env.as_deref(), cutoff);
+            match format {
+                StatsFormat::Table if !output::json() => stats::print_table(&stats),
+                _ => println!("{}", stats::to_json(&stats)?),
+            }
+        }
+        Commands::Dora { since, format } => {
+            let end = Utc::now();
+            let start = end - history::parse_since(&since)?;
+            let records = History::open_default()?.load()?;
+            let metrics = stats::dora_metrics(&records, start, end);
+            match format {
+                ReportFormat::Markdown => {
+                    print!("{}", stats::dora_to_markdown(&metrics, start, end))
+                }
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&metrics)?),
+            }
+        }
+        Commands::Registry {
+            command:
+                RegistryCommands::Export {
+                    env,
+                    service,
+                    output,
+                },
+        } => {
+            let selected_env = resolve_environment(&config, env)?;
+            let token = secrets::resolve_optional(selected_env.registry_access_token.as_deref())
+                .await
+                .context("Failed to resolve registry access token")?;
+            let services = selected_env
+                .list_services()
+                .context("Failed to list services")?;
+            let mut export = offline::RegistryExport {
+                exported_at: Utc::now(),
+                images: BTreeMap::new(),
+            };
+            for selected in services
+                .iter()
+                .filter(|s| service.as_ref().is_none_or(|name| &s.name == name))
+            {
+                for container in &selected.containers {
+                    let (image, _) = split_image_ref(&container.image_path);
+                    if export.images.contains_key(image) {
+                        continue;
+                    }
+                    let images = if mock::enabled() {
+                        mock::images()
+                    } else {
+                        Registry::fetch_images(image, token.as_deref())
+                            .await
+                            .with_context(|| format!("Failed to list the images of {}", image))?
+                    };
+                    println!("📦 {}: {} image(s)", image, images.len());
+                    export.images.insert(image.to_string(), images);
+                }
+            }
+            if export.images.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "No images to export from {}",
+                    selected_env.name
+                ));
+            }
+            fs::write(&output, serde_json::to_string_pretty(&export)?)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+            println!(
+                "✅ Exported {} repositories to {}",
+                export.images.len(),
+                output.display()
+            );
+        }
+        Commands::Completions { .. } => unreachable!("handled before loading the config"),
+        Commands::Doctor => unreachable!("handled before loading the config"),
+        Commands::Contexts => {
+            if mock::enabled() {
+                println!("Demo mode: environments use simulated clusters.");
+                return Ok(());
+            }
+            let local = contexts::local_contexts()?;
+            let mut unusable = 0;
+            for env in &config.environments {
+                let found = local.iter().find(|c| c.name == env.kubectl_context);
+                let server = |c: &contexts::LocalContext| {
+                    c.server.clone().unwrap_or_else(|| "no server".to_string())
+                };
+                let detail = match (&env.context_alias, found) {
+                    (Some(alias), Some(context)) => Ok(format!(
+                        "alias {} → {} ({})",
+                        alias,
+                        context.name,
+                        server(context)
+                    )),
+                    (Some(alias), None) => Err(format!(
+                        "alias {}: no kubeconfig context points at {}",
+                        alias,
+                        config
+                            .contexts
+                            .get(alias)
+                            .map(|a| a.server.as_str())
+                            .unwrap_or_default()
+                    )),
+                    (None, Some(context)) => Ok(format!("{} ({})", context.name, server(context))),
+                    (None, None) => Err(format!("{}: not in kubeconfig", env.kubectl_context)),
+                };
+                match detail {
+                    Ok(detail) => println!("✅ {:<16} {}", env.name, detail),
+                    Err(detail) => {
+                        unusable += 1;
+                        println!("❌ {:<16} {}", env.name, detail);
                     }
+                }
+            }
+            if unusable > 0 {
+                return Err(anyhow::anyhow!(
+                    "{} environment(s) have no usable kubectl context",
+                    unusable
+                ));
+            }
+        }
+        Commands::Pin { env, dry_run } => {
+            let mut selected_env = resolve_environment(&config, env)?;
+            selected_env.registry_access_token =
+                secrets::resolve_optional(selected_env.registry_access_token.as_deref())
+                    .await
+                    .context("Failed to resolve registry access token")?;
+            let git_dry_run = dry_run || mock::enabled();
+            pull_yaml_sources(&selected_env, git_dry_run, "pinning")?;
+            check_commit_signing(&selected_env, git_dry_run)?;
+
+            let services = selected_env
+                .list_services()
+                .context("Failed to list services")?;
+            let plan = pin::plan(&selected_env, &services).await?;
+            plan.print();
+            if plan.files.is_empty() {
+                println!("Nothing to pin in {}.", selected_env.name);
+                return Ok(());
+            }
+            for file in &plan.files {
+                let filename = file
+                    .path
+                    .strip_prefix(&file.source_root)
+                    .unwrap_or(&file.path)
+                    .display()
+                    .to_string();
+                Blueprint::show_diff(&file.original, &file.updated, &filename, true);
+            }
+
+            let confirmed = !interactive::enabled()
+                || Confirm::new(&format!(
+                    "Pin {} image(s) in {} file(s) and commit?",
+                    plan.pins.len(),
+                    plan.files.len()
+                ))
+                .with_default(false)
+                .prompt()
+                .context("Confirmation was cancelled")?;
+            if !confirmed {
+                println!("Pinning cancelled. No changes made.");
+                return Ok(());
+            }
+
+            let mut by_root: BTreeMap<&Path, Vec<PathBuf>> = BTreeMap::new();
+            for file in &plan.files {
+                if dry_run {
+                    println!(
+                        "Dry-run: would write pinned YAML to {}",
+                        file.path.display()
+                    );
                 } else {
-                    println!("Committing skipped by user.");
+                    fs::write(&file.path, &file.updated).with_context(|| {
+                        format!("Failed to write pinned YAML to {}", file.path.display())
+                    })?;
                 }
+                by_root
+                    .entry(file.source_root.as_path())
+                    .or_default()
+                    .push(file.path.clone());
             }
+            let message = format!(
+                "pin({}): pin {} image(s) to digests",
+                selected_env.name,
+                plan.pins.len()
+            );
+            for (root, files) in by_root {
+                if selected_env.dry_only.unwrap_or(false) {
+                    let branch = sandbox_branch(&selected_env.name, "pin", "digests", Local::now());
+                    Git::commit_to_branch(root, &branch, &message, &files, git_dry_run)?;
+                } else {
+                    Git::commit_and_push(root, &message, &files, git_dry_run)?;
+                }
+            }
+            println!(
+                "✅ {} image(s) pinned in {}. The cluster is unchanged until the next apply of each service.",
+                plan.pins.len(),
+                selected_env.name
+            );
         }
-        Commands::Info {
-            env,
-            namespace,
+        Commands::Lock {
             service,
+            env,
+            reason,
         } => {
             let selected_env = resolve_environment(&config, env)?;
+            let service = resolve_service(&selected_env, service)?.name;
+            let reason = match reason {
+                Some(reason) => reason,
+                None => interactive::text(Text::new("Reason:"), "--reason")?,
+            };
+            let lock = config::ServiceLock {
+                reason,
+                by: Some(history::current_user()),
+                since: Some(Utc::now()),
+            };
+            update_config(|content| {
+                config::set_service_lock(content, &selected_env.name, &service, Some(&lock))
+            })?;
+            audit::AuditEntry::new(audit::Action::Locked, &selected_env.name, &service)
+                .detail(lock.reason.as_str())
+                .record();
+            println!(
+                "🔒 {} is locked in {}: {}",
+                service, selected_env.name, lock.reason
+            );
+        }
+        Commands::Unlock { service, env } => {
+            let selected_env = resolve_environment(&config, env)?;
+            let service = match service {
+                Some(service) => service,
+                None => {
+                    interactive::require("The service to unlock")?;
+                    Select::new(
+                        "Select service to unlock:",
+                        selected_env.locks.keys().cloned().collect(),
+                    )
+                    .prompt()
+                    .context("Service selection was cancelled")?
+                }
+            };
+            update_config(|content| {
+                config::set_service_lock(content, &selected_env.name, &service, None)
+            })?;
+            audit::AuditEntry::new(audit::Action::Unlocked, &selected_env.name, &service).record();
+            println!(
+                "🔓 {} can be deployed to {} again.",
+                service, selected_env.name
+            );
+        }
+        Commands::Scaffold { command } => match command {
+            ScaffoldCommands::Service {
+                name,
+                image,
+                env,
+                namespace,
+                port,
+                replicas,
+            } => {
+                let selected_env = resolve_environment(&config, env)?;
+                let path = scaffold::write_service(
+                    &selected_env,
+                    &scaffold::ServiceScaffold {
+                        name,
+                        image,
+                        namespace,
+                        port,
+                        replicas,
+                    },
+                )?;
+                println!(
+                    "✅ Wrote {}. Review and commit it, then apply it once to create the service.",
+                    path.display()
+                );
+            }
+        },
+        Commands::Config { command } => match command {
+            ConfigCommands::Show => {
+                if output::json() {
+                    output::print_json(&config)?;
+                } else {
+                    println!("{:#?}", config);
+                }
+            }
+            ConfigCommands::Path => {
+                let path = Config::get_config_path()?;
+                println!("{}", path.display());
+            }
+            ConfigCommands::Validate { .. } | ConfigCommands::Init { .. } => {
+                unreachable!("handled before loading the config")
+            }
+            ConfigCommands::CloneEnv {
+                from,
+                name,
+                context,
+                env_yaml_dir,
+            } => {
+                clone_environment(&config, from, name, context, env_yaml_dir)?;
+            }
+        },
+        Commands::History {
+            command,
+            env,
+            service,
+            since,
+            limit,
+        } => match command {
+            Some(HistoryCommands::Export {
+                format,
+                since,
+                output,
+            }) => {
+                export_history(format, since.as_deref(), output.as_deref())?;
+            }
+            None => {
+                let cutoff = history_cutoff(since.as_deref())?;
+                let latest = History::open_default()?.latest(
+                    env.as_deref(),
+                    service.as_deref(),
+                    cutoff,
+                    limit,
+                )?;
+                if output::json() {
+                    output::print_json(&latest)?;
+                } else {
+                    history::print_table(&latest);
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Start of the period given as `--since`, if any.
+fn history_cutoff(since: Option<&str>) -> Result<Option<DateTime<Utc>>> {
+    Ok(since
+        .map(history::parse_since)
+        .transpose()?
+        .map(|period| Utc::now() - period))
+}
+
+fn export_history(format: ExportFormat, since: Option<&str>, output: Option<&Path>) -> Result<()> {
+    let cutoff = history_cutoff(since)?;
+    let records: Vec<DeployRecord> = History::open_default()?
+        .load()?
+        .into_iter()
+        .filter(|r| cutoff.is_none_or(|cutoff| r.timestamp >= cutoff))
+        .collect();
+
+    let content = match format {
+        ExportFormat::Csv => history::to_csv(&records),
+        ExportFormat::Json => history::to_json(&records)? + "\n",
+    };
+    match output {
+        Some(path) => {
+            fs::write(path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Exported {} deploys to {}", records.len(), path.display());
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+/// `davit config init`: builds a starter config from the kubeconfig contexts the user
+/// picks, asking for the manifests directory of each and filling in what gcloud tells.
+fn init_config(force: bool) -> Result<()> {
+    if !interactive::enabled() {
+        return Err(anyhow::anyhow!(
+            "`davit config init` is a wizard and needs an interactive session"
+        ));
+    }
+    let config_path = Config::get_config_path()?;
+    if config_path.exists()
+        && !force
+        && !Confirm::new(&format!(
+            "{} already exists. Overwrite it?",
+            config_path.display()
+        ))
+        .with_default(false)
+        .prompt()?
+    {
+        println!("Config left untouched.");
+        return Ok(());
+    }
+
+    let local: Vec<String> = contexts::local_contexts()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    let chosen = if local.is_empty() {
+        println!("⚠️  No kubectl contexts found in your kubeconfig.");
+        vec![Text::new("kubectl context of the first environment:").prompt()?]
+    } else {
+        MultiSelect::new("Contexts to set up as environments:", local).prompt()?
+    };
+    if chosen.is_empty() {
+        return Err(anyhow::anyhow!("Select at least one context"));
+    }
+    let projects = contexts::gcloud_projects();
+
+    let mut environments: Vec<config::EnvironmentInit> = Vec::new();
+    for kubectl_context in chosen {
+        println!("\nEnvironment for {}", kubectl_context);
+        let gke = contexts::gke_context_cluster(&kubectl_context);
+        let default_name = gke
+            .as_ref()
+            .map(|(_, cluster)| cluster.name.clone())
+            .unwrap_or_else(|| kubectl_context.clone());
+        let name = Text::new("Environment name:")
+            .with_default(&default_name)
+            .prompt()?;
+
+        // Environments usually sit side by side in the manifests repository.
+        let suggested = environments
+            .last()
+            .and_then(|previous| previous.env_yaml_dir.parent())
+            .map(|parent| parent.join(&name).display().to_string());
+        let mut prompt = Text::new("Manifests directory (env_yaml_dir):");
+        if let Some(suggested) = &suggested {
+            prompt = prompt.with_default(suggested);
+        }
+        let env_yaml_dir = expand_home(&prompt.prompt()?);
+        if !env_yaml_dir.is_dir() {
+            println!(
+                "⚠️  {} does not exist yet; clone the manifests repository there.",
+                env_yaml_dir.display()
+            );
+        }
+
+        let (gcp_project, gke_cluster) = match gke {
+            Some((project, cluster)) => {
+                println!(
+                    "GKE cluster {} ({}) in project {}",
+                    cluster.name, cluster.location, project
+                );
+                (Some(project), Some(cluster))
+            }
+            None if !projects.is_empty() => {
+                let none = "(none)".to_string();
+                let options: Vec<String> = std::iter::once(none.clone())
+                    .chain(projects.clone())
+                    .collect();
+                let project = Select::new("GCP project:", options).prompt()?;
+                ((project != none).then_some(project), None)
+            }
+            None => (None, None),
+        };
+
+        let protected = Confirm::new("Protected (confirm deploys, push them to Git)?")
+            .with_default(name.contains("prod"))
+            .prompt()?;
+
+        environments.push(config::EnvironmentInit {
+            name,
+            kubectl_context,
+            env_yaml_dir,
+            gcp_project,
+            gke_cluster,
+            protected,
+        });
+    }
+
+    let content = config::starter_config(&environments)?;
+    if let Some(dir) = config_path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(&config_path, content)
+        .with_context(|| format!("Failed to write config file at {}", config_path.display()))?;
+    println!(
+        "\n✅ Wrote {} with {} environment(s). Check it with `davit config validate`.",
+        config_path.display(),
+        environments.len()
+    );
+    Ok(())
+}
+
+/// `~/...` as typed in a prompt, relative to the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), directories::BaseDirs::new()) {
+        (Some(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `davit config validate`: reports every problem of the config file with its line,
+/// failing when one of them would break a command.
+async fn validate_config(skip_contexts: bool) -> Result<()> {
+    let path = Config::get_config_path()?;
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file at {}", path.display()))?;
+
+    let (config, mut issues) = config_check::check(&content);
+    if let Some(mut config) = config
+        && !skip_contexts
+        && !mock::enabled()
+    {
+        issues.extend(config_check::check_contexts(&content, &mut config).await);
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == lint::Severity::Error)
+        .count();
+    if output::json() {
+        output::print_json(&issues)?;
+    } else if issues.is_empty() {
+        println!("✅ {} is valid.", path.display());
+    } else {
+        config_check::print_issues(&path, &issues);
+    }
+    if errors > 0 {
+        return Err(anyhow::anyhow!(
+            "{} has {} error(s)",
+            path.display(),
+            errors
+        ));
+    }
+    Ok(())
+}
+
+async fn run_doctor(config: Result<Config>) -> Result<()> {
+    let checks = doctor::run(config).await;
+    let failed = checks
+        .iter()
+        .filter(|c| c.outcome == doctor::Outcome::Fail)
+        .count();
+    if output::json() {
+        output::print_json(&checks)?;
+    } else {
+        doctor::print_report(&checks);
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} check(s) failed; fix them before deploying",
+            failed
+        ));
+    }
+    Ok(())
+}
+
+fn clone_environment(
+    config: &Config,
+    from: Option<String>,
+    name: Option<String>,
+    context: Option<String>,
+    env_yaml_dir: Option<PathBuf>,
+) -> Result<()> {
+    let source = resolve_environment(config, from)?;
+
+    let name = match name {
+        Some(name) => name,
+        None => interactive::text(Text::new("New environment name:"), "--name")?,
+    };
+    let kubectl_context = match context {
+        Some(context) => context,
+        None => interactive::text(
+            Text::new("kubectl context:").with_default(&source.kubectl_context),
+            "--context",
+        )?,
+    };
+    let env_yaml_dir = match env_yaml_dir {
+        Some(dir) => dir,
+        None => PathBuf::from(interactive::text(
+            Text::new("YAML directory (env_yaml_dir):")
+                .with_default(&source.env_yaml_dir.display().to_string()),
+            "--env-yaml-dir",
+        )?),
+    };
+
+    let mut env_yaml_dir_extra = BTreeMap::new();
+    for (source_name, root) in &source.env_yaml_dir_extra {
+        let path = interactive::text(
+            Text::new(&format!(
+                "YAML directory for extra source '{}':",
+                source_name
+            ))
+            .with_default(&root.display().to_string()),
+            "A YAML directory for each extra source",
+        )?;
+        env_yaml_dir_extra.insert(source_name.clone(), PathBuf::from(path));
+    }
+
+    let clone = config::EnvironmentClone {
+        name,
+        kubectl_context,
+        env_yaml_dir,
+        env_yaml_dir_extra,
+    };
+
+    let config_path = Config::get_config_path()?;
+    let content = fs::read_to_string(&config_path)
+        .with_context(|| format!("Failed to read config file at {}", config_path.display()))?;
+    let updated = config::clone_environment(&content, &source.name, &clone)?;
+
+    for root in std::iter::once(&clone.env_yaml_dir).chain(clone.env_yaml_dir_extra.values()) {
+        if !root.exists() {
+            println!("⚠️  YAML directory {} does not exist yet.", root.display());
+        }
+    }
+
+    fs::write(&config_path, updated)
+        .with_context(|| format!("Failed to write config file at {}", config_path.display()))?;
+    println!(
+        "✅ Environment '{}' cloned from '{}' into {}",
+        clone.name,
+        source.name,
+        config_path.display()
+    );
+
+    Ok(())
+}
+
+fn resolve_environment(config: &Config, input: Option<String>) -> Result<Environment> {
+    let env_names: Vec<String> = config.environments.iter().map(|e| e.name.clone()).collect();
+
+    let name = match input {
+        Some(val) => resolve_from_list("Environment", &env_names, val)?,
+        None => {
+            interactive::require("--env")?;
+            Select::new("Select Environment:", env_names.clone())
+                .prompt()
+                .context("Environment selection was cancelled")?
+        }
+    };
+
+    let mut env = config
+        .environments
+        .iter()
+        .find(|e| e.name == name)
+        .cloned()
+        .context("Environment not found in config")?;
+    contexts::ensure_context(&config.contexts, &mut env)?;
+    Ok(env)
+}
+
+/// Sets the configured deploy annotations on the workload of the manifest content.
+/// Kustomize overlays and chart values hold no workload metadata and are left as is.
+fn annotate_manifest(
+    annotations: &DeployAnnotations,
+    service: &ServiceSource,
+    content: &str,
+    new_tag: &str,
+) -> Result<String> {
+    if service.helm_chart.is_some() || service.kustomize_overlay.is_some() {
+        return Ok(content.to_string());
+    }
+    let values = annotations.values(
+        &service.name,
+        split_image_ref(&service.image_path).1,
+        new_tag,
+        &history::current_user(),
+    );
+    if values.is_empty() {
+        return Ok(content.to_string());
+    }
+    Blueprint::update_annotations_in_document(content, service.document_index, &values)
+        .context("Failed to set the deploy annotations in YAML")
+}
+
+/// Applies the selected container tags to the service manifest content.
+fn update_manifest(
+    service: &ServiceSource,
+    original_content: &str,
+    container_tags: &[(ContainerImage, String)],
+) -> Result<String> {
+    let mut updated_content = original_content.to_string();
+    for (container, container_tag) in container_tags {
+        let (base_image, _) = split_image_ref(&container.image_path);
+        updated_content = if let Some(chart) = &service.helm_chart {
+            Blueprint::update_helm_values_tag(&updated_content, &chart.tag_value, container_tag)
+        } else if service.kustomize_overlay.is_some() {
+            Blueprint::update_kustomize_image_tag(&updated_content, base_image, container_tag)
+        } else if shares_base_image(service, container) {
+            Blueprint::update_container_image_tag_in_document(
+                &updated_content,
+                service.document_index,
+                &container.name,
+                base_image,
+                container_tag,
+            )
+        } else {
+            Blueprint::update_image_tag_in_document(
+                &updated_content,
+                service.document_index,
+                base_image,
+                container_tag,
+            )
+        }
+        .context("Failed to update image tag in YAML")?;
+    }
+    Ok(updated_content)
+}
+
+/// Builds the overlay before and after the kustomization change. The updated
+/// kustomization is built from a temporary copy, leaving the working tree untouched.
+fn render_kustomize_diff(
+    overlay: &Path,
+    kustomization: &Path,
+    updated_content: &str,
+) -> Result<(String, String)> {
+    let old_rendered = Blueprint::render_kustomize(overlay)?;
+    // Overlays usually reach their bases through `../`, which the whole work tree covers.
+    let root = Git::work_tree(overlay).unwrap_or_else(|_| overlay.to_path_buf());
+    let new_rendered =
+        Blueprint::render_kustomize_edited(&root, overlay, kustomization, updated_content)?;
+    Ok((old_rendered, new_rendered))
+}
+
+/// Whether another container of the workload runs the same image repository, so that
+/// its tag must be left alone when only `container` is updated.
+fn shares_base_image(service: &ServiceSource, container: &ContainerImage) -> bool {
+    let (base_image, _) = split_image_ref(&container.image_path);
+    service
+        .containers
+        .iter()
+        .any(|c| c.name != container.name && split_image_ref(&c.image_path).0 == base_image)
+}
+
+/// Asks which first-party containers to update when the workload has more than one.
+fn select_target_containers(service: &ServiceSource) -> Result<Vec<ContainerImage>> {
+    // A Helm upgrade sets a single tag value.
+    if service.containers.len() <= 1
+        || service.helm_release.is_some()
+        || service.helm_chart.is_some()
+    {
+        return Ok(vec![ContainerImage {
+            name: service.container_name.clone(),
+            image_path: service.image_path.clone(),
+        }]);
+    }
+    if !interactive::enabled() {
+        return Ok(service.containers.clone());
+    }
+
+    let options: Vec<String> = service
+        .containers
+        .iter()
+        .map(|c| match shares_base_image(service, c) {
+            true => format!("{} ({}, shared image)", c.name, c.image_path),
+            false => format!("{} ({})", c.name, c.image_path),
+        })
+        .collect();
+    let selected = MultiSelect::new(
+        &format!(
+            "{} has several first-party containers. Select which to update:",
+            service.name
+        ),
+        options.clone(),
+    )
+    .with_all_selected_by_default()
+    .prompt()
+    .context("Container selection was cancelled")?;
+
+    let targets: Vec<ContainerImage> = service
+        .containers
+        .iter()
+        .zip(&options)
+        .filter(|(_, option)| selected.contains(option))
+        .map(|(c, _)| c.clone())
+        .collect();
+
+    if targets.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No container selected. Deployment aborted."
+        ));
+    }
+    // A kustomization sets the tag of an image, whichever containers run it.
+    if service.kustomize_overlay.is_some()
+        && let Some(skipped) = service.containers.iter().find(|c| {
+            !targets.contains(c)
+                && targets
+                    .iter()
+                    .any(|t| split_image_ref(&t.image_path).0 == split_image_ref(&c.image_path).0)
+        })
+    {
+        println!(
+            "⚠️  {} shares its image with a selected container: the kustomization's `images` entry updates it too.",
+            skipped.name
+        );
+    }
+
+    Ok(targets)
+}
+
+/// Pairs every target container with its tag, optionally asking a distinct tag for each.
+async fn resolve_container_tags(
+    env: &Environment,
+    service: &ServiceSource,
+    targets: &[ContainerImage],
+    primary_tag: &str,
+) -> Result<Vec<(ContainerImage, String)>> {
+    let mut container_tags = vec![(targets[0].clone(), primary_tag.to_string())];
+    if targets.len() == 1 {
+        return Ok(container_tags);
+    }
+
+    let same_tag = interactive::confirm(
+        Confirm::new(&format!(
+            "Deploy tag '{}' to all selected containers?",
+            primary_tag
+        ))
+        .with_default(true),
+    )?;
+
+    for container in &targets[1..] {
+        let container_tag = if same_tag {
+            primary_tag.to_string()
+        } else {
+            println!("Selecting tag for container '{}'", container.name);
+            resolve_tag(env, &service.with_container(container), None, None)
+                .await?
+                .0
+        };
+        container_tags.push((container.clone(), container_tag));
+    }
+
+    Ok(container_tags)
+}
+
+/// Pairs every target container with the digest its tag points at, for manifests pinned
+/// to immutable references. `images` is the listing already fetched for the service's own
+/// image; other images are listed once each.
+async fn pin_container_digests(
+    env: &Environment,
+    service: &ServiceSource,
+    container_tags: &[(ContainerImage, String)],
+    images: &[ImageMetadata],
+) -> Result<Vec<(ContainerImage, String)>> {
+    let mut listings: HashMap<String, Vec<ImageMetadata>> = HashMap::new();
+    listings.insert(service.image_path.clone(), images.to_vec());
+    let mut pinned = Vec::with_capacity(container_tags.len());
+    for (container, tag) in container_tags {
+        if !listings.contains_key(&container.image_path) {
+            let images = fetch_service_images(env, &service.with_container(container), false)
+                .await
+                .unwrap_or_default();
+            listings.insert(container.image_path.clone(), images);
+        }
+        let digest = tag_digest(&listings[&container.image_path], tag).with_context(|| {
+            format!(
+                "Could not find the digest of {}:{} to pin it",
+                split_image_ref(&container.image_path).0,
+                tag
+            )
+        })?;
+        pinned.push((container.clone(), digest));
+    }
+    Ok(pinned)
+}
+
+fn deploy_summary(service_name: &str, container_tags: &[(ContainerImage, String)]) -> String {
+    let first_tag = container_tags
+        .first()
+        .map(|(_, t)| t.as_str())
+        .unwrap_or_default();
+    if container_tags.iter().all(|(_, t)| t == first_tag) {
+        return format!("{} to {}", service_name, first_tag);
+    }
+
+    let parts: Vec<String> = container_tags
+        .iter()
+        .map(|(c, t)| format!("{} to {}", c.name, t))
+        .collect();
+    format!("{} ({})", service_name, parts.join(", "))
+}
+
+/// Prints what may keep the service's SealedSecrets from unsealing, checked after the
+/// controller processed them when they were `applied`.
+async fn report_sealed_secret_warnings(
+    env: &Environment,
+    service: &ServiceSource,
+    manifests: &[sealed_secrets::SealedSecretManifest],
+    applied: bool,
+) {
+    if manifests.is_empty() {
+        return;
+    }
+
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let result = match cluster::client(&env.kubectl_context).await {
+        Ok(client) => sealed_secrets::check(&client, namespace, manifests, applied).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(warnings) => {
+            for warning in warnings {
+                println!("⚠️  {}", warning);
+            }
+        }
+        Err(e) => println!("⚠️  Could not verify SealedSecrets in the cluster: {}", e),
+    }
+}
+
+/// Checks the environment's resource policy, failing on protected environments.
+fn enforce_resource_policy(env: &Environment, workload: Option<&WorkloadResources>) -> Result<()> {
+    let Some(policy) = &env.policy else {
+        return Ok(());
+    };
+    let Some(workload) = workload else {
+        println!("⚠️  Could not read workload resources; resource policy not evaluated.");
+        return Ok(());
+    };
+
+    let violations = policy::evaluate(policy, workload);
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    let protected = env.protected.unwrap_or(false);
+    let marker = if protected { "❌" } else { "⚠️ " };
+    for violation in &violations {
+        println!(
+            "{} Policy {}: {}",
+            marker, violation.rule, violation.message
+        );
+    }
+
+    if protected {
+        return Err(anyhow::anyhow!(
+            "Deployment blocked: {} resource policy violation(s) on protected environment {}",
+            violations.len(),
+            env.name
+        ));
+    }
+    Ok(())
+}
+
+/// Fails when a lint finding reaches the environment's `lint_fail_on` severity.
+fn enforce_lint(env: &Environment, findings: &[lint::LintFinding]) -> Result<()> {
+    let Some(threshold) = env.lint_fail_on else {
+        return Ok(());
+    };
+
+    let blocking = findings.iter().filter(|f| f.severity >= threshold).count();
+    if blocking > 0 {
+        lint::print_findings(findings);
+        return Err(anyhow::anyhow!(
+            "Deployment blocked: {} lint finding(s) at or above '{}' on environment {}",
+            blocking,
+            threshold,
+            env.name
+        ));
+    }
+    Ok(())
+}
+
+/// Fails when the imagePullSecrets of the manifest are missing or lack credentials for the
+/// registry of its images. An unreachable cluster only warns.
+async fn enforce_pull_secrets(
+    env: &Environment,
+    service: &ServiceSource,
+    manifest: &str,
+) -> Result<()> {
+    let Some(workload) = parse_document(manifest, service.document_index) else {
+        return Ok(());
+    };
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let result = match cluster::client(&env.kubectl_context).await {
+        Ok(client) => image_pull::verify_pull_secrets(&client, env, namespace, &workload).await,
+        Err(e) => Err(e),
+    };
+    let problems = match result {
+        Ok(problems) => problems,
+        Err(e) => {
+            println!("⚠️  Could not verify imagePullSecrets: {}", e);
+            return Ok(());
+        }
+    };
+    if problems.is_empty() {
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("❌ {}", problem);
+    }
+    Err(anyhow::anyhow!(
+        "Deployment blocked: pods of {} would fail with ImagePullBackOff",
+        service.name
+    ))
+}
+
+/// Runs the environment's Rego policies on the manifest; any failed rule blocks the deploy.
+fn enforce_rego_policies(env: &Environment, manifest: &str) -> Result<()> {
+    let Some(report) = rego::evaluate(env, manifest).context("Failed to evaluate Rego policies")?
+    else {
+        return Ok(());
+    };
+
+    report.print();
+    if !report.failures.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Deployment blocked: {} Rego policy violation(s)",
+            report.failures.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the resources of the running workload and of the manifest about to be applied,
+/// falling back to the committed manifest when the cluster cannot be reached.
+async fn workload_resources_before_after(
+    env: &Environment,
+    service: &ServiceSource,
+    original_manifest: &str,
+    new_manifest: &str,
+) -> Option<(Option<WorkloadResources>, WorkloadResources)> {
+    let mut new = parse_document(new_manifest, service.document_index)
+        .and_then(|w| WorkloadResources::from_workload(&w))?;
+
+    let live = match cluster::client(&env.kubectl_context).await {
+        Ok(client) => {
+            retry::with_backoff("Live workload lookup", || {
+                resources::live_workload(&client, service)
+            })
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    let before = match live {
+        Ok(live) => live,
+        Err(_) if service.kustomize_overlay.is_none() && service.helm_chart.is_none() => {
+            parse_document(original_manifest, service.document_index)
+        }
+        Err(_) => return None,
+    };
+
+    let before = before.and_then(|w| WorkloadResources::from_workload(&w));
+    if new.replicas.is_none() {
+        // Without `replicas` in the manifest the running count (e.g. set by an HPA) is kept.
+        new.replicas = before.as_ref().and_then(|b| b.replicas);
+    }
+
+    Some((before, new))
+}
+
+/// Warms the new images up on the workload's nodes. Pull failures abort the deploy before
+/// anything is applied.
+async fn prepull_images(
+    env: &Environment,
+    service: &ServiceSource,
+    container_tags: &[(ContainerImage, String)],
+    manifest: &str,
+) -> Result<()> {
+    let images: Vec<String> = container_tags
+        .iter()
+        .map(|(container, tag)| {
+            let separator = if blueprint::is_digest(tag) { '@' } else { ':' };
+            format!(
+                "{}{}{}",
+                split_image_ref(&container.image_path).0,
+                separator,
+                tag
+            )
+        })
+        .collect();
+    let workload = parse_document(manifest, service.document_index);
+    let pod_spec = workload.as_ref().and_then(blueprint::find_pod_spec);
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+
+    let started = Instant::now();
+    let client = cluster::client(&env.kubectl_context).await?;
+    let nodes = prepull::prepull(client, namespace, &service.name, &images, pod_spec).await?;
+    println!(
+        "✅ Images pre-pulled on {} node(s) in {:.0}s.",
+        nodes,
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Reports containers whose live image differs from the applied one; `false` when the
+/// user stops the deploy because of it. Unattended deploys only warn.
+fn verify_applied_images(
+    env: &Environment,
+    service: &ServiceSource,
+    container_tags: &[(ContainerImage, String)],
+    auto_continue: bool,
+) -> Result<bool> {
+    let divergences = match verify::check_applied_images(env, service, container_tags) {
+        Ok(divergences) => divergences,
+        Err(e) => {
+            println!("⚠️  Could not read the applied workload back: {:#}", e);
+            return Ok(true);
+        }
+    };
+    if divergences.is_empty() {
+        println!("✅ The live workload has the applied images.");
+        return Ok(true);
+    }
+    println!("⚠️  The live workload differs from what was applied (mutating admission webhook?):");
+    for divergence in &divergences {
+        println!("   {}", divergence);
+    }
+    if auto_continue {
+        return Ok(true);
+    }
+    Confirm::new("Follow the rollout anyway?")
+        .with_default(false)
+        .prompt()
+        .context("Confirmation was cancelled")
+}
+
+/// Checks the gates of the next stage of the service's promotion pipeline and returns the
+/// deploy of the previous stage's tag to it.
+async fn promote(
+    config: &Config,
+    service: Option<String>,
+    to: Option<&str>,
+    dry_run: bool,
+    override_freeze: Option<String>,
+) -> Result<Commands> {
+    let pipelines: Vec<String> = config
+        .services
+        .iter()
+        .filter(|(_, s)| !s.promotion.is_empty())
+        .map(|(name, _)| name.clone())
+        .collect();
+    if pipelines.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No service defines a promotion pipeline in the configuration"
+        ));
+    }
+    let name = match service {
+        Some(val) => resolve_from_list("Service", &pipelines, val)?,
+        None => {
+            interactive::require("The service")?;
+            Select::new("Select Service:", pipelines)
+                .prompt()
+                .context("Service selection was cancelled")?
+        }
+    };
+    let service_config = config.service(&name);
+
+    let mut stages = Vec::new();
+    for stage in service_config.promotion {
+        let env = config
+            .environments
+            .iter()
+            .find(|e| e.name == stage.env)
+            .cloned()
+            .context("Environment not found in config")?;
+        pull_yaml_sources(&env, dry_run || mock::enabled(), "promotion")?;
+        let service = env
+            .list_services()
+            .with_context(|| format!("Failed to list services of {}", env.name))?
+            .into_iter()
+            .find(|s| s.name == name);
+        stages.push(promotion::StageState {
+            stage,
+            env,
+            service,
+        });
+    }
+    let target = promotion::target_stage(&stages, to)?;
+
+    println!("\n🚦 Promotion pipeline of {}", name);
+    for (index, stage) in stages.iter().enumerate() {
+        let marker = if index == target { "→" } else { " " };
+        println!(
+            "  {} {:<16} {}",
+            marker,
+            stage.env.name,
+            stage.tag().unwrap_or("-")
+        );
+    }
+    println!("\nGates of {}:", stages[target].env.name);
+    let gates = promotion::check_gates(&stages, target, service_config.health.as_ref()).await;
+    if gates.is_empty() {
+        println!("  (none)");
+    }
+    for gate in &gates {
+        println!("  {}", gate);
+    }
+    if gates.iter().any(|g| !g.passed) {
+        return Err(anyhow::anyhow!(
+            "Promotion to {} blocked by its gates",
+            stages[target].env.name
+        ));
+    }
+
+    let tag = stages[target - 1]
+        .tag()
+        .context("The previous stage has no tag to promote")?
+        .to_string();
+    Ok(Commands::Deploy {
+        env: Some(stages[target].env.name.clone()),
+        service: Some(name),
+        tag: Some(tag),
+        manifest: None,
+        wait_for_tag: None,
+        dry_run,
+        auto_apply: false,
+        auto_continue: false,
+        prepull: false,
+        progressive: false,
+        at: None,
+        dashboard: false,
+        pin_digest: false,
+        approved_by: Vec::new(),
+        override_freeze,
+        ticket: None,
+    })
+}
+
+/// Turns a rollback into the deploy of the tag the current one replaced: the one recorded
+/// by davit, else the previous tag committed in the service's YAML.
+fn rollback(
+    config: &Config,
+    env: Option<String>,
+    service: Option<String>,
+    dry_run: bool,
+    auto_continue: bool,
+    override_freeze: Option<String>,
+) -> Result<Commands> {
+    let env = resolve_environment(config, env)?;
+    pull_yaml_sources(&env, dry_run || mock::enabled(), "rollback")?;
+    let service = resolve_service(&env, service)?;
+    let current = split_image_ref(&service.image_path)
+        .1
+        .with_context(|| format!("{} has no tag to roll back from", service.image_path))?;
+
+    let records = History::open_default()
+        .and_then(|h| h.load())
+        .unwrap_or_default();
+    let (previous, source) =
+        match history::previous_tag(&records, &env.name, &service.name, current) {
+            Some(tag) => (tag.to_string(), "deploy history"),
+            None => (
+                blame::committed_previous_tag(&service).with_context(|| {
+                    format!(
+                        "No previous tag of {} in {}: it is not in the deploy history nor in the Git history of its YAML",
+                        service.name, env.name
+                    )
+                })?,
+                "Git history",
+            ),
+        };
+    println!(
+        "↩️  Rolling back {} in {}: {} → {} (from the {})",
+        service.name, env.name, current, previous, source
+    );
+
+    Ok(Commands::Deploy {
+        env: Some(env.name),
+        service: Some(service.name),
+        tag: Some(previous),
+        manifest: None,
+        wait_for_tag: None,
+        dry_run,
+        auto_apply: false,
+        auto_continue,
+        prepull: false,
+        progressive: false,
+        at: None,
+        dashboard: false,
+        pin_digest: false,
+        approved_by: Vec::new(),
+        override_freeze,
+        ticket: None,
+    })
+}
+
+/// Applies the manifest of a file-backed service as written to its file, with the
+/// related files or as its workload document alone depending on the apply scope.
+fn apply_service_file(
+    env: &Environment,
+    service: &ServiceSource,
+    tag: &str,
+    dry_run: bool,
+    sandbox: bool,
+) -> Result<()> {
+    let mut files = vec![service.yaml_path.clone()];
+    if env.apply_scope == ApplyScope::Related {
+        for document in service.related_documents() {
+            if !files.contains(&document.path) {
+                files.push(document.path);
+            }
+        }
+    }
+    let changed_only = env.apply_scope == ApplyScope::Changed;
+    let apply_args = match &service.kustomize_overlay {
+        Some(overlay) => vec!["-k".to_string(), overlay.display().to_string()],
+        None if changed_only => vec!["-f".to_string(), "-".to_string()],
+        None => files
+            .iter()
+            .flat_map(|f| ["-f".to_string(), f.display().to_string()])
+            .collect(),
+    };
+    if let Some(chart) = &service.helm_chart {
+        if dry_run {
+            println!(
+                "Dry-run: helm {}",
+                chart.upgrade_args(&env.kubectl_context, false).join(" ")
+            );
+        } else {
+            chart.upgrade(&env.kubectl_context, sandbox)?;
+        }
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "Dry-run: kubectl --context {} apply {}",
+            env.kubectl_context,
+            apply_args.join(" ")
+        );
+        return Ok(());
+    }
+    if mock::enabled() {
+        if !sandbox {
+            let (image, _) = split_image_ref(&service.image_path);
+            mock::apply(
+                &env.kubectl_context,
+                &service.name,
+                &service.image_path,
+                &format!("{}:{}", image, tag),
+            );
+        }
+        return Ok(());
+    }
+    let wrapper = env
+        .offline
+        .as_ref()
+        .map(|o| o.kubectl_wrapper.as_slice())
+        .filter(|w| !w.is_empty());
+    let manifests = || -> Result<String> {
+        match &service.kustomize_overlay {
+            Some(overlay) => Blueprint::render_kustomize(overlay),
+            // Releases and scaling only edit the workload document.
+            None if changed_only => service
+                .workload_document()
+                .context("Failed to read the workload to apply")
+                .and_then(|w| Ok(serde_yaml::to_string(&w)?)),
+            None => Ok(files
+                .iter()
+                .map(fs::read_to_string)
+                .collect::<io::Result<Vec<_>>>()
+                .context("Failed to read the manifests to apply")?
+                .join("\n---\n")),
+        }
+    };
+    let output = match wrapper {
+        Some(wrapper) => offline::apply_through(wrapper, &manifests()?, sandbox)?,
+        None if changed_only && service.kustomize_overlay.is_none() => offline::apply_through(
+            &kubectl_command(&env.kubectl_context),
+            &manifests()?,
+            sandbox,
+        )?,
+        None => Command::new("kubectl")
+            .args(["--context", &env.kubectl_context, "apply"])
+            .args(sandbox.then_some("--dry-run=server"))
+            .args(&apply_args)
+            .output()
+            .context("Failed to execute kubectl apply")?,
+    };
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "kubectl apply failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// `kubectl` on the context, as the program that [`offline::apply_through`] pipes
+/// manifests to.
+fn kubectl_command(context: &str) -> Vec<String> {
+    vec![
+        "kubectl".to_string(),
+        "--context".to_string(),
+        context.to_string(),
+    ]
+}
+
+/// Checks the compatibility rules involving the service against the tags deployed for
+/// the others. Violations of blocking rules abort the deploy, the others only warn.
+async fn enforce_compatibility(
+    config: &Config,
+    env: &Environment,
+    service: &ServiceSource,
+    tag: &str,
+) -> Result<()> {
+    let deployed = compatibility::deployed_tags(&config.compatibility, env, &service.name).await?;
+    let violations =
+        compatibility::violations(&config.compatibility, &service.name, tag, &deployed);
+    for violation in &violations {
+        let marker = if violation.block { "❌" } else { "⚠️ " };
+        println!("{} Incompatible versions: {}", marker, violation);
+    }
+    if violations.iter().any(|v| v.block) {
+        return Err(anyhow::anyhow!(
+            "Deployment blocked by version compatibility rules"
+        ));
+    }
+    Ok(())
+}
+
+/// Progressive delivery settings of a service, checked before anything is reviewed.
+fn progressive_delivery(config: &Config, service: &ServiceSource) -> Result<ProgressiveDelivery> {
+    if service.kind != "Deployment" {
+        return Err(anyhow::anyhow!(
+            "Progressive delivery supports Deployments only, {} is a {}",
+            service.name,
+            service.kind
+        ));
+    }
+    config.service(&service.name).progressive.with_context(|| {
+        format!(
+            "Service '{}' has no `progressive` routing configured",
+            service.name
+        )
+    })
+}
+
+/// Runs the new version as a canary and lets the user shift traffic to it from the
+/// dashboard. Returns once the canary receives all the traffic; quitting earlier or a
+/// failure sends the traffic back to the stable version and removes the canary.
+async fn run_canary(
+    config: &Config,
+    env: &Environment,
+    service: &ServiceSource,
+    tag: &str,
+    pinned_digest: Option<&str>,
+    delivery: &ProgressiveDelivery,
+    manifest: &str,
+) -> Result<Canary> {
+    let workload = parse_document(manifest, service.document_index)
+        .context("Failed to parse the workload manifest")?;
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let client = cluster::client(&env.kubectl_context).await?;
+    let canary = Canary::start(
+        client.clone(),
+        delivery,
+        namespace,
+        &service.name,
+        &workload,
+        tag,
+    )
+    .await?;
+    println!(
+        "🐤 Canary {} started without traffic. Starting dashboard...",
+        canary.name
+    );
+
+    let service_config = config.service(&service.name);
+    let mut dashboard = Dashboard::new(
+        service.name.clone(),
+        service.kind.clone(),
+        env.name.clone(),
+        tag.to_string(),
+        env.kubectl_context.clone(),
+        service.namespace.clone(),
+        service.selector.clone(),
+        service.container_name.clone(),
+        false,
+        config.dashboard.clone(),
+        service_config.health,
+        env.prometheus_url.clone(),
+        service_config.metrics,
+    );
+    dashboard.parse_logs_with(config.log_fields(&service.name));
+    dashboard.control_canary(canary.clone());
+    if let Some(digest) = pinned_digest {
+        dashboard.match_digest(digest);
+    }
+    let res = dashboard.run().await;
+    if let Ok(DashboardExit::RolloutCompleted) = res {
+        println!("✅ The canary receives all the traffic. Rolling out the stable workload...");
+        return Ok(canary);
+    }
+
+    canary
+        .abort(client)
+        .await
+        .context("Failed to send the traffic back to the stable version")?;
+    println!(
+        "↩️  Traffic back on {} and canary removed. Nothing else was applied.",
+        service.name
+    );
+    res.and(Err(anyhow::anyhow!("Canary aborted. Deployment aborted.")))
+}
+
+/// Removes the canary once the stable workload was rolled out (`promoted`) or failed to
+/// be applied, sending the traffic back to it. A failure only warns: the routing can
+/// be fixed by hand.
+async fn retire_canary(env: &Environment, canary: Option<&Canary>, promoted: bool) {
+    let Some(canary) = canary else {
+        return;
+    };
+    let result = match cluster::client(&env.kubectl_context).await {
+        Ok(client) if promoted => canary.finish(client).await,
+        Ok(client) => canary.abort(client).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => println!(
+            "🐤 Canary {} removed, traffic back on {}.",
+            canary.name, canary.workload
+        ),
+        Err(e) => println!("⚠️  Could not remove the canary {}: {:#}", canary.name, e),
+    }
+}
+
+/// Saves the diagnostic bundle of a failed deploy and prints where it is.
+async fn capture_failure_bundle(deploy: &FailedDeploy<'_>) {
+    match bundle::capture(deploy).await {
+        Ok(dir) => println!("🧰 Failure bundle saved to {}", dir.display()),
+        Err(e) => println!("⚠️  Could not save the failure bundle: {}", e),
+    }
+}
+
+/// Explains why new pods are not becoming ready, when the dashboard closes before the
+/// rollout completed.
+async fn report_stuck_rollout(env: &Environment, service: &ServiceSource, tag: &str) {
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let selector = service
+        .selector
+        .clone()
+        .unwrap_or_else(|| format!("app={}", service.name));
+    let result = match cluster::client(&env.kubectl_context).await {
+        Ok(client) => diagnose::diagnose(client, namespace, &selector, tag).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(findings) => diagnose::print_findings(&findings),
+        Err(e) => println!("⚠️  Could not diagnose the rollout: {}", e),
+    }
+}
+
+async fn report_image_pull_warnings(env: &Environment, service: &ServiceSource) {
+    let result = match cluster::client(&env.kubectl_context).await {
+        Ok(client) => image_pull::check(&client, env, service).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(warnings) => {
+            for warning in warnings {
+                println!("⚠️  {}", warning);
+            }
+        }
+        Err(e) => println!("⚠️  Could not verify image pull permissions: {}", e),
+    }
+}
+
+struct FileChange {
+    path: PathBuf,
+    old_content: String,
+    new_content: String,
+}
+
+/// Finds ConfigMaps next to the service YAML that changed since the last commit touching it.
+fn collect_configmap_changes(service: &ServiceSource) -> Vec<FileChange> {
+    // Charts ship their own ConfigMaps through the upgrade.
+    if service.helm_chart.is_some() {
+        return Vec::new();
+    }
+    let Some(service_dir) = service.yaml_path.parent() else {
+        return Vec::new();
+    };
+    let last_deploy = match Git::last_commit_for_file(&service.source_root, &service.yaml_path) {
+        Ok(Some(entry)) => entry.hash,
+        _ => return Vec::new(),
+    };
+    let Ok(entries) = fs::read_dir(service_dir) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p != &service.yaml_path)
+        .filter(|p| {
+            p.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e == "yaml" || e == "yml")
+        })
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let new_content = fs::read_to_string(&path).ok()?;
+            if !Blueprint::contains_configmap(&new_content) {
+                return None;
+            }
+            let old_content = Git::file_at_commit(&service.source_root, &last_deploy, &path)
+                .ok()?
+                .unwrap_or_default();
+            (old_content != new_content).then_some(FileChange {
+                path,
+                old_content,
+                new_content,
+            })
+        })
+        .collect()
+}
+
+fn show_configmap_changes(changes: &[FileChange], unified: bool) {
+    if changes.is_empty() {
+        return;
+    }
+
+    println!("⚠️  ConfigMaps changed since the last deploy of this service (applied together):");
+    for change in changes {
+        let filename = change
+            .path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("configmap.yaml");
+        Blueprint::show_diff(&change.old_content, &change.new_content, filename, unified);
+    }
+}
+
+/// Retags the images of the service's version group along with it: in the manifest being
+/// deployed and, for manifest files, in the other YAML files of its directory. Grouped
+/// tags missing from the registry need a confirmation.
+async fn retag_version_group(
+    env: &Environment,
+    service: &ServiceSource,
+    group: &[GroupedImage],
+    tag: &str,
+    content: &str,
+    auto_continue: bool,
+) -> Result<(String, Vec<FileChange>)> {
+    if group.is_empty() {
+        return Ok((content.to_string(), Vec::new()));
+    }
+    if let Some(grouped) = group.iter().find(|g| {
+        service
+            .containers
+            .iter()
+            .any(|c| split_image_ref(&c.image_path).0 == g.image)
+    }) {
+        return Err(anyhow::anyhow!(
+            "Version group image {} is a container image of {} itself",
+            grouped.image,
+            service.name
+        ));
+    }
+
+    let (updated, mut found) = if service.kustomize_overlay.is_some() {
+        // The overlay's `images` entries retag them wherever kustomize renders them.
+        let mut updated = content.to_string();
+        for grouped in group {
+            updated = Blueprint::update_kustomize_image_tag(
+                &updated,
+                &grouped.image,
+                &grouped.tag_for(tag),
+            )?;
+        }
+        (updated, group.iter().map(|g| g.image.clone()).collect())
+    } else {
+        retag_images(content, group, tag)
+    };
+
+    let mut changes = Vec::new();
+    if service.is_file_backed()
+        && service.kustomize_overlay.is_none()
+        && let Some(dir) = service.yaml_path.parent()
+        && let Ok(entries) = fs::read_dir(dir)
+    {
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p != &service.yaml_path)
+            .filter(|p| {
+                p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| e == "yaml" || e == "yml")
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Ok(old_content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let (new_content, found_here) = retag_images(&old_content, group, tag);
+            found.extend(found_here);
+            if new_content != old_content {
+                changes.push(FileChange {
+                    path,
+                    old_content,
+                    new_content,
+                });
+            }
+        }
+    }
+
+    for grouped in group.iter().filter(|g| !found.contains(&g.image)) {
+        println!(
+            "⚠️  Version group image {} is not referenced by the manifests of {}.",
+            grouped.image, service.name
+        );
+    }
+    let retagged: Vec<String> = group
+        .iter()
+        .filter(|g| found.contains(&g.image))
+        .map(|g| format!("{}:{}", g.image, g.tag_for(tag)))
+        .collect();
+    verify_version_group_tags(env, &retagged, auto_continue).await?;
+    Ok((updated, changes))
+}
 
-            pull_yaml_sources(&selected_env, false, "info")?;
+/// Sets the grouped tags on every reference to the grouped images in `content`. Returns
+/// the updated content and the images it references.
+fn retag_images(content: &str, group: &[GroupedImage], tag: &str) -> (String, HashSet<String>) {
+    let mut updated = content.to_string();
+    let mut found = HashSet::new();
+    for grouped in group {
+        if let Ok(retagged) =
+            Blueprint::update_image_tag(&updated, &grouped.image, &grouped.tag_for(tag))
+        {
+            updated = retagged;
+            found.insert(grouped.image.clone());
+        }
+    }
+    (updated, found)
+}
 
-            let selected_service =
-                resolve_service_with_ns_filter(&selected_env, service, namespace)?;
-            info::show_info(&selected_env, &selected_service).await?;
+/// Checks the registry has the tags a version group is about to reference; missing ones
+/// abort the deploy unless confirmed. Listing failures only warn.
+async fn verify_version_group_tags(
+    env: &Environment,
+    images: &[String],
+    auto_continue: bool,
+) -> Result<()> {
+    if mock::enabled() {
+        return Ok(());
+    }
+    let mut missing = Vec::new();
+    for image in images {
+        let (base, tag) = split_image_ref(image);
+        match offline::list_images(env, base).await {
+            Ok(found)
+                if found
+                    .iter()
+                    .any(|i| i.tags.iter().any(|t| Some(t.as_str()) == tag)) => {}
+            Ok(_) => missing.push(image.clone()),
+            Err(e) => println!("⚠️  Could not list the tags of {}: {}", base, e),
         }
-        Commands::Config { command } => match command {
-            ConfigCommands::Show => {
-                println!("{:#?}", config);
-            }
-            ConfigCommands::Path => {
-                let path = Config::get_config_path()?;
-                println!("{}", path.display());
-            }
-        },
+    }
+    if missing.is_empty() {
+        return Ok(());
     }
 
+    println!(
+        "❌ Version group tags missing from the registry: {}",
+        missing.join(", ")
+    );
+    if auto_continue
+        || !Confirm::new("Deploy the version group anyway?")
+            .with_default(false)
+            .prompt()?
+    {
+        return Err(anyhow::anyhow!(
+            "Version group images missing from the registry. Deployment aborted."
+        ));
+    }
     Ok(())
 }
 
-fn resolve_environment(config: &Config, input: Option<String>) -> Result<Environment> {
-    let env_names: Vec<String> = config.environments.iter().map(|e| e.name.clone()).collect();
+fn show_version_group_changes(changes: &[FileChange], unified: bool) {
+    if changes.is_empty() {
+        return;
+    }
 
-    let name = match input {
-        Some(val) => resolve_from_list("Environment", &env_names, val)?,
-        None => Select::new("Select Environment:", env_names.clone())
-            .prompt()
-            .context("Environment selection was cancelled")?,
-    };
+    println!("🔗 Version group images retagged in other files (applied together):");
+    for change in changes {
+        let filename = change
+            .path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("deployment.yaml");
+        Blueprint::show_diff(&change.old_content, &change.new_content, filename, unified);
+    }
+}
 
-    config
-        .environments
-        .iter()
-        .find(|e| e.name == name)
-        .cloned()
-        .context("Environment not found in config")
+/// Restores the service YAML and the files retagged with its version group.
+fn revert_local_changes(
+    yaml_path: &Path,
+    original_content: &str,
+    group_changes: &[FileChange],
+) -> Result<()> {
+    fs::write(yaml_path, original_content)?;
+    for change in group_changes {
+        fs::write(&change.path, &change.old_content)?;
+    }
+    Ok(())
 }
 
-fn get_service_display_name(
-    s: &ServiceSource,
-    all_services: &[ServiceSource],
-) -> String {
+fn build_release_notes(
+    config: &Config,
+    service: &ServiceSource,
+    new_tag: &str,
+) -> Option<ReleaseNotes> {
+    let source_repo = config.service(&service.name).source_repo?;
+    let (_, old_tag) = split_image_ref(&service.image_path);
+    let old_tag = old_tag?;
+
+    match Git::log_range(&source_repo, old_tag, new_tag) {
+        Ok(commits) => {
+            let notes = ReleaseNotes::from_commits(&commits);
+            (!notes.is_empty()).then_some(notes)
+        }
+        Err(e) => {
+            println!("⚠️  Could not build release notes: {}", e);
+            None
+        }
+    }
+}
+
+fn get_service_display_name(s: &ServiceSource, all_services: &[ServiceSource]) -> String {
     let duplicates: Vec<&ServiceSource> = all_services
         .iter()
         .filter(|&other| other.name == s.name)
@@ -478,9 +3118,12 @@ fn resolve_service_from_list(
 
     let selected_name = match input {
         Some(val) => resolve_from_list("Service", &display_names, val)?,
-        None => Select::new("Select Service:", display_names.clone())
-            .prompt()
-            .context("Service selection was cancelled")?,
+        None => {
+            interactive::require("--service")?;
+            Select::new("Select Service:", display_names.clone())
+                .prompt()
+                .context("Service selection was cancelled")?
+        }
     };
 
     service_map
@@ -496,35 +3139,152 @@ fn get_service_source_display_path(service: &ServiceSource) -> String {
     format!("[{}]/{}", service.source_name, relative_path.display())
 }
 
+fn save_dashboard_layout(layout: &config::DashboardLayout) -> Result<()> {
+    if mock::enabled() {
+        return Ok(());
+    }
+    update_config(|content| config::set_dashboard_layout(content, layout))
+}
+
+/// Rewrites the config file through `edit`, which receives its current content.
+fn update_config(edit: impl FnOnce(&str) -> Result<String>) -> Result<()> {
+    let config_path = Config::get_config_path()?;
+    let content = fs::read_to_string(&config_path)
+        .with_context(|| format!("Failed to read config file at {}", config_path.display()))?;
+    let updated = edit(&content)?;
+    fs::write(&config_path, updated)
+        .with_context(|| format!("Failed to write config file at {}", config_path.display()))
+}
+
+/// Appends an applied deploy to the local history; failing to write it only warns.
+#[allow(clippy::too_many_arguments)]
+fn record_deploy(
+    env: &Environment,
+    service: &ServiceSource,
+    new_tag: &str,
+    digest: Option<&str>,
+    applied_at: DateTime<Utc>,
+    outcome: Outcome,
+    phases: PhaseDurations,
+    approvals: &[String],
+    freeze_override: Option<&str>,
+) {
+    let old_tag = split_image_ref(&service.image_path).1.map(str::to_string);
+    let previous = History::open_default()
+        .and_then(|h| h.load())
+        .unwrap_or_default();
+
+    // A rollback goes back to the tag the last deploy replaced, as recorded by davit
+    // or, for deploys made outside of it, as committed in the YAML history.
+    let original = history::rollback_of(&previous, &env.name, &service.name, new_tag);
+    let is_rollback = original.is_some()
+        || (old_tag.as_deref() != Some(new_tag)
+            && blame::committed_previous_tag(service).as_deref() == Some(new_tag));
+    let rolls_back = original
+        .or_else(|| {
+            previous.iter().rev().find(|r| {
+                is_rollback
+                    && r.env == env.name
+                    && r.service == service.name
+                    && r.outcome == Outcome::Succeeded
+                    && Some(r.new_tag.as_str()) == old_tag.as_deref()
+            })
+        })
+        .map(|r| r.timestamp);
+
+    let record = DeployRecord {
+        timestamp: applied_at,
+        env: env.name.clone(),
+        service: service.name.clone(),
+        old_tag,
+        new_tag: new_tag.to_string(),
+        digest: digest.map(str::to_string),
+        user: history::current_user(),
+        duration_secs: (Utc::now() - applied_at).num_seconds().max(0) as u64,
+        outcome,
+        kind: if is_rollback {
+            DeployKind::Rollback
+        } else {
+            DeployKind::Deploy
+        },
+        rolls_back,
+        phases,
+        approvals: approvals.to_vec(),
+        freeze_override: freeze_override.map(str::to_string),
+    };
+    if let Err(e) = History::open_default().and_then(|h| h.record(&record)) {
+        println!("⚠️  Could not record the deploy in the history: {}", e);
+    } else if is_rollback {
+        match rolls_back {
+            Some(original) => println!(
+                "↩️  Recorded as a rollback of the deploy of {} on {}",
+                record.old_tag.as_deref().unwrap_or("-"),
+                original.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => println!(
+                "↩️  Recorded as a rollback of {}",
+                record.old_tag.as_deref().unwrap_or("-")
+            ),
+        }
+    }
+}
+
+fn sandbox_branch(env: &str, service: &str, tag: &str, now: DateTime<Local>) -> String {
+    let tag: String = tag
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!(
+        "davit-sandbox/{}/{}-{}-{}",
+        env,
+        service,
+        tag,
+        now.format("%Y%m%d%H%M%S")
+    )
+}
+
+/// Fails before anything is changed when the commits to come cannot be signed. Signing
+/// a probe may prompt for a passphrase or touch a hardware key, so only commands that
+/// commit call this.
+fn check_commit_signing(env: &Environment, dry_run: bool) -> Result<()> {
+    if dry_run {
+        return Ok(());
+    }
+    for source in unique_yaml_sources(env) {
+        Git::check_signing(&source.root)?;
+    }
+    Ok(())
+}
+
 fn pull_yaml_sources(env: &Environment, dry_run: bool, action: &str) -> Result<()> {
     let sources = unique_yaml_sources(env);
 
     if sources.is_empty() {
         return Ok(());
     }
-
-    println!("🔄 Checking for updates in configured YAML sources...");
+    output::progress("🔄 Checking for updates in configured YAML sources...");
     if sources.len() > 1 {
-        println!(
+        output::progress(format!(
             "Running up to {} git pulls in parallel and showing each repository output sequentially.",
             MAX_PARALLEL_PULLS
-        );
+        ));
     }
 
     let mut failures = Vec::new();
-    for (source, result) in collect_parallel_pull_results(&sources, MAX_PARALLEL_PULLS, move |source| {
-        Git::pull(&source.root, dry_run)
-    }) {
-        println!("  - [{}] {}", source.name, source.root.display());
+    for (source, result) in
+        collect_parallel_pull_results(&sources, MAX_PARALLEL_PULLS, move |source| {
+            Git::pull(&source.root, dry_run)
+        })
+    {
+        output::progress(format!("  - [{}] {}", source.name, source.root.display()));
         match result {
-            Ok(report) => {
-                print_git_pull_report(&report.stdout, false);
-                print_git_pull_report(&report.stderr, true);
-
-                if !report.success {
-                    failures.push((source, "git pull failed".to_string()));
-                }
-            }
+            Ok(report) => print_git_pull_report(&report.summary),
             Err(error) => failures.push((source, error.to_string())),
         }
     }
@@ -533,15 +3293,20 @@ fn pull_yaml_sources(env: &Environment, dry_run: bool, action: &str) -> Result<(
         return Ok(());
     }
 
-    println!("⚠️  Some YAML sources could not be updated:");
+    output::progress("⚠️  Some YAML sources could not be updated:");
     for (source, error) in &failures {
-        println!("  - [{}] {}: {}", source.name, source.root.display(), error);
+        output::progress(format!(
+            "  - [{}] {}: {}",
+            source.name,
+            source.root.display(),
+            error
+        ));
     }
 
-    if !Confirm::new(&format!("Do you want to continue with {} anyway?", action))
-        .with_default(false)
-        .prompt()?
-    {
+    if !interactive::confirm(
+        Confirm::new(&format!("Do you want to continue with {} anyway?", action))
+            .with_default(false),
+    )? {
         return Err(anyhow::anyhow!(
             "{} aborted by user after git pull failure.",
             capitalize_action(action)
@@ -592,13 +3357,9 @@ where
     results
 }
 
-fn print_git_pull_report(output: &str, is_stderr: bool) {
-    for line in output.lines() {
-        if is_stderr {
-            eprintln!("    {}", line);
-        } else {
-            println!("    {}", line);
-        }
+fn print_git_pull_report(summary: &str) {
+    for line in summary.lines() {
+        output::progress(format!("    {}", line));
     }
 }
 
@@ -627,18 +3388,19 @@ fn capitalize_action(action: &str) -> String {
     }
 }
 
-fn resolve_tag(
+/// The tag to deploy, with the registry listing it was found in.
+async fn resolve_tag(
     env: &Environment,
     service: &ServiceSource,
     input: Option<String>,
     wait_for_tag: Option<String>,
-) -> Result<String> {
+) -> Result<(String, Vec<ImageMetadata>)> {
     if let Some(tag) = wait_for_tag {
-        return wait_for_exact_tag(env, service, tag);
+        return wait_for_exact_tag(env, service, tag).await;
     }
 
     if let Some(tag) = input {
-        let images = fetch_service_images(env, service, true)?;
+        let images = fetch_service_images(env, service, true).await?;
         let available_tags = collect_available_tags(&images);
 
         if available_tags.is_empty() {
@@ -648,10 +3410,11 @@ fn resolve_tag(
             ));
         }
 
-        return resolve_from_list("Image tag", &available_tags, tag);
+        let tag = resolve_from_list("Image tag", &available_tags, tag)?;
+        return Ok((tag, images));
     }
 
-    let images = fetch_service_images(env, service, true)?;
+    let images = fetch_service_images(env, service, true).await?;
     let available_tags = collect_available_tags(&images);
 
     if available_tags.is_empty() {
@@ -673,6 +3436,7 @@ fn resolve_tag(
         })
         .collect();
 
+    interactive::require("--tag")?;
     let selection = Select::new("Select Image Tag:", options)
         .prompt()
         .context("Image selection was cancelled")?;
@@ -683,10 +3447,10 @@ fn resolve_tag(
         .unwrap_or("")
         .trim_end_matches(',')
         .to_string();
-    Ok(tag)
+    Ok((tag, images))
 }
 
-fn fetch_service_images(
+async fn fetch_service_images(
     env: &Environment,
     service: &ServiceSource,
     announce: bool,
@@ -700,11 +3464,15 @@ fn fetch_service_images(
         );
     }
 
-    let images = match Registry::fetch_images(&service.image_path) {
+    if mock::enabled() {
+        return Ok(mock::images());
+    }
+
+    let images = match offline::list_images(env, &service.image_path).await {
         Ok(imgs) => imgs,
         Err(e) => {
             if project == "MOCK_PROJECT" {
-                mock_images()
+                mock::images()
             } else {
                 return Err(e).context("Failed to fetch images from Artifact Registry");
             }
@@ -714,6 +3482,19 @@ fn fetch_service_images(
     Ok(images)
 }
 
+/// Digest the tag points at in `images`, recorded with the deploy; unknown when the
+/// listing failed or lacks the tag.
+fn tag_digest(images: &[ImageMetadata], tag: &str) -> Option<String> {
+    if blueprint::is_digest(tag) {
+        return Some(tag.to_string());
+    }
+    images
+        .iter()
+        .find(|image| image.tags.iter().any(|t| t == tag))?
+        .digest()
+        .map(str::to_string)
+}
+
 fn collect_available_tags(images: &[ImageMetadata]) -> Vec<String> {
     let mut seen = HashSet::new();
     let mut tags = Vec::new();
@@ -729,7 +3510,11 @@ fn collect_available_tags(images: &[ImageMetadata]) -> Vec<String> {
     tags
 }
 
-fn wait_for_exact_tag(env: &Environment, service: &ServiceSource, tag: String) -> Result<String> {
+async fn wait_for_exact_tag(
+    env: &Environment,
+    service: &ServiceSource,
+    tag: String,
+) -> Result<(String, Vec<ImageMetadata>)> {
     let mut attempt = 1;
 
     println!(
@@ -743,16 +3528,9 @@ fn wait_for_exact_tag(env: &Environment, service: &ServiceSource, tag: String) -
     );
 
     loop {
-        render_tag_wait_status(
-            &tag,
-            &service.name,
-            attempt,
-            "Checking registry",
-            None,
-            '.',
-        )?;
+        render_tag_wait_status(&tag, &service.name, attempt, "Checking registry", None, '.')?;
 
-        let images = fetch_service_images(env, service, false)?;
+        let images = fetch_service_images(env, service, false).await?;
         let available_tags = collect_available_tags(&images);
 
         if available_tags.iter().any(|available| available == &tag) {
@@ -765,7 +3543,7 @@ fn wait_for_exact_tag(env: &Environment, service: &ServiceSource, tag: String) -
                     tag, attempt
                 );
             }
-            return Ok(tag);
+            return Ok((tag, images));
         }
 
         wait_for_next_tag_check(service, &tag, attempt)?;
@@ -774,6 +3552,17 @@ fn wait_for_exact_tag(env: &Environment, service: &ServiceSource, tag: String) -
 }
 
 fn wait_for_next_tag_check(service: &ServiceSource, tag: &str, attempt: usize) -> Result<()> {
+    if !interactive::enabled() {
+        println!(
+            "Tag '{}' not available yet for {} ({} checks). Next check in {}s.",
+            tag,
+            service.name,
+            attempt,
+            TAG_RETRY_INTERVAL.as_secs()
+        );
+        thread::sleep(TAG_RETRY_INTERVAL);
+        return Ok(());
+    }
     let _raw_mode = RawModeGuard::new()?;
     let spinner = ['|', '/', '-', '\\'];
     let start = Instant::now();
@@ -845,7 +3634,11 @@ fn render_tag_wait_status(
         None => {
             print!(
                 "{} {} for '{}' on {}. Checks completed: {}. Press 'q' to cancel.",
-                marker, phase, tag, service_name, attempt.saturating_sub(1)
+                marker,
+                phase,
+                tag,
+                service_name,
+                attempt.saturating_sub(1)
             );
         }
     }
@@ -854,6 +3647,164 @@ fn render_tag_wait_status(
     Ok(())
 }
 
+/// Undoes the rollout of `tag`, bringing back the previous revision of the workload.
+fn roll_back_deploy(env: &Environment, service: &ServiceSource, tag: &str) -> Result<()> {
+    if mock::enabled() {
+        let (image, _) = split_image_ref(&service.image_path);
+        mock::apply(
+            &env.kubectl_context,
+            &service.name,
+            &format!("{}:{}", image, tag),
+            &service.image_path,
+        );
+    } else {
+        cluster::rollout_undo(
+            &env.kubectl_context,
+            &service.kind,
+            service.namespace.as_deref().unwrap_or("default"),
+            &service.name,
+        )
+        .context("Failed to roll back the workload")?;
+    }
+    audit::AuditEntry::new(audit::Action::RolledBack, &env.name, &service.name)
+        .detail(format!("rollout of {} undone", tag))
+        .record();
+    println!("↩️  {} rolled back to its previous revision.", service.name);
+    Ok(())
+}
+
+/// Confirms restarting a service: protected environments and services the way deploys
+/// confirm them, the others with a plain prompt. `false` when declined.
+fn confirm_restart(
+    config: &Config,
+    env: &Environment,
+    (service, tag): (&str, &str),
+    confirm_protected: Option<&str>,
+    approved_by: &[String],
+) -> Result<bool> {
+    if env.protected.unwrap_or(false)
+        || protection::service_protection(config, &env.name, service).confirm
+    {
+        let trail = deploy::AuditTrail::new(&env.name, vec![(service, tag)], false);
+        deploy::confirm_protection(
+            config,
+            env,
+            &trail,
+            confirm_protected,
+            approved_by,
+            "Restart",
+        )?;
+        return Ok(true);
+    }
+    interactive::confirm(
+        Confirm::new(&format!(
+            "Restart every pod of {} in {}?",
+            service, env.name
+        ))
+        .with_default(true),
+    )
+}
+
+/// Follows the rollout without the dashboard, for non-interactive runs.
+fn wait_for_rollout(env: &Environment, service: &ServiceSource) -> Result<()> {
+    if mock::enabled() {
+        println!("Demo mode: the simulated rollout completed.");
+        return Ok(());
+    }
+    cluster::rollout_status(
+        &env.kubectl_context,
+        &service.kind,
+        service.namespace.as_deref().unwrap_or("default"),
+        &service.name,
+        HEADLESS_ROLLOUT_TIMEOUT,
+    )
+}
+
+/// Counts down to the start of a scheduled deploy; `false` when cancelled with 'q'.
+fn wait_for_schedule(at: DateTime<Local>) -> Result<bool> {
+    println!(
+        "⏰ Deploy scheduled for {}. Keep this terminal open; press 'q' to cancel.",
+        at.format("%Y-%m-%d %H:%M %Z")
+    );
+    count_down(at)
+}
+
+/// Shows the time left until `at`; `false` when cancelled with 'q'.
+fn count_down(at: DateTime<Local>) -> Result<bool> {
+    if !interactive::enabled() {
+        println!("Starting at {}.", at.format("%H:%M:%S"));
+        while Local::now() < at {
+            thread::sleep(TAG_WAIT_POLL_INTERVAL);
+        }
+        return Ok(true);
+    }
+    let _raw_mode = RawModeGuard::new()?;
+    loop {
+        // Wall-clock comparison, so that a suspended machine starts as soon as it resumes.
+        let remaining = (at - Local::now()).num_seconds();
+        clear_tag_wait_status_line()?;
+        if remaining <= 0 {
+            return Ok(true);
+        }
+        print!("{}", countdown_text(remaining));
+        io::stdout().flush()?;
+
+        if event::poll(TAG_WAIT_POLL_INTERVAL)?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+        {
+            clear_tag_wait_status_line()?;
+            return Ok(false);
+        }
+    }
+}
+
+fn countdown_text(remaining_secs: i64) -> String {
+    format!(
+        "Starting in {}:{:02}:{:02}. Press 'q' to cancel.",
+        remaining_secs / 3600,
+        remaining_secs % 3600 / 60,
+        remaining_secs % 60
+    )
+}
+
+/// Checks, when a scheduled deploy starts, that what was reviewed is still what gets
+/// applied: the manifests did not move upstream and the service was not locked meanwhile.
+fn revalidate_scheduled_plan(
+    env: &Environment,
+    service: &ServiceSource,
+    original_content: &str,
+    group_changes: &[FileChange],
+    git_dry_run: bool,
+) -> Result<()> {
+    if service.is_file_backed() {
+        pull_yaml_sources(env, git_dry_run, "deployment")?;
+        let reviewed = std::iter::once((service.yaml_path.as_path(), original_content)).chain(
+            group_changes
+                .iter()
+                .map(|c| (c.path.as_path(), c.old_content.as_str())),
+        );
+        for (path, content) in reviewed {
+            if fs::read_to_string(path).ok().as_deref() != Some(content) {
+                return Err(anyhow::anyhow!(
+                    "{} changed since the deploy was reviewed",
+                    path.display()
+                ));
+            }
+        }
+    }
+    if !mock::enabled()
+        && let Some(current) = Config::load()?
+            .environments
+            .into_iter()
+            .find(|e| e.name == env.name)
+    {
+        current.ensure_unlocked(&service.name)?;
+    }
+    Ok(())
+}
+
 fn clear_tag_wait_status_line() -> Result<()> {
     crossterm::execute!(io::stdout(), MoveToColumn(0), Clear(ClearType::CurrentLine))
         .context("Failed to refresh tag wait status line")?;
@@ -864,7 +3815,7 @@ struct RawModeGuard;
 
 impl RawModeGuard {
     fn new() -> Result<Self> {
-        enable_raw_mode().context("Failed to enable terminal raw mode while waiting for image tag")?;
+        enable_raw_mode().context("Failed to enable terminal raw mode")?;
         Ok(Self)
     }
 }
@@ -875,28 +3826,6 @@ impl Drop for RawModeGuard {
     }
 }
 
-fn mock_images() -> Vec<ImageMetadata> {
-    use chrono::Duration;
-    let now = Utc::now();
-    vec![
-        ImageMetadata {
-            tags: vec!["v1.2.3".to_string(), "latest".to_string()],
-            update_time: now - Duration::hours(2),
-            name: "auth-service@sha256:abcdef123456789".to_string(),
-        },
-        ImageMetadata {
-            tags: vec!["v1.2.2".to_string()],
-            update_time: now - Duration::days(1),
-            name: "auth-service@sha256:123456789abcdef".to_string(),
-        },
-        ImageMetadata {
-            tags: vec!["v1.1.0".to_string()],
-            update_time: now - Duration::days(5),
-            name: "auth-service@sha256:987654321fedcba".to_string(),
-        },
-    ]
-}
-
 /// Generic disambiguation logic
 fn resolve_from_list(label: &str, items: &[String], input: String) -> Result<String> {
     // 1. Exact match
@@ -910,16 +3839,16 @@ fn resolve_from_list(label: &str, items: &[String], input: String) -> Result<Str
     match matches.len() {
         0 => {
             println!("No {} matches '{}'.", label.to_lowercase(), input);
+            interactive::require(&format!("An existing {}", label.to_lowercase()))?;
             Select::new(&format!("Select {}:", label), items.to_vec())
                 .prompt()
                 .context(format!("{} selection was cancelled", label))
         }
         1 => {
             let suggest = matches[0];
-            if Confirm::new(&format!("Did you mean '{}'?", suggest))
-                .with_default(true)
-                .prompt()?
-            {
+            if interactive::confirm(
+                Confirm::new(&format!("Did you mean '{}'?", suggest)).with_default(true),
+            )? {
                 Ok(suggest.clone())
             } else {
                 Select::new(&format!("Select {}:", label), items.to_vec())
@@ -927,22 +3856,26 @@ fn resolve_from_list(label: &str, items: &[String], input: String) -> Result<Str
                     .context(format!("{} selection was cancelled", label))
             }
         }
-        _ => Select::new(
-            &format!(
-                "Multiple matches for '{}'. Select {}:",
-                input,
-                label.to_lowercase()
-            ),
-            matches.into_iter().cloned().collect(),
-        )
-        .prompt()
-        .context(format!("{} selection was cancelled", label)),
+        _ => {
+            interactive::require(&format!("An unambiguous {}", label.to_lowercase()))?;
+            Select::new(
+                &format!(
+                    "Multiple matches for '{}'. Select {}:",
+                    input,
+                    label.to_lowercase()
+                ),
+                matches.into_iter().cloned().collect(),
+            )
+            .prompt()
+            .context(format!("{} selection was cancelled", label))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{TimeZone, Utc};
     use std::path::PathBuf;
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::time::Duration;
@@ -957,8 +3890,15 @@ mod tests {
             source_name: "main".to_string(),
             source_root: PathBuf::from("/root"),
             yaml_path: PathBuf::from("/root/dir1/deploy.yaml"),
+            document_index: 0,
+            kustomize_overlay: None,
             namespace: Some("ns1".to_string()),
             selector: None,
+            containers: Vec::new(),
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
         };
         let all = vec![s.clone()];
         assert_eq!(get_service_display_name(&s, &all), "service1");
@@ -974,8 +3914,15 @@ mod tests {
             source_name: "main".to_string(),
             source_root: PathBuf::from("/root"),
             yaml_path: PathBuf::from("/root/dir1/deploy.yaml"),
+            document_index: 0,
+            kustomize_overlay: None,
             namespace: Some("ns1".to_string()),
             selector: None,
+            containers: Vec::new(),
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
         };
         let s2 = ServiceSource {
             name: "service1".to_string(),
@@ -985,8 +3932,15 @@ mod tests {
             source_name: "main".to_string(),
             source_root: PathBuf::from("/root"),
             yaml_path: PathBuf::from("/root/dir2/deploy.yaml"),
+            document_index: 0,
+            kustomize_overlay: None,
             namespace: Some("ns2".to_string()),
             selector: None,
+            containers: Vec::new(),
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
         };
         let all = vec![s1.clone(), s2.clone()];
         assert_eq!(get_service_display_name(&s1, &all), "service1 (ns1)");
@@ -1003,8 +3957,15 @@ mod tests {
             source_name: "main".to_string(),
             source_root: PathBuf::from("/root"),
             yaml_path: PathBuf::from("/root/dir1/deploy.yaml"),
+            document_index: 0,
+            kustomize_overlay: None,
             namespace: Some("ns1".to_string()),
             selector: None,
+            containers: Vec::new(),
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
         };
         let s2 = ServiceSource {
             name: "service1".to_string(),
@@ -1014,8 +3975,15 @@ mod tests {
             source_name: "demo".to_string(),
             source_root: PathBuf::from("/root"),
             yaml_path: PathBuf::from("/root/dir2/deploy.yaml"),
+            document_index: 0,
+            kustomize_overlay: None,
             namespace: Some("ns1".to_string()),
             selector: None,
+            containers: Vec::new(),
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
         };
         let all = vec![s1.clone(), s2.clone()];
         assert_eq!(
@@ -1028,6 +3996,76 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_update_manifest_pins_by_tag_or_digest() {
+        let service = ServiceSource {
+            name: "auth-api".to_string(),
+            kind: "Deployment".to_string(),
+            image_path: "gcr.io/p/auth-api:v1".to_string(),
+            container_name: "auth-api".to_string(),
+            source_name: "main".to_string(),
+            source_root: PathBuf::from("/root"),
+            yaml_path: PathBuf::from("/root/auth-api.yaml"),
+            document_index: 0,
+            kustomize_overlay: None,
+            namespace: None,
+            selector: None,
+            containers: Vec::new(),
+            helm_release: None,
+            helm_chart: None,
+            live: false,
+            templated: false,
+        };
+        let container = ContainerImage {
+            name: "auth-api".to_string(),
+            image_path: "gcr.io/p/auth-api@sha256:abc123".to_string(),
+        };
+        let content =
+            "containers:\n  - name: auth-api\n    image: gcr.io/p/auth-api@sha256:abc123\n";
+
+        let by_tag =
+            update_manifest(&service, content, &[(container.clone(), "v2".to_string())]).unwrap();
+        assert!(by_tag.contains("image: gcr.io/p/auth-api:v2\n"));
+        let by_digest = update_manifest(
+            &service,
+            &by_tag,
+            &[(container, "sha256:def456".to_string())],
+        )
+        .unwrap();
+        assert!(by_digest.contains("image: gcr.io/p/auth-api@sha256:def456\n"));
+    }
+
+    #[test]
+    fn test_countdown_text_shows_hours_minutes_and_seconds_left() {
+        assert_eq!(
+            countdown_text(9),
+            "Starting in 0:00:09. Press 'q' to cancel."
+        );
+        assert_eq!(
+            countdown_text(3725),
+            "Starting in 1:02:05. Press 'q' to cancel."
+        );
+    }
+
+    #[test]
+    fn test_tag_digest_reads_the_fetched_listing() {
+        let images = vec![ImageMetadata {
+            tags: vec!["v1.2.3".to_string()],
+            update_time: Utc::now(),
+            name: "service@sha256:abcdef1".to_string(),
+        }];
+
+        assert_eq!(
+            tag_digest(&images, "v1.2.3").as_deref(),
+            Some("sha256:abcdef1")
+        );
+        assert_eq!(
+            tag_digest(&[], "sha256:abcdef2").as_deref(),
+            Some("sha256:abcdef2")
+        );
+        assert_eq!(tag_digest(&images, "v9.9.9"), None);
+    }
+
     #[test]
     fn test_collect_available_tags_preserves_order_and_deduplicates() {
         let now = Utc::now();
@@ -1054,6 +4092,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_deploy_summary_lists_containers_with_distinct_tags() {
+        let app = ContainerImage {
+            name: "app".to_string(),
+            image_path: "gcr.io/p/app:v1".to_string(),
+        };
+        let worker = ContainerImage {
+            name: "worker".to_string(),
+            image_path: "gcr.io/p/worker:v1".to_string(),
+        };
+
+        let same = vec![
+            (app.clone(), "v2".to_string()),
+            (worker.clone(), "v2".to_string()),
+        ];
+        assert_eq!(deploy_summary("svc", &same), "svc to v2");
+
+        let distinct = vec![(app, "v2".to_string()), (worker, "v3".to_string())];
+        assert_eq!(
+            deploy_summary("svc", &distinct),
+            "svc (app to v2, worker to v3)"
+        );
+    }
+
+    #[test]
+    fn test_retag_images_applies_group_tags_to_every_reference() {
+        let group = vec![
+            GroupedImage {
+                image: "gcr.io/p/payments-migrator".to_string(),
+                tag: "{tag}".to_string(),
+            },
+            GroupedImage {
+                image: "gcr.io/p/payments-cron".to_string(),
+                tag: "{tag}-cron".to_string(),
+            },
+            GroupedImage {
+                image: "gcr.io/p/payments-reports".to_string(),
+                tag: "{tag}".to_string(),
+            },
+        ];
+        let content = r#"apiVersion: batch/v1
+kind: Job
+spec:
+  template:
+    spec:
+      containers:
+        - name: migrate
+          image: gcr.io/p/payments-migrator:v1.4.0
+---
+apiVersion: batch/v1
+kind: CronJob
+spec:
+  jobTemplate:
+    spec:
+      template:
+        spec:
+          initContainers:
+            - name: migrate
+              image: gcr.io/p/payments-migrator:v1.4.0
+          containers:
+            - name: settle
+              image: gcr.io/p/payments-cron:v1.4.0-cron
+"#;
+
+        let (updated, found) = retag_images(content, &group, "v1.5.0");
+        assert_eq!(updated.matches("payments-migrator:v1.5.0").count(), 2);
+        assert!(updated.contains("payments-cron:v1.5.0-cron"));
+        assert!(!updated.contains("v1.4.0"));
+        assert_eq!(
+            found,
+            HashSet::from([
+                "gcr.io/p/payments-migrator".to_string(),
+                "gcr.io/p/payments-cron".to_string()
+            ])
+        );
+    }
+
     #[test]
     fn test_deploy_wait_for_tag_conflicts_with_tag() {
         let parse = Cli::try_parse_from([
@@ -1073,6 +4188,12 @@ mod tests {
         assert!(parse.is_ok());
     }
 
+    #[test]
+    fn test_deploy_at_conflicts_with_progressive() {
+        let parse = Cli::try_parse_from(["davit", "deploy", "--at", "02:00", "--progressive"]);
+        assert!(parse.is_err());
+    }
+
     #[test]
     fn test_deploy_auto_apply_accepts_flag() {
         let parse = Cli::try_parse_from(["davit", "deploy", "--auto-apply"]);
@@ -1134,4 +4255,52 @@ mod tests {
         assert_eq!(results.len(), 12);
         assert_eq!(max_seen.load(Ordering::SeqCst), 5);
     }
+
+    #[test]
+    fn test_sandbox_branch_sanitizes_tag() {
+        let now = Local.with_ymd_and_hms(2026, 10, 15, 9, 30, 5).unwrap();
+        assert_eq!(
+            sandbox_branch("production", "auth-api", "v1.2.3+build/7", now),
+            "davit-sandbox/production/auth-api-v1.2.3-build-7-20261015093005"
+        );
+    }
+
+    #[test]
+    fn test_non_interactive_restart_of_protected_targets_needs_the_flag() {
+        interactive::configure(false);
+        let config: Config = toml::from_str(
+            r#"environments = []
+
+[[protected_services]]
+services = ["payment-*"]
+"#,
+        )
+        .unwrap();
+        let production = Environment {
+            name: "production".to_string(),
+            protected: Some(true),
+            ..Default::default()
+        };
+        let staging = Environment {
+            name: "staging".to_string(),
+            ..Default::default()
+        };
+
+        let err = confirm_restart(&config, &production, ("auth-api", "v1"), None, &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("--confirm-protected production"));
+        assert!(confirm_restart(&config, &staging, ("payment-api", "v1"), None, &[]).is_err());
+        assert!(
+            confirm_restart(
+                &config,
+                &staging,
+                ("payment-api", "v1"),
+                Some("production"),
+                &[]
+            )
+            .is_err()
+        );
+        assert!(confirm_restart(&config, &staging, ("auth-api", "v1"), None, &[]).unwrap());
+    }
 }
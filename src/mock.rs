@@ -0,0 +1,631 @@
+//! Demo mode enabled with `DAVIT_MOCK=1`: fake environments backed by generated manifests,
+//! a fake registry and a simulated cluster whose pods roll over time and emit synthetic logs.
+
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http::{Request, Response, StatusCode};
+use http_body::Frame;
+use http_body_util::{BodyExt, Full, StreamBody, combinators::UnsyncBoxBody};
+use kube::Client;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::blueprint::{find_pod_spec, split_image_ref};
+use crate::config::{Config, Environment};
+use crate::registry::ImageMetadata;
+
+const MOCK_ENV_VAR: &str = "DAVIT_MOCK";
+const CONTEXT_PREFIX: &str = "mock-";
+const DEMO_NAMESPACE: &str = "demo";
+const DEMO_REGISTRY: &str = "europe-west1-docker.pkg.dev/davit-demo/apps";
+const DEMO_TAG: &str = "v1.2.2";
+const DEMO_ENVIRONMENTS: [&str; 2] = ["staging", "production"];
+const DEMO_SERVICES: [(&str, i64); 3] =
+    [("auth-api", 3), ("billing-worker", 2), ("web-frontend", 2)];
+
+/// Delay between the creation of two new pods during a simulated rollout.
+const POD_STEP: Duration = Duration::from_secs(4);
+const POD_PENDING: Duration = Duration::from_secs(2);
+const POD_STARTING: Duration = Duration::from_secs(5);
+const LOG_INTERVAL: Duration = Duration::from_millis(700);
+
+pub fn enabled() -> bool {
+    env::var(MOCK_ENV_VAR).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+fn workspace() -> PathBuf {
+    env::temp_dir().join("davit-mock")
+}
+
+fn demo_manifest(name: &str, replicas: i64) -> String {
+    format!(
+        r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: {name}
+  namespace: {DEMO_NAMESPACE}
+spec:
+  replicas: {replicas}
+  selector:
+    matchLabels:
+      app: {name}
+  template:
+    metadata:
+      labels:
+        app: {name}
+    spec:
+      containers:
+      - name: {name}
+        image: {DEMO_REGISTRY}/{name}:{DEMO_TAG}
+        resources:
+          requests:
+            cpu: 250m
+            memory: 256Mi
+          limits:
+            cpu: 500m
+            memory: 512Mi
+        readinessProbe:
+          httpGet:
+            path: /ready
+            port: 8080
+        livenessProbe:
+          httpGet:
+            path: /health
+            port: 8080
+"#
+    )
+}
+
+/// Writes the demo manifests (once) and returns a config pointing at them.
+pub fn config() -> Result<Config> {
+    let mut environments = Vec::new();
+    for env_name in DEMO_ENVIRONMENTS {
+        let dir = workspace().join(env_name);
+        fs::create_dir_all(&dir)?;
+        for (name, replicas) in DEMO_SERVICES {
+            let path = dir.join(format!("{}.yaml", name));
+            if !path.exists() {
+                fs::write(&path, demo_manifest(name, replicas))?;
+            }
+        }
+
+        environments.push(Environment {
+            name: env_name.to_string(),
+            env_yaml_dir: dir,
+            kubectl_context: format!("{}{}", CONTEXT_PREFIX, env_name),
+            protected: Some(env_name == "production"),
+            ..Default::default()
+        });
+    }
+
+    Ok(Config {
+        environments,
+        services: BTreeMap::new(),
+        pricing: None,
+        dashboard: Default::default(),
+        retry: Default::default(),
+        compatibility: Vec::new(),
+        protected_services: Vec::new(),
+        contexts: BTreeMap::new(),
+        defaults: Default::default(),
+        audit_log: None,
+        signing: Default::default(),
+        annotations: Default::default(),
+        logs: Default::default(),
+    })
+}
+
+/// Registry listing used instead of Artifact Registry.
+pub fn images() -> Vec<ImageMetadata> {
+    use chrono::Duration;
+    let now = Utc::now();
+    vec![
+        ImageMetadata {
+            tags: vec!["v1.2.3".to_string(), "latest".to_string()],
+            update_time: now - Duration::hours(2),
+            name: "auth-service@sha256:abcdef123456789".to_string(),
+        },
+        ImageMetadata {
+            tags: vec!["v1.2.2".to_string()],
+            update_time: now - Duration::days(1),
+            name: "auth-service@sha256:123456789abcdef".to_string(),
+        },
+        ImageMetadata {
+            tags: vec!["v1.1.0".to_string()],
+            update_time: now - Duration::days(5),
+            name: "auth-service@sha256:987654321fedcba".to_string(),
+        },
+    ]
+}
+
+#[derive(Debug, Clone)]
+struct Rollout {
+    old_image: String,
+    new_image: String,
+    started: Instant,
+}
+
+fn rollouts() -> &'static Mutex<HashMap<(String, String), Rollout>> {
+    static ROLLOUTS: OnceLock<Mutex<HashMap<(String, String), Rollout>>> = OnceLock::new();
+    ROLLOUTS.get_or_init(Default::default)
+}
+
+/// Simulates `kubectl apply` by starting a rollout from `old_image` to `new_image`.
+pub fn apply(context: &str, workload: &str, old_image: &str, new_image: &str) {
+    rollouts().lock().unwrap().insert(
+        (context.to_string(), workload.to_string()),
+        Rollout {
+            old_image: old_image.to_string(),
+            new_image: new_image.to_string(),
+            started: Instant::now(),
+        },
+    );
+}
+
+/// Builds a Kubernetes client answering from the simulated cluster.
+pub fn client(context: &str) -> Client {
+    let context = context.to_string();
+    let service = tower::service_fn(move |request: Request<kube::client::Body>| {
+        let response = handle(&context, &request);
+        async move { Ok::<_, Infallible>(response) }
+    });
+    Client::new(service, DEMO_NAMESPACE)
+}
+
+/// Workload as described by the demo manifest of an environment.
+struct DemoWorkload {
+    container: String,
+    image: String,
+    replicas: i64,
+}
+
+fn demo_workload(context: &str, name: &str) -> Option<DemoWorkload> {
+    let env_name = context.strip_prefix(CONTEXT_PREFIX)?;
+    let content =
+        fs::read_to_string(workspace().join(env_name).join(format!("{}.yaml", name))).ok()?;
+    let manifest: serde_yaml::Value = serde_yaml::from_str(&content).ok()?;
+    let container = find_pod_spec(&manifest)?.get("containers")?.get(0)?;
+
+    Some(DemoWorkload {
+        container: container.get("name")?.as_str()?.to_string(),
+        image: container.get("image")?.as_str()?.to_string(),
+        replicas: manifest
+            .get("spec")
+            .and_then(|s| s.get("replicas"))
+            .and_then(|r| r.as_i64())
+            .unwrap_or(1),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SimulatedPod {
+    name: String,
+    image: String,
+    phase: &'static str,
+    ready: bool,
+    created: DateTime<Utc>,
+}
+
+/// Pods of a workload `elapsed` after a rollout started: new pods are created one every
+/// `POD_STEP`, go Pending → Running → Ready, and each ready pod replaces an old one.
+fn simulated_pods(
+    name: &str,
+    replicas: i64,
+    rollout: Option<(&Rollout, Duration)>,
+    current_image: &str,
+) -> Vec<SimulatedPod> {
+    let now = Utc::now();
+    let settled = now - chrono::Duration::hours(3);
+    let pod = |hash: &str, index: i64, image: &str, phase, ready, created| SimulatedPod {
+        name: format!(
+            "{}-{}-{}",
+            name,
+            hash,
+            POD_SUFFIXES[index as usize % POD_SUFFIXES.len()]
+        ),
+        image: image.to_string(),
+        phase,
+        ready,
+        created,
+    };
+
+    let Some((rollout, elapsed)) = rollout else {
+        return (0..replicas)
+            .map(|i| pod("7d9f8c6b5", i, current_image, "Running", true, settled))
+            .collect();
+    };
+
+    let mut pods = Vec::new();
+    let mut replaced = 0;
+    for i in 0..replicas {
+        let Some(age) = elapsed.checked_sub(POD_STEP * i as u32) else {
+            break;
+        };
+        let created = now - chrono::Duration::from_std(age).unwrap_or_default();
+        let (phase, ready) = if age < POD_PENDING {
+            ("Pending", false)
+        } else if age < POD_STARTING {
+            ("Running", false)
+        } else {
+            replaced += 1;
+            ("Running", true)
+        };
+        pods.push(pod(
+            "5b6c4a8d2",
+            i,
+            &rollout.new_image,
+            phase,
+            ready,
+            created,
+        ));
+    }
+    for i in replaced..replicas {
+        pods.push(pod(
+            "7d9f8c6b5",
+            i,
+            &rollout.old_image,
+            "Running",
+            true,
+            settled,
+        ));
+    }
+    pods
+}
+
+const POD_SUFFIXES: [&str; 6] = ["x2k9p", "m4q7z", "c8v1n", "t5w3r", "h6j2b", "f9d4s"];
+
+fn handle(
+    context: &str,
+    request: &Request<kube::client::Body>,
+) -> Response<UnsyncBoxBody<Bytes, Infallible>> {
+    let path = request.uri().path();
+    let query = request.uri().query().unwrap_or_default();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    // /api/v1/namespaces/{ns}/{resource}[/{name}[/{sub}]] or /apis/{group}/{version}/namespaces/...
+    let rest = match segments.as_slice() {
+        ["api", _, rest @ ..] => rest,
+        ["apis", _, _, rest @ ..] => rest,
+        _ => return not_found(),
+    };
+    let (namespace, rest) = match rest {
+        ["namespaces", namespace, rest @ ..] => (*namespace, rest),
+        rest => (DEMO_NAMESPACE, rest),
+    };
+
+    match rest {
+        ["pods"] => {
+            let workload = query_param(query, "labelSelector")
+                .and_then(|s| s.strip_prefix("app=").map(|s| s.to_string()))
+                .unwrap_or_default();
+            let container = demo_workload(context, &workload)
+                .map(|w| w.container)
+                .unwrap_or_else(|| "main".to_string());
+            let items: Vec<Value> = pods(context, &workload)
+                .iter()
+                .map(|p| pod_json(p, namespace, &workload, &container))
+                .collect();
+            list_response("PodList", items)
+        }
+        ["services"] => list_response(
+            "ServiceList",
+            DEMO_SERVICES
+                .iter()
+                .map(|(name, _)| {
+                    json!({
+                        "metadata": {"name": name, "namespace": namespace},
+                        "spec": {"selector": {"app": name}, "ports": [{"port": 80}]},
+                    })
+                })
+                .collect(),
+        ),
+        ["endpointslices"] => {
+            let service = query_param(query, "labelSelector")
+                .and_then(|s| {
+                    s.strip_prefix("kubernetes.io/service-name=")
+                        .map(|s| s.to_string())
+                })
+                .unwrap_or_default();
+            let endpoints: Vec<Value> = pods(context, &service)
+                .iter()
+                .filter(|p| p.phase == "Running")
+                .map(|p| {
+                    json!({
+                        "addresses": ["10.8.0.1"],
+                        "conditions": {"ready": p.ready},
+                        "targetRef": {"kind": "Pod", "name": p.name},
+                    })
+                })
+                .collect();
+            list_response(
+                "EndpointSliceList",
+                vec![json!({
+                    "metadata": {"name": format!("{}-demo", service)},
+                    "addressType": "IPv4",
+                    "endpoints": endpoints,
+                })],
+            )
+        }
+        ["ingresses"] => list_response(
+            "IngressList",
+            vec![json!({
+                "metadata": {"name": "demo", "namespace": namespace},
+                "spec": {
+                    "tls": [{"hosts": ["demo.davit.dev"]}],
+                    "rules": [{"host": "demo.davit.dev", "http": {"paths": [
+                        {"path": "/", "pathType": "Prefix",
+                         "backend": {"service": {"name": "web-frontend", "port": {"number": 80}}}},
+                        {"path": "/auth", "pathType": "Prefix",
+                         "backend": {"service": {"name": "auth-api", "port": {"number": 80}}}},
+                    ]}}],
+                },
+            })],
+        ),
+        ["pods", pod, "log"] => log_stream(pod),
+        ["deployments", name] => match deployment_json(context, name, namespace) {
+            Some(deployment) => json_response(deployment),
+            None => not_found(),
+        },
+        ["serviceaccounts", name] => json_response(json!({
+            "apiVersion": "v1",
+            "kind": "ServiceAccount",
+            "metadata": {"name": name, "namespace": namespace}
+        })),
+        [_] => list_response("List", Vec::new()),
+        _ => not_found(),
+    }
+}
+
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| percent_decode(v))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn pods(context: &str, workload: &str) -> Vec<SimulatedPod> {
+    let Some(demo) = demo_workload(context, workload) else {
+        return Vec::new();
+    };
+    let rollouts = rollouts().lock().unwrap();
+    let rollout = rollouts
+        .get(&(context.to_string(), workload.to_string()))
+        .map(|r| (r, r.started.elapsed()));
+    simulated_pods(workload, demo.replicas, rollout, &demo.image)
+}
+
+fn pod_json(pod: &SimulatedPod, namespace: &str, workload: &str, container: &str) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": pod.name,
+            "namespace": namespace,
+            "labels": {"app": workload},
+            "creationTimestamp": pod.created.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        },
+        "spec": {"containers": [{"name": container, "image": pod.image}]},
+        "status": {
+            "phase": pod.phase,
+            "containerStatuses": [{
+                "name": container,
+                "image": pod.image,
+                "imageID": "",
+                "ready": pod.ready,
+                "restartCount": 0,
+            }],
+        },
+    })
+}
+
+fn deployment_json(context: &str, name: &str, namespace: &str) -> Option<Value> {
+    let demo = demo_workload(context, name)?;
+    let pods = pods(context, name);
+    let template_image = rollouts()
+        .lock()
+        .unwrap()
+        .get(&(context.to_string(), name.to_string()))
+        .map(|r| r.new_image.clone())
+        .unwrap_or(demo.image);
+    let (template_name, _) = split_image_ref(&template_image);
+    let updated = pods
+        .iter()
+        .filter(|p| p.image == template_image || split_image_ref(&p.image).0 != template_name)
+        .count();
+    let ready = pods.iter().filter(|p| p.ready).count();
+
+    Some(json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": {"name": name, "namespace": namespace},
+        "spec": {
+            "replicas": demo.replicas,
+            "selector": {"matchLabels": {"app": name}},
+            "template": {
+                "metadata": {"labels": {"app": name}},
+                "spec": {"containers": [{"name": demo.container, "image": template_image}]},
+            },
+        },
+        "status": {
+            "replicas": pods.len(),
+            "updatedReplicas": updated,
+            "readyReplicas": ready,
+            "availableReplicas": ready,
+        },
+    }))
+}
+
+const LOG_MESSAGES: [(&str, &str); 6] = [
+    ("INFO", "GET /api/v1/sessions 200 12ms"),
+    ("INFO", "POST /api/v1/tokens 201 48ms"),
+    ("DEBUG", "cache hit ratio 0.93"),
+    ("INFO", "GET /health 200 1ms"),
+    ("WARN", "slow query on accounts table took 812ms"),
+    ("INFO", "published 12 events to billing topic"),
+];
+
+fn log_stream(pod: &str) -> Response<UnsyncBoxBody<Bytes, Infallible>> {
+    let pod = pod.to_string();
+    let stream = futures::stream::unfold(0usize, move |index| {
+        let pod = pod.clone();
+        async move {
+            if index > 0 {
+                tokio::time::sleep(LOG_INTERVAL).await;
+            }
+            let (level, message) = if index == 0 {
+                ("INFO", format!("starting {}", pod))
+            } else {
+                let (level, message) = LOG_MESSAGES[index % LOG_MESSAGES.len()];
+                (level, message.to_string())
+            };
+            let line = json!({
+                "severity": level,
+                "timestamp": Utc::now().to_rfc3339(),
+                "message": message,
+            });
+            let frame = Frame::data(Bytes::from(format!("{}\n", line)));
+            Some((Ok::<_, Infallible>(frame), index + 1))
+        }
+    });
+
+    Response::new(StreamBody::new(stream).boxed_unsync())
+}
+
+fn list_response(kind: &str, items: Vec<Value>) -> Response<UnsyncBoxBody<Bytes, Infallible>> {
+    json_response(json!({"apiVersion": "v1", "kind": kind, "metadata": {}, "items": items}))
+}
+
+fn json_response(value: Value) -> Response<UnsyncBoxBody<Bytes, Infallible>> {
+    Response::new(Full::new(Bytes::from(value.to_string())).boxed_unsync())
+}
+
+fn not_found() -> Response<UnsyncBoxBody<Bytes, Infallible>> {
+    let status = json!({
+        "apiVersion": "v1",
+        "kind": "Status",
+        "status": "Failure",
+        "message": "not found in the simulated cluster",
+        "reason": "NotFound",
+        "code": 404,
+    });
+    let mut response = json_response(status);
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::core::v1::Pod;
+    use kube::Api;
+    use kube::api::ListParams;
+
+    #[tokio::test]
+    async fn test_client_lists_demo_pods() -> Result<()> {
+        config()?;
+        let pods: Api<Pod> = Api::namespaced(client("mock-staging"), DEMO_NAMESPACE);
+        let list = pods
+            .list(&ListParams::default().labels("app=auth-api"))
+            .await?;
+
+        assert_eq!(list.items.len(), 3);
+        assert!(
+            list.items
+                .iter()
+                .all(|p| { p.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running") })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_routes_demo_traffic() -> Result<()> {
+        config()?;
+        let labels = BTreeMap::from([("app".to_string(), "auth-api".to_string())]);
+        let services =
+            crate::traffic::fetch(client("mock-staging"), DEMO_NAMESPACE, &labels).await?;
+
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].serving_pods.len(), 3);
+        assert_eq!(services[0].urls, vec!["https://demo.davit.dev/auth"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulated_pods_replace_old_pods_progressively() {
+        let rollout = Rollout {
+            old_image: "img:v1".to_string(),
+            new_image: "img:v2".to_string(),
+            started: Instant::now(),
+        };
+        let summary = |elapsed: u64| {
+            simulated_pods(
+                "api",
+                2,
+                Some((&rollout, Duration::from_secs(elapsed))),
+                "img:v1",
+            )
+            .iter()
+            .map(|p| (p.image.clone(), p.phase, p.ready))
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            summary(1),
+            vec![
+                ("img:v2".to_string(), "Pending", false),
+                ("img:v1".to_string(), "Running", true),
+                ("img:v1".to_string(), "Running", true),
+            ]
+        );
+        assert_eq!(
+            summary(6),
+            vec![
+                ("img:v2".to_string(), "Running", true),
+                ("img:v2".to_string(), "Running", false),
+                ("img:v1".to_string(), "Running", true),
+            ]
+        );
+        assert_eq!(
+            summary(20),
+            vec![
+                ("img:v2".to_string(), "Running", true),
+                ("img:v2".to_string(), "Running", true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+use tokio::runtime::Handle;
+
+use crate::mock;
+
+/// Announces a deploy event: printed, shown as a desktop notification when a notifier is
+/// installed, and posted to the environment's webhook. Failures only print a warning.
+pub fn send(webhook: Option<&str>, message: &str) {
+    println!("🔔 {}", message);
+    if mock::enabled() {
+        return;
+    }
+    desktop(message);
+    if let Some(url) = webhook {
+        // Callers run on the multi-threaded runtime, possibly outside of async code.
+        let posted = tokio::task::block_in_place(|| Handle::current().block_on(post(url, message)));
+        if let Err(e) = posted {
+            println!("⚠️  Could not post the notification: {:#}", e);
+        }
+    }
+}
+
+/// Posts `{"text": message}`, the payload of Slack, Mattermost and Google Chat webhooks.
+async fn post(url: &str, message: &str) -> Result<()> {
+    reqwest::Client::new()
+        .post(url)
+        .json(&serde_json::json!({ "text": message }))
+        .send()
+        .await
+        .context("request failed")?
+        .error_for_status()
+        .context("webhook rejected the notification")?;
+    Ok(())
+}
+
+fn desktop(message: &str) {
+    let script = format!("display notification {:?} with title \"davit\"", message);
+    let notifiers: [(&str, Vec<&str>); 2] = [
+        ("notify-send", vec!["davit", message]),
+        ("osascript", vec!["-e", &script]),
+    ];
+    for (program, args) in notifiers {
+        let shown = Command::new(program)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success());
+        if shown {
+            return;
+        }
+    }
+}
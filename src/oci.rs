@@ -0,0 +1,550 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use directories::BaseDirs;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::registry::ImageMetadata;
+
+const DOCKER_HUB_HOST: &str = "registry-1.docker.io";
+const MAX_PARALLEL_MANIFESTS: usize = 8;
+const INDEX_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+const MANIFEST_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+
+/// Username and password (or token) for a registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Lists the images of a repository through the Docker Registry HTTP API v2, newest
+/// first: every tag is resolved to its manifest, and dated by the `created` field of
+/// its config blob. Without `credentials`, they are read from `~/.docker/config.json`.
+pub async fn fetch_images(
+    base_image: &str,
+    credentials: Option<Credentials>,
+) -> Result<Vec<ImageMetadata>> {
+    let repository = Repository::parse(base_image);
+    let credentials = match credentials {
+        Some(credentials) => Some(credentials),
+        None => docker_credentials(&repository.host)?,
+    };
+    let client = Client {
+        http: reqwest::Client::new(),
+        repository,
+        credentials,
+        authorization: Mutex::new(None),
+    };
+
+    // Listing tags first settles authentication before the parallel manifest reads.
+    let tags = client.tags().await?;
+    let resolved: Vec<(String, Result<ResolvedTag>)> = futures::stream::iter(tags)
+        .map(|tag| {
+            let client = &client;
+            async move {
+                let resolved = client.resolve(&tag).await;
+                (tag, resolved)
+            }
+        })
+        .buffer_unordered(MAX_PARALLEL_MANIFESTS)
+        .collect()
+        .await;
+
+    let mut images: Vec<ImageMetadata> = Vec::new();
+    for (tag, resolved) in resolved {
+        let resolved =
+            resolved.with_context(|| format!("Failed to read {}:{}", base_image, tag))?;
+        let name = match &resolved.digest {
+            Some(digest) => format!("{}@{}", base_image, digest),
+            None => format!("{}:{}", base_image, tag),
+        };
+        match images.iter_mut().find(|i| i.name == name) {
+            Some(existing) => existing.tags.push(tag),
+            None => images.push(ImageMetadata {
+                tags: vec![tag],
+                update_time: resolved.created,
+                name,
+            }),
+        }
+    }
+    images.sort_by_key(|image| std::cmp::Reverse(image.update_time));
+    Ok(images)
+}
+
+/// Registry host and repository of an image path, Docker Hub's defaults applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Repository {
+    host: String,
+    name: String,
+}
+
+impl Repository {
+    fn parse(base_image: &str) -> Self {
+        match base_image.split_once('/') {
+            Some((host, name))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                let host = if host == "docker.io" {
+                    DOCKER_HUB_HOST
+                } else {
+                    host
+                };
+                Self {
+                    host: host.to_string(),
+                    name: name.to_string(),
+                }
+            }
+            Some(_) => Self {
+                host: DOCKER_HUB_HOST.to_string(),
+                name: base_image.to_string(),
+            },
+            None => Self {
+                host: DOCKER_HUB_HOST.to_string(),
+                name: format!("library/{}", base_image),
+            },
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("https://{}/v2/{}/{}", self.host, self.name, path)
+    }
+}
+
+struct ResolvedTag {
+    digest: Option<String>,
+    created: DateTime<Utc>,
+}
+
+struct Client {
+    http: reqwest::Client,
+    repository: Repository,
+    credentials: Option<Credentials>,
+    /// `Authorization` header obtained from the registry's challenge, once answered.
+    authorization: Mutex<Option<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagList {
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    config: Option<Descriptor>,
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Descriptor {
+    digest: String,
+    platform: Option<Platform>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageConfig {
+    created: Option<DateTime<Utc>>,
+}
+
+impl Client {
+    async fn tags(&self) -> Result<Vec<String>> {
+        let mut tags = Vec::new();
+        let mut url = self.repository.url("tags/list?n=1000");
+        loop {
+            let response = self.get(&url, &[]).await?;
+            let next = response
+                .headers()
+                .get(reqwest::header::LINK)
+                .and_then(|link| link.to_str().ok())
+                .and_then(next_link)
+                .map(|path| format!("https://{}{}", self.repository.host, path));
+            let page: TagList = response
+                .json()
+                .await
+                .context("Failed to parse the registry tag list")?;
+            tags.extend(page.tags.unwrap_or_default());
+            match next {
+                Some(next) => url = next,
+                None => break,
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Digest and creation time of a tag; for multi-platform images, those of the index
+    /// and of the linux/amd64 image (else the first one).
+    async fn resolve(&self, tag: &str) -> Result<ResolvedTag> {
+        let accept: Vec<&str> = INDEX_TYPES.iter().chain(&MANIFEST_TYPES).copied().collect();
+        let response = self
+            .get(&self.repository.url(&format!("manifests/{}", tag)), &accept)
+            .await?;
+        let digest = response
+            .headers()
+            .get("docker-content-digest")
+            .and_then(|d| d.to_str().ok())
+            .map(str::to_string);
+        let mut manifest: Manifest = response
+            .json()
+            .await
+            .context("Failed to parse the image manifest")?;
+
+        if manifest.config.is_none() {
+            let image = manifest
+                .manifests
+                .iter()
+                .find(|m| {
+                    m.platform
+                        .as_ref()
+                        .is_some_and(|p| p.os == "linux" && p.architecture == "amd64")
+                })
+                .or(manifest.manifests.first())
+                .context("The image index lists no manifest")?;
+            manifest = self
+                .get(
+                    &self.repository.url(&format!("manifests/{}", image.digest)),
+                    &MANIFEST_TYPES,
+                )
+                .await?
+                .json()
+                .await
+                .context("Failed to parse the image manifest")?;
+        }
+
+        let config = manifest
+            .config
+            .context("The image manifest has no config")?;
+        let config: ImageConfig = self
+            .get(
+                &self.repository.url(&format!("blobs/{}", config.digest)),
+                &[],
+            )
+            .await?
+            .json()
+            .await
+            .context("Failed to parse the image config")?;
+        Ok(ResolvedTag {
+            digest,
+            created: config.created.unwrap_or(DateTime::UNIX_EPOCH),
+        })
+    }
+
+    /// GET answering the registry's authentication challenge on the first 401.
+    async fn get(&self, url: &str, accept: &[&str]) -> Result<reqwest::Response> {
+        let mut response = self.send(url, accept).await?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            let challenge = response
+                .headers()
+                .get(reqwest::header::WWW_AUTHENTICATE)
+                .and_then(|c| c.to_str().ok())
+                .map(str::to_string)
+                .with_context(|| {
+                    format!(
+                        "{} requires authentication without a challenge",
+                        self.repository.host
+                    )
+                })?;
+            let authorization = self.authenticate(&challenge).await?;
+            *self.authorization.lock().unwrap() = Some(authorization);
+            response = self.send(url, accept).await?;
+        }
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "Registry {} returned {}: {}",
+                self.repository.host,
+                status,
+                body.trim()
+            ));
+        }
+        Ok(response)
+    }
+
+    async fn send(&self, url: &str, accept: &[&str]) -> Result<reqwest::Response> {
+        let mut request = self.http.get(url);
+        if !accept.is_empty() {
+            request = request.header(reqwest::header::ACCEPT, accept.join(", "));
+        }
+        let authorization = self.authorization.lock().unwrap().clone();
+        if let Some(authorization) = authorization {
+            request = request.header(reqwest::header::AUTHORIZATION, authorization);
+        }
+        request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach registry {}", self.repository.host))
+    }
+
+    /// `Authorization` header answering a `Basic` or `Bearer` challenge; bearer tokens are
+    /// requested from the challenge's realm for pull access to the repository.
+    async fn authenticate(&self, challenge: &str) -> Result<String> {
+        let (scheme, params) = parse_challenge(challenge);
+        if scheme.eq_ignore_ascii_case("basic") {
+            let credentials = self.credentials.as_ref().with_context(|| {
+                format!(
+                    "{} requires credentials; run `docker login {}`",
+                    self.repository.host, self.repository.host
+                )
+            })?;
+            let encoded =
+                STANDARD.encode(format!("{}:{}", credentials.username, credentials.password));
+            return Ok(format!("Basic {}", encoded));
+        }
+
+        let realm = params
+            .get("realm")
+            .with_context(|| format!("Unsupported registry challenge: {}", challenge))?;
+        let mut query = vec![("scope", format!("repository:{}:pull", self.repository.name))];
+        if let Some(service) = params.get("service") {
+            query.push(("service", service.clone()));
+        }
+        let mut request = self.http.get(realm).query(&query);
+        if let Some(credentials) = &self.credentials {
+            request = request.basic_auth(&credentials.username, Some(&credentials.password));
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach the token service {}", realm))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "Token service {} returned {} for {}",
+                realm,
+                status,
+                self.repository.name
+            ));
+        }
+        let token: serde_json::Value = response
+            .json()
+            .await
+            .context("Failed to parse the registry token")?;
+        let token = token
+            .get("token")
+            .or_else(|| token.get("access_token"))
+            .and_then(|t| t.as_str())
+            .context("The token service returned no token")?;
+        Ok(format!("Bearer {}", token))
+    }
+}
+
+/// Scheme and parameters of a `WWW-Authenticate` header such as
+/// `Bearer realm="https://auth.docker.io/token",service="registry.docker.io"`.
+fn parse_challenge(challenge: &str) -> (&str, BTreeMap<String, String>) {
+    let (scheme, rest) = challenge
+        .trim()
+        .split_once(' ')
+        .unwrap_or((challenge.trim(), ""));
+    let mut params = BTreeMap::new();
+    let mut rest = rest.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let (value, remaining) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        params.insert(key, value.to_string());
+        rest = remaining.trim();
+    }
+    (scheme, params)
+}
+
+/// Path of the `rel="next"` page in a `Link` header.
+fn next_link(link: &str) -> Option<&str> {
+    link.split(',')
+        .find(|part| part.contains("rel=\"next\""))?
+        .split_once('<')?
+        .1
+        .split_once('>')
+        .map(|(path, _)| path)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DockerConfig {
+    #[serde(default)]
+    auths: BTreeMap<String, DockerAuth>,
+    creds_store: Option<String>,
+    #[serde(default)]
+    cred_helpers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DockerAuth {
+    auth: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl DockerConfig {
+    /// Credential helper for `host`, when credentials are not stored in the file.
+    fn helper(&self, host: &str) -> Option<&str> {
+        self.cred_helpers
+            .get(host)
+            .or(self.creds_store.as_ref())
+            .map(String::as_str)
+    }
+
+    /// Credentials stored in `auths` for `host`, keyed by host or URL.
+    fn stored(&self, host: &str) -> Option<Credentials> {
+        let hub = host == DOCKER_HUB_HOST;
+        let auth = self.auths.iter().find_map(|(key, auth)| {
+            let key = key
+                .trim_start_matches("https://")
+                .trim_start_matches("http://");
+            let key = key.split('/').next().unwrap_or(key);
+            (key == host || (hub && (key == "index.docker.io" || key == "docker.io")))
+                .then_some(auth)
+        })?;
+        if let (Some(username), Some(password)) = (&auth.username, &auth.password) {
+            return Some(Credentials {
+                username: username.clone(),
+                password: password.clone(),
+            });
+        }
+        let decoded = STANDARD.decode(auth.auth.as_ref()?).ok()?;
+        let (username, password) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+        Some(Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
+fn docker_config_path() -> Option<PathBuf> {
+    match std::env::var_os("DOCKER_CONFIG") {
+        Some(dir) => Some(PathBuf::from(dir).join("config.json")),
+        None => Some(BaseDirs::new()?.home_dir().join(".docker/config.json")),
+    }
+}
+
+/// Credentials for `host` from the Docker config, through its credential helpers when
+/// the file does not store them; `None` for anonymous access.
+fn docker_credentials(host: &str) -> Result<Option<Credentials>> {
+    let Some(path) = docker_config_path().filter(|p| p.exists()) else {
+        return Ok(None);
+    };
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let config: DockerConfig = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    if let Some(credentials) = config.stored(host) {
+        return Ok(Some(credentials));
+    }
+    match config.helper(host) {
+        Some(helper) => helper_credentials(helper, host),
+        None => Ok(None),
+    }
+}
+
+/// Runs `docker-credential-<helper> get`; a helper without credentials for `host` means
+/// anonymous access.
+fn helper_credentials(helper: &str, host: &str) -> Result<Option<Credentials>> {
+    let program = format!("docker-credential-{}", helper);
+    let mut child = Command::new(&program)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to execute {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(host.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct HelperCredentials {
+        username: String,
+        secret: String,
+    }
+    let found: HelperCredentials = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Failed to parse the output of {}", program))?;
+    Ok(Some(Credentials {
+        username: found.username,
+        password: found.secret,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repository_challenge_and_docker_config() {
+        assert_eq!(
+            Repository::parse("nginx").url("tags/list"),
+            "https://registry-1.docker.io/v2/library/nginx/tags/list"
+        );
+        assert_eq!(
+            Repository::parse("harbor.acme.io:8443/apps/auth-api"),
+            Repository {
+                host: "harbor.acme.io:8443".to_string(),
+                name: "apps/auth-api".to_string(),
+            }
+        );
+
+        let (scheme, params) = parse_challenge(
+            r#"Bearer realm="https://harbor.acme.io/service/token",service="harbor-registry",scope="repository:apps/auth-api:pull,push""#,
+        );
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(params["realm"], "https://harbor.acme.io/service/token");
+        assert_eq!(params["service"], "harbor-registry");
+        assert_eq!(params["scope"], "repository:apps/auth-api:pull,push");
+        assert_eq!(
+            next_link(r#"</v2/apps/auth-api/tags/list?last=v2&n=1000>; rel="next""#),
+            Some("/v2/apps/auth-api/tags/list?last=v2&n=1000")
+        );
+
+        let config: DockerConfig = serde_json::from_str(
+            r#"{
+  "auths": {
+    "https://index.docker.io/v1/": {"auth": "aHViLXVzZXI6aHViLXBhc3M="},
+    "harbor.acme.io": {"username": "robot$ci", "password": "s3cret"}
+  },
+  "credHelpers": {"123456789012.dkr.ecr.eu-west-1.amazonaws.com": "ecr-login"}
+}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.stored(DOCKER_HUB_HOST).map(|c| c.username),
+            Some("hub-user".to_string())
+        );
+        assert_eq!(
+            config.stored("harbor.acme.io").map(|c| c.password),
+            Some("s3cret".to_string())
+        );
+        assert_eq!(config.stored("registry.internal.corp"), None);
+        assert_eq!(
+            config.helper("123456789012.dkr.ecr.eu-west-1.amazonaws.com"),
+            Some("ecr-login")
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+use crate::blueprint::split_image_ref;
+use crate::config::Environment;
+use crate::registry::{ImageMetadata, Registry};
+
+/// Registry listings written by `davit registry export`, keyed by image without tag, for
+/// environments deploying from a network without registry access.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegistryExport {
+    pub exported_at: DateTime<Utc>,
+    pub images: BTreeMap<String, Vec<ImageMetadata>>,
+}
+
+/// Images of a repository, from the registry or, for offline environments, from their
+/// local images file.
+pub async fn list_images(env: &Environment, image_path: &str) -> Result<Vec<ImageMetadata>> {
+    match &env.offline {
+        Some(offline) => images(&offline.images, image_path),
+        None => Registry::fetch_images(image_path, env.registry_access_token.as_deref()).await,
+    }
+}
+
+/// Images of `image_path` in a `davit registry export` file (`.json`) or in a mirror
+/// manifest listing one image reference per line.
+pub fn images(path: &Path, image_path: &str) -> Result<Vec<ImageMetadata>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read offline images file {}", path.display()))?;
+    let (base, _) = split_image_ref(image_path);
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let export: RegistryExport = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse registry export {}", path.display()))?;
+        return Ok(export.images.get(base).cloned().unwrap_or_default());
+    }
+    let listed_at = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+    Ok(mirror_images(&content, base, listed_at))
+}
+
+/// Entries of a mirror manifest (`repo:tag`, `repo@digest` or `repo:tag@digest`, `#`
+/// comments) for one repository, tags of the same digest merged.
+fn mirror_images(content: &str, base: &str, listed_at: DateTime<Utc>) -> Vec<ImageMetadata> {
+    let mut images: Vec<ImageMetadata> = Vec::new();
+    let references = content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty());
+    for reference in references {
+        let (tagged, digest) = match reference.split_once('@') {
+            Some((tagged, digest)) => (tagged, Some(digest)),
+            None => (reference, None),
+        };
+        let (image, tag) = split_image_ref(tagged);
+        if image != base {
+            continue;
+        }
+        let name = match digest {
+            Some(digest) => format!("{}@{}", image, digest),
+            None => tagged.to_string(),
+        };
+        let tag = tag.map(str::to_string);
+        match images
+            .iter_mut()
+            .find(|i| digest.is_some() && i.name == name)
+        {
+            Some(existing) => existing.tags.extend(tag),
+            None => images.push(ImageMetadata {
+                tags: tag.into_iter().collect(),
+                update_time: listed_at,
+                name,
+            }),
+        }
+    }
+    images
+}
+
+/// Runs `kubectl apply -f -` through a wrapper command such as
+/// `["ssh", "bastion.prod", "kubectl"]`, piping the manifests since the wrapper cannot
+/// read local files.
+pub fn apply_through(wrapper: &[String], manifests: &str, server_dry_run: bool) -> Result<Output> {
+    let (program, args) = wrapper
+        .split_first()
+        .context("The kubectl wrapper is empty")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .arg("apply")
+        .args(server_dry_run.then_some("--dry-run=server"))
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to execute the kubectl wrapper {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(manifests.as_bytes())?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_images_merges_tags_per_digest() {
+        let manifest = r#"# synced 2026-10-14
+gcr.io/p/app:v2@sha256:bbb
+gcr.io/p/app:latest@sha256:bbb
+gcr.io/p/app:v1   # no digest recorded
+gcr.io/p/worker:v2@sha256:ccc
+"#;
+        let images = mirror_images(manifest, "gcr.io/p/app", Utc::now());
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].tags, vec!["v2", "latest"]);
+        assert_eq!(images[0].digest(), Some("sha256:bbb"));
+        assert_eq!(images[1].tags, vec!["v1"]);
+        assert_eq!(images[1].digest(), None);
+    }
+}
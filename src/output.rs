@@ -0,0 +1,142 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use crate::blueprint::split_image_ref;
+use crate::config::ServiceSource;
+use crate::registry::ImageMetadata;
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Sets the format of command results; text until then. In JSON mode only the result
+/// goes to stdout, progress messages move to stderr so the output can be piped to `jq`.
+pub fn configure(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn json() -> bool {
+    FORMAT.get().copied().unwrap_or_default() == OutputFormat::Json
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Prints a progress message: on stdout in text mode, on stderr in JSON mode.
+pub fn progress(message: impl Display) {
+    if json() {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// A service as listed by `davit services`.
+#[derive(Debug, Serialize)]
+pub struct ServiceEntry {
+    pub name: String,
+    pub kind: String,
+    pub namespace: Option<String>,
+    pub image: String,
+    pub tag: Option<String>,
+    pub source: String,
+    pub yaml_path: String,
+}
+
+impl From<&ServiceSource> for ServiceEntry {
+    fn from(service: &ServiceSource) -> Self {
+        let (image, tag) = split_image_ref(&service.image_path);
+        Self {
+            name: service.name.clone(),
+            kind: service.kind.clone(),
+            namespace: service.namespace.clone(),
+            image: image.to_string(),
+            tag: tag.map(str::to_string),
+            source: service.source_name.clone(),
+            yaml_path: service.yaml_path.display().to_string(),
+        }
+    }
+}
+
+/// An image as listed by `davit tags`.
+#[derive(Debug, Serialize)]
+pub struct TagEntry {
+    pub tags: Vec<String>,
+    pub digest: Option<String>,
+    pub pushed_at: DateTime<Utc>,
+}
+
+impl From<&ImageMetadata> for TagEntry {
+    fn from(image: &ImageMetadata) -> Self {
+        Self {
+            tags: image.tags.clone(),
+            digest: image.digest().map(str::to_string),
+            pushed_at: image.update_time,
+        }
+    }
+}
+
+pub fn print_services(services: &[ServiceSource]) {
+    if services.is_empty() {
+        println!("No services found.");
+        return;
+    }
+    println!(
+        "{:<25} {:<12} {:<15} {:<20} IMAGE",
+        "SERVICE", "KIND", "NAMESPACE", "TAG"
+    );
+    for service in services {
+        let entry = ServiceEntry::from(service);
+        println!(
+            "{:<25} {:<12} {:<15} {:<20} {}",
+            entry.name,
+            entry.kind,
+            entry.namespace.as_deref().unwrap_or("-"),
+            entry.tag.as_deref().unwrap_or("-"),
+            entry.image
+        );
+    }
+}
+
+pub fn print_tags(images: &[ImageMetadata]) {
+    if images.is_empty() {
+        println!("No images found.");
+        return;
+    }
+    println!("{:<30} {:>10}  DIGEST", "TAGS", "AGE");
+    for image in images {
+        println!(
+            "{:<30} {:>10}  {}",
+            image.display_tag(),
+            image.age_string(),
+            image.short_hash()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_entry_splits_digest() {
+        let image = ImageMetadata {
+            tags: vec!["v2".to_string(), "latest".to_string()],
+            update_time: Utc::now(),
+            name: "gcr.io/p/app@sha256:bbb".to_string(),
+        };
+        let value = serde_json::to_value(TagEntry::from(&image)).unwrap();
+        assert_eq!(value["tags"], serde_json::json!(["v2", "latest"]));
+        assert_eq!(value["digest"], "sha256:bbb");
+    }
+}
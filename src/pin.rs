@@ -0,0 +1,163 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::blueprint::{Blueprint, is_digest, split_image_ref};
+use crate::config::{Environment, ServiceSource};
+use crate::mock;
+use crate::offline;
+use crate::registry::ImageMetadata;
+
+/// A container image rewritten from its tag to the digest the tag points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedImage {
+    pub service: String,
+    pub container: String,
+    pub image: String,
+    pub tag: String,
+    pub digest: String,
+}
+
+/// A manifest file with its pinned content.
+#[derive(Debug, Clone)]
+pub struct PinnedFile {
+    pub path: PathBuf,
+    pub source_root: PathBuf,
+    pub original: String,
+    pub updated: String,
+}
+
+/// Everything `davit pin` would change in an environment, and what it leaves alone.
+#[derive(Debug, Default)]
+pub struct PinPlan {
+    pub pins: Vec<PinnedImage>,
+    /// `service/container` with the reason it is not pinned.
+    pub skipped: Vec<(String, String)>,
+    pub files: Vec<PinnedFile>,
+}
+
+/// Resolves the tag of every container of the services to its digest in the registry
+/// and rewrites their manifests accordingly. Files are read but not written.
+pub async fn plan(env: &Environment, services: &[ServiceSource]) -> Result<PinPlan> {
+    let mut plan = PinPlan::default();
+    let mut listings: HashMap<String, Vec<ImageMetadata>> = HashMap::new();
+    let mut files: BTreeMap<PathBuf, PinnedFile> = BTreeMap::new();
+
+    for service in services {
+        for container in &service.containers {
+            let label = format!("{}/{}", service.name, container.name);
+            if !service.is_file_backed() {
+                plan.skipped
+                    .push((label, "not deployed from a manifest file".to_string()));
+                continue;
+            }
+            if service.templated {
+                plan.skipped
+                    .push((label, "rendered from a templated YAML".to_string()));
+                continue;
+            }
+            if service.helm_chart.is_some() {
+                plan.skipped
+                    .push((label, "tag set in Helm values".to_string()));
+                continue;
+            }
+            let (image, tag) = split_image_ref(&container.image_path);
+            let tag = match tag {
+                Some(tag) if is_digest(tag) => continue,
+                Some(tag) => tag,
+                None => {
+                    plan.skipped.push((label, "no tag".to_string()));
+                    continue;
+                }
+            };
+
+            if !listings.contains_key(image) {
+                let images = if mock::enabled() {
+                    mock::images()
+                } else {
+                    offline::list_images(env, image)
+                        .await
+                        .with_context(|| format!("Failed to list the images of {}", image))?
+                };
+                listings.insert(image.to_string(), images);
+            }
+            let Some(digest) = listings[image]
+                .iter()
+                .find(|i| i.tags.iter().any(|t| t == tag))
+                .and_then(ImageMetadata::digest)
+            else {
+                plan.skipped
+                    .push((label, format!("tag {} not found in the registry", tag)));
+                continue;
+            };
+
+            if !files.contains_key(&service.yaml_path) {
+                let original = fs::read_to_string(&service.yaml_path).with_context(|| {
+                    format!(
+                        "Failed to read YAML file at {}",
+                        service.yaml_path.display()
+                    )
+                })?;
+                files.insert(
+                    service.yaml_path.clone(),
+                    PinnedFile {
+                        path: service.yaml_path.clone(),
+                        source_root: service.source_root.clone(),
+                        updated: original.clone(),
+                        original,
+                    },
+                );
+            }
+            let file = files
+                .get_mut(&service.yaml_path)
+                .expect("file was just read");
+            file.updated = if service.kustomize_overlay.is_some() {
+                Blueprint::update_kustomize_image_tag(&file.updated, image, digest)?
+            } else {
+                Blueprint::update_image_tag_in_document(
+                    &file.updated,
+                    service.document_index,
+                    image,
+                    digest,
+                )?
+            };
+            plan.pins.push(PinnedImage {
+                service: service.name.clone(),
+                container: container.name.clone(),
+                image: image.to_string(),
+                tag: tag.to_string(),
+                digest: digest.to_string(),
+            });
+        }
+    }
+
+    plan.files = files
+        .into_values()
+        .filter(|f| f.updated != f.original)
+        .collect();
+    Ok(plan)
+}
+
+impl PinPlan {
+    pub fn print(&self) {
+        for pin in &self.pins {
+            println!(
+                "📌 {}/{}: {}:{} → @{}",
+                pin.service,
+                pin.container,
+                pin.image,
+                pin.tag,
+                short_digest(&pin.digest)
+            );
+        }
+        for (label, reason) in &self.skipped {
+            println!("⏭️  {}: {}", label, reason);
+        }
+    }
+}
+
+fn short_digest(digest: &str) -> &str {
+    let end = digest.len().min("sha256:".len() + 12);
+    &digest[..end]
+}
@@ -0,0 +1,153 @@
+use crate::config::ResourcePolicy;
+use crate::resources::{Quantity, WorkloadResources, parse_cpu, parse_memory};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// Evaluates an environment's resource policy against the workload about to be applied.
+pub fn evaluate(policy: &ResourcePolicy, workload: &WorkloadResources) -> Vec<PolicyViolation> {
+    let mut violations = Vec::new();
+
+    if let Some(max) = policy.max_replicas {
+        let replicas = workload.replicas.unwrap_or(1);
+        if replicas > max {
+            violations.push(PolicyViolation {
+                rule: "max_replicas",
+                message: format!("{} replicas exceed the maximum of {}", replicas, max),
+            });
+        }
+    }
+
+    for container in &workload.containers {
+        if policy.require_limits {
+            let missing: Vec<&str> = [
+                ("cpu", &container.limits.cpu),
+                ("memory", &container.limits.memory),
+            ]
+            .into_iter()
+            .filter(|(_, limit)| limit.is_none())
+            .map(|(name, _)| name)
+            .collect();
+            if !missing.is_empty() {
+                violations.push(PolicyViolation {
+                    rule: "require_limits",
+                    message: format!(
+                        "container '{}' has no {} limit",
+                        container.name,
+                        missing.join(" or ")
+                    ),
+                });
+            }
+        }
+
+        let checks = [
+            (
+                "max_cpu_limit",
+                "cpu",
+                &policy.max_cpu_limit,
+                &container.limits.cpu,
+                parse_cpu as fn(&str) -> Option<f64>,
+            ),
+            (
+                "max_memory_limit",
+                "memory",
+                &policy.max_memory_limit,
+                &container.limits.memory,
+                parse_memory,
+            ),
+        ];
+        for (rule, resource, max, limit, parse) in checks {
+            if let Some(max) = max
+                && let Some(max_value) = parse(max)
+                && let Some(Quantity { raw, value }) = limit
+                && *value > max_value
+            {
+                violations.push(PolicyViolation {
+                    rule,
+                    message: format!(
+                        "container '{}' {} limit {} exceeds {}",
+                        container.name, resource, raw, max
+                    ),
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workload(yaml: &str) -> WorkloadResources {
+        WorkloadResources::from_workload(&serde_yaml::from_str(yaml).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_reports_each_violated_rule() {
+        let policy = ResourcePolicy {
+            max_replicas: Some(4),
+            require_limits: true,
+            max_cpu_limit: Some("1".to_string()),
+            max_memory_limit: Some("2Gi".to_string()),
+        };
+        let workload = workload(
+            r#"
+kind: Deployment
+spec:
+  replicas: 6
+  template:
+    spec:
+      containers:
+      - name: main
+        resources:
+          limits:
+            cpu: 1500m
+            memory: 1Gi
+      - name: sidecar
+        resources:
+          limits:
+            cpu: 100m
+"#,
+        );
+
+        let rules: Vec<&str> = evaluate(&policy, &workload)
+            .iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(
+            rules,
+            vec!["max_replicas", "max_cpu_limit", "require_limits"]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_passes_compliant_workload() {
+        let policy = ResourcePolicy {
+            max_replicas: Some(4),
+            require_limits: true,
+            ..Default::default()
+        };
+        let workload = workload(
+            r#"
+kind: Deployment
+spec:
+  replicas: 2
+  template:
+    spec:
+      containers:
+      - name: main
+        resources:
+          limits:
+            cpu: 500m
+            memory: 512Mi
+"#,
+        );
+
+        assert!(evaluate(&policy, &workload).is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{Context, Result};
+use k8s_openapi::api::apps::v1::DaemonSet;
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    Api, Client,
+    api::{DeleteParams, ListParams, PostParams},
+};
+use std::time::{Duration, Instant};
+
+const PAUSE_IMAGE: &str = "registry.k8s.io/pause:3.10";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Pulls `images` onto the nodes the workload can run on before the real apply, through a
+/// short-lived DaemonSet whose init containers use them. The DaemonSet is always deleted.
+/// Returns on how many nodes the images were pulled; a pull failure is an error, while a
+/// timeout only cuts the warm-up short.
+pub async fn prepull(
+    client: Client,
+    namespace: &str,
+    service: &str,
+    images: &[String],
+    pod_spec: Option<&serde_yaml::Value>,
+) -> Result<usize> {
+    let name = format!("davit-prepull-{}", service);
+    let api: Api<DaemonSet> = Api::namespaced(client.clone(), namespace);
+    let pods: Api<Pod> = Api::namespaced(client, namespace);
+
+    // A leftover from an interrupted run would hold an older image list.
+    if api.get_opt(&name).await?.is_some() {
+        api.delete(&name, &DeleteParams::background()).await?;
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    api.create(
+        &PostParams::default(),
+        &daemon_set(&name, images, pod_spec)?,
+    )
+    .await
+    .context("Failed to create the pre-pull DaemonSet")?;
+
+    let result = wait_for_pulls(&api, &pods, &name).await;
+    if let Err(e) = api.delete(&name, &DeleteParams::background()).await {
+        println!(
+            "⚠️  Could not delete the pre-pull DaemonSet {}: {}",
+            name, e
+        );
+    }
+    result
+}
+
+async fn wait_for_pulls(api: &Api<DaemonSet>, pods: &Api<Pod>, name: &str) -> Result<usize> {
+    let started = Instant::now();
+    let mut reported = None;
+    loop {
+        let desired = api
+            .get_status(name)
+            .await?
+            .status
+            .map(|s| s.desired_number_scheduled)
+            .unwrap_or(0) as usize;
+        let pods = pods
+            .list(&ListParams::default().labels(&format!("davit-prepull={}", name)))
+            .await?
+            .items;
+        if let Some(failure) = pods.iter().find_map(pull_failure) {
+            return Err(anyhow::anyhow!("Image pre-pull failed: {}", failure));
+        }
+        let pulled = pods.iter().filter(|p| images_pulled(p)).count();
+
+        if reported != Some(pulled) {
+            println!("⏬ Pre-pulling images: {}/{} nodes ready", pulled, desired);
+            reported = Some(pulled);
+        }
+        if pulled >= desired && (desired > 0 || started.elapsed() > POLL_INTERVAL * 5) {
+            return Ok(pulled);
+        }
+        if started.elapsed() > TIMEOUT {
+            println!(
+                "⚠️  Pre-pull timed out after {}s on {}/{} nodes; continuing with the deploy.",
+                TIMEOUT.as_secs(),
+                pulled,
+                desired
+            );
+            return Ok(pulled);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// DaemonSet scheduled like the workload (node selector, affinity, tolerations, pull
+/// secrets) whose init containers only need their image to be pulled.
+fn daemon_set(
+    name: &str,
+    images: &[String],
+    pod_spec: Option<&serde_yaml::Value>,
+) -> Result<DaemonSet> {
+    let copied = |field: &str| -> Result<serde_json::Value> {
+        Ok(match pod_spec.and_then(|s| s.get(field)) {
+            Some(value) => serde_json::to_value(value)?,
+            None => serde_json::Value::Null,
+        })
+    };
+    let init_containers: Vec<serde_json::Value> = images
+        .iter()
+        .enumerate()
+        .map(|(i, image)| {
+            serde_json::json!({
+                "name": format!("pull-{}", i),
+                "image": image,
+                "imagePullPolicy": "IfNotPresent",
+                // Images without a shell fail to start, once pulled: good enough.
+                "command": ["sh", "-c", "exit 0"],
+                "resources": {"requests": {"cpu": "1m", "memory": "8Mi"}}
+            })
+        })
+        .collect();
+
+    let daemon_set = serde_json::json!({
+        "apiVersion": "apps/v1",
+        "kind": "DaemonSet",
+        "metadata": {"name": name, "labels": {"app.kubernetes.io/managed-by": "davit"}},
+        "spec": {
+            "selector": {"matchLabels": {"davit-prepull": name}},
+            "template": {
+                "metadata": {"labels": {"davit-prepull": name}},
+                "spec": {
+                    "nodeSelector": copied("nodeSelector")?,
+                    "affinity": copied("affinity")?,
+                    "tolerations": copied("tolerations")?,
+                    "imagePullSecrets": copied("imagePullSecrets")?,
+                    "initContainers": init_containers,
+                    "containers": [{
+                        "name": "pause",
+                        "image": PAUSE_IMAGE,
+                        "resources": {"requests": {"cpu": "1m", "memory": "8Mi"}}
+                    }],
+                    "terminationGracePeriodSeconds": 0
+                }
+            }
+        }
+    });
+    serde_json::from_value(daemon_set).context("Failed to build the pre-pull DaemonSet")
+}
+
+/// Whether every init container image is on the node: the container was created,
+/// whether or not it managed to run.
+fn images_pulled(pod: &Pod) -> bool {
+    let Some(statuses) = pod
+        .status
+        .as_ref()
+        .and_then(|s| s.init_container_statuses.as_ref())
+    else {
+        return false;
+    };
+    !statuses.is_empty()
+        && statuses.iter().all(|s| {
+            !s.image_id.is_empty()
+                || s.last_state
+                    .as_ref()
+                    .is_some_and(|l| l.terminated.is_some())
+                || s.state
+                    .as_ref()
+                    .is_some_and(|st| st.running.is_some() || st.terminated.is_some())
+        })
+}
+
+fn pull_failure(pod: &Pod) -> Option<String> {
+    pod.status
+        .as_ref()?
+        .init_container_statuses
+        .iter()
+        .flatten()
+        .find_map(|s| {
+            let waiting = s.state.as_ref()?.waiting.as_ref()?;
+            matches!(
+                waiting.reason.as_deref(),
+                Some("ErrImagePull" | "ImagePullBackOff" | "InvalidImageName")
+            )
+            .then(|| {
+                format!(
+                    "{}: {}",
+                    s.image,
+                    waiting.message.as_deref().unwrap_or("image pull failed")
+                )
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_set_copies_scheduling_of_workload() -> Result<()> {
+        let pod_spec: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+nodeSelector:
+  pool: gpu
+tolerations:
+  - key: nvidia.com/gpu
+    operator: Exists
+containers:
+  - name: trainer
+    image: europe-docker.pkg.dev/acme/ml/trainer:v1
+"#,
+        )?;
+        let images = vec!["europe-docker.pkg.dev/acme/ml/trainer:v2".to_string()];
+
+        let ds = daemon_set("davit-prepull-trainer", &images, Some(&pod_spec))?;
+        let spec = ds.spec.unwrap().template.spec.unwrap();
+        assert_eq!(spec.node_selector.unwrap()["pool"], "gpu");
+        assert_eq!(
+            spec.tolerations.unwrap()[0].key.as_deref(),
+            Some("nvidia.com/gpu")
+        );
+        assert!(spec.affinity.is_none());
+        let init = &spec.init_containers.unwrap()[0];
+        assert_eq!(init.image.as_deref(), Some(images[0].as_str()));
+        assert_eq!(spec.containers[0].image.as_deref(), Some(PAUSE_IMAGE));
+        Ok(())
+    }
+
+    #[test]
+    fn test_images_pulled_and_pull_failure() {
+        let pod = |status: serde_json::Value| -> Pod {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {"name": "p"},
+                "status": {"initContainerStatuses": [status]}
+            }))
+            .unwrap()
+        };
+        let pulling = pod(serde_json::json!({
+            "name": "pull-0", "image": "app:v2", "imageID": "", "ready": false,
+            "restartCount": 0, "state": {"waiting": {"reason": "PodInitializing"}}
+        }));
+        let crashed = pod(serde_json::json!({
+            "name": "pull-0", "image": "app:v2", "imageID": "sha256:ab", "ready": false,
+            "restartCount": 1, "state": {"waiting": {"reason": "CrashLoopBackOff"}}
+        }));
+        let denied = pod(serde_json::json!({
+            "name": "pull-0", "image": "app:v2", "imageID": "", "ready": false,
+            "restartCount": 0,
+            "state": {"waiting": {"reason": "ImagePullBackOff", "message": "403 Forbidden"}}
+        }));
+
+        assert!(!images_pulled(&pulling));
+        assert!(images_pulled(&crashed));
+        assert_eq!(pull_failure(&pulling), None);
+        assert_eq!(
+            pull_failure(&denied).as_deref(),
+            Some("app:v2: 403 Forbidden")
+        );
+    }
+}
@@ -0,0 +1,668 @@
+use anyhow::{Context, Result};
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::core::v1::{Service, ServicePort};
+use kube::{
+    Api, Client,
+    api::{
+        ApiResource, DeleteParams, DynamicObject, GroupVersionKind, Patch, PatchParams, PostParams,
+    },
+};
+use serde_json::{Value, json};
+
+use crate::config::{GatewayRouting, IstioRouting, ProgressiveDelivery};
+
+const FIELD_MANAGER: &str = "davit";
+const ISTIO_GROUP: &str = "networking.istio.io";
+const ISTIO_VERSION: &str = "v1beta1";
+const GATEWAY_GROUP: &str = "gateway.networking.k8s.io";
+const GATEWAY_VERSION: &str = "v1";
+const STABLE_SUBSET: &str = "stable";
+const CANARY_SUBSET: &str = "canary";
+
+/// The new version of a Deployment running next to the stable one, as a separate
+/// backend the routing sends a share of the traffic to.
+#[derive(Debug, Clone)]
+pub struct Canary {
+    pub namespace: String,
+    /// The stable Deployment.
+    pub workload: String,
+    /// The Deployment running the new version, `<workload>-canary`.
+    pub name: String,
+    pub steps: Vec<u8>,
+    router: Router,
+    version_label: String,
+    stable_replicas: i32,
+    stable_version: String,
+    canary_version: String,
+}
+
+#[derive(Debug, Clone)]
+enum Router {
+    /// Subsets of the DestinationRule applying to `host`.
+    Istio { routing: IstioRouting, host: String },
+    /// Version Services next to the backend `service` of the HTTPRoute.
+    Gateway {
+        routing: GatewayRouting,
+        service: String,
+    },
+}
+
+impl Canary {
+    /// Creates the canary Deployment from the reviewed `manifest` and the backends routing
+    /// to it, with all the traffic still on the stable version. Whatever was created is
+    /// removed again when a step fails.
+    pub async fn start(
+        client: Client,
+        delivery: &ProgressiveDelivery,
+        namespace: &str,
+        workload: &str,
+        manifest: &serde_yaml::Value,
+        tag: &str,
+    ) -> Result<Canary> {
+        let deployments: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+        let stable = deployments
+            .get(workload)
+            .await
+            .with_context(|| format!("Failed to read deployment {}", workload))?;
+        let version_label = delivery.version_label.clone();
+        let stable_version = template_label(&stable, &version_label).with_context(|| {
+            format!(
+                "The pods of {} have no `{}` label, which the routing needs to tell the stable version from the canary",
+                workload, version_label
+            )
+        })?;
+        let stable_replicas = stable.spec.as_ref().and_then(|s| s.replicas).unwrap_or(1);
+
+        let router = match (&delivery.istio, &delivery.gateway) {
+            (Some(routing), _) => {
+                let rules = routing_api(client.clone(), namespace, "DestinationRule");
+                let host = rules
+                    .get(routing.destination_rule())
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to read DestinationRule {}",
+                            routing.destination_rule()
+                        )
+                    })?
+                    .data
+                    .pointer("/spec/host")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .with_context(|| {
+                        format!("DestinationRule {} has no host", routing.destination_rule())
+                    })?;
+                Router::Istio {
+                    routing: routing.clone(),
+                    host,
+                }
+            }
+            (None, Some(routing)) => Router::Gateway {
+                service: routing
+                    .service
+                    .clone()
+                    .unwrap_or_else(|| workload.to_string()),
+                routing: routing.clone(),
+            },
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "Progressive delivery needs `istio` or `gateway` routing"
+                ));
+            }
+        };
+
+        let canary = Canary {
+            namespace: namespace.to_string(),
+            workload: workload.to_string(),
+            name: format!("{}-canary", workload),
+            steps: delivery.steps.clone(),
+            canary_version: canary_version(tag, &stable_version),
+            router,
+            version_label,
+            stable_replicas,
+            stable_version,
+        };
+        deployments
+            .patch(
+                &canary.name,
+                &PatchParams::apply(FIELD_MANAGER).force(),
+                &Patch::Apply(canary.workload_manifest(manifest)?),
+            )
+            .await
+            .context("Failed to create the canary deployment")?;
+
+        let routed = async {
+            canary.add_backends(&client, &canary.stable_version).await?;
+            canary.route(&client, Some(0)).await
+        };
+        if let Err(e) = routed.await {
+            if let Err(cleanup) = canary.abort(client).await {
+                println!("⚠️  Could not remove the canary: {:#}", cleanup);
+            }
+            return Err(e);
+        }
+        Ok(canary)
+    }
+
+    /// The step after `weight`, if any.
+    pub fn next_step(&self, weight: u8) -> Option<u8> {
+        self.steps.iter().copied().find(|step| *step > weight)
+    }
+
+    /// Routing object shifting the traffic, e.g. `httproute/auth-api`.
+    pub fn route_name(&self) -> String {
+        match &self.router {
+            Router::Istio { routing, .. } => {
+                format!("virtualservice/{}", routing.virtual_service)
+            }
+            Router::Gateway { routing, .. } => format!("httproute/{}", routing.http_route),
+        }
+    }
+
+    /// Names of the stable and canary backends the traffic is split between.
+    pub fn backend_names(&self) -> (String, String) {
+        match &self.router {
+            Router::Istio { .. } => (STABLE_SUBSET.to_string(), CANARY_SUBSET.to_string()),
+            Router::Gateway { service, .. } => (
+                format!("svc/{}-{}", service, STABLE_SUBSET),
+                format!("svc/{}-{}", service, CANARY_SUBSET),
+            ),
+        }
+    }
+
+    /// Scales the canary to its share of the replicas, then sends it `weight` percent of
+    /// the traffic.
+    pub async fn set_weight(&self, client: Client, weight: u8) -> Result<()> {
+        let deployments: Api<Deployment> = Api::namespaced(client.clone(), &self.namespace);
+        deployments
+            .patch(
+                &self.name,
+                &PatchParams::default(),
+                &Patch::Merge(json!({
+                    "spec": {"replicas": canary_replicas(self.stable_replicas, weight)}
+                })),
+            )
+            .await
+            .context("Failed to scale the canary deployment")?;
+        self.route(&client, Some(weight)).await
+    }
+
+    /// Sends all the traffic back to the stable version and removes the canary.
+    pub async fn abort(&self, client: Client) -> Result<()> {
+        self.retire(client, &self.stable_version).await
+    }
+
+    /// Once the stable Deployment runs the new version too, routes the traffic back to
+    /// it and removes the canary.
+    pub async fn finish(&self, client: Client) -> Result<()> {
+        let deployments: Api<Deployment> = Api::namespaced(client.clone(), &self.namespace);
+        let stable_version = deployments
+            .get(&self.workload)
+            .await
+            .ok()
+            .and_then(|d| template_label(&d, &self.version_label))
+            .unwrap_or_else(|| self.stable_version.clone());
+        self.retire(client, &stable_version).await
+    }
+
+    async fn retire(&self, client: Client, stable_version: &str) -> Result<()> {
+        // The canary backend must outlive the routes referencing it.
+        self.add_backends(&client, stable_version).await?;
+        self.route(&client, None).await?;
+        self.remove_backends(&client, stable_version).await?;
+        let deployments: Api<Deployment> = Api::namespaced(client, &self.namespace);
+        if deployments.get_opt(&self.name).await?.is_some() {
+            deployments
+                .delete(&self.name, &DeleteParams::background())
+                .await
+                .context("Failed to delete the canary deployment")?;
+        }
+        Ok(())
+    }
+
+    /// Makes the stable and canary versions addressable by the routing: subsets for
+    /// Istio, Services selecting each version for the Gateway API.
+    async fn add_backends(&self, client: &Client, stable_version: &str) -> Result<()> {
+        match &self.router {
+            Router::Istio { routing, .. } => {
+                edit_spec(
+                    client,
+                    &self.namespace,
+                    "DestinationRule",
+                    routing.destination_rule(),
+                    |spec| {
+                        set_subsets(
+                            spec,
+                            &self.version_label,
+                            stable_version,
+                            Some(&self.canary_version),
+                        );
+                        Ok(())
+                    },
+                )
+                .await
+            }
+            Router::Gateway { service, .. } => {
+                let services: Api<Service> = Api::namespaced(client.clone(), &self.namespace);
+                let backend = services
+                    .get(service)
+                    .await
+                    .with_context(|| format!("Failed to read service {}", service))?;
+                for (subset, version) in [
+                    (STABLE_SUBSET, stable_version),
+                    (CANARY_SUBSET, self.canary_version.as_str()),
+                ] {
+                    let name = format!("{}-{}", service, subset);
+                    services
+                        .patch(
+                            &name,
+                            &PatchParams::apply(FIELD_MANAGER).force(),
+                            &Patch::Apply(version_service(
+                                &backend,
+                                &name,
+                                &self.version_label,
+                                version,
+                            )?),
+                        )
+                        .await
+                        .with_context(|| format!("Failed to apply service {}", name))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes the canary backend once no route references it.
+    async fn remove_backends(&self, client: &Client, stable_version: &str) -> Result<()> {
+        match &self.router {
+            Router::Istio { routing, .. } => {
+                edit_spec(
+                    client,
+                    &self.namespace,
+                    "DestinationRule",
+                    routing.destination_rule(),
+                    |spec| {
+                        set_subsets(spec, &self.version_label, stable_version, None);
+                        Ok(())
+                    },
+                )
+                .await
+            }
+            Router::Gateway { service, .. } => {
+                // The route points at the backend Service again: both version Services go.
+                let services: Api<Service> = Api::namespaced(client.clone(), &self.namespace);
+                for subset in [STABLE_SUBSET, CANARY_SUBSET] {
+                    let name = format!("{}-{}", service, subset);
+                    if services.get_opt(&name).await?.is_some() {
+                        services
+                            .delete(&name, &DeleteParams::background())
+                            .await
+                            .with_context(|| format!("Failed to delete service {}", name))?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn route(&self, client: &Client, weight: Option<u8>) -> Result<()> {
+        let (kind, name) = match &self.router {
+            Router::Istio { routing, .. } => ("VirtualService", &routing.virtual_service),
+            Router::Gateway { routing, .. } => ("HTTPRoute", &routing.http_route),
+        };
+        edit_spec(client, &self.namespace, kind, name, |spec| {
+            let (updated, target) = match &self.router {
+                Router::Istio { host, .. } => (set_weights(spec, host, weight), host),
+                Router::Gateway { service, .. } => {
+                    (set_backend_weights(spec, service, weight), service)
+                }
+            };
+            if updated == 0 {
+                return Err(anyhow::anyhow!(
+                    "{} {} has no HTTP route to {}",
+                    kind,
+                    name,
+                    target
+                ));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// The reviewed workload renamed, with its own version label so that neither the
+    /// backends nor its selector overlap with the stable pods.
+    fn workload_manifest(&self, manifest: &serde_yaml::Value) -> Result<Value> {
+        let mut workload = serde_json::to_value(manifest)?;
+        let labels = workload
+            .pointer("/metadata/labels")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        workload["metadata"] = json!({
+            "name": self.name,
+            "namespace": self.namespace,
+            "labels": labels,
+        });
+        workload["metadata"]["labels"]["app.kubernetes.io/managed-by"] = json!("davit");
+        if let Some(workload) = workload.as_object_mut() {
+            workload.remove("status");
+        }
+
+        let spec = workload
+            .get_mut("spec")
+            .filter(|spec| spec.is_object())
+            .context("The workload manifest has no spec")?;
+        spec["replicas"] = json!(canary_replicas(self.stable_replicas, 0));
+        let label = self.version_label.as_str();
+        spec["selector"]["matchLabels"][label] = json!(self.canary_version);
+        spec["template"]["metadata"]["labels"][label] = json!(self.canary_version);
+        Ok(workload)
+    }
+}
+
+fn template_label(deployment: &Deployment, label: &str) -> Option<String> {
+    deployment
+        .spec
+        .as_ref()?
+        .template
+        .metadata
+        .as_ref()?
+        .labels
+        .as_ref()?
+        .get(label)
+        .cloned()
+}
+
+/// Version label value of the canary pods: the tag as a valid label value, distinct
+/// from the stable one.
+fn canary_version(tag: &str, stable_version: &str) -> String {
+    let sanitized: String = tag
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(56)
+        .collect();
+    let version = sanitized.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    match version {
+        "" => CANARY_SUBSET.to_string(),
+        version if version == stable_version => format!("{}-canary", version),
+        version => version.to_string(),
+    }
+}
+
+/// Canary replicas carrying `weight` percent of the traffic, never less than one.
+fn canary_replicas(stable_replicas: i32, weight: u8) -> i32 {
+    let replicas = (stable_replicas.max(1) as u32 * weight as u32).div_ceil(100);
+    replicas.max(1) as i32
+}
+
+/// Service selecting the pods of one version, with the ports of the backend Service.
+fn version_service(backend: &Service, name: &str, label: &str, version: &str) -> Result<Value> {
+    let spec = backend.spec.clone().unwrap_or_default();
+    let mut selector = spec.selector.unwrap_or_default();
+    selector.insert(label.to_string(), version.to_string());
+    let ports: Vec<_> = spec
+        .ports
+        .unwrap_or_default()
+        .into_iter()
+        .map(|port| ServicePort {
+            node_port: None,
+            ..port
+        })
+        .collect();
+    Ok(json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": {
+            "name": name,
+            "labels": {"app.kubernetes.io/managed-by": "davit"}
+        },
+        "spec": {"selector": selector, "ports": serde_json::to_value(ports)?}
+    }))
+}
+
+fn routing_api(client: Client, namespace: &str, kind: &str) -> Api<DynamicObject> {
+    let gvk = match kind {
+        "HTTPRoute" => GroupVersionKind::gvk(GATEWAY_GROUP, GATEWAY_VERSION, kind),
+        _ => GroupVersionKind::gvk(ISTIO_GROUP, ISTIO_VERSION, kind),
+    };
+    Api::namespaced_with(client, namespace, &ApiResource::from_gvk(&gvk))
+}
+
+/// Edits the spec of a routing object. The replace carries the read resource version,
+/// so a concurrent change makes it fail instead of being overwritten.
+async fn edit_spec(
+    client: &Client,
+    namespace: &str,
+    kind: &str,
+    name: &str,
+    edit: impl FnOnce(&mut Value) -> Result<()>,
+) -> Result<()> {
+    let api = routing_api(client.clone(), namespace, kind);
+    let mut object = api
+        .get(name)
+        .await
+        .with_context(|| format!("Failed to read {} {}", kind, name))?;
+    let spec = object
+        .data
+        .get_mut("spec")
+        .with_context(|| format!("{} {} has no spec", kind, name))?;
+    edit(spec)?;
+    api.replace(name, &PostParams::default(), &object)
+        .await
+        .with_context(|| format!("Failed to update {} {}", kind, name))?;
+    Ok(())
+}
+
+/// Points the `stable` subset of a DestinationRule spec at `stable_version` and adds or
+/// removes the `canary` one. Other subsets and subset traffic policies are kept.
+fn set_subsets(spec: &mut Value, label: &str, stable_version: &str, canary_version: Option<&str>) {
+    if !spec["subsets"].is_array() {
+        spec["subsets"] = json!([]);
+    }
+    let Some(subsets) = spec["subsets"].as_array_mut() else {
+        return;
+    };
+    let mut upsert = |name: &str, version: &str| {
+        let labels = json!({ label: version });
+        match subsets.iter_mut().find(|s| s["name"] == name) {
+            Some(subset) => subset["labels"] = labels,
+            None => subsets.push(json!({"name": name, "labels": labels})),
+        }
+    };
+    upsert(STABLE_SUBSET, stable_version);
+    match canary_version {
+        Some(version) => upsert(CANARY_SUBSET, version),
+        None => subsets.retain(|s| s["name"] != CANARY_SUBSET),
+    }
+}
+
+/// Splits the HTTP routes of a VirtualService spec going to `host` between the stable
+/// and canary subsets, `weight` percent to the canary; without a weight they go to the
+/// stable subset only. Routes also sending traffic elsewhere are left alone. Returns how
+/// many routes were updated.
+fn set_weights(spec: &mut Value, host: &str, weight: Option<u8>) -> usize {
+    let same_host = |candidate: &Value| {
+        candidate["destination"]["host"]
+            .as_str()
+            .is_some_and(|h| h.split('.').next() == host.split('.').next())
+    };
+    let mut updated = 0;
+    let routes = spec["http"].as_array_mut().into_iter().flatten();
+    for destinations in routes.filter_map(|r| r["route"].as_array_mut()) {
+        if destinations.is_empty() || !destinations.iter().all(same_host) {
+            continue;
+        }
+        let destination = destinations[0]["destination"].clone();
+        let to_subset = |subset: &str| {
+            let mut destination = destination.clone();
+            destination["subset"] = json!(subset);
+            destination
+        };
+        *destinations = match weight {
+            Some(weight) => vec![
+                json!({"destination": to_subset(STABLE_SUBSET), "weight": 100 - weight}),
+                json!({"destination": to_subset(CANARY_SUBSET), "weight": weight}),
+            ],
+            None => vec![json!({"destination": to_subset(STABLE_SUBSET)})],
+        };
+        updated += 1;
+    }
+    updated
+}
+
+/// Splits the rules of an HTTPRoute spec whose backends are `service` between its stable
+/// and canary version Services, `weight` percent to the canary; without a weight they go
+/// back to `service` itself. Rules also sending traffic elsewhere are left alone. Returns
+/// how many rules were updated.
+fn set_backend_weights(spec: &mut Value, service: &str, weight: Option<u8>) -> usize {
+    let stable = format!("{}-{}", service, STABLE_SUBSET);
+    let canary = format!("{}-{}", service, CANARY_SUBSET);
+    let ours = |backend: &Value| {
+        backend["group"].as_str().unwrap_or_default().is_empty()
+            && backend["kind"].as_str().unwrap_or("Service") == "Service"
+            && backend["name"]
+                .as_str()
+                .is_some_and(|name| name == service || name == stable || name == canary)
+    };
+    let mut updated = 0;
+    let rules = spec["rules"].as_array_mut().into_iter().flatten();
+    for backends in rules.filter_map(|r| r["backendRefs"].as_array_mut()) {
+        if backends.is_empty() || !backends.iter().all(ours) {
+            continue;
+        }
+        let mut backend = backends[0].clone();
+        if let Some(fields) = backend.as_object_mut() {
+            fields.remove("weight");
+        }
+        let to_service = |name: &str, weight: Option<u8>| {
+            let mut backend = backend.clone();
+            backend["name"] = json!(name);
+            if let Some(weight) = weight {
+                backend["weight"] = json!(weight);
+            }
+            backend
+        };
+        *backends = match weight {
+            Some(weight) => vec![
+                to_service(&stable, Some(100 - weight)),
+                to_service(&canary, Some(weight)),
+            ],
+            None => vec![to_service(service, None)],
+        };
+        updated += 1;
+    }
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routing_shifts_weights_between_subsets() {
+        let mut rule = json!({
+            "host": "auth-api.auth.svc.cluster.local",
+            "subsets": [{"name": "stable", "labels": {"version": "v1"},
+                         "trafficPolicy": {"loadBalancer": {"simple": "LEAST_REQUEST"}}}]
+        });
+        set_subsets(&mut rule, "version", "v1", Some("v2.4.0"));
+        assert_eq!(
+            rule["subsets"][0]["trafficPolicy"]["loadBalancer"]["simple"],
+            "LEAST_REQUEST"
+        );
+        assert_eq!(
+            rule["subsets"][1],
+            json!({"name": "canary", "labels": {"version": "v2.4.0"}})
+        );
+        set_subsets(&mut rule, "version", "v2", None);
+        assert_eq!(rule["subsets"].as_array().unwrap().len(), 1);
+        assert_eq!(rule["subsets"][0]["labels"]["version"], "v2");
+
+        let mut service = json!({
+            "hosts": ["auth.acme.com"],
+            "http": [
+                {"route": [{"destination": {"host": "auth-api", "port": {"number": 80}}}]},
+                {"route": [{"destination": {"host": "legacy-auth"}}]}
+            ]
+        });
+        assert_eq!(
+            set_weights(&mut service, "auth-api.auth.svc.cluster.local", Some(10)),
+            1
+        );
+        assert_eq!(
+            service["http"][0]["route"],
+            json!([
+                {"destination": {"host": "auth-api", "port": {"number": 80}, "subset": "stable"}, "weight": 90},
+                {"destination": {"host": "auth-api", "port": {"number": 80}, "subset": "canary"}, "weight": 10}
+            ])
+        );
+        assert_eq!(
+            service["http"][1]["route"][0]["destination"]["host"],
+            "legacy-auth"
+        );
+        set_weights(&mut service, "auth-api", None);
+        assert_eq!(
+            service["http"][0]["route"],
+            json!([{"destination": {"host": "auth-api", "port": {"number": 80}, "subset": "stable"}}])
+        );
+    }
+
+    #[test]
+    fn test_http_route_shifts_weights_between_version_services() {
+        let mut route = json!({
+            "parentRefs": [{"name": "public"}],
+            "rules": [
+                {"matches": [{"path": {"type": "PathPrefix", "value": "/auth"}}],
+                 "backendRefs": [{"name": "auth-api", "port": 8080}]},
+                {"backendRefs": [{"name": "auth-api", "port": 8080, "weight": 80},
+                                 {"name": "auth-api-legacy", "port": 8080, "weight": 20}]}
+            ]
+        });
+        assert_eq!(set_backend_weights(&mut route, "auth-api", Some(50)), 1);
+        assert_eq!(
+            route["rules"][0]["backendRefs"],
+            json!([
+                {"name": "auth-api-stable", "port": 8080, "weight": 50},
+                {"name": "auth-api-canary", "port": 8080, "weight": 50}
+            ])
+        );
+        assert_eq!(route["rules"][1]["backendRefs"][1]["weight"], 20);
+
+        set_backend_weights(&mut route, "auth-api", None);
+        assert_eq!(
+            route["rules"][0]["backendRefs"],
+            json!([{"name": "auth-api", "port": 8080}])
+        );
+
+        let backend: Service = serde_json::from_value(json!({
+            "metadata": {"name": "auth-api"},
+            "spec": {"type": "NodePort", "selector": {"app": "auth-api"},
+                     "ports": [{"name": "http", "port": 8080, "targetPort": 8080, "nodePort": 30080}]}
+        }))
+        .unwrap();
+        let stable = version_service(&backend, "auth-api-stable", "version", "v1").unwrap();
+        assert_eq!(
+            stable["spec"]["selector"],
+            json!({"app": "auth-api", "version": "v1"})
+        );
+        assert_eq!(stable["spec"]["ports"][0]["port"], 8080);
+        assert!(stable["spec"]["ports"][0].get("nodePort").is_none());
+    }
+
+    #[test]
+    fn test_canary_version_and_replicas() {
+        assert_eq!(canary_version("v2.4.0", "v1"), "v2.4.0");
+        assert_eq!(canary_version("v1", "v1"), "v1-canary");
+        assert_eq!(canary_version("sha256:ab+c", "v1"), "sha256-ab-c");
+        assert_eq!(canary_replicas(4, 0), 1);
+        assert_eq!(canary_replicas(4, 10), 1);
+        assert_eq!(canary_replicas(4, 50), 2);
+        assert_eq!(canary_replicas(3, 100), 3);
+    }
+}
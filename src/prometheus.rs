@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct QueryResponse {
+    status: String,
+    #[serde(default)]
+    error: Option<String>,
+    data: Option<QueryData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "resultType", content = "result", rename_all = "lowercase")]
+enum QueryData {
+    Vector(Vec<VectorSample>),
+    Scalar((f64, String)),
+}
+
+#[derive(Debug, Deserialize)]
+struct VectorSample {
+    value: (f64, String),
+}
+
+/// Runs an instant PromQL query and returns its value. Queries are expected to
+/// aggregate to a single series; the first one is used otherwise. `None` when the
+/// result is empty (e.g. no traffic yet).
+pub async fn query(client: &reqwest::Client, base_url: &str, promql: &str) -> Result<Option<f64>> {
+    let url = format!("{}/api/v1/query", base_url.trim_end_matches('/'));
+    let body = client
+        .get(&url)
+        .query(&[("query", promql)])
+        .send()
+        .await
+        .with_context(|| format!("Failed to query Prometheus at {}", url))?
+        .bytes()
+        .await?;
+    parse_response(&body)
+}
+
+fn parse_response(body: &[u8]) -> Result<Option<f64>> {
+    // Range (matrix) and string results fail to parse: queries must be instant vectors or scalars.
+    let response: QueryResponse =
+        serde_json::from_slice(body).context("Failed to parse Prometheus response")?;
+    if response.status != "success" {
+        return Err(anyhow::anyhow!(
+            "Prometheus query failed: {}",
+            response.error.unwrap_or(response.status)
+        ));
+    }
+
+    let value = match response.data {
+        Some(QueryData::Vector(samples)) => samples.into_iter().next().map(|s| s.value.1),
+        Some(QueryData::Scalar((_, value))) => Some(value),
+        None => None,
+    };
+    value
+        .map(|v| v.parse::<f64>().context("Invalid sample value"))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response_reads_first_vector_sample() -> Result<()> {
+        let body = br#"{"status":"success","data":{"resultType":"vector","result":[
+            {"metric":{"service":"auth-api"},"value":[1760000000.123,"0.042"]}
+        ]}}"#;
+        assert_eq!(parse_response(body)?, Some(0.042));
+
+        let empty = br#"{"status":"success","data":{"resultType":"vector","result":[]}}"#;
+        assert_eq!(parse_response(empty)?, None);
+
+        let scalar =
+            br#"{"status":"success","data":{"resultType":"scalar","result":[1760000000,"3"]}}"#;
+        assert_eq!(parse_response(scalar)?, Some(3.0));
+
+        let error = br#"{"status":"error","errorType":"bad_data","error":"parse error"}"#;
+        assert!(parse_response(error).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use k8s_openapi::api::core::v1::Pod;
+use kube::Api;
+use kube::api::ListParams;
+use std::fmt;
+
+use crate::blame;
+use crate::blueprint::split_image_ref;
+use crate::config::{Environment, HealthCheck, PromotionStage, ServiceSource};
+use crate::dashboard::pod_runs_tag;
+use crate::{cluster, health, history, mock};
+
+/// One environment of a service's pipeline with the tag deployed there.
+#[derive(Debug, Clone)]
+pub struct StageState {
+    pub stage: PromotionStage,
+    pub env: Environment,
+    pub service: Option<ServiceSource>,
+}
+
+impl StageState {
+    pub fn tag(&self) -> Option<&str> {
+        split_image_ref(&self.service.as_ref()?.image_path).1
+    }
+}
+
+/// Outcome of one gate of the promotion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GateResult {
+    pub gate: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl fmt::Display for GateResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = if self.passed { "✅" } else { "❌" };
+        write!(f, "{} {:<9} {}", marker, self.gate, self.detail)
+    }
+}
+
+/// Index of the stage to promote into: the requested environment, else the first stage
+/// whose tag differs from the previous one's.
+pub fn target_stage(stages: &[StageState], to: Option<&str>) -> Result<usize> {
+    if let Some(to) = to {
+        return match stages.iter().position(|s| s.stage.env == to) {
+            Some(0) => Err(anyhow::anyhow!(
+                "{} is the first stage of the pipeline and cannot be promoted into",
+                to
+            )),
+            Some(index) => Ok(index),
+            None => Err(anyhow::anyhow!("{} is not a stage of the pipeline", to)),
+        };
+    }
+    (1..stages.len())
+        .find(|&i| stages[i - 1].tag().is_some() && stages[i - 1].tag() != stages[i].tag())
+        .context("Every stage already runs the tag of the previous one")
+}
+
+/// Checks the gates of `stages[target]` for promoting the tag of the previous stage.
+pub async fn check_gates(
+    stages: &[StageState],
+    target: usize,
+    health_check: Option<&HealthCheck>,
+) -> Vec<GateResult> {
+    let stage = &stages[target].stage;
+    let from = &stages[target - 1];
+    let (Some(service), Some(tag)) = (&from.service, from.tag()) else {
+        return vec![GateResult {
+            gate: "source",
+            passed: false,
+            detail: format!("no tag deployed in {}", from.env.name),
+        }];
+    };
+
+    let mut results = Vec::new();
+    if let Some(soak) = &stage.soak {
+        let required = history::parse_since(soak).unwrap_or_default();
+        let since = blame::deployed_since(&from.env, service, tag);
+        results.push(soak_gate(&from.env.name, required, since, Utc::now()));
+    }
+    if stage.smoke {
+        results.push(match health_check {
+            Some(check) => smoke_gate(&from.env, service, tag, check).await,
+            None => GateResult {
+                gate: "smoke",
+                passed: false,
+                detail: "no health check configured".to_string(),
+            },
+        });
+    }
+    if !stage.approvers.is_empty() {
+        results.push(approval_gate(&stage.approvers, &history::current_user()));
+    }
+    results
+}
+
+fn soak_gate(
+    env: &str,
+    required: Duration,
+    since: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> GateResult {
+    let Some(since) = since else {
+        return GateResult {
+            gate: "soak",
+            passed: false,
+            detail: format!("no record of when the tag reached {}", env),
+        };
+    };
+    let soaked = now - since;
+    GateResult {
+        gate: "soak",
+        passed: soaked >= required,
+        detail: format!(
+            "{} in {} (minimum {})",
+            format_duration(soaked),
+            env,
+            format_duration(required)
+        ),
+    }
+}
+
+fn approval_gate(approvers: &[String], user: &str) -> GateResult {
+    let passed = approvers.iter().any(|a| a.eq_ignore_ascii_case(user));
+    GateResult {
+        gate: "approval",
+        passed,
+        detail: if passed {
+            format!("approved by {}", user)
+        } else {
+            format!("{} is not among {}", user, approvers.join(", "))
+        },
+    }
+}
+
+/// Probes the health check against the pods running `tag`, through the external URL
+/// when no port is configured.
+async fn smoke_gate(
+    env: &Environment,
+    service: &ServiceSource,
+    tag: &str,
+    check: &HealthCheck,
+) -> GateResult {
+    let result = if mock::enabled() {
+        Ok("not probed in demo mode".to_string())
+    } else {
+        probe(env, service, tag, check).await
+    };
+    match result {
+        Ok(detail) => GateResult {
+            gate: "smoke",
+            passed: true,
+            detail,
+        },
+        Err(e) => GateResult {
+            gate: "smoke",
+            passed: false,
+            detail: format!("{:#}", e),
+        },
+    }
+}
+
+async fn probe(
+    env: &Environment,
+    service: &ServiceSource,
+    tag: &str,
+    check: &HealthCheck,
+) -> Result<String> {
+    let Some(port) = check.port else {
+        let url = check.url.as_deref().unwrap_or_default();
+        let sample = health::probe_url(&reqwest::Client::new(), url).await;
+        return describe(url, &sample);
+    };
+
+    let client = cluster::client(&env.kubectl_context).await?;
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let pods: Api<Pod> = Api::namespaced(client, namespace);
+    let mut lp = ListParams::default();
+    if let Some(selector) = &service.selector {
+        lp = lp.labels(selector);
+    }
+    let running: Vec<String> = pods
+        .list(&lp)
+        .await
+        .context("Failed to list pods")?
+        .items
+        .into_iter()
+        .filter(|p| {
+            pod_runs_tag(p, tag)
+                && p.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running")
+        })
+        .filter_map(|p| p.metadata.name)
+        .collect();
+    if running.is_empty() {
+        return Err(anyhow::anyhow!("no running pod of {} in {}", tag, env.name));
+    }
+    for pod in &running {
+        let sample = health::probe_pod(&pods, pod, port, &check.path).await;
+        describe(pod, &sample)?;
+    }
+    Ok(format!(
+        "{} pod(s) healthy on {}{}",
+        running.len(),
+        port,
+        check.path
+    ))
+}
+
+fn describe(target: &str, sample: &health::ProbeSample) -> Result<String> {
+    match sample.status {
+        Some(status) if sample.is_healthy() => Ok(format!(
+            "{} answered {} in {}ms",
+            target,
+            status,
+            sample.latency.as_millis()
+        )),
+        Some(status) => Err(anyhow::anyhow!("{} answered {}", target, status)),
+        None => Err(anyhow::anyhow!(
+            "{}: {}",
+            target,
+            sample.error.as_deref().unwrap_or("no response")
+        )),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let hours = duration.num_hours();
+    if hours >= 48 {
+        format!("{}d {}h", hours / 24, hours % 24)
+    } else {
+        format!("{}h {}m", hours, duration.num_minutes() % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soak_and_approval_gates() {
+        let now = Utc::now();
+        let soak = soak_gate(
+            "staging",
+            Duration::hours(24),
+            Some(now - Duration::hours(30)),
+            now,
+        );
+        assert!(soak.passed);
+        assert_eq!(soak.detail, "30h 0m in staging (minimum 24h 0m)");
+        assert!(!soak_gate("staging", Duration::hours(24), Some(now), now).passed);
+        assert!(!soak_gate("staging", Duration::hours(1), None, now).passed);
+
+        let approvers = vec!["Jane@acme.io".to_string()];
+        assert!(approval_gate(&approvers, "jane@acme.io").passed);
+        assert!(!approval_gate(&approvers, "joe@acme.io").passed);
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use inquire::Text;
+use inquire::validator::Validation;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::interactive;
+
+/// Services that need a typed confirmation to be deployed, like protected environments,
+/// and optionally the approval of other people. Some staging services (payment
+/// gateways) are as sensitive as production.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ProtectedServices {
+    /// Service names; `*` matches any run of characters, e.g. `payment-*`.
+    pub services: Vec<String>,
+    /// Environments the rule applies to; all of them when empty.
+    #[serde(default)]
+    pub environments: Vec<String>,
+    /// People other than the deployer whose names must be recorded as approving the
+    /// deploy, in its history entry and Git commit.
+    #[serde(default)]
+    pub min_approvals: usize,
+}
+
+impl ProtectedServices {
+    fn applies_to(&self, env: &str, service: &str) -> bool {
+        (self.environments.is_empty() || self.environments.iter().any(|e| e == env))
+            && self
+                .services
+                .iter()
+                .any(|pattern| glob_match(pattern, service))
+    }
+}
+
+/// Protection of a service in an environment, from the rules matching it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Protection {
+    pub confirm: bool,
+    pub min_approvals: usize,
+}
+
+pub fn service_protection(config: &Config, env: &str, service: &str) -> Protection {
+    config
+        .protected_services
+        .iter()
+        .filter(|rule| rule.applies_to(env, service))
+        .fold(Protection::default(), |protection, rule| Protection {
+            confirm: true,
+            min_approvals: protection.min_approvals.max(rule.min_approvals),
+        })
+}
+
+/// A protected environment, or a protected service within one, to confirm before
+/// changing it.
+#[derive(Debug, Clone, Copy)]
+pub enum ProtectedTarget<'a> {
+    Environment(&'a str),
+    Service { name: &'a str, env: &'a str },
+}
+
+impl ProtectedTarget<'_> {
+    fn kind(&self) -> &'static str {
+        match self {
+            ProtectedTarget::Environment(_) => "environment",
+            ProtectedTarget::Service { .. } => "service",
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            ProtectedTarget::Environment(name) | ProtectedTarget::Service { name, .. } => name,
+        }
+    }
+
+    fn env(&self) -> &str {
+        match self {
+            ProtectedTarget::Environment(env) | ProtectedTarget::Service { env, .. } => env,
+        }
+    }
+}
+
+/// Confirms a change to a protected target: with `--confirm-protected` naming its
+/// environment when given, which non-interactive runs require, else by typing the
+/// target's name. Returns how it was confirmed, for the audit log.
+pub fn confirm_protected(
+    target: ProtectedTarget,
+    confirm_flag: Option<&str>,
+    operation: &str,
+) -> Result<String> {
+    match target {
+        ProtectedTarget::Environment(env) => {
+            println!("⚠️  WARNING: {} is a PROTECTED environment!", env)
+        }
+        ProtectedTarget::Service { name, env } => {
+            println!("⚠️  WARNING: {} is a PROTECTED service in {}!", name, env)
+        }
+    }
+    if confirm_flag.is_some() || !interactive::enabled() {
+        return flag_confirmation(target, confirm_flag);
+    }
+    let confirmation = Text::new(&format!(
+        "Type the {} name '{}' to confirm:",
+        target.kind(),
+        target.name()
+    ))
+    .prompt()
+    .with_context(|| format!("Protected {} confirmation was cancelled", target.kind()))?;
+    if confirmation != target.name() {
+        return Err(anyhow::anyhow!(
+            "Confirmation failed. {} aborted.",
+            operation
+        ));
+    }
+    Ok(format!("typed the protected {} name", target.kind()))
+}
+
+fn flag_confirmation(target: ProtectedTarget, confirm_flag: Option<&str>) -> Result<String> {
+    match confirm_flag {
+        None => Err(anyhow::anyhow!(
+            "{} {} is protected: pass --confirm-protected {} to confirm it in non-interactive mode",
+            target.kind(),
+            target.name(),
+            target.env()
+        )),
+        Some(flag) if flag != target.env() => Err(anyhow::anyhow!(
+            "--confirm-protected {} does not match the environment {}",
+            flag,
+            target.env()
+        )),
+        Some(flag) => Ok(format!("--confirm-protected {}", flag)),
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Why `name` cannot approve the deploy, if it cannot.
+fn reject_approver(name: &str, deployer: &str, approved: &[String]) -> Option<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        Some("Enter a name or initials".to_string())
+    } else if name.eq_ignore_ascii_case(deployer) {
+        Some("The deployer cannot approve their own deploy".to_string())
+    } else if approved.iter().any(|a| a.eq_ignore_ascii_case(name)) {
+        Some(format!("{} already approved", name))
+    } else {
+        None
+    }
+}
+
+/// Names of the `required` people approving the deploy: those given with
+/// `--approved-by`, then prompted for. Non-interactive runs must give them all.
+pub fn collect_approvals(required: usize, given: &[String], deployer: &str) -> Result<Vec<String>> {
+    let mut approved: Vec<String> = Vec::new();
+    for name in given {
+        if let Some(reason) = reject_approver(name, deployer, &approved) {
+            return Err(anyhow::anyhow!("Invalid approver '{}': {}", name, reason));
+        }
+        approved.push(name.trim().to_string());
+    }
+    while approved.len() < required {
+        if !interactive::enabled() {
+            return Err(anyhow::anyhow!(
+                "This deploy needs {} approval(s), {} given: pass the approvers with --approved-by",
+                required,
+                approved.len()
+            ));
+        }
+        let so_far = approved.clone();
+        let deployer = deployer.to_string();
+        let name = Text::new(&format!(
+            "Approver {} of {} (name or initials):",
+            approved.len() + 1,
+            required
+        ))
+        .with_validator(move |input: &str| {
+            Ok(match reject_approver(input, &deployer, &so_far) {
+                Some(reason) => Validation::Invalid(reason.into()),
+                None => Validation::Valid,
+            })
+        })
+        .prompt()
+        .context("Approval was cancelled")?;
+        approved.push(name.trim().to_string());
+    }
+    Ok(approved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_protection_matches_globs_per_environment() {
+        let config: Config = toml::from_str(
+            r#"environments = []
+
+[[protected_services]]
+services = ["payment-*"]
+environments = ["staging"]
+
+[[protected_services]]
+services = ["*-gateway"]
+min_approvals = 1
+"#,
+        )
+        .unwrap();
+
+        let staging = service_protection(&config, "staging", "payment-api");
+        assert_eq!(
+            staging,
+            Protection {
+                confirm: true,
+                min_approvals: 0
+            }
+        );
+        assert!(!service_protection(&config, "dev", "payment-api").confirm);
+        assert_eq!(
+            service_protection(&config, "staging", "payment-gateway").min_approvals,
+            1
+        );
+        assert!(!service_protection(&config, "staging", "auth-api").confirm);
+        assert!(glob_match("a*b*c", "a-b-b-c"));
+        assert!(!glob_match("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn test_protected_targets_need_the_flag_naming_their_environment() {
+        let env = ProtectedTarget::Environment("production");
+        assert!(flag_confirmation(env, None).is_err());
+        assert!(flag_confirmation(env, Some("staging")).is_err());
+        assert_eq!(
+            flag_confirmation(env, Some("production")).unwrap(),
+            "--confirm-protected production"
+        );
+
+        let service = ProtectedTarget::Service {
+            name: "payment-api",
+            env: "staging",
+        };
+        assert!(flag_confirmation(service, Some("payment-api")).is_err());
+        assert!(flag_confirmation(service, Some("staging")).is_ok());
+    }
+
+    #[test]
+    fn test_approvers_must_be_other_people() {
+        assert!(reject_approver("JD", "jane@acme.io", &[]).is_none());
+        assert!(reject_approver("Jane@acme.io", "jane@acme.io", &[]).is_some());
+        assert!(reject_approver(" ", "jane@acme.io", &[]).is_some());
+        assert!(reject_approver("jd", "jane@acme.io", &["JD".to_string()]).is_some());
+        assert_eq!(
+            collect_approvals(1, &["JD".to_string()], "jane@acme.io").unwrap(),
+            ["JD"]
+        );
+    }
+}
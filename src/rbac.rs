@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
+use kube::api::PostParams;
+use kube::{Api, Client};
+use std::fmt;
+
+use crate::cluster;
+use crate::config::{Environment, ServiceSource};
+use crate::mock;
+
+/// A verb on a resource, as asked with `kubectl auth can-i`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permission {
+    pub verb: &'static str,
+    /// API group, empty for the core group.
+    pub group: &'static str,
+    pub resource: String,
+    pub subresource: Option<&'static str>,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.verb, self.resource)?;
+        if let Some(subresource) = self.subresource {
+            write!(f, "/{}", subresource)?;
+        }
+        if !self.group.is_empty() {
+            write!(f, ".{}", self.group)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a deploy of a workload of `kind` does in its namespace: read and patch the
+/// workload, then follow its pods and their logs.
+pub fn deploy_permissions(kind: &str) -> Vec<Permission> {
+    let workload = format!("{}s", kind.to_lowercase());
+    vec![
+        Permission {
+            verb: "get",
+            group: "apps",
+            resource: workload.clone(),
+            subresource: None,
+        },
+        Permission {
+            verb: "patch",
+            group: "apps",
+            resource: workload,
+            subresource: None,
+        },
+        Permission {
+            verb: "list",
+            group: "",
+            resource: "pods".to_string(),
+            subresource: None,
+        },
+        Permission {
+            verb: "get",
+            group: "",
+            resource: "pods".to_string(),
+            subresource: Some("log"),
+        },
+    ]
+}
+
+/// The permissions the current user lacks in `namespace`, each with the reason the API
+/// server gives, through SelfSubjectAccessReviews.
+pub async fn denied(
+    client: Client,
+    namespace: &str,
+    permissions: &[Permission],
+) -> Result<Vec<(Permission, Option<String>)>> {
+    let api: Api<SelfSubjectAccessReview> = Api::all(client);
+    let mut denied = Vec::new();
+    for permission in permissions {
+        let review = SelfSubjectAccessReview {
+            spec: SelfSubjectAccessReviewSpec {
+                resource_attributes: Some(ResourceAttributes {
+                    group: Some(permission.group.to_string()),
+                    resource: Some(permission.resource.clone()),
+                    subresource: permission.subresource.map(str::to_string),
+                    verb: Some(permission.verb.to_string()),
+                    namespace: Some(namespace.to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let status = api
+            .create(&PostParams::default(), &review)
+            .await
+            .with_context(|| format!("Failed to review access to {}", permission))?
+            .status;
+        if !status.as_ref().is_some_and(|s| s.allowed) {
+            let reason = status.and_then(|s| s.reason).filter(|r| !r.is_empty());
+            denied.push((permission.clone(), reason));
+        }
+    }
+    Ok(denied)
+}
+
+/// Fails before anything is changed when the user may not deploy the service with the
+/// context of the environment. A review that cannot run only warns: `kubectl` reports
+/// the actual failure. Demo and offline environments have no cluster to ask.
+pub async fn preflight(env: &Environment, service: &ServiceSource) -> Result<()> {
+    if mock::enabled() || env.offline.is_some() {
+        return Ok(());
+    }
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let reviewed = match cluster::client(&env.kubectl_context).await {
+        Ok(client) => denied(client, namespace, &deploy_permissions(&service.kind)).await,
+        Err(e) => Err(e),
+    };
+    let denied = match reviewed {
+        Ok(denied) => denied,
+        Err(e) => {
+            eprintln!("⚠️  Could not check your permissions: {:#}", e);
+            return Ok(());
+        }
+    };
+    let Some((first, _)) = denied.first() else {
+        return Ok(());
+    };
+    let lines: Vec<String> = denied
+        .iter()
+        .map(|(permission, reason)| match reason {
+            Some(reason) => format!("  - {} ({})", permission, reason),
+            None => format!("  - {}", permission),
+        })
+        .collect();
+    Err(anyhow::anyhow!(
+        "🔒 You cannot deploy {} to {}: context {} lacks these permissions in namespace {}:\n{}\nAsk a cluster admin for a Role granting them; check with `kubectl auth can-i {} {} -n {} --context {}`.",
+        service.name,
+        env.name,
+        env.kubectl_context,
+        namespace,
+        lines.join("\n"),
+        first.verb,
+        match first.subresource {
+            Some(subresource) => format!("{}/{}", first.resource, subresource),
+            None => first.resource.clone(),
+        },
+        namespace,
+        env.kubectl_context
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deploy_permissions_read_like_kubectl_auth_can_i() {
+        let permissions: Vec<String> = deploy_permissions("StatefulSet")
+            .iter()
+            .map(Permission::to_string)
+            .collect();
+        assert_eq!(
+            permissions,
+            [
+                "get statefulsets.apps",
+                "patch statefulsets.apps",
+                "list pods",
+                "get pods/log"
+            ]
+        );
+    }
+}
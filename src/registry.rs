@@ -1,9 +1,17 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
-use std::process::Command;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
 
-#[derive(Debug, Deserialize, Clone)]
+use crate::oci::{self, Credentials};
+use crate::retry;
+
+const ARTIFACT_REGISTRY_API: &str = "https://artifactregistry.googleapis.com/v1";
+const RESOURCE_MANAGER_API: &str = "https://cloudresourcemanager.googleapis.com/v1";
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ImageMetadata {
     pub tags: Vec<String>,
     #[serde(rename = "updateTime")]
@@ -20,10 +28,15 @@ impl ImageMetadata {
         }
     }
 
+    /// Manifest digest (`sha256:...`) of the image.
+    pub fn digest(&self) -> Option<&str> {
+        self.name.split_once('@').map(|(_, digest)| digest)
+    }
+
     pub fn short_hash(&self) -> String {
         self.name
             .split('@')
-            .last()
+            .next_back()
             .and_then(|h| h.strip_prefix("sha256:"))
             .and_then(|h| h.get(0..7))
             .unwrap_or("unknown")
@@ -49,147 +62,674 @@ impl ImageMetadata {
 pub struct Registry;
 
 impl Registry {
-    pub fn fetch_images(image_path: &str) -> Result<Vec<ImageMetadata>> {
+    /// Runs gcloud, handing it `access_token` through stdin so it never touches disk.
+    fn gcloud(args: &[&str], access_token: Option<&str>) -> std::io::Result<Output> {
+        let mut command = Command::new("gcloud");
+        command.args(args);
+
+        let Some(token) = access_token else {
+            return command.output();
+        };
+
+        let mut child = command
+            .arg("--access-token-file=/dev/stdin")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(token.as_bytes())?;
+        }
+        child.wait_with_output()
+    }
+
+    pub async fn fetch_images(
+        image_path: &str,
+        access_token: Option<&str>,
+    ) -> Result<Vec<ImageMetadata>> {
         let base_image = image_path.split(':').next().unwrap_or(image_path);
 
-        if base_image.contains("gcr.io") {
-            let output = Command::new("gcloud")
-                .args([
-                    "container",
-                    "images",
-                    "list-tags",
-                    base_image,
-                    "--format=json",
-                    "--sort-by=~timestamp",
-                ])
-                .output()
-                .context("Failed to execute gcloud command for GCR. Is gcloud installed?")?;
-
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                return Err(anyhow::anyhow!("gcloud command failed for GCR: {}", stderr));
+        retry::with_backoff("Registry listing", || async {
+            if let Some(repository) = EcrRepository::parse(base_image) {
+                Self::fetch_ecr_images(&repository, base_image)
+            } else if base_image.contains("gcr.io") {
+                match Self::fetch_gcr_images(base_image, access_token) {
+                    Err(e) if command_missing(&e) => {
+                        Self::fetch_gcr_images_over_oci(base_image, access_token).await
+                    }
+                    result => result,
+                }
+            } else if ArtifactRepository::parse(base_image).is_some() {
+                Self::fetch_artifact_registry_images(base_image, access_token).await
+            } else {
+                oci::fetch_images(base_image, None).await
             }
+        })
+        .await
+    }
 
-            let gcr_images: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)
-                .context("Failed to parse GCR JSON output")?;
-
-            let images = gcr_images
-                .into_iter()
-                .filter_map(|v| {
-                    let tags = v
-                        .get("tags")?
-                        .as_array()?
-                        .iter()
-                        .filter_map(|t| t.as_str().map(|s| s.to_string()))
-                        .collect::<Vec<_>>();
-                    if tags.is_empty() {
-                        return None;
-                    }
+    fn fetch_gcr_images(
+        base_image: &str,
+        access_token: Option<&str>,
+    ) -> Result<Vec<ImageMetadata>> {
+        let output = Self::gcloud(
+            &[
+                "container",
+                "images",
+                "list-tags",
+                base_image,
+                "--format=json",
+                "--sort-by=~timestamp",
+            ],
+            access_token,
+        )
+        .context("Failed to execute gcloud command for GCR. Is gcloud installed?")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("gcloud command failed for GCR: {}", stderr));
+        }
 
-                    let digest = v.get("digest")?.as_str()?.to_string();
-
-                    // Try to parse timestamp. GCR format can be tricky.
-                    // Output example: "2026-02-05 19:49:35+01:00"
-                    let update_time = if let Some(ts) = v.get("timestamp") {
-                        if let Some(dt) = ts.get("datetime").and_then(|d| d.as_str()) {
-                            // Try parsing with timezone offset first (e.g., +01:00)
-                            if let Ok(dt_parsed) =
-                                DateTime::parse_from_str(dt, "%Y-%m-%d %H:%M:%S%:z")
-                            {
-                                dt_parsed.with_timezone(&Utc)
-                            } else if let Ok(ndt) =
-                                chrono::NaiveDateTime::parse_from_str(dt, "%Y-%m-%d %H:%M:%S")
-                            {
-                                DateTime::<Utc>::from_naive_utc_and_offset(ndt, Utc)
-                            } else {
-                                // Last resort: use individual fields if present
-                                let year =
-                                    ts.get("year").and_then(|y| y.as_i64()).unwrap_or(1970) as i32;
-                                let month =
-                                    ts.get("month").and_then(|m| m.as_u64()).unwrap_or(1) as u32;
-                                let day =
-                                    ts.get("day").and_then(|d| d.as_u64()).unwrap_or(1) as u32;
-                                let hour =
-                                    ts.get("hour").and_then(|h| h.as_u64()).unwrap_or(0) as u32;
-                                let minute =
-                                    ts.get("minute").and_then(|m| m.as_u64()).unwrap_or(0) as u32;
-                                let second =
-                                    ts.get("second").and_then(|s| s.as_u64()).unwrap_or(0) as u32;
-
-                                let ndt = chrono::NaiveDate::from_ymd_opt(year, month, day)?
-                                    .and_hms_opt(hour, minute, second)?;
-                                DateTime::<Utc>::from_naive_utc_and_offset(ndt, Utc)
-                            }
+        let gcr_images: Vec<serde_json::Value> =
+            serde_json::from_slice(&output.stdout).context("Failed to parse GCR JSON output")?;
+
+        let images = gcr_images
+            .into_iter()
+            .filter_map(|v| {
+                let tags = v
+                    .get("tags")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|t| t.as_str().map(|s| s.to_string()))
+                    .collect::<Vec<_>>();
+                if tags.is_empty() {
+                    return None;
+                }
+
+                let digest = v.get("digest")?.as_str()?.to_string();
+
+                // Try to parse timestamp. GCR format can be tricky.
+                // Output example: "2026-02-05 19:49:35+01:00"
+                let update_time = if let Some(ts) = v.get("timestamp") {
+                    if let Some(dt) = ts.get("datetime").and_then(|d| d.as_str()) {
+                        // Try parsing with timezone offset first (e.g., +01:00)
+                        if let Ok(dt_parsed) = DateTime::parse_from_str(dt, "%Y-%m-%d %H:%M:%S%:z")
+                        {
+                            dt_parsed.with_timezone(&Utc)
+                        } else if let Ok(ndt) =
+                            chrono::NaiveDateTime::parse_from_str(dt, "%Y-%m-%d %H:%M:%S")
+                        {
+                            DateTime::<Utc>::from_naive_utc_and_offset(ndt, Utc)
                         } else {
-                            return None;
+                            // Last resort: use individual fields if present
+                            let year =
+                                ts.get("year").and_then(|y| y.as_i64()).unwrap_or(1970) as i32;
+                            let month =
+                                ts.get("month").and_then(|m| m.as_u64()).unwrap_or(1) as u32;
+                            let day = ts.get("day").and_then(|d| d.as_u64()).unwrap_or(1) as u32;
+                            let hour = ts.get("hour").and_then(|h| h.as_u64()).unwrap_or(0) as u32;
+                            let minute =
+                                ts.get("minute").and_then(|m| m.as_u64()).unwrap_or(0) as u32;
+                            let second =
+                                ts.get("second").and_then(|s| s.as_u64()).unwrap_or(0) as u32;
+
+                            let ndt = chrono::NaiveDate::from_ymd_opt(year, month, day)?
+                                .and_hms_opt(hour, minute, second)?;
+                            DateTime::<Utc>::from_naive_utc_and_offset(ndt, Utc)
                         }
                     } else {
                         return None;
-                    };
+                    }
+                } else {
+                    return None;
+                };
 
-                    Some(ImageMetadata {
-                        tags,
-                        update_time,
-                        name: format!("{}@{}", base_image, digest),
-                    })
+                Some(ImageMetadata {
+                    tags,
+                    update_time,
+                    name: format!("{}@{}", base_image, digest),
                 })
-                .collect();
+            })
+            .collect();
 
-            Ok(images)
-        } else {
-            let output = Command::new("gcloud")
-                .args([
-                    "artifacts",
-                    "docker",
-                    "images",
-                    "list",
-                    base_image,
-                    "--include-tags",
-                    "--format=json",
-                    "--sort-by=~updateTime",
-                ])
-                .output()
-                .context("Failed to execute gcloud command. Is gcloud installed and in PATH?")?;
-
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                return Err(anyhow::anyhow!("gcloud command failed: {}", stderr));
+        Ok(images)
+    }
+
+    /// Lists GCR images through the registry API when gcloud is not installed, with an
+    /// access token as password of the `oauth2accesstoken` user.
+    async fn fetch_gcr_images_over_oci(
+        base_image: &str,
+        access_token: Option<&str>,
+    ) -> Result<Vec<ImageMetadata>> {
+        let credentials = Credentials {
+            username: "oauth2accesstoken".to_string(),
+            password: Self::access_token(access_token).await?,
+        };
+        oci::fetch_images(base_image, Some(credentials)).await
+    }
+
+    /// Lists the images of an ECR repository with `aws ecr describe-images`, newest first.
+    /// Credentials come from the AWS CLI's usual chain (`AWS_PROFILE`, SSO, instance role).
+    fn fetch_ecr_images(
+        repository: &EcrRepository,
+        base_image: &str,
+    ) -> Result<Vec<ImageMetadata>> {
+        let output = Command::new("aws")
+            .args([
+                "ecr",
+                "describe-images",
+                "--registry-id",
+                &repository.registry_id,
+                "--region",
+                &repository.region,
+                "--repository-name",
+                &repository.repository,
+                "--output",
+                "json",
+            ])
+            .output()
+            .context("Failed to execute aws command for ECR. Is the AWS CLI installed?")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!(
+                "aws command failed for ECR: {}",
+                stderr.trim()
+            ));
+        }
+
+        let listing: EcrImageListing =
+            serde_json::from_slice(&output.stdout).context("Failed to parse ECR JSON output")?;
+        let mut images: Vec<ImageMetadata> = listing.images_of(base_image).collect();
+        images.sort_by_key(|image| std::cmp::Reverse(image.update_time));
+        Ok(images)
+    }
+
+    /// Lists the images of an Artifact Registry package through the REST API, newest first.
+    async fn fetch_artifact_registry_images(
+        base_image: &str,
+        access_token: Option<&str>,
+    ) -> Result<Vec<ImageMetadata>> {
+        let repository = ArtifactRepository::parse(base_image).with_context(|| {
+            format!(
+                "Unsupported Artifact Registry image path '{}': expected <location>-docker.pkg.dev/<project>/<repository>/<image>",
+                base_image
+            )
+        })?;
+        let token = Self::access_token(access_token).await?;
+
+        let client = reqwest::Client::new();
+        let mut images = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("pageSize", "1000".to_string())];
+            if let Some(page_token) = &page_token {
+                query.push(("pageToken", page_token.clone()));
             }
 
-            let raw: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)
-                .context("Failed to parse gcloud JSON output")?;
-
-            let images = raw
-                .into_iter()
-                .filter_map(|v| {
-                    let tags = v
-                        .get("tags")
-                        .and_then(|t| t.as_array())
-                        .map(|arr| {
-                            arr.iter()
-                                .filter_map(|t| t.as_str().map(|s| s.to_string()))
-                                .collect::<Vec<_>>()
-                        })
-                        .unwrap_or_default();
-
-                    let update_time: DateTime<Utc> = v
-                        .get("updateTime")?
-                        .as_str()?
-                        .parse()
-                        .ok()?;
-
-                    // name is nested under metadata.name
-                    let name = v
-                        .get("metadata")
-                        .and_then(|m| m.get("name"))
-                        .and_then(|n| n.as_str())
-                        .map(|s| s.to_string())?;
-
-                    Some(ImageMetadata { tags, update_time, name })
-                })
-                .collect();
+            let response = client
+                .get(repository.docker_images_url())
+                .bearer_auth(&token)
+                .query(&query)
+                .send()
+                .await
+                .context("Failed to reach the Artifact Registry API")?;
+
+            let status = response.status();
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_default();
+                return Err(anyhow::anyhow!(
+                    "Artifact Registry API returned {}: {}",
+                    status,
+                    api_error_message(&body)
+                ));
+            }
+
+            let page: DockerImagesPage = response
+                .json()
+                .await
+                .context("Failed to parse Artifact Registry API response")?;
+            let next_page_token = page.next_page_token.clone();
+            images.extend(page.images_of(base_image));
+
+            match next_page_token.filter(|t| !t.is_empty()) {
+                Some(next) => page_token = Some(next),
+                None => break,
+            }
+        }
+
+        images.sort_by_key(|image| std::cmp::Reverse(image.update_time));
+        Ok(images)
+    }
+
+    /// Returns `access_token` when configured, otherwise a token from Application Default Credentials.
+    pub async fn access_token(access_token: Option<&str>) -> Result<String> {
+        match access_token {
+            Some(token) => Ok(token.to_string()),
+            None => Self::default_access_token().await,
+        }
+    }
+
+    /// Checks whether `member` (e.g. `serviceAccount:nodes@project.iam.gserviceaccount.com`)
+    /// holds a role allowing image pulls, on the Artifact Registry repository or its project.
+    pub async fn grants_pull(
+        location: &RegistryLocation,
+        member: &str,
+        token: &str,
+    ) -> Result<bool> {
+        let client = reqwest::Client::new();
+
+        if let Some(repository) = &location.repository {
+            let url = format!(
+                "{}/projects/{}/locations/{}/repositories/{}:getIamPolicy",
+                ARTIFACT_REGISTRY_API, location.project, location.region, repository
+            );
+            let policy = Self::iam_policy(client.get(url).bearer_auth(token)).await?;
+            if policy_grants_pull(&policy, member) {
+                return Ok(true);
+            }
+        }
+
+        let url = format!(
+            "{}/projects/{}:getIamPolicy",
+            RESOURCE_MANAGER_API, location.project
+        );
+        let policy = Self::iam_policy(
+            client
+                .post(url)
+                .bearer_auth(token)
+                .json(&serde_json::json!({})),
+        )
+        .await?;
+        Ok(policy_grants_pull(&policy, member))
+    }
+
+    async fn iam_policy(request: reqwest::RequestBuilder) -> Result<serde_json::Value> {
+        let response = retry::with_backoff("IAM policy read", || async {
+            let request = request
+                .try_clone()
+                .context("IAM policy request cannot be retried")?;
+            let response = request
+                .send()
+                .await
+                .context("Failed to reach the Google IAM API")?;
+            // Server errors are retried; other statuses are reported below.
+            if response.status().is_server_error() {
+                return Err(anyhow::anyhow!(
+                    "Reading IAM policy returned {}",
+                    response.status()
+                ));
+            }
+            Ok(response)
+        })
+        .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "Reading IAM policy returned {}: {}",
+                status,
+                api_error_message(&body)
+            ));
+        }
+        response
+            .json()
+            .await
+            .context("Failed to parse IAM policy response")
+    }
+
+    /// Fetches a token through Application Default Credentials: service account keys,
+    /// `gcloud auth application-default login` or the GCE/GKE metadata server.
+    async fn default_access_token() -> Result<String> {
+        let provider = gcp_auth::provider()
+            .await
+            .context("No Google Application Default Credentials found")?;
+        let token = provider
+            .token(&[CLOUD_PLATFORM_SCOPE])
+            .await
+            .context("Failed to obtain an access token from Application Default Credentials")?;
+        Ok(token.as_str().to_string())
+    }
+}
+
+/// Whether an external command failed because it is not installed.
+fn command_missing(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+    })
+}
 
-            Ok(images)
+/// Roles that include `artifactregistry.repositories.downloadArtifacts`.
+const PULL_ROLES: [&str; 7] = [
+    "roles/artifactregistry.reader",
+    "roles/artifactregistry.writer",
+    "roles/artifactregistry.repoAdmin",
+    "roles/artifactregistry.admin",
+    "roles/viewer",
+    "roles/editor",
+    "roles/owner",
+];
+
+fn policy_grants_pull(policy: &serde_json::Value, member: &str) -> bool {
+    policy
+        .get("bindings")
+        .and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|binding| {
+            binding
+                .get("role")
+                .and_then(|r| r.as_str())
+                .is_some_and(|role| PULL_ROLES.contains(&role))
+        })
+        .filter_map(|binding| binding.get("members").and_then(|m| m.as_array()))
+        .flatten()
+        .filter_map(|m| m.as_str())
+        .any(|m| m == member || m == "allUsers" || m == "allAuthenticatedUsers")
+}
+
+/// Registry host and GCP project an image is pulled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryLocation {
+    pub host: String,
+    pub project: String,
+    /// Artifact Registry region and repository; empty region for gcr.io hosts.
+    pub region: String,
+    pub repository: Option<String>,
+}
+
+impl RegistryLocation {
+    pub fn parse(image: &str) -> Option<Self> {
+        let (host, rest) = image.split_once('/')?;
+        let project = rest.split('/').next().filter(|p| !p.is_empty())?;
+
+        if host.ends_with("gcr.io") {
+            return Some(Self {
+                host: host.to_string(),
+                project: project.to_string(),
+                region: String::new(),
+                repository: None,
+            });
+        }
+
+        let repository = ArtifactRepository::parse(image.split(':').next().unwrap_or(image))?;
+        Some(Self {
+            host: host.to_string(),
+            project: repository.project,
+            region: repository.location,
+            repository: Some(repository.repository),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ArtifactRepository {
+    location: String,
+    project: String,
+    repository: String,
+}
+
+impl ArtifactRepository {
+    /// Parses `<location>-docker.pkg.dev/<project>/<repository>/<image>`.
+    fn parse(base_image: &str) -> Option<Self> {
+        let mut segments = base_image.split('/');
+        let location = segments.next()?.strip_suffix("-docker.pkg.dev")?;
+        let project = segments.next()?;
+        let repository = segments.next()?;
+        segments.next()?;
+
+        Some(Self {
+            location: location.to_string(),
+            project: project.to_string(),
+            repository: repository.to_string(),
+        })
+    }
+
+    fn docker_images_url(&self) -> String {
+        format!(
+            "{}/projects/{}/locations/{}/repositories/{}/dockerImages",
+            ARTIFACT_REGISTRY_API, self.project, self.location, self.repository
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EcrRepository {
+    registry_id: String,
+    region: String,
+    repository: String,
+}
+
+impl EcrRepository {
+    /// Parses `<account>.dkr.ecr.<region>.amazonaws.com[.cn]/<repository>`.
+    fn parse(base_image: &str) -> Option<Self> {
+        let (host, repository) = base_image.split_once('/')?;
+        let (registry_id, rest) = host.split_once(".dkr.ecr.")?;
+        let region = rest
+            .strip_suffix(".amazonaws.com")
+            .or_else(|| rest.strip_suffix(".amazonaws.com.cn"))?;
+        if registry_id.is_empty() || region.is_empty() || repository.is_empty() {
+            return None;
         }
+
+        Some(Self {
+            registry_id: registry_id.to_string(),
+            region: region.to_string(),
+            repository: repository.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EcrImageListing {
+    #[serde(default)]
+    image_details: Vec<EcrImage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EcrImage {
+    image_digest: String,
+    #[serde(default)]
+    image_tags: Vec<String>,
+    image_pushed_at: serde_json::Value,
+}
+
+impl EcrImageListing {
+    fn images_of(self, base_image: &str) -> impl Iterator<Item = ImageMetadata> + '_ {
+        self.image_details.into_iter().filter_map(move |image| {
+            Some(ImageMetadata {
+                update_time: ecr_timestamp(&image.image_pushed_at)?,
+                tags: image.image_tags,
+                name: format!("{}@{}", base_image, image.image_digest),
+            })
+        })
+    }
+}
+
+/// AWS CLI v2 prints timestamps as RFC 3339 strings, v1 as epoch seconds.
+fn ecr_timestamp(value: &serde_json::Value) -> Option<DateTime<Utc>> {
+    match value {
+        serde_json::Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc)),
+        serde_json::Value::Number(n) => {
+            let seconds = n.as_f64()?;
+            DateTime::from_timestamp(seconds.trunc() as i64, (seconds.fract() * 1e9) as u32)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DockerImagesPage {
+    #[serde(default)]
+    docker_images: Vec<DockerImage>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DockerImage {
+    uri: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    update_time: DateTime<Utc>,
+}
+
+impl DockerImagesPage {
+    /// The repository listing covers every package; keep only digests of `base_image`.
+    fn images_of(self, base_image: &str) -> impl Iterator<Item = ImageMetadata> + '_ {
+        self.docker_images
+            .into_iter()
+            .filter(move |image| {
+                image
+                    .uri
+                    .split_once('@')
+                    .is_some_and(|(name, _)| name == base_image)
+            })
+            .map(|image| ImageMetadata {
+                tags: image.tags,
+                update_time: image.update_time,
+                name: image.uri,
+            })
+    }
+}
+
+/// Extracts `error.message` from a Google API error body, falling back to the raw body.
+fn api_error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| {
+            v.get("error")
+                .and_then(|e| e.get("message"))
+                .and_then(|m| m.as_str())
+                .map(|m| m.to_string())
+        })
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_artifact_repository_parses_image_path() {
+        let repository =
+            ArtifactRepository::parse("europe-west1-docker.pkg.dev/acme/apps/team/auth-api")
+                .unwrap();
+        assert_eq!(repository.location, "europe-west1");
+        assert_eq!(repository.project, "acme");
+        assert_eq!(repository.repository, "apps");
+        assert_eq!(
+            repository.docker_images_url(),
+            "https://artifactregistry.googleapis.com/v1/projects/acme/locations/europe-west1/repositories/apps/dockerImages"
+        );
+
+        assert!(ArtifactRepository::parse("europe-west1-docker.pkg.dev/acme/apps").is_none());
+        assert!(ArtifactRepository::parse("docker.io/library/nginx").is_none());
+    }
+
+    #[test]
+    fn test_ecr_listing_parses_both_cli_timestamp_formats() {
+        let base_image = "123456789012.dkr.ecr.eu-west-1.amazonaws.com/team/auth-api";
+        let repository = EcrRepository::parse(base_image).unwrap();
+        assert_eq!(repository.registry_id, "123456789012");
+        assert_eq!(repository.region, "eu-west-1");
+        assert_eq!(repository.repository, "team/auth-api");
+        assert!(EcrRepository::parse("europe-west1-docker.pkg.dev/acme/apps/auth-api").is_none());
+
+        let listing: EcrImageListing = serde_json::from_str(
+            r#"{
+  "imageDetails": [
+    {
+      "registryId": "123456789012",
+      "repositoryName": "team/auth-api",
+      "imageDigest": "sha256:1111111111",
+      "imageTags": ["v1.2.0", "latest"],
+      "imagePushedAt": "2026-02-05T19:49:35+01:00"
+    },
+    {
+      "imageDigest": "sha256:2222222222",
+      "imagePushedAt": 1770317375.5
+    }
+  ]
+}"#,
+        )
+        .unwrap();
+
+        let images: Vec<ImageMetadata> = listing.images_of(base_image).collect();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].tags, vec!["v1.2.0", "latest"]);
+        assert_eq!(images[0].digest(), Some("sha256:1111111111"));
+        assert_eq!(
+            images[0].update_time.to_rfc3339(),
+            "2026-02-05T18:49:35+00:00"
+        );
+        assert!(images[1].tags.is_empty());
+        assert_eq!(images[1].update_time.timestamp(), 1770317375);
+    }
+
+    #[test]
+    fn test_registry_location_and_pull_roles() {
+        let location =
+            RegistryLocation::parse("europe-west1-docker.pkg.dev/shared-images/apps/auth-api:v1")
+                .unwrap();
+        assert_eq!(location.host, "europe-west1-docker.pkg.dev");
+        assert_eq!(location.project, "shared-images");
+        assert_eq!(location.repository.as_deref(), Some("apps"));
+        assert_eq!(
+            RegistryLocation::parse("eu.gcr.io/legacy/auth-api")
+                .unwrap()
+                .project,
+            "legacy"
+        );
+
+        let policy = serde_json::json!({"bindings": [
+            {"role": "roles/artifactregistry.reader", "members": ["serviceAccount:nodes@prod.iam.gserviceaccount.com"]},
+            {"role": "roles/logging.viewer", "members": ["serviceAccount:other@prod.iam.gserviceaccount.com"]}
+        ]});
+        assert!(policy_grants_pull(
+            &policy,
+            "serviceAccount:nodes@prod.iam.gserviceaccount.com"
+        ));
+        assert!(!policy_grants_pull(
+            &policy,
+            "serviceAccount:other@prod.iam.gserviceaccount.com"
+        ));
+    }
+
+    #[test]
+    fn test_docker_images_page_keeps_only_requested_image() {
+        let page: DockerImagesPage = serde_json::from_str(
+            r#"{
+  "dockerImages": [
+    {
+      "uri": "europe-west1-docker.pkg.dev/acme/apps/auth-api@sha256:1111111111",
+      "tags": ["v1.2.0"],
+      "updateTime": "2026-02-05T18:49:35.123Z"
+    },
+    {
+      "uri": "europe-west1-docker.pkg.dev/acme/apps/auth-api-worker@sha256:2222222222",
+      "tags": ["v9.0.0"],
+      "updateTime": "2026-02-05T18:49:35Z"
+    }
+  ],
+  "nextPageToken": "abc"
+}"#,
+        )
+        .unwrap();
+
+        let images: Vec<ImageMetadata> = page
+            .images_of("europe-west1-docker.pkg.dev/acme/apps/auth-api")
+            .collect();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].tags, vec!["v1.2.0"]);
+        assert_eq!(images[0].short_hash(), "1111111");
     }
 }
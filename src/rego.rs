@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::config::Environment;
+
+/// A failed or warning Rego rule reported by conftest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegoResult {
+    pub namespace: String,
+    pub rule: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RegoReport {
+    pub failures: Vec<RegoResult>,
+    pub warnings: Vec<RegoResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConftestOutput {
+    #[serde(default)]
+    namespace: String,
+    #[serde(default)]
+    failures: Option<Vec<ConftestResult>>,
+    #[serde(default)]
+    warnings: Option<Vec<ConftestResult>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConftestResult {
+    msg: String,
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+}
+
+impl RegoReport {
+    /// Parses `conftest test --output json`.
+    pub fn from_conftest_json(output: &[u8]) -> Result<Self> {
+        let outputs: Vec<ConftestOutput> =
+            serde_json::from_slice(output).context("Failed to parse conftest JSON output")?;
+        let mut report = Self::default();
+
+        for output in outputs {
+            let convert = |results: Option<Vec<ConftestResult>>| {
+                results.into_iter().flatten().map(|r| RegoResult {
+                    namespace: output.namespace.clone(),
+                    rule: r
+                        .metadata
+                        .as_ref()
+                        .and_then(|m| m.get("query"))
+                        .and_then(|q| q.as_str())
+                        .map(|q| q.trim_start_matches("data.").to_string())
+                        .unwrap_or_else(|| output.namespace.clone()),
+                    message: r.msg,
+                })
+            };
+            report.failures.extend(convert(output.failures));
+            report.warnings.extend(convert(output.warnings));
+        }
+
+        Ok(report)
+    }
+
+    pub fn print(&self) {
+        for warning in &self.warnings {
+            println!("⚠️  Rego {}: {}", warning.rule, warning.message);
+        }
+        for failure in &self.failures {
+            println!("❌ Rego {}: {}", failure.rule, failure.message);
+        }
+    }
+}
+
+/// Policy directory for an environment: the configured one or, with only a bundle,
+/// a per-environment cache directory conftest downloads it into.
+fn policy_dir(env: &Environment) -> Result<Option<PathBuf>> {
+    if let Some(dir) = &env.rego_policy_dir {
+        return Ok(Some(dir.clone()));
+    }
+    if env.rego_policy_bundle.is_none() {
+        return Ok(None);
+    }
+
+    let proj_dirs = ProjectDirs::from("com", "davit", "davit")
+        .context("Could not determine project directories")?;
+    Ok(Some(proj_dirs.cache_dir().join("policy").join(&env.name)))
+}
+
+/// Evaluates the manifest with conftest against the environment's Rego policies.
+/// Returns `None` when the environment has no policies configured.
+pub fn evaluate(env: &Environment, manifest: &str) -> Result<Option<RegoReport>> {
+    let Some(dir) = policy_dir(env)? else {
+        return Ok(None);
+    };
+
+    let mut command = Command::new("conftest");
+    command.arg("test").arg("--policy").arg(&dir).args([
+        "--all-namespaces",
+        "--parser",
+        "yaml",
+        "--output",
+        "json",
+    ]);
+    if let Some(bundle) = &env.rego_policy_bundle {
+        command.arg("--update").arg(bundle);
+    }
+
+    let mut child = command
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute conftest. Is it installed and in PATH?")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(manifest.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+
+    // conftest exits non-zero when rules fail, so rely on the JSON report instead.
+    if output.stdout.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("conftest failed: {}", stderr.trim()));
+    }
+
+    RegoReport::from_conftest_json(&output.stdout).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_from_conftest_json_keeps_rule_names() -> Result<()> {
+        let output = br#"[
+  {
+    "filename": "",
+    "namespace": "kubernetes.security",
+    "successes": 3,
+    "failures": [
+      {"msg": "Containers must not run as root", "metadata": {"query": "data.kubernetes.security.deny"}}
+    ],
+    "warnings": [
+      {"msg": "Image uses latest tag"}
+    ]
+  },
+  {"filename": "", "namespace": "main", "successes": 1}
+]"#;
+
+        let report = RegoReport::from_conftest_json(output)?;
+        assert_eq!(
+            report.failures,
+            vec![RegoResult {
+                namespace: "kubernetes.security".to_string(),
+                rule: "kubernetes.security.deny".to_string(),
+                message: "Containers must not run as root".to_string(),
+            }]
+        );
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].rule, "kubernetes.security");
+
+        Ok(())
+    }
+}
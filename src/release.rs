@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A service of a release manifest with the tag it is deployed at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseService {
+    pub name: String,
+    pub tag: String,
+    /// Services of the release that must be rolled out before this one.
+    pub after: Vec<String>,
+}
+
+/// A manifest file of the release with the tags of its services set.
+#[derive(Debug, Clone)]
+pub struct ReleaseFile {
+    pub path: PathBuf,
+    pub source_root: PathBuf,
+    pub original: String,
+    pub updated: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DetailedEntry {
+    tag: Value,
+    #[serde(default)]
+    after: Vec<String>,
+}
+
+/// Reads a release manifest, mapping each service to its tag, either directly
+/// (`auth-api: v1.4.0`) or with the services it waits for
+/// (`web: {tag: v2.0.1, after: [auth-api]}`), and returns its services in apply order.
+pub fn read(path: &Path) -> Result<Vec<ReleaseService>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the release manifest {}", path.display()))?;
+    parse(&content)
+        .and_then(order)
+        .with_context(|| format!("Invalid release manifest {}", path.display()))
+}
+
+fn parse(content: &str) -> Result<Vec<ReleaseService>> {
+    let entries: Mapping = serde_yaml::from_str(content)
+        .context("Expected a mapping of service names to image tags")?;
+    let mut services = Vec::new();
+    for (name, value) in entries {
+        let name = scalar(&name).context("Service names must be strings")?;
+        let (tag, after) = match value {
+            Value::Mapping(_) => {
+                let entry: DetailedEntry = serde_yaml::from_value(value)
+                    .with_context(|| format!("Invalid entry for {}", name))?;
+                (scalar(&entry.tag), entry.after)
+            }
+            value => (scalar(&value), Vec::new()),
+        };
+        let tag = tag
+            .filter(|t| !t.is_empty())
+            .with_context(|| format!("Missing tag for {}", name))?;
+        services.push(ReleaseService { name, tag, after });
+    }
+    if services.is_empty() {
+        return Err(anyhow::anyhow!("The release lists no services"));
+    }
+    Ok(services)
+}
+
+/// Tags such as `2024.06` are read by YAML as numbers.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Sorts the services so that each comes after those it lists in `after`, keeping the
+/// manifest order otherwise.
+fn order(mut pending: Vec<ReleaseService>) -> Result<Vec<ReleaseService>> {
+    for service in &pending {
+        if let Some(unknown) = service
+            .after
+            .iter()
+            .find(|dep| !pending.iter().any(|s| &s.name == *dep))
+        {
+            return Err(anyhow::anyhow!(
+                "{} comes after {}, which is not part of the release",
+                service.name,
+                unknown
+            ));
+        }
+    }
+    let mut ordered: Vec<ReleaseService> = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|service| {
+            service
+                .after
+                .iter()
+                .all(|dep| ordered.iter().any(|s| &s.name == dep))
+        });
+        let Some(ready) = ready else {
+            let names: Vec<&str> = pending.iter().map(|s| s.name.as_str()).collect();
+            return Err(anyhow::anyhow!(
+                "The ordering of {} is circular",
+                names.join(", ")
+            ));
+        };
+        ordered.push(pending.remove(ready));
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_services_follow_their_dependencies() -> Result<()> {
+        let services = order(parse(
+            r#"web:
+  tag: v2.0.1
+  after: [auth-api, billing]
+auth-api: v1.4.0
+billing: 2024.06
+worker:
+  tag: v3
+  after: [auth-api]
+"#,
+        )?)?;
+        let names: Vec<&str> = services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["auth-api", "billing", "web", "worker"]);
+        assert_eq!(services[1].tag, "2024.06");
+
+        let circular = parse("a: {tag: v1, after: [b]}\nb: {tag: v1, after: [a]}\n")?;
+        assert!(order(circular).is_err());
+        let unknown = parse("a: {tag: v1, after: [c]}\n")?;
+        assert!(order(unknown).is_err());
+        assert!(parse("a:\n").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use console::style;
+use regex::Regex;
+
+use crate::git::GitCommitMessage;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseNote {
+    pub scope: Option<String>,
+    pub description: String,
+    pub hash: String,
+}
+
+/// Commit range between two image versions grouped as conventional commits.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReleaseNotes {
+    pub breaking: Vec<ReleaseNote>,
+    pub features: Vec<ReleaseNote>,
+    pub fixes: Vec<ReleaseNote>,
+    pub other: Vec<ReleaseNote>,
+}
+
+impl ReleaseNotes {
+    pub fn from_commits(commits: &[GitCommitMessage]) -> Self {
+        let header = Regex::new(
+            r"^(?P<type>[a-zA-Z]+)(?:\((?P<scope>[^)]*)\))?(?P<bang>!)?:\s*(?P<desc>.+)$",
+        )
+        .unwrap();
+        let mut notes = Self::default();
+
+        for commit in commits {
+            let short_hash = commit.hash.get(0..7).unwrap_or(&commit.hash).to_string();
+            let breaking_footer = commit
+                .body
+                .lines()
+                .any(|l| l.starts_with("BREAKING CHANGE:") || l.starts_with("BREAKING-CHANGE:"));
+
+            let Some(caps) = header.captures(commit.subject.trim()) else {
+                notes.other.push(ReleaseNote {
+                    scope: None,
+                    description: commit.subject.trim().to_string(),
+                    hash: short_hash,
+                });
+                continue;
+            };
+
+            let note = ReleaseNote {
+                scope: caps
+                    .name("scope")
+                    .map(|s| s.as_str().to_string())
+                    .filter(|s| !s.is_empty()),
+                description: caps["desc"].trim().to_string(),
+                hash: short_hash,
+            };
+
+            if caps.name("bang").is_some() || breaking_footer {
+                notes.breaking.push(note);
+                continue;
+            }
+
+            match caps["type"].to_ascii_lowercase().as_str() {
+                "feat" => notes.features.push(note),
+                "fix" => notes.fixes.push(note),
+                _ => notes.other.push(note),
+            }
+        }
+
+        notes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breaking.is_empty()
+            && self.features.is_empty()
+            && self.fixes.is_empty()
+            && self.other.is_empty()
+    }
+
+    fn sections(&self) -> [(&'static str, &Vec<ReleaseNote>); 4] {
+        [
+            ("Breaking changes", &self.breaking),
+            ("Features", &self.features),
+            ("Fixes", &self.fixes),
+            ("Other", &self.other),
+        ]
+    }
+
+    /// Renders the notes as markdown, suitable for commit bodies and notifications.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        for (title, notes) in self.sections() {
+            if notes.is_empty() {
+                continue;
+            }
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("### {}\n", title));
+            for note in notes {
+                out.push_str(&format!("- {} ({})\n", note.display(), note.hash));
+            }
+        }
+        out
+    }
+
+    /// Prints the notes to the terminal with section highlighting.
+    pub fn print(&self) {
+        for (title, notes) in self.sections() {
+            if notes.is_empty() {
+                continue;
+            }
+            let heading = if title == "Breaking changes" {
+                style(title).red().bold()
+            } else {
+                style(title).bold()
+            };
+            println!("{}", heading);
+            for note in notes {
+                println!("  - {} {}", note.display(), style(&note.hash).dim());
+            }
+        }
+    }
+}
+
+impl ReleaseNote {
+    fn display(&self) -> String {
+        match &self.scope {
+            Some(scope) => format!("{}: {}", scope, self.description),
+            None => self.description.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(subject: &str, body: &str) -> GitCommitMessage {
+        GitCommitMessage {
+            hash: "0123456789abcdef".to_string(),
+            subject: subject.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_release_notes_groups_conventional_commits() {
+        let notes = ReleaseNotes::from_commits(&[
+            commit("feat(api): add login endpoint", ""),
+            commit("fix: handle empty token", ""),
+            commit("feat!: drop v1 routes", ""),
+            commit(
+                "refactor(db): change schema",
+                "BREAKING CHANGE: column renamed",
+            ),
+            commit("chore: bump deps", ""),
+            commit("Merge branch 'main'", ""),
+        ]);
+
+        assert_eq!(notes.features.len(), 1);
+        assert_eq!(notes.features[0].scope.as_deref(), Some("api"));
+        assert_eq!(notes.fixes.len(), 1);
+        assert_eq!(notes.breaking.len(), 2);
+        assert_eq!(notes.other.len(), 2);
+        assert_eq!(notes.features[0].hash, "0123456");
+    }
+
+    #[test]
+    fn test_release_notes_markdown_skips_empty_sections() {
+        let notes = ReleaseNotes::from_commits(&[commit("fix: handle empty token", "")]);
+        assert_eq!(
+            notes.to_markdown(),
+            "### Fixes\n- handle empty token (0123456)\n"
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use anyhow::Result;
+use console::style;
+use kube::api::{ApiResource, DynamicObject, GroupVersionKind, ListParams};
+use kube::{Api, Client};
+
+use crate::blueprint::find_pod_spec;
+use crate::config::{Pricing, ServiceSource};
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// A resource quantity as written in the manifest and its value in cores or bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    pub raw: String,
+    pub value: f64,
+}
+
+/// CPU and memory quantities of a container.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Quantities {
+    pub cpu: Option<Quantity>,
+    pub memory: Option<Quantity>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerResources {
+    pub name: String,
+    pub requests: Quantities,
+    pub limits: Quantities,
+}
+
+/// Replicas and per-container resources of a workload manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadResources {
+    pub kind: String,
+    pub replicas: Option<i64>,
+    pub containers: Vec<ContainerResources>,
+}
+
+impl WorkloadResources {
+    pub fn from_workload(workload: &serde_yaml::Value) -> Option<Self> {
+        let kind = workload.get("kind")?.as_str()?.to_string();
+        let replicas = workload
+            .get("spec")
+            .and_then(|s| s.get("replicas"))
+            .and_then(|r| r.as_i64());
+        let containers = find_pod_spec(workload)?
+            .get("containers")?
+            .as_sequence()?
+            .iter()
+            .map(|c| ContainerResources {
+                name: c
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or("default")
+                    .to_string(),
+                requests: quantities(c.get("resources").and_then(|r| r.get("requests"))),
+                limits: quantities(c.get("resources").and_then(|r| r.get("limits"))),
+            })
+            .collect();
+
+        Some(Self {
+            kind,
+            replicas,
+            containers,
+        })
+    }
+
+    /// Pods the requests are multiplied by; a DaemonSet is counted per node.
+    fn pod_count(&self) -> f64 {
+        match self.kind.as_str() {
+            "Deployment" | "StatefulSet" => self.replicas.unwrap_or(1) as f64,
+            _ => 1.0,
+        }
+    }
+
+    /// Total requested CPU cores and memory bytes across all replicas.
+    pub fn total_requests(&self) -> (f64, f64) {
+        let (cpu, memory) = self.containers.iter().fold((0.0, 0.0), |(cpu, mem), c| {
+            (
+                cpu + c.requests.cpu.as_ref().map_or(0.0, |q| q.value),
+                mem + c.requests.memory.as_ref().map_or(0.0, |q| q.value),
+            )
+        });
+        (cpu * self.pod_count(), memory * self.pod_count())
+    }
+}
+
+fn quantities(value: Option<&serde_yaml::Value>) -> Quantities {
+    let field = |key: &str| value.and_then(|v| v.get(key)).and_then(quantity_string);
+    let parse = |raw: Option<String>, parser: fn(&str) -> Option<f64>| {
+        raw.and_then(|raw| parser(&raw).map(|value| Quantity { raw, value }))
+    };
+    Quantities {
+        cpu: parse(field("cpu"), parse_cpu),
+        memory: parse(field("memory"), parse_memory),
+    }
+}
+
+fn quantity_string(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Parses a CPU quantity (`500m`, `1`, `0.5`, or `12345678n` as the metrics API
+/// reports usage) into cores.
+pub fn parse_cpu(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    for (suffix, divisor) in [("m", 1e3), ("u", 1e6), ("n", 1e9)] {
+        if let Some(value) = quantity.strip_suffix(suffix) {
+            return value.parse::<f64>().ok().map(|v| v / divisor);
+        }
+    }
+    quantity.parse().ok()
+}
+
+/// Parses a memory quantity (`512Mi`, `1G`, `1.5Gi`, `1e9`) into bytes.
+pub fn parse_memory(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let suffixes: [(&str, f64); 12] = [
+        ("Ki", 1024.0),
+        ("Mi", 1024.0_f64.powi(2)),
+        ("Gi", 1024.0_f64.powi(3)),
+        ("Ti", 1024.0_f64.powi(4)),
+        ("Pi", 1024.0_f64.powi(5)),
+        ("Ei", 1024.0_f64.powi(6)),
+        ("k", 1e3),
+        ("M", 1e6),
+        ("G", 1e9),
+        ("T", 1e12),
+        ("P", 1e15),
+        ("E", 1e18),
+    ];
+
+    for (suffix, multiplier) in suffixes {
+        if let Some(number) = quantity.strip_suffix(suffix) {
+            return number.parse::<f64>().ok().map(|n| n * multiplier);
+        }
+    }
+    quantity.parse().ok()
+}
+
+/// Summarizes replica and per-container request/limit changes, one line per workload
+/// property, e.g. `main: cpu request 500m→1, memory limit unchanged`.
+pub fn summarize_changes(
+    before: Option<&WorkloadResources>,
+    after: &WorkloadResources,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    let replicas_before = before.and_then(|b| b.replicas);
+    if replicas_before != after.replicas {
+        lines.push(format!(
+            "replicas {}→{}",
+            format_optional(replicas_before.map(|r| r.to_string()).as_deref()),
+            format_optional(after.replicas.map(|r| r.to_string()).as_deref())
+        ));
+    }
+
+    for container in &after.containers {
+        let previous = before.and_then(|b| b.containers.iter().find(|c| c.name == container.name));
+        let Some(previous) = previous else {
+            lines.push(format!("{}: new container", container.name));
+            continue;
+        };
+        if previous == container {
+            continue;
+        }
+
+        let fields = [
+            (
+                "cpu request",
+                &previous.requests.cpu,
+                &container.requests.cpu,
+            ),
+            ("cpu limit", &previous.limits.cpu, &container.limits.cpu),
+            (
+                "memory request",
+                &previous.requests.memory,
+                &container.requests.memory,
+            ),
+            (
+                "memory limit",
+                &previous.limits.memory,
+                &container.limits.memory,
+            ),
+        ];
+        let changes: Vec<String> = fields
+            .iter()
+            .map(|(label, old, new)| {
+                if old.as_ref().map(|q| q.value) == new.as_ref().map(|q| q.value) {
+                    format!("{} unchanged", label)
+                } else {
+                    format!(
+                        "{} {}→{}",
+                        label,
+                        format_optional(old.as_ref().map(|q| q.raw.as_str())),
+                        format_optional(new.as_ref().map(|q| q.raw.as_str()))
+                    )
+                }
+            })
+            .collect();
+        if changes.iter().any(|c| !c.ends_with("unchanged")) {
+            lines.push(format!("{}: {}", container.name, changes.join(", ")));
+        }
+    }
+
+    for removed in before
+        .map(|b| b.containers.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|c| !after.containers.iter().any(|a| a.name == c.name))
+    {
+        lines.push(format!("{}: container removed", removed.name));
+    }
+
+    lines
+}
+
+/// Prints the resource summary shown above the raw diff.
+pub fn print_changes(lines: &[String]) {
+    if lines.is_empty() {
+        println!("{}", style("Resources: unchanged").dim());
+        return;
+    }
+
+    println!("{}", style("Resource changes").bold());
+    for line in lines {
+        println!("  {}", style(line).yellow());
+    }
+}
+
+fn format_optional(value: Option<&str>) -> &str {
+    value.unwrap_or("none")
+}
+
+/// Change in requested capacity between the running workload and the new manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityImpact {
+    pub cpu_before: f64,
+    pub cpu_after: f64,
+    pub memory_before: f64,
+    pub memory_after: f64,
+    pub per_node: bool,
+}
+
+impl CapacityImpact {
+    /// Returns `None` when the requested capacity does not change.
+    pub fn between(before: Option<&WorkloadResources>, after: &WorkloadResources) -> Option<Self> {
+        let (cpu_before, memory_before) = before.map(|b| b.total_requests()).unwrap_or_default();
+        let (cpu_after, memory_after) = after.total_requests();
+
+        if (cpu_after - cpu_before).abs() < 1e-9 && (memory_after - memory_before).abs() < 1.0 {
+            return None;
+        }
+
+        Some(Self {
+            cpu_before,
+            cpu_after,
+            memory_before,
+            memory_after,
+            per_node: after.kind == "DaemonSet",
+        })
+    }
+
+    /// Approximate monthly cost delta with the configured prices.
+    pub fn monthly_cost_delta(&self, pricing: &Pricing) -> f64 {
+        (self.cpu_after - self.cpu_before) * pricing.cpu_core_month
+            + (self.memory_after - self.memory_before) / GIB * pricing.memory_gib_month
+    }
+
+    pub fn print(&self, pricing: Option<&Pricing>) {
+        let scope = if self.per_node { " (per node)" } else { "" };
+        println!("{}", style(format!("Capacity impact{}", scope)).bold());
+        println!(
+            "  CPU requested:    {} → {} cores ({:+.3})",
+            format_cores(self.cpu_before),
+            format_cores(self.cpu_after),
+            self.cpu_after - self.cpu_before
+        );
+        println!(
+            "  Memory requested: {:.2} → {:.2} GiB ({:+.2})",
+            self.memory_before / GIB,
+            self.memory_after / GIB,
+            (self.memory_after - self.memory_before) / GIB
+        );
+        if let Some(pricing) = pricing {
+            let delta = self.monthly_cost_delta(pricing);
+            let line = format!(
+                "  Estimated cost:   {:+.2} {}/month",
+                delta, pricing.currency
+            );
+            if delta > 0.0 {
+                println!("{}", style(line).yellow());
+            } else {
+                println!("{}", style(line).green());
+            }
+        }
+    }
+}
+
+fn format_cores(cores: f64) -> String {
+    let formatted = format!("{:.3}", cores);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Fetches the running workload from the cluster as YAML, `None` if it does not exist yet.
+pub async fn live_workload(
+    client: &Client,
+    service: &ServiceSource,
+) -> Result<Option<serde_yaml::Value>> {
+    let group = match service.kind.as_str() {
+        "Job" | "CronJob" => "batch",
+        _ => "apps",
+    };
+    let resource = ApiResource::from_gvk(&GroupVersionKind::gvk(group, "v1", &service.kind));
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+    let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace, &resource);
+
+    let Some(object) = api.get_opt(&service.name).await? else {
+        return Ok(None);
+    };
+    let mut value = serde_yaml::to_value(&object)?;
+    if let Some(map) = value.as_mapping_mut() {
+        map.insert("kind".into(), service.kind.clone().into());
+    }
+    Ok(Some(value))
+}
+
+/// Current CPU (cores) and memory (bytes) usage of a pod, summed over its containers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PodUsage {
+    pub pod: String,
+    pub cpu: f64,
+    pub memory: f64,
+}
+
+/// Usage of the pods matching `selector`, from the metrics API (metrics-server).
+pub async fn pod_usage(client: Client, namespace: &str, selector: &str) -> Result<Vec<PodUsage>> {
+    let resource = ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "metrics.k8s.io",
+        "v1beta1",
+        "PodMetrics",
+    ));
+    let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &resource);
+    let metrics = api.list(&ListParams::default().labels(selector)).await?;
+    Ok(metrics.items.iter().map(usage_of).collect())
+}
+
+fn usage_of(metrics: &DynamicObject) -> PodUsage {
+    let containers = metrics.data["containers"].as_array();
+    let total = |resource: &str, parse: fn(&str) -> Option<f64>| {
+        containers
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c["usage"][resource].as_str().and_then(parse))
+            .sum()
+    };
+    PodUsage {
+        pod: metrics.metadata.name.clone().unwrap_or_default(),
+        cpu: total("cpu", parse_cpu),
+        memory: total("memory", parse_memory),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workload(replicas: i64, cpu: &str, memory: &str) -> WorkloadResources {
+        let yaml = format!(
+            r#"
+kind: Deployment
+spec:
+  replicas: {replicas}
+  template:
+    spec:
+      containers:
+      - name: main
+        resources:
+          requests:
+            cpu: {cpu}
+            memory: {memory}
+"#
+        );
+        WorkloadResources::from_workload(&serde_yaml::from_str(&yaml).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_quantities() {
+        assert_eq!(parse_cpu("500m"), Some(0.5));
+        assert_eq!(parse_cpu("2"), Some(2.0));
+        assert_eq!(parse_cpu("250000000n"), Some(0.25));
+        assert_eq!(parse_memory("512Mi"), Some(512.0 * 1024.0 * 1024.0));
+        assert_eq!(parse_memory("1G"), Some(1e9));
+        assert_eq!(parse_memory("1e3"), Some(1000.0));
+        assert_eq!(parse_memory("lots"), None);
+    }
+
+    #[test]
+    fn test_summarize_changes_lists_changed_fields_only() {
+        let before = workload(3, "500m", "1Gi");
+        let after = workload(5, "1", "1024Mi");
+
+        assert_eq!(
+            summarize_changes(Some(&before), &after),
+            vec![
+                "replicas 3→5",
+                "main: cpu request 500m→1, cpu limit unchanged, memory request unchanged, memory limit unchanged",
+            ]
+        );
+        assert!(summarize_changes(Some(&before), &before).is_empty());
+        assert_eq!(
+            summarize_changes(None, &before),
+            vec!["replicas none→3", "main: new container"]
+        );
+    }
+
+    #[test]
+    fn test_capacity_impact_multiplies_by_replicas_and_prices() {
+        let before = workload(3, "500m", "1Gi");
+        let after = workload(5, "500m", "1Gi");
+
+        let impact = CapacityImpact::between(Some(&before), &after).unwrap();
+        assert_eq!(impact.cpu_before, 1.5);
+        assert_eq!(impact.cpu_after, 2.5);
+        assert_eq!(impact.memory_after - impact.memory_before, 2.0 * GIB);
+
+        let pricing = Pricing {
+            cpu_core_month: 20.0,
+            memory_gib_month: 3.0,
+            currency: "EUR".to_string(),
+        };
+        assert_eq!(impact.monthly_cost_delta(&pricing), 26.0);
+
+        assert_eq!(CapacityImpact::between(Some(&before), &before), None);
+    }
+}
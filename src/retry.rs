@@ -0,0 +1,201 @@
+use anyhow::Result;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::RetryPolicy;
+use crate::git::GitError;
+use crate::output;
+
+static POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+
+/// Sets the policy used by `with_backoff`; the defaults apply until then.
+pub fn configure(policy: RetryPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+fn policy() -> RetryPolicy {
+    POLICY.get().copied().unwrap_or_default()
+}
+
+/// Runs `operation`, retrying transient failures with exponential backoff.
+pub async fn with_backoff<T, F, Fut>(what: &str, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let policy = policy();
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if attempt < policy.attempts && is_transient(&e) => {
+                let delay = policy.delay(attempt);
+                output::progress(format!(
+                    "⏳ {} failed ({}), retrying in {:.1}s ({}/{})...",
+                    what,
+                    e,
+                    delay.as_secs_f64(),
+                    attempt,
+                    policy.attempts - 1
+                ));
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Blocking variant of `with_backoff`, for external commands.
+pub fn with_backoff_blocking<T>(what: &str, mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+    let policy = policy();
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Err(e) if attempt < policy.attempts && is_transient(&e) => {
+                let delay = policy.delay(attempt);
+                output::progress(format!(
+                    "⏳ {} failed ({}), retrying in {:.1}s ({}/{})...",
+                    what,
+                    e,
+                    delay.as_secs_f64(),
+                    attempt,
+                    policy.attempts - 1
+                ));
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether an error is worth retrying: timeouts, dropped connections, throttling,
+/// server errors and rejected non-fast-forward pushes.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            if e.is_timeout() || e.is_connect() {
+                return true;
+            }
+            if let Some(status) = e.status() {
+                return status.is_server_error() || status.as_u16() == 429;
+            }
+        }
+        if let Some(e) = cause.downcast_ref::<GitError>() {
+            return e.is_transient();
+        }
+        if let Some(kube::Error::Api(status)) = cause.downcast_ref::<kube::Error>() {
+            return status.code >= 500 || status.code == 429;
+        }
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind::*;
+            if matches!(
+                e.kind(),
+                TimedOut | ConnectionReset | ConnectionAborted | ConnectionRefused | BrokenPipe
+            ) {
+                return true;
+            }
+        }
+    }
+
+    let message = format!("{:#}", error).to_lowercase();
+    [
+        "timed out",
+        "timeout",
+        "connection reset",
+        "connection refused",
+        "temporarily unavailable",
+        "too many requests",
+        "returned 429",
+        "returned 500",
+        "returned 502",
+        "returned 503",
+        "returned 504",
+        "internal error",
+        "non-fast-forward",
+        "fetch first",
+        "could not resolve host",
+        "unexpected disconnect",
+        "the remote end hung up",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+impl RetryPolicy {
+    /// Delay before the retry following `attempt` (1-based), doubling up to `max_delay_ms`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(
+            self.initial_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_doubles_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            attempts: 5,
+            initial_delay_ms: 500,
+            max_delay_ms: 1500,
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(2), Duration::from_millis(1000));
+        assert_eq!(policy.delay(3), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&anyhow::anyhow!(
+            "Artifact Registry API returned 503 Service Unavailable: backend error"
+        )));
+        assert!(is_transient(&anyhow::anyhow!(
+            "git push rejected: ! [rejected] main -> main (non-fast-forward)"
+        )));
+        assert!(is_transient(&anyhow::Error::new(std::io::Error::from(
+            std::io::ErrorKind::TimedOut
+        ))));
+        assert!(!is_transient(&anyhow::anyhow!(
+            "Artifact Registry API returned 403 Forbidden: permission denied"
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_with_backoff_retries_transient_errors_only() {
+        configure(RetryPolicy {
+            attempts: 3,
+            initial_delay_ms: 1,
+            max_delay_ms: 1,
+        });
+
+        let mut calls = 0;
+        let result = with_backoff("test", || {
+            calls += 1;
+            let attempt = calls;
+            async move {
+                if attempt < 3 {
+                    Err(anyhow::anyhow!("connection reset by peer"))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let result: Result<()> = with_backoff_blocking("test", || {
+            calls += 1;
+            Err(anyhow::anyhow!("permission denied"))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::blueprint::{find_kustomization, split_image_ref};
+use crate::config::Environment;
+
+/// A new service to generate manifests for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceScaffold {
+    pub name: String,
+    pub image: String,
+    pub namespace: Option<String>,
+    /// Container port, exposed through a Service when set.
+    pub port: Option<u16>,
+    pub replicas: u32,
+}
+
+/// Writes the manifests of a new service into the environment's YAML directory and
+/// returns the file path. Existing files are never overwritten.
+pub fn write_service(env: &Environment, scaffold: &ServiceScaffold) -> Result<PathBuf> {
+    if split_image_ref(&scaffold.image).1.is_none() {
+        return Err(anyhow::anyhow!(
+            "Image '{}' needs a tag (e.g. {}:v0.1.0)",
+            scaffold.image,
+            scaffold.image
+        ));
+    }
+    if !env.is_deployable_image(&scaffold.image) {
+        return Err(anyhow::anyhow!(
+            "Image '{}' is not from a registry of {} (`image_registries`), so davit would not discover the service",
+            scaffold.image,
+            env.name
+        ));
+    }
+    if env.env_yaml_dir.as_os_str().is_empty() {
+        return Err(anyhow::anyhow!(
+            "Environment '{}' has no `env_yaml_dir` to write manifests to",
+            env.name
+        ));
+    }
+
+    let path = env.env_yaml_dir.join(format!("{}.yaml", scaffold.name));
+    if path.exists() {
+        return Err(anyhow::anyhow!("{} already exists", path.display()));
+    }
+    fs::create_dir_all(&env.env_yaml_dir)
+        .with_context(|| format!("Failed to create {}", env.env_yaml_dir.display()))?;
+    fs::write(&path, render_service(scaffold))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    if find_kustomization(&env.env_yaml_dir).is_some() {
+        println!(
+            "⚠️  {} is a kustomize overlay: add {}.yaml to its resources.",
+            env.env_yaml_dir.display(),
+            scaffold.name
+        );
+    }
+    Ok(path)
+}
+
+/// Deployment (and Service when a port is set) following the discovery conventions:
+/// an `app` label used as selector, the container named after the service.
+pub fn render_service(scaffold: &ServiceScaffold) -> String {
+    let name = &scaffold.name;
+    let namespace = scaffold
+        .namespace
+        .as_ref()
+        .map(|ns| format!("\n  namespace: {}", ns))
+        .unwrap_or_default();
+    let ports = scaffold
+        .port
+        .map(|port| {
+            format!(
+                "\n          ports:\n            - name: http\n              containerPort: {}",
+                port
+            )
+        })
+        .unwrap_or_default();
+
+    let mut yaml = format!(
+        r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: {name}{namespace}
+  labels:
+    app: {name}
+spec:
+  replicas: {replicas}
+  selector:
+    matchLabels:
+      app: {name}
+  template:
+    metadata:
+      labels:
+        app: {name}
+    spec:
+      containers:
+        - name: {name}
+          image: {image}{ports}
+          resources:
+            requests:
+              cpu: 100m
+              memory: 128Mi
+            limits:
+              memory: 256Mi
+"#,
+        replicas = scaffold.replicas,
+        image = scaffold.image,
+    );
+
+    if let Some(port) = scaffold.port {
+        yaml.push_str(&format!(
+            r#"---
+apiVersion: v1
+kind: Service
+metadata:
+  name: {name}{namespace}
+  labels:
+    app: {name}
+spec:
+  selector:
+    app: {name}
+  ports:
+    - name: http
+      port: 80
+      targetPort: {port}
+"#
+        ));
+    }
+    yaml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scaffolded_service_is_discovered() -> Result<()> {
+        let dir = tempdir()?;
+        let env = Environment {
+            name: "staging".to_string(),
+            env_yaml_dir: dir.path().join("k8s"),
+            kubectl_context: "staging".to_string(),
+            ..Default::default()
+        };
+        let scaffold = ServiceScaffold {
+            name: "invoices".to_string(),
+            image: "europe-docker.pkg.dev/acme/apps/invoices:v0.1.0".to_string(),
+            namespace: Some("billing".to_string()),
+            port: Some(8080),
+            replicas: 2,
+        };
+
+        let path = write_service(&env, &scaffold)?;
+        assert_eq!(path, dir.path().join("k8s/invoices.yaml"));
+        assert!(write_service(&env, &scaffold).is_err());
+
+        let services = env.list_services()?;
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].name, "invoices");
+        assert_eq!(services[0].namespace.as_deref(), Some("billing"));
+        assert_eq!(services[0].selector.as_deref(), Some("app=invoices"));
+        assert_eq!(services[0].image_path, scaffold.image);
+
+        let untagged = ServiceScaffold {
+            name: "other".to_string(),
+            image: "europe-docker.pkg.dev/acme/apps/other".to_string(),
+            ..scaffold.clone()
+        };
+        assert!(write_service(&env, &untagged).is_err());
+        let foreign = ServiceScaffold {
+            name: "other".to_string(),
+            image: "docker.io/library/nginx:1.27".to_string(),
+            ..scaffold
+        };
+        assert!(write_service(&env, &foreign).is_err());
+        Ok(())
+    }
+}
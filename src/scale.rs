@@ -0,0 +1,329 @@
+use anyhow::{Context, Result};
+use k8s_openapi::api::autoscaling::v2::HorizontalPodAutoscaler;
+use k8s_openapi::api::policy::v1::PodDisruptionBudget;
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::api::ListParams;
+use kube::{Api, Client};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::cluster;
+use crate::config::{Environment, ServiceSource};
+use crate::mock;
+
+/// Autoscaler bounds and disruption budgets of a workload, which a manual replica count
+/// can conflict with.
+#[derive(Debug, Default)]
+pub struct ScaleGuards {
+    /// HorizontalPodAutoscaler targeting the workload, with its min and max replicas.
+    pub autoscaler: Option<(String, i32, i32)>,
+    /// PodDisruptionBudgets selecting the workload's pods, with their `minAvailable`.
+    pub budgets: Vec<(String, Option<IntOrString>)>,
+}
+
+/// Reads the autoscaler and disruption budgets of the service from its namespace. Demo
+/// mode has none.
+pub async fn guards(env: &Environment, service: &ServiceSource) -> Result<ScaleGuards> {
+    if mock::enabled() {
+        return Ok(ScaleGuards::default());
+    }
+    let client = cluster::client(&env.kubectl_context).await?;
+    let namespace = service.namespace.as_deref().unwrap_or("default");
+
+    let autoscalers: Api<HorizontalPodAutoscaler> = Api::namespaced(client.clone(), namespace);
+    let autoscaler = autoscalers
+        .list(&ListParams::default())
+        .await
+        .context("Failed to list HorizontalPodAutoscalers")?
+        .items
+        .into_iter()
+        .find_map(|hpa| {
+            let spec = hpa.spec?;
+            let target = &spec.scale_target_ref;
+            (target.name == service.name && target.kind.eq_ignore_ascii_case(&service.kind)).then(
+                || {
+                    (
+                        hpa.metadata.name.unwrap_or_default(),
+                        spec.min_replicas.unwrap_or(1),
+                        spec.max_replicas,
+                    )
+                },
+            )
+        });
+
+    let labels = pod_labels(service);
+    let budgets: Api<PodDisruptionBudget> = Api::namespaced(client, namespace);
+    let budgets = budgets
+        .list(&ListParams::default())
+        .await
+        .context("Failed to list PodDisruptionBudgets")?
+        .items
+        .into_iter()
+        .filter_map(|pdb| {
+            let spec = pdb.spec?;
+            let match_labels = spec.selector?.match_labels.filter(|m| !m.is_empty())?;
+            match_labels
+                .iter()
+                .all(|(k, v)| labels.get(k) == Some(v))
+                .then(|| (pdb.metadata.name.unwrap_or_default(), spec.min_available))
+        })
+        .collect();
+
+    Ok(ScaleGuards {
+        autoscaler,
+        budgets,
+    })
+}
+
+/// What the HorizontalPodAutoscaler of a workload currently does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoscalerStatus {
+    pub name: String,
+    pub min_replicas: i32,
+    pub max_replicas: i32,
+    pub current_replicas: i32,
+    pub desired_replicas: i32,
+    /// Each metric as `name current/target`, e.g. `cpu 72%/80%`.
+    pub metrics: Vec<String>,
+}
+
+impl AutoscalerStatus {
+    pub fn from_hpa(hpa: &HorizontalPodAutoscaler) -> Option<Self> {
+        let spec = hpa.spec.as_ref()?;
+        let status = hpa.status.as_ref();
+        let current: Vec<Value> = status
+            .and_then(|s| s.current_metrics.as_ref())
+            .into_iter()
+            .flatten()
+            .filter_map(|m| serde_json::to_value(m).ok())
+            .collect();
+        let metrics = spec
+            .metrics
+            .iter()
+            .flatten()
+            .filter_map(|m| serde_json::to_value(m).ok())
+            .filter_map(|target| {
+                let name = metric_name(&target)?;
+                let current = current
+                    .iter()
+                    .find(|c| metric_name(c).as_ref() == Some(&name))
+                    .and_then(|c| metric_value(c, "current"))
+                    .unwrap_or_else(|| "?".to_string());
+                let target = metric_value(&target, "target").unwrap_or_else(|| "?".to_string());
+                Some(format!("{} {}/{}", name, current, target))
+            })
+            .collect();
+        Some(AutoscalerStatus {
+            name: hpa.metadata.name.clone().unwrap_or_default(),
+            min_replicas: spec.min_replicas.unwrap_or(1),
+            max_replicas: spec.max_replicas,
+            current_replicas: status.and_then(|s| s.current_replicas).unwrap_or(0),
+            desired_replicas: status.map(|s| s.desired_replicas).unwrap_or(0),
+            metrics,
+        })
+    }
+
+    /// Why the replica counts of a rollout with `rollout_replicas` desired replicas also
+    /// reflect autoscaling.
+    pub fn interference(&self, rollout_replicas: i32) -> Option<String> {
+        if self.desired_replicas != self.current_replicas {
+            Some(format!(
+                "the autoscaler is scaling from {} to {} replicas: pod counts mix scaling and rollout",
+                self.current_replicas, self.desired_replicas
+            ))
+        } else if self.current_replicas >= self.max_replicas {
+            Some(format!(
+                "the autoscaler is at maxReplicas ({}): surge pods may keep old pods around longer",
+                self.max_replicas
+            ))
+        } else if rollout_replicas > 0 && rollout_replicas != self.desired_replicas {
+            Some(format!(
+                "the autoscaler wants {} replicas while the rollout targets {}",
+                self.desired_replicas, rollout_replicas
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// The source of an autoscaling metric given as JSON: its `resource`, `pods`, ... field
+/// named after its `type`.
+fn metric_source(metric: &Value) -> Option<&Value> {
+    let kind = metric["type"].as_str()?;
+    let mut chars = kind.chars();
+    let key: String = chars.next()?.to_lowercase().chain(chars).collect();
+    metric.get(key)
+}
+
+/// Name of an autoscaling metric: `cpu`, `http_requests`, ...
+fn metric_name(metric: &Value) -> Option<String> {
+    let source = metric_source(metric)?;
+    source["name"]
+        .as_str()
+        .or_else(|| source["metric"]["name"].as_str())
+        .map(str::to_string)
+}
+
+/// The `current` or `target` value of an autoscaling metric.
+fn metric_value(metric: &Value, field: &str) -> Option<String> {
+    let value = &metric_source(metric)?[field];
+    match &value["averageUtilization"] {
+        Value::Number(n) => Some(format!("{}%", n)),
+        _ => value["averageValue"]
+            .as_str()
+            .or_else(|| value["value"].as_str())
+            .map(str::to_string),
+    }
+}
+
+/// The autoscaler targeting the `kind` workload `name`, if any.
+pub async fn autoscaler_status(
+    client: Client,
+    namespace: &str,
+    kind: &str,
+    name: &str,
+) -> Result<Option<AutoscalerStatus>> {
+    let autoscalers: Api<HorizontalPodAutoscaler> = Api::namespaced(client, namespace);
+    Ok(autoscalers
+        .list(&ListParams::default())
+        .await
+        .context("Failed to list HorizontalPodAutoscalers")?
+        .items
+        .iter()
+        .find(|hpa| {
+            hpa.spec.as_ref().is_some_and(|spec| {
+                spec.scale_target_ref.name == name
+                    && spec.scale_target_ref.kind.eq_ignore_ascii_case(kind)
+            })
+        })
+        .and_then(AutoscalerStatus::from_hpa))
+}
+
+/// Labels of the workload's pods, from its pod template or else its selector.
+fn pod_labels(service: &ServiceSource) -> BTreeMap<String, String> {
+    let template_labels = service.workload_document().and_then(|workload| {
+        let labels = workload
+            .get("spec")?
+            .get("template")?
+            .get("metadata")?
+            .get("labels")?;
+        serde_yaml::from_value::<BTreeMap<String, String>>(labels.clone()).ok()
+    });
+    template_labels.unwrap_or_else(|| {
+        service
+            .selector
+            .iter()
+            .flat_map(|s| s.split(','))
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect()
+    })
+}
+
+impl ScaleGuards {
+    /// Why scaling to `replicas` is risky or will not stick.
+    pub fn warnings(&self, replicas: i32) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some((name, min, max)) = &self.autoscaler {
+            if replicas < *min {
+                warnings.push(format!(
+                    "{} replicas is below the minReplicas ({}) of HorizontalPodAutoscaler {}, which will scale it back up.",
+                    replicas, min, name
+                ));
+            } else if replicas > *max {
+                warnings.push(format!(
+                    "{} replicas is above the maxReplicas ({}) of HorizontalPodAutoscaler {}, which will scale it back down.",
+                    replicas, max, name
+                ));
+            } else {
+                warnings.push(format!(
+                    "HorizontalPodAutoscaler {} manages the replicas ({}-{}) and may change them again.",
+                    name, min, max
+                ));
+            }
+        }
+        for (name, min_available) in &self.budgets {
+            // Percentages scale with the replica count, so only absolute budgets can block.
+            let Some(IntOrString::Int(min_available)) = min_available else {
+                continue;
+            };
+            if replicas < *min_available {
+                warnings.push(format!(
+                    "{} replicas is below the minAvailable ({}) of PodDisruptionBudget {}: the budget can never be met.",
+                    replicas, min_available, name
+                ));
+            } else if replicas == *min_available {
+                warnings.push(format!(
+                    "{} replicas equals the minAvailable of PodDisruptionBudget {}: node drains will be blocked.",
+                    replicas, name
+                ));
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warnings_flag_autoscaler_bounds_and_budgets() {
+        let guards = ScaleGuards {
+            autoscaler: Some(("auth-api".to_string(), 3, 10)),
+            budgets: vec![
+                ("auth-api-pdb".to_string(), Some(IntOrString::Int(2))),
+                (
+                    "auth-api-pct".to_string(),
+                    Some(IntOrString::String("50%".to_string())),
+                ),
+            ],
+        };
+        let low = guards.warnings(1);
+        assert_eq!(low.len(), 2);
+        assert!(low[0].contains("below the minReplicas (3)"));
+        assert!(low[1].contains("can never be met"));
+        assert!(guards.warnings(2)[1].contains("node drains will be blocked"));
+        assert!(guards.warnings(12)[0].contains("above the maxReplicas (10)"));
+        assert_eq!(guards.warnings(5).len(), 1);
+        assert!(ScaleGuards::default().warnings(0).is_empty());
+    }
+
+    #[test]
+    fn test_autoscaler_status_pairs_metrics_and_flags_scaling() {
+        let hpa: HorizontalPodAutoscaler = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "auth-api" },
+            "spec": {
+                "scaleTargetRef": { "kind": "Deployment", "name": "auth-api" },
+                "minReplicas": 2,
+                "maxReplicas": 10,
+                "metrics": [
+                    { "type": "Resource", "resource": { "name": "cpu",
+                        "target": { "type": "Utilization", "averageUtilization": 80 } } },
+                    { "type": "Pods", "pods": { "metric": { "name": "http_requests" },
+                        "target": { "type": "AverageValue", "averageValue": "100" } } }
+                ]
+            },
+            "status": {
+                "currentReplicas": 4,
+                "desiredReplicas": 6,
+                "currentMetrics": [
+                    { "type": "Resource", "resource": { "name": "cpu",
+                        "current": { "averageUtilization": 112 } } }
+                ]
+            }
+        }))
+        .unwrap();
+        let status = AutoscalerStatus::from_hpa(&hpa).unwrap();
+        assert_eq!(status.metrics, ["cpu 112%/80%", "http_requests ?/100"]);
+        assert!(status.interference(4).unwrap().contains("from 4 to 6"));
+
+        let steady = AutoscalerStatus {
+            current_replicas: 6,
+            ..status
+        };
+        assert_eq!(steady.interference(6), None);
+        assert!(steady.interference(3).unwrap().contains("wants 6"));
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+
+/// How far ahead cron expressions are searched for their next run.
+const CRON_HORIZON_DAYS: i64 = 366;
+
+/// Resolves the `--at` value of a scheduled deploy to its start time: an RFC 3339
+/// timestamp (seconds and `Z` optional, e.g. `2024-06-01T02:00Z`), a local
+/// `YYYY-MM-DD HH:MM`, a local `HH:MM` (its next occurrence) or a five-field cron
+/// expression (its next run).
+pub fn parse_at(value: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let value = value.trim();
+    let at = if value.split_whitespace().count() == 5 {
+        next_cron_run(value, now)?
+    } else if let Ok(time) = NaiveTime::parse_from_str(value, "%H:%M") {
+        let today = now.date_naive().and_time(time);
+        let at = local(today)?;
+        if at > now {
+            at
+        } else {
+            local(today + Duration::days(1))?
+        }
+    } else {
+        parse_timestamp(value).with_context(|| {
+            format!(
+                "Invalid --at '{}': expected a timestamp like 2024-06-01T02:00Z, HH:MM or a cron expression",
+                value
+            )
+        })?
+    };
+    if at <= now {
+        return Err(anyhow::anyhow!(
+            "--at {} is in the past",
+            at.format("%Y-%m-%d %H:%M %Z")
+        ));
+    }
+    Ok(at)
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Local>> {
+    let zoned = match value.strip_suffix('Z') {
+        Some(utc) => format!("{}+00:00", utc),
+        None => value.to_string(),
+    };
+    for format in ["%Y-%m-%dT%H:%M:%S%:z", "%Y-%m-%dT%H:%M%:z"] {
+        if let Ok(at) = DateTime::parse_from_str(&zoned, format) {
+            return Some(at.with_timezone(&Local));
+        }
+    }
+    ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|naive| local(naive).ok())
+}
+
+fn local(naive: NaiveDateTime) -> Result<DateTime<Local>> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .with_context(|| format!("{} does not exist in the local time zone", naive))
+}
+
+/// Five-field cron expression: `minute hour day-of-month month day-of-week`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    fields: Vec<Vec<u32>>,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow::anyhow!(
+                "Invalid cron expression '{}': expected 5 fields",
+                expression
+            ));
+        }
+        let ranges = [(0, 59), (0, 23), (1, 31), (1, 12), (0, 7)];
+        let fields = fields
+            .iter()
+            .zip(ranges)
+            .map(|(field, (min, max))| parse_cron_field(field, min, max))
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid cron expression '{}'", expression))?;
+        Ok(Self { fields })
+    }
+
+    /// Whether the expression runs at the minute of `at`.
+    pub fn matches(&self, at: NaiveDateTime) -> bool {
+        let matches = |values: &Vec<u32>, value: u32| values.contains(&value);
+        let weekday = at.weekday().num_days_from_sunday();
+        matches(&self.fields[0], at.minute())
+            && matches(&self.fields[1], at.hour())
+            && matches(&self.fields[2], at.day())
+            && matches(&self.fields[3], at.month())
+            && (matches(&self.fields[4], weekday) || (weekday == 0 && matches(&self.fields[4], 7)))
+    }
+}
+
+/// First minute after `now` matching the cron expression.
+fn next_cron_run(expression: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let cron = Cron::parse(expression)?;
+    let start = now
+        .naive_local()
+        .with_second(0)
+        .and_then(|t| t.with_nanosecond(0));
+    let mut candidate = start.context("Invalid current time")? + Duration::minutes(1);
+    let horizon = candidate + Duration::days(CRON_HORIZON_DAYS);
+    while candidate < horizon {
+        if cron.matches(candidate)
+            && let Ok(at) = local(candidate)
+        {
+            return Ok(at);
+        }
+        candidate += Duration::minutes(1);
+    }
+    Err(anyhow::anyhow!(
+        "Cron expression '{}' has no run within a year",
+        expression
+    ))
+}
+
+/// Values of a cron field: `*`, `*/step`, `a`, `a-b`, `a-b/step` or comma-separated lists.
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>> {
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                None => {
+                    let value = range.parse()?;
+                    (value, value)
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(anyhow::anyhow!(
+                "'{}' is out of range {}-{}",
+                part,
+                min,
+                max
+            ));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_at_accepts_timestamps_times_and_cron() -> Result<()> {
+        let now = Local.with_ymd_and_hms(2026, 10, 15, 10, 0, 0).unwrap();
+
+        let utc = parse_at("2026-10-16T02:00Z", now)?;
+        assert_eq!(
+            utc.with_timezone(&chrono::Utc).to_rfc3339(),
+            "2026-10-16T02:00:00+00:00"
+        );
+        assert_eq!(
+            parse_at("09:30", now)?,
+            Local.with_ymd_and_hms(2026, 10, 16, 9, 30, 0).unwrap()
+        );
+        // Thursday 15th: the next weekday run at 02:30 is Friday.
+        assert_eq!(
+            parse_at("30 2 * * 1-5", now)?,
+            Local.with_ymd_and_hms(2026, 10, 16, 2, 30, 0).unwrap()
+        );
+        assert_eq!(
+            parse_at("0 */6 * * 0", now)?,
+            Local.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap()
+        );
+        assert!(parse_at("2026-10-14T02:00Z", now).is_err());
+        assert!(parse_at("61 2 * * *", now).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,423 @@
+use anyhow::Result;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::api::{ApiResource, DynamicObject, GroupVersionKind};
+use kube::{Api, Client};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::config::ServiceSource;
+
+const SEALED_SECRET_CRD: &str = "sealedsecrets.bitnami.com";
+const CLUSTER_WIDE_ANNOTATION: &str = "sealedsecrets.bitnami.com/cluster-wide";
+const NAMESPACE_WIDE_ANNOTATION: &str = "sealedsecrets.bitnami.com/namespace-wide";
+/// How long the controller gets to process newly applied SealedSecrets.
+const UNSEAL_TIMEOUT: Duration = Duration::from_secs(30);
+const UNSEAL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedSecretManifest {
+    /// Name of the SealedSecret object.
+    pub name: String,
+    /// Name of the Secret it unseals to: its template's, else its own.
+    pub secret_name: String,
+    pub namespace: Option<String>,
+    pub cluster_wide: bool,
+    pub path: PathBuf,
+}
+
+/// Names of the Secrets a workload references through env, envFrom, volumes or imagePullSecrets.
+pub fn referenced_secret_names(workload: &serde_yaml::Value) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    collect_secret_names(workload, &mut names);
+    names
+}
+
+fn collect_secret_names(value: &serde_yaml::Value, names: &mut BTreeSet<String>) {
+    if let Some(map) = value.as_mapping() {
+        for (key, child) in map {
+            let name = match key.as_str() {
+                Some("secretKeyRef") | Some("secretRef") => child.get("name"),
+                Some("secret") => child.get("secretName"),
+                _ => None,
+            };
+            if let Some(name) = name.and_then(|n| n.as_str()) {
+                names.insert(name.to_string());
+            }
+
+            if key.as_str() == Some("imagePullSecrets")
+                && let Some(seq) = child.as_sequence()
+            {
+                names.extend(
+                    seq.iter()
+                        .filter_map(|s| s.get("name").and_then(|n| n.as_str()))
+                        .map(|n| n.to_string()),
+                );
+            }
+
+            collect_secret_names(child, names);
+        }
+    }
+
+    if let Some(seq) = value.as_sequence() {
+        for child in seq {
+            collect_secret_names(child, names);
+        }
+    }
+}
+
+/// Finds SealedSecret manifests in `dir` that produce one of the given Secret names.
+pub fn find_sealed_secrets(dir: &Path, names: &BTreeSet<String>) -> Vec<SealedSecretManifest> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut manifests = Vec::new();
+    for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+        let is_yaml = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e == "yaml" || e == "yml");
+        let Some(content) = is_yaml.then(|| fs::read_to_string(&path).ok()).flatten() else {
+            continue;
+        };
+
+        for document in serde_yaml::Deserializer::from_str(&content) {
+            let Ok(resource) = serde_yaml::Value::deserialize(document) else {
+                continue;
+            };
+            if let Some(manifest) = parse_sealed_secret(&resource, &path)
+                && names.contains(&manifest.secret_name)
+            {
+                manifests.push(manifest);
+            }
+        }
+    }
+
+    manifests.sort_by(|a, b| a.name.cmp(&b.name));
+    manifests
+}
+
+fn parse_sealed_secret(resource: &serde_yaml::Value, path: &Path) -> Option<SealedSecretManifest> {
+    if resource.get("kind")?.as_str()? != "SealedSecret" {
+        return None;
+    }
+    let metadata = resource.get("metadata")?;
+    let name = metadata.get("name")?.as_str()?.to_string();
+    let secret_name = resource
+        .get("spec")
+        .and_then(|s| s.get("template"))
+        .and_then(|t| t.get("metadata"))
+        .and_then(|m| m.get("name"))
+        .and_then(|n| n.as_str())
+        .map_or_else(|| name.clone(), str::to_string);
+    let annotation = |key: &str| {
+        metadata
+            .get("annotations")
+            .and_then(|a| a.get(key))
+            .and_then(|v| v.as_str())
+            == Some("true")
+    };
+
+    Some(SealedSecretManifest {
+        name,
+        secret_name,
+        namespace: metadata
+            .get("namespace")
+            .and_then(|n| n.as_str())
+            .map(|n| n.to_string()),
+        cluster_wide: annotation(CLUSTER_WIDE_ANNOTATION) || annotation(NAMESPACE_WIDE_ANNOTATION),
+        path: path.to_path_buf(),
+    })
+}
+
+/// Reads the workload document of a service and returns the SealedSecrets it depends on.
+pub fn service_sealed_secrets(service: &ServiceSource) -> Vec<SealedSecretManifest> {
+    let Some(dir) = service.yaml_path.parent() else {
+        return Vec::new();
+    };
+    let Some(workload) = service.workload_document() else {
+        return Vec::new();
+    };
+
+    find_sealed_secrets(dir, &referenced_secret_names(&workload))
+}
+
+/// Checks the SealedSecrets a deploy depends on against the target cluster and
+/// returns human-readable warnings. Once they were `applied`, the controller is first
+/// given time to process them.
+pub async fn check(
+    client: &Client,
+    namespace: &str,
+    manifests: &[SealedSecretManifest],
+    applied: bool,
+) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    if manifests.is_empty() {
+        return Ok(warnings);
+    }
+
+    let crds: Api<CustomResourceDefinition> = Api::all(client.clone());
+    if crds.get_opt(SEALED_SECRET_CRD).await?.is_none() {
+        warnings.push(format!(
+            "The workload uses {} SealedSecret(s) but the Sealed Secrets controller is not installed in this cluster (CRD {} missing); the Secrets will never be created.",
+            manifests.len(),
+            SEALED_SECRET_CRD
+        ));
+        return Ok(warnings);
+    }
+
+    let resource = ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "bitnami.com",
+        "v1alpha1",
+        "SealedSecret",
+    ));
+    let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace, &resource);
+    if applied {
+        let pending = wait_until_processed(&api, manifests).await?;
+        if !pending.is_empty() {
+            warnings.push(format!(
+                "The Sealed Secrets controller has not processed {} after {}s: their Secrets may be missing or stale.",
+                pending.join(", "),
+                UNSEAL_TIMEOUT.as_secs()
+            ));
+        }
+    }
+
+    for manifest in manifests {
+        if let Some(sealed_ns) = &manifest.namespace
+            && sealed_ns != namespace
+            && !manifest.cluster_wide
+        {
+            warnings.push(format!(
+                "SealedSecret '{}' ({}) is sealed for namespace '{}' but the workload runs in '{}'.",
+                manifest.name,
+                manifest.path.display(),
+                sealed_ns,
+                namespace
+            ));
+        }
+
+        if let Some(failure) = unseal_failure(&api, &manifest.name).await? {
+            let hint = if failure.contains("no key could decrypt") {
+                " It was most likely sealed with another cluster's key."
+            } else {
+                ""
+            };
+            warnings.push(format!(
+                "SealedSecret '{}' cannot be unsealed by the controller: {}.{}",
+                manifest.name, failure, hint
+            ));
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Polls the applied SealedSecrets until the controller has processed them all, returning
+/// the names of those still pending at the timeout.
+async fn wait_until_processed(
+    api: &Api<DynamicObject>,
+    manifests: &[SealedSecretManifest],
+) -> Result<Vec<String>> {
+    let deadline = Instant::now() + UNSEAL_TIMEOUT;
+    loop {
+        let mut pending = Vec::new();
+        for manifest in manifests {
+            let object = api.get_opt(&manifest.name).await?;
+            if !object.as_ref().is_some_and(processed) {
+                pending.push(manifest.name.clone());
+            }
+        }
+        if pending.is_empty() || Instant::now() >= deadline {
+            return Ok(pending);
+        }
+        tokio::time::sleep(UNSEAL_POLL_INTERVAL).await;
+    }
+}
+
+/// Whether the controller reported on the current generation of a SealedSecret.
+/// Controllers that do not track `observedGeneration` only set the `Synced` condition.
+fn processed(object: &DynamicObject) -> bool {
+    let Some(status) = object.data.get("status") else {
+        return false;
+    };
+    let synced = status
+        .get("conditions")
+        .and_then(|c| c.as_array())
+        .is_some_and(|conditions| {
+            conditions
+                .iter()
+                .any(|c| c.get("type").and_then(|t| t.as_str()) == Some("Synced"))
+        });
+    let observed = status.get("observedGeneration").and_then(|g| g.as_i64());
+    synced
+        && match (observed, object.metadata.generation) {
+            (Some(observed), Some(generation)) => observed >= generation,
+            _ => true,
+        }
+}
+
+/// Returns the controller's unseal error for an applied SealedSecret, if any.
+async fn unseal_failure(api: &Api<DynamicObject>, name: &str) -> Result<Option<String>> {
+    let Some(object) = api.get_opt(name).await? else {
+        return Ok(None);
+    };
+
+    let failure = object
+        .data
+        .get("status")
+        .and_then(|s| s.get("conditions"))
+        .and_then(|c| c.as_array())
+        .and_then(|conditions| {
+            conditions.iter().find(|c| {
+                c.get("type").and_then(|t| t.as_str()) == Some("Synced")
+                    && c.get("status").and_then(|s| s.as_str()) == Some("False")
+            })
+        })
+        .map(|c| {
+            c.get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error")
+                .to_string()
+        });
+
+    Ok(failure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_referenced_secret_names_covers_env_volumes_and_pull_secrets() {
+        let workload: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+kind: Deployment
+spec:
+  template:
+    spec:
+      imagePullSecrets:
+      - name: registry-creds
+      containers:
+      - name: main
+        env:
+        - name: TOKEN
+          valueFrom:
+            secretKeyRef:
+              name: api-token
+              key: token
+        envFrom:
+        - secretRef:
+            name: app-env
+      volumes:
+      - name: certs
+        secret:
+          secretName: tls-certs
+"#,
+        )
+        .unwrap();
+
+        let names: Vec<String> = referenced_secret_names(&workload).into_iter().collect();
+        assert_eq!(
+            names,
+            vec!["api-token", "app-env", "registry-creds", "tls-certs"]
+        );
+    }
+
+    #[test]
+    fn test_find_sealed_secrets_matches_referenced_names() -> Result<()> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("secrets.yaml"),
+            r#"
+apiVersion: bitnami.com/v1alpha1
+kind: SealedSecret
+metadata:
+  name: api-token
+  namespace: payments
+spec:
+  encryptedData:
+    token: AgBy...
+---
+apiVersion: bitnami.com/v1alpha1
+kind: SealedSecret
+metadata:
+  name: unrelated
+spec:
+  encryptedData:
+    key: AgBy...
+"#,
+        )?;
+
+        let names = BTreeSet::from(["api-token".to_string()]);
+        let found = find_sealed_secrets(dir.path(), &names);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "api-token");
+        assert_eq!(found[0].namespace.as_deref(), Some("payments"));
+        assert!(!found[0].cluster_wide);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_sealed_secrets_matches_the_template_name_and_keeps_the_object_name() -> Result<()>
+    {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("secrets.yaml"),
+            r#"
+apiVersion: bitnami.com/v1alpha1
+kind: SealedSecret
+metadata:
+  name: api-token-sealed
+spec:
+  template:
+    metadata:
+      name: api-token
+  encryptedData:
+    token: AgBy...
+"#,
+        )?;
+
+        let names = BTreeSet::from(["api-token".to_string()]);
+        let found = find_sealed_secrets(dir.path(), &names);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "api-token-sealed");
+        assert_eq!(found[0].secret_name, "api-token");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_processed_waits_for_the_current_generation() {
+        let object = |generation: i64, status: serde_json::Value| {
+            let mut object: DynamicObject = serde_json::from_value(serde_json::json!({
+                "apiVersion": "bitnami.com/v1alpha1",
+                "kind": "SealedSecret",
+                "metadata": { "name": "api-token", "generation": generation },
+            }))
+            .unwrap();
+            object.data = serde_json::json!({ "status": status });
+            object
+        };
+        let synced = serde_json::json!([{ "type": "Synced", "status": "True" }]);
+
+        assert!(!processed(&object(2, serde_json::json!({}))));
+        assert!(!processed(&object(
+            2,
+            serde_json::json!({ "observedGeneration": 1, "conditions": synced })
+        )));
+        assert!(processed(&object(
+            2,
+            serde_json::json!({ "observedGeneration": 2, "conditions": synced })
+        )));
+        assert!(processed(&object(
+            2,
+            serde_json::json!({ "conditions": synced })
+        )));
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use std::env;
+use std::fs;
+
+const VAULT_PREFIX: &str = "vault:";
+
+/// A credential value from the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    Literal(String),
+    /// `vault:<path>#<key>`, e.g. `vault:kv/data/davit#github_token`.
+    Vault {
+        path: String,
+        key: String,
+    },
+}
+
+impl SecretRef {
+    pub fn parse(value: &str) -> Result<Self> {
+        let Some(reference) = value.strip_prefix(VAULT_PREFIX) else {
+            return Ok(Self::Literal(value.to_string()));
+        };
+
+        let (path, key) = reference
+            .rsplit_once('#')
+            .filter(|(path, key)| !path.is_empty() && !key.is_empty())
+            .with_context(|| {
+                format!(
+                    "Invalid Vault reference '{}': expected vault:<path>#<key>",
+                    value
+                )
+            })?;
+
+        Ok(Self::Vault {
+            path: path.trim_matches('/').to_string(),
+            key: key.to_string(),
+        })
+    }
+}
+
+/// Resolves a credential value, fetching `vault:` references from Vault.
+pub async fn resolve(value: &str) -> Result<String> {
+    match SecretRef::parse(value)? {
+        SecretRef::Literal(value) => Ok(value),
+        SecretRef::Vault { path, key } => read_vault_field(&path, &key).await,
+    }
+}
+
+/// Resolves an optional credential value, leaving `None` untouched.
+pub async fn resolve_optional(value: Option<&str>) -> Result<Option<String>> {
+    match value {
+        Some(value) => resolve(value).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Reads a single field through the Vault HTTP API.
+///
+/// `VAULT_AGENT_ADDR` takes precedence so a local Vault Agent can inject its own token;
+/// otherwise `VAULT_ADDR` is used with `VAULT_TOKEN` or the token cached by `vault login`.
+async fn read_vault_field(path: &str, key: &str) -> Result<String> {
+    let agent_addr = env::var("VAULT_AGENT_ADDR").ok();
+    let addr = agent_addr
+        .clone()
+        .or_else(|| env::var("VAULT_ADDR").ok())
+        .context(
+            "Vault reference found in config but neither VAULT_AGENT_ADDR nor VAULT_ADDR is set",
+        )?;
+
+    let mut request =
+        reqwest::Client::new().get(format!("{}/v1/{}", addr.trim_end_matches('/'), path));
+    if let Some(token) = vault_token() {
+        request = request.header("X-Vault-Token", token);
+    } else if agent_addr.is_none() {
+        return Err(anyhow::anyhow!(
+            "No Vault token found: set VAULT_TOKEN or run `vault login`"
+        ));
+    }
+    if let Ok(namespace) = env::var("VAULT_NAMESPACE") {
+        request = request.header("X-Vault-Namespace", namespace);
+    }
+
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach Vault at {}", addr))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow::anyhow!(
+            "Vault returned {} while reading '{}'",
+            status,
+            path
+        ));
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .context("Failed to parse Vault response")?;
+
+    extract_field(&body, key)
+        .with_context(|| format!("Key '{}' not found in Vault secret '{}'", key, path))
+}
+
+fn vault_token() -> Option<String> {
+    if let Ok(token) = env::var("VAULT_TOKEN") {
+        return Some(token);
+    }
+
+    let base_dirs = BaseDirs::new()?;
+    fs::read_to_string(base_dirs.home_dir().join(".vault-token"))
+        .ok()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Extracts a field from a KV v2 (`data.data`) or KV v1 (`data`) response.
+fn extract_field(body: &serde_json::Value, key: &str) -> Option<String> {
+    let data = body.get("data")?;
+    let secret = data
+        .get("data")
+        .filter(|d| d.is_object() && data.get("metadata").is_some())
+        .unwrap_or(data);
+
+    match secret.get(key)? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_secret_ref_parses_vault_references() -> Result<()> {
+        assert_eq!(
+            SecretRef::parse("vault:kv/data/davit#github_token")?,
+            SecretRef::Vault {
+                path: "kv/data/davit".to_string(),
+                key: "github_token".to_string(),
+            }
+        );
+        assert_eq!(
+            SecretRef::parse("plain-token")?,
+            SecretRef::Literal("plain-token".to_string())
+        );
+        assert!(SecretRef::parse("vault:kv/data/davit").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_field_supports_kv_v1_and_v2() {
+        let v2 = json!({"data": {"data": {"github_token": "abc"}, "metadata": {"version": 3}}});
+        let v1 = json!({"data": {"github_token": "def"}});
+
+        assert_eq!(extract_field(&v2, "github_token").as_deref(), Some("abc"));
+        assert_eq!(extract_field(&v1, "github_token").as_deref(), Some("def"));
+        assert_eq!(extract_field(&v1, "missing"), None);
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+static SETTINGS: OnceLock<CommitSigning> = OnceLock::new();
+
+/// Signing of the commits davit makes, for GitOps repositories that require signed
+/// commits. Unset fields follow the git configuration of the repository.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommitSigning {
+    /// Sign commits; the repository's `commit.gpgsign` by default.
+    pub sign: Option<bool>,
+    /// The repository's `gpg.format` by default.
+    pub format: Option<SigningFormat>,
+    /// OpenPGP key ID, or SSH key file (or `key::` public key literal held by the SSH
+    /// agent); `user.signingkey` by default, else the committer email for OpenPGP.
+    pub key: Option<String>,
+    /// Signing program; `gpg.program` or `gpg.ssh.program` by default, else `gpg` or
+    /// `ssh-keygen`.
+    pub program: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningFormat {
+    Openpgp,
+    Ssh,
+}
+
+/// Sets the signing settings read by [`signer`]; until then only the git configuration
+/// of each repository applies.
+pub fn configure(settings: CommitSigning) {
+    let _ = SETTINGS.set(settings);
+}
+
+/// Signs commit buffers as `git commit -S` does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signer {
+    pub format: SigningFormat,
+    pub key: String,
+    pub program: String,
+}
+
+/// The signer of the commits made in `repo`, or `None` when they are not signed.
+pub fn signer(repo: &Repository) -> Result<Option<Signer>> {
+    let config = repo
+        .config()
+        .and_then(|mut c| c.snapshot())
+        .context("Failed to read the git configuration")?;
+    resolve(&SETTINGS.get().cloned().unwrap_or_default(), &config)
+}
+
+fn resolve(settings: &CommitSigning, config: &git2::Config) -> Result<Option<Signer>> {
+    let sign = settings
+        .sign
+        .unwrap_or_else(|| config.get_bool("commit.gpgsign").unwrap_or(false));
+    if !sign {
+        return Ok(None);
+    }
+    let format = match settings.format {
+        Some(format) => format,
+        None => match config.get_string("gpg.format").ok().as_deref() {
+            None | Some("openpgp") => SigningFormat::Openpgp,
+            Some("ssh") => SigningFormat::Ssh,
+            Some(other) => {
+                return Err(anyhow::anyhow!(
+                    "gpg.format {} is not supported: davit signs commits with openpgp or ssh",
+                    other
+                ));
+            }
+        },
+    };
+    let (program_key, default_program) = match format {
+        SigningFormat::Openpgp => ("gpg.program", "gpg"),
+        SigningFormat::Ssh => ("gpg.ssh.program", "ssh-keygen"),
+    };
+    let program = settings
+        .program
+        .clone()
+        .or_else(|| config.get_string(program_key).ok())
+        .unwrap_or_else(|| default_program.to_string());
+    let key = settings
+        .key
+        .clone()
+        .or_else(|| config.get_string("user.signingkey").ok())
+        .filter(|k| !k.trim().is_empty());
+    let key = match (format, key) {
+        (_, Some(key)) => key,
+        (SigningFormat::Openpgp, None) => config.get_string("user.email").map_err(|_| {
+            anyhow::anyhow!(
+                "Commits must be signed but no OpenPGP key is set: set signing.key or git's user.signingkey"
+            )
+        })?,
+        (SigningFormat::Ssh, None) => {
+            return Err(anyhow::anyhow!(
+                "Commits must be signed but no SSH key is set: set signing.key or git's user.signingkey"
+            ));
+        }
+    };
+    Ok(Some(Signer {
+        format,
+        key,
+        program,
+    }))
+}
+
+impl Signer {
+    /// The armored signature of `payload`, stored in the `gpgsig` header of the commit.
+    pub fn sign(&self, payload: &str) -> Result<String> {
+        match self.format {
+            SigningFormat::Openpgp => {
+                let (signature, status) =
+                    self.run(&["--status-fd=2", "-bsau", &self.key], payload)?;
+                // As git, trust the status line rather than the exit code alone.
+                if !status
+                    .lines()
+                    .any(|line| line.starts_with("[GNUPG:] SIG_CREATED "))
+                {
+                    return Err(anyhow::anyhow!(
+                        "{} did not sign with key {}: {}",
+                        self.program,
+                        self.key,
+                        status.trim()
+                    ));
+                }
+                Ok(signature)
+            }
+            SigningFormat::Ssh => {
+                let literal = self
+                    .key
+                    .strip_prefix("key::")
+                    .or_else(|| self.key.starts_with("ssh-").then_some(self.key.as_str()));
+                match literal {
+                    // The private key of a literal public key is looked up in the SSH agent.
+                    Some(public_key) => {
+                        let file = std::env::temp_dir()
+                            .join(format!("davit-signing-{}.pub", std::process::id()));
+                        fs::write(&file, format!("{}\n", public_key.trim()))
+                            .with_context(|| format!("Failed to write {}", file.display()))?;
+                        let file_arg = file.to_string_lossy().into_owned();
+                        let result =
+                            self.run(&["-Y", "sign", "-n", "git", "-f", &file_arg, "-U"], payload);
+                        let _ = fs::remove_file(&file);
+                        Ok(result?.0)
+                    }
+                    None => {
+                        let file = expand_home(&self.key);
+                        let file_arg = file.to_string_lossy().into_owned();
+                        Ok(self
+                            .run(&["-Y", "sign", "-n", "git", "-f", &file_arg], payload)?
+                            .0)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs the signing program with the payload as input; returns its output and stderr.
+    fn run(&self, args: &[&str], payload: &str) -> Result<(String, String)> {
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {} to sign the commit", self.program))?;
+        child
+            .stdin
+            .take()
+            .context("Failed to open the input of the signing program")?
+            .write_all(payload.as_bytes())
+            .with_context(|| format!("Failed to send the commit to {}", self.program))?;
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("Failed to run {} to sign the commit", self.program))?;
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        if !output.status.success() || output.stdout.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{} failed to sign with key {}: {}",
+                self.program,
+                self.key,
+                stderr.trim()
+            ));
+        }
+        Ok((String::from_utf8_lossy(&output.stdout).into_owned(), stderr))
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (
+        path.strip_prefix("~/"),
+        directories::BaseDirs::new().map(|d| d.home_dir().to_path_buf()),
+    ) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_follows_settings_then_git_config() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let mut config = repo.config()?;
+        config.set_str("user.email", "davit@example.com")?;
+        let read = |settings: &CommitSigning| resolve(settings, &repo.config()?.snapshot()?);
+
+        assert_eq!(read(&CommitSigning::default())?, None);
+        config.set_bool("commit.gpgsign", true)?;
+        assert_eq!(
+            read(&CommitSigning::default())?,
+            Some(Signer {
+                format: SigningFormat::Openpgp,
+                key: "davit@example.com".to_string(),
+                program: "gpg".to_string(),
+            })
+        );
+        assert_eq!(
+            read(&CommitSigning {
+                sign: Some(false),
+                ..Default::default()
+            })?,
+            None
+        );
+
+        config.set_str("gpg.format", "ssh")?;
+        assert!(read(&CommitSigning::default()).is_err());
+        let signer = read(&CommitSigning {
+            key: Some("~/.ssh/id_ed25519.pub".to_string()),
+            ..Default::default()
+        })?
+        .unwrap();
+        assert_eq!(signer.format, SigningFormat::Ssh);
+        assert_eq!(signer.program, "ssh-keygen");
+
+        config.set_str("gpg.format", "x509")?;
+        assert!(read(&CommitSigning::default()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::history::{self, DeployRecord, Outcome};
+
+/// Deploy counts of one service over a period.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ServiceStats {
+    pub env: String,
+    pub service: String,
+    pub deploys: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub rollbacks: usize,
+    /// Median duration of the successful deploys.
+    pub median_duration_secs: Option<u64>,
+}
+
+/// Summarizes the deploys since `cutoff`, per environment and service. Earlier records
+/// are still used to recognize rollbacks.
+pub fn service_stats(
+    records: &[DeployRecord],
+    env: Option<&str>,
+    cutoff: Option<DateTime<Utc>>,
+) -> Vec<ServiceStats> {
+    let mut stats: BTreeMap<(String, String), (ServiceStats, Vec<u64>)> = BTreeMap::new();
+    for (index, record) in records.iter().enumerate() {
+        if env.is_some_and(|env| record.env != env)
+            || cutoff.is_some_and(|cutoff| record.timestamp < cutoff)
+        {
+            continue;
+        }
+        let (entry, durations) = stats
+            .entry((record.env.clone(), record.service.clone()))
+            .or_insert_with(|| {
+                (
+                    ServiceStats {
+                        env: record.env.clone(),
+                        service: record.service.clone(),
+                        ..Default::default()
+                    },
+                    Vec::new(),
+                )
+            });
+        entry.deploys += 1;
+        match record.outcome {
+            Outcome::Succeeded => {
+                entry.succeeded += 1;
+                durations.push(record.duration_secs);
+            }
+            Outcome::Failed => entry.failed += 1,
+        }
+        if history::is_rollback(&records[..index], record) {
+            entry.rollbacks += 1;
+        }
+    }
+
+    stats
+        .into_values()
+        .map(|(mut entry, mut durations)| {
+            entry.median_duration_secs = median(&mut durations);
+            entry
+        })
+        .collect()
+}
+
+/// DORA metrics of one environment over a period.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DoraMetrics {
+    pub env: String,
+    pub deploys: usize,
+    pub deploys_per_week: f64,
+    /// Share of deploys that failed or were rolled back.
+    pub change_failure_rate: Option<f64>,
+    /// Median time from a failed or rolled-back deploy to the end of the deploy restoring service.
+    pub median_time_to_restore_secs: Option<u64>,
+    pub restores: usize,
+}
+
+/// Computes deployment frequency, change failure rate and time to restore per environment
+/// for the deploys in `[start, end]`.
+pub fn dora_metrics(
+    records: &[DeployRecord],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<DoraMetrics> {
+    struct Acc {
+        deploys: usize,
+        failed_changes: usize,
+        restore_times: Vec<u64>,
+    }
+    let finished = |r: &DeployRecord| r.timestamp + Duration::seconds(r.duration_secs as i64);
+    let in_period = |r: &DeployRecord| r.timestamp >= start && r.timestamp <= end;
+
+    let mut envs: BTreeMap<String, Acc> = BTreeMap::new();
+    for (index, record) in records.iter().enumerate() {
+        if !in_period(record) {
+            continue;
+        }
+        let acc = envs.entry(record.env.clone()).or_insert(Acc {
+            deploys: 0,
+            failed_changes: 0,
+            restore_times: Vec::new(),
+        });
+        acc.deploys += 1;
+        let same_service = |r: &&DeployRecord| r.env == record.env && r.service == record.service;
+
+        match record.outcome {
+            Outcome::Failed => {
+                acc.failed_changes += 1;
+                let restored = records[index + 1..]
+                    .iter()
+                    .filter(same_service)
+                    .find(|r| r.outcome == Outcome::Succeeded);
+                if let Some(restored) = restored {
+                    acc.restore_times
+                        .push((finished(restored) - record.timestamp).num_seconds().max(0) as u64);
+                }
+            }
+            Outcome::Succeeded => {
+                // Deploys that get rolled back count as failed changes.
+                if history::was_rolled_back(records, index) {
+                    acc.failed_changes += 1;
+                }
+                let broken = match record.rolls_back {
+                    Some(original) => records[..index]
+                        .iter()
+                        .filter(same_service)
+                        .find(|r| r.timestamp == original),
+                    None if history::is_rollback(&records[..index], record) => records[..index]
+                        .iter()
+                        .rev()
+                        .filter(same_service)
+                        .find(|r| r.outcome == Outcome::Succeeded),
+                    None => None,
+                };
+                if let Some(broken) = broken {
+                    acc.restore_times
+                        .push((finished(record) - broken.timestamp).num_seconds().max(0) as u64);
+                }
+            }
+        }
+    }
+
+    let weeks = ((end - start).num_seconds() as f64 / (7.0 * 86_400.0)).max(1.0 / 7.0);
+    envs.into_iter()
+        .map(|(env, mut acc)| DoraMetrics {
+            env,
+            deploys: acc.deploys,
+            deploys_per_week: acc.deploys as f64 / weeks,
+            change_failure_rate: (acc.deploys > 0)
+                .then(|| acc.failed_changes as f64 / acc.deploys as f64),
+            restores: acc.restore_times.len(),
+            median_time_to_restore_secs: median(&mut acc.restore_times),
+        })
+        .collect()
+}
+
+pub fn dora_to_markdown(
+    metrics: &[DoraMetrics],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> String {
+    let mut md = format!(
+        "## DORA metrics ({} to {})\n\n",
+        start.format("%Y-%m-%d"),
+        end.format("%Y-%m-%d")
+    );
+    md.push_str(
+        "| Environment | Deploys | Deploys / week | Change failure rate | Median time to restore |\n",
+    );
+    md.push_str("| --- | ---: | ---: | ---: | ---: |\n");
+    for m in metrics {
+        md.push_str(&format!(
+            "| {} | {} | {:.1} | {} | {} |\n",
+            m.env,
+            m.deploys,
+            m.deploys_per_week,
+            m.change_failure_rate
+                .map(|r| format!("{:.1}%", r * 100.0))
+                .unwrap_or_else(|| "-".to_string()),
+            m.median_time_to_restore_secs
+                .map(format_duration)
+                .unwrap_or_else(|| "-".to_string()),
+        ));
+    }
+    md
+}
+
+fn median(values: &mut [u64]) -> Option<u64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2
+    } else {
+        values[mid]
+    })
+}
+
+pub fn to_json(stats: &[ServiceStats]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(stats)?)
+}
+
+pub fn print_table(stats: &[ServiceStats]) {
+    if stats.is_empty() {
+        println!("No deploys recorded in this period.");
+        return;
+    }
+    println!(
+        "{:<15} {:<25} {:>7} {:>9} {:>6} {:>9} {:>8}",
+        "ENV", "SERVICE", "DEPLOYS", "SUCCEEDED", "FAILED", "ROLLBACKS", "MEDIAN"
+    );
+    for s in stats {
+        println!(
+            "{:<15} {:<25} {:>7} {:>9} {:>6} {:>9} {:>8}",
+            s.env,
+            s.service,
+            s.deploys,
+            s.succeeded,
+            s.failed,
+            s.rollbacks,
+            s.median_duration_secs
+                .map(format_duration)
+                .unwrap_or_else(|| "-".to_string())
+        );
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        day: u32,
+        service: &str,
+        old: &str,
+        new: &str,
+        outcome: Outcome,
+        secs: u64,
+    ) -> DeployRecord {
+        DeployRecord {
+            timestamp: format!("2026-10-{:02}T09:00:00Z", day).parse().unwrap(),
+            env: "production".to_string(),
+            service: service.to_string(),
+            old_tag: Some(old.to_string()),
+            new_tag: new.to_string(),
+            digest: None,
+            user: "dev@acme.io".to_string(),
+            duration_secs: secs,
+            outcome,
+            kind: history::DeployKind::Deploy,
+            rolls_back: None,
+            phases: Default::default(),
+            approvals: Vec::new(),
+            freeze_override: None,
+        }
+    }
+
+    #[test]
+    fn test_service_stats_counts_outcomes_and_rollbacks() {
+        let records = vec![
+            record(1, "auth-api", "v1", "v2", Outcome::Succeeded, 100),
+            record(3, "auth-api", "v2", "v3", Outcome::Succeeded, 60),
+            record(4, "auth-api", "v3", "v2", Outcome::Succeeded, 40),
+            record(5, "auth-api", "v2", "v4", Outcome::Failed, 300),
+            record(5, "billing", "v7", "v8", Outcome::Succeeded, 90),
+        ];
+        let cutoff = "2026-10-02T00:00:00Z".parse().ok();
+
+        let stats = service_stats(&records, Some("production"), cutoff);
+        assert_eq!(
+            stats[0],
+            ServiceStats {
+                env: "production".to_string(),
+                service: "auth-api".to_string(),
+                deploys: 3,
+                succeeded: 2,
+                failed: 1,
+                rollbacks: 1,
+                median_duration_secs: Some(50),
+            }
+        );
+        assert_eq!(stats[1].service, "billing");
+        assert!(service_stats(&records, Some("staging"), None).is_empty());
+    }
+
+    #[test]
+    fn test_dora_metrics_counts_failed_changes_and_restores() {
+        let records = vec![
+            record(1, "auth-api", "v1", "v2", Outcome::Succeeded, 100),
+            // Rolled back a day later: a failed change restored after 1 day and 60s.
+            record(8, "auth-api", "v2", "v3", Outcome::Succeeded, 60),
+            record(9, "auth-api", "v3", "v2", Outcome::Succeeded, 60),
+            // Failed, fixed by the next deploy 2 days later.
+            record(10, "auth-api", "v2", "v4", Outcome::Failed, 300),
+            record(12, "auth-api", "v2", "v5", Outcome::Succeeded, 120),
+        ];
+        let start = "2026-10-01T00:00:00Z".parse().unwrap();
+        let end = "2026-10-15T00:00:00Z".parse().unwrap();
+
+        let metrics = dora_metrics(&records, start, end);
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].deploys, 5);
+        assert_eq!(metrics[0].deploys_per_week, 2.5);
+        assert_eq!(metrics[0].change_failure_rate, Some(0.4));
+        assert_eq!(metrics[0].restores, 2);
+        assert_eq!(metrics[0].median_time_to_restore_secs, Some(129_690));
+        assert!(
+            dora_to_markdown(&metrics, start, end).contains("| production | 5 | 2.5 | 40.0% |")
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::blueprint::split_image_ref;
+use crate::cluster;
+use crate::config::Environment;
+use crate::info;
+use crate::mock;
+use crate::offline;
+use crate::registry::ImageMetadata;
+
+/// The version of a service in an environment, as committed and, when requested, as
+/// running in the cluster.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServiceStatus {
+    pub env: String,
+    pub service: String,
+    pub tag: Option<String>,
+    /// How long ago the tag was pushed, when the registry lists it.
+    pub age: Option<String>,
+    /// Tag running in the cluster; `None` when not queried or unreadable.
+    pub live_tag: Option<String>,
+}
+
+impl ServiceStatus {
+    /// Whether the cluster was read and runs another tag than the manifest's.
+    pub fn drifted(&self) -> bool {
+        self.live_tag.is_some() && self.live_tag != self.tag
+    }
+}
+
+/// Status of every service of the environment. Registry or cluster failures leave the
+/// age or live tag empty instead of failing the listing.
+pub async fn collect(env: &Environment, live: bool) -> Result<Vec<ServiceStatus>> {
+    let services = env
+        .list_services()
+        .with_context(|| format!("Failed to list services of {}", env.name))?;
+    let client = if live {
+        cluster::client(&env.kubectl_context).await.ok()
+    } else {
+        None
+    };
+    let mut listings: HashMap<String, Vec<ImageMetadata>> = HashMap::new();
+
+    let mut statuses = Vec::new();
+    for service in services {
+        let (image, tag) = split_image_ref(&service.image_path);
+        if !listings.contains_key(image) {
+            let images = if mock::enabled() {
+                mock::images()
+            } else {
+                offline::list_images(env, image).await.unwrap_or_default()
+            };
+            listings.insert(image.to_string(), images);
+        }
+        let age = tag.and_then(|tag| {
+            listings[image]
+                .iter()
+                .find(|i| i.tags.iter().any(|t| t == tag))
+                .map(ImageMetadata::age_string)
+        });
+        let live_tag = match &client {
+            Some(client) => info::running_tag(client, &service).await,
+            None => None,
+        };
+        statuses.push(ServiceStatus {
+            env: env.name.clone(),
+            service: service.name.clone(),
+            tag: tag.map(str::to_string),
+            age,
+            live_tag,
+        });
+    }
+    Ok(statuses)
+}
+
+pub fn print_table(statuses: &[ServiceStatus], live: bool) {
+    if statuses.is_empty() {
+        println!("No services found.");
+        return;
+    }
+    let tag_width = statuses
+        .iter()
+        .filter_map(|s| s.tag.as_ref().map(String::len))
+        .max()
+        .unwrap_or_default()
+        .max(3);
+    print!(
+        "{:<15} {:<25} {:<tag_width$} {:>10}",
+        "ENV", "SERVICE", "TAG", "AGE"
+    );
+    println!("{}", if live { "  LIVE" } else { "" });
+    for status in statuses {
+        print!(
+            "{:<15} {:<25} {:<tag_width$} {:>10}",
+            status.env,
+            status.service,
+            status.tag.as_deref().unwrap_or("-"),
+            status.age.as_deref().unwrap_or("-")
+        );
+        if !live {
+            println!();
+        } else if status.drifted() {
+            println!("  ⚠️  {}", status.live_tag.as_deref().unwrap_or_default());
+        } else {
+            println!("  {}", status.live_tag.as_deref().unwrap_or("-"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drift_needs_a_differing_live_tag() {
+        let mut status = ServiceStatus {
+            env: "production".to_string(),
+            service: "auth-api".to_string(),
+            tag: Some("v1.2.3".to_string()),
+            age: None,
+            live_tag: None,
+        };
+        assert!(!status.drifted());
+        status.live_tag = Some("v1.2.3".to_string());
+        assert!(!status.drifted());
+        status.live_tag = Some("v1.2.2".to_string());
+        assert!(status.drifted());
+    }
+}
@@ -0,0 +1,178 @@
+use console::Style as TermStyle;
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    /// No colors, as with `NO_COLOR`: emphasis only through bold and reversed text.
+    Monochrome,
+}
+
+/// Colors of the dashboard and of diffs, by role.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub success: Style,
+    pub warning: Style,
+    pub error: Style,
+    pub info: Style,
+    /// Old pods, placeholders and other secondary text.
+    pub muted: Style,
+    /// Search matches in the logs.
+    pub highlight: Style,
+    /// Colors telling streamed containers apart.
+    pub containers: [Color; 6],
+    pub diff_added: TermStyle,
+    pub diff_removed: TermStyle,
+    pub diff_hunk: TermStyle,
+    /// Unchanged lines and file headers of diffs.
+    pub diff_context: TermStyle,
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        let fg = |color| Style::default().fg(color);
+        match name {
+            ThemeName::Dark => Self {
+                success: fg(Color::Green),
+                warning: fg(Color::Yellow),
+                error: fg(Color::Red),
+                info: fg(Color::Cyan),
+                muted: fg(Color::DarkGray),
+                highlight: Style::default().fg(Color::Black).bg(Color::Yellow),
+                containers: [
+                    Color::Cyan,
+                    Color::Magenta,
+                    Color::LightBlue,
+                    Color::LightYellow,
+                    Color::LightGreen,
+                    Color::LightRed,
+                ],
+                diff_added: TermStyle::new().green(),
+                diff_removed: TermStyle::new().red(),
+                diff_hunk: TermStyle::new().cyan(),
+                diff_context: TermStyle::new().dim(),
+            },
+            // Yellow and light colors fade into a light background.
+            ThemeName::Light => Self {
+                success: fg(Color::Green),
+                warning: fg(Color::Magenta),
+                error: fg(Color::Red),
+                info: fg(Color::Blue),
+                muted: fg(Color::Indexed(243)),
+                highlight: Style::default().fg(Color::White).bg(Color::Blue),
+                containers: [
+                    Color::Blue,
+                    Color::Magenta,
+                    Color::Cyan,
+                    Color::Green,
+                    Color::Red,
+                    Color::Indexed(130),
+                ],
+                diff_added: TermStyle::new().green(),
+                diff_removed: TermStyle::new().red(),
+                diff_hunk: TermStyle::new().blue(),
+                diff_context: TermStyle::new(),
+            },
+            ThemeName::HighContrast => Self {
+                success: fg(Color::LightGreen).add_modifier(Modifier::BOLD),
+                warning: fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                error: fg(Color::LightRed).add_modifier(Modifier::BOLD),
+                info: fg(Color::LightCyan),
+                muted: fg(Color::White),
+                highlight: Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::LightYellow)
+                    .add_modifier(Modifier::BOLD),
+                containers: [
+                    Color::LightCyan,
+                    Color::LightMagenta,
+                    Color::LightBlue,
+                    Color::LightYellow,
+                    Color::LightGreen,
+                    Color::LightRed,
+                ],
+                diff_added: TermStyle::new().green().bright().bold(),
+                diff_removed: TermStyle::new().red().bright().bold(),
+                diff_hunk: TermStyle::new().cyan().bright(),
+                diff_context: TermStyle::new(),
+            },
+            ThemeName::Monochrome => Self {
+                success: Style::default(),
+                warning: Style::default(),
+                error: Style::default().add_modifier(Modifier::BOLD),
+                info: Style::default(),
+                muted: Style::default(),
+                highlight: Style::default().add_modifier(Modifier::REVERSED),
+                containers: [Color::Reset; 6],
+                diff_added: TermStyle::new(),
+                diff_removed: TermStyle::new(),
+                diff_hunk: TermStyle::new(),
+                diff_context: TermStyle::new(),
+            },
+        }
+    }
+}
+
+/// The theme asked with `--theme`, else monochrome when `NO_COLOR` is set, else the
+/// configured one.
+pub fn resolve(flag: Option<ThemeName>, configured: ThemeName) -> ThemeName {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    pick(flag, no_color, configured)
+}
+
+fn pick(flag: Option<ThemeName>, no_color: bool, configured: ThemeName) -> ThemeName {
+    match flag {
+        Some(name) => name,
+        None if no_color => ThemeName::Monochrome,
+        None => configured,
+    }
+}
+
+/// Sets the theme of the dashboard and diffs; dark until then. Monochrome also turns
+/// off the colors of every other message.
+pub fn configure(name: ThemeName) {
+    if name == ThemeName::Monochrome {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+    let _ = THEME.set(Theme::new(name));
+}
+
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(|| Theme::new(ThemeName::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_flag_beats_no_color_which_beats_config() {
+        assert_eq!(
+            pick(Some(ThemeName::Light), true, ThemeName::HighContrast),
+            ThemeName::Light
+        );
+        assert_eq!(
+            pick(None, true, ThemeName::HighContrast),
+            ThemeName::Monochrome
+        );
+        assert_eq!(
+            pick(None, false, ThemeName::HighContrast),
+            ThemeName::HighContrast
+        );
+        assert!(
+            Theme::new(ThemeName::Monochrome)
+                .containers
+                .iter()
+                .all(|c| *c == Color::Reset)
+        );
+    }
+}
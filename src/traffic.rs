@@ -0,0 +1,231 @@
+use anyhow::Result;
+use k8s_openapi::api::core::v1::Service;
+use k8s_openapi::api::discovery::v1::EndpointSlice;
+use k8s_openapi::api::networking::v1::Ingress;
+use kube::{Api, Client, api::ListParams};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Traffic routing for one Service selecting the workload's pods.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceTraffic {
+    pub name: String,
+    /// Pods listed as ready endpoints, i.e. currently receiving traffic.
+    pub serving_pods: BTreeSet<String>,
+    pub not_ready_endpoints: usize,
+    /// External URLs from Ingress rules or the LoadBalancer address.
+    pub urls: Vec<String>,
+}
+
+/// Resolves the Services selecting pods with the given labels, their ready endpoints
+/// and the external addresses routing to them.
+pub async fn fetch(
+    client: Client,
+    namespace: &str,
+    pod_labels: &BTreeMap<String, String>,
+) -> Result<Vec<ServiceTraffic>> {
+    let services_api: Api<Service> = Api::namespaced(client.clone(), namespace);
+    let slices_api: Api<EndpointSlice> = Api::namespaced(client.clone(), namespace);
+    let ingresses_api: Api<Ingress> = Api::namespaced(client, namespace);
+
+    let services: Vec<Service> = services_api
+        .list(&ListParams::default())
+        .await?
+        .items
+        .into_iter()
+        .filter(|s| selects(s, pod_labels))
+        .collect();
+    if services.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Ingresses are optional (and may not be readable); routing still shows without them.
+    let ingresses = ingresses_api
+        .list(&ListParams::default())
+        .await
+        .map(|l| l.items)
+        .unwrap_or_default();
+
+    let mut traffic = Vec::new();
+    for service in services {
+        let name = service.metadata.name.clone().unwrap_or_default();
+        let slices = slices_api
+            .list(&ListParams::default().labels(&format!("kubernetes.io/service-name={}", name)))
+            .await?
+            .items;
+
+        let mut entry = ServiceTraffic {
+            name: name.clone(),
+            ..Default::default()
+        };
+        for endpoint in slices.iter().flat_map(|s| &s.endpoints) {
+            // An unknown readiness is to be treated as ready.
+            let ready = endpoint.conditions.as_ref().and_then(|c| c.ready) != Some(false);
+            let pod = endpoint
+                .target_ref
+                .as_ref()
+                .filter(|r| r.kind.as_deref() == Some("Pod"))
+                .and_then(|r| r.name.clone());
+            match (ready, pod) {
+                (true, Some(pod)) => {
+                    entry.serving_pods.insert(pod);
+                }
+                (false, _) => entry.not_ready_endpoints += 1,
+                _ => {}
+            }
+        }
+        entry.urls = ingresses
+            .iter()
+            .flat_map(|i| ingress_urls(i, &name))
+            .chain(load_balancer_urls(&service))
+            .collect();
+        traffic.push(entry);
+    }
+
+    Ok(traffic)
+}
+
+fn selects(service: &Service, pod_labels: &BTreeMap<String, String>) -> bool {
+    service
+        .spec
+        .as_ref()
+        .and_then(|s| s.selector.as_ref())
+        .is_some_and(|selector| {
+            !selector.is_empty()
+                && selector
+                    .iter()
+                    .all(|(key, value)| pod_labels.get(key) == Some(value))
+        })
+}
+
+/// URLs of the Ingress rules (or default backend) pointing at the service.
+fn ingress_urls(ingress: &Ingress, service: &str) -> Vec<String> {
+    let Some(spec) = &ingress.spec else {
+        return Vec::new();
+    };
+    let targets = |backend: &k8s_openapi::api::networking::v1::IngressBackend| {
+        backend.service.as_ref().is_some_and(|s| s.name == service)
+    };
+    let tls_hosts: BTreeSet<&str> = spec
+        .tls
+        .iter()
+        .flatten()
+        .flat_map(|t| t.hosts.iter().flatten())
+        .map(|h| h.as_str())
+        .collect();
+    let address = ingress
+        .status
+        .as_ref()
+        .and_then(|s| s.load_balancer.as_ref())
+        .and_then(|lb| lb.ingress.as_ref())
+        .and_then(|i| i.first())
+        .and_then(|i| i.hostname.clone().or_else(|| i.ip.clone()));
+
+    let mut urls = Vec::new();
+    for rule in spec.rules.iter().flatten() {
+        let Some(host) = rule.host.clone().or_else(|| address.clone()) else {
+            continue;
+        };
+        let scheme = if tls_hosts.contains(host.as_str()) {
+            "https"
+        } else {
+            "http"
+        };
+        for path in rule.http.iter().flat_map(|h| &h.paths) {
+            if targets(&path.backend) {
+                let path = path.path.as_deref().unwrap_or("/");
+                urls.push(format!("{}://{}{}", scheme, host, path));
+            }
+        }
+    }
+    if let Some(address) = address
+        && spec.default_backend.as_ref().is_some_and(targets)
+    {
+        urls.push(format!("http://{}/", address));
+    }
+    urls
+}
+
+fn load_balancer_urls(service: &Service) -> Vec<String> {
+    let spec = service.spec.as_ref();
+    if spec.and_then(|s| s.type_.as_deref()) != Some("LoadBalancer") {
+        return Vec::new();
+    }
+    let port = spec
+        .and_then(|s| s.ports.as_ref())
+        .and_then(|p| p.first())
+        .map(|p| p.port)
+        .unwrap_or(80);
+
+    service
+        .status
+        .as_ref()
+        .and_then(|s| s.load_balancer.as_ref())
+        .and_then(|lb| lb.ingress.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(|i| i.hostname.clone().or_else(|| i.ip.clone()))
+        .map(|address| match port {
+            80 => format!("http://{}/", address),
+            443 => format!("https://{}/", address),
+            port => format!("http://{}:{}/", address, port),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ingress_urls_match_service_backend_and_tls() {
+        let ingress: Ingress = serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "public"},
+            "spec": {
+                "tls": [{"hosts": ["api.acme.io"]}],
+                "rules": [
+                    {"host": "api.acme.io", "http": {"paths": [
+                        {"path": "/auth", "pathType": "Prefix",
+                         "backend": {"service": {"name": "auth-api", "port": {"number": 80}}}},
+                        {"path": "/billing", "pathType": "Prefix",
+                         "backend": {"service": {"name": "billing", "port": {"number": 80}}}}
+                    ]}},
+                    {"host": "auth.internal", "http": {"paths": [
+                        {"pathType": "Prefix",
+                         "backend": {"service": {"name": "auth-api", "port": {"number": 80}}}}
+                    ]}}
+                ]
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            ingress_urls(&ingress, "auth-api"),
+            vec!["https://api.acme.io/auth", "http://auth.internal/"]
+        );
+    }
+
+    #[test]
+    fn test_selects_requires_every_selector_label() {
+        let service: Service = serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "auth-api"},
+            "spec": {"selector": {"app": "auth-api", "tier": "api"}}
+        }))
+        .unwrap();
+        let labels = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        assert!(selects(
+            &service,
+            &labels(&[
+                ("app", "auth-api"),
+                ("tier", "api"),
+                ("pod-template-hash", "x")
+            ])
+        ));
+        assert!(!selects(&service, &labels(&[("app", "auth-api")])));
+    }
+}
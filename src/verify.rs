@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+use serde_yaml::Value;
+use std::fmt;
+
+use crate::blueprint::{find_pod_spec, is_digest, split_image_ref};
+use crate::cluster;
+use crate::config::{ContainerImage, Environment, ServiceSource};
+
+/// A container whose live image is not the one that was applied, typically rewritten by
+/// a mutating admission webhook (registry mirrors, image policy controllers).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageDivergence {
+    pub container: String,
+    pub expected: String,
+    pub live: Option<String>,
+}
+
+impl fmt::Display for ImageDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.live {
+            Some(live) => write!(
+                f,
+                "{}: applied {}, the cluster has {}",
+                self.container, self.expected, live
+            ),
+            None => write!(
+                f,
+                "{}: applied {}, the container is missing from the live object",
+                self.container, self.expected
+            ),
+        }
+    }
+}
+
+/// Reads the applied workload back from the cluster and compares its container images
+/// with the tags that were written.
+pub fn check_applied_images(
+    env: &Environment,
+    service: &ServiceSource,
+    container_tags: &[(ContainerImage, String)],
+) -> Result<Vec<ImageDivergence>> {
+    let manifest = cluster::live_manifest(
+        &env.kubectl_context,
+        &service.kind,
+        service.namespace.as_deref().unwrap_or("default"),
+        &service.name,
+    )?;
+    let live: Value = serde_yaml::from_str(&manifest).context("Failed to parse the live object")?;
+    let expected: Vec<(String, String)> = container_tags
+        .iter()
+        .map(|(container, tag)| {
+            let (image, _) = split_image_ref(&container.image_path);
+            let separator = if is_digest(tag) { '@' } else { ':' };
+            (
+                container.name.clone(),
+                format!("{}{}{}", image, separator, tag),
+            )
+        })
+        .collect();
+    Ok(divergences(&live, &expected))
+}
+
+/// Expected `(container, image)` pairs not found as such in the workload. An image
+/// resolved to its digest on top of the expected tag (`repo:tag@sha256:...`) matches.
+fn divergences(workload: &Value, expected: &[(String, String)]) -> Vec<ImageDivergence> {
+    let containers: Vec<&Value> = find_pod_spec(workload)
+        .map(|spec| {
+            ["containers", "initContainers"]
+                .iter()
+                .filter_map(|key| spec.get(key)?.as_sequence())
+                .flatten()
+                .collect()
+        })
+        .unwrap_or_default();
+
+    expected
+        .iter()
+        .filter_map(|(name, image)| {
+            let live = containers
+                .iter()
+                .find(|c| c.get("name").and_then(Value::as_str) == Some(name.as_str()))
+                .and_then(|c| c.get("image")?.as_str());
+            let matches = live.is_some_and(|live| {
+                live == image
+                    || live
+                        .strip_prefix(image.as_str())
+                        .is_some_and(|rest| rest.starts_with('@'))
+            });
+            (!matches).then(|| ImageDivergence {
+                container: name.clone(),
+                expected: image.clone(),
+                live: live.map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divergences_report_rewritten_images() {
+        let workload: Value = serde_yaml::from_str(
+            r#"kind: Deployment
+spec:
+  template:
+    spec:
+      initContainers:
+        - name: migrate
+          image: gcr.io/p/migrate:v2@sha256:abc
+      containers:
+        - name: app
+          image: mirror.acme.io/p/app:v2
+"#,
+        )
+        .unwrap();
+        let expected = vec![
+            ("app".to_string(), "gcr.io/p/app:v2".to_string()),
+            ("migrate".to_string(), "gcr.io/p/migrate:v2".to_string()),
+            ("sidecar".to_string(), "gcr.io/p/sidecar:v2".to_string()),
+        ];
+
+        let found = divergences(&workload, &expected);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[0].to_string(),
+            "app: applied gcr.io/p/app:v2, the cluster has mirror.acme.io/p/app:v2"
+        );
+        assert_eq!(found[1].container, "sidecar");
+        assert_eq!(found[1].live, None);
+    }
+}